@@ -0,0 +1,81 @@
+// Compares reading a big HDF export through serde with parse_smart_meter_csv, run with
+// cargo bench --bench parse
+use chrono::{Duration, NaiveDate};
+use energy_comparator::{parse_smart_meter_csv, read_csv, SmartMeterData};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Duration as Elapsed, Instant},
+};
+
+// Four years of half hourly imports and exports of one meter, newest first like the export
+fn write_hdf(path: &Path, days: i64) -> usize {
+    let mut file = BufWriter::new(File::create(path).unwrap());
+    writeln!(
+        file,
+        "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time"
+    )
+    .unwrap();
+    let start = NaiveDate::from_ymd_opt(2021, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let mut rows = 0;
+    for interval in (1..=days * 48).rev() {
+        let end = start + Duration::minutes(30 * interval);
+        for (read_type, value) in [("Import", interval % 7), ("Export", interval % 3)] {
+            writeln!(
+                file,
+                "10308375697,34996871,{}.{:03},Active {read_type} Interval (kW),{}",
+                value,
+                interval % 1000,
+                end.format("%d-%m-%Y %H:%M")
+            )
+            .unwrap();
+            rows += 1;
+        }
+    }
+    rows
+}
+
+// The fastest of a few runs, the first one also warms up the page cache
+fn best_of(runs: usize, mut f: impl FnMut() -> usize) -> (Elapsed, usize) {
+    (0..runs)
+        .map(|_| {
+            let started = Instant::now();
+            let rows = f();
+            (started.elapsed(), rows)
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let path = std::env::temp_dir().join("energy_comparator_bench_hdf.csv");
+    let rows = write_hdf(&path, 4 * 365);
+
+    let (serde, serde_rows) = best_of(3, || read_csv::<SmartMeterData>(&path).unwrap().len());
+    let (fast, fast_rows) = best_of(3, || {
+        parse_smart_meter_csv(File::open(&path).unwrap())
+            .unwrap()
+            .len()
+    });
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(serde_rows, rows);
+    assert_eq!(fast_rows, rows);
+
+    let per_second = |elapsed: Elapsed| rows as f64 / elapsed.as_secs_f64();
+    println!("{rows} readings");
+    println!(
+        "serde:                 {:>8.1} ms, {:>10.0} readings/s",
+        serde.as_secs_f64() * 1000.0,
+        per_second(serde)
+    );
+    println!(
+        "parse_smart_meter_csv: {:>8.1} ms, {:>10.0} readings/s ({:.1}x)",
+        fast.as_secs_f64() * 1000.0,
+        per_second(fast),
+        serde.as_secs_f64() / fast.as_secs_f64()
+    );
+}
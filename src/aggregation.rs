@@ -0,0 +1,385 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::{
+    BillBreakdown, BillingPeriod, EnergyBillEntry, PricePlanStrategy, SmartMeterData,
+    SmartMeterDataType,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    Month,
+    // ISO weeks, starting on Monday
+    Week,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Bucket {
+    Month { year: i32, month: u32 },
+    Week { year: i32, week: u32 },
+}
+
+impl Bucket {
+    pub fn of(date: NaiveDate, granularity: Granularity) -> Bucket {
+        match granularity {
+            Granularity::Month => Bucket::Month {
+                year: date.year(),
+                month: date.month(),
+            },
+            Granularity::Week => {
+                let week = date.iso_week();
+                Bucket::Week {
+                    year: week.year(),
+                    week: week.week(),
+                }
+            }
+        }
+    }
+}
+
+impl Serialize for Bucket {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl Display for Bucket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bucket::Month { year, month } => write!(f, "{year}-{month:02}"),
+            Bucket::Week { year, week } => write!(f, "{year}-W{week:02}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BucketBreakdown {
+    pub bucket: Bucket,
+    // the part of the billing period inside the bucket, the first and last buckets are usually partial
+    pub period: BillingPeriod,
+    pub breakdown: BillBreakdown,
+}
+
+// The days of the period grouped by bucket, in calendar order
+pub fn split_period(
+    period: &BillingPeriod,
+    granularity: Granularity,
+) -> Vec<(Bucket, BillingPeriod)> {
+    let mut buckets: Vec<(Bucket, BillingPeriod)> = vec![];
+    for date in period.days() {
+        let bucket = Bucket::of(date, granularity);
+        match buckets.last_mut() {
+            Some((last, bucket_period)) if *last == bucket => bucket_period.end = date,
+            _ => buckets.push((
+                bucket,
+                BillingPeriod {
+                    start: date,
+                    end: date,
+                },
+            )),
+        }
+    }
+    buckets
+}
+
+// Prices the plan separately for each month or week of the period
+pub fn breakdown_by_bucket(
+    plan: &dyn PricePlanStrategy,
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+    granularity: Granularity,
+) -> Vec<BucketBreakdown> {
+    let mut readings: BTreeMap<Bucket, Vec<SmartMeterData>> = BTreeMap::new();
+    for datapoint in datapoints {
+        readings
+            .entry(Bucket::of(datapoint.date(), granularity))
+            .or_default()
+            .push(datapoint.clone());
+    }
+
+    split_period(period, granularity)
+        .into_iter()
+        .map(|(bucket, bucket_period)| {
+            let bucket_readings = readings.remove(&bucket).unwrap_or_default();
+            BucketBreakdown {
+                bucket,
+                period: bucket_period,
+                breakdown: plan.compute_bill_breakdown(&bucket_readings, &bucket_period),
+            }
+        })
+        .collect()
+}
+
+// The energy and cost that fell into one band of a plan, like a line of an itemised bill
+#[derive(Debug, Clone, Serialize)]
+pub struct BandBreakdown {
+    pub band: String,
+    pub kwh: f32,
+    pub cost: EnergyBillEntry,
+}
+
+// Groups the readings by the band the plan priced them under, in the order the bands first appear
+pub fn breakdown_by_band(
+    plan: &dyn PricePlanStrategy,
+    datapoints: &[SmartMeterData],
+) -> Vec<BandBreakdown> {
+    let mut bands: Vec<BandBreakdown> = vec![];
+    for (datapoint, cost) in datapoints.iter().zip(plan.price_readings(datapoints)) {
+        let band = plan.band_for_singe_period(datapoint);
+        match bands.iter_mut().find(|b| b.band == band) {
+            Some(existing) => {
+                existing.kwh += datapoint.energy_kwh();
+                existing.cost += cost;
+            }
+            None => bands.push(BandBreakdown {
+                band,
+                kwh: datapoint.energy_kwh(),
+                cost,
+            }),
+        }
+    }
+    let rounding = plan.rounding();
+    for band in &mut bands {
+        band.cost = band.cost.in_currency(plan.currency()).round(&rounding);
+    }
+    bands
+}
+
+// What a kWh imported cost on the plan on average, in cent: the energy alone, and all in with
+// the standing charge and the levy spread over the kWh too, so a plan with bands can be set
+// against a flat rate. Export credits and one off credits are left out of both
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EffectiveRate {
+    #[serde(with = "rust_decimal::serde::float")]
+    pub energy_cents: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub all_in_cents: Decimal,
+}
+
+// None without any imports to spread the costs over
+pub fn effective_rate(
+    plan: &dyn PricePlanStrategy,
+    datapoints: &[SmartMeterData],
+    breakdown: &BillBreakdown,
+) -> Option<EffectiveRate> {
+    let imports: Vec<SmartMeterData> = datapoints
+        .iter()
+        .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+        .cloned()
+        .collect();
+    let kwh = Decimal::from_f32(imports.iter().map(SmartMeterData::energy_kwh).sum())?;
+    if kwh <= Decimal::ZERO {
+        return None;
+    }
+    let energy = plan
+        .price_readings(&imports)
+        .iter()
+        .sum::<EnergyBillEntry>()
+        .round(&plan.rounding())
+        .signed_amount();
+    let all_in =
+        energy + breakdown.standing_charge.signed_amount() + breakdown.pso_levy.signed_amount();
+    let cents = |amount: Decimal| (amount / kwh * Decimal::ONE_HUNDRED).round_dp(2);
+    Some(EffectiveRate {
+        energy_cents: cents(energy),
+        all_in_cents: cents(all_in),
+    })
+}
+
+// What the plan charged for one day of the readings, without the standing charge, the kWh
+// are the imports and the bands have the exports too
+#[derive(Debug, Clone, Serialize)]
+pub struct DayCost {
+    pub date: NaiveDate,
+    pub kwh: f32,
+    pub cost: EnergyBillEntry,
+    pub bands: Vec<BandBreakdown>,
+}
+
+// The count days the plan charged the most for, the costliest first. The readings are priced
+// together so tiers fall as they would on the bill, and each price goes to the day of its reading
+pub fn most_expensive_days(
+    plan: &dyn PricePlanStrategy,
+    datapoints: &[SmartMeterData],
+    count: usize,
+) -> Vec<DayCost> {
+    let mut days: BTreeMap<NaiveDate, DayCost> = BTreeMap::new();
+    for (datapoint, cost) in datapoints.iter().zip(plan.price_readings(datapoints)) {
+        let date = datapoint.date();
+        let day = days.entry(date).or_insert_with(|| DayCost {
+            date,
+            kwh: 0.0,
+            cost: EnergyBillEntry::ZERO,
+            bands: vec![],
+        });
+        let band = plan.band_for_singe_period(datapoint);
+        if datapoint.read_type == SmartMeterDataType::ActiveImport {
+            day.kwh += datapoint.energy_kwh();
+        }
+        day.cost += cost;
+        match day.bands.iter_mut().find(|b| b.band == band) {
+            Some(existing) => {
+                existing.kwh += datapoint.energy_kwh();
+                existing.cost += cost;
+            }
+            None => day.bands.push(BandBreakdown {
+                band,
+                kwh: datapoint.energy_kwh(),
+                cost,
+            }),
+        }
+    }
+    let rounding = plan.rounding();
+    let mut days: Vec<DayCost> = days.into_values().collect();
+    for day in &mut days {
+        day.cost = day.cost.in_currency(plan.currency()).round(&rounding);
+        for band in &mut day.bands {
+            band.cost = band.cost.in_currency(plan.currency()).round(&rounding);
+        }
+    }
+    days.sort_by(|a, b| b.cost.cmp(&a.cost).then(a.date.cmp(&b.date)));
+    days.truncate(count);
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, sse_airtricity_20};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn each_month_is_priced_on_its_own_days_and_readings() {
+        let plan = sse_airtricity_20();
+        let data = vec![
+            reading_at("30-01-2024 12:00", 2.0),
+            reading_at("02-02-2024 12:00", 4.0),
+        ];
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let months = breakdown_by_bucket(&plan, &data, &period, Granularity::Month);
+        let labels: Vec<String> = months.iter().map(|m| m.bucket.to_string()).collect();
+        assert_eq!(labels, vec!["2024-01", "2024-02"]);
+        assert_eq!(months[0].period.days().count(), 2);
+        assert_eq!(months[1].period.days().count(), 2);
+        let whole = plan.compute_bill_breakdown(&data, &period);
+        assert_eq!(
+            months[0].breakdown.usage + months[1].breakdown.usage,
+            whole.usage
+        );
+        assert_eq!(
+            months[0].breakdown.standing_charge,
+            months[1].breakdown.standing_charge
+        );
+    }
+
+    #[test]
+    fn weeks_are_iso_weeks_across_the_new_year() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let period = BillingPeriod {
+            start: date(2024, 12, 29),
+            end: date(2025, 1, 6),
+        };
+        let weeks: Vec<(String, usize)> = split_period(&period, Granularity::Week)
+            .into_iter()
+            .map(|(bucket, days)| (bucket.to_string(), days.days().count()))
+            .collect();
+        assert_eq!(
+            weeks,
+            vec![
+                ("2024-W52".to_string(), 1),
+                ("2025-W01".to_string(), 7),
+                ("2025-W02".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn readings_are_itemised_by_the_band_they_are_priced_under() {
+        let plan = sse_airtricity_20();
+        let mut export = reading_at("08-01-2024 13:00", 1.0);
+        export.read_type = crate::SmartMeterDataType::ActiveExport;
+        let data = vec![
+            reading_at("08-01-2024 18:00", 2.0),
+            reading_at("08-01-2024 03:00", 1.0),
+            reading_at("08-01-2024 18:30", 1.0),
+            export,
+        ];
+        let bands = breakdown_by_band(&plan, &data);
+        let lines: Vec<(&str, f32)> = bands.iter().map(|b| (b.band.as_str(), b.kwh)).collect();
+        assert_eq!(lines, vec![("peak", 1.5), ("night", 0.5), ("export", 0.5)]);
+        // each band is rounded like a line of the bill
+        assert_eq!(
+            bands[0].cost,
+            (plan.price_for_singe_period(&data[0]) + plan.price_for_singe_period(&data[2]))
+                .round(&plan.rounding())
+        );
+        assert!(matches!(bands[2].cost, EnergyBillEntry::Credit(_)));
+    }
+
+    #[test]
+    fn the_standing_charge_is_spread_over_the_kwh() {
+        let plan = sse_airtricity_20();
+        // 10 kWh on one day
+        let data = vec![reading_at("08-01-2024 12:00", 20.0)];
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let breakdown = plan.compute_bill_breakdown(&data, &period);
+        let rate = effective_rate(&plan, &data, &breakdown).unwrap();
+        assert_eq!(
+            rate.energy_cents,
+            (breakdown.usage.signed_amount() * dec!(10)).round_dp(2)
+        );
+        assert_eq!(
+            rate.all_in_cents,
+            ((breakdown.total - breakdown.credits).signed_amount() * dec!(10)).round_dp(2)
+        );
+        assert!(effective_rate(&plan, &[], &breakdown).is_none());
+    }
+
+    #[test]
+    fn the_costliest_days_come_first() {
+        let data = vec![
+            reading_at("08-01-2024 12:00", 1.0),
+            reading_at("09-01-2024 12:00", 1.0),
+            reading_at("09-01-2024 18:00", 2.0),
+            reading_at("10-01-2024 03:00", 2.0),
+        ];
+        let days = most_expensive_days(&sse_airtricity_20(), &data, 2);
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].date, NaiveDate::from_ymd_opt(2024, 1, 9).unwrap());
+        assert_eq!(days[0].kwh, 1.5);
+        let bands: Vec<&str> = days[0].bands.iter().map(|b| b.band.as_str()).collect();
+        assert_eq!(bands, vec!["day", "peak"]);
+        // a night kWh is cheaper than a day one, but there's twice as much of it
+        assert_eq!(days[1].date, NaiveDate::from_ymd_opt(2024, 1, 10).unwrap());
+    }
+
+    #[test]
+    fn band_and_day_costs_are_in_the_currency_of_the_plan() {
+        let plan: crate::ConfigurablePlan = toml::from_str(
+            r#"
+name = "uk flat"
+currency = "GBP"
+standing_charge = { per_day = 0.5 }
+
+[[bands]]
+name = "all"
+start = "00:00"
+end = "00:00"
+rate = 0.25
+"#,
+        )
+        .unwrap();
+        let data = vec![reading_at("08-01-2024 12:00", 2.0)];
+        let bands = breakdown_by_band(&plan, &data);
+        assert_eq!(bands[0].cost.currency(), crate::Currency::Gbp);
+        assert_eq!(bands[0].cost.to_string(), "£0.25");
+        let days = most_expensive_days(&plan, &data, 1);
+        assert_eq!(days[0].cost.currency(), crate::Currency::Gbp);
+        assert_eq!(days[0].bands[0].cost.currency(), crate::Currency::Gbp);
+    }
+}
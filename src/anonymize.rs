@@ -0,0 +1,75 @@
+use anyhow::Result;
+use chrono::Duration;
+use std::{collections::HashMap, io::Write, sync::Arc};
+
+use crate::SmartMeterData;
+
+// Real MPRNs start with 10, the fake ones can't be mistaken for somebody's
+const FAKE_MPRN_BASE: u64 = 99_000_000_000;
+
+// The readings with the meter point and meter numbers replaced by made up ones, numbered in the
+// order they first appear so the same file always gets the same ones, and moved by shift_days.
+// A shift of whole weeks keeps the weekdays, which the weekend plans depend on
+pub fn anonymize_readings(datapoints: Vec<SmartMeterData>, shift_days: i64) -> Vec<SmartMeterData> {
+    let mut mprns: HashMap<Arc<str>, Arc<str>> = HashMap::new();
+    let mut serials: HashMap<Arc<str>, Arc<str>> = HashMap::new();
+    datapoints
+        .into_iter()
+        .map(|datapoint| {
+            let count = mprns.len() as u64;
+            let mprn = mprns
+                .entry(datapoint.mprn.clone())
+                .or_insert_with(|| format!("{}", FAKE_MPRN_BASE + count + 1).into())
+                .clone();
+            let count = serials.len();
+            let serial = serials
+                .entry(datapoint.meter_serial_number.clone())
+                .or_insert_with(|| format!("{:08}", count + 1).into())
+                .clone();
+            SmartMeterData {
+                mprn,
+                meter_serial_number: serial,
+                read_data_and_end_time: datapoint.read_data_and_end_time
+                    + Duration::days(shift_days),
+                ..datapoint
+            }
+        })
+        .collect()
+}
+
+// The readings as an HDF csv like ESB Networks exports it
+pub fn write_hdf_csv(datapoints: &[SmartMeterData], writer: impl Write) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    for datapoint in datapoints {
+        wtr.serialize(datapoint)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_smart_meter_csv;
+
+    #[test]
+    fn the_same_meter_gets_the_same_fake_numbers() {
+        let csv = "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+10308375697,34996871,0.5,Active Import Interval (kW),08-01-2024 03:30
+10308375697,34996871,0.25,Active Export Interval (kW),08-01-2024 03:30
+10412345678,11112222,0.125,Active Import Interval (kWh),08-01-2024 04:00
+";
+        let data = parse_smart_meter_csv(csv.as_bytes()).unwrap();
+        let anonymized = anonymize_readings(data, 7);
+        let mut written = vec![];
+        write_hdf_csv(&anonymized, &mut written).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+99000000001,00000001,0.5,Active Import Interval (kW),15-01-2024 03:30
+99000000001,00000001,0.25,Active Export Interval (kW),15-01-2024 03:30
+99000000002,00000002,0.125,Active Import Interval (kWh),15-01-2024 04:00
+"
+        );
+    }
+}
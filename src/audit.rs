@@ -0,0 +1,100 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use serde::Serialize;
+use std::io::Write;
+
+use crate::{group_by_mprn, GapFilling, PricePlanStrategy, SmartMeterData, SmartMeterDataType};
+
+// How one reading was priced on one plan. The charge is signed like the results csv and
+// includes VAT and discounts, the rate is the charge over the kWh so it shows what was really
+// applied. Export caps and the standing charge are worked out for the whole period and are
+// not in here. A reading made up for a gap says what it was filled with
+#[derive(Debug, Serialize)]
+struct AuditRow<'a> {
+    mprn: &'a str,
+    plan: &'a str,
+    // in UTC like the HDF file, the bands go by the local time next to it
+    end_time: NaiveDateTime,
+    local_end_time: NaiveDateTime,
+    direction: &'static str,
+    kwh: f32,
+    band: String,
+    rate: Option<Decimal>,
+    charge: Decimal,
+    filled: Option<String>,
+}
+
+// Every reading of every meter on every plan, plan by plan and oldest reading first
+pub fn write_audit_csv(
+    plans: &[Box<dyn PricePlanStrategy>],
+    datapoints: &[SmartMeterData],
+    filling: &GapFilling,
+    writer: impl Write,
+) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    for (mprn, mut readings) in group_by_mprn(datapoints.to_vec()) {
+        readings.sort_by_key(|datapoint| datapoint.read_data_and_end_time);
+        for plan in plans {
+            let name = plan.name();
+            let prices = plan.price_readings(&readings);
+            for (datapoint, price) in readings.iter().zip(prices) {
+                let kwh = datapoint.energy_kwh();
+                let charge = price.signed_amount();
+                let rate = Decimal::from_f32(kwh)
+                    .filter(|kwh| !kwh.is_zero())
+                    .map(|kwh| (charge / kwh).abs().round_dp(6));
+                wtr.serialize(AuditRow {
+                    mprn: &mprn,
+                    plan: &name,
+                    end_time: datapoint.read_data_and_end_time,
+                    local_end_time: datapoint.local_end_time(),
+                    direction: match datapoint.read_type {
+                        SmartMeterDataType::ActiveImport => "import",
+                        SmartMeterDataType::ActiveExport => "export",
+                    },
+                    kwh,
+                    band: plan.band_for_singe_period(datapoint),
+                    rate,
+                    charge,
+                    filled: filling
+                        .strategy_for(datapoint)
+                        .map(|strategy| strategy.to_string()),
+                })?;
+            }
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, sse_airtricity_20};
+
+    #[test]
+    fn every_reading_shows_its_band_and_rate() {
+        let data = vec![
+            reading_at("08-01-2024 18:00", 2.0),
+            reading_at("08-01-2024 03:30", 0.0),
+        ];
+        let plans: Vec<Box<dyn PricePlanStrategy>> = vec![Box::new(sse_airtricity_20())];
+        let mut csv = vec![];
+        write_audit_csv(&plans, &data, &GapFilling::default(), &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "mprn,plan,end_time,local_end_time,direction,kwh,band,rate,charge,filled"
+        );
+        // oldest first, a reading of nothing has no rate
+        assert!(lines[1].starts_with("10000000000,SSEAirtricity20,2024-01-08T03:30:00,"));
+        assert!(lines[1].ends_with(",,0,"));
+        let peak: Vec<&str> = lines[2].split(',').collect();
+        assert_eq!(peak[5], "1.0");
+        let rate: Decimal = peak[7].parse().unwrap();
+        let charge: Decimal = peak[8].parse().unwrap();
+        assert_eq!(rate, charge);
+    }
+}
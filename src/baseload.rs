@@ -0,0 +1,94 @@
+use chrono::{NaiveDate, NaiveTime};
+use serde::Serialize;
+
+use crate::{
+    standard_profile_readings, time_in_window, ConsumptionProfile, SmartMeterData,
+    SmartMeterDataType,
+};
+
+// The hours nearly everything but the fridge and the standby lights is off
+const OVERNIGHT_START: NaiveTime = match NaiveTime::from_hms_opt(1, 0, 0) {
+    Some(time) => time,
+    None => panic!("invalid time"),
+};
+const OVERNIGHT_END: NaiveTime = match NaiveTime::from_hms_opt(5, 0, 0) {
+    Some(time) => time,
+    None => panic!("invalid time"),
+};
+
+// The percentile of the overnight power taken as the baseload, low enough to leave out the
+// night storage heater or the dishwasher on a timer
+const BASELOAD_PERCENTILE: f32 = 0.05;
+
+// Above this the house is using more on standby than most, around 100 to 150 W is usual
+pub const HIGH_BASELOAD_KW: f32 = 0.25;
+
+// What the house draws when nothing is being used, all day and every day
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Baseload {
+    pub kw: f32,
+    pub annual_kwh: f32,
+    pub high: bool,
+}
+
+// The baseload of the imports, None without any overnight readings
+pub fn baseload(datapoints: &[SmartMeterData]) -> Option<Baseload> {
+    let mut overnight: Vec<f32> = datapoints
+        .iter()
+        .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+        .filter(|d| time_in_window(d.local_end_time().time(), OVERNIGHT_START, OVERNIGHT_END))
+        .map(|d| d.energy_kwh() * 60.0 / d.interval_minutes as f32)
+        .collect();
+    if overnight.is_empty() {
+        return None;
+    }
+    overnight.sort_by(f32::total_cmp);
+    let index = ((overnight.len() - 1) as f32 * BASELOAD_PERCENTILE).round() as usize;
+    let kw = overnight[index];
+    Some(Baseload {
+        kw,
+        annual_kwh: kw * 24.0 * 365.0,
+        high: kw > HIGH_BASELOAD_KW,
+    })
+}
+
+impl Baseload {
+    // A year of the baseload from the start day, the same power in every half hour, for the
+    // plans to price what standby costs them
+    pub fn year_of_readings(&self, start: NaiveDate) -> Vec<SmartMeterData> {
+        // the share of the day in each window of standard_profile_readings
+        let profile = ConsumptionProfile {
+            annual_kwh: self.annual_kwh,
+            day_share: 13.0 / 24.0,
+            night_share: 9.0 / 24.0,
+            peak_share: 2.0 / 24.0,
+        };
+        standard_profile_readings(&profile, start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn the_baseload_is_the_quiet_overnight_power() {
+        let mut data: Vec<SmartMeterData> = (0..20)
+            .map(|night| reading_at(&format!("{:02}-01-2024 03:00", night + 1), 0.3))
+            .collect();
+        // the immersion on a timer one night, and the evening doesn't count
+        data.push(reading_at("21-01-2024 03:30", 3.0));
+        data.push(reading_at("21-01-2024 03:00", 0.1));
+        data.push(reading_at("21-01-2024 19:00", 0.05));
+        let baseload = baseload(&data).unwrap();
+        assert_eq!(baseload.kw, 0.3);
+        assert!(baseload.high);
+        let year: f32 = baseload
+            .year_of_readings(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap())
+            .iter()
+            .map(SmartMeterData::energy_kwh)
+            .sum();
+        assert!((year - 0.3 * 24.0 * 365.0).abs() < 0.5);
+    }
+}
@@ -0,0 +1,137 @@
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    saving_against, BillBreakdown, BillingPeriod, ReadUnit, Saving, SmartMeterData,
+    SmartMeterDataType, TimeWindow,
+};
+
+// A home battery charged from the grid in one window and emptied into the imports of the
+// rest of the day, or of the discharge window when there is one
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Battery {
+    pub capacity_kwh: f32,
+    // the most it charges or discharges in an hour
+    pub power_kw: f32,
+    // the share of the energy charged that comes back out, lost on the way in
+    pub round_trip_efficiency: f32,
+    pub charge: TimeWindow,
+    pub discharge: Option<TimeWindow>,
+    // what the battery costs installed, to work out how long it takes to pay for itself
+    pub cost: Option<Decimal>,
+}
+
+// What a plan would cost with the battery and how long its saving takes to pay for it
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BatterySaving {
+    pub breakdown: BillBreakdown,
+    pub saving: Saving,
+    // years of the saving of the period, scaled to a year, to make up the cost. None when
+    // the battery has no cost or doesn't save anything on the plan
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payback_years: Option<Decimal>,
+}
+
+impl Battery {
+    fn set_energy(datapoint: &mut SmartMeterData, kwh: f32) {
+        datapoint.read_value = match datapoint.unit {
+            ReadUnit::Kilowatts => kwh * 60.0 / datapoint.interval_minutes as f32,
+            ReadUnit::KilowattHours => kwh,
+        };
+    }
+
+    // The imports with the battery in the house, it starts empty and runs through the
+    // readings of each meter point in time order. In the charge window it fills up from the
+    // grid as fast as its power allows, at other times it covers the imports until it runs out.
+    // Exports are left as they are
+    pub fn simulate(&self, datapoints: &[SmartMeterData]) -> Vec<SmartMeterData> {
+        let mut simulated = datapoints.to_vec();
+        let mut meters: BTreeMap<&str, Vec<(NaiveDateTime, usize)>> = BTreeMap::new();
+        for (index, datapoint) in datapoints.iter().enumerate() {
+            if datapoint.read_type == SmartMeterDataType::ActiveImport {
+                meters
+                    .entry(datapoint.mprn.as_ref())
+                    .or_default()
+                    .push((datapoint.read_data_and_end_time, index));
+            }
+        }
+        for readings in meters.values_mut() {
+            readings.sort();
+            // the energy stored, already net of the losses
+            let mut stored = 0.0_f32;
+            for &(_, index) in readings.iter() {
+                let datapoint = &mut simulated[index];
+                let most = self.power_kw * datapoint.interval_minutes as f32 / 60.0;
+                let import = datapoint.energy_kwh();
+                if self.charge.contains(datapoint) {
+                    let drawn = most.min((self.capacity_kwh - stored) / self.round_trip_efficiency);
+                    stored += drawn * self.round_trip_efficiency;
+                    Battery::set_energy(datapoint, import + drawn);
+                } else if self
+                    .discharge
+                    .is_none_or(|window| window.contains(datapoint))
+                {
+                    let delivered = most.min(stored).min(import);
+                    stored -= delivered;
+                    Battery::set_energy(datapoint, import - delivered);
+                }
+            }
+        }
+        simulated
+    }
+
+    pub fn saving(
+        &self,
+        without: &BillBreakdown,
+        with: BillBreakdown,
+        period: &BillingPeriod,
+    ) -> BatterySaving {
+        let saving = saving_against(without, &with);
+        let yearly = saving.amount.amount() * Decimal::from(365)
+            / Decimal::from(period.number_of_days().max(1));
+        let payback_years = self
+            .cost
+            .filter(|_| yearly > Decimal::ZERO)
+            .map(|cost| (cost / yearly).round_dp(1));
+        BatterySaving {
+            breakdown: with,
+            saving,
+            payback_years,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn the_battery_moves_the_evening_to_the_night() {
+        let battery = Battery {
+            capacity_kwh: 1.5,
+            power_kw: 2.0,
+            round_trip_efficiency: 0.75,
+            charge: "02:00-05:00".parse().unwrap(),
+            discharge: Some("17:00-19:00".parse().unwrap()),
+            cost: None,
+        };
+        let data = vec![
+            reading_at("08-01-2024 18:00", 4.0),
+            reading_at("08-01-2024 03:00", 0.0),
+            reading_at("08-01-2024 03:30", 0.0),
+            reading_at("08-01-2024 12:00", 1.0),
+            reading_at("08-01-2024 18:30", 2.0),
+        ];
+        let kwh: Vec<f32> = battery
+            .simulate(&data)
+            .iter()
+            .map(SmartMeterData::energy_kwh)
+            .collect();
+        // 2 kWh bought at night store 1.5, the first half hour of the evening is held back by
+        // the power of the battery and the second gets what is left
+        assert_eq!(kwh, vec![1.0, 1.0, 1.0, 0.5, 0.5]);
+    }
+}
@@ -0,0 +1,298 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{
+    cmp::Ordering,
+    fmt,
+    iter::Sum,
+    ops::{Add, AddAssign, Neg, Sub},
+};
+
+use crate::{Currency, Money, Rounding};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "amount", rename_all = "snake_case")]
+pub enum EnergyBillEntry {
+    Credit(Money),
+    Debit(Money),
+}
+
+impl EnergyBillEntry {
+    pub const ZERO: EnergyBillEntry = EnergyBillEntry::Debit(Money::ZERO);
+
+    // Debits are positive and credits negative, so entries can be compared and sorted
+    pub fn signed_amount(&self) -> Decimal {
+        match self {
+            EnergyBillEntry::Credit(value) => -value.amount(),
+            EnergyBillEntry::Debit(value) => value.amount(),
+        }
+    }
+
+    // Positive amounts are debits and negative ones credits, the opposite of signed_amount
+    pub fn from_signed(amount: Decimal) -> EnergyBillEntry {
+        if amount < Decimal::ZERO {
+            EnergyBillEntry::Credit(Money::new(-amount))
+        } else {
+            EnergyBillEntry::Debit(Money::new(amount))
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.signed_amount().is_zero()
+    }
+
+    pub fn currency(&self) -> Currency {
+        match self {
+            EnergyBillEntry::Credit(value) | EnergyBillEntry::Debit(value) => value.currency(),
+        }
+    }
+
+    pub fn in_currency(self, currency: Currency) -> EnergyBillEntry {
+        match self {
+            EnergyBillEntry::Credit(value) => EnergyBillEntry::Credit(value.in_currency(currency)),
+            EnergyBillEntry::Debit(value) => EnergyBillEntry::Debit(value.in_currency(currency)),
+        }
+    }
+
+    pub fn round(self, rounding: &Rounding) -> EnergyBillEntry {
+        match self {
+            EnergyBillEntry::Credit(value) => EnergyBillEntry::Credit(value.round(rounding)),
+            EnergyBillEntry::Debit(value) => EnergyBillEntry::Debit(value.round(rounding)),
+        }
+    }
+}
+
+// Debits print as the amount and credits as CR before it, like a paper bill: €843.23 and
+// CR €12.10. The decimal places follow the formatter like Money
+impl fmt::Display for EnergyBillEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decimals = f.precision().unwrap_or(2);
+        match self {
+            EnergyBillEntry::Credit(value) => write!(f, "CR {value:.decimals$}"),
+            EnergyBillEntry::Debit(value) => write!(f, "{value:.decimals$}"),
+        }
+    }
+}
+
+impl Add for EnergyBillEntry {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        match (self, rhs) {
+            (EnergyBillEntry::Debit(self_value), EnergyBillEntry::Debit(rhs_value)) => {
+                EnergyBillEntry::Debit(self_value + rhs_value)
+            }
+            (EnergyBillEntry::Credit(self_value), EnergyBillEntry::Credit(rhs_value)) => {
+                EnergyBillEntry::Credit(self_value + rhs_value)
+            }
+            (EnergyBillEntry::Credit(self_value), EnergyBillEntry::Debit(rhs_value)) => {
+                if self_value > rhs_value {
+                    EnergyBillEntry::Credit(self_value - rhs_value)
+                } else {
+                    EnergyBillEntry::Debit(rhs_value - self_value)
+                }
+            }
+            (EnergyBillEntry::Debit(self_value), EnergyBillEntry::Credit(rhs_value)) => {
+                if rhs_value > self_value {
+                    EnergyBillEntry::Credit(rhs_value - self_value)
+                } else {
+                    EnergyBillEntry::Debit(self_value - rhs_value)
+                }
+            }
+        }
+    }
+}
+
+impl AddAssign for EnergyBillEntry {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+// A debit becomes a credit of the same amount, nothing stays a zero debit
+impl Neg for EnergyBillEntry {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        EnergyBillEntry::from_signed(-self.signed_amount()).in_currency(self.currency())
+    }
+}
+
+// How much more the left side costs, a credit when it's the cheaper one
+impl Sub for EnergyBillEntry {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self + -rhs
+    }
+}
+
+impl Sum for EnergyBillEntry {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(EnergyBillEntry::ZERO, |acc, entry| acc + entry)
+    }
+}
+
+impl<'a> Sum<&'a EnergyBillEntry> for EnergyBillEntry {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+// Cheapest first, the way the plans are ranked. A zero credit comes before a zero debit and
+// the currency settles the rest, so the order agrees with ==
+impl Ord for EnergyBillEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let rank = |entry: &EnergyBillEntry| matches!(entry, EnergyBillEntry::Debit(_));
+        self.signed_amount()
+            .cmp(&other.signed_amount())
+            .then_with(|| rank(self).cmp(&rank(other)))
+            .then_with(|| self.currency().cmp(&other.currency()))
+    }
+}
+
+impl PartialOrd for EnergyBillEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// The components of a bill for a given period
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BillBreakdown {
+    // the imports and the exports priced together, import_cost and export_credit added up
+    pub usage: EnergyBillEntry,
+    // what the imported energy cost, before the exports took anything off it
+    pub import_cost: EnergyBillEntry,
+    // what the exports earned, netting included, see ExportRules
+    #[serde(skip_serializing_if = "EnergyBillEntry::is_zero")]
+    pub export_credit: EnergyBillEntry,
+    pub standing_charge: EnergyBillEntry,
+    // charged per day on every electricity bill whatever the plan, see PsoLevy
+    pub pso_levy: EnergyBillEntry,
+    // fixed credits dated within the period, the same for every plan, see CreditSchedule
+    pub credits: EnergyBillEntry,
+    // the fixed credit of the discount of the plan, see Discount
+    pub bill_credit: EnergyBillEntry,
+    // charged on the peak demand of the readings, see DemandCharge
+    #[serde(skip_serializing_if = "EnergyBillEntry::is_zero")]
+    pub demand_charge: EnergyBillEntry,
+    pub total: EnergyBillEntry,
+}
+
+impl BillBreakdown {
+    pub fn in_currency(self, currency: Currency) -> BillBreakdown {
+        BillBreakdown {
+            usage: self.usage.in_currency(currency),
+            import_cost: self.import_cost.in_currency(currency),
+            export_credit: self.export_credit.in_currency(currency),
+            standing_charge: self.standing_charge.in_currency(currency),
+            pso_levy: self.pso_levy.in_currency(currency),
+            credits: self.credits.in_currency(currency),
+            bill_credit: self.bill_credit.in_currency(currency),
+            demand_charge: self.demand_charge.in_currency(currency),
+            total: self.total.in_currency(currency),
+        }
+    }
+
+    // The bill with the demand charge of the readings added to it
+    pub fn with_demand_charge(self, demand_charge: EnergyBillEntry) -> BillBreakdown {
+        if demand_charge.is_zero() {
+            return self;
+        }
+        BillBreakdown {
+            demand_charge: self.demand_charge + demand_charge,
+            total: self.total + demand_charge,
+            ..self
+        }
+    }
+
+    // The bill with its usage told apart into what the imports cost and what the exports
+    // earned, import_cost in the currency of the bill already
+    pub fn with_import_cost(self, import_cost: EnergyBillEntry) -> BillBreakdown {
+        BillBreakdown {
+            import_cost,
+            export_credit: self.usage - import_cost,
+            ..self
+        }
+    }
+
+    // Every line of the bill with what it is, the total is what they add up to
+    pub fn line_items(&self) -> [(&'static str, EnergyBillEntry); 7] {
+        [
+            ("import cost", self.import_cost),
+            ("export credit", self.export_credit),
+            ("standing charge", self.standing_charge),
+            ("PSO levy", self.pso_levy),
+            ("credits", self.credits),
+            ("bill credit", self.bill_credit),
+            ("demand charge", self.demand_charge),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn entries_print_like_a_bill() {
+        let debit = EnergyBillEntry::Debit(Money::new(dec!(843.2341)));
+        let credit = EnergyBillEntry::Credit(Money::new(dec!(12.1)));
+        assert_eq!(debit.to_string(), "€843.23");
+        assert_eq!(format!("{debit:.3}"), "€843.234");
+        assert_eq!(credit.to_string(), "CR €12.10");
+        assert_eq!(format!("{credit:.0}"), "CR €12");
+        assert_eq!(Money::new(dec!(-0.5)).to_string(), "-€0.50");
+    }
+
+    #[test]
+    fn entries_add_up_and_sort_by_cost() {
+        let debit = |amount| EnergyBillEntry::Debit(Money::new(amount));
+        let credit = |amount| EnergyBillEntry::Credit(Money::new(amount));
+        let entries = [debit(dec!(10)), credit(dec!(4)), debit(dec!(1.5))];
+        assert_eq!(entries.iter().sum::<EnergyBillEntry>(), debit(dec!(7.5)));
+        assert_eq!(debit(dec!(3)) - debit(dec!(5)), credit(dec!(2)));
+        assert_eq!(-credit(dec!(2)), debit(dec!(2)));
+
+        let mut total = debit(dec!(1));
+        total += credit(dec!(3));
+        assert_eq!(total, credit(dec!(2)));
+
+        let mut sorted = entries;
+        sorted.sort();
+        assert_eq!(sorted, [credit(dec!(4)), debit(dec!(1.5)), debit(dec!(10))]);
+        assert!(credit(dec!(0)) < debit(dec!(0)));
+    }
+
+    #[test]
+    fn the_lines_add_up_to_the_total() {
+        let debit = |amount| EnergyBillEntry::Debit(Money::new(amount));
+        let credit = |amount| EnergyBillEntry::Credit(Money::new(amount));
+        let breakdown = BillBreakdown {
+            usage: debit(dec!(30)),
+            import_cost: debit(dec!(30)),
+            export_credit: EnergyBillEntry::ZERO,
+            standing_charge: debit(dec!(10)),
+            pso_levy: EnergyBillEntry::ZERO,
+            credits: credit(dec!(5)),
+            bill_credit: EnergyBillEntry::ZERO,
+            demand_charge: EnergyBillEntry::ZERO,
+            total: debit(dec!(35)),
+        }
+        .with_import_cost(debit(dec!(42)));
+        assert_eq!(breakdown.export_credit, credit(dec!(12)));
+        let lines: EnergyBillEntry = breakdown.line_items().iter().map(|(_, line)| *line).sum();
+        assert_eq!(lines, breakdown.total);
+    }
+
+    #[test]
+    fn amounts_keep_their_currency() {
+        let pounds = EnergyBillEntry::Debit(Money::of(dec!(12.1), Currency::Gbp));
+        assert_eq!(pounds.to_string(), "£12.10");
+        // a zero, like the start of a sum, takes the currency of what is added to it
+        assert_eq!(EnergyBillEntry::ZERO + pounds, pounds);
+        let euro = EnergyBillEntry::Debit(Money::new(dec!(1)));
+        assert!(std::panic::catch_unwind(|| pounds + euro).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{path::Path, sync::OnceLock};
+
+use crate::{BillingPeriod, Currency, EnergyBillEntry, Money};
+
+// A fixed amount taken off the bill, like the government electricity credits of 2022 to 2024,
+// dated on the day it was applied and including VAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct BillCredit {
+    pub date: NaiveDate,
+    pub amount: Decimal,
+}
+
+// The credits every plan gets, none unless a schedule was installed, see credits.example.toml
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct CreditSchedule {
+    #[serde(default)]
+    pub credit: Vec<BillCredit>,
+}
+
+static SCHEDULE: OnceLock<CreditSchedule> = OnceLock::new();
+
+impl CreditSchedule {
+    // Every credit dated within the period, so splitting a period never counts one twice. The
+    // credits are Irish, plans priced in another currency don't get them
+    pub fn for_period(&self, period: &BillingPeriod, currency: Currency) -> EnergyBillEntry {
+        if currency != Currency::Eur {
+            return EnergyBillEntry::ZERO;
+        }
+        let amount: Decimal = self
+            .credit
+            .iter()
+            .filter(|credit| credit.date >= period.start && credit.date <= period.end)
+            .map(|credit| credit.amount)
+            .sum();
+        EnergyBillEntry::Credit(Money::new(amount))
+    }
+
+    pub fn global() -> &'static CreditSchedule {
+        SCHEDULE.get_or_init(CreditSchedule::default)
+    }
+
+    // Only works before any bill was computed, so it has to happen while starting up
+    pub fn install(self) -> Result<()> {
+        SCHEDULE
+            .set(self)
+            .map_err(|_| anyhow::anyhow!("the credit schedule is already in use"))
+    }
+}
+
+pub fn load_credit_schedule(filename: &Path) -> Result<CreditSchedule> {
+    let contents = std::fs::read_to_string(filename)?;
+    toml::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("invalid credits file {}: {e}", filename.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn a_split_period_counts_every_credit_once() {
+        let schedule = load_credit_schedule(Path::new("credits.example.toml")).unwrap();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let period = |start, end| BillingPeriod { start, end };
+        let whole =
+            schedule.for_period(&period(date(2022, 10, 1), date(2023, 1, 31)), Currency::Eur);
+        assert_eq!(whole, EnergyBillEntry::Credit(Money::new(dec!(400))));
+        // the credit of the 1st of november is on the first day of the second part
+        let first = schedule.for_period(
+            &period(date(2022, 10, 1), date(2022, 10, 31)),
+            Currency::Eur,
+        );
+        let second =
+            schedule.for_period(&period(date(2022, 11, 1), date(2023, 1, 31)), Currency::Eur);
+        assert_eq!(first, EnergyBillEntry::Credit(Money::ZERO));
+        assert_eq!(first + second, whole);
+        assert_eq!(
+            CreditSchedule::default()
+                .for_period(&period(date(2022, 10, 1), date(2023, 1, 31)), Currency::Eur),
+            EnergyBillEntry::Credit(Money::ZERO)
+        );
+    }
+
+    #[test]
+    fn only_plans_in_euro_get_the_credits() {
+        let schedule: CreditSchedule = toml::from_str(
+            r#"credit = [{ date = "2023-03-01", amount = 200 }, { date = "2023-06-01", amount = 100 }]"#,
+        )
+        .unwrap();
+        let period = BillingPeriod {
+            start: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2023, 3, 31).unwrap(),
+        };
+        assert_eq!(
+            schedule.for_period(&period, Currency::Eur),
+            EnergyBillEntry::Credit(Money::new(dec!(200)))
+        );
+        assert_eq!(
+            schedule.for_period(&period, Currency::Gbp),
+            EnergyBillEntry::ZERO
+        );
+    }
+}
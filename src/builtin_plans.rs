@@ -0,0 +1,273 @@
+use chrono::{NaiveTime, Weekday};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::{
+    DaySet, Eligibility, FlatRateGasPlan, FreeTime, FreeWindow, GasPlanStrategy, PlanMetadata,
+    PricePlanStrategy, StandingChargeDefinition, TimeOfUseTariff, TimeOfUseTariffBuilder,
+};
+
+pub(crate) const PEAK_ENERGY_START_TIME: NaiveTime = match NaiveTime::from_hms_opt(17, 0, 0) {
+    Some(t) => t,
+    None => panic!("Must be a valid time"),
+};
+pub(crate) const PEAK_ENERGY_END_TIME: NaiveTime = match NaiveTime::from_hms_opt(19, 0, 0) {
+    Some(t) => t,
+    None => panic!("Must be a valid time"),
+};
+
+const NIGHT_ENERGY_START_TIME: NaiveTime = match NaiveTime::from_hms_opt(23, 0, 0) {
+    Some(t) => t,
+    None => panic!("Must be a valid time"),
+};
+const NIGHT_ENERGY_END_TIME: NaiveTime = match NaiveTime::from_hms_opt(8, 0, 0) {
+    Some(t) => t,
+    None => panic!("Must be a valid time"),
+};
+
+const FREE_ENERGY_START_TIME: NaiveTime = match NaiveTime::from_hms_opt(9, 0, 0) {
+    Some(t) => t,
+    None => panic!("Must be a valid time"),
+};
+const FREE_ENERGY_END_TIME: NaiveTime = match NaiveTime::from_hms_opt(18, 0, 0) {
+    Some(t) => t,
+    None => panic!("Must be a valid time"),
+};
+
+const BOOST_ENERGY_START_TIME: NaiveTime = match NaiveTime::from_hms_opt(2, 0, 0) {
+    Some(t) => t,
+    None => panic!("Must be a valid time"),
+};
+const BOOST_ENERGY_END_TIME: NaiveTime = match NaiveTime::from_hms_opt(5, 0, 0) {
+    Some(t) => t,
+    None => panic!("Must be a valid time"),
+};
+
+// The plans compared when no tariff file narrows them down
+pub fn builtin_plans() -> Vec<Box<dyn PricePlanStrategy>> {
+    vec![
+        Box::new(electric_ireland_home_electric_14()),
+        Box::new(sse_airtricity_20()),
+        Box::new(bord_gais_energy_25_weekend_free()),
+        Box::new(sse_airtricity_ev_boost()),
+    ]
+}
+
+// The gas plans compared when gas usage is given
+pub fn builtin_gas_plans() -> Vec<Box<dyn GasPlanStrategy>> {
+    vec![
+        Box::new(FlatRateGasPlan {
+            name: "Bord Gais Energy Gas".to_string(),
+            unit_rate: dec!(0.1328),
+            discount: Decimal::ZERO,
+            annual_standing_charge: dec!(118.17),
+        }),
+        Box::new(FlatRateGasPlan {
+            name: "Electric Ireland Gas".to_string(),
+            unit_rate: dec!(0.1206),
+            discount: Decimal::ZERO,
+            annual_standing_charge: dec!(126.35),
+        }),
+    ]
+}
+
+// The built in bands never overlap, so building them can't fail
+fn build(tariff: TimeOfUseTariffBuilder) -> TimeOfUseTariff {
+    tariff
+        .build()
+        .expect("the built in plans have no overlapping bands")
+}
+
+// Only the supplier is known of the built in plans, pass a tariff file with the metadata of
+// the plan to check it against the price list
+fn supplier(name: &str) -> PlanMetadata {
+    PlanMetadata {
+        supplier: Some(name.to_string()),
+        ..PlanMetadata::default()
+    }
+}
+
+pub fn electric_ireland_home_electric_14() -> TimeOfUseTariff {
+    build(
+        TimeOfUseTariff::builder("ElectricIrelandHomeElectric14")
+            .metadata(PlanMetadata {
+                aliases: vec!["EI HE 14%".to_string()],
+                ..supplier("Electric Ireland")
+            })
+            .default_rate("standard", dec!(0.3895))
+            .discount(dec!(0.14))
+            .export_rate(dec!(0.21))
+            .standing_charge(StandingChargeDefinition::Annual(dec!(272.61)))
+            .rural_standing_charge(StandingChargeDefinition::Annual(dec!(333.28))),
+    )
+}
+
+// the reading ending at 17:00 is day rate, the one ending at 19:00 is the last peak one
+// the reading ending at 23:00 is day rate, the one ending at 08:00 is the last night one
+pub fn sse_airtricity_20() -> TimeOfUseTariff {
+    build(
+        TimeOfUseTariff::builder("SSEAirtricity20")
+            .metadata(supplier("SSE Airtricity"))
+            .band(
+                "peak",
+                DaySet::all(),
+                PEAK_ENERGY_START_TIME,
+                PEAK_ENERGY_END_TIME,
+                dec!(0.4882),
+            )
+            .band(
+                "night",
+                DaySet::all(),
+                NIGHT_ENERGY_START_TIME,
+                NIGHT_ENERGY_END_TIME,
+                dec!(0.2506),
+            )
+            .default_rate("day", dec!(0.3865))
+            .discount(dec!(0.20))
+            .export_rate(dec!(0.24))
+            .standing_charge(StandingChargeDefinition::PerDay(dec!(0.6602)))
+            .rural_standing_charge(StandingChargeDefinition::PerDay(dec!(0.8054))),
+    )
+}
+
+// the day, night and peak bands of sse_airtricity_20 with a boost for charging an EV from 02:00
+// to 05:00, the readings ending at 02:30 up to and including 05:00
+pub fn sse_airtricity_ev_boost() -> TimeOfUseTariff {
+    build(
+        TimeOfUseTariff::builder("SSEAirtricityEVBoost")
+            .metadata(PlanMetadata {
+                eligibility: Eligibility {
+                    smart_meter: true,
+                    ev: true,
+                    ..Eligibility::default()
+                },
+                ..supplier("SSE Airtricity")
+            })
+            .boost(
+                "boost",
+                BOOST_ENERGY_START_TIME,
+                BOOST_ENERGY_END_TIME,
+                dec!(0.0600),
+            )
+            .band(
+                "peak",
+                DaySet::all(),
+                PEAK_ENERGY_START_TIME,
+                PEAK_ENERGY_END_TIME,
+                dec!(0.4882),
+            )
+            .band(
+                "night",
+                DaySet::all(),
+                NIGHT_ENERGY_START_TIME,
+                NIGHT_ENERGY_END_TIME,
+                dec!(0.2506),
+            )
+            .default_rate("day", dec!(0.3865))
+            .discount(dec!(0.10))
+            .export_rate(dec!(0.24))
+            .standing_charge(StandingChargeDefinition::PerDay(dec!(0.6602)))
+            .rural_standing_charge(StandingChargeDefinition::PerDay(dec!(0.8054))),
+    )
+}
+
+/**
+    Urban Day units (8am to 11pm)    43.04 35.30 cent per kWh
+    Urban Peak units (5pm to 7pm)    52.58 43.12 cent per kWh
+    Urban Night units (11pm to 8am)  31.63 25.94 cent per kWh
+    Annual Standing Charge           €237.56
+    Rural Annual Standing Charge     €299.41
+
+    free from 9am to 6pm on sundays and no peak time on weekends,
+    bank holidays are priced like a sunday
+*/
+pub fn bord_gais_energy_25_weekend_free() -> TimeOfUseTariff {
+    build(
+        TimeOfUseTariff::builder("BordGaisEnergy25WeekendFree")
+            .metadata(supplier("Bord Gáis Energy"))
+            .free_time(FreeTime::new(
+                FreeWindow {
+                    days: DaySet::of(&[Weekday::Sun]),
+                    start: FREE_ENERGY_START_TIME,
+                    end: FREE_ENERGY_END_TIME,
+                },
+                vec![],
+            ))
+            .band(
+                "peak",
+                DaySet::weekdays(),
+                PEAK_ENERGY_START_TIME,
+                PEAK_ENERGY_END_TIME,
+                dec!(0.5258),
+            )
+            .band(
+                "night",
+                DaySet::all(),
+                NIGHT_ENERGY_START_TIME,
+                NIGHT_ENERGY_END_TIME,
+                dec!(0.3163),
+            )
+            .default_rate("day", dec!(0.4304))
+            .discount(dec!(0.25))
+            .export_rate(dec!(0.185))
+            .standing_charge(StandingChargeDefinition::Annual(dec!(237.56)))
+            .rural_standing_charge(StandingChargeDefinition::Annual(dec!(299.41)))
+            .holidays_like(Weekday::Sun),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, EnergyBillEntry, Money};
+
+    #[test]
+    fn sse_airtricity_band_boundaries() {
+        let day = dec!(0.3865) * dec!(0.80);
+        let peak = dec!(0.4882) * dec!(0.80);
+        let night = dec!(0.2506) * dec!(0.80);
+        let cases = [
+            ("08-01-2024 08:00", night),
+            ("08-01-2024 08:30", day),
+            ("08-01-2024 17:00", day),
+            ("08-01-2024 17:30", peak),
+            ("08-01-2024 19:00", peak),
+            ("08-01-2024 19:30", day),
+            ("08-01-2024 23:00", day),
+            ("08-01-2024 23:30", night),
+            ("09-01-2024 00:00", night),
+            ("09-01-2024 03:00", night),
+        ];
+
+        // 2 kW over half an hour is 1 kWh, so the price is the rate itself
+        for (datetime, rate) in cases {
+            assert_eq!(
+                sse_airtricity_20().price_for_singe_period(&reading_at(datetime, 2.0)),
+                EnergyBillEntry::Debit(Money::new(rate)),
+                "{datetime}"
+            );
+        }
+    }
+
+    #[test]
+    fn ev_boost_wins_over_the_night_band() {
+        let plan = sse_airtricity_ev_boost();
+        let cases = [
+            ("09-01-2024 02:00", "night"),
+            ("09-01-2024 02:30", "boost"),
+            ("09-01-2024 05:00", "boost"),
+            ("09-01-2024 05:30", "night"),
+        ];
+        for (datetime, band) in cases {
+            assert_eq!(
+                plan.band_for_singe_period(&reading_at(datetime, 2.0)),
+                band,
+                "{datetime}"
+            );
+        }
+        assert_eq!(
+            plan.price_for_singe_period(&reading_at("09-01-2024 03:00", 2.0)),
+            EnergyBillEntry::Debit(Money::new(dec!(0.0600) * dec!(0.90)))
+        );
+    }
+}
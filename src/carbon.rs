@@ -0,0 +1,296 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io::Write, path::Path, process::Command};
+
+use crate::{
+    meter_data::{smart_meter_datetime_desserialize, smart_meter_datetime_serialize},
+    read_csv, SmartMeterData, SmartMeterDataType, LOCAL_TIMEZONE,
+};
+
+// Grams of CO2 per kWh, roughly the yearly average of the Irish grid of the last few years.
+// Used for every reading when there's no intensity file, or for the holes in one
+pub const IRISH_AVERAGE_INTENSITY: f32 = 280.0;
+
+// An intensity is used for the readings starting up to this long after it, like the prices
+// of a dynamic plan
+const MAX_INTENSITY_LENGTH_MINUTES: i64 = 60;
+
+// How much of the usage of each day --carbon moves to the cleanest interval of the day
+pub const DEFAULT_SHIFTABLE_SHARE: f32 = 0.1;
+
+const DASHBOARD_API: &str = "https://www.smartgriddashboard.com/DashboardService.svc/data";
+
+// One row of an intensity file, the start of the interval in UTC like the HDF readings and
+// the grams of CO2 per kWh of the grid, e.g. 08-01-2024 00:00,212
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IntensityPoint {
+    #[serde(
+        deserialize_with = "smart_meter_datetime_desserialize",
+        serialize_with = "smart_meter_datetime_serialize"
+    )]
+    pub start: NaiveDateTime,
+    pub intensity: f32,
+}
+
+// The carbon intensity of the grid by the start of its interval, in grams of CO2 per kWh
+#[derive(Debug, Clone)]
+pub struct CarbonIntensity {
+    series: BTreeMap<NaiveDateTime, f32>,
+    average: f32,
+}
+
+impl CarbonIntensity {
+    // The same intensity at every hour, shifting usage saves nothing with it
+    pub fn irish_average() -> CarbonIntensity {
+        CarbonIntensity {
+            series: BTreeMap::new(),
+            average: IRISH_AVERAGE_INTENSITY,
+        }
+    }
+
+    pub fn new(points: Vec<IntensityPoint>) -> CarbonIntensity {
+        CarbonIntensity {
+            series: points
+                .into_iter()
+                .map(|point| (point.start, point.intensity))
+                .collect(),
+            average: IRISH_AVERAGE_INTENSITY,
+        }
+    }
+
+    pub fn load(filename: &Path) -> Result<CarbonIntensity> {
+        let intensity = CarbonIntensity::new(read_csv(filename)?);
+        if intensity.series.is_empty() {
+            bail!("no carbon intensities in {}", filename.display());
+        }
+        Ok(intensity)
+    }
+
+    // The intensity of the interval starting at start, the average of the points inside it
+    // so quarter hour intensities work for half hour readings. The average of the grid
+    // where the series has nothing for it
+    pub fn intensity_at(&self, start: NaiveDateTime, minutes: i64) -> f32 {
+        let inside: Vec<f32> = self
+            .series
+            .range(start..start + Duration::minutes(minutes))
+            .map(|(_, &intensity)| intensity)
+            .collect();
+        if !inside.is_empty() {
+            return inside.iter().sum::<f32>() / inside.len() as f32;
+        }
+        match self.series.range(..=start).next_back() {
+            Some((&from, &intensity))
+                if start - from < Duration::minutes(MAX_INTENSITY_LENGTH_MINUTES) =>
+            {
+                intensity
+            }
+            _ => self.average,
+        }
+    }
+}
+
+// The emissions of the imports of a meter point, exports aren't counted against them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CarbonFootprint {
+    pub import_kwh: f32,
+    pub kg_co2: f32,
+    // grams per kWh of the hours the energy was actually used
+    pub average_intensity: f32,
+    pub shiftable_share: f32,
+    // what moving the shiftable share of each day to its cleanest interval would save
+    pub shifting_saves_kg_co2: f32,
+}
+
+// Works out the emissions of the imports with the intensity of the interval of each reading.
+// The shifting benefit takes shiftable_share of the energy of every interval of a day and
+// uses it in the interval of the day with the lowest intensity instead
+pub fn carbon_footprint(
+    datapoints: &[SmartMeterData],
+    intensity: &CarbonIntensity,
+    shiftable_share: f32,
+) -> CarbonFootprint {
+    // per local day, the energy, the grams and the lowest intensity of the day
+    let mut days: BTreeMap<NaiveDate, (f32, f32, f32)> = BTreeMap::new();
+    for datapoint in datapoints
+        .iter()
+        .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+    {
+        let minutes = datapoint.interval_minutes as i64;
+        let start = datapoint.read_data_and_end_time - Duration::minutes(minutes);
+        let grams_per_kwh = intensity.intensity_at(start, minutes);
+        let kwh = datapoint.energy_kwh();
+        let day = days.entry(datapoint.date()).or_insert((0.0, 0.0, f32::MAX));
+        day.0 += kwh;
+        day.1 += kwh * grams_per_kwh;
+        day.2 = day.2.min(grams_per_kwh);
+    }
+    let import_kwh: f32 = days.values().map(|day| day.0).sum();
+    let grams: f32 = days.values().map(|day| day.1).sum();
+    let shifted_grams: f32 = days
+        .values()
+        .map(|&(kwh, grams, cleanest)| shiftable_share * (grams - kwh * cleanest))
+        .sum();
+    CarbonFootprint {
+        import_kwh,
+        kg_co2: grams / 1000.0,
+        average_intensity: if import_kwh > 0.0 {
+            grams / import_kwh
+        } else {
+            0.0
+        },
+        shiftable_share,
+        shifting_saves_kg_co2: shifted_grams.max(0.0) / 1000.0,
+    }
+}
+
+// The rows of an area of the EirGrid smart grid dashboard, in Irish local time
+#[derive(Debug, Deserialize)]
+struct DashboardResponse {
+    #[serde(rename = "Rows", default)]
+    rows: Vec<DashboardRow>,
+    #[serde(rename = "ErrorMessage", default)]
+    error_message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashboardRow {
+    #[serde(rename = "EffectiveTime")]
+    effective_time: String,
+    #[serde(rename = "Value")]
+    value: Option<f32>,
+}
+
+// The values of a dashboard answer by the start of their quarter hour in UTC, what is the
+// name of the values for the errors. The quarter hours not published yet have no value and
+// are left out
+pub(crate) fn dashboard_values(json: &str, what: &str) -> Result<Vec<(NaiveDateTime, f32)>> {
+    let response: DashboardResponse =
+        serde_json::from_str(json).context("unexpected answer from the grid dashboard")?;
+    if response.rows.is_empty() {
+        if let Some(error) = response.error_message.filter(|e| !e.is_empty()) {
+            bail!("the grid dashboard has no {what}: {error}");
+        }
+    }
+    let mut values = vec![];
+    for row in response.rows {
+        let Some(value) = row.value else {
+            continue;
+        };
+        let local = NaiveDateTime::parse_from_str(&row.effective_time, "%d-%b-%Y %H:%M:%S")
+            .with_context(|| {
+                format!(
+                    "invalid time {} from the grid dashboard",
+                    row.effective_time
+                )
+            })?;
+        // the hour repeated when the clocks go back is taken the first time
+        let Some(start) = LOCAL_TIMEZONE.from_local_datetime(&local).earliest() else {
+            continue;
+        };
+        values.push((start.naive_utc(), value));
+    }
+    Ok(values)
+}
+
+// Reads the intensities out of a dashboard answer
+pub fn parse_dashboard_intensity(json: &str) -> Result<Vec<IntensityPoint>> {
+    Ok(dashboard_values(json, "carbon intensities")?
+        .into_iter()
+        .map(|(start, intensity)| IntensityPoint { start, intensity })
+        .collect())
+}
+
+// Downloads an area of the EirGrid smart grid dashboard for the Republic of Ireland, like
+// co2intensity or windactual, every quarter hour of the days from from to to
+pub(crate) fn fetch_dashboard(
+    area: &str,
+    what: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<String> {
+    let url = format!(
+        "{DASHBOARD_API}?area={area}&region=ROI&datefrom={}+00%3A00&dateto={}+23%3A59",
+        from.format("%d-%b-%Y"),
+        to.format("%d-%b-%Y")
+    );
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", &url])
+        .output()
+        .with_context(|| format!("could not run curl to download the {what}"))?;
+    if !output.status.success() {
+        bail!(
+            "downloading the {what} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+pub fn fetch_carbon_intensity(from: NaiveDate, to: NaiveDate) -> Result<Vec<IntensityPoint>> {
+    parse_dashboard_intensity(&fetch_dashboard(
+        "co2intensity",
+        "carbon intensity",
+        from,
+        to,
+    )?)
+}
+
+// Writes the intensities in the format --carbon-intensity reads
+pub fn write_intensity_file(points: &[IntensityPoint], writer: impl Write) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    for point in points {
+        wtr.serialize(point)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn shifting_to_the_cleanest_interval_saves_emissions() {
+        // 1 kWh at a dirty 400 g and 1 kWh at a clean 100 g
+        let data = vec![
+            reading_at("08-01-2024 18:00", 2.0),
+            reading_at("08-01-2024 03:30", 2.0),
+        ];
+        let time = |text| NaiveDateTime::parse_from_str(text, "%d-%m-%Y %H:%M").unwrap();
+        let intensity = CarbonIntensity::new(vec![
+            IntensityPoint {
+                start: time("08-01-2024 17:30"),
+                intensity: 400.0,
+            },
+            IntensityPoint {
+                start: time("08-01-2024 03:00"),
+                intensity: 100.0,
+            },
+        ]);
+        let footprint = carbon_footprint(&data, &intensity, 0.5);
+        assert_eq!(footprint.import_kwh, 2.0);
+        assert_eq!(footprint.kg_co2, 0.5);
+        assert_eq!(footprint.average_intensity, 250.0);
+        // half of each kWh moved to 100 g: 0.5 * (500 - 2 * 100) g
+        assert_eq!(footprint.shifting_saves_kg_co2, 0.15);
+
+        let flat = carbon_footprint(&data, &CarbonIntensity::irish_average(), 0.5);
+        assert_eq!(flat.kg_co2, 2.0 * IRISH_AVERAGE_INTENSITY / 1000.0);
+        assert_eq!(flat.shifting_saves_kg_co2, 0.0);
+    }
+
+    #[test]
+    fn reads_the_grid_dashboard() {
+        let json = r#"{"ErrorMessage":null,"Rows":[
+            {"EffectiveTime":"01-Jul-2024 10:00:00","FieldName":"CO2_INTENSITY","Region":"ROI","Value":180},
+            {"EffectiveTime":"01-Jul-2024 10:15:00","FieldName":"CO2_INTENSITY","Region":"ROI","Value":null}
+        ],"Status":"Success"}"#;
+        let points = parse_dashboard_intensity(json).unwrap();
+        assert_eq!(points.len(), 1);
+        // summer time is an hour ahead of UTC
+        assert_eq!(points[0].start.format("%H:%M").to_string(), "09:00");
+        assert_eq!(points[0].intensity, 180.0);
+    }
+}
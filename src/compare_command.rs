@@ -0,0 +1,758 @@
+use anyhow::{bail, Result};
+use energy_comparator::{
+    annual_usage_readings, attribute_circuits, baseload, billed_usage_readings, builtin_gas_plans,
+    builtin_plans, carbon_footprint, compare_dual_fuel, compare_plans, coverage, cumulative_costs,
+    detect_source, estimate_from_manual_reads, estimated_annual_bills, estimated_gas_readings,
+    group_by_mprn, load_billed_usage, load_credit_schedule, load_holiday_overrides,
+    load_manual_reads, load_plan_file, load_pso_overrides, load_registry, merge_plans,
+    monthly_winners, peak_demand, rank_gas_plans, rate_sensitivity, read_csv,
+    render_cost_chart_svg, render_heatmap_svg, render_report, stream_comparison, usage_heatmap,
+    validate_plan, write_audit_csv, write_results_csv, Battery, BillingPeriod, CachedPlan,
+    CarbonIntensity, Circuit, ComparisonOptions, ComparisonReport, Contract, Currency, CustomPlan,
+    CustomerType, DualFuelBundle, DuosGroup, EstimatedPeriod, EvDetection, Explorer, GapFilling,
+    GasConversion, GasMeterData, GasPlanStrategy, GenerationSeries, Granularity, HolidayCalendar,
+    Household, InDuosGroup, InvoiceCycle, LoadShift, ManualReading, MaximumImportCapacity, Metrics,
+    ParseMode, PlanFile, PlanSelection, PlanSource, PricePlanStrategy, PsoLevy, Quarantine,
+    ReportFormat, SharedUsageCache, SmartMeterData, Solar, SolarArray, UsageCache, UsageGrowth,
+    WithImportCapacity,
+};
+use rust_decimal::Decimal;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::text_output::{print_gap_filling, print_text};
+use crate::{
+    expand_inputs, input_snapshot, replace_file, report_format, CompareArgs, GlobalArgs,
+    OutputFormat, PLAN_REGISTRY, USAGE_CACHE,
+};
+
+// The currency of the readings, UK readings are compared on plans in pounds. The store
+// doesn't know where its readings came from, they are taken to be Irish
+fn readings_currency(global: &GlobalArgs, cli: &CompareArgs) -> Result<Currency> {
+    if global.store.is_some() {
+        return Ok(Currency::default());
+    }
+    let mut currencies = BTreeSet::new();
+    for path in expand_inputs(&cli.inputs)? {
+        currencies.insert(detect_source(&path)?.currency());
+    }
+    match currencies.len() {
+        0 | 1 => Ok(currencies.into_iter().next().unwrap_or_default()),
+        _ => bail!("the inputs mix readings of meters in different countries, compare them apart"),
+    }
+}
+
+// Sets up what every plan is priced with, once before the first comparison
+pub(crate) fn install_options(cli: &CompareArgs) -> Result<()> {
+    if cli.strict {
+        ParseMode::Strict.install()?;
+    }
+    if let Some(rejects) = &cli.rejects {
+        Quarantine::create(rejects)?.install()?;
+    }
+    if let Some(holidays) = &cli.holidays {
+        HolidayCalendar::irish()
+            .with_overrides(&load_holiday_overrides(holidays)?)
+            .install()?;
+    }
+    if let Some(credits) = &cli.credits {
+        load_credit_schedule(credits)?.install()?;
+    }
+    if let Some(pso) = &cli.pso {
+        PsoLevy::irish()
+            .with_overrides(&load_pso_overrides(pso)?)
+            .install()?;
+    }
+    Ok(())
+}
+
+// The electricity plans of a tariff file with a warning for each mistake in them, the gas
+// plans and bundles are left in it
+pub(crate) fn tariff_plans(plan_file: &mut PlanFile) -> Vec<Box<dyn PricePlanStrategy>> {
+    for plan in &plan_file.plan {
+        for issue in validate_plan(plan) {
+            eprintln!("warning: tariff {}: {issue}", plan.name);
+        }
+    }
+    plan_file.take_electricity_plans()
+}
+
+// The plans of a comparison: the built in plans with the registry, the tariff file and the
+// custom plans merged over them, and where each of them came from
+pub(crate) struct LoadedPlans {
+    pub(crate) plans: Vec<Box<dyn PricePlanStrategy>>,
+    sources: HashMap<String, PlanSource>,
+    gas_plans: Vec<Box<dyn GasPlanStrategy>>,
+    bundles: Vec<DualFuelBundle>,
+}
+
+pub(crate) fn load_plans(
+    tariffs: Option<&Path>,
+    no_registry: bool,
+    custom_plans: &[CustomPlan],
+) -> Result<LoadedPlans> {
+    let mut plans = builtin_plans();
+    let mut sources: HashMap<String, PlanSource> = plans
+        .iter()
+        .map(|plan| plan.name())
+        .map(|name| (name.to_lowercase(), PlanSource::BuiltIn))
+        .collect();
+    let mut gas_plans = builtin_gas_plans();
+    let mut bundles = vec![];
+    // the registry replaces the built in plans of the same name and the tariff file both
+    let registry = if no_registry {
+        None
+    } else {
+        load_registry(PLAN_REGISTRY.as_ref())?
+    };
+    let tariff_file = tariffs.map(load_plan_file).transpose()?;
+    for (source, mut plan_file) in registry
+        .map(|file| (PlanSource::Registry, file))
+        .into_iter()
+        .chain(tariff_file.map(|file| (PlanSource::TariffFile, file)))
+    {
+        add_plans(
+            &mut plans,
+            &mut sources,
+            tariff_plans(&mut plan_file),
+            source,
+        );
+        for plan in plan_file.gas_plan {
+            gas_plans.push(Box::new(plan));
+        }
+        bundles.extend(plan_file.dual_fuel);
+    }
+    let custom_plans = custom_plans
+        .iter()
+        .map(|plan| Ok(Box::new(plan.to_plan()?) as Box<dyn PricePlanStrategy>))
+        .collect::<Result<Vec<_>>>()?;
+    add_plans(
+        &mut plans,
+        &mut sources,
+        custom_plans,
+        PlanSource::CustomPlan,
+    );
+    Ok(LoadedPlans {
+        plans,
+        sources,
+        gas_plans,
+        bundles,
+    })
+}
+
+pub(crate) fn compare(global: &GlobalArgs, cli: &CompareArgs) -> Result<Vec<ComparisonReport>> {
+    let LoadedPlans {
+        plans,
+        sources,
+        mut gas_plans,
+        bundles,
+    } = load_plans(cli.tariffs.as_deref(), cli.no_registry, &cli.custom_plan)?;
+    // the aliases are only taken here, everything after goes by the names of the plans
+    let resolved = cli.with_plan_names(&plans);
+    let cli = &resolved;
+    let currency = readings_currency(global, cli)?;
+    gas_plans.retain(|plan| plan.currency() == currency);
+    let mut plans = selected_plans(plans, cli, currency)?;
+
+    let usage_cache =
+        (!cli.no_cache && !cli.stream).then(|| UsageCache::load(USAGE_CACHE.as_ref()).shared());
+    if let Some(cache) = &usage_cache {
+        plans = with_usage_cache(plans, &sources, cli, cache);
+    }
+
+    if cli.stream {
+        let mut reports = stream_comparison(
+            &expand_inputs(&cli.inputs)?,
+            &plans,
+            global.from,
+            global.to,
+            cli.current_plan.as_deref(),
+        )?;
+        if reports.is_empty() {
+            bail!("no readings left to compare");
+        }
+        label_sources(&mut reports, &sources);
+        Metrics::global().compared(&reports);
+        add_snapshot(global, cli, &mut reports)?;
+        print_reports(global, cli, &reports)?;
+        return Ok(reports);
+    }
+
+    let (data, filling, estimated) = comparison_readings(global, cli)?;
+
+    // the csv export and the reports always carry a breakdown, monthly unless asked otherwise
+    let is_report = matches!(global.format(), OutputFormat::Html | OutputFormat::Markdown);
+    let options = comparison_options(cli, is_report)?;
+    // gas is optional, most homes only have the electricity export
+    let gas_data: Vec<GasMeterData> = match &cli.gas {
+        Some(gas_file) => read_csv(gas_file)?,
+        None => vec![],
+    };
+    let has_gas = cli.gas.is_some() || cli.gas_kwh_per_day.is_some() || cli.gas_kwh.is_some();
+
+    // the CRU profile is of an Irish home
+    if cli.eab && currency != Currency::Eur {
+        bail!(
+            "estimated annual bills are only worked out for plans priced in {}",
+            Currency::Eur
+        );
+    }
+
+    if cli.interactive {
+        if group_by_mprn(data.clone()).len() > 1 {
+            bail!("--interactive looks at one meter point at a time, give it the readings of one");
+        }
+        let stdin = std::io::stdin();
+        Explorer::new(&plans, &data, cli.current_plan.clone())
+            .run(stdin.lock(), std::io::stdout())?;
+        return Ok(vec![]);
+    }
+    let intensity = match &cli.carbon_intensity {
+        Some(file) => CarbonIntensity::load(file)?,
+        None => CarbonIntensity::irish_average(),
+    };
+    if let Some(heatmap) = &cli.heatmap {
+        std::fs::write(heatmap, render_heatmap_svg(&usage_heatmap(&data)))?;
+    }
+    if let Some(explain) = &cli.explain {
+        write_audit_csv(&plans, &data, &filling, File::create(explain)?)?;
+    }
+
+    // every meter point is compared on its own, over its own billing period
+    let mut reports = vec![];
+    let meters = group_by_mprn(data);
+    let several_meters = meters.len() > 1;
+    for (mprn, data) in meters {
+        let Some(period) = BillingPeriod::from_data(&data) else {
+            continue;
+        };
+        let period = match cli.days {
+            Some(days) => BillingPeriod::starting_at(period.start, days),
+            None => period,
+        };
+        let gas_plans = if has_gas {
+            let days = period.number_of_days().max(1) as f32;
+            let gas_data = match (cli.gas_kwh_per_day, cli.gas_kwh) {
+                (Some(kwh_per_day), _) => estimated_gas_readings(&period, kwh_per_day),
+                (None, Some(kwh)) => estimated_gas_readings(&period, kwh / days),
+                (None, None) => gas_data.clone(),
+            };
+            rank_gas_plans(&gas_plans, &gas_data, &gas_conversion(cli), &period)
+        } else {
+            vec![]
+        };
+        let estimated_annual_bills = if cli.eab {
+            estimated_annual_bills(&plans, period.start)
+        } else {
+            vec![]
+        };
+        if let Some(chart) = &cli.cost_chart {
+            let chart = if several_meters {
+                chart.with_file_name(format!(
+                    "{}-{mprn}.svg",
+                    chart.file_stem().unwrap_or_default().to_string_lossy()
+                ))
+            } else {
+                chart.clone()
+            };
+            let curves = cumulative_costs(&plans, &data, &period);
+            std::fs::write(chart, render_cost_chart_svg(&curves))?;
+        }
+        let carbon = cli
+            .carbon
+            .then(|| carbon_footprint(&data, &intensity, cli.shiftable / 100.0));
+        let results = compare_plans(&plans, &data, &period, &options)?;
+        let monthly_winners = options
+            .monthly_winners
+            .then(|| monthly_winners(&plans, &data, &period, &results))
+            .flatten();
+        let rate_sensitivity = options
+            .rate_sensitivity
+            .map(|change| rate_sensitivity(&plans, &data, &period, &results, change));
+        let plans = results;
+        let dual_fuel = if gas_plans.is_empty() {
+            vec![]
+        } else {
+            compare_dual_fuel(&bundles, &plans, &gas_plans)?
+        };
+        reports.push(ComparisonReport {
+            plans,
+            mprn: mprn.to_string(),
+            period,
+            gas_plans,
+            dual_fuel,
+            estimated_annual_bills,
+            carbon,
+            load_shift: options.load_shift,
+            battery: options.battery,
+            solar: options.solar.as_ref().map(|solar| solar.apply(&data).1),
+            usage_growth: options
+                .usage_growth
+                .as_ref()
+                .map(|growth| growth.apply(&data).1),
+            ev_charging: options.ev.map(|ev| ev.detect(&data)),
+            sub_metering: (!options.circuits.is_empty())
+                .then(|| attribute_circuits(&options.circuits, &data)),
+            baseload: options.baseload.then(|| baseload(&data)).flatten(),
+            peak_demand: options.peaks.map(|count| peak_demand(&data, count)),
+            contract: options.contract,
+            scenarios: options.scenarios.clone(),
+            monthly_winners,
+            rate_sensitivity,
+            coverage: Some(coverage(&data, &period, &filling, &estimated)),
+            estimated: estimated.clone(),
+            snapshot: None,
+        });
+    }
+
+    if let Some(cache) = &usage_cache {
+        // failing to write the cache only costs time on the next run
+        if let Err(e) = cache.lock().unwrap().save(USAGE_CACHE.as_ref()) {
+            eprintln!("could not write cache {USAGE_CACHE}: {e}");
+        }
+    }
+    print_warnings(&reports);
+    check_coverage(cli, &reports)?;
+    label_sources(&mut reports, &sources);
+    Metrics::global().compared(&reports);
+    add_snapshot(global, cli, &mut reports)?;
+    print_reports(global, cli, &reports)?;
+    Ok(reports)
+}
+
+// The plans the readings can be compared on, priced for the connection of the options
+fn selected_plans(
+    plans: Vec<Box<dyn PricePlanStrategy>>,
+    cli: &CompareArgs,
+    currency: Currency,
+) -> Result<Vec<Box<dyn PricePlanStrategy>>> {
+    let selection = PlanSelection {
+        currency,
+        customer_type: CustomerType::from(cli.customer_type),
+        household: Household {
+            smart_meter: cli.no_smart_meter.then_some(false),
+            ev: cli.no_ev.then_some(false),
+            gas: cli.no_gas.then_some(false),
+            supplier: cli.current_supplier.clone(),
+        },
+        prepay: cli.prepay,
+        plans: cli.plans_to_compare.clone(),
+        current_plan: cli.current_plan.clone(),
+    };
+    let selected = selection.select(plans)?;
+    if selected.other_currency > 0 {
+        eprintln!(
+            "warning: left out {} plans that aren't priced in {currency} like the readings",
+            selected.other_currency
+        );
+    }
+    if !selected.ineligible.is_empty() {
+        eprintln!(
+            "warning: left out {} plans you can't sign up for: {}",
+            selected.ineligible.len(),
+            selected.ineligible.join(", ")
+        );
+    }
+    let mut plans = selected.plans;
+
+    if let Some(group) = cli.duos_group {
+        let group = DuosGroup::from(group);
+        plans = plans
+            .into_iter()
+            .map(|plan| InDuosGroup::wrap(plan, group))
+            .collect();
+    }
+    if let Some(kva) = cli.mic_kva {
+        let mic = MaximumImportCapacity(kva);
+        plans = plans
+            .into_iter()
+            .map(|plan| WithImportCapacity::wrap(plan, mic))
+            .collect();
+    }
+    Ok(plans)
+}
+
+// The readings of the files or the store, or made up from the usage of a year or of bills,
+// with the gaps filled and the manual readings added
+fn comparison_readings(
+    global: &GlobalArgs,
+    cli: &CompareArgs,
+) -> Result<(Vec<SmartMeterData>, GapFilling, Vec<EstimatedPeriod>)> {
+    let (data, profiled) = match (cli.annual_kwh, &cli.billed_kwh) {
+        (Some(kwh), _) => {
+            let start = global
+                .from
+                .unwrap_or_else(|| chrono::Local::now().date_naive() - chrono::Duration::days(365));
+            let (data, period) = annual_usage_readings(kwh, start);
+            (data, vec![period])
+        }
+        (None, Some(bills)) => billed_usage_readings(&load_billed_usage(bills)?),
+        (None, None) => (global.load_readings(&cli.inputs, !cli.no_cache)?, vec![]),
+    };
+    if data.is_empty() {
+        bail!("no readings left to compare");
+    }
+    let (data, filling) = cli.gaps.fill(data);
+    print_gap_filling(&filling);
+    let (data, mut estimated) = match &cli.manual_reads {
+        Some(file) => add_manual_reads(data, &load_manual_reads(file)?)?,
+        None => (data, vec![]),
+    };
+    estimated.extend(profiled);
+    Ok((data, filling, estimated))
+}
+
+fn comparison_options(cli: &CompareArgs, is_report: bool) -> Result<ComparisonOptions> {
+    Ok(ComparisonOptions {
+        current_plan: cli.current_plan.clone(),
+        bands: cli.bands || is_report,
+        effective_rates: cli.effective_rates,
+        exports: cli.exports,
+        granularity: match cli.breakdown {
+            Some(breakdown) => Some(breakdown.into()),
+            None if is_report || cli.output.is_some() => Some(Granularity::Month),
+            None => None,
+        },
+        years: cli.years,
+        annualize: cli.annualize,
+        load_shift: match (cli.shift_kwh, cli.shift_from, cli.shift_to) {
+            (Some(kwh_per_day), Some(from), Some(to)) => Some(LoadShift {
+                kwh_per_day,
+                from,
+                to,
+            }),
+            _ => None,
+        },
+        solar: match (&cli.solar_generation, cli.solar_kwp) {
+            (Some(file), _) => Some(Solar::Measured(GenerationSeries::load(file)?)),
+            (None, Some(kwp)) => Some(Solar::Modelled(SolarArray {
+                kwp,
+                tilt: cli.solar_tilt,
+                azimuth: cli.solar_azimuth,
+            })),
+            (None, None) => None,
+        },
+        usage_growth: cli
+            .usage_growth
+            .as_deref()
+            .map(UsageGrowth::load)
+            .transpose()?,
+        baseload: cli.baseload,
+        peaks: cli.peaks,
+        expensive_days: cli.expensive_days,
+        expensive_days_plan: cli.expensive_days_plan.clone(),
+        free_time_choices: cli.free_time_choices,
+        monthly_winners: cli.monthly_winners || is_report,
+        rate_sensitivity: cli
+            .sensitivity
+            .map(|percent| percent / Decimal::ONE_HUNDRED),
+        scenarios: cli.scenario.clone(),
+        level_pay: cli
+            .level_pay
+            .then(|| cli.level_pay_buffer / Decimal::ONE_HUNDRED),
+        contract: cli.contract_end.map(|end| Contract {
+            end,
+            exit_fee: cli.exit_fee,
+            switch_on: cli
+                .switch_on
+                .unwrap_or_else(|| chrono::Local::now().date_naive()),
+        }),
+        ev: cli.ev.then_some(EvDetection {
+            threshold_kw: cli.ev_kw,
+            min_minutes: cli.ev_minutes,
+        }),
+        invoices: cli.invoices.then_some(InvoiceCycle {
+            anchor: cli.invoice_anchor,
+            months: cli.invoice_months,
+        }),
+        circuits: cli
+            .circuit
+            .iter()
+            .map(|(name, file)| Circuit::load(name, file))
+            .collect::<Result<_>>()?,
+        battery: cli.battery_kwh.map(|capacity_kwh| Battery {
+            capacity_kwh,
+            power_kw: cli.battery_kw,
+            round_trip_efficiency: cli.battery_efficiency / 100.0,
+            charge: cli.battery_charge,
+            discharge: cli.battery_discharge,
+            cost: cli.battery_cost,
+        }),
+    })
+}
+
+// Merges the plans of a source into the others, telling about the ones that were also there
+// under another name. A plan of the same name is overridden without a word, that is what the
+// later sources are for
+fn add_plans(
+    plans: &mut Vec<Box<dyn PricePlanStrategy>>,
+    sources: &mut HashMap<String, PlanSource>,
+    overrides: Vec<Box<dyn PricePlanStrategy>>,
+    source: PlanSource,
+) {
+    let names: Vec<String> = overrides.iter().map(|plan| plan.name()).collect();
+    let duplicates = merge_plans(plans, overrides);
+    for duplicate in &duplicates {
+        let left_out_source = match duplicate.replaced {
+            true => sources
+                .get(&duplicate.left_out.to_lowercase())
+                .copied()
+                .unwrap_or(source),
+            false => source,
+        };
+        if duplicate.replaced && duplicate.left_out.eq_ignore_ascii_case(&duplicate.kept) {
+            continue;
+        }
+        eprintln!(
+            "warning: left out {} of {left_out_source}, it is the same plan as {} of {source}",
+            duplicate.left_out, duplicate.kept
+        );
+    }
+    for name in names {
+        if !duplicates.iter().any(|d| !d.replaced && d.left_out == name) {
+            sources.insert(name.to_lowercase(), source);
+        }
+    }
+}
+
+// The plans with their usage looked up in the cache. The built in rates change with the
+// program, the others with the files they were read from, and the holidays change the bands of
+// every plan. The filters move the plans around, so each goes by where it came from
+fn with_usage_cache(
+    plans: Vec<Box<dyn PricePlanStrategy>>,
+    sources: &HashMap<String, PlanSource>,
+    cli: &CompareArgs,
+    cache: &SharedUsageCache,
+) -> Vec<Box<dyn PricePlanStrategy>> {
+    let contents = |file: &Option<PathBuf>| {
+        file.as_ref()
+            .and_then(|file| std::fs::read_to_string(file).ok())
+            .unwrap_or_default()
+    };
+    let builtin_version = format!("{}{}", env!("CARGO_PKG_VERSION"), contents(&cli.holidays));
+    let registry = (!cli.no_registry).then(|| PathBuf::from(PLAN_REGISTRY));
+    let tariff_version = format!(
+        "{builtin_version}{}{}{:?}",
+        contents(&registry),
+        contents(&cli.tariffs),
+        cli.custom_plan
+    );
+    plans
+        .into_iter()
+        .map(|plan| {
+            let version = match sources.get(&plan.name().to_lowercase()) {
+                Some(PlanSource::BuiltIn) => &builtin_version,
+                _ => &tariff_version,
+            };
+            CachedPlan::wrap(plan, version, cache)
+        })
+        .collect()
+}
+
+// What the plans warned about pricing the readings, once for each plan and meter point
+fn print_warnings(reports: &[ComparisonReport]) {
+    for report in reports {
+        for warning in report
+            .plans
+            .iter()
+            .filter_map(|result| result.warning.as_ref())
+        {
+            eprintln!("warning: {warning}");
+        }
+    }
+}
+
+fn label_sources(reports: &mut [ComparisonReport], sources: &HashMap<String, PlanSource>) {
+    for report in reports {
+        for result in report
+            .plans
+            .iter_mut()
+            .chain(&mut report.estimated_annual_bills)
+        {
+            result.source = sources.get(&result.name.to_lowercase()).copied();
+        }
+    }
+}
+
+// The conversion of gas readings in m3, the average Irish gas unless the bill says otherwise
+fn gas_conversion(cli: &CompareArgs) -> GasConversion {
+    let default = GasConversion::default();
+    GasConversion {
+        calorific_value: cli.calorific_value.unwrap_or(default.calorific_value),
+        correction_factor: cli.correction_factor.unwrap_or(default.correction_factor),
+    }
+}
+
+// A ranking on part of the period looks like one on all of it, so it needs --allow-partial
+fn check_coverage(cli: &CompareArgs, reports: &[ComparisonReport]) -> Result<()> {
+    // usage totals are estimated on purpose, there are no readings to miss
+    if cli.annual_kwh.is_some() || cli.billed_kwh.is_some() {
+        return Ok(());
+    }
+    for report in reports {
+        let Some(coverage) = report.coverage else {
+            continue;
+        };
+        let share = coverage.actual_share() * 100.0;
+        if share >= cli.min_coverage {
+            continue;
+        }
+        let message = format!(
+            "only {share:.1}% of the intervals of meter point {} from {} to {} have actual readings, under the {}% of --min-coverage",
+            report.mprn, report.period.start, report.period.end, cli.min_coverage
+        );
+        if !cli.allow_partial {
+            bail!("{message}, rank the plans anyway with --allow-partial");
+        }
+        eprintln!(
+            "warning: {message}, the ranking leaves out whatever the other {} intervals would have cost",
+            coverage.expected_intervals - coverage.actual_intervals
+        );
+    }
+    Ok(())
+}
+
+// Only the json output carries the snapshot, hashing the inputs isn't free
+fn add_snapshot(
+    global: &GlobalArgs,
+    cli: &CompareArgs,
+    reports: &mut [ComparisonReport],
+) -> Result<()> {
+    let json = match &cli.report_to {
+        Some(_) => matches!(report_format(global)?, OutputFormat::Json),
+        None => matches!(global.format(), OutputFormat::Json),
+    };
+    if json {
+        let snapshot = input_snapshot(global, cli)?;
+        for report in reports {
+            report.snapshot = Some(snapshot.clone());
+        }
+    }
+    Ok(())
+}
+
+// The manual readings go on the meter point of the smart readings and stop at the first of them
+fn add_manual_reads(
+    mut data: Vec<SmartMeterData>,
+    reads: &[ManualReading],
+) -> Result<(Vec<SmartMeterData>, Vec<EstimatedPeriod>)> {
+    let Some(first) = data.iter().min_by_key(|d| d.read_data_and_end_time) else {
+        return Ok((data, vec![]));
+    };
+    if data.iter().any(|d| d.mprn != first.mprn) {
+        bail!("--manual-reads are of one meter point, give it the readings of one");
+    }
+    let (estimated, periods) = estimate_from_manual_reads(
+        reads,
+        Arc::clone(&first.mprn),
+        Arc::clone(&first.meter_serial_number),
+        Some(first.read_data_and_end_time),
+    );
+    if periods.is_empty() {
+        eprintln!("warning: the manual readings are all after the first smart reading");
+    }
+    data.extend(estimated);
+    Ok((data, periods))
+}
+
+fn print_reports(
+    global: &GlobalArgs,
+    cli: &CompareArgs,
+    reports: &[ComparisonReport],
+) -> Result<()> {
+    if let Some(output) = &cli.output {
+        write_results_csv(reports, File::create(output)?)?;
+    }
+    if let Some(report_to) = &cli.report_to {
+        let report = match (report_format(global)?, reports) {
+            (OutputFormat::Markdown, reports) => render_report(reports, ReportFormat::Markdown),
+            (OutputFormat::Json, [report]) => serde_json::to_string_pretty(report)? + "\n",
+            (OutputFormat::Json, reports) => serde_json::to_string_pretty(reports)? + "\n",
+            (_, reports) => render_report(reports, ReportFormat::Html),
+        };
+        return replace_file(report_to, report.as_bytes());
+    }
+    match global.format() {
+        OutputFormat::Text => {
+            for (index, report) in reports.iter().enumerate() {
+                if index > 0 {
+                    println!();
+                }
+                print_text(report, cli.decimals, cli.plan_details);
+            }
+        }
+        // a single meter keeps printing a single object, several meters print an array
+        OutputFormat::Json => match reports {
+            [report] => println!("{}", serde_json::to_string_pretty(report)?),
+            reports => println!("{}", serde_json::to_string_pretty(reports)?),
+        },
+        OutputFormat::Html => print!("{}", render_report(reports, ReportFormat::Html)),
+        OutputFormat::Markdown => print!("{}", render_report(reports, ReportFormat::Markdown)),
+        OutputFormat::Csv | OutputFormat::Parquet => {
+            bail!("csv and parquet are for export, --output writes the comparison as a csv")
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cli;
+    use clap::Parser;
+    use energy_comparator::parse_smart_meter_csv;
+
+    #[test]
+    fn a_cached_tariff_plan_is_priced_again_when_its_rate_changes() {
+        let dir = std::env::temp_dir().join(format!("cached-tariff-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tariffs = dir.join("tariffs.toml");
+        let cli = Cli::try_parse_from([
+            "compare",
+            "HDF.csv",
+            "--no-registry",
+            "--tariffs",
+            &tariffs.display().to_string(),
+        ])
+        .unwrap()
+        .compare;
+        let data = parse_smart_meter_csv(
+            "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+10308375697,34996871,2.0,Active Import Interval (kW),08-01-2024 03:30
+"
+            .as_bytes(),
+        )
+        .unwrap();
+        let mut sources: HashMap<String, PlanSource> = builtin_plans()
+            .iter()
+            .map(|plan| (plan.name().to_lowercase(), PlanSource::BuiltIn))
+            .collect();
+        sources.insert("mine".to_string(), PlanSource::TariffFile);
+        let cache = UsageCache::default().shared();
+        // the plans left after --plan Mine, the tariff plan is the first of them
+        let usage_at = |rate: &str| {
+            std::fs::write(
+                &tariffs,
+                format!(
+                    "[[plan]]\nname = \"Mine\"\nstanding_charge = {{ per_day = 0.5 }}\n\n\
+                     [[plan.bands]]\nname = \"all day\"\nstart = \"00:00\"\nend = \"00:00\"\nrate = {rate}\n"
+                ),
+            )
+            .unwrap();
+            let plans = tariff_plans(&mut load_plan_file(&tariffs).unwrap());
+            let uncached = plans[0].compute_total_bill_for_period(&data);
+            let cached = with_usage_cache(plans, &sources, &cli, &cache);
+            (cached[0].compute_total_bill_for_period(&data), uncached)
+        };
+        let (before, _) = usage_at("0.30");
+        let (after, uncached) = usage_at("0.60");
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_ne!(after, before);
+        assert_eq!(after, uncached);
+    }
+}
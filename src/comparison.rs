@@ -0,0 +1,800 @@
+use anyhow::{bail, Result};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use crate::{
+    annual_estimate, attribute_circuits, baseload, breakdown_by_band, breakdown_by_bucket,
+    coverage, effective_rate, export_earnings, group_by_mprn, level_pay, monthly_winners,
+    most_expensive_days, peak_cost, peak_demand, project_plan, rate_sensitivity, shift_load,
+    simulate_invoices, BandBreakdown, Baseload, Battery, BatterySaving, BillBreakdown,
+    BillingPeriod, BucketBreakdown, CarbonFootprint, Circuit, CircuitCosts, Contract, Coverage,
+    Currency, DayCost, DualFuelComparison, EffectiveRate, EnergyBillEntry, EstimatedPeriod,
+    EvCharging, EvCost, EvDetection, ExportEarnings, FreeTimeChoice, GapFilling, GasConversion,
+    GasMeterData, GasPlanStrategy, Granularity, GrowthSummary, InputSnapshot, Invoice,
+    InvoiceCycle, LevelPay, LoadShift, Money, MonthlyWinners, PeakCost, PeakDemand, PlanMetadata,
+    PlanSource, PricePlanStrategy, RateSensitivity, Scenario, ScenarioProjection, ShiftedCost,
+    SmartMeterData, Solar, SolarSummary, SubMetering, SwitchAdvice, UsageGrowth, YearProjection,
+};
+
+// How a single plan did over the period
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanComparison {
+    pub name: String,
+    pub breakdown: BillBreakdown,
+    // who sells the plan and on what terms, left out when the plan says nothing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<PlanMetadata>,
+    // where the plan was defined, set by the caller that loaded the plans
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<PlanSource>,
+    // the total once the sign up bonus is taken off, only set for plans with a bonus
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub with_bonus: Option<EnergyBillEntry>,
+    // only set when a current plan was given, and left out for the current plan itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saving: Option<Saving>,
+    pub current: bool,
+    // what the plan warned about pricing the readings, see PricePlanStrategy::pricing_warning
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bands: Vec<BandBreakdown>,
+    // what the exports earn, see ComparisonOptions::exports
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exports: Option<ExportEarnings>,
+    // the average cent per kWh imported, see ComparisonOptions::effective_rates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_rate: Option<EffectiveRate>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub buckets: Vec<BucketBreakdown>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub projection: Vec<YearProjection>,
+    // a whole year estimated from the period, see annual_estimate, the breakdown above is
+    // what the readings actually cost
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annual_estimate: Option<BillBreakdown>,
+    // the cost with the load shifted, see ComparisonOptions::load_shift
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_shifted: Option<ShiftedCost>,
+    // the cost with a home battery, see ComparisonOptions::battery
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub with_battery: Option<BatterySaving>,
+    // the cost with solar panels, see ComparisonOptions::solar
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub with_solar: Option<ShiftedCost>,
+    // the cost with the usage grown, see ComparisonOptions::usage_growth
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub with_growth: Option<ShiftedCost>,
+    // what the car costs apart from the house, see ComparisonOptions::ev
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ev_cost: Option<EvCost>,
+    // what each circuit costs apart from the rest of the house, see ComparisonOptions::circuits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_costs: Option<CircuitCosts>,
+    // the period billed invoice by invoice, see ComparisonOptions::invoices
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub invoices: Vec<Invoice>,
+    // what a year of the baseload costs in usage, see ComparisonOptions::baseload
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseload_cost: Option<EnergyBillEntry>,
+    // what the imports of the peak cost on the plan, see ComparisonOptions::peaks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_cost: Option<PeakCost>,
+    // the days the plan charged the most for, see ComparisonOptions::expensive_days
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expensive_days: Vec<DayCost>,
+    // the plan with each free window it lets the customer pick, cheapest first, see
+    // ComparisonOptions::free_time_choices
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub free_time_choices: Vec<FreeTimeChoice>,
+    // whether to switch to the plan before the contract of the current plan ends, see
+    // ComparisonOptions::contract
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub switch_advice: Option<SwitchAdvice>,
+    // the monthly direct debit for a year of the plan, see ComparisonOptions::level_pay
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level_pay: Option<LevelPay>,
+    // the cost of each year under each of ComparisonOptions::scenarios
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scenarios: Vec<ScenarioProjection>,
+}
+
+// What switching from the baseline to a plan would save, negative when the plan costs more
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Saving {
+    pub amount: Money,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub percentage: Decimal,
+}
+
+// What else to work out on top of the totals
+#[derive(Debug, Clone, Default)]
+pub struct ComparisonOptions {
+    pub current_plan: Option<String>,
+    pub bands: bool,
+    // work out what a kWh cost on average on every plan
+    pub effective_rates: bool,
+    // work out what the exports earn on every plan, month by month
+    pub exports: bool,
+    pub granularity: Option<Granularity>,
+    // project the cost over this many years of contract
+    pub years: Option<u32>,
+    pub annualize: bool,
+    // price every plan again with this much of the usage moved to another time of the day
+    pub load_shift: Option<LoadShift>,
+    // price every plan again with this battery charging and discharging
+    pub battery: Option<Battery>,
+    // price every plan again with the generation of solar panels taken off the imports
+    pub solar: Option<Solar>,
+    // price every plan again with more usage on top of the readings, like a heat pump or a car
+    pub usage_growth: Option<UsageGrowth>,
+    // find the charging of an electric car in the readings and price it apart
+    pub ev: Option<EvDetection>,
+    // split the imports between these circuits of the house and price each apart
+    pub circuits: Vec<Circuit>,
+    // price every plan invoice by invoice like the supplier would bill it
+    pub invoices: Option<InvoiceCycle>,
+    // work out the standby power of the house and what a year of it costs on each plan
+    pub baseload: bool,
+    // list this many of the intervals with the highest demand and price the peak of each plan
+    pub peaks: Option<usize>,
+    // list this many of the days each plan charged the most for, or only the plan named by
+    // expensive_days_plan
+    pub expensive_days: Option<usize>,
+    pub expensive_days_plan: Option<String>,
+    // price the free time plans with every free window they offer
+    pub free_time_choices: bool,
+    // the contract of the current plan, to tell whether paying its exit fee to switch now is
+    // cheaper than waiting for its end
+    pub contract: Option<Contract>,
+    // project the plans over the years again with prices rising or falling this way
+    pub scenarios: Vec<Scenario>,
+    // work out a level pay direct debit for every plan with this buffer on top, 0.1 for 10%
+    pub level_pay: Option<Decimal>,
+    // find the cheapest plan of every month and how often it wasn't the cheapest overall
+    pub monthly_winners: bool,
+    // move the unit rates and standing charge of each plan by this fraction either way and
+    // see what it does to the ranking
+    pub rate_sensitivity: Option<Decimal>,
+}
+
+// Everything a comparison found, ready to be printed in any of the output formats
+#[derive(Debug, Clone, Serialize)]
+pub struct ComparisonReport {
+    pub mprn: String,
+    pub period: BillingPeriod,
+    pub plans: Vec<PlanComparison>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub gas_plans: Vec<PlanComparison>,
+    // bundles of both fuels, only when gas is compared too
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dual_fuel: Vec<DualFuelComparison>,
+    // the plans on the CRU standard usage for a year from the start of the period, see
+    // estimated_annual_bills
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub estimated_annual_bills: Vec<PlanComparison>,
+    // the emissions of the imports, the same on every plan, only with --carbon
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub carbon: Option<CarbonFootprint>,
+    // the shift the plans were priced again with, see PlanComparison::load_shifted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_shift: Option<LoadShift>,
+    // the battery the plans were priced again with, see PlanComparison::with_battery
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery: Option<Battery>,
+    // what the panels made and where it went, with ComparisonOptions::solar
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub solar: Option<SolarSummary>,
+    // the imports with and without ComparisonOptions::usage_growth
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_growth: Option<GrowthSummary>,
+    // the charging sessions found, with ComparisonOptions::ev
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ev_charging: Option<EvCharging>,
+    // the imports of each circuit, with ComparisonOptions::circuits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub_metering: Option<SubMetering>,
+    // with ComparisonOptions::baseload
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseload: Option<Baseload>,
+    // with ComparisonOptions::peaks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_demand: Option<PeakDemand>,
+    // with ComparisonOptions::contract
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract: Option<Contract>,
+    // with ComparisonOptions::scenarios
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scenarios: Vec<Scenario>,
+    // the cheapest plan of each month, with ComparisonOptions::monthly_winners
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monthly_winners: Option<MonthlyWinners>,
+    // how the ranking holds up with the rates of each plan moved, with
+    // ComparisonOptions::rate_sensitivity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_sensitivity: Option<RateSensitivity>,
+    // the days made up from manual readings before the smart meter or from usage totals, see
+    // estimate_from_manual_reads and annual_usage_readings
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub estimated: Vec<EstimatedPeriod>,
+    // how much of the period the imports were actually read for, none when streamed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<Coverage>,
+    // what the comparison was made from, set by the caller for the json output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<InputSnapshot>,
+}
+
+// Runs f over the items spread over every core, the results keep the order of the items
+pub(crate) fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    // a single core, or a browser that can't start threads at all
+    if threads == 1 {
+        return items.iter().map(f).collect();
+    }
+    let chunk_size = items.len().div_ceil(threads).max(1);
+    let f = &f;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
+// Prices every plan over the period, cheapest first. Each plan goes through all the readings,
+// so the plans are priced in parallel
+pub fn rank_plans(
+    plans: &[Box<dyn PricePlanStrategy>],
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+) -> Vec<PlanComparison> {
+    let results = parallel_map(plans, |plan| {
+        let mut result =
+            PlanComparison::new(plan.name(), plan.compute_bill_breakdown(datapoints, period));
+        result.metadata = Some(plan.metadata()).filter(|m| !m.is_empty());
+        result
+    });
+    sort_by_total(results)
+}
+
+pub fn rank_gas_plans(
+    plans: &[Box<dyn GasPlanStrategy>],
+    readings: &[GasMeterData],
+    conversion: &GasConversion,
+    period: &BillingPeriod,
+) -> Vec<PlanComparison> {
+    let results = plans
+        .iter()
+        .map(|plan| {
+            PlanComparison::new(
+                format!("{plan:?}"),
+                plan.compute_gas_bill_breakdown(readings, conversion, period),
+            )
+        })
+        .collect();
+    sort_by_total(results)
+}
+
+// A plan that couldn't price the readings would rank as the cheapest at zero
+pub(crate) fn ensure_priced(plans: &[Box<dyn PricePlanStrategy>]) -> Result<()> {
+    for plan in plans {
+        if let Some(error) = plan.pricing_error() {
+            bail!("{} could not be priced: {error}", plan.name());
+        }
+    }
+    Ok(())
+}
+
+// Plans of different countries can't be ranked against each other, their totals are in
+// different money
+pub(crate) fn ensure_one_currency(plans: &[&dyn PricePlanStrategy]) -> Result<()> {
+    let currencies: BTreeSet<Currency> = plans.iter().map(|plan| plan.currency()).collect();
+    if currencies.len() > 1 {
+        let names: Vec<String> = currencies.iter().map(Currency::to_string).collect();
+        bail!(
+            "the plans are priced in {}, compare the plans of one currency at a time",
+            names.join(" and ")
+        );
+    }
+    Ok(())
+}
+
+// Ranks the plans and fills in whatever else the options ask for
+pub fn compare_plans(
+    plans: &[Box<dyn PricePlanStrategy>],
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+    options: &ComparisonOptions,
+) -> Result<Vec<PlanComparison>> {
+    ensure_one_currency(&plans.iter().map(|plan| plan.as_ref()).collect::<Vec<_>>())?;
+    let mut results = rank_plans(plans, datapoints, period);
+    ensure_priced(plans)?;
+    if let Some(current) = &options.current_plan {
+        mark_current_plan(&mut results, current)?;
+    }
+
+    if let Some(name) = &options.expensive_days_plan {
+        if !plans.iter().any(|p| p.name().eq_ignore_ascii_case(name)) {
+            bail!("the plan {name} is not one of the compared plans");
+        }
+    }
+    let shifted = options
+        .load_shift
+        .map(|shift| shift_load(datapoints, &shift));
+    let with_battery = options
+        .battery
+        .map(|battery| (battery, battery.simulate(datapoints)));
+    let with_solar = options
+        .solar
+        .as_ref()
+        .map(|solar| solar.apply(datapoints).0);
+    let with_growth = options
+        .usage_growth
+        .as_ref()
+        .map(|growth| growth.apply(datapoints).0);
+    let ev_charging = options.ev.map(|ev| ev.detect(datapoints));
+    let sub_metering =
+        (!options.circuits.is_empty()).then(|| attribute_circuits(&options.circuits, datapoints));
+    let year_of_baseload = options
+        .baseload
+        .then(|| baseload(datapoints))
+        .flatten()
+        .map(|baseload| baseload.year_of_readings(period.start));
+    let year = BillingPeriod::starting_at(period.start, 365);
+    // the plans are weighed against a year of the current one
+    let current_year = match (&options.contract, &options.current_plan) {
+        (Some(_), Some(current)) => plans
+            .iter()
+            .find(|p| p.name().eq_ignore_ascii_case(current))
+            .map(|plan| annual_estimate(plan.as_ref(), datapoints, period)),
+        (Some(_), None) => bail!("the contract end needs the current plan"),
+        (None, _) => None,
+    };
+    let results = parallel_map(&results, |result| {
+        let mut result = result.clone();
+        let Some(plan) = plans.iter().find(|p| p.name() == result.name) else {
+            return result;
+        };
+        let bonus = plan.sign_up_bonus();
+        if bonus.amount() > Decimal::ZERO {
+            result.with_bonus = Some(result.breakdown.total + EnergyBillEntry::Credit(bonus));
+        }
+        result.warning = plan.pricing_warning(datapoints);
+        if options.exports {
+            result.exports = export_earnings(plan.as_ref(), datapoints);
+        }
+        if options.effective_rates {
+            result.effective_rate = effective_rate(plan.as_ref(), datapoints, &result.breakdown);
+        }
+        if options.bands {
+            result.bands = breakdown_by_band(plan.as_ref(), datapoints);
+        }
+        if let Some(granularity) = options.granularity {
+            result.buckets = breakdown_by_bucket(plan.as_ref(), datapoints, period, granularity);
+        }
+        if options.annualize {
+            result.annual_estimate = Some(annual_estimate(plan.as_ref(), datapoints, period));
+        }
+        if let Some(shifted) = &shifted {
+            let breakdown = plan.compute_bill_breakdown(shifted, period);
+            result.load_shifted = Some(ShiftedCost {
+                breakdown,
+                saving: saving_against(&result.breakdown, &breakdown),
+            });
+        }
+        if let Some((battery, simulated)) = &with_battery {
+            let breakdown = plan.compute_bill_breakdown(simulated, period);
+            result.with_battery = Some(battery.saving(&result.breakdown, breakdown, period));
+        }
+        if let Some(with_solar) = &with_solar {
+            let breakdown = plan.compute_bill_breakdown(with_solar, period);
+            result.with_solar = Some(ShiftedCost {
+                breakdown,
+                saving: saving_against(&result.breakdown, &breakdown),
+            });
+        }
+        if let Some(with_growth) = &with_growth {
+            let breakdown = plan.compute_bill_breakdown(with_growth, period);
+            result.with_growth = Some(ShiftedCost {
+                breakdown,
+                saving: saving_against(&result.breakdown, &breakdown),
+            });
+        }
+        if let Some(ev_charging) = &ev_charging {
+            result.ev_cost =
+                Some(ev_charging.cost(plan.as_ref(), datapoints, result.breakdown.total));
+        }
+        if let Some(cycle) = &options.invoices {
+            result.invoices = simulate_invoices(plan.as_ref(), datapoints, period, cycle);
+        }
+        if let Some(sub_metering) = &sub_metering {
+            result.circuit_costs =
+                Some(sub_metering.cost(plan.as_ref(), datapoints, result.breakdown.total));
+        }
+        if let Some(readings) = &year_of_baseload {
+            result.baseload_cost = Some(plan.compute_bill_breakdown(readings, &year).usage);
+        }
+        if options.peaks.is_some() {
+            result.peak_cost = Some(peak_cost(plan.as_ref(), datapoints));
+        }
+        if let Some(count) = options.expensive_days {
+            if options
+                .expensive_days_plan
+                .as_ref()
+                .is_none_or(|name| name.eq_ignore_ascii_case(&result.name))
+            {
+                result.expensive_days = most_expensive_days(plan.as_ref(), datapoints, count);
+            }
+        }
+        if options.free_time_choices {
+            let chosen = plan.free_time().map(|free_time| free_time.chosen);
+            let mut choices: Vec<FreeTimeChoice> = plan
+                .free_time_choices()
+                .into_iter()
+                .map(|(window, choice)| FreeTimeChoice {
+                    chosen: chosen.as_ref() == Some(&window),
+                    window: window.to_string(),
+                    breakdown: choice.compute_bill_breakdown(datapoints, period),
+                })
+                .collect();
+            choices.sort_by_key(|choice| choice.breakdown.total);
+            result.free_time_choices = choices;
+        }
+        if let (Some(contract), Some(current_year)) = (&options.contract, &current_year) {
+            if !result.current {
+                let plan_year = result
+                    .annual_estimate
+                    .unwrap_or_else(|| annual_estimate(plan.as_ref(), datapoints, period));
+                result.switch_advice = Some(contract.advice(current_year, &plan_year));
+            }
+        }
+        if let Some(buffer) = options.level_pay {
+            result.level_pay = Some(level_pay(plan.as_ref(), datapoints, period, buffer));
+        }
+        if let Some(years) = options.years {
+            result.projection = project_plan(plan.as_ref(), datapoints, period, years);
+            result.scenarios = options
+                .scenarios
+                .iter()
+                .map(|scenario| scenario.project(plan.as_ref(), datapoints, period, years))
+                .collect();
+        }
+        result
+    });
+    // the other breakdowns price the readings again
+    ensure_priced(plans)?;
+    Ok(results)
+}
+
+// Compares the plans on every meter point of the readings, each over its own billing period
+pub fn compare_meter_points(
+    plans: &[Box<dyn PricePlanStrategy>],
+    datapoints: Vec<SmartMeterData>,
+    options: &ComparisonOptions,
+) -> Result<Vec<ComparisonReport>> {
+    let mut reports = vec![];
+    for (mprn, data) in group_by_mprn(datapoints) {
+        let Some(period) = BillingPeriod::from_data(&data) else {
+            continue;
+        };
+        let results = compare_plans(plans, &data, &period, options)?;
+        reports.push(ComparisonReport {
+            monthly_winners: options
+                .monthly_winners
+                .then(|| monthly_winners(plans, &data, &period, &results))
+                .flatten(),
+            rate_sensitivity: options
+                .rate_sensitivity
+                .map(|change| rate_sensitivity(plans, &data, &period, &results, change)),
+            estimated: vec![],
+            coverage: Some(coverage(&data, &period, &GapFilling::default(), &[])),
+            snapshot: None,
+            plans: results,
+            mprn: mprn.to_string(),
+            period,
+            gas_plans: vec![],
+            dual_fuel: vec![],
+            estimated_annual_bills: vec![],
+            carbon: None,
+            load_shift: options.load_shift,
+            battery: options.battery,
+            solar: options.solar.as_ref().map(|solar| solar.apply(&data).1),
+            usage_growth: options
+                .usage_growth
+                .as_ref()
+                .map(|growth| growth.apply(&data).1),
+            ev_charging: options.ev.map(|ev| ev.detect(&data)),
+            sub_metering: (!options.circuits.is_empty())
+                .then(|| attribute_circuits(&options.circuits, &data)),
+            baseload: options.baseload.then(|| baseload(&data)).flatten(),
+            peak_demand: options.peaks.map(|count| peak_demand(&data, count)),
+            contract: options.contract,
+            scenarios: options.scenarios.clone(),
+        });
+    }
+    Ok(reports)
+}
+
+// Flags the current plan and works out what switching to each of the others saves
+pub(crate) fn mark_current_plan(results: &mut [PlanComparison], current: &str) -> Result<()> {
+    let Some(baseline) = results
+        .iter()
+        .find(|r| r.name.eq_ignore_ascii_case(current))
+        .map(|r| r.breakdown)
+    else {
+        bail!("the current plan {current} is not one of the compared plans");
+    };
+    for result in results {
+        if result.name.eq_ignore_ascii_case(current) {
+            result.current = true;
+        } else {
+            result.saving = Some(saving_against(&baseline, &result.breakdown));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn sort_by_total(mut results: Vec<PlanComparison>) -> Vec<PlanComparison> {
+    results.sort_by_key(|result| result.breakdown.total);
+    results
+}
+
+impl PlanComparison {
+    pub(crate) fn new(name: String, breakdown: BillBreakdown) -> PlanComparison {
+        PlanComparison {
+            name,
+            breakdown,
+            source: None,
+            metadata: None,
+            with_bonus: None,
+            saving: None,
+            current: false,
+            warning: None,
+            bands: vec![],
+            effective_rate: None,
+            exports: None,
+            buckets: vec![],
+            projection: vec![],
+            annual_estimate: None,
+            load_shifted: None,
+            with_battery: None,
+            with_solar: None,
+            with_growth: None,
+            ev_cost: None,
+            circuit_costs: None,
+            invoices: vec![],
+            baseload_cost: None,
+            peak_cost: None,
+            expensive_days: vec![],
+            free_time_choices: vec![],
+            switch_advice: None,
+            level_pay: None,
+            scenarios: vec![],
+        }
+    }
+}
+
+pub fn saving_against(baseline: &BillBreakdown, other: &BillBreakdown) -> Saving {
+    let baseline_total = baseline.total.signed_amount();
+    let amount = baseline_total - other.total.signed_amount();
+    let percentage = if baseline_total.is_zero() {
+        Decimal::ZERO
+    } else {
+        (amount / baseline_total.abs() * Decimal::ONE_HUNDRED).round_dp(2)
+    };
+    Saving {
+        amount: Money::of(amount, baseline.total.currency()),
+        percentage,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builtin_plans, meter_data::reading_at};
+
+    #[test]
+    fn plans_are_ranked_cheapest_first_with_the_saving_over_the_current_one() {
+        let data = vec![
+            reading_at("08-01-2024 18:00", 1.5),
+            reading_at("08-01-2024 03:30", 0.8),
+        ];
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let mut results = rank_plans(&builtin_plans(), &data, &period);
+        assert!(results
+            .windows(2)
+            .all(|pair| pair[0].breakdown.total <= pair[1].breakdown.total));
+
+        let current = results.last().unwrap().name.clone();
+        mark_current_plan(&mut results, &current).unwrap();
+        let (last, others) = results.split_last().unwrap();
+        assert!(last.current && last.saving.is_none());
+        let cheapest = others[0].saving.unwrap();
+        let difference =
+            last.breakdown.total.signed_amount() - others[0].breakdown.total.signed_amount();
+        assert_eq!(cheapest.amount.amount(), difference);
+        assert!(cheapest.percentage > Decimal::ZERO);
+        assert!(mark_current_plan(&mut results, "no such plan").is_err());
+    }
+
+    #[test]
+    fn the_report_serializes_to_the_json_of_format_json() {
+        let data = vec![reading_at("08-01-2024 18:00", 1.5)];
+        let options = ComparisonOptions {
+            current_plan: Some("SSEAirtricity20".to_string()),
+            bands: true,
+            granularity: Some(Granularity::Month),
+            ..ComparisonOptions::default()
+        };
+        let reports = compare_meter_points(&builtin_plans()[..2], data, &options).unwrap();
+        let json = serde_json::to_value(&reports).unwrap();
+        let report = &json[0];
+        assert_eq!(report["mprn"], "10000000000");
+        assert_eq!(report["period"]["start"], "2024-01-08");
+        let (current, other) = (&report["plans"][0], &report["plans"][1]);
+        assert_eq!(current["name"], "SSEAirtricity20");
+        assert_eq!(current["current"], true);
+        assert!(current.get("saving").is_none());
+        assert_eq!(
+            current["breakdown"]["total"],
+            serde_json::json!({ "type": "debit", "amount": 0.95 })
+        );
+        assert_eq!(current["bands"][0]["band"], "peak");
+        assert_eq!(current["buckets"][0]["bucket"], "2024-01");
+        assert_eq!(
+            other["saving"],
+            serde_json::json!({ "amount": -0.04, "percentage": -4.21 })
+        );
+        // what wasn't asked for is left out
+        assert!(report.get("gas_plans").is_none());
+    }
+
+    #[test]
+    fn every_meter_point_gets_a_comparison_of_its_own() {
+        let mut second_home = reading_at("10-01-2024 18:00", 3.0);
+        second_home.mprn = "10308375697".into();
+        let data = vec![
+            second_home.clone(),
+            reading_at("08-01-2024 18:00", 1.5),
+            reading_at("09-01-2024 18:00", 1.5),
+        ];
+        let reports =
+            compare_meter_points(&builtin_plans(), data, &ComparisonOptions::default()).unwrap();
+        let meters: Vec<(&str, u32)> = reports
+            .iter()
+            .map(|r| (r.mprn.as_str(), r.period.number_of_days()))
+            .collect();
+        assert_eq!(meters, vec![("10000000000", 2), ("10308375697", 1)]);
+        let alone = compare_meter_points(
+            &builtin_plans(),
+            vec![second_home],
+            &ComparisonOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            reports[1].plans[0].breakdown.total,
+            alone[0].plans[0].breakdown.total
+        );
+    }
+
+    #[test]
+    fn the_sign_up_bonus_only_comes_off_the_first_bill() {
+        let flat = |bonus: &str| -> Box<dyn PricePlanStrategy> {
+            let plan: crate::ConfigurablePlan = toml::from_str(&format!(
+                "name = \"flat {bonus}\"\nstanding_charge = {{ per_day = 0.5 }}\nsign_up_bonus = {bonus}\n\
+                 [[bands]]\nname = \"all\"\nstart = \"00:00\"\nend = \"00:00\"\nrate = 0.30"
+            ))
+            .unwrap();
+            Box::new(plan)
+        };
+        let plans = vec![flat("0"), flat("50")];
+        let data = vec![reading_at("08-01-2024 18:00", 2.0)];
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let results = compare_plans(&plans, &data, &period, &ComparisonOptions::default()).unwrap();
+        let with_bonus = results.iter().find(|r| r.name == "flat 50").unwrap();
+        let without = results.iter().find(|r| r.name == "flat 0").unwrap();
+        // the bonus doesn't change the ranking, only the first bill
+        assert_eq!(with_bonus.breakdown.total, without.breakdown.total);
+        assert!(without.with_bonus.is_none());
+        assert_eq!(
+            with_bonus.with_bonus.unwrap().signed_amount(),
+            with_bonus.breakdown.total.signed_amount() - Decimal::from(50)
+        );
+
+        let years = crate::project_plan(plans[1].as_ref(), &data, &period, 2);
+        let first_year = years[0].total.signed_amount();
+        assert_eq!(
+            first_year,
+            years[1].total.signed_amount() - Decimal::from(50)
+        );
+    }
+
+    #[test]
+    fn parallel_results_keep_the_order_of_the_items() {
+        let items: Vec<u32> = (0..1000).collect();
+        let doubled = parallel_map(&items, |item| item * 2);
+        assert_eq!(
+            doubled,
+            items.iter().map(|item| item * 2).collect::<Vec<_>>()
+        );
+        assert!(parallel_map(&[] as &[u32], |item| *item).is_empty());
+
+        // the plans come out the same as priced one after the other
+        let data = vec![reading_at("08-01-2024 18:00", 1.5)];
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let plans = builtin_plans();
+        let totals: Vec<EnergyBillEntry> = parallel_map(&plans, |plan| {
+            plan.compute_bill_breakdown(&data, &period).total
+        });
+        for (plan, total) in plans.iter().zip(totals) {
+            assert_eq!(plan.compute_bill_breakdown(&data, &period).total, total);
+        }
+    }
+
+    #[test]
+    fn a_saving_is_negative_when_the_plan_costs_more() {
+        let data = vec![reading_at("08-01-2024 18:00", 1.5)];
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let bill = builtin_plans()[0].compute_bill_breakdown(&data, &period);
+        let breakdown = |total: Decimal| BillBreakdown {
+            total: EnergyBillEntry::Debit(Money::new(total)),
+            ..bill
+        };
+        let saving = saving_against(
+            &breakdown(Decimal::new(200, 0)),
+            &breakdown(Decimal::new(250, 0)),
+        );
+        assert_eq!(saving.amount.amount(), Decimal::new(-50, 0));
+        assert_eq!(saving.percentage, Decimal::new(-25, 0));
+        assert_eq!(
+            saving_against(&breakdown(Decimal::ZERO), &breakdown(Decimal::ONE)).percentage,
+            Decimal::ZERO
+        );
+    }
+
+    #[test]
+    fn a_plan_warns_once_for_all_the_readings_of_the_meter_point() {
+        let mut file: crate::PlanFile = toml::from_str(
+            r#"
+            [[plan]]
+            name = "gappy"
+            standing_charge = { per_day = 0.5 }
+
+            [[plan.bands]]
+            name = "day"
+            start = "08:00"
+            end = "19:00"
+            rate = 0.40
+            "#,
+        )
+        .unwrap();
+        // the cache prices each month on its own
+        let cache = crate::UsageCache::default().shared();
+        let plans: Vec<Box<dyn PricePlanStrategy>> = file
+            .take_electricity_plans()
+            .into_iter()
+            .map(|plan| crate::CachedPlan::wrap(plan, "test", &cache))
+            .collect();
+        let data = vec![
+            reading_at("08-01-2024 12:00", 1.0),
+            reading_at("08-01-2024 20:30", 1.0),
+            reading_at("09-02-2024 02:00", 1.0),
+            reading_at("09-02-2024 22:00", 1.0),
+        ];
+        let reports = compare_meter_points(&plans, data, &ComparisonOptions::default()).unwrap();
+        assert_eq!(
+            reports[0].plans[0].warning.as_deref(),
+            Some("3 readings do not fall into any rate band of gappy and were priced at zero")
+        );
+    }
+}
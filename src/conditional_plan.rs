@@ -0,0 +1,360 @@
+use anyhow::{bail, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    carbon::{dashboard_values, fetch_dashboard},
+    meter_data::{smart_meter_datetime_desserialize, smart_meter_datetime_serialize},
+    projection::default_discount_months,
+    read_csv,
+    usage_cache::Fnv,
+    vat::default_vat_included,
+    DuosGroup, EnergyBillEntry, ExportRules, Money, PlanMetadata, PricePlanStrategy, Rounding,
+    SmartMeterData, SmartMeterDataType, StandingCharge, StandingChargeDefinition,
+};
+
+// A value is used for the readings starting up to this long after it, like the prices of a
+// dynamic plan
+const MAX_CONDITION_LENGTH_MINUTES: i64 = 60;
+
+// One row of a conditions file, the start of the interval in UTC like the HDF readings and
+// the value of the grid then, like the percent of the demand met by wind,
+// e.g. 08-01-2024 00:00,62.5
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConditionPoint {
+    #[serde(
+        deserialize_with = "smart_meter_datetime_desserialize",
+        serialize_with = "smart_meter_datetime_serialize"
+    )]
+    pub start: NaiveDateTime,
+    pub value: f32,
+}
+
+// What the grid was like by the start of each interval
+#[derive(Debug, Clone, Default)]
+pub struct GridConditions {
+    series: BTreeMap<NaiveDateTime, f32>,
+}
+
+impl GridConditions {
+    pub fn new(points: Vec<ConditionPoint>) -> GridConditions {
+        GridConditions {
+            series: points
+                .into_iter()
+                .map(|point| (point.start, point.value))
+                .collect(),
+        }
+    }
+
+    pub fn load(filename: &Path) -> Result<GridConditions> {
+        let conditions = GridConditions::new(read_csv(filename)?);
+        if conditions.series.is_empty() {
+            bail!("no grid conditions in {}", filename.display());
+        }
+        Ok(conditions)
+    }
+
+    // Identifies the values, for the usage cache
+    pub fn version(&self) -> u64 {
+        let mut hasher = Fnv::new();
+        for (start, value) in &self.series {
+            hasher
+                .u64(start.and_utc().timestamp() as u64)
+                .u64(u64::from(value.to_bits()));
+        }
+        hasher.finish()
+    }
+
+    // The value of the interval starting at start, the average of the points inside it so
+    // quarter hours of the dashboard work for half hour readings. None when the series has
+    // nothing for it
+    pub fn value_at(&self, start: NaiveDateTime, minutes: i64) -> Option<f32> {
+        let inside: Vec<f32> = self
+            .series
+            .range(start..start + Duration::minutes(minutes))
+            .map(|(_, &value)| value)
+            .collect();
+        if !inside.is_empty() {
+            return Some(inside.iter().sum::<f32>() / inside.len() as f32);
+        }
+        let (&from, &value) = self.series.range(..=start).next_back()?;
+        (start - from < Duration::minutes(MAX_CONDITION_LENGTH_MINUTES)).then_some(value)
+    }
+}
+
+// A flat plan with a cheaper rate for the intervals the grid is over a threshold, like the
+// hours wind meets more than half of the demand. condition_rate is charged when the value of
+// the conditions file is above the threshold, unit_rate the rest of the time
+#[derive(Clone, Deserialize)]
+pub struct ConditionalPlan {
+    pub name: String,
+    pub unit_rate: Decimal,
+    pub condition_rate: Decimal,
+    // the conditions file, relative to the tariff file
+    pub conditions: PathBuf,
+    pub above: f32,
+    #[serde(default = "default_discount_months")]
+    pub discount_months: u32,
+    #[serde(default)]
+    pub sign_up_bonus: Decimal,
+    #[serde(default = "default_vat_included")]
+    pub vat_included: Decimal,
+    #[serde(default)]
+    pub export_rate: Decimal,
+    #[serde(default)]
+    pub export: ExportRules,
+    pub standing_charge: StandingChargeDefinition,
+    #[serde(default)]
+    pub rural_standing_charge: Option<StandingChargeDefinition>,
+    #[serde(default)]
+    pub rounding: Rounding,
+    #[serde(skip)]
+    pub series: GridConditions,
+    #[serde(default)]
+    pub metadata: PlanMetadata,
+}
+
+// The name is enough to identify the plan on the output
+impl Debug for ConditionalPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl ConditionalPlan {
+    // Reads the conditions file, a relative path is taken from the directory of the tariff file
+    pub fn load_conditions(&mut self, tariff_file: &Path) -> Result<()> {
+        let path = match tariff_file.parent() {
+            Some(dir) if self.conditions.is_relative() => dir.join(&self.conditions),
+            _ => self.conditions.clone(),
+        };
+        self.series = GridConditions::load(&path)?;
+        Ok(())
+    }
+
+    // Whether the grid was over the threshold, None when the conditions file doesn't say
+    fn condition_met(&self, datapoint: &SmartMeterData) -> Option<bool> {
+        let minutes = datapoint.interval_minutes as i64;
+        let start = datapoint.read_data_and_end_time - Duration::minutes(minutes);
+        self.series
+            .value_at(start, minutes)
+            .map(|value| value > self.above)
+    }
+
+    // Readings outside the conditions file are priced at the unit rate, so the user has to be
+    // told about them
+    fn missing_conditions_warning(&self, datapoints: &[SmartMeterData]) -> Option<String> {
+        let missing = datapoints
+            .iter()
+            .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+            .filter(|d| self.condition_met(d).is_none())
+            .count();
+        (missing > 0).then(|| {
+            format!(
+                "{missing} readings have no grid conditions in {} and were priced at the unit rate",
+                self.conditions.display()
+            )
+        })
+    }
+}
+
+impl PricePlanStrategy for ConditionalPlan {
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => {
+                let rate = match self.condition_met(datapoint) {
+                    Some(true) => self.condition_rate,
+                    _ => self.unit_rate,
+                };
+                EnergyBillEntry::Debit(Money::for_quantity(rate, datapoint.energy_kwh()))
+            }
+            SmartMeterDataType::ActiveExport => EnergyBillEntry::Credit(Money::for_quantity(
+                self.export_rate,
+                datapoint.energy_kwh(),
+            )),
+        }
+    }
+
+    fn band_for_singe_period(&self, datapoint: &SmartMeterData) -> String {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => match self.condition_met(datapoint) {
+                Some(true) => "condition".to_string(),
+                _ => "standard".to_string(),
+            },
+            SmartMeterDataType::ActiveExport => "export".to_string(),
+        }
+    }
+
+    fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    fn export_rules(&self) -> ExportRules {
+        self.export
+    }
+
+    // the conditions file can change without the tariff file changing
+    fn cache_version(&self) -> u64 {
+        self.series.version()
+    }
+
+    fn discount_months(&self) -> u32 {
+        self.discount_months
+    }
+
+    fn sign_up_bonus(&self) -> Money {
+        Money::new(self.sign_up_bonus)
+    }
+
+    fn metadata(&self) -> PlanMetadata {
+        self.metadata.clone()
+    }
+
+    fn pricing_warning(&self, datapoints: &[SmartMeterData]) -> Option<String> {
+        self.missing_conditions_warning(datapoints)
+    }
+}
+
+impl StandingCharge for ConditionalPlan {
+    fn standing_charge_per_day(&self, date: NaiveDate, group: DuosGroup) -> EnergyBillEntry {
+        group
+            .pick(self.standing_charge, self.rural_standing_charge)
+            .for_day(date)
+    }
+
+    fn vat_included(&self) -> Decimal {
+        self.vat_included
+    }
+}
+
+// The percent of the demand of each quarter hour met by wind out of a windactual and a
+// demandactual answer of the dashboard, the quarter hours without both are left out
+pub fn parse_dashboard_wind_share(wind: &str, demand: &str) -> Result<Vec<ConditionPoint>> {
+    let demand: BTreeMap<NaiveDateTime, f32> =
+        dashboard_values(demand, "demand")?.into_iter().collect();
+    Ok(dashboard_values(wind, "wind generation")?
+        .into_iter()
+        .filter_map(|(start, wind)| {
+            let demand = *demand.get(&start).filter(|demand| **demand > 0.0)?;
+            Some(ConditionPoint {
+                start,
+                value: wind / demand * 100.0,
+            })
+        })
+        .collect())
+}
+
+// Downloads the wind share of the grid of the Republic of Ireland from the EirGrid smart grid
+// dashboard, every quarter hour of the days from from to to
+pub fn fetch_wind_share(from: NaiveDate, to: NaiveDate) -> Result<Vec<ConditionPoint>> {
+    let wind = fetch_dashboard("windactual", "wind generation", from, to)?;
+    let demand = fetch_dashboard("demandactual", "demand", from, to)?;
+    parse_dashboard_wind_share(&wind, &demand)
+}
+
+// Writes the values in the format of the conditions files of conditional plans
+pub fn write_conditions_file(points: &[ConditionPoint], writer: impl Write) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    for point in points {
+        wtr.serialize(point)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn windy_intervals_get_the_condition_rate() {
+        let wind = r#"{"Rows":[
+            {"EffectiveTime":"08-Jan-2024 03:00:00","FieldName":"WIND_ACTUAL","Value":2400},
+            {"EffectiveTime":"08-Jan-2024 03:15:00","FieldName":"WIND_ACTUAL","Value":2000},
+            {"EffectiveTime":"08-Jan-2024 17:30:00","FieldName":"WIND_ACTUAL","Value":500},
+            {"EffectiveTime":"08-Jan-2024 17:45:00","FieldName":"WIND_ACTUAL","Value":null}
+        ]}"#;
+        let demand = r#"{"Rows":[
+            {"EffectiveTime":"08-Jan-2024 03:00:00","FieldName":"DEMAND_ACTUAL","Value":3000},
+            {"EffectiveTime":"08-Jan-2024 03:15:00","FieldName":"DEMAND_ACTUAL","Value":3000},
+            {"EffectiveTime":"08-Jan-2024 17:30:00","FieldName":"DEMAND_ACTUAL","Value":5000}
+        ]}"#;
+        let points = parse_dashboard_wind_share(wind, demand).unwrap();
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0].value, 80.0);
+
+        let plan = ConditionalPlan {
+            name: "windy".to_string(),
+            unit_rate: dec!(0.40),
+            condition_rate: dec!(0.10),
+            conditions: PathBuf::from("wind.csv"),
+            above: 50.0,
+            discount_months: crate::DEFAULT_DISCOUNT_MONTHS,
+            sign_up_bonus: Decimal::ZERO,
+            vat_included: crate::QUOTED_VAT_RATE,
+            export_rate: Decimal::ZERO,
+            export: ExportRules::default(),
+            standing_charge: StandingChargeDefinition::PerDay(Decimal::ZERO),
+            rural_standing_charge: None,
+            rounding: Rounding::default(),
+            series: GridConditions::new(points),
+            metadata: PlanMetadata::default(),
+        };
+        // 1 kWh each, 73% wind in the half hour to 03:30 and 10% in the one to 18:00
+        let price = |datetime| plan.price_for_singe_period(&reading_at(datetime, 2.0));
+        assert_eq!(
+            price("08-01-2024 03:30"),
+            EnergyBillEntry::Debit(Money::new(dec!(0.10)))
+        );
+        assert_eq!(
+            price("08-01-2024 18:00"),
+            EnergyBillEntry::Debit(Money::new(dec!(0.40)))
+        );
+        let night = reading_at("08-01-2024 06:00", 2.0);
+        assert_eq!(plan.band_for_singe_period(&night), "standard");
+        assert!(plan.missing_conditions_warning(&[night]).is_some());
+    }
+
+    #[test]
+    fn the_usage_cache_prices_the_plan_again_when_the_conditions_change() {
+        let data = vec![reading_at("08-01-2024 03:30", 2.0)];
+        let cache = crate::UsageCache::default().shared();
+        // what the plan charges with and without the cache
+        let usage = |wind: f32| {
+            let mut plan: ConditionalPlan = toml::from_str(
+                r#"
+                name = "windy"
+                unit_rate = 0.40
+                condition_rate = 0.10
+                conditions = "wind.csv"
+                above = 50
+                standing_charge = { per_day = 0 }
+                "#,
+            )
+            .unwrap();
+            plan.series = GridConditions::new(vec![ConditionPoint {
+                start: NaiveDateTime::parse_from_str("08-01-2024 03:00", "%d-%m-%Y %H:%M").unwrap(),
+                value: wind,
+            }]);
+            assert!(plan.usage_adds_up_by_month());
+            let uncached = plan.compute_total_bill_for_period(&data);
+            let cached = crate::CachedPlan::wrap(Box::new(plan), "tariffs", &cache)
+                .compute_total_bill_for_period(&data);
+            (cached, uncached)
+        };
+        let (windy, uncached) = usage(80.0);
+        assert_eq!(windy, uncached);
+        let (calm, uncached) = usage(10.0);
+        assert_eq!(calm, uncached);
+        assert_ne!(calm, windy);
+    }
+}
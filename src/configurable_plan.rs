@@ -0,0 +1,821 @@
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{fmt::Debug, path::Path};
+
+use crate::{
+    annual_to_daily, projection::default_discount_months, time_in_window,
+    vat::default_vat_included, BillingPeriod, ConditionalPlan, Currency, DayNightPlan, DaySet,
+    DemandCharge, Discount, DualFuelBundle, DuosGroup, DynamicPlan, EnergyBillEntry, ExportRules,
+    FlatRateGasPlan, FreeTime, FreeWindow, MaximumImportCapacity, Money, PlanMetadata, PluginPlan,
+    PricePlanStrategy, Rounding, SmartMeterData, SmartMeterDataType, StandingCharge, TieredPlan,
+};
+
+// A day of every year, written like 11-01 for the first of november
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct MonthDay {
+    pub month: u32,
+    pub day: u32,
+}
+
+impl MonthDay {
+    pub fn of(date: NaiveDate) -> MonthDay {
+        MonthDay {
+            month: date.month(),
+            day: date.day(),
+        }
+    }
+}
+
+impl TryFrom<String> for MonthDay {
+    type Error = String;
+
+    // checked against a leap year so 02-29 is a day too
+    fn try_from(text: String) -> Result<MonthDay, String> {
+        NaiveDate::parse_from_str(&format!("2024-{text}"), "%Y-%m-%d")
+            .map(MonthDay::of)
+            .map_err(|_| format!("{text} is not a day of the year like 11-01"))
+    }
+}
+
+// The part of every year a band applies in, from one day to another and both included, like
+// from = "11-01" and to = "02-29" for the winter, which goes over the new year
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub struct Season {
+    pub from: MonthDay,
+    pub to: MonthDay,
+}
+
+impl Season {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        let day = MonthDay::of(date);
+        if self.from <= self.to {
+            self.from <= day && day <= self.to
+        } else {
+            day >= self.from || day <= self.to
+        }
+    }
+}
+
+// A time window with its own unit rate, see time_in_window for the boundary semantics
+// no days means the band applies every day and no season all year
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateBand {
+    pub name: String,
+    #[serde(default)]
+    pub days: DaySet,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub rate: Decimal,
+    #[serde(default)]
+    pub season: Option<Season>,
+}
+
+impl RateBand {
+    // date is the day of the reading, see SmartMeterData::date
+    pub(crate) fn applies_to(
+        &self,
+        datetime: &NaiveDateTime,
+        weekday: Weekday,
+        date: NaiveDate,
+    ) -> bool {
+        if !self.days.contains(weekday, date) {
+            return false;
+        }
+        if self.season.is_some_and(|season| !season.contains(date)) {
+            return false;
+        }
+        time_in_window(datetime.time(), self.start, self.end)
+    }
+}
+
+// The bands of a season first, they take over from the ones of the whole year
+fn by_season(bands: &[RateBand]) -> impl Iterator<Item = &RateBand> {
+    bands
+        .iter()
+        .filter(|band| band.season.is_some())
+        .chain(bands.iter().filter(|band| band.season.is_none()))
+}
+
+// Suppliers publish standing charges either per day or per year
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StandingChargeDefinition {
+    PerDay(Decimal),
+    Annual(Decimal),
+}
+
+impl StandingChargeDefinition {
+    pub fn for_day(&self, date: NaiveDate) -> EnergyBillEntry {
+        match *self {
+            StandingChargeDefinition::PerDay(value) => EnergyBillEntry::Debit(Money::new(value)),
+            StandingChargeDefinition::Annual(value) => {
+                EnergyBillEntry::Debit(Money::new(annual_to_daily(value, date)))
+            }
+        }
+    }
+}
+
+// What pay as you go meters charge on top of the standing charge of the plan, every day
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PrepayCharges {
+    pub service_charge: StandingChargeDefinition,
+}
+
+// The rates of a plan from a date on, until the next version. Whatever a version leaves out
+// is taken from the plan itself
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateVersion {
+    pub from: NaiveDate,
+    pub bands: Vec<RateBand>,
+    #[serde(default)]
+    pub standing_charge: Option<StandingChargeDefinition>,
+    #[serde(default)]
+    pub rural_standing_charge: Option<StandingChargeDefinition>,
+    #[serde(default)]
+    pub export_rate: Option<Decimal>,
+    #[serde(default)]
+    pub export_bands: Option<Vec<RateBand>>,
+}
+
+// A plan described by data instead of code, bands are checked in order and the first match wins
+#[derive(Clone, Deserialize)]
+pub struct ConfigurablePlan {
+    pub name: String,
+    pub bands: Vec<RateBand>,
+    // a window checked before the bands, like the night boost of EV tariffs, see validate_plan
+    #[serde(default)]
+    pub boost: Option<RateBand>,
+    // the free hours the customer picked, checked before the boost, see FreeTime
+    #[serde(default)]
+    pub free_time: Option<FreeTime>,
+    #[serde(default)]
+    pub discount: Discount,
+    #[serde(default = "default_discount_months")]
+    pub discount_months: u32,
+    #[serde(default)]
+    pub fixed_rate_months: u32,
+    #[serde(default)]
+    pub sign_up_bonus: Decimal,
+    #[serde(default)]
+    pub currency: Currency,
+    #[serde(default = "default_vat_included")]
+    pub vat_included: Decimal,
+    #[serde(default)]
+    pub export_rate: Decimal,
+    // export rates by time of day and season, picked like the bands. Exports outside all of
+    // them are credited at export_rate
+    #[serde(default)]
+    pub export_bands: Vec<RateBand>,
+    #[serde(default)]
+    pub export: ExportRules,
+    pub standing_charge: StandingChargeDefinition,
+    // charged instead of standing_charge for rural meter points, see DuosGroup
+    #[serde(default)]
+    pub rural_standing_charge: Option<StandingChargeDefinition>,
+    // charged on the peak demand of each billing period, see DemandCharge
+    #[serde(default)]
+    pub demand_charge: DemandCharge,
+    #[serde(default)]
+    pub rounding: Rounding,
+    // price bank holidays with the bands of this day, usually sun
+    #[serde(default)]
+    pub holidays_like: Option<Weekday>,
+    // set for pay as you go plans, which get no discount
+    #[serde(default)]
+    pub prepay: Option<PrepayCharges>,
+    // later rates of the plan, the bands above apply before the first one
+    #[serde(default)]
+    pub versions: Vec<RateVersion>,
+    #[serde(default)]
+    pub metadata: PlanMetadata,
+}
+
+// The name is enough to identify the plan on the output
+impl Debug for ConfigurablePlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl ConfigurablePlan {
+    // The version in force on the day, None before the first one
+    pub fn version_on(&self, date: NaiveDate) -> Option<&RateVersion> {
+        self.versions
+            .iter()
+            .filter(|version| version.from <= date)
+            .max_by_key(|version| version.from)
+    }
+
+    fn bands_on(&self, date: NaiveDate) -> &[RateBand] {
+        self.version_on(date)
+            .map_or(&self.bands, |version| &version.bands)
+    }
+
+    // the bands of a season take over from the ones of the whole year in whatever order
+    fn band_for(&self, datapoint: &SmartMeterData) -> Option<&RateBand> {
+        let bands = self.bands_on(datapoint.date());
+        let chosen = self.free_time.iter().map(FreeTime::band).chain(&self.boost);
+        self.first_band(datapoint, chosen.chain(by_season(bands)))
+    }
+
+    fn export_band_for(&self, datapoint: &SmartMeterData) -> Option<&RateBand> {
+        let bands = self
+            .version_on(datapoint.date())
+            .and_then(|version| version.export_bands.as_deref())
+            .unwrap_or(&self.export_bands);
+        self.first_band(datapoint, by_season(bands))
+    }
+
+    fn first_band<'a>(
+        &self,
+        datapoint: &SmartMeterData,
+        mut bands: impl Iterator<Item = &'a RateBand>,
+    ) -> Option<&'a RateBand> {
+        let local = datapoint.local_end_time();
+        let weekday = match self.holidays_like {
+            Some(weekday) if self.is_holiday(local.date()) => weekday,
+            _ => local.weekday(),
+        };
+        bands.find(|band| band.applies_to(&local, weekday, datapoint.date()))
+    }
+
+    // Readings that no band covers are priced at zero, so the user has to be told about them
+    fn unmatched_band_warning(&self, datapoints: &[SmartMeterData]) -> Option<String> {
+        let unmatched = datapoints
+            .iter()
+            .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+            .filter(|d| self.band_for(d).is_none())
+            .count();
+        if unmatched == 0 {
+            None
+        } else {
+            Some(format!(
+                "{unmatched} readings do not fall into any rate band of {} and were priced at zero",
+                self.name
+            ))
+        }
+    }
+}
+
+impl PricePlanStrategy for ConfigurablePlan {
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => match self.band_for(datapoint) {
+                Some(band) => EnergyBillEntry::Debit(Money::for_quantity(
+                    self.discount().on_unit_rate(band.rate),
+                    datapoint.energy_kwh(),
+                )),
+                None => EnergyBillEntry::ZERO,
+            },
+            SmartMeterDataType::ActiveExport => EnergyBillEntry::Credit(Money::for_quantity(
+                match self.export_band_for(datapoint) {
+                    Some(band) => band.rate,
+                    None => self
+                        .version_on(datapoint.date())
+                        .and_then(|version| version.export_rate)
+                        .unwrap_or(self.export_rate),
+                },
+                datapoint.energy_kwh(),
+            )),
+        }
+    }
+
+    fn band_for_singe_period(&self, datapoint: &SmartMeterData) -> String {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => match self.band_for(datapoint) {
+                Some(band) => band.name.clone(),
+                None => "unmatched".to_string(),
+            },
+            SmartMeterDataType::ActiveExport => match self.export_band_for(datapoint) {
+                Some(band) => format!("export {}", band.name),
+                None => "export".to_string(),
+            },
+        }
+    }
+
+    fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    fn export_rules(&self) -> ExportRules {
+        self.export
+    }
+
+    fn demand_charge_with_mic(
+        &self,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+        mic: Option<MaximumImportCapacity>,
+    ) -> EnergyBillEntry {
+        self.demand_charge
+            .for_period(datapoints, period, self.vat_included, self.currency, mic)
+    }
+
+    fn at_standard_rates(&self) -> Option<Box<dyn PricePlanStrategy>> {
+        Some(Box::new(ConfigurablePlan {
+            discount: Discount::default(),
+            ..self.clone()
+        }))
+    }
+
+    // Prepay plans are never discounted, whatever the file says
+    fn discount(&self) -> Discount {
+        match self.prepay {
+            Some(_) => Discount::default(),
+            None => self.discount,
+        }
+    }
+
+    fn discount_months(&self) -> u32 {
+        self.discount_months
+    }
+
+    fn fixed_rate_months(&self) -> u32 {
+        self.fixed_rate_months
+    }
+
+    fn sign_up_bonus(&self) -> Money {
+        Money::of(self.sign_up_bonus, self.currency)
+    }
+
+    // the prices are as of the latest version unless the file says otherwise
+    fn metadata(&self) -> PlanMetadata {
+        let mut metadata = self.metadata.clone();
+        if metadata.tariff_date.is_none() {
+            metadata.tariff_date = self.versions.iter().map(|version| version.from).max();
+        }
+        metadata
+    }
+
+    fn is_prepay(&self) -> bool {
+        self.prepay.is_some()
+    }
+
+    fn free_time(&self) -> Option<FreeTime> {
+        self.free_time.clone()
+    }
+
+    fn free_time_choices(&self) -> Vec<(FreeWindow, Box<dyn PricePlanStrategy>)> {
+        let Some(free_time) = &self.free_time else {
+            return vec![];
+        };
+        free_time
+            .windows()
+            .into_iter()
+            .map(|window| {
+                let plan: Box<dyn PricePlanStrategy> = Box::new(ConfigurablePlan {
+                    free_time: Some(free_time.choosing(&window)),
+                    ..self.clone()
+                });
+                (window, plan)
+            })
+            .collect()
+    }
+
+    fn pricing_warning(&self, datapoints: &[SmartMeterData]) -> Option<String> {
+        self.unmatched_band_warning(datapoints)
+    }
+}
+
+impl StandingCharge for ConfigurablePlan {
+    fn standing_charge_per_day(&self, date: NaiveDate, group: DuosGroup) -> EnergyBillEntry {
+        let version = self.version_on(date);
+        let service_charge = self.prepay.map_or(EnergyBillEntry::ZERO, |prepay| {
+            prepay.service_charge.for_day(date)
+        });
+        let standing_charge = group
+            .pick(
+                version
+                    .and_then(|version| version.standing_charge)
+                    .unwrap_or(self.standing_charge),
+                version
+                    .and_then(|version| version.rural_standing_charge)
+                    .or(self.rural_standing_charge),
+            )
+            .for_day(date);
+        service_charge + self.discount().on_standing_charge(standing_charge)
+    }
+
+    fn vat_included(&self) -> Decimal {
+        self.vat_included
+    }
+
+    fn currency(&self) -> Currency {
+        self.currency
+    }
+}
+
+// The layout of a tariff file, see plans.example.toml
+#[derive(Debug, Deserialize)]
+pub struct PlanFile {
+    #[serde(default)]
+    pub plan: Vec<ConfigurablePlan>,
+    #[serde(default)]
+    pub tiered_plan: Vec<TieredPlan>,
+    #[serde(default)]
+    pub day_night_plan: Vec<DayNightPlan>,
+    #[serde(default)]
+    pub dynamic_plan: Vec<DynamicPlan>,
+    #[serde(default)]
+    pub conditional_plan: Vec<ConditionalPlan>,
+    #[serde(default)]
+    pub gas_plan: Vec<FlatRateGasPlan>,
+    #[serde(default)]
+    pub dual_fuel: Vec<DualFuelBundle>,
+    #[serde(default)]
+    pub plugin_plan: Vec<PluginPlan>,
+}
+
+impl PlanFile {
+    // The electricity plans of the file, the gas plans and bundles are left in it
+    pub fn take_electricity_plans(&mut self) -> Vec<Box<dyn PricePlanStrategy>> {
+        let mut plans: Vec<Box<dyn PricePlanStrategy>> = vec![];
+        for plan in std::mem::take(&mut self.plan) {
+            plans.push(Box::new(plan));
+        }
+        for plan in std::mem::take(&mut self.tiered_plan) {
+            plans.push(Box::new(plan));
+        }
+        for plan in std::mem::take(&mut self.day_night_plan) {
+            plans.push(Box::new(plan));
+        }
+        for plan in std::mem::take(&mut self.dynamic_plan) {
+            plans.push(Box::new(plan));
+        }
+        for plan in std::mem::take(&mut self.conditional_plan) {
+            plans.push(Box::new(plan));
+        }
+        for plan in std::mem::take(&mut self.plugin_plan) {
+            plans.extend(plan.into_plan());
+        }
+        plans
+    }
+}
+
+// Dynamic plans have their prices read, conditional plans their grid conditions and plugin
+// plans are made too, so every plan is ready to price readings
+pub fn load_plan_file(filename: &Path) -> Result<PlanFile> {
+    let contents = std::fs::read_to_string(filename)?;
+    let mut plan_file: PlanFile = toml::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("invalid tariff file {}: {e}", filename.display()))?;
+    for plan in &mut plan_file.dynamic_plan {
+        plan.load_prices(filename)?;
+    }
+    for plan in &mut plan_file.conditional_plan {
+        plan.load_conditions(filename)?;
+    }
+    for plan in &mut plan_file.plugin_plan {
+        plan.load(filename)?;
+    }
+    Ok(plan_file)
+}
+
+pub fn load_plans(filename: &Path) -> Result<Vec<ConfigurablePlan>> {
+    Ok(load_plan_file(filename)?.plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+    use rust_decimal_macros::dec;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn the_example_tariff_file_loads_every_kind_of_plan() {
+        let mut file = load_plan_file(Path::new("plans.example.toml")).unwrap();
+        assert_eq!(file.gas_plan.len(), 1);
+        assert_eq!(file.dual_fuel.len(), 1);
+        let plans = file.take_electricity_plans();
+        let names: Vec<String> = plans.iter().map(|plan| plan.name()).collect();
+        assert_eq!(names.len(), 4, "{names:?}");
+        assert!(names.contains(&"SSE Airtricity 20% (from file)".to_string()));
+        // the gas plans and bundles are left in the file
+        assert_eq!(file.gas_plan.len(), 1);
+        assert!(file.plan.is_empty());
+
+        let sse = &plans[0];
+        assert_eq!(
+            sse.band_for_singe_period(&reading_at("08-01-2024 17:30", 2.0)),
+            "peak"
+        );
+        // the peak rate with the 20% discount off
+        assert_eq!(
+            sse.price_for_singe_period(&reading_at("08-01-2024 17:30", 2.0)),
+            EnergyBillEntry::Debit(Money::new(dec!(0.4882) * dec!(0.8)))
+        );
+    }
+
+    #[test]
+    fn prepay_plans_pay_the_service_charge_and_get_no_discount() {
+        let plan: ConfigurablePlan = toml::from_str(
+            r#"
+name = "pay as you go"
+discount = 0.2
+standing_charge = { per_day = 0.5 }
+prepay = { service_charge = { per_day = 0.3 } }
+
+[[bands]]
+name = "all"
+start = "00:00"
+end = "00:00"
+rate = 0.30
+"#,
+        )
+        .unwrap();
+        assert!(plan.is_prepay());
+        assert_eq!(
+            plan.price_for_singe_period(&reading_at("08-01-2024 18:00", 2.0)),
+            EnergyBillEntry::Debit(Money::new(dec!(0.30)))
+        );
+        assert_eq!(
+            plan.standing_charge_per_day(
+                NaiveDate::from_ymd_opt(2024, 1, 8).unwrap(),
+                DuosGroup::Urban
+            ),
+            EnergyBillEntry::Debit(Money::new(dec!(0.8)))
+        );
+        let issues: Vec<String> = crate::validate_plan(&plan)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(issues.len(), 1);
+        assert!(
+            issues[0].starts_with("prepay plans get no discount"),
+            "{issues:?}"
+        );
+    }
+
+    #[test]
+    fn gappy_bands_trigger_unmatched_warning() {
+        // nothing covers 19:00 to 23:00
+        let plan = ConfigurablePlan {
+            name: "gappy".to_string(),
+            bands: vec![
+                RateBand {
+                    name: "day".to_string(),
+                    days: DaySet::all(),
+                    start: time(8, 0),
+                    end: time(19, 0),
+                    rate: dec!(0.40),
+                    season: None,
+                },
+                RateBand {
+                    name: "night".to_string(),
+                    days: DaySet::all(),
+                    start: time(23, 0),
+                    end: time(8, 0),
+                    rate: dec!(0.20),
+                    season: None,
+                },
+            ],
+            boost: None,
+            free_time: None,
+            discount: Discount::default(),
+            discount_months: crate::DEFAULT_DISCOUNT_MONTHS,
+            fixed_rate_months: 0,
+            sign_up_bonus: Decimal::ZERO,
+            currency: Currency::Eur,
+            vat_included: crate::QUOTED_VAT_RATE,
+            export_rate: Decimal::ZERO,
+            export_bands: vec![],
+            export: ExportRules::default(),
+            standing_charge: StandingChargeDefinition::PerDay(Decimal::ZERO),
+            rural_standing_charge: None,
+            demand_charge: DemandCharge::default(),
+            rounding: Rounding::default(),
+            holidays_like: None,
+            prepay: None,
+            versions: vec![],
+            metadata: PlanMetadata::default(),
+        };
+        let data = vec![
+            reading_at("08-01-2024 12:00", 1.0),
+            reading_at("08-01-2024 20:30", 1.0),
+            reading_at("08-01-2024 22:00", 1.0),
+            reading_at("09-01-2024 02:00", 1.0),
+        ];
+
+        let warning = plan
+            .unmatched_band_warning(&data)
+            .expect("expected a warning");
+        assert!(warning.contains("2 readings"), "{warning}");
+    }
+
+    #[test]
+    fn seasonal_bands_price_the_readings_of_their_season() {
+        let plan: ConfigurablePlan = toml::from_str(
+            r#"
+name = "winter rates"
+standing_charge = { per_day = 0.5 }
+vat_included = 0
+
+[[bands]]
+name = "day"
+start = "00:00"
+end = "00:00"
+rate = 0.30
+
+[[bands]]
+name = "winter"
+season = { from = "11-01", to = "02-29" }
+start = "00:00"
+end = "00:00"
+rate = 0.40
+"#,
+        )
+        .unwrap();
+        // the winter goes over the new year and includes both of its days
+        for (reading, band) in [
+            ("01-11-2023 12:00", "winter"),
+            ("08-01-2024 12:00", "winter"),
+            ("29-02-2024 12:00", "winter"),
+            ("01-03-2024 12:00", "day"),
+            ("31-10-2024 12:00", "day"),
+        ] {
+            assert_eq!(
+                plan.band_for_singe_period(&reading_at(reading, 1.0)),
+                band,
+                "{reading}"
+            );
+        }
+    }
+
+    #[test]
+    fn the_usage_is_rounded_where_the_plan_rounds_it() {
+        let plan = |per: &str| -> ConfigurablePlan {
+            toml::from_str(&format!(
+                r#"
+name = "rounded"
+standing_charge = {{ per_day = 0.5 }}
+vat_included = 0
+rounding = {{ decimal_places = 2, per = "{per}" }}
+
+[[bands]]
+name = "all day"
+start = "00:00"
+end = "00:00"
+rate = 0.30
+"#
+            ))
+            .unwrap()
+        };
+        // two readings of 0.5 kWh on each of two days, 0.1635 each with the VAT
+        let data = vec![
+            reading_at("08-01-2024 12:00", 1.0),
+            reading_at("08-01-2024 12:30", 1.0),
+            reading_at("09-01-2024 12:00", 1.0),
+            reading_at("09-01-2024 12:30", 1.0),
+        ];
+        let total = |per: &str| {
+            plan(per)
+                .compute_total_bill_for_period(&data)
+                .signed_amount()
+        };
+        assert_eq!(total("interval"), dec!(0.64));
+        assert_eq!(total("day"), dec!(0.66));
+        assert_eq!(total("bill").round_dp(2), dec!(0.65));
+        // the days still add up from the readings
+        let prices = plan("day").price_readings(&data);
+        assert_eq!(prices[0].signed_amount(), dec!(0.1635));
+        assert_eq!((prices[0] + prices[1]).signed_amount(), dec!(0.33));
+    }
+
+    #[test]
+    fn full_coverage_has_no_warning() {
+        let plan = ConfigurablePlan {
+            name: "flat".to_string(),
+            bands: vec![RateBand {
+                name: "all day".to_string(),
+                days: DaySet::all(),
+                start: time(0, 0),
+                end: time(0, 0),
+                rate: dec!(0.30),
+                season: None,
+            }],
+            boost: None,
+            free_time: None,
+            discount: Discount::default(),
+            discount_months: crate::DEFAULT_DISCOUNT_MONTHS,
+            fixed_rate_months: 0,
+            sign_up_bonus: Decimal::ZERO,
+            currency: Currency::Eur,
+            vat_included: crate::QUOTED_VAT_RATE,
+            export_rate: Decimal::ZERO,
+            export_bands: vec![],
+            export: ExportRules::default(),
+            standing_charge: StandingChargeDefinition::PerDay(Decimal::ZERO),
+            rural_standing_charge: None,
+            demand_charge: DemandCharge::default(),
+            rounding: Rounding::default(),
+            holidays_like: None,
+            prepay: None,
+            versions: vec![],
+            metadata: PlanMetadata::default(),
+        };
+        let data = vec![
+            reading_at("08-01-2024 00:00", 1.0),
+            reading_at("08-01-2024 20:30", 1.0),
+        ];
+
+        assert!(plan.unmatched_band_warning(&data).is_none());
+    }
+
+    #[test]
+    fn readings_are_priced_with_the_version_in_force() {
+        let file: PlanFile = toml::from_str(
+            r#"
+            [[plan]]
+            name = "repriced"
+            standing_charge = { per_day = 0.50 }
+
+            [[plan.bands]]
+            name = "all day"
+            start = "00:00"
+            end = "00:00"
+            rate = 0.30
+
+            [[plan.versions]]
+            from = "2024-01-09"
+            standing_charge = { per_day = 0.60 }
+
+            [[plan.versions.bands]]
+            name = "all day"
+            start = "00:00"
+            end = "00:00"
+            rate = 0.40
+            "#,
+        )
+        .unwrap();
+        let plan = &file.plan[0];
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+
+        // midnight still belongs to the 8th
+        assert_eq!(
+            plan.price_for_singe_period(&reading_at("09-01-2024 00:00", 2.0)),
+            EnergyBillEntry::Debit(Money::new(dec!(0.30)))
+        );
+        assert_eq!(
+            plan.price_for_singe_period(&reading_at("09-01-2024 00:30", 2.0)),
+            EnergyBillEntry::Debit(Money::new(dec!(0.40)))
+        );
+        assert_eq!(
+            plan.standing_charge_per_day(day(9), DuosGroup::Urban),
+            EnergyBillEntry::Debit(Money::new(dec!(0.60)))
+        );
+    }
+
+    #[test]
+    fn exports_are_credited_at_the_rate_of_their_band() {
+        let plan: ConfigurablePlan = toml::from_str(
+            r#"
+name = "banded export"
+standing_charge = { per_day = 0.5 }
+export_rate = 0.10
+
+[[bands]]
+name = "all day"
+start = "00:00"
+end = "00:00"
+rate = 0.30
+
+[[export_bands]]
+name = "evening"
+start = "17:00"
+end = "19:00"
+rate = 0.25
+
+[[export_bands]]
+name = "summer"
+season = { from = "05-01", to = "08-31" }
+start = "11:00"
+end = "16:00"
+rate = 0.15
+"#,
+        )
+        .unwrap();
+        let export = |datetime| SmartMeterData {
+            read_type: SmartMeterDataType::ActiveExport,
+            ..reading_at(datetime, 2.0)
+        };
+        for (reading, band, rate) in [
+            ("08-01-2024 18:00", "export evening", dec!(0.25)),
+            ("08-01-2024 12:00", "export", dec!(0.10)),
+            // the readings are in UTC, 12:00 is 13:00 in the summer
+            ("08-07-2024 12:00", "export summer", dec!(0.15)),
+        ] {
+            assert_eq!(plan.band_for_singe_period(&export(reading)), band);
+            assert_eq!(
+                plan.price_for_singe_period(&export(reading)),
+                EnergyBillEntry::Credit(Money::new(rate)),
+                "{reading}"
+            );
+        }
+    }
+}
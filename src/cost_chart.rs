@@ -0,0 +1,168 @@
+use chrono::NaiveDate;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{BillingPeriod, EnergyBillEntry, PricePlanStrategy, SmartMeterData};
+
+// How much a plan had cost by the end of each day of the period
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostCurve {
+    pub name: String,
+    pub points: Vec<(NaiveDate, Decimal)>,
+}
+
+// The running total of every plan day by day, each day pays its usage and its fixed charges
+// so a plan with a dear standing charge starts ahead and can be caught up. Export caps
+// count the whole period and are left out
+pub fn cumulative_costs(
+    plans: &[Box<dyn PricePlanStrategy>],
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+) -> Vec<CostCurve> {
+    plans
+        .iter()
+        .map(|plan| {
+            let mut usage: BTreeMap<NaiveDate, EnergyBillEntry> = BTreeMap::new();
+            for (datapoint, price) in datapoints.iter().zip(plan.price_readings(datapoints)) {
+                *usage
+                    .entry(datapoint.date())
+                    .or_insert(EnergyBillEntry::ZERO) += price;
+            }
+            let mut total = Decimal::ZERO;
+            let points = period
+                .days()
+                .map(|date| {
+                    let day = BillingPeriod {
+                        start: date,
+                        end: date,
+                    };
+                    let usage = usage.get(&date).copied().unwrap_or(EnergyBillEntry::ZERO);
+                    total += plan
+                        .bill_breakdown_for_usage(usage, &day)
+                        .total
+                        .signed_amount();
+                    (date, total)
+                })
+                .collect();
+            CostCurve {
+                name: plan.name(),
+                points,
+            }
+        })
+        .collect()
+}
+
+const PALETTE: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+];
+
+const WIDTH: f64 = 900.0;
+const HEIGHT: f64 = 440.0;
+// room for the amounts on the left, the dates under the chart and the legend on the right
+const LEFT: f64 = 60.0;
+const BOTTOM: f64 = 30.0;
+const TOP: f64 = 20.0;
+const PLOT_WIDTH: f64 = 540.0;
+
+// A line per plan of the running total, the lines cross where one plan overtakes another
+pub fn render_cost_chart_svg(curves: &[CostCurve]) -> String {
+    let days = curves.iter().map(|c| c.points.len()).max().unwrap_or(0);
+    let amounts = || {
+        curves
+            .iter()
+            .flat_map(|c| &c.points)
+            .map(|p| p.1.to_f64().unwrap_or(0.0))
+    };
+    let max = amounts().fold(0.0_f64, f64::max).max(1.0);
+    let min = amounts().fold(0.0_f64, f64::min);
+    let plot_height = HEIGHT - TOP - BOTTOM;
+    let x = |day: usize| LEFT + PLOT_WIDTH * day as f64 / (days.max(2) - 1) as f64;
+    let y = |amount: f64| TOP + plot_height * (max - amount) / (max - min);
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" font-family="sans-serif" font-size="12">
+"#
+    );
+    // five steps of the amounts with their grid lines
+    for step in 0..=5 {
+        let amount = min + (max - min) * step as f64 / 5.0;
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{LEFT}" y1="{0:.1}" x2="{1}" y2="{0:.1}" stroke="#dddddd"/><text x="4" y="{2:.1}">{amount:.0}</text>"##,
+            y(amount),
+            LEFT + PLOT_WIDTH,
+            y(amount) + 4.0
+        );
+    }
+    if let Some(first) = curves.iter().find(|c| !c.points.is_empty()) {
+        let last = first.points.len() - 1;
+        for day in [0, last / 2, last] {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#,
+                x(day),
+                HEIGHT - 10.0,
+                first.points[day].0
+            );
+        }
+    }
+    for (index, curve) in curves.iter().enumerate() {
+        let colour = PALETTE[index % PALETTE.len()];
+        let points: Vec<String> = curve
+            .points
+            .iter()
+            .enumerate()
+            .map(|(day, (_, amount))| {
+                format!("{:.1},{:.1}", x(day), y(amount.to_f64().unwrap_or(0.0)))
+            })
+            .collect();
+        let _ = writeln!(
+            svg,
+            r#"<polyline fill="none" stroke="{colour}" stroke-width="2" points="{}"><title>{}</title></polyline>"#,
+            points.join(" "),
+            escape(&curve.name)
+        );
+        let total = curve.points.last().map_or(Decimal::ZERO, |p| p.1);
+        let legend = TOP + 10.0 + index as f64 * 18.0;
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{0}" y="{1}" width="12" height="12" fill="{colour}"/><text x="{2}" y="{3}">{4} {total:.2}</text>"#,
+            LEFT + PLOT_WIDTH + 16.0,
+            legend - 10.0,
+            LEFT + PLOT_WIDTH + 34.0,
+            legend,
+            escape(&curve.name)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, sse_airtricity_20};
+
+    #[test]
+    fn the_running_total_ends_at_the_bill() {
+        let data = vec![
+            reading_at("08-01-2024 18:00", 2.0),
+            reading_at("10-01-2024 03:30", 1.0),
+        ];
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let plans: Vec<Box<dyn PricePlanStrategy>> = vec![Box::new(sse_airtricity_20())];
+        let curves = cumulative_costs(&plans, &data, &period);
+        assert_eq!(curves[0].points.len(), 3);
+        // the middle day has no readings and only pays the fixed charges
+        let bill = plans[0].compute_bill_breakdown(&data, &period).total;
+        let (_, end) = curves[0].points[2];
+        assert!((end - bill.signed_amount()).abs() < Decimal::new(2, 2));
+        assert!(render_cost_chart_svg(&curves).contains("<polyline"));
+    }
+}
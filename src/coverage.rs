@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::{
+    validation::local_day_minutes, BillingPeriod, EstimatedPeriod, GapFilling, SmartMeterData,
+    SmartMeterDataType,
+};
+
+// The share of the intervals a ranking needs actual readings for to be trusted
+pub const DEFAULT_MIN_COVERAGE: f32 = 0.9;
+
+// How much of the billing period the imports were read for, the intervals of each local day
+// split into the actual readings, the ones filled in for short gaps, the ones estimated from
+// manual readings or usage totals and the ones with nothing at all. A ranking on part of the
+// period leaves out whatever the missing intervals would have cost
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Coverage {
+    pub expected_intervals: usize,
+    pub actual_intervals: usize,
+    pub filled_intervals: usize,
+    pub estimated_intervals: usize,
+    pub missing_intervals: usize,
+}
+
+impl Coverage {
+    // The fraction of the intervals with actual readings
+    pub fn actual_share(&self) -> f32 {
+        self.share(self.actual_intervals)
+    }
+
+    pub fn share(&self, intervals: usize) -> f32 {
+        if self.expected_intervals == 0 {
+            return 0.0;
+        }
+        intervals as f32 / self.expected_intervals as f32
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.actual_intervals >= self.expected_intervals
+    }
+}
+
+pub fn coverage(
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+    filling: &GapFilling,
+    estimated: &[EstimatedPeriod],
+) -> Coverage {
+    let imports: Vec<&SmartMeterData> = datapoints
+        .iter()
+        .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+        .collect();
+    let interval = imports
+        .first()
+        .map_or(30, |d| i64::from(d.interval_minutes));
+    if interval == 0 {
+        return Coverage::default();
+    }
+    // the actual, filled and estimated readings of each day
+    let mut per_day: HashMap<_, (usize, usize, usize)> = HashMap::new();
+    for datapoint in imports {
+        let date = datapoint.date();
+        let day = per_day.entry(date).or_default();
+        if estimated.iter().any(|e| (e.from..=e.to).contains(&date)) {
+            day.2 += 1;
+        } else if filling.strategy_for(datapoint).is_some() {
+            day.1 += 1;
+        } else {
+            day.0 += 1;
+        }
+    }
+    let mut coverage = Coverage::default();
+    for date in period.days() {
+        let expected = (local_day_minutes(date) / interval) as usize;
+        let (actual, filled, estimated) = per_day.get(&date).copied().unwrap_or_default();
+        // a duplicated reading doesn't make up for a missing one on the same day
+        let actual = actual.min(expected);
+        let filled = filled.min(expected - actual);
+        let estimated = estimated.min(expected - actual - filled);
+        coverage.expected_intervals += expected;
+        coverage.actual_intervals += actual;
+        coverage.filled_intervals += filled;
+        coverage.estimated_intervals += estimated;
+        coverage.missing_intervals += expected - actual - filled - estimated;
+    }
+    coverage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, Gap};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn the_intervals_are_split_by_where_they_came_from() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        // a whole day of readings on the 8th, one filled in, the 9th estimated and the 10th
+        // left without any
+        let midnight = date(8).and_hms_opt(0, 0, 0).unwrap();
+        let mut data: Vec<SmartMeterData> = (1..=48)
+            .map(|n| {
+                let end = midnight + chrono::Duration::minutes(30 * n);
+                reading_at(&end.format("%d-%m-%Y %H:%M").to_string(), 1.0)
+            })
+            .collect();
+        data.push(reading_at("09-01-2024 12:00", 1.0));
+        let filled = data[10].clone();
+        let filling = GapFilling {
+            filled: vec![crate::FilledGap {
+                gap: Gap {
+                    mprn: filled.mprn.to_string(),
+                    read_type: filled.read_type,
+                    after: data[9].read_data_and_end_time,
+                    before: data[11].read_data_and_end_time,
+                    missing_intervals: 1,
+                },
+                strategy: crate::GapFillStrategy::Zero,
+            }],
+            left: vec![],
+        };
+        let estimated = [EstimatedPeriod {
+            from: date(9),
+            to: date(9),
+            kwh: 0.5,
+        }];
+        let period = BillingPeriod {
+            start: date(8),
+            end: date(10),
+        };
+        let coverage = coverage(&data, &period, &filling, &estimated);
+        assert_eq!(
+            coverage,
+            Coverage {
+                expected_intervals: 144,
+                actual_intervals: 47,
+                filled_intervals: 1,
+                estimated_intervals: 1,
+                missing_intervals: 95,
+            }
+        );
+        assert!(!coverage.is_complete());
+        assert!((coverage.actual_share() - 47.0 / 144.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{bail, Context, Result};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+use crate::{StandingChargeDefinition, TimeOfUseTariff};
+
+// A flat plan given on the command line, like a deal seen on a comparison site:
+// myplan:unit=0.32,standing=0.70/day,export=0.20,discount=15%
+// standing can also be per year, standing=255.50/year, and bonus is a sign up bonus in euro.
+// Like the tariff files the rates include VAT and the discount is off the unit rate
+#[derive(Debug, Clone)]
+pub struct CustomPlan {
+    pub name: String,
+    pub unit_rate: Decimal,
+    pub standing_charge: StandingChargeDefinition,
+    pub export_rate: Decimal,
+    pub discount: Decimal,
+    pub sign_up_bonus: Decimal,
+}
+
+fn amount(key: &str, value: &str) -> Result<Decimal> {
+    value
+        .trim()
+        .parse()
+        .with_context(|| format!("{key}={value} is not an amount like 0.32"))
+}
+
+impl FromStr for CustomPlan {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<CustomPlan> {
+        let Some((name, fields)) = text.split_once(':') else {
+            bail!("{text} is not a plan like myplan:unit=0.32,standing=0.70/day");
+        };
+        if name.trim().is_empty() {
+            bail!("the plan {text} has no name");
+        }
+        let mut unit_rate = None;
+        let mut plan = CustomPlan {
+            name: name.trim().to_string(),
+            unit_rate: Decimal::ZERO,
+            standing_charge: StandingChargeDefinition::PerDay(Decimal::ZERO),
+            export_rate: Decimal::ZERO,
+            discount: Decimal::ZERO,
+            sign_up_bonus: Decimal::ZERO,
+        };
+        for field in fields.split(',').filter(|field| !field.trim().is_empty()) {
+            let Some((key, value)) = field.split_once('=') else {
+                bail!("{field} in {text} is not a key=value pair");
+            };
+            match key.trim() {
+                "unit" => unit_rate = Some(amount(key, value)?),
+                "standing" => {
+                    plan.standing_charge = match value.trim().split_once('/') {
+                        None | Some((_, "day")) => StandingChargeDefinition::PerDay(amount(
+                            key,
+                            value.trim().trim_end_matches("/day"),
+                        )?),
+                        Some((annual, "year")) => {
+                            StandingChargeDefinition::Annual(amount(key, annual)?)
+                        }
+                        Some((_, per)) => {
+                            bail!("standing={value} is per {per}, it can be per day or per year")
+                        }
+                    }
+                }
+                "export" => plan.export_rate = amount(key, value)?,
+                "discount" => {
+                    plan.discount = match value.trim().strip_suffix('%') {
+                        Some(percent) => amount(key, percent)? / Decimal::ONE_HUNDRED,
+                        None => amount(key, value)?,
+                    };
+                    if !(Decimal::ZERO..Decimal::ONE).contains(&plan.discount) {
+                        bail!("discount={value} is not a discount like 15% or 0.15");
+                    }
+                }
+                "bonus" => plan.sign_up_bonus = amount(key, value)?,
+                other => bail!(
+                    "unknown {other} in {text}, the plan takes unit, standing, export, discount and bonus"
+                ),
+            }
+        }
+        plan.unit_rate = unit_rate.with_context(|| format!("the plan {text} has no unit rate"))?;
+        Ok(plan)
+    }
+}
+
+impl CustomPlan {
+    // The same as a plan of the tariff file with a single band all day
+    pub fn to_plan(&self) -> Result<TimeOfUseTariff> {
+        TimeOfUseTariff::builder(self.name.clone())
+            .default_rate("standard", self.unit_rate)
+            .discount(self.discount)
+            .export_rate(self.export_rate)
+            .standing_charge(self.standing_charge)
+            .sign_up_bonus(self.sign_up_bonus)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, PricePlanStrategy};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn a_plan_from_a_flag() {
+        let plan: CustomPlan = "myplan:unit=0.32,standing=255.50/year,export=0.20,discount=15%"
+            .parse()
+            .unwrap();
+        assert_eq!(plan.discount, dec!(0.15));
+        assert!(matches!(
+            plan.standing_charge,
+            StandingChargeDefinition::Annual(charge) if charge == dec!(255.50)
+        ));
+        // 1 kWh at 32 cent with 15% off
+        let price = plan
+            .to_plan()
+            .unwrap()
+            .price_for_singe_period(&reading_at("08-01-2024 12:00", 2.0));
+        assert_eq!(price.signed_amount(), dec!(0.272));
+
+        assert!("myplan:standing=0.70/day".parse::<CustomPlan>().is_err());
+        assert!("myplan:unit=0.32,discount=150%"
+            .parse::<CustomPlan>()
+            .is_err());
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Who the meter point belongs to, the supplier prices homes and businesses from different
+// price lists. Businesses on low voltage maximum demand (LVMD) connections pay higher
+// standing charges and charges on their maximum import capacity, see DemandCharge
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomerType {
+    #[default]
+    Domestic,
+    Business,
+}
+
+impl CustomerType {
+    pub fn is_domestic(&self) -> bool {
+        *self == CustomerType::Domestic
+    }
+}
+
+impl fmt::Display for CustomerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CustomerType::Domestic => f.write_str("domestic"),
+            CustomerType::Business => f.write_str("business"),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use crate::{read_csv, PricePoint};
+
+const ENTSOE_API: &str = "https://web-api.tp.entsoe.eu/api";
+// The bidding zone of the Irish single electricity market
+const SEM_BIDDING_ZONE: &str = "10Y1001A1001A59C";
+// The datetimes of the api, always in UTC
+const ENTSOE_DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%MZ";
+
+// The text between the first <tag> and its </tag>
+fn tag_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&format!("</{tag}>"))?;
+    Some(xml[start..end].trim())
+}
+
+// Every block between <tag> and </tag>, without nesting
+fn blocks<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+    let mut found = vec![];
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let inner = &rest[start + open.len()..];
+        let Some(end) = inner.find(&close) else {
+            break;
+        };
+        found.push(&inner[..end]);
+        rest = &inner[end + close.len()..];
+    }
+    found
+}
+
+fn resolution_minutes(resolution: &str) -> Result<i64> {
+    match resolution {
+        "PT15M" => Ok(15),
+        "PT30M" => Ok(30),
+        "PT60M" => Ok(60),
+        other => bail!("unexpected resolution {other} in the day ahead prices"),
+    }
+}
+
+// Reads the prices out of an ENTSO-E day ahead document. Prices are in euro per MWh and are
+// turned into cent per kWh, points the document leaves out repeat the one before
+pub fn parse_day_ahead_document(xml: &str) -> Result<Vec<PricePoint>> {
+    if let Some(reason) = tag_text(xml, "Reason") {
+        if tag_text(xml, "TimeSeries").is_none() {
+            bail!(
+                "the day ahead prices are not available: {}",
+                tag_text(reason, "text").unwrap_or(reason)
+            );
+        }
+    }
+    let mut points = vec![];
+    for period in blocks(xml, "Period") {
+        let interval = tag_text(period, "timeInterval").context("period without an interval")?;
+        let parse = |tag| -> Result<NaiveDateTime> {
+            let text = tag_text(interval, tag).context("interval without a start or end")?;
+            Ok(NaiveDateTime::parse_from_str(text, ENTSOE_DATETIME_FORMAT)?)
+        };
+        let (start, end) = (parse("start")?, parse("end")?);
+        let step = Duration::minutes(resolution_minutes(
+            tag_text(period, "resolution").context("period without a resolution")?,
+        )?);
+        let count = ((end - start).num_minutes() / step.num_minutes()) as usize;
+
+        let mut prices: Vec<Option<Decimal>> = vec![None; count];
+        for point in blocks(period, "Point") {
+            let position: usize = tag_text(point, "position")
+                .context("point without a position")?
+                .parse()?;
+            let price = Decimal::from_str(
+                tag_text(point, "price.amount").context("point without a price")?,
+            )?;
+            if let Some(slot) = position.checked_sub(1).and_then(|i| prices.get_mut(i)) {
+                *slot = Some(price);
+            }
+        }
+        let mut last = None;
+        for (index, price) in prices.into_iter().enumerate() {
+            last = price.or(last);
+            if let Some(price) = last {
+                points.push(PricePoint {
+                    start: start + step * index as i32,
+                    price: price / Decimal::TEN,
+                });
+            }
+        }
+    }
+    Ok(points)
+}
+
+// The curl config with the url of the document. The url has the token in it, so it goes in
+// through stdin and not on the command line where every user of the machine can see it
+fn curl_config(token: &str, start: NaiveDate, end: NaiveDate) -> String {
+    let format = |date: NaiveDate| date.format("%Y%m%d0000").to_string();
+    let url = format!(
+        "{ENTSOE_API}?documentType=A44&in_Domain={SEM_BIDDING_ZONE}&out_Domain={SEM_BIDDING_ZONE}&periodStart={}&periodEnd={}&securityToken={token}",
+        format(start),
+        format(end)
+    );
+    let quoted = url.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("url = \"{quoted}\"\n")
+}
+
+fn fetch_document(token: &str, start: NaiveDate, end: NaiveDate) -> Result<String> {
+    // curl is on every machine that can reach the api, so there's no http client to build
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("could not run curl to download the day ahead prices")?;
+    child
+        .stdin
+        .take()
+        .context("curl has no stdin")?
+        .write_all(curl_config(token, start, end).as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "downloading the day ahead prices failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+// Writes the prices in the format of the price files of dynamic plans
+pub fn write_price_file(points: &[PricePoint], writer: impl Write) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    for point in points {
+        wtr.serialize(point)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// Downloads the day ahead prices of every month from the month of from up to the one of to,
+// keeping one price file per month in cache_dir. Months that are over are only downloaded once
+pub fn fetch_day_ahead_prices(
+    token: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    cache_dir: &Path,
+    today: NaiveDate,
+) -> Result<Vec<PricePoint>> {
+    fs::create_dir_all(cache_dir)?;
+    let mut points = vec![];
+    let mut month = from.with_day(1).context("invalid from date")?;
+    while month <= to {
+        let next = month
+            .checked_add_months(chrono::Months::new(1))
+            .context("date out of range")?;
+        let cache = cache_dir.join(format!("day-ahead-{}.csv", month.format("%Y-%m")));
+        let month_points: Vec<PricePoint> = if cache.exists() && next <= today {
+            read_csv(&cache)?
+        } else {
+            let fetched = parse_day_ahead_document(&fetch_document(token, month, next)?)?;
+            if next <= today {
+                write_price_file(&fetched, File::create(&cache)?)?;
+            }
+            fetched
+        };
+        points.extend(month_points.into_iter().filter(|point| {
+            let date = point.start.date();
+            date >= from && date <= to
+        }));
+        month = next;
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn the_token_goes_in_the_quoted_config() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let config = curl_config("ab\"c", date(1), date(2));
+        assert!(config.starts_with(&format!("url = \"{ENTSOE_API}?documentType=A44")));
+        assert!(config.contains("&periodStart=202401010000&periodEnd=202401020000"));
+        assert!(config.ends_with("&securityToken=ab\\\"c\"\n"));
+    }
+
+    #[test]
+    fn left_out_points_repeat_the_one_before() {
+        let xml = r#"<Publication_MarketDocument>
+            <TimeSeries><Period>
+                <timeInterval><start>2024-01-07T23:00Z</start><end>2024-01-08T02:00Z</end></timeInterval>
+                <resolution>PT60M</resolution>
+                <Point><position>1</position><price.amount>98.5</price.amount></Point>
+                <Point><position>3</position><price.amount>-4.2</price.amount></Point>
+            </Period></TimeSeries>
+        </Publication_MarketDocument>"#;
+        let points = parse_day_ahead_document(xml).unwrap();
+        let prices: Vec<(String, Decimal)> = points
+            .iter()
+            .map(|p| (p.start.format("%H:%M").to_string(), p.price))
+            .collect();
+        assert_eq!(
+            prices,
+            vec![
+                ("23:00".to_string(), dec!(9.85)),
+                ("00:00".to_string(), dec!(9.85)),
+                ("01:00".to_string(), dec!(-0.42)),
+            ]
+        );
+    }
+}
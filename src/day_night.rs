@@ -0,0 +1,233 @@
+use chrono::{NaiveDate, NaiveTime};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+use crate::{
+    projection::default_discount_months, time_in_window, vat::default_vat_included, Currency,
+    Discount, DuosGroup, EnergyBillEntry, ExportRules, Money, Numeric, PlanMetadata,
+    PricePlanStrategy, PricingNumber, Rounding, SmartMeterData, SmartMeterDataType, StandingCharge,
+    StandingChargeDefinition,
+};
+
+// Older day/night meters switch register on GMT all year and ignore the clocks, so in summer
+// the night register runs from 00:00 to 09:00 local time
+const NIGHT_REGISTER_START_TIME: NaiveTime = match NaiveTime::from_hms_opt(23, 0, 0) {
+    Some(t) => t,
+    None => panic!("Must be a valid time"),
+};
+const NIGHT_REGISTER_END_TIME: NaiveTime = match NaiveTime::from_hms_opt(8, 0, 0) {
+    Some(t) => t,
+    None => panic!("Must be a valid time"),
+};
+
+// The two registers of a day/night meter, like the ones NightSaver tariffs are billed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Register {
+    Day,
+    Night,
+}
+
+impl Register {
+    // The register a half hour reading would have been counted on, readings are in UTC so
+    // they line up with the meter without any conversion
+    pub fn for_reading(datapoint: &SmartMeterData) -> Register {
+        let time = datapoint.read_data_and_end_time.time();
+        if time_in_window(time, NIGHT_REGISTER_START_TIME, NIGHT_REGISTER_END_TIME) {
+            Register::Night
+        } else {
+            Register::Day
+        }
+    }
+}
+
+// The imported energy on each register, what a day/night meter shows between two reads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegisterReads {
+    pub day_kwh: Decimal,
+    pub night_kwh: Decimal,
+}
+
+// Adds the half hour imports up onto the registers, so smart data can be compared against
+// a register tariff
+pub fn register_reads(datapoints: &[SmartMeterData]) -> RegisterReads {
+    let (mut day, mut night) = (PricingNumber::default(), PricingNumber::default());
+    for datapoint in datapoints
+        .iter()
+        .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+    {
+        let kwh = PricingNumber::from_f32(datapoint.energy_kwh());
+        match Register::for_reading(datapoint) {
+            Register::Day => day += kwh,
+            Register::Night => night += kwh,
+        }
+    }
+    // a decimal once for each register, not for each reading
+    RegisterReads {
+        day_kwh: day.to_decimal(),
+        night_kwh: night.to_decimal(),
+    }
+}
+
+// A tariff with one rate per register instead of time of use bands
+#[derive(Clone, Deserialize)]
+pub struct DayNightPlan {
+    pub name: String,
+    pub day_rate: Decimal,
+    pub night_rate: Decimal,
+    #[serde(default)]
+    pub discount: Discount,
+    #[serde(default = "default_discount_months")]
+    pub discount_months: u32,
+    #[serde(default)]
+    pub fixed_rate_months: u32,
+    #[serde(default)]
+    pub sign_up_bonus: Decimal,
+    #[serde(default)]
+    pub currency: Currency,
+    #[serde(default = "default_vat_included")]
+    pub vat_included: Decimal,
+    #[serde(default)]
+    pub export_rate: Decimal,
+    #[serde(default)]
+    pub export: ExportRules,
+    pub standing_charge: StandingChargeDefinition,
+    // charged instead of standing_charge for rural meter points, see DuosGroup
+    #[serde(default)]
+    pub rural_standing_charge: Option<StandingChargeDefinition>,
+    #[serde(default)]
+    pub rounding: Rounding,
+    #[serde(default)]
+    pub metadata: PlanMetadata,
+}
+
+// The name is enough to identify the plan on the output
+impl Debug for DayNightPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl DayNightPlan {
+    fn rate(&self, register: Register) -> Decimal {
+        let rate = match register {
+            Register::Day => self.day_rate,
+            Register::Night => self.night_rate,
+        };
+        self.discount.on_unit_rate(rate)
+    }
+
+    // The cost of the energy on the registers, for households that only have meter reads
+    pub fn price_registers(&self, reads: &RegisterReads) -> EnergyBillEntry {
+        EnergyBillEntry::Debit(
+            Money::new(
+                reads.day_kwh * self.rate(Register::Day)
+                    + reads.night_kwh * self.rate(Register::Night),
+            )
+            .round(&self.rounding),
+        )
+    }
+}
+
+impl PricePlanStrategy for DayNightPlan {
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => EnergyBillEntry::Debit(Money::for_quantity(
+                self.rate(Register::for_reading(datapoint)),
+                datapoint.energy_kwh(),
+            )),
+            SmartMeterDataType::ActiveExport => EnergyBillEntry::Credit(Money::for_quantity(
+                self.export_rate,
+                datapoint.energy_kwh(),
+            )),
+        }
+    }
+
+    fn band_for_singe_period(&self, datapoint: &SmartMeterData) -> String {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => match Register::for_reading(datapoint) {
+                Register::Day => "day".to_string(),
+                Register::Night => "night".to_string(),
+            },
+            SmartMeterDataType::ActiveExport => "export".to_string(),
+        }
+    }
+
+    fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    fn export_rules(&self) -> ExportRules {
+        self.export
+    }
+
+    fn at_standard_rates(&self) -> Option<Box<dyn PricePlanStrategy>> {
+        Some(Box::new(DayNightPlan {
+            discount: Discount::default(),
+            ..self.clone()
+        }))
+    }
+
+    fn discount(&self) -> Discount {
+        self.discount
+    }
+
+    fn discount_months(&self) -> u32 {
+        self.discount_months
+    }
+
+    fn fixed_rate_months(&self) -> u32 {
+        self.fixed_rate_months
+    }
+
+    fn sign_up_bonus(&self) -> Money {
+        Money::of(self.sign_up_bonus, self.currency)
+    }
+
+    fn metadata(&self) -> PlanMetadata {
+        self.metadata.clone()
+    }
+}
+
+impl StandingCharge for DayNightPlan {
+    fn standing_charge_per_day(&self, date: NaiveDate, group: DuosGroup) -> EnergyBillEntry {
+        self.discount.on_standing_charge(
+            group
+                .pick(self.standing_charge, self.rural_standing_charge)
+                .for_day(date),
+        )
+    }
+
+    fn vat_included(&self) -> Decimal {
+        self.vat_included
+    }
+
+    fn currency(&self) -> Currency {
+        self.currency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn registers_follow_gmt_in_summer() {
+        // 08:00 UTC is 09:00 irish summer time and still the night register
+        let data = vec![
+            reading_at("15-07-2024 08:00", 2.0),
+            reading_at("15-07-2024 08:30", 2.0),
+            reading_at("15-07-2024 23:30", 4.0),
+        ];
+        assert_eq!(
+            register_reads(&data),
+            RegisterReads {
+                day_kwh: dec!(1),
+                night_kwh: dec!(3),
+            }
+        );
+    }
+}
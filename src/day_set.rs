@@ -0,0 +1,212 @@
+use anyhow::{bail, Result};
+use chrono::{NaiveDate, Weekday};
+use serde::Deserialize;
+use std::{fmt::Display, str::FromStr};
+
+use crate::HolidayCalendar;
+
+const WEEKDAYS: [Weekday; 5] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+];
+const WEEKENDS: [Weekday; 2] = [Weekday::Sat, Weekday::Sun];
+
+// What a set of days does with the bank holidays of the calendar, see HolidayCalendar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HolidayRule {
+    #[default]
+    Include,
+    Exclude,
+    Only,
+}
+
+// The days a band or a free window applies on: some weekdays or every day without any, with
+// the bank holidays in them, left out or on their own. A tariff file gives either a list like
+// ["Sat", "Sun"] or one of "all", "weekdays", "weekends" and "holidays", followed by
+// " except holidays" to leave them out, like "weekdays except holidays" for a peak
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "DaySetFields")]
+pub struct DaySet {
+    pub weekdays: Vec<Weekday>,
+    pub holidays: HolidayRule,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DaySetFields {
+    Weekdays(Vec<Weekday>),
+    Named(String),
+}
+
+impl TryFrom<DaySetFields> for DaySet {
+    type Error = anyhow::Error;
+
+    fn try_from(fields: DaySetFields) -> Result<DaySet> {
+        match fields {
+            DaySetFields::Weekdays(weekdays) => Ok(DaySet::of(&weekdays)),
+            DaySetFields::Named(text) => text.parse(),
+        }
+    }
+}
+
+impl DaySet {
+    pub fn all() -> DaySet {
+        DaySet::default()
+    }
+
+    pub fn of(weekdays: &[Weekday]) -> DaySet {
+        DaySet {
+            weekdays: weekdays.to_vec(),
+            holidays: HolidayRule::Include,
+        }
+    }
+
+    pub fn weekdays() -> DaySet {
+        DaySet::of(&WEEKDAYS)
+    }
+
+    pub fn weekends() -> DaySet {
+        DaySet::of(&WEEKENDS)
+    }
+
+    pub fn holidays() -> DaySet {
+        DaySet {
+            weekdays: vec![],
+            holidays: HolidayRule::Only,
+        }
+    }
+
+    pub fn except_holidays(self) -> DaySet {
+        DaySet {
+            holidays: HolidayRule::Exclude,
+            ..self
+        }
+    }
+
+    // Whether the set has a day of the week, on a bank holiday or on any other day
+    pub fn applies(&self, weekday: Weekday, holiday: bool) -> bool {
+        (self.weekdays.is_empty() || self.weekdays.contains(&weekday))
+            && match self.holidays {
+                HolidayRule::Include => true,
+                HolidayRule::Exclude => !holiday,
+                HolidayRule::Only => holiday,
+            }
+    }
+
+    // Whether a day is in both sets
+    pub fn overlaps(&self, other: &DaySet) -> bool {
+        WEEKDAYS.iter().chain(&WEEKENDS).any(|&weekday| {
+            [false, true]
+                .into_iter()
+                .any(|holiday| self.applies(weekday, holiday) && other.applies(weekday, holiday))
+        })
+    }
+
+    // weekday is the one the plan prices the date like, a plan can price bank holidays like
+    // a sunday. The calendar is only asked when the set cares about holidays
+    pub fn contains(&self, weekday: Weekday, date: NaiveDate) -> bool {
+        let holiday =
+            self.holidays != HolidayRule::Include && HolidayCalendar::global().is_holiday(date);
+        self.applies(weekday, holiday)
+    }
+}
+
+impl FromStr for DaySet {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<DaySet> {
+        let lower = text.trim().to_lowercase();
+        let (days, except_holidays) = match lower.strip_suffix("except holidays") {
+            Some(days) => (days.trim(), true),
+            None => (lower.as_str(), false),
+        };
+        let set = match days {
+            "all" | "every day" => DaySet::all(),
+            "weekdays" => DaySet::weekdays(),
+            "weekends" => DaySet::weekends(),
+            "holidays" if !except_holidays => DaySet::holidays(),
+            days => {
+                let weekdays: Vec<Weekday> = days
+                    .split([' ', ','])
+                    .filter(|day| !day.is_empty())
+                    .map(|day| day.parse::<Weekday>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| {
+                        anyhow::anyhow!(
+                            "{text} is not days like \"weekdays\", \"weekends except holidays\" or \"Sat Sun\""
+                        )
+                    })?;
+                if weekdays.is_empty() {
+                    bail!("{text} has no days in it");
+                }
+                DaySet::of(&weekdays)
+            }
+        };
+        Ok(if except_holidays {
+            set.except_holidays()
+        } else {
+            set
+        })
+    }
+}
+
+// Like Sat Sun, every day except holidays or holidays
+impl Display for DaySet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let days: Vec<String> = self.weekdays.iter().map(Weekday::to_string).collect();
+        match (self.holidays, days.is_empty()) {
+            (HolidayRule::Only, true) => f.write_str("holidays"),
+            (HolidayRule::Only, false) => write!(f, "holidays on {}", days.join(" ")),
+            (rule, empty) => {
+                f.write_str(if empty { "every day" } else { "" })?;
+                f.write_str(&days.join(" "))?;
+                if rule == HolidayRule::Exclude {
+                    f.write_str(" except holidays")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day_sets_are_read_from_lists_and_names() {
+        #[derive(Deserialize)]
+        struct Band {
+            days: DaySet,
+        }
+        let days = |toml: &str| toml::from_str::<Band>(toml).map(|band| band.days);
+        assert_eq!(
+            days(r#"days = ["Sat", "Sun"]"#).unwrap(),
+            DaySet::weekends()
+        );
+        assert_eq!(days("days = []").unwrap(), DaySet::all());
+        let peak = days(r#"days = "weekdays except holidays""#).unwrap();
+        assert_eq!(peak, DaySet::weekdays().except_holidays());
+        assert!(days(r#"days = "fortnights""#).is_err());
+
+        assert!(peak.applies(Weekday::Mon, false));
+        assert!(!peak.applies(Weekday::Mon, true));
+        assert!(!peak.applies(Weekday::Sat, false));
+        assert!(DaySet::holidays().applies(Weekday::Sat, true));
+        assert!(!DaySet::holidays().applies(Weekday::Mon, false));
+        // st patrick's day of 2025 is a monday
+        let holiday = NaiveDate::from_ymd_opt(2025, 3, 17).unwrap();
+        assert!(!peak.contains(Weekday::Mon, holiday));
+        assert!(DaySet::weekdays().contains(Weekday::Mon, holiday));
+
+        assert_eq!(peak.to_string(), "Mon Tue Wed Thu Fri except holidays");
+        assert_eq!(
+            "every day except holidays".parse::<DaySet>().unwrap(),
+            DaySet::all().except_holidays()
+        );
+        assert_eq!(DaySet::all().to_string(), "every day");
+    }
+}
@@ -0,0 +1,393 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt::Debug};
+
+use crate::{
+    projection::days_in_month, with_vat_on, BillingPeriod, Currency, Discount, DuosGroup,
+    EnergyBillEntry, ExportRules, FreeTime, FreeWindow, Money, PlanMetadata, PricePlanStrategy,
+    Rounding, SmartMeterData, SmartMeterDataType, StandingCharge,
+};
+
+// A band of the connection capacity, the daily charge of a house whose maximum demand is up
+// to up_to_kw, the band without a limit takes every demand above the others
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct CapacityBand {
+    #[serde(default)]
+    pub up_to_kw: Option<f32>,
+    pub per_day: Decimal,
+}
+
+// Charges on the highest demand rather than on the energy, like network charges and small
+// business tariffs have. per_kw is charged on the highest average power of any interval of
+// each month, for the days of the month in the period, and the capacity band is picked by the
+// highest of the whole period. Business connections also pay per_kva_mic a day for each kVA of
+// their maximum import capacity, and excess_per_kva for each kVA the peak of a month went over
+// it, taking a kW for a kVA
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct DemandCharge {
+    #[serde(default)]
+    pub per_kw: Decimal,
+    #[serde(default)]
+    pub capacity: Vec<CapacityBand>,
+    #[serde(default)]
+    pub per_kva_mic: Decimal,
+    #[serde(default)]
+    pub excess_per_kva: Decimal,
+}
+
+// The maximum import capacity (MIC) of the connection in kVA, agreed with ESB Networks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaximumImportCapacity(pub f32);
+
+fn kw(datapoint: &SmartMeterData) -> f32 {
+    datapoint.energy_kwh() * 60.0 / datapoint.interval_minutes as f32
+}
+
+// The highest average power of the imports of any interval, none without imports
+pub fn max_demand_kw<'a>(datapoints: impl IntoIterator<Item = &'a SmartMeterData>) -> Option<f32> {
+    datapoints
+        .into_iter()
+        .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+        .map(kw)
+        .reduce(f32::max)
+}
+
+impl DemandCharge {
+    pub fn is_empty(&self) -> bool {
+        self.per_kw.is_zero()
+            && self.capacity.is_empty()
+            && self.per_kva_mic.is_zero()
+            && self.excess_per_kva.is_zero()
+    }
+
+    // The band of the demand, the smallest that takes it
+    pub fn capacity_band(&self, kw: f32) -> Option<&CapacityBand> {
+        self.capacity
+            .iter()
+            .filter(|band| band.up_to_kw.is_none_or(|limit| kw <= limit))
+            .min_by(|a, b| {
+                let limit = |band: &CapacityBand| band.up_to_kw.unwrap_or(f32::MAX);
+                limit(a).total_cmp(&limit(b))
+            })
+    }
+
+    // Like the other charges of a plan the figures include vat_included of VAT and are
+    // repriced with the rate of each day. Without a MIC the connection is taken to be sized
+    // for the peak of the period, to the next whole kVA, so nothing is over it
+    pub fn for_period(
+        &self,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+        vat_included: Decimal,
+        currency: Currency,
+        mic: Option<MaximumImportCapacity>,
+    ) -> EnergyBillEntry {
+        if self.is_empty() {
+            return EnergyBillEntry::ZERO;
+        }
+        let Some(peak) = max_demand_kw(datapoints) else {
+            return EnergyBillEntry::ZERO;
+        };
+        let mut months: BTreeMap<(i32, u32), Vec<&SmartMeterData>> = BTreeMap::new();
+        for datapoint in datapoints {
+            let date = datapoint.date();
+            months
+                .entry((date.year(), date.month()))
+                .or_default()
+                .push(datapoint);
+        }
+        let band = self.capacity_band(peak).map(|band| band.per_day);
+        let mic = mic.map_or(peak.ceil(), |mic| mic.0);
+        let capacity = self.per_kva_mic * Decimal::from_f32(mic).unwrap_or_default();
+        period
+            .days()
+            .map(|date| {
+                let month = months
+                    .get(&(date.year(), date.month()))
+                    .and_then(|readings| max_demand_kw(readings.iter().copied()))
+                    .unwrap_or(0.0);
+                let decimal = |kw: f32| Decimal::from_f32(kw).unwrap_or_default();
+                let demand = (self.per_kw * decimal(month)
+                    + self.excess_per_kva * decimal((month - mic).max(0.0)))
+                    / Decimal::from(days_in_month(date));
+                let charge = EnergyBillEntry::Debit(Money::new(
+                    demand + capacity + band.unwrap_or_default(),
+                ));
+                with_vat_on(charge, vat_included, date, currency)
+            })
+            .sum()
+    }
+}
+
+// A plan priced for the maximum import capacity of a connection, plans on their own are
+// priced as if it was sized for their peak. Like InDuosGroup this lets the server price each
+// upload for its own connection
+#[derive(Debug)]
+pub struct WithImportCapacity {
+    plan: Box<dyn PricePlanStrategy>,
+    mic: MaximumImportCapacity,
+}
+
+impl WithImportCapacity {
+    pub fn wrap(
+        plan: Box<dyn PricePlanStrategy>,
+        mic: MaximumImportCapacity,
+    ) -> Box<dyn PricePlanStrategy> {
+        Box::new(WithImportCapacity { plan, mic })
+    }
+}
+
+impl StandingCharge for WithImportCapacity {
+    fn standing_charge_per_day(&self, date: NaiveDate, group: DuosGroup) -> EnergyBillEntry {
+        self.plan.standing_charge_per_day(date, group)
+    }
+
+    fn vat_included(&self) -> Decimal {
+        self.plan.vat_included()
+    }
+
+    fn currency(&self) -> Currency {
+        self.plan.currency()
+    }
+
+    fn standing_charge_for_period(&self, period: &BillingPeriod) -> EnergyBillEntry {
+        self.plan.standing_charge_for_period(period)
+    }
+}
+
+impl PricePlanStrategy for WithImportCapacity {
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+        self.plan.price_for_singe_period(datapoint)
+    }
+
+    fn band_for_singe_period(&self, datapoint: &SmartMeterData) -> String {
+        self.plan.band_for_singe_period(datapoint)
+    }
+
+    fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.plan.is_holiday(date)
+    }
+
+    fn rounding(&self) -> Rounding {
+        self.plan.rounding()
+    }
+
+    fn name(&self) -> String {
+        self.plan.name()
+    }
+
+    fn price_with_accumulated_usage(
+        &self,
+        datapoint: &SmartMeterData,
+        imported_kwh: f32,
+    ) -> EnergyBillEntry {
+        self.plan
+            .price_with_accumulated_usage(datapoint, imported_kwh)
+    }
+
+    fn price_readings(&self, datapoints: &[SmartMeterData]) -> Vec<EnergyBillEntry> {
+        self.plan.price_readings(datapoints)
+    }
+
+    // the plans a plan turns into are on the same connection
+    fn at_standard_rates(&self) -> Option<Box<dyn PricePlanStrategy>> {
+        self.plan
+            .at_standard_rates()
+            .map(|plan| WithImportCapacity::wrap(plan, self.mic))
+    }
+
+    fn discount_months(&self) -> u32 {
+        self.plan.discount_months()
+    }
+
+    fn fixed_rate_months(&self) -> u32 {
+        self.plan.fixed_rate_months()
+    }
+
+    fn discount(&self) -> Discount {
+        self.plan.discount()
+    }
+
+    fn sign_up_bonus(&self) -> Money {
+        self.plan.sign_up_bonus()
+    }
+
+    fn metadata(&self) -> PlanMetadata {
+        self.plan.metadata()
+    }
+
+    fn free_time(&self) -> Option<FreeTime> {
+        self.plan.free_time()
+    }
+
+    fn free_time_choices(&self) -> Vec<(FreeWindow, Box<dyn PricePlanStrategy>)> {
+        self.plan
+            .free_time_choices()
+            .into_iter()
+            .map(|(window, plan)| (window, WithImportCapacity::wrap(plan, self.mic)))
+            .collect()
+    }
+
+    fn is_prepay(&self) -> bool {
+        self.plan.is_prepay()
+    }
+
+    fn pricing_error(&self) -> Option<String> {
+        self.plan.pricing_error()
+    }
+
+    fn pricing_warning(&self, datapoints: &[SmartMeterData]) -> Option<String> {
+        self.plan.pricing_warning(datapoints)
+    }
+
+    fn export_rules(&self) -> ExportRules {
+        self.plan.export_rules()
+    }
+
+    fn usage_adds_up_by_month(&self) -> bool {
+        self.plan.usage_adds_up_by_month()
+    }
+
+    fn cache_version(&self) -> u64 {
+        self.plan.cache_version()
+    }
+
+    fn pso_levy_for_period(&self, period: &BillingPeriod) -> EnergyBillEntry {
+        self.plan.pso_levy_for_period(period)
+    }
+
+    fn demand_charge_with_mic(
+        &self,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+        _mic: Option<MaximumImportCapacity>,
+    ) -> EnergyBillEntry {
+        self.plan
+            .demand_charge_with_mic(datapoints, period, Some(self.mic))
+    }
+
+    fn demand_charge_for_period(
+        &self,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+    ) -> EnergyBillEntry {
+        self.demand_charge_with_mic(datapoints, period, None)
+    }
+
+    fn compute_total_bill_for_period(&self, datapoints: &[SmartMeterData]) -> EnergyBillEntry {
+        self.plan.compute_total_bill_for_period(datapoints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn the_peak_of_each_month_and_the_capacity_band_are_charged() {
+        let charge: DemandCharge = toml::from_str(
+            "per_kw = 3.1\ncapacity = [{ per_day = 0.5 }, { up_to_kw = 6, per_day = 0.1 }, { up_to_kw = 12, per_day = 0.25 }]",
+        )
+        .unwrap();
+        assert_eq!(charge.capacity_band(4.0).unwrap().per_day, dec!(0.1));
+        assert_eq!(charge.capacity_band(7.5).unwrap().per_day, dec!(0.25));
+        assert_eq!(charge.capacity_band(20.0).unwrap().per_day, dec!(0.5));
+
+        // 8 kW for half an hour in january, all of the month in the period
+        let data = vec![
+            reading_at("08-01-2024 18:00", 8.0),
+            reading_at("31-01-2024 18:00", 1.0),
+        ];
+        let period = BillingPeriod {
+            start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+        };
+        let total = charge.for_period(&data, &period, crate::QUOTED_VAT_RATE, Currency::Eur, None);
+        // 3.1 a kW for the month and 31 days of the band up to 12 kW
+        assert_eq!(
+            total.signed_amount().round_dp(2),
+            dec!(8) * dec!(3.1) + dec!(31) * dec!(0.25)
+        );
+        assert_eq!(
+            DemandCharge::default().for_period(
+                &data,
+                &period,
+                crate::QUOTED_VAT_RATE,
+                Currency::Eur,
+                None
+            ),
+            EnergyBillEntry::ZERO
+        );
+    }
+
+    #[test]
+    fn the_capacity_and_what_goes_over_it_are_charged() {
+        let charge: DemandCharge =
+            toml::from_str("per_kva_mic = 0.02\nexcess_per_kva = 5").unwrap();
+        let data = vec![reading_at("08-01-2024 18:00", 8.0)];
+        let period = BillingPeriod {
+            start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+        };
+        let total = |mic| {
+            charge
+                .for_period(&data, &period, crate::QUOTED_VAT_RATE, Currency::Eur, mic)
+                .signed_amount()
+                .round_dp(2)
+        };
+        // 2 kVA over a MIC of 6 in january
+        assert_eq!(
+            total(Some(MaximumImportCapacity(6.0))),
+            dec!(31) * dec!(6) * dec!(0.02) + dec!(2) * dec!(5)
+        );
+        // sized for the peak without one
+        assert_eq!(total(None), dec!(31) * dec!(8) * dec!(0.02));
+    }
+
+    #[test]
+    fn the_same_plan_is_priced_for_each_connection() {
+        let plan = || {
+            toml::from_str::<crate::PlanFile>(
+                r#"
+            [[plan]]
+            name = "Business"
+            standing_charge = { per_day = 0.5 }
+            demand_charge = { per_kva_mic = 0.02 }
+            [[plan.bands]]
+            name = "All day"
+            start = "00:00"
+            end = "00:00"
+            rate = 0.3
+            "#,
+            )
+            .unwrap()
+            .take_electricity_plans()
+            .remove(0)
+        };
+        let data = vec![reading_at("08-01-2024 18:00", 8.0)];
+        let period = BillingPeriod {
+            start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+        };
+        let charge = |kva| {
+            WithImportCapacity::wrap(plan(), MaximumImportCapacity(kva))
+                .demand_charge_for_period(&data, &period)
+                .signed_amount()
+                .round_dp(2)
+        };
+        assert_eq!(charge(30.0), dec!(31) * dec!(30) * dec!(0.02));
+        assert_eq!(charge(12.0), dec!(31) * dec!(12) * dec!(0.02));
+        // on its own the plan is sized for the peak of 8 kW
+        assert_eq!(
+            plan()
+                .demand_charge_for_period(&data, &period)
+                .signed_amount()
+                .round_dp(2),
+            charge(8.0)
+        );
+    }
+}
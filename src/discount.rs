@@ -0,0 +1,163 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::{BillingPeriod, EnergyBillEntry, StandingChargeDefinition};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DiscountFields {
+    UnitRate(Decimal),
+    Components {
+        #[serde(default)]
+        unit_rate: Decimal,
+        #[serde(default)]
+        standing_charge: Decimal,
+        #[serde(default)]
+        bill_credit: Option<StandingChargeDefinition>,
+    },
+}
+
+// What a supplier takes off the bill, suppliers word it differently: a fraction off the unit
+// rates, a fraction off the standing charge or a fixed credit. A plain number like
+// discount = 0.20 is off the unit rates, like the discounts have always been
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(from = "DiscountFields")]
+pub struct Discount {
+    pub unit_rate: Decimal,
+    pub standing_charge: Decimal,
+    // credited every day like a negative standing charge, given per day or per year
+    pub bill_credit: Option<StandingChargeDefinition>,
+}
+
+impl From<DiscountFields> for Discount {
+    fn from(fields: DiscountFields) -> Discount {
+        match fields {
+            DiscountFields::UnitRate(unit_rate) => Discount::from(unit_rate),
+            DiscountFields::Components {
+                unit_rate,
+                standing_charge,
+                bill_credit,
+            } => Discount {
+                unit_rate,
+                standing_charge,
+                bill_credit,
+            },
+        }
+    }
+}
+
+impl From<Decimal> for Discount {
+    fn from(unit_rate: Decimal) -> Discount {
+        Discount {
+            unit_rate,
+            ..Discount::default()
+        }
+    }
+}
+
+impl Discount {
+    pub fn is_none(&self) -> bool {
+        self.unit_rate.is_zero() && self.standing_charge.is_zero() && self.bill_credit.is_none()
+    }
+
+    pub fn on_unit_rate(&self, rate: Decimal) -> Decimal {
+        rate * (Decimal::ONE - self.unit_rate)
+    }
+
+    pub fn on_standing_charge(&self, charge: EnergyBillEntry) -> EnergyBillEntry {
+        EnergyBillEntry::from_signed(charge.signed_amount() * (Decimal::ONE - self.standing_charge))
+            .in_currency(charge.currency())
+    }
+
+    // The credit is a fixed amount given as it is, it isn't repriced with the VAT of the day
+    pub fn bill_credit_for_period(&self, period: &BillingPeriod) -> EnergyBillEntry {
+        let Some(credit) = self.bill_credit else {
+            return EnergyBillEntry::ZERO;
+        };
+        -period
+            .days()
+            .map(|date| credit.for_day(date))
+            .sum::<EnergyBillEntry>()
+    }
+
+    // Like 20% off the unit rates, 10% off the standing charge, a €50.00 a year credit
+    pub fn summary(&self) -> String {
+        let percent = |fraction: Decimal| (fraction * Decimal::ONE_HUNDRED).normalize();
+        let mut parts = vec![];
+        if !self.unit_rate.is_zero() {
+            parts.push(format!("{}% off the unit rates", percent(self.unit_rate)));
+        }
+        if !self.standing_charge.is_zero() {
+            parts.push(format!(
+                "{}% off the standing charge",
+                percent(self.standing_charge)
+            ));
+        }
+        match self.bill_credit {
+            Some(StandingChargeDefinition::PerDay(credit)) => {
+                parts.push(format!("a €{credit:.2} a day credit"))
+            }
+            Some(StandingChargeDefinition::Annual(credit)) => {
+                parts.push(format!("a €{credit:.2} a year credit"))
+            }
+            None => {}
+        }
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, ConfigurablePlan, PricePlanStrategy};
+    use rust_decimal_macros::dec;
+
+    fn plan(discount: &str) -> ConfigurablePlan {
+        toml::from_str(&format!(
+            r#"
+name = "discounted"
+standing_charge = {{ per_day = 1.0 }}
+vat_included = 0
+discount = {discount}
+
+[[bands]]
+name = "all day"
+start = "00:00"
+end = "00:00"
+rate = 0.40
+"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn each_part_comes_off_its_own_line() {
+        // 1 kWh over two days, 8 and 9 january 2024, with the 9% VAT of the day on the figures
+        let data = vec![
+            reading_at("08-01-2024 12:00", 1.0),
+            reading_at("09-01-2024 12:00", 1.0),
+        ];
+        let period = BillingPeriod::from_data(&data).unwrap();
+
+        let old = plan("0.25").compute_bill_breakdown(&data, &period);
+        assert_eq!(old.usage.signed_amount(), dec!(0.33));
+        assert_eq!(old.standing_charge.signed_amount(), dec!(2.18));
+        assert!(old.bill_credit.signed_amount().is_zero());
+
+        let discounted =
+            plan("{ unit_rate = 0.25, standing_charge = 0.5, bill_credit = { per_day = 0.10 } }");
+        let breakdown = discounted.compute_bill_breakdown(&data, &period);
+        assert_eq!(breakdown.usage, old.usage);
+        assert_eq!(breakdown.standing_charge.signed_amount(), dec!(1.09));
+        assert_eq!(breakdown.bill_credit.signed_amount(), dec!(-0.20));
+        assert_eq!(
+            discounted.discount.summary(),
+            "25% off the unit rates, 50% off the standing charge, a €0.10 a day credit"
+        );
+        // none of it lasts past the discount
+        let standard = discounted.at_standard_rates().unwrap();
+        let breakdown = standard.compute_bill_breakdown(&data, &period);
+        assert_eq!(breakdown.usage.signed_amount(), dec!(0.44));
+        assert!(breakdown.bill_credit.signed_amount().is_zero());
+    }
+}
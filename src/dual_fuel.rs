@@ -0,0 +1,146 @@
+use anyhow::{bail, Result};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{BillBreakdown, EnergyBillEntry, Money, PlanComparison, Rounding};
+
+// An electricity and a gas plan taken together from the same supplier, which usually takes
+// a further discount off the usage of both bills, see plans.example.toml
+#[derive(Debug, Clone, Deserialize)]
+pub struct DualFuelBundle {
+    pub name: String,
+    pub electricity_plan: String,
+    pub gas_plan: String,
+    #[serde(default)]
+    pub discount: Decimal,
+}
+
+// What a bundle, or the cheapest plans of each fuel taken apart, costs over the period
+#[derive(Debug, Clone, Serialize)]
+pub struct DualFuelComparison {
+    pub name: String,
+    pub electricity_plan: String,
+    pub gas_plan: String,
+    pub electricity: EnergyBillEntry,
+    pub gas: EnergyBillEntry,
+    pub total: EnergyBillEntry,
+}
+
+// The name of the entry made of the cheapest electricity and the cheapest gas plan
+pub const STANDALONE: &str = "cheapest of each fuel";
+
+// The bill with the bundle discount taken off the usage, only charges are discounted
+fn discounted_total(breakdown: &BillBreakdown, discount: Decimal) -> EnergyBillEntry {
+    let usage = breakdown.usage.signed_amount().max(Decimal::ZERO);
+    let off = Money::of(usage * discount, breakdown.total.currency()).round(&Rounding::default());
+    breakdown.total + EnergyBillEntry::Credit(off)
+}
+
+// Prices every bundle from the plans already compared, next to the cheapest electricity and
+// gas plans from any supplier, cheapest first
+pub fn compare_dual_fuel(
+    bundles: &[DualFuelBundle],
+    electricity: &[PlanComparison],
+    gas: &[PlanComparison],
+) -> Result<Vec<DualFuelComparison>> {
+    let find = |results: &[PlanComparison], name: &str| {
+        results
+            .iter()
+            .find(|r| r.name.eq_ignore_ascii_case(name))
+            .cloned()
+    };
+    let mut comparisons = vec![];
+    for bundle in bundles {
+        let Some(electricity_plan) = find(electricity, &bundle.electricity_plan) else {
+            bail!(
+                "the electricity plan {} of {} is not one of the compared plans",
+                bundle.electricity_plan,
+                bundle.name
+            );
+        };
+        let Some(gas_plan) = find(gas, &bundle.gas_plan) else {
+            bail!(
+                "the gas plan {} of {} is not one of the compared gas plans",
+                bundle.gas_plan,
+                bundle.name
+            );
+        };
+        let electricity = discounted_total(&electricity_plan.breakdown, bundle.discount);
+        let gas = discounted_total(&gas_plan.breakdown, bundle.discount);
+        comparisons.push(DualFuelComparison {
+            name: bundle.name.clone(),
+            electricity_plan: electricity_plan.name,
+            gas_plan: gas_plan.name,
+            electricity,
+            gas,
+            total: electricity + gas,
+        });
+    }
+    // the rankings are sorted, so the first of each is the cheapest
+    if let (Some(electricity), Some(gas)) = (electricity.first(), gas.first()) {
+        comparisons.push(DualFuelComparison {
+            name: STANDALONE.to_string(),
+            electricity_plan: electricity.name.clone(),
+            gas_plan: gas.name.clone(),
+            electricity: electricity.breakdown.total,
+            gas: gas.breakdown.total,
+            total: electricity.breakdown.total + gas.breakdown.total,
+        });
+    }
+    comparisons.sort_by_key(|c| c.total);
+    Ok(comparisons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn result(name: &str, usage: Decimal, standing_charge: Decimal) -> PlanComparison {
+        let usage = EnergyBillEntry::Debit(Money::new(usage));
+        let standing_charge = EnergyBillEntry::Debit(Money::new(standing_charge));
+        PlanComparison::new(
+            name.to_string(),
+            BillBreakdown {
+                usage,
+                import_cost: usage,
+                export_credit: EnergyBillEntry::ZERO,
+                standing_charge,
+                pso_levy: EnergyBillEntry::ZERO,
+                credits: EnergyBillEntry::ZERO,
+                bill_credit: EnergyBillEntry::ZERO,
+                demand_charge: EnergyBillEntry::ZERO,
+                total: usage + standing_charge,
+            },
+        )
+    }
+
+    #[test]
+    fn a_bundle_takes_its_discount_off_the_usage_of_both_fuels() {
+        let electricity = vec![
+            result("Cheap", dec!(90), dec!(20)),
+            result("Bundled", dec!(100), dec!(20)),
+        ];
+        let gas = vec![result("Gas", dec!(50), dec!(10))];
+        let bundles = [DualFuelBundle {
+            name: "Both".to_string(),
+            electricity_plan: "bundled".to_string(),
+            gas_plan: "Gas".to_string(),
+            discount: dec!(0.1),
+        }];
+        let comparisons = compare_dual_fuel(&bundles, &electricity, &gas).unwrap();
+        assert_eq!(comparisons.len(), 2);
+        // 120 - 10 and 60 - 5 against 110 and 60 apart
+        assert_eq!(comparisons[0].name, "Both");
+        assert_eq!(comparisons[0].total.signed_amount(), dec!(165));
+        assert_eq!(comparisons[1].name, STANDALONE);
+        assert_eq!(comparisons[1].electricity_plan, "Cheap");
+        assert_eq!(comparisons[1].total.signed_amount(), dec!(170));
+
+        let missing = DualFuelBundle {
+            gas_plan: "Other".to_string(),
+            ..bundles[0].clone()
+        };
+        assert!(compare_dual_fuel(&[missing], &electricity, &gas).is_err());
+    }
+}
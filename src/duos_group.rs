@@ -0,0 +1,221 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, sync::Arc};
+
+use crate::{
+    BillingPeriod, Currency, Discount, EnergyBillEntry, ExportRules, FreeTime, FreeWindow,
+    MaximumImportCapacity, Money, PlanMetadata, PricePlanStrategy, Rounding, SmartMeterData,
+    StandingCharge, StandingChargeDefinition,
+};
+
+// The network charge group of the meter point, suppliers publish a standing charge for each.
+// Most homes are urban, rural lines cost more to maintain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuosGroup {
+    #[default]
+    Urban,
+    Rural,
+}
+
+impl DuosGroup {
+    // The standing charge of the group, plans without a rural figure charge the urban one
+    pub fn pick(
+        self,
+        urban: StandingChargeDefinition,
+        rural: Option<StandingChargeDefinition>,
+    ) -> StandingChargeDefinition {
+        match (self, rural) {
+            (DuosGroup::Rural, Some(rural)) => rural,
+            _ => urban,
+        }
+    }
+}
+
+// A plan charged the standing charge of a group, plans on their own charge the urban one. The
+// plan is shared so the same plan can be priced for both groups at once, like the server does
+#[derive(Clone)]
+pub struct InDuosGroup {
+    plan: Arc<dyn PricePlanStrategy>,
+    group: DuosGroup,
+}
+
+impl InDuosGroup {
+    pub fn new(plan: Arc<dyn PricePlanStrategy>, group: DuosGroup) -> InDuosGroup {
+        InDuosGroup { plan, group }
+    }
+
+    // Plans for urban meter points are returned as they are
+    pub fn wrap(plan: Box<dyn PricePlanStrategy>, group: DuosGroup) -> Box<dyn PricePlanStrategy> {
+        match group {
+            DuosGroup::Urban => plan,
+            group => Box::new(InDuosGroup::new(plan.into(), group)),
+        }
+    }
+}
+
+impl Debug for InDuosGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.plan.fmt(f)
+    }
+}
+
+impl StandingCharge for InDuosGroup {
+    fn standing_charge_per_day(&self, date: NaiveDate, _group: DuosGroup) -> EnergyBillEntry {
+        self.plan.standing_charge_per_day(date, self.group)
+    }
+
+    fn vat_included(&self) -> Decimal {
+        self.plan.vat_included()
+    }
+
+    fn currency(&self) -> Currency {
+        self.plan.currency()
+    }
+}
+
+impl PricePlanStrategy for InDuosGroup {
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+        self.plan.price_for_singe_period(datapoint)
+    }
+
+    fn band_for_singe_period(&self, datapoint: &SmartMeterData) -> String {
+        self.plan.band_for_singe_period(datapoint)
+    }
+
+    fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.plan.is_holiday(date)
+    }
+
+    fn rounding(&self) -> Rounding {
+        self.plan.rounding()
+    }
+
+    fn name(&self) -> String {
+        self.plan.name()
+    }
+
+    fn price_with_accumulated_usage(
+        &self,
+        datapoint: &SmartMeterData,
+        imported_kwh: f32,
+    ) -> EnergyBillEntry {
+        self.plan
+            .price_with_accumulated_usage(datapoint, imported_kwh)
+    }
+
+    fn price_readings(&self, datapoints: &[SmartMeterData]) -> Vec<EnergyBillEntry> {
+        self.plan.price_readings(datapoints)
+    }
+
+    // the plans a plan turns into are in the same group
+    fn at_standard_rates(&self) -> Option<Box<dyn PricePlanStrategy>> {
+        self.plan
+            .at_standard_rates()
+            .map(|plan| InDuosGroup::wrap(plan, self.group))
+    }
+
+    fn discount_months(&self) -> u32 {
+        self.plan.discount_months()
+    }
+
+    fn fixed_rate_months(&self) -> u32 {
+        self.plan.fixed_rate_months()
+    }
+
+    fn discount(&self) -> Discount {
+        self.plan.discount()
+    }
+
+    fn sign_up_bonus(&self) -> Money {
+        self.plan.sign_up_bonus()
+    }
+
+    fn metadata(&self) -> PlanMetadata {
+        self.plan.metadata()
+    }
+
+    fn free_time(&self) -> Option<FreeTime> {
+        self.plan.free_time()
+    }
+
+    fn free_time_choices(&self) -> Vec<(FreeWindow, Box<dyn PricePlanStrategy>)> {
+        self.plan
+            .free_time_choices()
+            .into_iter()
+            .map(|(window, plan)| (window, InDuosGroup::wrap(plan, self.group)))
+            .collect()
+    }
+
+    fn is_prepay(&self) -> bool {
+        self.plan.is_prepay()
+    }
+
+    fn pricing_error(&self) -> Option<String> {
+        self.plan.pricing_error()
+    }
+
+    fn pricing_warning(&self, datapoints: &[SmartMeterData]) -> Option<String> {
+        self.plan.pricing_warning(datapoints)
+    }
+
+    fn export_rules(&self) -> ExportRules {
+        self.plan.export_rules()
+    }
+
+    fn usage_adds_up_by_month(&self) -> bool {
+        self.plan.usage_adds_up_by_month()
+    }
+
+    fn cache_version(&self) -> u64 {
+        self.plan.cache_version()
+    }
+
+    fn pso_levy_for_period(&self, period: &BillingPeriod) -> EnergyBillEntry {
+        self.plan.pso_levy_for_period(period)
+    }
+
+    fn demand_charge_with_mic(
+        &self,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+        mic: Option<MaximumImportCapacity>,
+    ) -> EnergyBillEntry {
+        self.plan.demand_charge_with_mic(datapoints, period, mic)
+    }
+
+    fn demand_charge_for_period(
+        &self,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+    ) -> EnergyBillEntry {
+        self.plan.demand_charge_for_period(datapoints, period)
+    }
+
+    fn compute_total_bill_for_period(&self, datapoints: &[SmartMeterData]) -> EnergyBillEntry {
+        self.plan.compute_total_bill_for_period(datapoints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::electric_ireland_home_electric_14;
+
+    #[test]
+    fn the_same_plan_is_charged_for_both_groups_at_once() {
+        let plan: Arc<dyn PricePlanStrategy> = Arc::new(electric_ireland_home_electric_14());
+        let urban = InDuosGroup::new(Arc::clone(&plan), DuosGroup::Urban);
+        let rural = InDuosGroup::new(plan, DuosGroup::Rural);
+        let period = BillingPeriod::starting_at(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), 366);
+        assert!(
+            rural.standing_charge_for_period(&period).signed_amount()
+                > urban.standing_charge_for_period(&period).signed_amount()
+        );
+        assert_eq!(
+            urban.standing_charge_for_period(&period),
+            electric_ireland_home_electric_14().standing_charge_for_period(&period)
+        );
+    }
+}
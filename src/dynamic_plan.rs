@@ -0,0 +1,299 @@
+use anyhow::{bail, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    meter_data::{smart_meter_datetime_desserialize, smart_meter_datetime_serialize},
+    projection::default_discount_months,
+    read_csv,
+    usage_cache::Fnv,
+    Currency, DuosGroup, EnergyBillEntry, ExportRules, Money, PlanMetadata, PricePlanStrategy,
+    Rounding, SmartMeterData, SmartMeterDataType, StandingCharge, StandingChargeDefinition,
+};
+
+// A price is used for the readings starting up to this long after it, so hourly and half
+// hourly series both work but a hole in the series isn't papered over
+const MAX_PRICE_LENGTH_MINUTES: i64 = 60;
+
+// One row of a price file, the start of the interval in UTC like the HDF readings and the
+// wholesale price in cent per kWh, e.g. 08-01-2024 00:00,9.85
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PricePoint {
+    #[serde(
+        deserialize_with = "smart_meter_datetime_desserialize",
+        serialize_with = "smart_meter_datetime_serialize"
+    )]
+    pub start: NaiveDateTime,
+    pub price: Decimal,
+}
+
+// Wholesale prices by the start of their interval, in euro per kWh
+#[derive(Debug, Clone, Default)]
+pub struct PriceSeries {
+    prices: BTreeMap<NaiveDateTime, Decimal>,
+}
+
+impl PriceSeries {
+    pub fn new(points: Vec<PricePoint>) -> PriceSeries {
+        PriceSeries {
+            prices: points
+                .into_iter()
+                .map(|point| (point.start, point.price / Decimal::ONE_HUNDRED))
+                .collect(),
+        }
+    }
+
+    pub fn load(filename: &Path) -> Result<PriceSeries> {
+        let series = PriceSeries::new(read_csv(filename)?);
+        if series.prices.is_empty() {
+            bail!("no prices in {}", filename.display());
+        }
+        Ok(series)
+    }
+
+    // Identifies the prices, for the usage cache
+    pub fn version(&self) -> u64 {
+        let mut hasher = Fnv::new();
+        for (start, price) in &self.prices {
+            hasher
+                .u64(start.and_utc().timestamp() as u64)
+                .bytes(&price.serialize());
+        }
+        hasher.finish()
+    }
+
+    // The price of the interval starting at start, None when the series has nothing for it
+    pub fn price_at(&self, start: NaiveDateTime) -> Option<Decimal> {
+        let (&from, &price) = self.prices.range(..=start).next_back()?;
+        (start - from < Duration::minutes(MAX_PRICE_LENGTH_MINUTES)).then_some(price)
+    }
+}
+
+// A plan whose unit rate follows the day ahead market, the wholesale price of each interval
+// plus the margin of the supplier. Wholesale prices are before VAT, so unlike the other plans
+// vat_included is zero unless set
+#[derive(Clone, Deserialize)]
+pub struct DynamicPlan {
+    pub name: String,
+    // the price file, relative to the tariff file
+    pub prices: PathBuf,
+    // euro per kWh added to every wholesale price
+    #[serde(default)]
+    pub margin: Decimal,
+    #[serde(default = "default_discount_months")]
+    pub discount_months: u32,
+    #[serde(default)]
+    pub sign_up_bonus: Decimal,
+    #[serde(default)]
+    pub currency: Currency,
+    #[serde(default)]
+    pub vat_included: Decimal,
+    #[serde(default)]
+    pub export_rate: Decimal,
+    #[serde(default)]
+    pub export: ExportRules,
+    pub standing_charge: StandingChargeDefinition,
+    #[serde(default)]
+    pub rural_standing_charge: Option<StandingChargeDefinition>,
+    #[serde(default)]
+    pub rounding: Rounding,
+    #[serde(skip)]
+    pub series: PriceSeries,
+    #[serde(default)]
+    pub metadata: PlanMetadata,
+}
+
+// The name is enough to identify the plan on the output
+impl Debug for DynamicPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl DynamicPlan {
+    // Reads the price file, a relative path is taken from the directory of the tariff file
+    pub fn load_prices(&mut self, tariff_file: &Path) -> Result<()> {
+        let path = match tariff_file.parent() {
+            Some(dir) if self.prices.is_relative() => dir.join(&self.prices),
+            _ => self.prices.clone(),
+        };
+        self.series = PriceSeries::load(&path)?;
+        Ok(())
+    }
+
+    fn rate_for(&self, datapoint: &SmartMeterData) -> Option<Decimal> {
+        let start =
+            datapoint.read_data_and_end_time - Duration::minutes(datapoint.interval_minutes as i64);
+        self.series.price_at(start).map(|price| price + self.margin)
+    }
+
+    // Readings outside the price file are priced at zero, so the user has to be told about them
+    fn missing_price_warning(&self, datapoints: &[SmartMeterData]) -> Option<String> {
+        let missing = datapoints
+            .iter()
+            .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+            .filter(|d| self.rate_for(d).is_none())
+            .count();
+        (missing > 0).then(|| {
+            format!(
+                "{missing} readings have no price in {} and were priced at zero",
+                self.prices.display()
+            )
+        })
+    }
+}
+
+impl PricePlanStrategy for DynamicPlan {
+    // negative wholesale prices make the interval a credit
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => match self.rate_for(datapoint) {
+                Some(rate) => EnergyBillEntry::from_signed(
+                    Money::for_quantity(rate, datapoint.energy_kwh()).amount(),
+                ),
+                None => EnergyBillEntry::ZERO,
+            },
+            SmartMeterDataType::ActiveExport => EnergyBillEntry::Credit(Money::for_quantity(
+                self.export_rate,
+                datapoint.energy_kwh(),
+            )),
+        }
+    }
+
+    fn band_for_singe_period(&self, datapoint: &SmartMeterData) -> String {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => "dynamic".to_string(),
+            SmartMeterDataType::ActiveExport => "export".to_string(),
+        }
+    }
+
+    fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    fn export_rules(&self) -> ExportRules {
+        self.export
+    }
+
+    // the price file can change without the tariff file changing
+    fn cache_version(&self) -> u64 {
+        self.series.version()
+    }
+
+    fn discount_months(&self) -> u32 {
+        self.discount_months
+    }
+
+    fn sign_up_bonus(&self) -> Money {
+        Money::of(self.sign_up_bonus, self.currency)
+    }
+
+    fn metadata(&self) -> PlanMetadata {
+        self.metadata.clone()
+    }
+
+    fn pricing_warning(&self, datapoints: &[SmartMeterData]) -> Option<String> {
+        self.missing_price_warning(datapoints)
+    }
+}
+
+impl StandingCharge for DynamicPlan {
+    fn standing_charge_per_day(&self, date: NaiveDate, group: DuosGroup) -> EnergyBillEntry {
+        group
+            .pick(self.standing_charge, self.rural_standing_charge)
+            .for_day(date)
+    }
+
+    fn vat_included(&self) -> Decimal {
+        self.vat_included
+    }
+
+    fn currency(&self) -> Currency {
+        self.currency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, CachedPlan, UsageCache};
+    use rust_decimal_macros::dec;
+
+    fn at(datetime: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(datetime, "%d-%m-%Y %H:%M").unwrap()
+    }
+
+    fn dynamic(series: PriceSeries) -> DynamicPlan {
+        DynamicPlan {
+            name: "dynamic".to_string(),
+            prices: PathBuf::from("prices.csv"),
+            margin: dec!(0.05),
+            discount_months: crate::DEFAULT_DISCOUNT_MONTHS,
+            sign_up_bonus: Decimal::ZERO,
+            currency: Currency::Eur,
+            vat_included: Decimal::ZERO,
+            export_rate: Decimal::ZERO,
+            export: ExportRules::default(),
+            standing_charge: StandingChargeDefinition::PerDay(Decimal::ZERO),
+            rural_standing_charge: None,
+            rounding: Rounding::default(),
+            series,
+            metadata: PlanMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn hourly_prices_cover_both_half_hours() {
+        let plan = dynamic(PriceSeries::new(vec![
+            PricePoint {
+                start: at("08-01-2024 00:00"),
+                price: dec!(10),
+            },
+            PricePoint {
+                start: at("08-01-2024 01:00"),
+                price: dec!(-2),
+            },
+        ]));
+
+        // 1 kWh each, the one ending at 01:00 started in the first hour
+        let price = |datetime| plan.price_for_singe_period(&reading_at(datetime, 2.0));
+        assert_eq!(
+            price("08-01-2024 01:00"),
+            EnergyBillEntry::Debit(Money::new(dec!(0.15)))
+        );
+        assert_eq!(
+            price("08-01-2024 02:00"),
+            EnergyBillEntry::Debit(Money::new(dec!(0.03)))
+        );
+        assert_eq!(price("08-01-2024 03:00"), EnergyBillEntry::ZERO);
+    }
+
+    #[test]
+    fn the_usage_cache_prices_the_plan_again_when_the_prices_change() {
+        let data = vec![reading_at("08-01-2024 00:30", 2.0)];
+        let cache = UsageCache::default().shared();
+        // what the plan charges with and without the cache
+        let usage = |price| {
+            let plan = dynamic(PriceSeries::new(vec![PricePoint {
+                start: at("08-01-2024 00:00"),
+                price,
+            }]));
+            assert!(plan.usage_adds_up_by_month());
+            let uncached = plan.compute_total_bill_for_period(&data);
+            let cached = CachedPlan::wrap(Box::new(plan), "tariffs", &cache)
+                .compute_total_bill_for_period(&data);
+            (cached, uncached)
+        };
+        let (before, uncached) = usage(dec!(10));
+        assert_eq!(before, uncached);
+        let (after, uncached) = usage(dec!(20));
+        assert_eq!(after, uncached);
+        assert_ne!(after, before);
+    }
+}
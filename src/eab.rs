@@ -0,0 +1,135 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use std::sync::Arc;
+
+use crate::{
+    rank_plans, time_in_window, BillingPeriod, PlanComparison, PricePlanStrategy, ReadUnit,
+    SmartMeterData, SmartMeterDataType, LOCAL_TIMEZONE,
+};
+
+// The usage the CRU has suppliers publish their estimated annual bill for, an urban home
+// using 4,200 kWh a year, 62% in the day, 24% at night and 14% at peak
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConsumptionProfile {
+    pub annual_kwh: f32,
+    pub day_share: f32,
+    pub night_share: f32,
+    pub peak_share: f32,
+}
+
+impl ConsumptionProfile {
+    pub const CRU_STANDARD: ConsumptionProfile = ConsumptionProfile {
+        annual_kwh: 4200.0,
+        day_share: 0.62,
+        night_share: 0.24,
+        peak_share: 0.14,
+    };
+}
+
+// the standard smart tariff windows the shares are split by, night is 23:00 to 08:00 and peak
+// is 17:00 to 19:00 on every day
+fn hm(hour: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, 0, 0).unwrap_or_default()
+}
+
+// The window of a reading ending at this local time: 0 for day, 1 for night and 2 for peak
+pub(crate) fn standard_window(local: NaiveTime) -> usize {
+    if time_in_window(local, hm(23), hm(8)) {
+        1
+    } else if time_in_window(local, hm(17), hm(19)) {
+        2
+    } else {
+        0
+    }
+}
+
+// The half hours of a local day with the kWh of the day spread evenly over each window by the
+// shares of the profile, ending in UTC like the readings
+pub(crate) fn profile_day(
+    profile: &ConsumptionProfile,
+    date: NaiveDate,
+    kwh: f32,
+) -> Vec<(NaiveDateTime, f32)> {
+    let shares = [profile.day_share, profile.night_share, profile.peak_share];
+    // days when the clocks change have 46 or 50 half hours
+    let Some(midnight) = LOCAL_TIMEZONE
+        .from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()
+    else {
+        return vec![];
+    };
+    let next_midnight = LOCAL_TIMEZONE
+        .from_local_datetime(&(date + Duration::days(1)).and_time(NaiveTime::MIN))
+        .earliest()
+        .unwrap_or(midnight + Duration::days(1));
+    let mut ends = vec![];
+    let mut end = midnight + Duration::minutes(30);
+    while end <= next_midnight {
+        ends.push((end.naive_utc(), standard_window(end.time())));
+        end += Duration::minutes(30);
+    }
+    ends.iter()
+        .map(|&(end, window)| {
+            let in_window = ends.iter().filter(|(_, w)| *w == window).count() as f32;
+            (end, kwh * shares[window] / in_window)
+        })
+        .collect()
+}
+
+// A year of half hour readings from the start day with the profile's energy spread evenly
+// over each window of each day, so a plan of any bands can price it
+pub fn standard_profile_readings(
+    profile: &ConsumptionProfile,
+    start: NaiveDate,
+) -> Vec<SmartMeterData> {
+    let meter: Arc<str> = "EAB".into();
+    let days = 365;
+    let daily_kwh = profile.annual_kwh / days as f32;
+    start
+        .iter_days()
+        .take(days)
+        .flat_map(|date| profile_day(profile, date, daily_kwh))
+        .map(|(end, kwh)| SmartMeterData {
+            mprn: Arc::clone(&meter),
+            meter_serial_number: Arc::clone(&meter),
+            read_value: kwh,
+            read_type: SmartMeterDataType::ActiveImport,
+            unit: ReadUnit::KilowattHours,
+            read_data_and_end_time: end,
+            interval_minutes: 30,
+        })
+        .collect()
+}
+
+// Every plan over a year of the CRU standard usage from the start day, cheapest first, to
+// check against the estimated annual bills the suppliers publish
+pub fn estimated_annual_bills(
+    plans: &[Box<dyn PricePlanStrategy>],
+    start: NaiveDate,
+) -> Vec<PlanComparison> {
+    let readings = standard_profile_readings(&ConsumptionProfile::CRU_STANDARD, start);
+    let period = BillingPeriod::starting_at(start, 365);
+    rank_plans(plans, &readings, &period)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_standard_year_adds_up_to_the_profile() {
+        // the clocks change twice in the year
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let readings = standard_profile_readings(&ConsumptionProfile::CRU_STANDARD, start);
+        assert_eq!(readings.len(), 365 * 48);
+        let kwh = |from: u32, to: u32| -> f32 {
+            readings
+                .iter()
+                .filter(|d| time_in_window(d.local_end_time().time(), hm(from), hm(to)))
+                .map(|d| d.energy_kwh())
+                .sum()
+        };
+        assert!((kwh(0, 0) - 4200.0).abs() < 0.5);
+        assert!((kwh(17, 19) - 588.0).abs() < 0.5);
+        assert!((kwh(23, 8) - 1008.0).abs() < 0.5);
+    }
+}
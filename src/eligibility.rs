@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+// Who can sign up for a plan. Time of use plans need a smart meter, EV plans proof of owning
+// an electric car, some plans are only for customers new to the supplier and dual fuel ones
+// need the gas taken from the supplier too
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Eligibility {
+    #[serde(default)]
+    pub smart_meter: bool,
+    #[serde(default)]
+    pub ev: bool,
+    #[serde(default)]
+    pub new_customers_only: bool,
+    #[serde(default)]
+    pub dual_fuel: bool,
+}
+
+// What is known of the home, none where the user said nothing so no plan is left out for it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Household {
+    pub smart_meter: Option<bool>,
+    pub ev: Option<bool>,
+    pub gas: Option<bool>,
+    // the supplier now, whose plans for new customers can't be taken
+    pub supplier: Option<String>,
+}
+
+impl Eligibility {
+    pub fn is_empty(&self) -> bool {
+        *self == Eligibility::default()
+    }
+
+    // What it takes to sign up, for the plan details
+    pub fn requirements(&self) -> Vec<&'static str> {
+        let mut requirements = vec![];
+        if self.smart_meter {
+            requirements.push("needs a smart meter");
+        }
+        if self.ev {
+            requirements.push("needs proof of an EV");
+        }
+        if self.new_customers_only {
+            requirements.push("new customers only");
+        }
+        if self.dual_fuel {
+            requirements.push("only with gas");
+        }
+        requirements
+    }
+
+    // The first requirement of the plan of the supplier the home doesn't meet, none when it
+    // can sign up
+    pub fn unmet(&self, supplier: Option<&str>, household: &Household) -> Option<&'static str> {
+        if self.smart_meter && household.smart_meter == Some(false) {
+            return Some("needs a smart meter");
+        }
+        if self.ev && household.ev == Some(false) {
+            return Some("needs proof of an EV");
+        }
+        let same_supplier = match (supplier, &household.supplier) {
+            (Some(supplier), Some(current)) => supplier.eq_ignore_ascii_case(current),
+            _ => false,
+        };
+        if self.new_customers_only && same_supplier {
+            return Some("new customers only");
+        }
+        if self.dual_fuel && household.gas == Some(false) {
+            return Some("only with gas");
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_what_the_home_is_known_not_to_meet_leaves_a_plan_out() {
+        let eligibility: Eligibility =
+            toml::from_str("ev = true\nnew_customers_only = true").unwrap();
+        assert_eq!(
+            eligibility.requirements(),
+            vec!["needs proof of an EV", "new customers only"]
+        );
+        assert_eq!(
+            eligibility.unmet(Some("SSE Airtricity"), &Household::default()),
+            None
+        );
+        let household = Household {
+            ev: Some(true),
+            supplier: Some("sse airtricity".to_string()),
+            ..Household::default()
+        };
+        assert_eq!(
+            eligibility.unmet(Some("SSE Airtricity"), &household),
+            Some("new customers only")
+        );
+        assert_eq!(eligibility.unmet(Some("Flogas"), &household), None);
+        let without_ev = Household {
+            ev: Some(false),
+            ..Household::default()
+        };
+        assert_eq!(
+            eligibility.unmet(None, &without_ev),
+            Some("needs proof of an EV")
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::Result;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+// What was wrong with a row that couldn't be read, the skipped rows are counted by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RowProblem {
+    MissingField,
+    NotANumber,
+    UnknownType,
+    BadDate,
+    Unreadable,
+}
+
+impl fmt::Display for RowProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RowProblem::MissingField => "missing field",
+            RowProblem::NotANumber => "non-numeric value",
+            RowProblem::UnknownType => "unknown read type",
+            RowProblem::BadDate => "bad date format",
+            RowProblem::Unreadable => "unreadable row",
+        })
+    }
+}
+
+// What can go wrong reading the files, the rest of the crate reports through anyhow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    // a row of a csv that couldn't be read, line is the line of the file counting the header
+    // and row the row as it is in the file
+    BadRow {
+        file: String,
+        line: u64,
+        problem: RowProblem,
+        reason: String,
+        row: String,
+    },
+    MissingColumn {
+        file: String,
+        column: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BadRow {
+                file, line, reason, ..
+            } => write!(f, "{file} line {line}: {reason}"),
+            Error::MissingColumn { file, column } => write!(f, "{file} has no {column} column"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// What to do with rows that can't be read, skip them and say how many by default or stop
+// at the first one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+static MODE: OnceLock<ParseMode> = OnceLock::new();
+
+impl ParseMode {
+    pub fn global() -> ParseMode {
+        *MODE.get_or_init(ParseMode::default)
+    }
+
+    // Only works before any file was read, so it has to happen while starting up
+    pub fn install(self) -> Result<()> {
+        MODE.set(self)
+            .map_err(|_| anyhow::anyhow!("the parse mode is already in use"))
+    }
+}
+
+// A csv the skipped rows are copied to, so they can be looked at and fixed
+pub struct Quarantine {
+    writer: Mutex<csv::Writer<File>>,
+}
+
+static QUARANTINE: OnceLock<Quarantine> = OnceLock::new();
+
+impl Quarantine {
+    pub fn create(path: &Path) -> Result<Quarantine> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(["file", "line", "problem", "reason", "row"])?;
+        writer.flush()?;
+        Ok(Quarantine {
+            writer: Mutex::new(writer),
+        })
+    }
+
+    // None unless a quarantine file was installed
+    pub fn global() -> Option<&'static Quarantine> {
+        QUARANTINE.get()
+    }
+
+    pub fn install(self) -> Result<()> {
+        QUARANTINE
+            .set(self)
+            .map_err(|_| anyhow::anyhow!("the quarantine file is already in use"))
+    }
+
+    pub fn record(&self, error: &Error) -> Result<()> {
+        let Error::BadRow {
+            file,
+            line,
+            problem,
+            reason,
+            row,
+        } = error
+        else {
+            return Ok(());
+        };
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_record([file, &line.to_string(), &problem.to_string(), reason, row])?;
+        // only a handful of rows end up here, so every one is written straight away
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// Goes through the rows of one file keeping the bad ones apart, in strict mode the first bad
+// row is an error and otherwise they are counted by problem for the warning at the end
+pub struct RowChecker {
+    file: String,
+    mode: ParseMode,
+    skipped: BTreeMap<RowProblem, usize>,
+    first: Option<Error>,
+}
+
+impl RowChecker {
+    pub fn new(file: impl Into<String>, mode: ParseMode) -> RowChecker {
+        RowChecker {
+            file: file.into(),
+            mode,
+            skipped: BTreeMap::new(),
+            first: None,
+        }
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.skipped.values().sum()
+    }
+
+    pub fn bad_row(
+        &self,
+        line: u64,
+        problem: RowProblem,
+        reason: impl Into<String>,
+        row: impl Into<String>,
+    ) -> Error {
+        Error::BadRow {
+            file: self.file.clone(),
+            line,
+            problem,
+            reason: reason.into(),
+            row: row.into(),
+        }
+    }
+
+    // The row when it could be read, None when it was skipped
+    pub fn check<T>(&mut self, row: Result<T, Error>) -> Result<Option<T>> {
+        let error = match row {
+            Ok(row) => return Ok(Some(row)),
+            Err(error) => error,
+        };
+        if let Some(quarantine) = Quarantine::global() {
+            quarantine.record(&error)?;
+        }
+        if self.mode == ParseMode::Strict {
+            return Err(error.into());
+        }
+        if let Error::BadRow { problem, .. } = &error {
+            *self.skipped.entry(*problem).or_default() += 1;
+        }
+        self.first.get_or_insert(error);
+        Ok(None)
+    }
+
+    // Says how many rows were skipped and why, naming the first so it can be looked up
+    pub fn finish(self) {
+        let Some(first) = &self.first else {
+            return;
+        };
+        let problems: Vec<String> = self
+            .skipped
+            .iter()
+            .map(|(problem, count)| format!("{count} {problem}"))
+            .collect();
+        eprintln!(
+            "warning: skipped {} rows that couldn't be read ({}), the first is {first}",
+            self.skipped(),
+            problems.join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skipped_rows_are_counted_by_problem_and_quarantined() {
+        let path = std::env::temp_dir().join(format!("rejects-{}.csv", std::process::id()));
+        let quarantine = Quarantine::create(&path).unwrap();
+        let mut rows = RowChecker::new("HDF.csv", ParseMode::Lenient);
+        let bad = |line, problem, row: &str| -> Result<(), Error> {
+            Err(rows.bad_row(line, problem, "bad", row))
+        };
+        let errors = [
+            bad(2, RowProblem::NotANumber, "1,2,abc"),
+            bad(3, RowProblem::BadDate, "1,2,3,import,soon"),
+            bad(4, RowProblem::NotANumber, "1,2,x"),
+        ];
+        for error in errors {
+            quarantine.record(error.as_ref().unwrap_err()).unwrap();
+            assert_eq!(rows.check(error).unwrap(), None);
+        }
+        assert_eq!(rows.check(Ok(5)).unwrap(), Some(5));
+        assert_eq!(rows.skipped(), 3);
+        assert_eq!(
+            rows.skipped.into_iter().collect::<Vec<_>>(),
+            vec![(RowProblem::NotANumber, 2), (RowProblem::BadDate, 1)]
+        );
+
+        let rejects = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = rejects.lines().collect();
+        assert_eq!(lines[0], "file,line,problem,reason,row");
+        assert_eq!(lines[1], "HDF.csv,2,non-numeric value,bad,\"1,2,abc\"");
+        assert_eq!(lines.len(), 4);
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+const PORTAL: &str = "https://myaccount.esbnetworks.ie";
+// The portal turns plain curl away, so every request looks like it comes from a browser
+const USER_AGENT: &str =
+    "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0 Safari/537.36";
+// Separates the body curl prints from the url it ended up at after the redirects
+const URL_MARKER: &str = "\n--energy-comparator-url--";
+
+// The value of "key":"value" in the settings the login page is built from
+fn settings_value<'a>(page: &'a str, key: &str) -> Option<&'a str> {
+    let needle = format!("\"{key}\":\"");
+    let start = page.find(&needle)? + needle.len();
+    let end = start + page[start..].find('"')?;
+    Some(&page[start..end])
+}
+
+// The value of an attribute of the first tag containing after, single or double quoted like
+// the login pages write them
+fn attribute_after<'a>(page: &'a str, after: &str, attribute: &str) -> Option<&'a str> {
+    let tag_start = page.find(after)?;
+    let tag = &page[page[..=tag_start].rfind('<')?..];
+    let tag = &tag[..tag.find('>')?];
+    let needle = format!("{attribute}=");
+    let start = tag.find(&needle)? + needle.len();
+    let quote = tag[start..].chars().next()?;
+    let value = &tag[start + 1..];
+    Some(&value[..value.find(quote)?])
+}
+
+fn hidden_input<'a>(page: &'a str, name: &str) -> Option<&'a str> {
+    attribute_after(page, &format!("name='{name}'"), "value")
+        .or_else(|| attribute_after(page, &format!("name=\"{name}\""), "value"))
+}
+
+fn form_encode(fields: &[(&str, &str)]) -> String {
+    let encode = |value: &str| {
+        value
+            .bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{b:02X}"),
+            })
+            .collect::<String>()
+    };
+    fields
+        .iter()
+        .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+// The cookies of one login, kept by curl in a file that goes away with the session
+struct Session {
+    cookies: PathBuf,
+}
+
+impl Session {
+    fn new() -> Session {
+        Session {
+            cookies: std::env::temp_dir().join(format!(
+                "energy_comparator-esb-{}.cookies",
+                std::process::id()
+            )),
+        }
+    }
+
+    // Runs curl with the cookies of the session, the body goes in through stdin so the
+    // password never shows up in the list of processes
+    fn request(&self, args: &[&str], body: Option<&str>) -> Result<(String, String)> {
+        let cookies = self.cookies.to_string_lossy();
+        let write_out = format!("{URL_MARKER}%{{url_effective}}");
+        let mut child = Command::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--location"])
+            .args(["--cookie", &cookies, "--cookie-jar", &cookies])
+            .args(["--user-agent", USER_AGENT, "--write-out", &write_out])
+            .args(args)
+            .args(body.map(|_| ["--data-binary", "@-"]).into_iter().flatten())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("could not run curl to log into the ESB Networks portal")?;
+        if let (Some(body), Some(mut stdin)) = (body, child.stdin.take()) {
+            stdin.write_all(body.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!(
+                "the ESB Networks portal request failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let text = String::from_utf8_lossy(&output.stdout).into_owned();
+        let (page, url) = text
+            .rsplit_once(URL_MARKER)
+            .context("curl did not say where the request ended up")?;
+        Ok((page.to_string(), url.to_string()))
+    }
+
+    fn get(&self, url: &str) -> Result<(String, String)> {
+        self.request(&[url], None)
+    }
+
+    fn post(&self, url: &str, headers: &[String], body: &str) -> Result<(String, String)> {
+        let mut args: Vec<&str> = vec![];
+        for header in headers {
+            args.extend(["--header", header]);
+        }
+        args.push(url);
+        self.request(&args, Some(body))
+    }
+
+    // Goes through the Azure B2C sign in the portal redirects to, what a browser does when
+    // the user types the email and password in
+    fn log_in(&self, username: &str, password: &str) -> Result<()> {
+        let (page, url) = self.get(PORTAL)?;
+        let host = url.split('/').take(3).collect::<Vec<_>>().join("/");
+        let (Some(csrf), Some(transaction), Some(tenant), Some(policy)) = (
+            settings_value(&page, "csrf"),
+            settings_value(&page, "transId"),
+            settings_value(&page, "tenant"),
+            settings_value(&page, "policy"),
+        ) else {
+            bail!("the ESB Networks login page changed, could not find the sign in form");
+        };
+
+        let (answer, _) = self.post(
+            &format!("{host}{tenant}/SelfAsserted?tx={transaction}&p={policy}"),
+            &[format!("X-CSRF-TOKEN: {csrf}")],
+            &form_encode(&[
+                ("signInName", username),
+                ("password", password),
+                ("request_type", "RESPONSE"),
+            ]),
+        )?;
+        if !answer.contains("\"status\":\"200\"") {
+            bail!("the ESB Networks portal did not accept the email and password");
+        }
+
+        let (confirmed, _) = self.get(&format!(
+            "{host}{tenant}/api/CombinedSigninAndSignup/confirmed?rememberMe=false&csrf_token={csrf}&tx={transaction}&p={policy}"
+        ))?;
+        let (Some(action), Some(state), Some(client_info), Some(code)) = (
+            attribute_after(&confirmed, "<form", "action"),
+            hidden_input(&confirmed, "state"),
+            hidden_input(&confirmed, "client_info"),
+            hidden_input(&confirmed, "code"),
+        ) else {
+            bail!("the ESB Networks portal did not finish the sign in");
+        };
+        self.post(
+            action,
+            &[],
+            &form_encode(&[
+                ("state", state),
+                ("client_info", client_info),
+                ("code", code),
+            ]),
+        )?;
+        Ok(())
+    }
+
+    // The HDF file with every half hour reading the portal has for the meter point
+    fn download_hdf(&self, mprn: &str) -> Result<String> {
+        let (token, _) = self.get(&format!("{PORTAL}/af/t"))?;
+        let token = settings_value(&token, "token")
+            .context("the ESB Networks portal did not hand out a download token")?;
+        let (hdf, _) = self.post(
+            &format!("{PORTAL}/DataHub/DownloadHdfPeriodic"),
+            &[
+                "Content-Type: application/json".to_string(),
+                format!("X-Xsrf-Token: {token}"),
+            ],
+            &serde_json::json!({"mprn": mprn, "searchType": "intervalkw"}).to_string(),
+        )?;
+        if !hdf.starts_with("MPRN,") {
+            bail!("the ESB Networks portal did not return an HDF file for {mprn}");
+        }
+        Ok(hdf)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.cookies);
+    }
+}
+
+// Logs into the ESB Networks customer portal and downloads the latest HDF file of the meter
+// point, the same file the "download" button of the portal gives
+pub fn fetch_hdf_file(username: &str, password: &str, mprn: &str) -> Result<String> {
+    let session = Session::new();
+    session.log_in(username, password)?;
+    session.download_hdf(mprn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_sign_in_form() {
+        let page = r#"<form id='auto' method='post' action='https://myaccount.esbnetworks.ie/signin-oidc'>
+            <input type='hidden' name='state' id='state' value='StateProperties=abc'/>
+            <input type="hidden" name="code" value="xyz"/></form>
+            var SETTINGS = {"csrf":"c1==","transId":"StateProperties=t1"};"#;
+        assert_eq!(
+            attribute_after(page, "<form", "action"),
+            Some("https://myaccount.esbnetworks.ie/signin-oidc")
+        );
+        assert_eq!(hidden_input(page, "state"), Some("StateProperties=abc"));
+        assert_eq!(hidden_input(page, "code"), Some("xyz"));
+        assert_eq!(settings_value(page, "csrf"), Some("c1=="));
+        assert_eq!(form_encode(&[("a", "b c=&")]), "a=b%20c%3D%26");
+    }
+}
@@ -0,0 +1,214 @@
+use chrono::{Duration, NaiveDateTime, TimeZone};
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    EnergyBillEntry, Money, PricePlanStrategy, SmartMeterData, SmartMeterDataType, LOCAL_TIMEZONE,
+};
+
+// What counts as a car charging: the imports staying at or above threshold_kw for at least
+// min_minutes in a row. Home chargers draw 3.6 to 7.4 kW, far above the rest of a house
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct EvDetection {
+    pub threshold_kw: f32,
+    pub min_minutes: u32,
+}
+
+// A block of readings taken for a car charging, in local time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChargingSession {
+    pub mprn: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    // the energy above what the house was using around the session
+    pub kwh: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvCharging {
+    pub sessions: Vec<ChargingSession>,
+    pub kwh: f32,
+    // the energy of each reading that went into the car, lined up with the readings
+    #[serde(skip)]
+    pub ev_kwh: Vec<f32>,
+}
+
+// What the car costs on a plan next to the rest of the house, which also pays the fixed charges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EvCost {
+    pub ev: EnergyBillEntry,
+    pub house: EnergyBillEntry,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub ev_per_kwh: Decimal,
+}
+
+fn kw(datapoint: &SmartMeterData) -> f32 {
+    datapoint.energy_kwh() * 60.0 / datapoint.interval_minutes as f32
+}
+
+impl EvDetection {
+    // Finds the sessions of every meter point. The house keeps using energy while the car
+    // charges, so the average power of the readings just before and after a session is left
+    // to the house and only what is above it goes to the car
+    pub fn detect(&self, datapoints: &[SmartMeterData]) -> EvCharging {
+        let mut ev_kwh = vec![0.0; datapoints.len()];
+        let mut sessions = vec![];
+        let mut meters: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (index, datapoint) in datapoints.iter().enumerate() {
+            if datapoint.read_type == SmartMeterDataType::ActiveImport {
+                meters
+                    .entry(datapoint.mprn.as_ref())
+                    .or_default()
+                    .push(index);
+            }
+        }
+        for (mprn, mut readings) in meters {
+            readings.sort_by_key(|&i| datapoints[i].read_data_and_end_time);
+            // the next reading follows straight on from this one
+            let follows = |a: usize, b: usize| {
+                datapoints[b].read_data_and_end_time - datapoints[a].read_data_and_end_time
+                    == Duration::minutes(datapoints[b].interval_minutes as i64)
+            };
+            let mut position = 0;
+            while position < readings.len() {
+                if kw(&datapoints[readings[position]]) < self.threshold_kw {
+                    position += 1;
+                    continue;
+                }
+                let first = position;
+                while position + 1 < readings.len()
+                    && follows(readings[position], readings[position + 1])
+                    && kw(&datapoints[readings[position + 1]]) >= self.threshold_kw
+                {
+                    position += 1;
+                }
+                let last = position;
+                position += 1;
+                let block = &readings[first..=last];
+                let minutes: u32 = block.iter().map(|&i| datapoints[i].interval_minutes).sum();
+                if minutes < self.min_minutes {
+                    continue;
+                }
+                let mut around = vec![];
+                if first > 0 && follows(readings[first - 1], readings[first]) {
+                    around.push(kw(&datapoints[readings[first - 1]]));
+                }
+                if last + 1 < readings.len() && follows(readings[last], readings[last + 1]) {
+                    around.push(kw(&datapoints[readings[last + 1]]));
+                }
+                let house_kw = if around.is_empty() {
+                    0.0
+                } else {
+                    around.iter().sum::<f32>() / around.len() as f32
+                };
+                let mut kwh = 0.0;
+                for &i in block {
+                    let hours = datapoints[i].interval_minutes as f32 / 60.0;
+                    ev_kwh[i] = (kw(&datapoints[i]) - house_kw).max(0.0) * hours;
+                    kwh += ev_kwh[i];
+                }
+                let local =
+                    |time: NaiveDateTime| LOCAL_TIMEZONE.from_utc_datetime(&time).naive_local();
+                let first = &datapoints[block[0]];
+                sessions.push(ChargingSession {
+                    mprn: mprn.to_string(),
+                    start: local(
+                        first.read_data_and_end_time
+                            - Duration::minutes(first.interval_minutes as i64),
+                    ),
+                    end: local(datapoints[block[block.len() - 1]].read_data_and_end_time),
+                    kwh,
+                });
+            }
+        }
+        EvCharging {
+            kwh: sessions.iter().map(|s| s.kwh).sum(),
+            sessions,
+            ev_kwh,
+        }
+    }
+}
+
+impl EvCharging {
+    // The share of the price of each reading that went into the car, so tiers and time of
+    // use bands are charged to the car as they fell
+    pub fn cost(
+        &self,
+        plan: &dyn PricePlanStrategy,
+        datapoints: &[SmartMeterData],
+        total: EnergyBillEntry,
+    ) -> EvCost {
+        let mut ev = Decimal::ZERO;
+        for ((datapoint, price), &ev_kwh) in datapoints
+            .iter()
+            .zip(plan.price_readings(datapoints))
+            .zip(&self.ev_kwh)
+        {
+            let kwh = datapoint.energy_kwh();
+            if ev_kwh <= 0.0 || kwh <= 0.0 {
+                continue;
+            }
+            let share = Decimal::from_f32(ev_kwh / kwh).unwrap_or_default();
+            ev += price.signed_amount() * share;
+        }
+        let ev = EnergyBillEntry::from_signed(ev)
+            .in_currency(plan.currency())
+            .round(&plan.rounding());
+        let ev_per_kwh = match Decimal::from_f32(self.kwh) {
+            Some(kwh) if !kwh.is_zero() => (ev.signed_amount() / kwh).round_dp(4),
+            _ => Decimal::ZERO,
+        };
+        EvCost {
+            ev,
+            house: total - ev,
+            ev_per_kwh,
+        }
+    }
+}
+
+impl EvCost {
+    pub fn ev_per_kwh(&self) -> Money {
+        Money::of(self.ev_per_kwh, self.ev.currency())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, sse_airtricity_20, BillingPeriod};
+
+    #[test]
+    fn sustained_high_draw_is_a_charging_session() {
+        let detection = EvDetection {
+            threshold_kw: 3.0,
+            min_minutes: 60,
+        };
+        let data = vec![
+            reading_at("08-01-2024 01:00", 0.5),
+            reading_at("08-01-2024 01:30", 7.5),
+            reading_at("08-01-2024 02:00", 7.5),
+            reading_at("08-01-2024 02:30", 1.5),
+            // a kettle is too short to be a car
+            reading_at("08-01-2024 08:00", 3.0),
+        ];
+        let charging = detection.detect(&data);
+        assert_eq!(charging.sessions.len(), 1);
+        assert_eq!(
+            charging.sessions[0].start.format("%H:%M").to_string(),
+            "01:00"
+        );
+        // the house used 1 kW around the session, the car 6.5 kW for an hour
+        assert_eq!(charging.kwh, 6.5);
+
+        let plan = sse_airtricity_20();
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let total = plan.compute_bill_breakdown(&data, &period).total;
+        let cost = charging.cost(&plan, &data, total);
+        // a flat plan charges the car its unit rate, the house pays the rest of the bill
+        let usage = plan.compute_bill_breakdown(&data, &period).usage;
+        let rate = usage.signed_amount() / Decimal::TEN;
+        assert!((cost.ev_per_kwh - rate).abs() < Decimal::new(1, 3));
+        assert_eq!(cost.ev + cost.house, total);
+    }
+}
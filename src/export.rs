@@ -0,0 +1,142 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::{BillBreakdown, ComparisonReport};
+
+// One line per plan for the whole period followed by one line per plan and month or week,
+// amounts are signed so credits are negative and the columns can be summed in a spreadsheet
+#[derive(Debug, Serialize)]
+struct ResultRow<'a> {
+    mprn: &'a str,
+    plan: &'a str,
+    period: String,
+    start: NaiveDate,
+    end: NaiveDate,
+    usage: Decimal,
+    import_cost: Decimal,
+    export_credit: Decimal,
+    standing_charge: Decimal,
+    pso_levy: Decimal,
+    credits: Decimal,
+    bill_credit: Decimal,
+    demand_charge: Decimal,
+    total: Decimal,
+    with_bonus: Option<Decimal>,
+    saving: Option<Decimal>,
+    saving_percentage: Option<Decimal>,
+}
+
+impl<'a> ResultRow<'a> {
+    fn new(
+        mprn: &'a str,
+        plan: &'a str,
+        period: String,
+        start: NaiveDate,
+        end: NaiveDate,
+        breakdown: &BillBreakdown,
+    ) -> ResultRow<'a> {
+        ResultRow {
+            mprn,
+            plan,
+            period,
+            start,
+            end,
+            usage: breakdown.usage.signed_amount(),
+            import_cost: breakdown.import_cost.signed_amount(),
+            export_credit: breakdown.export_credit.signed_amount(),
+            standing_charge: breakdown.standing_charge.signed_amount(),
+            pso_levy: breakdown.pso_levy.signed_amount(),
+            credits: breakdown.credits.signed_amount(),
+            bill_credit: breakdown.bill_credit.signed_amount(),
+            demand_charge: breakdown.demand_charge.signed_amount(),
+            total: breakdown.total.signed_amount(),
+            with_bonus: None,
+            saving: None,
+            saving_percentage: None,
+        }
+    }
+}
+
+// All the meters go in the same file, one after the other
+pub fn write_results_csv(reports: &[ComparisonReport], writer: impl Write) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    for report in reports {
+        write_report_rows(&mut wtr, report)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_report_rows<W: Write>(wtr: &mut csv::Writer<W>, report: &ComparisonReport) -> Result<()> {
+    let results = report.plans.iter().chain(&report.gas_plans);
+    for result in results.clone() {
+        let mut row = ResultRow::new(
+            &report.mprn,
+            &result.name,
+            "total".to_string(),
+            report.period.start,
+            report.period.end,
+            &result.breakdown,
+        );
+        row.with_bonus = result.with_bonus.map(|total| total.signed_amount());
+        row.saving = result.saving.map(|s| s.amount.amount());
+        row.saving_percentage = result.saving.map(|s| s.percentage);
+        wtr.serialize(row)?;
+    }
+    for result in results {
+        for bucket in &result.buckets {
+            wtr.serialize(ResultRow::new(
+                &report.mprn,
+                &result.name,
+                bucket.bucket.to_string(),
+                bucket.period.start,
+                bucket.period.end,
+                &bucket.breakdown,
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin_plans, compare_meter_points, meter_data::reading_at, ComparisonOptions, Granularity,
+    };
+
+    #[test]
+    fn the_totals_come_before_the_months_of_every_plan() {
+        let data = vec![
+            reading_at("30-01-2024 18:00", 1.5),
+            reading_at("01-02-2024 03:00", 1.0),
+        ];
+        let options = ComparisonOptions {
+            current_plan: Some("SSEAirtricity20".to_string()),
+            granularity: Some(Granularity::Month),
+            ..ComparisonOptions::default()
+        };
+        let reports = compare_meter_points(&builtin_plans()[..2], data, &options).unwrap();
+        let mut csv = vec![];
+        write_results_csv(&reports, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[0].starts_with("mprn,plan,period,start,end,usage,"));
+        assert!(lines[0].ends_with(",total,with_bonus,saving,saving_percentage"));
+        let periods: Vec<&str> = lines[1..]
+            .iter()
+            .map(|line| line.split(',').nth(2).unwrap())
+            .collect();
+        assert_eq!(
+            periods,
+            vec!["total", "total", "2024-01", "2024-02", "2024-01", "2024-02"]
+        );
+        // the current plan saves nothing over itself, the other one has its saving
+        let saving = |line: &str| line.split(',').nth(15).unwrap().to_string();
+        assert_eq!(saving(lines[1]), "");
+        assert_ne!(saving(lines[2]), "");
+    }
+}
@@ -0,0 +1,320 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
+
+use crate::{
+    Bucket, EnergyBillEntry, Granularity, Numeric, PricingNumber, SmartMeterData,
+    SmartMeterDataType,
+};
+
+// Whether a plan takes the exports off the imports before pricing them, only what is left of
+// the larger one is charged or credited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Netting {
+    #[default]
+    None,
+    // the exports of a reading against the imports of the same interval
+    Interval,
+    // the exports of a day against the imports of that day, each reading scaled down alike
+    Day,
+}
+
+// The part of the imports and of the exports left once they are netted
+fn netted_fractions(import_kwh: f32, export_kwh: f32) -> (f32, f32) {
+    let left = |kwh: f32, other: f32| {
+        if kwh > 0.0 {
+            (kwh - other).max(0.0) / kwh
+        } else {
+            1.0
+        }
+    };
+    (left(import_kwh, export_kwh), left(export_kwh, import_kwh))
+}
+
+impl Netting {
+    // The readings with what was netted taken off each of them, in the same order
+    pub fn apply<'a>(&self, datapoints: &'a [SmartMeterData]) -> Cow<'a, [SmartMeterData]> {
+        #[derive(PartialEq, Eq, PartialOrd, Ord)]
+        enum Key {
+            Interval(Arc<str>, NaiveDateTime),
+            Day(Arc<str>, NaiveDate),
+        }
+        if *self == Netting::None {
+            return Cow::Borrowed(datapoints);
+        }
+        let key = |datapoint: &SmartMeterData| match self {
+            Netting::Day => Key::Day(datapoint.mprn.clone(), datapoint.date()),
+            _ => Key::Interval(datapoint.mprn.clone(), datapoint.read_data_and_end_time),
+        };
+        // the imported and exported kWh of each interval or day
+        let mut totals: BTreeMap<Key, (f32, f32)> = BTreeMap::new();
+        for datapoint in datapoints {
+            let total = totals.entry(key(datapoint)).or_default();
+            match datapoint.read_type {
+                SmartMeterDataType::ActiveImport => total.0 += datapoint.energy_kwh(),
+                SmartMeterDataType::ActiveExport => total.1 += datapoint.energy_kwh(),
+            }
+        }
+        let mut netted = datapoints.to_vec();
+        for datapoint in &mut netted {
+            let (import_kwh, export_kwh) = totals[&key(datapoint)];
+            let (imports, exports) = netted_fractions(import_kwh, export_kwh);
+            datapoint.read_value *= match datapoint.read_type {
+                SmartMeterDataType::ActiveImport => imports,
+                SmartMeterDataType::ActiveExport => exports,
+            };
+        }
+        Cow::Owned(netted)
+    }
+}
+
+// How a plan pays for exported energy, by default every exported kWh is credited in full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct ExportRules {
+    // the most exported kWh credited in a billing period, readings past it earn nothing
+    #[serde(default)]
+    pub kwh_cap: Option<Decimal>,
+    // the export credit of a month can't be more than the import charge of that month
+    #[serde(default)]
+    pub limit_to_import: bool,
+    // with limit_to_import, what couldn't be credited goes to the next month instead of being lost
+    #[serde(default)]
+    pub carry_over: bool,
+    // netted before the readings are priced, the caps and limits come after
+    #[serde(default)]
+    pub netting: Netting,
+}
+
+impl ExportRules {
+    pub fn is_unlimited(&self) -> bool {
+        self.kwh_cap.is_none() && !self.limit_to_import
+    }
+
+    // Like capped at 1000 kWh, credited up to the import charge of the month
+    pub fn summary(&self) -> String {
+        let mut parts = vec![];
+        match self.netting {
+            Netting::Interval => {
+                parts.push("netted against the imports of each interval".to_string())
+            }
+            Netting::Day => parts.push("netted against the imports of each day".to_string()),
+            Netting::None => {}
+        }
+        if let Some(cap) = self.kwh_cap {
+            parts.push(format!("capped at {} kWh", cap.normalize()));
+        }
+        if self.limit_to_import {
+            parts.push("credited up to the import charge of the month".to_string());
+        }
+        if self.limit_to_import && self.carry_over {
+            parts.push("the rest carried to the next month".to_string());
+        }
+        if parts.is_empty() {
+            return "every kWh credited in full".to_string();
+        }
+        parts.join(", ")
+    }
+
+    // Adds up the priced readings applying the rules to the credits, prices lines up with datapoints.
+    // The prices are of the netted readings, so the cap counts what is left of them too
+    pub fn apply(
+        &self,
+        datapoints: &[SmartMeterData],
+        prices: &[EnergyBillEntry],
+    ) -> EnergyBillEntry {
+        if self.is_unlimited() {
+            return prices.iter().sum();
+        }
+        let datapoints = self.netting.apply(datapoints);
+
+        let mut order: Vec<usize> = (0..datapoints.len()).collect();
+        order.sort_by_key(|&i| datapoints[i].read_data_and_end_time);
+
+        // import charge and export credit per month, both positive
+        let mut months: BTreeMap<Bucket, (Decimal, Decimal)> = BTreeMap::new();
+        let mut exported_kwh = PricingNumber::default();
+        for i in order {
+            let datapoint = &datapoints[i];
+            let amount = prices[i].signed_amount();
+            let month = months
+                .entry(Bucket::of(datapoint.date(), Granularity::Month))
+                .or_default();
+            match datapoint.read_type {
+                SmartMeterDataType::ActiveImport => month.0 += amount,
+                SmartMeterDataType::ActiveExport => {
+                    let kwh = PricingNumber::from_f32(datapoint.energy_kwh());
+                    let credited = self.credited_fraction(exported_kwh, kwh);
+                    exported_kwh += kwh;
+                    month.1 += -amount * credited.to_decimal();
+                }
+            }
+        }
+
+        let mut total = Decimal::ZERO;
+        let mut carried = Decimal::ZERO;
+        for (import, credit) in months.into_values() {
+            let available = credit + carried;
+            let applied = if self.limit_to_import {
+                available.min(import.max(Decimal::ZERO))
+            } else {
+                available
+            };
+            carried = if self.carry_over {
+                available - applied
+            } else {
+                Decimal::ZERO
+            };
+            total += import - applied;
+        }
+        EnergyBillEntry::from_signed(total)
+    }
+
+    // The part of a reading of kwh that is still under the cap after exported_kwh were credited
+    fn credited_fraction<N: Numeric>(&self, exported_kwh: N, kwh: N) -> N {
+        let zero = N::default();
+        match self.kwh_cap {
+            Some(_) if kwh == zero => N::ONE,
+            Some(cap) => {
+                let left = N::from_decimal(cap) - exported_kwh;
+                let credited = if left < zero {
+                    zero
+                } else if left > kwh {
+                    kwh
+                } else {
+                    left
+                };
+                credited / kwh
+            }
+            None => N::ONE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, Money};
+    use rust_decimal_macros::dec;
+
+    fn export_at(datetime: &str, read_value: f32) -> SmartMeterData {
+        SmartMeterData {
+            read_type: SmartMeterDataType::ActiveExport,
+            ..reading_at(datetime, read_value)
+        }
+    }
+
+    // 1 kWh of import or export per reading, charged and credited at 0.30
+    fn priced(datapoints: &[SmartMeterData]) -> Vec<EnergyBillEntry> {
+        datapoints
+            .iter()
+            .map(|d| {
+                let money = Money::for_quantity(dec!(0.30), d.energy_kwh());
+                match d.read_type {
+                    SmartMeterDataType::ActiveImport => EnergyBillEntry::Debit(money),
+                    SmartMeterDataType::ActiveExport => EnergyBillEntry::Credit(money),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn netting_leaves_what_is_left_of_the_larger_side() {
+        // 1 kWh imported and 0.25 exported at noon, 0.5 kWh exported at one
+        let data = vec![
+            reading_at("08-01-2024 12:00", 2.0),
+            export_at("08-01-2024 12:00", 0.5),
+            export_at("08-01-2024 13:00", 1.0),
+        ];
+        let kwh = |netting: Netting| -> Vec<f32> {
+            netting
+                .apply(&data)
+                .iter()
+                .map(SmartMeterData::energy_kwh)
+                .collect()
+        };
+        assert_eq!(kwh(Netting::Interval), vec![0.75, 0.0, 0.5]);
+        assert_eq!(kwh(Netting::Day), vec![0.25, 0.0, 0.0]);
+        assert!(matches!(Netting::None.apply(&data), Cow::Borrowed(_)));
+
+        let rules: ExportRules = toml::from_str(r#"netting = "day""#).unwrap();
+        assert_eq!(rules.netting, Netting::Day);
+        assert!(rules.is_unlimited());
+        assert_eq!(rules.summary(), "netted against the imports of each day");
+    }
+
+    #[test]
+    fn kwh_cap_stops_crediting_part_way_through_a_reading() {
+        let data = vec![
+            export_at("08-01-2024 12:00", 2.0),
+            export_at("08-01-2024 12:30", 2.0),
+        ];
+        let rules = ExportRules {
+            kwh_cap: Some(dec!(1.5)),
+            ..ExportRules::default()
+        };
+        assert_eq!(
+            rules.apply(&data, &priced(&data)),
+            EnergyBillEntry::Credit(Money::new(dec!(0.45)))
+        );
+    }
+
+    #[test]
+    fn the_cap_is_worked_out_alike_in_floats_and_decimals() {
+        let rules: ExportRules = toml::from_str("kwh_cap = 1.5").unwrap();
+        for (exported, kwh) in [(0.0, 1.0), (1.0, 1.0), (2.0, 1.0), (0.5, 0.0)] {
+            let exact: Decimal =
+                rules.credited_fraction(Decimal::from_f32(exported), Decimal::from_f32(kwh));
+            let float: f64 = rules.credited_fraction(exported as f64, kwh as f64);
+            assert_eq!(exact, float.to_decimal());
+        }
+    }
+
+    #[test]
+    fn kwh_cap_counts_the_netted_exports() {
+        // 1 kWh imported and 2 exported at noon, 1 kWh exported at one, 2 kWh left either way
+        let data = vec![
+            reading_at("08-01-2024 12:00", 2.0),
+            export_at("08-01-2024 12:00", 4.0),
+            export_at("08-01-2024 13:00", 2.0),
+        ];
+        for netting in [Netting::Interval, Netting::Day] {
+            let rules = ExportRules {
+                kwh_cap: Some(dec!(1.5)),
+                netting,
+                ..ExportRules::default()
+            };
+            let total = rules.apply(&data, &priced(&netting.apply(&data)));
+            assert_eq!(total.signed_amount().round_dp(2), dec!(-0.45));
+        }
+    }
+
+    #[test]
+    fn credit_over_the_import_charge_carries_to_the_next_month() {
+        // january exports 2 kWh and imports 1, february imports 1
+        let data = vec![
+            reading_at("08-01-2024 12:00", 2.0),
+            export_at("08-01-2024 12:00", 2.0),
+            export_at("08-01-2024 12:30", 2.0),
+            reading_at("08-02-2024 12:00", 2.0),
+        ];
+        let limited = ExportRules {
+            limit_to_import: true,
+            ..ExportRules::default()
+        };
+        assert_eq!(
+            limited.apply(&data, &priced(&data)),
+            EnergyBillEntry::Debit(Money::new(dec!(0.30)))
+        );
+
+        let carried = ExportRules {
+            carry_over: true,
+            ..limited
+        };
+        assert_eq!(
+            carried.apply(&data, &priced(&data)),
+            EnergyBillEntry::Debit(Money::new(dec!(0.00)))
+        );
+    }
+}
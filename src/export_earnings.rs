@@ -0,0 +1,127 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    Bucket, EnergyBillEntry, Granularity, Money, PricePlanStrategy, SmartMeterData,
+    SmartMeterDataType,
+};
+
+// The exports of a month and what the export rate of the plan pays for them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MonthExport {
+    pub month: Bucket,
+    pub kwh: f32,
+    pub earned: Money,
+}
+
+// What the exports earn on a plan. earned is every kWh at the export rate, credited is what
+// is left of it on the bill once the export rules of the plan have netted it against the
+// imports or capped it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportEarnings {
+    pub kwh: f32,
+    pub earned: Money,
+    pub credited: Money,
+    pub rules: String,
+    pub months: Vec<MonthExport>,
+}
+
+// None without any exports
+pub fn export_earnings(
+    plan: &dyn PricePlanStrategy,
+    datapoints: &[SmartMeterData],
+) -> Option<ExportEarnings> {
+    // priced apart the imports and the exports have nothing to be netted against
+    let (imports, exports): (Vec<SmartMeterData>, Vec<SmartMeterData>) = datapoints
+        .iter()
+        .cloned()
+        .partition(|d| d.read_type == SmartMeterDataType::ActiveImport);
+    let mut months: BTreeMap<Bucket, (f32, Decimal)> = BTreeMap::new();
+    for (datapoint, price) in exports.iter().zip(plan.price_readings(&exports)) {
+        let month = months
+            .entry(Bucket::of(datapoint.date(), Granularity::Month))
+            .or_default();
+        month.0 += datapoint.energy_kwh();
+        month.1 -= price.signed_amount();
+    }
+    if months.is_empty() {
+        return None;
+    }
+    let rounding = plan.rounding();
+    let money = |amount: Decimal| Money::of(amount, plan.currency()).round(&rounding);
+    // what the exports take off the usage of the bill once the rules are applied
+    let imported: EnergyBillEntry = plan.price_readings(&imports).iter().sum();
+    let credited = imported.signed_amount()
+        - plan
+            .compute_total_bill_for_period(datapoints)
+            .signed_amount();
+    Some(ExportEarnings {
+        kwh: months.values().map(|(kwh, _)| kwh).sum(),
+        earned: money(months.values().map(|(_, earned)| earned).sum()),
+        credited: money(credited),
+        rules: plan.export_rules().summary(),
+        months: months
+            .into_iter()
+            .map(|(month, (kwh, earned))| MonthExport {
+                month,
+                kwh,
+                earned: money(earned),
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, ConfigurablePlan};
+
+    fn export_at(datetime: &str, read_value: f32) -> SmartMeterData {
+        SmartMeterData {
+            read_type: SmartMeterDataType::ActiveExport,
+            ..reading_at(datetime, read_value)
+        }
+    }
+
+    #[test]
+    fn the_import_limit_holds_back_what_the_exports_earned() {
+        let plan: ConfigurablePlan = toml::from_str(
+            r#"
+name = "netted"
+standing_charge = { per_day = 0.5 }
+vat_included = 0
+export_rate = 0.20
+export = { limit_to_import = true }
+
+[[bands]]
+name = "all day"
+start = "00:00"
+end = "00:00"
+rate = 0.40
+"#,
+        )
+        .unwrap();
+        // 2 kWh exported in january against 0.5 kWh imported, 1 kWh exported in february
+        let data = vec![
+            reading_at("08-01-2024 12:00", 1.0),
+            export_at("08-01-2024 13:00", 4.0),
+            export_at("08-02-2024 13:00", 2.0),
+        ];
+        let earnings = export_earnings(&plan, &data).unwrap();
+        assert_eq!(earnings.kwh, 3.0);
+        assert_eq!(earnings.months.len(), 2);
+        assert_eq!(earnings.months[1].kwh, 1.0);
+        let earned = earnings.months[0].earned.amount() + earnings.months[1].earned.amount();
+        assert_eq!(earnings.earned.amount(), earned);
+        // january credits no more than its imports, february has none to credit against
+        assert!(earnings.credited.amount() < earnings.earned.amount());
+        let imports = plan.price_readings(&data[..1])[0].signed_amount();
+        assert_eq!(earnings.credited.amount(), imports.round_dp(2));
+        assert_eq!(
+            earnings.rules,
+            "credited up to the import charge of the month"
+        );
+        assert!(export_earnings(&plan, &data[..1]).is_none());
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::NaiveTime;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::{BillBreakdown, DaySet, RateBand};
+
+// The hours of free energy of a plan, on the days given or every day without any
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FreeWindow {
+    #[serde(default)]
+    pub days: DaySet,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+// Like Sat 09:00-17:00, or every day 11:00-13:00
+impl Display for FreeWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}-{}",
+            self.days,
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct FreeTimeFields {
+    #[serde(flatten)]
+    chosen: FreeWindow,
+    #[serde(default)]
+    choices: Vec<FreeWindow>,
+}
+
+// Free time plans let the customer pick the free day or hours from the choices of the
+// supplier. The chosen window is priced at zero before any band of the plan
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "FreeTimeFields")]
+pub struct FreeTime {
+    pub chosen: FreeWindow,
+    pub choices: Vec<FreeWindow>,
+    band: RateBand,
+}
+
+impl From<FreeTimeFields> for FreeTime {
+    fn from(fields: FreeTimeFields) -> FreeTime {
+        FreeTime::new(fields.chosen, fields.choices)
+    }
+}
+
+impl FreeTime {
+    pub fn new(chosen: FreeWindow, choices: Vec<FreeWindow>) -> FreeTime {
+        let band = RateBand {
+            name: "free".to_string(),
+            days: chosen.days.clone(),
+            start: chosen.start,
+            end: chosen.end,
+            rate: Decimal::ZERO,
+            season: None,
+        };
+        FreeTime {
+            chosen,
+            choices,
+            band,
+        }
+    }
+
+    pub fn band(&self) -> &RateBand {
+        &self.band
+    }
+
+    // Every window that can be picked, the chosen one too when it isn't one of the choices
+    pub fn windows(&self) -> Vec<FreeWindow> {
+        let mut windows = self.choices.clone();
+        if !windows.contains(&self.chosen) {
+            windows.insert(0, self.chosen.clone());
+        }
+        windows
+    }
+
+    // The same choices with another one picked
+    pub fn choosing(&self, window: &FreeWindow) -> FreeTime {
+        FreeTime::new(window.clone(), self.choices.clone())
+    }
+}
+
+// What a plan costs with one of its free time choices picked
+#[derive(Debug, Clone, Serialize)]
+pub struct FreeTimeChoice {
+    pub window: String,
+    pub breakdown: BillBreakdown,
+    // the window the plan was compared with
+    pub chosen: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        meter_data::reading_at, BillingPeriod, ConfigurablePlan, PricePlanStrategy, SmartMeterData,
+    };
+
+    #[test]
+    fn every_choice_is_priced() {
+        let plan: ConfigurablePlan = toml::from_str(
+            r#"
+name = "free time"
+standing_charge = { per_day = 0.5 }
+free_time = { days = ["Sun"], start = "09:00", end = "17:00", choices = [
+    { days = ["Sat"], start = "09:00", end = "17:00" },
+    { days = ["Sun"], start = "09:00", end = "17:00" },
+] }
+
+[[bands]]
+name = "all day"
+start = "00:00"
+end = "00:00"
+rate = 0.40
+"#,
+        )
+        .unwrap();
+        // a busy saturday and a quiet sunday, 6 and 13 january 2024 are saturdays
+        let data: Vec<SmartMeterData> = vec![
+            reading_at("06-01-2024 12:00", 4.0),
+            reading_at("07-01-2024 12:00", 1.0),
+        ];
+        assert_eq!(
+            plan.band_for_singe_period(&data[1]),
+            "free",
+            "the chosen sunday is free"
+        );
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let choices = plan.free_time_choices();
+        assert_eq!(choices.len(), 2);
+        let totals: Vec<Decimal> = choices
+            .iter()
+            .map(|(_, plan)| {
+                plan.compute_bill_breakdown(&data, &period)
+                    .usage
+                    .signed_amount()
+            })
+            .collect();
+        // free saturdays leave the 0.5 kWh of sunday, free sundays the 2 kWh of saturday
+        assert!(totals[0] < totals[1]);
+        assert_eq!(choices[0].0.to_string(), "Sat 09:00-17:00");
+    }
+}
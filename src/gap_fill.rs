@@ -0,0 +1,197 @@
+use chrono::{Duration, NaiveDateTime};
+use serde::Serialize;
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{validate_readings, Gap, SmartMeterData, SmartMeterDataType};
+
+// How the readings of a short gap are made up, a communication outage usually loses a few
+// hours of an otherwise complete month
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GapFillStrategy {
+    // the reading of the same time a week before, or a week after in the first week
+    LastWeek,
+    // a straight line from the reading before the gap to the one after it
+    Interpolate,
+    Zero,
+}
+
+impl Display for GapFillStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GapFillStrategy::LastWeek => "last week",
+            GapFillStrategy::Interpolate => "interpolated",
+            GapFillStrategy::Zero => "zero",
+        })
+    }
+}
+
+// A gap and what it was filled with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilledGap {
+    pub gap: Gap,
+    pub strategy: GapFillStrategy,
+}
+
+// The gaps that got readings made up for them and the ones left as they were, longer than the
+// limit or without a reading a week away for every interval
+#[derive(Debug, Clone, Default)]
+pub struct GapFilling {
+    pub filled: Vec<FilledGap>,
+    pub left: Vec<Gap>,
+}
+
+impl GapFilling {
+    // What the reading was filled with, none for a real reading
+    pub fn strategy_for(&self, datapoint: &SmartMeterData) -> Option<GapFillStrategy> {
+        self.filled
+            .iter()
+            .find(|filled| {
+                *filled.gap.mprn == *datapoint.mprn
+                    && filled.gap.read_type == datapoint.read_type
+                    && filled.gap.after < datapoint.read_data_and_end_time
+                    && datapoint.read_data_and_end_time < filled.gap.before
+            })
+            .map(|filled| filled.strategy)
+    }
+
+    pub fn filled_readings(&self) -> i64 {
+        self.filled
+            .iter()
+            .map(|filled| filled.gap.missing_intervals)
+            .sum()
+    }
+}
+
+// Fills the gaps of up to max_gap of missing time with readings of the strategy. With any
+// filled the readings come back sorted by meter, direction and time
+pub fn fill_gaps(
+    mut data: Vec<SmartMeterData>,
+    strategy: GapFillStrategy,
+    max_gap: Duration,
+) -> (Vec<SmartMeterData>, GapFilling) {
+    let report = validate_readings(&data);
+    let mut filling = GapFilling::default();
+    if report.gaps.is_empty() {
+        return (data, filling);
+    }
+    let by_time: HashMap<(&str, SmartMeterDataType, NaiveDateTime), &SmartMeterData> = data
+        .iter()
+        .map(|d| ((&*d.mprn, d.read_type, d.read_data_and_end_time), d))
+        .collect();
+    let mut added = vec![];
+    for gap in report.gaps {
+        let Some(before) = by_time.get(&(gap.mprn.as_str(), gap.read_type, gap.before)) else {
+            continue;
+        };
+        let after = by_time[&(gap.mprn.as_str(), gap.read_type, gap.after)];
+        let interval = Duration::minutes(i64::from(before.interval_minutes));
+        if interval * gap.missing_intervals as i32 > max_gap {
+            filling.left.push(gap);
+            continue;
+        }
+        let week = Duration::days(7);
+        let readings: Option<Vec<SmartMeterData>> = (1..=gap.missing_intervals)
+            .map(|step| {
+                let end_time = gap.after + interval * step as i32;
+                let read_value = match strategy {
+                    GapFillStrategy::Zero => 0.0,
+                    GapFillStrategy::Interpolate => {
+                        let share = step as f32 / (gap.missing_intervals + 1) as f32;
+                        after.read_value + (before.read_value - after.read_value) * share
+                    }
+                    // in UTC, a week across a change of the clocks is an hour off in local time
+                    GapFillStrategy::LastWeek => {
+                        [end_time - week, end_time + week]
+                            .iter()
+                            .find_map(|time| {
+                                by_time.get(&(gap.mprn.as_str(), gap.read_type, *time))
+                            })?
+                            .read_value
+                    }
+                };
+                Some(SmartMeterData {
+                    read_value,
+                    read_data_and_end_time: end_time,
+                    ..(*before).clone()
+                })
+            })
+            .collect();
+        match readings {
+            Some(readings) => {
+                added.extend(readings);
+                filling.filled.push(FilledGap { gap, strategy });
+            }
+            None => filling.left.push(gap),
+        }
+    }
+    if added.is_empty() {
+        return (data, filling);
+    }
+    data.extend(added);
+    data.sort_by(|a, b| {
+        (&a.mprn, a.read_type, a.read_data_and_end_time).cmp(&(
+            &b.mprn,
+            b.read_type,
+            b.read_data_and_end_time,
+        ))
+    });
+    (data, filling)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn short_gaps_are_filled_and_marked() {
+        // two weeks of half hours with 02:00 to 03:00 of the second monday missing
+        let data: Vec<SmartMeterData> = (8..22)
+            .flat_map(|day| (0..48).map(move |half| (day, half)))
+            .map(|(day, half)| {
+                let time = format!("{day:02}-01-2024 {:02}:{:02}", half / 2, half % 2 * 30);
+                reading_at(&time, half as f32 / 10.0)
+            })
+            .filter(|d| {
+                let time = d.read_data_and_end_time.format("%d %H:%M").to_string();
+                !["15 02:00", "15 02:30", "15 03:00"].contains(&time.as_str())
+            })
+            .collect();
+        let missing = reading_at("15-01-2024 02:30", 0.0);
+
+        let (filled, filling) =
+            fill_gaps(data.clone(), GapFillStrategy::LastWeek, Duration::hours(6));
+        assert_eq!(filled.len(), 14 * 48);
+        assert_eq!(filling.filled_readings(), 3);
+        assert!(filling.left.is_empty());
+        let copied = filled
+            .iter()
+            .find(|d| d.read_data_and_end_time == missing.read_data_and_end_time)
+            .unwrap();
+        assert_eq!(copied.read_value, 0.5);
+        assert_eq!(
+            filling.strategy_for(copied),
+            Some(GapFillStrategy::LastWeek)
+        );
+        assert_eq!(filling.strategy_for(&filled[0]), None);
+
+        // 01:30 read 0.3 and 03:30 0.7, a quarter of the way is 0.4
+        let (filled, _) = fill_gaps(
+            data.clone(),
+            GapFillStrategy::Interpolate,
+            Duration::hours(6),
+        );
+        let first = filled
+            .iter()
+            .find(|d| {
+                d.read_data_and_end_time
+                    == reading_at("15-01-2024 02:00", 0.0).read_data_and_end_time
+            })
+            .unwrap();
+        assert!((first.read_value - 0.4).abs() < 1e-6);
+
+        let (filled, filling) = fill_gaps(data, GapFillStrategy::Zero, Duration::hours(1));
+        assert_eq!(filled.len(), 14 * 48 - 3);
+        assert_eq!(filling.left.len(), 1);
+    }
+}
@@ -0,0 +1,253 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::fmt::Debug;
+
+use crate::{
+    annual_to_daily, meter_data::smart_meter_datetime_desserialize, with_vat_on, BillBreakdown,
+    BillingPeriod, DuosGroup, EnergyBillEntry, Money, Rounding, StandingCharge,
+};
+
+// Defines the signature for the functions to define the price for a gas plan
+// gas has no time of use bands, so only a unit rate is needed
+pub trait GasPlanStrategy: StandingCharge {
+    fn unit_rate(&self) -> Decimal;
+
+    fn rounding(&self) -> Rounding {
+        Rounding::default()
+    }
+
+    fn price_for_gas_reading(
+        &self,
+        reading: &GasMeterData,
+        conversion: &GasConversion,
+    ) -> EnergyBillEntry {
+        with_vat_on(
+            EnergyBillEntry::Debit(Money::for_quantity(
+                self.unit_rate(),
+                reading.energy_kwh(conversion),
+            )),
+            self.vat_included(),
+            reading.date(),
+            self.currency(),
+        )
+    }
+
+    fn compute_total_gas_bill_for_period(
+        &self,
+        readings: &[GasMeterData],
+        conversion: &GasConversion,
+    ) -> EnergyBillEntry {
+        readings
+            .iter()
+            .map(|r| self.price_for_gas_reading(r, conversion))
+            .sum()
+    }
+
+    fn compute_gas_bill_breakdown(
+        &self,
+        readings: &[GasMeterData],
+        conversion: &GasConversion,
+        period: &BillingPeriod,
+    ) -> BillBreakdown {
+        let rounding = self.rounding();
+        let usage = self
+            .compute_total_gas_bill_for_period(readings, conversion)
+            .round(&rounding);
+        let standing_charge = self.standing_charge_for_period(period).round(&rounding);
+        BillBreakdown {
+            usage,
+            import_cost: usage,
+            export_credit: EnergyBillEntry::ZERO,
+            standing_charge,
+            pso_levy: EnergyBillEntry::ZERO,
+            credits: EnergyBillEntry::ZERO,
+            bill_credit: EnergyBillEntry::ZERO,
+            demand_charge: EnergyBillEntry::ZERO,
+            total: usage + standing_charge,
+        }
+    }
+}
+
+// A gas plan with a single unit rate, also read from the [[gas_plan]] of a tariff file
+#[derive(Clone, Deserialize)]
+pub struct FlatRateGasPlan {
+    pub name: String,
+    pub unit_rate: Decimal,
+    #[serde(default)]
+    pub discount: Decimal,
+    pub annual_standing_charge: Decimal,
+}
+
+// The name is enough to identify the plan on the output
+impl Debug for FlatRateGasPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl GasPlanStrategy for FlatRateGasPlan {
+    fn unit_rate(&self) -> Decimal {
+        self.unit_rate * (Decimal::ONE - self.discount)
+    }
+}
+
+impl StandingCharge for FlatRateGasPlan {
+    // gas has no network groups, every home pays the same
+    fn standing_charge_per_day(&self, date: NaiveDate, _group: DuosGroup) -> EnergyBillEntry {
+        EnergyBillEntry::Debit(Money::new(annual_to_daily(
+            self.annual_standing_charge,
+            date,
+        )))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum GasMeterUnit {
+    #[serde(rename = "kWh")]
+    KilowattHours,
+    #[serde(rename = "m3")]
+    CubicMetres,
+}
+
+// Converts a gas volume into energy, kWh = m3 * correction factor * calorific value / 3.6
+#[derive(Debug, Clone, Copy)]
+pub struct GasConversion {
+    // MJ/m3, published by Gas Networks Ireland and usually around 39 to 40
+    pub calorific_value: f32,
+    // accounts for temperature and pressure at the meter
+    pub correction_factor: f32,
+}
+
+impl Default for GasConversion {
+    fn default() -> Self {
+        GasConversion {
+            calorific_value: 39.5,
+            correction_factor: 1.02264,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GasMeterData {
+    //format:
+    // GPRN,Read Value,Read Unit,Read Date and End Time
+    // 1234567,3.2,m3,08-01-2024 00:00
+    #[serde(rename = "GPRN")]
+    pub gprn: String,
+    #[serde(rename = "Read Value")]
+    pub read_value: f32,
+    #[serde(rename = "Read Unit")]
+    pub read_unit: GasMeterUnit,
+    #[serde(
+        rename = "Read Date and End Time",
+        deserialize_with = "smart_meter_datetime_desserialize"
+    )]
+    pub read_data_and_end_time: NaiveDateTime,
+}
+
+// Daily readings for households that only know their usage, like from a gas bill, each one
+// ends at midnight so it belongs to the day before
+pub fn estimated_gas_readings(period: &BillingPeriod, kwh_per_day: f32) -> Vec<GasMeterData> {
+    period
+        .days()
+        .filter_map(|date| {
+            Some(GasMeterData {
+                gprn: "estimate".to_string(),
+                read_value: kwh_per_day,
+                read_unit: GasMeterUnit::KilowattHours,
+                read_data_and_end_time: date.succ_opt()?.and_hms_opt(0, 0, 0)?,
+            })
+        })
+        .collect()
+}
+
+impl GasMeterData {
+    // Readings are timestamped at the end of their interval, so midnight belongs to the day before
+    pub fn date(&self) -> NaiveDate {
+        (self.read_data_and_end_time - Duration::seconds(1)).date()
+    }
+
+    fn energy_kwh(&self, conversion: &GasConversion) -> f32 {
+        match self.read_unit {
+            GasMeterUnit::KilowattHours => self.read_value,
+            GasMeterUnit::CubicMetres => {
+                self.read_value * conversion.correction_factor * conversion.calorific_value / 3.6
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builtin_gas_plans, rank_gas_plans};
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn reading(read_value: f32, read_unit: GasMeterUnit) -> GasMeterData {
+        GasMeterData {
+            gprn: "1234567".to_string(),
+            read_value,
+            read_unit,
+            read_data_and_end_time: NaiveDate::from_ymd_opt(2024, 1, 9)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        }
+    }
+
+    #[test]
+    fn cubic_metres_are_converted_with_the_calorific_value() {
+        let conversion = GasConversion::default();
+        let kwh = reading(10.0, GasMeterUnit::CubicMetres).energy_kwh(&conversion);
+        assert!((kwh - 10.0 * 1.02264 * 39.5 / 3.6).abs() < 1e-3);
+        assert_eq!(
+            reading(10.0, GasMeterUnit::KilowattHours).energy_kwh(&conversion),
+            10.0
+        );
+        let from_bill = GasConversion {
+            calorific_value: 36.0,
+            correction_factor: 1.0,
+        };
+        assert_eq!(
+            reading(10.0, GasMeterUnit::CubicMetres).energy_kwh(&from_bill),
+            100.0
+        );
+    }
+
+    #[test]
+    fn an_estimated_usage_gets_a_reading_for_every_day_of_the_period() {
+        let period = BillingPeriod {
+            start: NaiveDate::from_ymd_opt(2024, 2, 27).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+        };
+        let readings = estimated_gas_readings(&period, 30.0);
+        let days: Vec<NaiveDate> = readings.iter().map(|r| r.date()).collect();
+        assert_eq!(days, period.days().collect::<Vec<_>>());
+        let conversion = GasConversion::default();
+        let kwh: f32 = readings.iter().map(|r| r.energy_kwh(&conversion)).sum();
+        assert_eq!(kwh, 120.0);
+        let ranking = rank_gas_plans(&builtin_gas_plans(), &readings, &conversion, &period);
+        assert_eq!(ranking[0].breakdown.usage.signed_amount(), dec!(14.47));
+    }
+
+    #[test]
+    fn gas_plans_are_ranked_cheapest_first() {
+        // a day of 100 kWh
+        let readings = [reading(10.0, GasMeterUnit::CubicMetres)];
+        let period = BillingPeriod {
+            start: NaiveDate::from_ymd_opt(2024, 1, 8).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 1, 8).unwrap(),
+        };
+        let conversion = GasConversion {
+            calorific_value: 36.0,
+            correction_factor: 1.0,
+        };
+        let ranking = rank_gas_plans(&builtin_gas_plans(), &readings, &conversion, &period);
+        let names: Vec<&str> = ranking.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["Electric Ireland Gas", "Bord Gais Energy Gas"]);
+        assert_eq!(ranking[0].breakdown.usage.signed_amount(), dec!(12.06));
+        assert!(ranking[0].breakdown.total < ranking[1].breakdown.total);
+    }
+}
@@ -0,0 +1,142 @@
+use chrono::{Datelike, Duration, NaiveDate, Timelike};
+use std::{collections::BTreeSet, fmt::Write};
+
+use crate::{SmartMeterData, SmartMeterDataType};
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+// The average energy of each hour of each day of the week in local time, Monday first, to
+// see when the energy is used before choosing between a flat and a time of use plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageHeatmap {
+    pub import: [[f32; 24]; 7],
+    pub export: [[f32; 24]; 7],
+}
+
+pub fn usage_heatmap(datapoints: &[SmartMeterData]) -> UsageHeatmap {
+    let mut import = [[0.0; 24]; 7];
+    let mut export = [[0.0; 24]; 7];
+    // a weekday missing from the readings stays empty instead of dividing by nothing
+    let mut dates: [BTreeSet<NaiveDate>; 7] = Default::default();
+    for datapoint in datapoints {
+        // readings are stamped at the end of their interval, the hour is the one it started in
+        let start =
+            datapoint.local_end_time() - Duration::minutes(datapoint.interval_minutes as i64);
+        let day = start.weekday().num_days_from_monday() as usize;
+        let cells = match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => &mut import,
+            SmartMeterDataType::ActiveExport => &mut export,
+        };
+        cells[day][start.hour() as usize] += datapoint.energy_kwh();
+        dates[day].insert(start.date());
+    }
+    for (day, dates) in dates.iter().enumerate() {
+        let count = dates.len().max(1) as f32;
+        for hour in 0..24 {
+            import[day][hour] /= count;
+            export[day][hour] /= count;
+        }
+    }
+    UsageHeatmap { import, export }
+}
+
+const CELL_WIDTH: usize = 28;
+const CELL_HEIGHT: usize = 22;
+const LEFT: usize = 44;
+const PANEL_HEIGHT: usize = 30 + 7 * CELL_HEIGHT + 24;
+
+// One grid of the chart, the darkest cell is the busiest hour of the grid
+fn panel(svg: &mut String, top: usize, title: &str, cells: &[[f32; 24]; 7], rgb: (u8, u8, u8)) {
+    let max = cells
+        .iter()
+        .flatten()
+        .fold(0.0_f32, |max, &kwh| max.max(kwh));
+    let _ = writeln!(
+        svg,
+        r#"<text x="{LEFT}" y="{}" font-weight="bold">{title}, darkest {max:.2} kWh</text>"#,
+        top + 18
+    );
+    for (day, hours) in cells.iter().enumerate() {
+        let y = top + 30 + day * CELL_HEIGHT;
+        let _ = writeln!(
+            svg,
+            r#"<text x="4" y="{}">{}</text>"#,
+            y + CELL_HEIGHT - 7,
+            WEEKDAYS[day]
+        );
+        for (hour, kwh) in hours.iter().enumerate() {
+            let strength = if max > 0.0 { kwh / max } else { 0.0 };
+            // from white to the colour of the grid
+            let mix = |channel: u8| (255.0 - (255.0 - channel as f32) * strength).round() as u8;
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{}" y="{y}" width="{CELL_WIDTH}" height="{CELL_HEIGHT}" fill="#{:02x}{:02x}{:02x}" stroke="#ffffff"><title>{} {hour:02}:00 {kwh:.3} kWh</title></rect>"##,
+                LEFT + hour * CELL_WIDTH,
+                mix(rgb.0),
+                mix(rgb.1),
+                mix(rgb.2),
+                WEEKDAYS[day]
+            );
+        }
+    }
+    let labels = top + 30 + 7 * CELL_HEIGHT + 16;
+    for hour in (0..24).step_by(3) {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{labels}">{hour:02}:00</text>"#,
+            LEFT + hour * CELL_WIDTH
+        );
+    }
+}
+
+// The heatmap as a standalone svg, the imports on top and the exports under them when there
+// are any. Hovering a cell shows its hour and energy
+pub fn render_heatmap_svg(heatmap: &UsageHeatmap) -> String {
+    let has_export = heatmap.export.iter().flatten().any(|&kwh| kwh > 0.0);
+    let panels = if has_export { 2 } else { 1 };
+    let (width, height) = (LEFT + 24 * CELL_WIDTH + 10, panels * PANEL_HEIGHT);
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="sans-serif" font-size="12">
+"#
+    );
+    panel(
+        &mut svg,
+        0,
+        "Average import per hour",
+        &heatmap.import,
+        (0xc0, 0x39, 0x2b),
+    );
+    if has_export {
+        panel(
+            &mut svg,
+            PANEL_HEIGHT,
+            "Average export per hour",
+            &heatmap.export,
+            (0x27, 0xae, 0x60),
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn hours_are_averaged_over_the_days_of_the_week() {
+        // two Mondays, the reading ending at 19:00 is the 18:00 hour
+        let data = vec![
+            reading_at("08-01-2024 18:30", 2.0),
+            reading_at("08-01-2024 19:00", 2.0),
+            reading_at("15-01-2024 19:00", 1.0),
+        ];
+        let heatmap = usage_heatmap(&data);
+        assert_eq!(heatmap.import[0][18], 1.25);
+        assert_eq!(heatmap.import[1][18], 0.0);
+        let svg = render_heatmap_svg(&heatmap);
+        assert!(svg.contains("Mon 18:00 1.250 kWh"));
+        assert!(!svg.contains("Average export"));
+    }
+}
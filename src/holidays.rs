@@ -0,0 +1,224 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::{collections::BTreeSet, path::Path, sync::OnceLock};
+
+// Irish public holidays, with the day off given when one falls on a weekend
+const IRISH_BANK_HOLIDAYS: &[(i32, u32, u32)] = &[
+    (2023, 1, 1),
+    (2023, 1, 2),
+    (2023, 2, 6),
+    (2023, 3, 17),
+    (2023, 4, 10),
+    (2023, 5, 1),
+    (2023, 6, 5),
+    (2023, 8, 7),
+    (2023, 10, 30),
+    (2023, 12, 25),
+    (2023, 12, 26),
+    (2024, 1, 1),
+    (2024, 2, 5),
+    (2024, 3, 17),
+    (2024, 3, 18),
+    (2024, 4, 1),
+    (2024, 5, 6),
+    (2024, 6, 3),
+    (2024, 8, 5),
+    (2024, 10, 28),
+    (2024, 12, 25),
+    (2024, 12, 26),
+    (2025, 1, 1),
+    (2025, 2, 3),
+    (2025, 3, 17),
+    (2025, 4, 21),
+    (2025, 5, 5),
+    (2025, 6, 2),
+    (2025, 8, 4),
+    (2025, 10, 27),
+    (2025, 12, 25),
+    (2025, 12, 26),
+    (2026, 1, 1),
+    (2026, 2, 2),
+    (2026, 3, 17),
+    (2026, 4, 6),
+    (2026, 5, 4),
+    (2026, 6, 1),
+    (2026, 8, 3),
+    (2026, 10, 26),
+    (2026, 12, 25),
+    (2026, 12, 26),
+    (2026, 12, 28),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HolidayCalendar {
+    dates: BTreeSet<NaiveDate>,
+}
+
+// Changes to the built in calendar, see holidays.example.toml
+#[derive(Debug, Default, Deserialize)]
+pub struct HolidayOverrides {
+    #[serde(default)]
+    pub add: Vec<NaiveDate>,
+    #[serde(default)]
+    pub remove: Vec<NaiveDate>,
+}
+
+static CALENDAR: OnceLock<HolidayCalendar> = OnceLock::new();
+
+impl HolidayCalendar {
+    pub fn irish() -> HolidayCalendar {
+        HolidayCalendar {
+            dates: IRISH_BANK_HOLIDAYS
+                .iter()
+                .filter_map(|&(year, month, day)| NaiveDate::from_ymd_opt(year, month, day))
+                .collect(),
+        }
+    }
+
+    pub fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.dates.contains(&date)
+    }
+
+    pub fn with_overrides(mut self, overrides: &HolidayOverrides) -> HolidayCalendar {
+        self.dates.extend(&overrides.add);
+        for date in &overrides.remove {
+            self.dates.remove(date);
+        }
+        self
+    }
+
+    // The calendar every plan asks, the built in one unless another was installed first
+    pub fn global() -> &'static HolidayCalendar {
+        CALENDAR.get_or_init(HolidayCalendar::irish)
+    }
+
+    // Only works before any plan asked for a holiday, so it has to happen while starting up
+    pub fn install(self) -> Result<()> {
+        CALENDAR
+            .set(self)
+            .map_err(|_| anyhow::anyhow!("the holiday calendar is already in use"))
+    }
+}
+
+pub fn load_holiday_overrides(filename: &Path) -> Result<HolidayOverrides> {
+    let contents = std::fs::read_to_string(filename)?;
+    toml::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("invalid holiday file {}: {e}", filename.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, Duration, Weekday};
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    // The anonymous gregorian algorithm
+    fn easter_sunday(year: i32) -> NaiveDate {
+        let (a, b, c) = (year % 19, year / 100, year % 100);
+        let (d, e) = (b / 4, b % 4);
+        let g = (8 * b + 13) / 25;
+        let h = (19 * a + b - d - g + 15) % 30;
+        let (i, k) = (c / 4, c % 4);
+        let l = (32 + 2 * e + 2 * i - h - k) % 7;
+        let m = (a + 11 * h + 19 * l) / 433;
+        let month = (h + l - 7 * m + 90) / 25;
+        let day = (h + l - 7 * m + 33 * month + 19) % 32;
+        date(year, month as u32, day as u32)
+    }
+
+    fn monday(year: i32, month: u32, n: u8) -> NaiveDate {
+        NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Mon, n).unwrap()
+    }
+
+    // The holidays of a year by the rules, the fixed ones falling on a weekend also give the
+    // next weekday that isn't a holiday already
+    fn by_the_rules(year: i32) -> BTreeSet<NaiveDate> {
+        let brigid = match date(year, 2, 1) {
+            first if first.weekday() == Weekday::Fri => first,
+            _ => monday(year, 2, 1),
+        };
+        let october = NaiveDate::from_weekday_of_month_opt(year, 10, Weekday::Mon, 5)
+            .unwrap_or_else(|| monday(year, 10, 4));
+        let mut dates = BTreeSet::from([
+            brigid,
+            easter_sunday(year) + Duration::days(1),
+            monday(year, 5, 1),
+            monday(year, 6, 1),
+            monday(year, 8, 1),
+            october,
+        ]);
+        for fixed in [
+            date(year, 1, 1),
+            date(year, 3, 17),
+            date(year, 12, 25),
+            date(year, 12, 26),
+        ] {
+            dates.insert(fixed);
+            if fixed.weekday().number_from_monday() > 5 {
+                let mut day_off = fixed;
+                while day_off.weekday().number_from_monday() > 5 || dates.contains(&day_off) {
+                    day_off += Duration::days(1);
+                }
+                dates.insert(day_off);
+            }
+        }
+        dates
+    }
+
+    #[test]
+    fn the_holidays_of_2023_and_2024_are_in_the_calendar() {
+        let calendar = HolidayCalendar::irish();
+        for holiday in [
+            // new year on a sunday and the monday after
+            date(2023, 1, 1),
+            date(2023, 1, 2),
+            // st brigid's day, the first monday of february as the 1st is a wednesday
+            date(2023, 2, 6),
+            // easter monday
+            date(2023, 4, 10),
+            date(2023, 5, 1),
+            date(2023, 10, 30),
+            // the 1st of february 2024 is a thursday
+            date(2024, 2, 5),
+            // st patrick's day on a sunday and the monday after
+            date(2024, 3, 17),
+            date(2024, 3, 18),
+            date(2024, 4, 1),
+            date(2024, 5, 6),
+            date(2024, 6, 3),
+            date(2024, 8, 5),
+        ] {
+            assert!(calendar.is_holiday(holiday), "{holiday}");
+        }
+        assert!(!calendar.is_holiday(date(2023, 2, 1)));
+        assert!(!calendar.is_holiday(date(2024, 3, 29)));
+    }
+
+    #[test]
+    fn every_year_of_the_calendar_follows_the_rules() {
+        let calendar = HolidayCalendar::irish();
+        for year in 2023..=2026 {
+            let in_calendar: BTreeSet<NaiveDate> = calendar
+                .dates
+                .iter()
+                .filter(|date| date.year() == year)
+                .copied()
+                .collect();
+            assert_eq!(in_calendar, by_the_rules(year), "{year}");
+        }
+    }
+
+    #[test]
+    fn overrides_add_and_remove_days() {
+        let overrides: HolidayOverrides =
+            toml::from_str("add = [\"2022-09-19\"]\nremove = [\"2024-12-26\"]").unwrap();
+        let calendar = HolidayCalendar::irish().with_overrides(&overrides);
+        assert!(calendar.is_holiday(date(2022, 9, 19)));
+        assert!(!calendar.is_holiday(date(2024, 12, 26)));
+        assert!(calendar.is_holiday(date(2024, 12, 25)));
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime, TimeZone};
+use std::{collections::BTreeMap, io::Read, sync::Arc};
+
+use crate::{
+    meter_data::{raw_row, Interner},
+    meter_source::parse_timestamp,
+    MeterDataSource, Progress, ReadUnit, RowChecker, RowProblem, SmartMeterData,
+    SmartMeterDataType, LOCAL_TIMEZONE,
+};
+
+// The long term statistics Home Assistant keeps for an energy sensor, exported as a csv or
+// tsv with a row per hour: statistic_id, unit, start, state, sum. The sum keeps growing, so
+// the energy of an hour is how much it went up since the hour before
+pub struct HomeAssistantStatistics;
+
+// A row of the export, the statistic, the start of the hour, the line and the sum in kWh
+type Hour = (String, NaiveDateTime, u64, f64);
+
+// The hours of one statistic, the line and sum by the start of the hour
+type Hours = BTreeMap<NaiveDateTime, (u64, f64)>;
+
+// Exports straight from the database are in UTC, the statistics integration writes the
+// local time of the instance as 08.01.2024 03:00
+fn parse_start(text: &str) -> Option<NaiveDateTime> {
+    if let Some(start) = parse_timestamp(text) {
+        return Some(start);
+    }
+    let local = NaiveDateTime::parse_from_str(text, "%d.%m.%Y %H:%M").ok()?;
+    let start = LOCAL_TIMEZONE.from_local_datetime(&local).earliest()?;
+    Some(start.naive_utc())
+}
+
+// The sum in kWh, sensors measured in Wh are converted
+fn kwh_per_unit(unit: &str) -> Option<f64> {
+    match unit {
+        "kWh" | "" => Some(1.0),
+        "Wh" => Some(0.001),
+        "MWh" => Some(1000.0),
+        _ => None,
+    }
+}
+
+impl HomeAssistantStatistics {
+    pub(crate) fn recognises(headers: &[String]) -> bool {
+        ["statistic_id", "start", "sum"]
+            .iter()
+            .all(|column| headers.iter().any(|header| header == column))
+    }
+}
+
+impl MeterDataSource for HomeAssistantStatistics {
+    fn name(&self) -> &'static str {
+        "Home Assistant statistics"
+    }
+
+    fn parse(
+        &self,
+        reader: &mut dyn Read,
+        rows: &mut RowChecker,
+        progress: &mut Progress,
+    ) -> Result<Vec<SmartMeterData>> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        let delimiter = if text.lines().next().unwrap_or("").contains('\t') {
+            b'\t'
+        } else {
+            b','
+        };
+        let mut rdr = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(text.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let column = |name: &str| headers.iter().position(|header| header == name);
+        let (id, start, sum, unit) = (
+            column("statistic_id"),
+            column("start"),
+            column("sum"),
+            column("unit"),
+        );
+
+        let mut statistics: BTreeMap<String, Hours> = BTreeMap::new();
+        for record in rdr.records() {
+            let record = record?;
+            let parsed = (|| -> Result<Hour, (RowProblem, String)> {
+                let field = |index: Option<usize>, name: &str| {
+                    index
+                        .and_then(|index| record.get(index))
+                        .ok_or_else(|| (RowProblem::MissingField, format!("the row has no {name}")))
+                };
+                let (id, start, sum) = (
+                    field(id, "statistic_id")?,
+                    field(start, "start")?,
+                    field(sum, "sum")?,
+                );
+                let unit = unit.and_then(|unit| record.get(unit)).unwrap_or("");
+                let kwh = kwh_per_unit(unit).ok_or_else(|| {
+                    (
+                        RowProblem::UnknownType,
+                        format!("{id} is in {unit:?}, not an energy unit"),
+                    )
+                })?;
+                let sum: f64 = sum.parse().map_err(|_| {
+                    (
+                        RowProblem::NotANumber,
+                        format!("the sum {sum:?} is not a number"),
+                    )
+                })?;
+                let start = parse_start(start).ok_or_else(|| {
+                    (
+                        RowProblem::BadDate,
+                        format!("the start {start:?} is not like 2024-01-08 03:00:00"),
+                    )
+                })?;
+                let line = record.position().map_or(0, |position| position.line());
+                Ok((id.to_string(), start, line, sum * kwh))
+            })();
+            let parsed = parsed.map_err(|(problem, reason)| {
+                let line = record.position().map_or(0, |position| position.line());
+                rows.bad_row(
+                    line,
+                    problem,
+                    reason,
+                    raw_row(record.as_byte_record().iter()),
+                )
+            });
+            if let Some((id, start, line, sum)) = rows.check(parsed)? {
+                statistics.entry(id).or_default().insert(start, (line, sum));
+            }
+        }
+
+        let mut meters = Interner::default();
+        let mut data = vec![];
+        for (id, hours) in statistics {
+            let meter = meters.intern(&id);
+            // sensors named like sensor.grid_export count what was sent back to the grid
+            let read_type = if id.contains("export") {
+                SmartMeterDataType::ActiveExport
+            } else {
+                SmartMeterDataType::ActiveImport
+            };
+            // the first hour has nothing to go up from, so it only starts the count
+            let mut before = None;
+            for (start, (line, sum)) in hours {
+                let Some(before) = before.replace(sum) else {
+                    continue;
+                };
+                let kwh = sum - before;
+                // the sum only goes down when the sensor was reset, that hour can't be known
+                if kwh < 0.0 {
+                    let reason = format!("the sum of {id} went down at {start}");
+                    let row = format!("{id},{start},{sum}");
+                    let bad = rows.bad_row(line, RowProblem::NotANumber, reason, row);
+                    rows.check::<()>(Err(bad))?;
+                    continue;
+                }
+                data.push(SmartMeterData {
+                    mprn: Arc::clone(&meter),
+                    meter_serial_number: Arc::clone(&meter),
+                    read_value: kwh as f32,
+                    read_type,
+                    unit: ReadUnit::KilowattHours,
+                    read_data_and_end_time: start + Duration::hours(1),
+                    interval_minutes: 60,
+                });
+            }
+            progress.update(data.len(), rows.skipped());
+        }
+        progress.finish(data.len(), rows.skipped());
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseMode;
+
+    #[test]
+    fn hourly_energy_is_how_much_the_sum_went_up() {
+        let tsv = "statistic_id\tunit\tstart\tstate\tsum
+sensor.grid_import\tWh\t08.01.2024 03:00\t0\t1000
+sensor.grid_import\tWh\t08.01.2024 05:00\t0\t2250
+sensor.grid_import\tWh\t08.01.2024 04:00\t0\t1500
+sensor.grid_import\tWh\t08.01.2024 06:00\t0\t10
+sensor.grid_export\tkWh\t2024-01-08 03:00:00\t0\t4.5
+sensor.grid_export\tkWh\t2024-01-08 04:00:00\t0\t5
+";
+        let mut rows = RowChecker::new("statistics.tsv", ParseMode::Lenient);
+        let data = HomeAssistantStatistics
+            .parse(&mut tsv.as_bytes(), &mut rows, &mut Progress::hidden())
+            .unwrap();
+        let hours: Vec<_> = data
+            .iter()
+            .map(|d| {
+                (
+                    &*d.mprn,
+                    d.read_data_and_end_time.to_string(),
+                    d.energy_kwh(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            hours,
+            [
+                ("sensor.grid_export", "2024-01-08 05:00:00".to_string(), 0.5),
+                ("sensor.grid_import", "2024-01-08 05:00:00".to_string(), 0.5),
+                (
+                    "sensor.grid_import",
+                    "2024-01-08 06:00:00".to_string(),
+                    0.75
+                ),
+            ]
+        );
+        assert_eq!(data[0].read_type, SmartMeterDataType::ActiveExport);
+        // the reset at 06:00 is the skipped row
+        assert_eq!(rows.skipped(), 1);
+    }
+}
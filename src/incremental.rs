@@ -0,0 +1,262 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use crate::{
+    comparison::{ensure_one_currency, ensure_priced, mark_current_plan, sort_by_total},
+    coverage,
+    plan::{has_exports, imports_of, readings_breakdown},
+    BillingPeriod, Bucket, ComparisonReport, EnergyBillEntry, GapFilling, Granularity,
+    PlanComparison, PricePlanStrategy, SmartMeterData, SmartMeterDataType,
+};
+
+// What a plan charged for the readings of a month, the usage and the cost of the imports
+// alone when something was exported
+type MonthCost = (EnergyBillEntry, Option<EnergyBillEntry>);
+
+// A reading by direction, end time and meter, like merge_readings, so one sent again replaces
+// the one before and two meters of the same meter point are both kept
+type ReadingKey = (SmartMeterDataType, NaiveDateTime, Arc<str>);
+
+// The readings of one meter point so far, by month and then by reading, and what each plan
+// charged for every month, none for the plans whose usage doesn't add up month by month
+#[derive(Default)]
+struct MeterState {
+    months: BTreeMap<Bucket, BTreeMap<ReadingKey, SmartMeterData>>,
+    costs: BTreeMap<Bucket, Vec<Option<MonthCost>>>,
+}
+
+// The plans and the readings a long running comparison has seen, for an add-on or a server
+// that gets a few readings at a time and keeps the totals up to date with compare_incremental
+pub struct ComparisonState {
+    plans: Vec<Box<dyn PricePlanStrategy>>,
+    current_plan: Option<String>,
+    meters: BTreeMap<Arc<str>, MeterState>,
+}
+
+impl ComparisonState {
+    pub fn new(
+        plans: Vec<Box<dyn PricePlanStrategy>>,
+        current_plan: Option<String>,
+    ) -> Result<ComparisonState> {
+        ensure_one_currency(&plans.iter().map(|plan| plan.as_ref()).collect::<Vec<_>>())?;
+        Ok(ComparisonState {
+            plans,
+            current_plan,
+            meters: BTreeMap::new(),
+        })
+    }
+
+    pub fn plans(&self) -> &[Box<dyn PricePlanStrategy>] {
+        &self.plans
+    }
+
+    // Every reading of the meter point, oldest first
+    pub fn readings(&self, mprn: &str) -> Vec<SmartMeterData> {
+        self.meters
+            .get(mprn)
+            .map(|meter| {
+                meter
+                    .months
+                    .values()
+                    .flat_map(|m| m.values())
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn month_cost(plan: &dyn PricePlanStrategy, readings: &[SmartMeterData]) -> MonthCost {
+    let import_cost =
+        has_exports(readings).then(|| plan.compute_total_bill_for_period(&imports_of(readings)));
+    (plan.compute_total_bill_for_period(readings), import_cost)
+}
+
+// Adds the readings to the state and compares the plans on every meter point again. Plans
+// whose usage adds up month by month only price again the months the new readings fall in,
+// the others and the demand charges still go through all of the readings
+pub fn compare_incremental(
+    state: &mut ComparisonState,
+    readings: Vec<SmartMeterData>,
+) -> Result<Vec<ComparisonReport>> {
+    let mut touched: BTreeSet<(Arc<str>, Bucket)> = BTreeSet::new();
+    for datapoint in readings {
+        let bucket = Bucket::of(datapoint.date(), Granularity::Month);
+        touched.insert((datapoint.mprn.clone(), bucket));
+        state
+            .meters
+            .entry(datapoint.mprn.clone())
+            .or_default()
+            .months
+            .entry(bucket)
+            .or_default()
+            .insert(
+                (
+                    datapoint.read_type,
+                    datapoint.read_data_and_end_time,
+                    datapoint.meter_serial_number.clone(),
+                ),
+                datapoint,
+            );
+    }
+    for (mprn, bucket) in touched {
+        let meter = state.meters.get_mut(&mprn).unwrap();
+        let month: Vec<SmartMeterData> = meter.months[&bucket].values().cloned().collect();
+        let costs = state
+            .plans
+            .iter()
+            .map(|plan| {
+                plan.usage_adds_up_by_month()
+                    .then(|| month_cost(plan.as_ref(), &month))
+            })
+            .collect();
+        meter.costs.insert(bucket, costs);
+    }
+
+    let mut reports = vec![];
+    for (mprn, meter) in &state.meters {
+        let data: Vec<SmartMeterData> = meter
+            .months
+            .values()
+            .flat_map(|m| m.values())
+            .cloned()
+            .collect();
+        let Some(period) = BillingPeriod::from_data(&data) else {
+            continue;
+        };
+        let results = state
+            .plans
+            .iter()
+            .enumerate()
+            .map(|(index, plan)| {
+                let months: Option<Vec<MonthCost>> =
+                    meter.costs.values().map(|costs| costs[index]).collect();
+                let (usage, import_cost) = match months {
+                    Some(months) => (
+                        months.iter().map(|(usage, _)| usage).sum(),
+                        // the months without exports cost the same on their imports alone
+                        months
+                            .iter()
+                            .any(|(_, imports)| imports.is_some())
+                            .then(|| {
+                                months
+                                    .iter()
+                                    .map(|(usage, imports)| imports.unwrap_or(*usage))
+                                    .sum()
+                            }),
+                    ),
+                    None => month_cost(plan.as_ref(), &data),
+                };
+                let breakdown =
+                    readings_breakdown(plan.as_ref(), usage, import_cost, &data, &period);
+                let mut result = PlanComparison::new(plan.name(), breakdown);
+                result.metadata = Some(plan.metadata()).filter(|m| !m.is_empty());
+                result.warning = plan.pricing_warning(&data);
+                let bonus = plan.sign_up_bonus();
+                if bonus.amount() > Decimal::ZERO {
+                    result.with_bonus = Some(breakdown.total + EnergyBillEntry::Credit(bonus));
+                }
+                result
+            })
+            .collect();
+        let mut plans = sort_by_total(results);
+        if let Some(current) = &state.current_plan {
+            mark_current_plan(&mut plans, current)?;
+        }
+        reports.push(ComparisonReport {
+            mprn: mprn.to_string(),
+            plans,
+            gas_plans: vec![],
+            dual_fuel: vec![],
+            estimated_annual_bills: vec![],
+            carbon: None,
+            load_shift: None,
+            battery: None,
+            solar: None,
+            ev_charging: None,
+            sub_metering: None,
+            baseload: None,
+            peak_demand: None,
+            contract: None,
+            usage_growth: None,
+            scenarios: vec![],
+            monthly_winners: None,
+            rate_sensitivity: None,
+            estimated: vec![],
+            coverage: Some(coverage(&data, &period, &GapFilling::default(), &[])),
+            snapshot: None,
+            period,
+        });
+    }
+    ensure_priced(&state.plans)?;
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compare_meter_points, electric_ireland_home_electric_14, meter_data::reading_at,
+        sse_airtricity_20, ComparisonOptions,
+    };
+
+    #[test]
+    fn readings_added_a_few_at_a_time_cost_the_same_as_all_at_once() {
+        let plans = || -> Vec<Box<dyn PricePlanStrategy>> {
+            vec![
+                Box::new(sse_airtricity_20()),
+                Box::new(electric_ireland_home_electric_14()),
+            ]
+        };
+        let mut export = reading_at("20-02-2024 13:00", 0.6);
+        export.read_type = SmartMeterDataType::ActiveExport;
+        let data = vec![
+            reading_at("08-01-2024 18:00", 1.5),
+            reading_at("31-01-2024 03:30", 0.8),
+            reading_at("20-02-2024 13:00", 0.2),
+            export,
+            reading_at("02-03-2024 09:00", 1.1),
+        ];
+        let mut state = ComparisonState::new(plans(), None).unwrap();
+        compare_incremental(&mut state, data[..2].to_vec()).unwrap();
+        // the last reading of january sent again with the rest
+        let reports = compare_incremental(&mut state, data[1..].to_vec()).unwrap();
+        let whole = compare_meter_points(&plans(), data, &ComparisonOptions::default()).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(state.readings(&reports[0].mprn).len(), 5);
+        assert_eq!(reports[0].period, whole[0].period);
+        for (incremental, all) in reports[0].plans.iter().zip(&whole[0].plans) {
+            assert_eq!(incremental.name, all.name);
+            assert_eq!(
+                incremental.breakdown.line_items(),
+                all.breakdown.line_items()
+            );
+            assert_eq!(incremental.breakdown.total, all.breakdown.total);
+        }
+    }
+
+    #[test]
+    fn the_readings_of_two_meters_at_the_same_time_are_both_kept() {
+        let mut state = ComparisonState::new(vec![Box::new(sse_airtricity_20())], None).unwrap();
+        let mut second_meter = reading_at("08-01-2024 18:00", 1.0);
+        second_meter.meter_serial_number = "11111111".into();
+        let data = vec![reading_at("08-01-2024 18:00", 1.5), second_meter];
+        let reports = compare_incremental(&mut state, data.clone()).unwrap();
+        let whole = compare_meter_points(
+            &[Box::new(sse_airtricity_20()) as Box<dyn PricePlanStrategy>],
+            data,
+            &ComparisonOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(state.readings(&reports[0].mprn).len(), 2);
+        assert_eq!(
+            reports[0].plans[0].breakdown.total,
+            whole[0].plans[0].breakdown.total
+        );
+    }
+}
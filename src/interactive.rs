@@ -0,0 +1,252 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::{
+    collections::BTreeSet,
+    fmt::Write as _,
+    io::{BufRead, Write},
+};
+
+use crate::{
+    compare_plans, filter_by_date, BillingPeriod, ComparisonOptions, Granularity, PlanComparison,
+    PricePlanStrategy, SmartMeterData,
+};
+
+// Clears the terminal and puts the cursor back at the top
+const CLEAR: &str = "\x1b[2J\x1b[H";
+
+const HELP: &str = "number: show that plan, h number: hide it, a: show all plans, \
+f/t YYYY-MM-DD: from/to (alone clears it), v: actual/projected, q: quit";
+
+// The readings of a meter point explored from the terminal, each command redraws the
+// ranking, the monthly breakdown and the bands of the chosen plan
+pub struct Explorer<'a> {
+    plans: &'a [Box<dyn PricePlanStrategy>],
+    datapoints: &'a [SmartMeterData],
+    current_plan: Option<String>,
+    hidden: BTreeSet<String>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    // the position in the ranking of the plan whose breakdowns are shown
+    selected: usize,
+    projected: bool,
+    message: String,
+}
+
+impl<'a> Explorer<'a> {
+    pub fn new(
+        plans: &'a [Box<dyn PricePlanStrategy>],
+        datapoints: &'a [SmartMeterData],
+        current_plan: Option<String>,
+    ) -> Explorer<'a> {
+        Explorer {
+            plans,
+            datapoints,
+            current_plan,
+            hidden: BTreeSet::new(),
+            from: None,
+            to: None,
+            selected: 0,
+            projected: false,
+            message: String::new(),
+        }
+    }
+
+    // Works the comparison out again for the plans still shown and the dates chosen
+    fn compare(&self) -> Result<Option<(BillingPeriod, Vec<PlanComparison>)>> {
+        let data = filter_by_date(self.datapoints.to_vec(), self.from, self.to);
+        let Some(period) = BillingPeriod::from_data(&data) else {
+            return Ok(None);
+        };
+        let options = ComparisonOptions {
+            current_plan: self.current_plan.clone(),
+            bands: true,
+            granularity: Some(Granularity::Month),
+            years: None,
+            annualize: self.projected,
+            load_shift: None,
+            battery: None,
+            solar: None,
+            ev: None,
+            circuits: vec![],
+            invoices: None,
+            baseload: false,
+            peaks: None,
+            expensive_days: None,
+            expensive_days_plan: None,
+            free_time_choices: false,
+            contract: None,
+            usage_growth: None,
+            scenarios: vec![],
+            level_pay: None,
+            effective_rates: false,
+            exports: false,
+            monthly_winners: false,
+            rate_sensitivity: None,
+        };
+        // the hidden plans are still priced so the savings stay against the current plan
+        let mut results = compare_plans(self.plans, &data, &period, &options)?;
+        results.retain(|result| !self.hidden.contains(&result.name));
+        if self.projected {
+            results.sort_by_key(|r| r.annual_estimate.map_or(r.breakdown.total, |e| e.total));
+        }
+        Ok(Some((period, results)))
+    }
+
+    pub fn render(&self) -> Result<String> {
+        let mut out = String::from(CLEAR);
+        let Some((period, results)) = self.compare()? else {
+            let _ = writeln!(
+                out,
+                "No readings between the dates chosen\n\n{}\n{HELP}",
+                self.message
+            );
+            return Ok(out);
+        };
+        let view = if self.projected {
+            "projected cost of a year"
+        } else {
+            "actual cost"
+        };
+        let _ = writeln!(
+            out,
+            "{} to {} ({} days), {view}\n",
+            period.start,
+            period.end,
+            period.number_of_days()
+        );
+        let _ = writeln!(out, "── Plans ──");
+        for (position, result) in results.iter().enumerate() {
+            let breakdown = match (self.projected, &result.annual_estimate) {
+                (true, Some(estimate)) => estimate,
+                _ => &result.breakdown,
+            };
+            let marker = if position == self.selected { '>' } else { ' ' };
+            let _ = write!(
+                out,
+                "{marker}{:>2}. {}: {}",
+                position + 1,
+                result.name,
+                breakdown.total
+            );
+            if result.current {
+                out.push_str(" (current plan)");
+            }
+            if let Some(saving) = &result.saving {
+                let _ = write!(out, ", saves {} ({}%)", saving.amount, saving.percentage);
+            }
+            out.push('\n');
+        }
+        if !self.hidden.is_empty() {
+            let hidden: Vec<&str> = self.hidden.iter().map(String::as_str).collect();
+            let _ = writeln!(out, "hidden: {}", hidden.join(", "));
+        }
+        if let Some(result) = results.get(self.selected) {
+            let _ = writeln!(out, "\n── {} by month, actual ──", result.name);
+            for bucket in &result.buckets {
+                let _ = writeln!(out, "  {}: {}", bucket.bucket, bucket.breakdown.total);
+            }
+            let _ = writeln!(out, "\n── {} bands, actual ──", result.name);
+            for band in &result.bands {
+                let _ = writeln!(out, "  {}: {:.2} kWh, {}", band.band, band.kwh, band.cost);
+            }
+        }
+        let _ = writeln!(out, "\n{}\n{HELP}", self.message);
+        Ok(out)
+    }
+
+    // Applies one command, false when it was the one to quit
+    pub fn command(&mut self, line: &str) -> Result<bool> {
+        self.message.clear();
+        let mut words = line.split_whitespace();
+        let (command, argument) = (words.next().unwrap_or(""), words.next());
+        let date = |argument: Option<&str>| -> Result<Option<NaiveDate>, String> {
+            argument
+                .map(|text| {
+                    text.parse()
+                        .map_err(|_| format!("{text} is not like 2024-01-31"))
+                })
+                .transpose()
+        };
+        match command {
+            "q" => return Ok(false),
+            "" => {}
+            "a" => self.hidden.clear(),
+            "v" => self.projected = !self.projected,
+            "f" => match date(argument) {
+                Ok(from) => self.from = from,
+                Err(message) => self.message = message,
+            },
+            "t" => match date(argument) {
+                Ok(to) => self.to = to,
+                Err(message) => self.message = message,
+            },
+            "h" => {
+                let results = self
+                    .compare()?
+                    .map(|(_, results)| results)
+                    .unwrap_or_default();
+                match argument
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .and_then(|n| results.get(n.wrapping_sub(1)))
+                {
+                    // hiding every plan would leave nothing to compare
+                    Some(_) if results.len() == 1 => {
+                        self.message = "the last plan can't be hidden".to_string()
+                    }
+                    Some(result) => {
+                        self.hidden.insert(result.name.clone());
+                        self.selected = 0;
+                    }
+                    None => self.message = "h needs the number of a plan".to_string(),
+                }
+            }
+            number => match number.parse::<usize>() {
+                Ok(position) if position >= 1 => self.selected = position - 1,
+                _ => self.message = format!("unknown command {number:?}"),
+            },
+        }
+        Ok(true)
+    }
+
+    // Draws the comparison and redraws it after every command read from the input
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> Result<()> {
+        write!(output, "{}", self.render()?)?;
+        output.flush()?;
+        for line in input.lines() {
+            if !self.command(&line?)? {
+                break;
+            }
+            write!(output, "{}", self.render()?)?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builtin_plans, meter_data::reading_at};
+
+    #[test]
+    fn commands_change_what_is_shown() {
+        let plans = builtin_plans();
+        let data = vec![
+            reading_at("08-01-2024 18:00", 2.0),
+            reading_at("09-02-2024 03:30", 1.0),
+        ];
+        let mut explorer = Explorer::new(&plans, &data, None);
+        let mut output = vec![];
+        explorer
+            .run("h 1\nf 2024-02-01\nv\n2\nq\nv\n".as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(explorer.hidden.len(), 1);
+        assert!(explorer.projected);
+        assert_eq!(explorer.selected, 1);
+        let last = String::from_utf8(output).unwrap();
+        let last = last.rsplit(CLEAR).next().unwrap();
+        assert!(last.starts_with("2024-02-09 to 2024-02-09 (1 days), projected cost of a year"));
+        assert!(last.contains("2024-02:"));
+        assert!(!last.contains("2024-01:"));
+    }
+}
@@ -0,0 +1,168 @@
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::Serialize;
+
+use crate::{
+    BillBreakdown, BillingPeriod, EnergyBillEntry, PricePlanStrategy, SmartMeterData,
+    SmartMeterDataType,
+};
+
+// Irish suppliers bill every two months, each invoice from the day of the month the account
+// was opened on
+pub const DEFAULT_INVOICE_MONTHS: u32 = 2;
+
+// When the invoices of an account start, the anchor and every months months either side of it.
+// Without an anchor the invoices start on the first day of the readings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct InvoiceCycle {
+    pub anchor: Option<NaiveDate>,
+    pub months: u32,
+}
+
+impl Default for InvoiceCycle {
+    fn default() -> InvoiceCycle {
+        InvoiceCycle {
+            anchor: None,
+            months: DEFAULT_INVOICE_MONTHS,
+        }
+    }
+}
+
+impl InvoiceCycle {
+    // the start of the invoice number cycle of them away from the anchor
+    fn start(anchor: NaiveDate, months: u32, cycle: i32) -> NaiveDate {
+        let step = Months::new(cycle.unsigned_abs() * months);
+        let start = if cycle < 0 {
+            anchor.checked_sub_months(step)
+        } else {
+            anchor.checked_add_months(step)
+        };
+        start.unwrap_or(anchor)
+    }
+
+    // The invoice periods of the billing period, the first and last cut to it, with whether
+    // each was cut
+    pub fn split(&self, period: &BillingPeriod) -> Vec<(BillingPeriod, bool)> {
+        let anchor = self.anchor.unwrap_or(period.start);
+        let months = self.months.max(1);
+        let months_away = (period.start.year() - anchor.year()) * 12 + period.start.month() as i32
+            - anchor.month() as i32;
+        let mut cycle = months_away.div_euclid(months as i32);
+        while Self::start(anchor, months, cycle) > period.start {
+            cycle -= 1;
+        }
+        while Self::start(anchor, months, cycle + 1) <= period.start {
+            cycle += 1;
+        }
+        let mut invoices = vec![];
+        while Self::start(anchor, months, cycle) <= period.end {
+            let start = Self::start(anchor, months, cycle);
+            let end = Self::start(anchor, months, cycle + 1) - Duration::days(1);
+            invoices.push((
+                BillingPeriod {
+                    start: start.max(period.start),
+                    end: end.min(period.end),
+                },
+                start < period.start || end > period.end,
+            ));
+            cycle += 1;
+        }
+        invoices
+    }
+}
+
+// One simulated invoice of a plan, rounded and charged the levy on its own like a real one
+#[derive(Debug, Clone, Serialize)]
+pub struct Invoice {
+    pub period: BillingPeriod,
+    // the readings only cover part of the invoice
+    pub partial: bool,
+    pub import_kwh: f32,
+    pub export_kwh: f32,
+    pub breakdown: BillBreakdown,
+}
+
+// Prices the plan invoice by invoice. The lines of each invoice are rounded apart, so the
+// invoices can add up to a few cent more or less than the period priced at once
+pub fn simulate_invoices(
+    plan: &dyn PricePlanStrategy,
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+    cycle: &InvoiceCycle,
+) -> Vec<Invoice> {
+    cycle
+        .split(period)
+        .into_iter()
+        .map(|(invoice_period, partial)| {
+            let readings: Vec<SmartMeterData> = datapoints
+                .iter()
+                .filter(|d| (invoice_period.start..=invoice_period.end).contains(&d.date()))
+                .cloned()
+                .collect();
+            let kwh = |read_type| -> f32 {
+                readings
+                    .iter()
+                    .filter(|d| d.read_type == read_type)
+                    .map(|d| d.energy_kwh())
+                    .sum()
+            };
+            Invoice {
+                period: invoice_period,
+                partial,
+                import_kwh: kwh(SmartMeterDataType::ActiveImport),
+                export_kwh: kwh(SmartMeterDataType::ActiveExport),
+                breakdown: plan.compute_bill_breakdown(&readings, &invoice_period),
+            }
+        })
+        .collect()
+}
+
+// What the invoices add up to
+pub fn invoices_total(invoices: &[Invoice]) -> EnergyBillEntry {
+    invoices.iter().map(|invoice| invoice.breakdown.total).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, sse_airtricity_20};
+
+    #[test]
+    fn invoices_follow_the_anchor_and_add_up_to_the_period() {
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        let period = BillingPeriod {
+            start: date(2024, 1, 20),
+            end: date(2024, 6, 10),
+        };
+        let cycle = InvoiceCycle {
+            anchor: Some(date(2023, 3, 15)),
+            months: 2,
+        };
+        let invoices = cycle.split(&period);
+        let starts: Vec<NaiveDate> = invoices.iter().map(|(p, _)| p.start).collect();
+        assert_eq!(
+            starts,
+            vec![date(2024, 1, 20), date(2024, 3, 15), date(2024, 5, 15)]
+        );
+        assert_eq!(invoices[0].0.end, date(2024, 3, 14));
+        assert_eq!(
+            invoices
+                .iter()
+                .map(|(_, partial)| *partial)
+                .collect::<Vec<_>>(),
+            vec![true, false, true]
+        );
+
+        let data = vec![
+            reading_at("20-01-2024 18:00", 2.0),
+            reading_at("15-03-2024 03:30", 2.0),
+            reading_at("10-06-2024 12:00", 2.0),
+        ];
+        let plan = sse_airtricity_20();
+        let invoices = simulate_invoices(&plan, &data, &period, &cycle);
+        assert_eq!(invoices.len(), 3);
+        assert_eq!(invoices[1].import_kwh, 1.0);
+        let whole = plan.compute_bill_breakdown(&data, &period).total;
+        let difference = invoices_total(&invoices) - whole;
+        assert!(difference.signed_amount().abs() < rust_decimal::Decimal::new(5, 2));
+    }
+}
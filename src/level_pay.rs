@@ -0,0 +1,122 @@
+use chrono::{Datelike, Months, NaiveDate};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    projection::{annual_usage, days_in_month, EXPORT_PROFILE, IMPORT_PROFILE},
+    BillingPeriod, EnergyBillEntry, Money, PricePlanStrategy, Rounding, SmartMeterData,
+    SmartMeterDataType,
+};
+
+// A month of a level pay plan, the balance is what the account has once its bill is paid
+// out of the debits so far, negative when it owes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MonthBalance {
+    pub month: NaiveDate,
+    pub cost: EnergyBillEntry,
+    pub balance: Money,
+}
+
+// The same direct debit every month for a year of the plan, the year's estimate split in
+// twelve with a buffer on top, rounded up to the euro like suppliers set them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LevelPay {
+    pub monthly_payment: Money,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub buffer: Decimal,
+    pub months: Vec<MonthBalance>,
+}
+
+impl LevelPay {
+    pub fn lowest_balance(&self) -> Money {
+        self.months
+            .iter()
+            .map(|month| month.balance)
+            .min_by_key(|balance| balance.amount())
+            .unwrap_or(self.monthly_payment)
+    }
+
+    pub fn final_balance(&self) -> Money {
+        self.months
+            .last()
+            .map_or(self.monthly_payment, |month| month.balance)
+    }
+}
+
+// The twelve months from the one after the readings, each with the energy a month like it
+// usually has of a year estimated from the readings, see annual_estimate, and its own standing
+// charge and levy. buffer is the fraction added to the debit, 0.1 for 10%
+pub fn level_pay(
+    plan: &dyn PricePlanStrategy,
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+    buffer: Decimal,
+) -> LevelPay {
+    let imports = annual_usage(plan, datapoints, period, SmartMeterDataType::ActiveImport);
+    let exports = annual_usage(plan, datapoints, period, SmartMeterDataType::ActiveExport);
+    let rounding = plan.rounding();
+    let first = period.end.with_day(1).unwrap_or(period.end) + Months::new(1);
+    let costs: Vec<(NaiveDate, EnergyBillEntry)> = (0..12)
+        .map(|month| {
+            let start = first + Months::new(month);
+            let index = start.month0() as usize;
+            let usage = imports * IMPORT_PROFILE[index] + exports * EXPORT_PROFILE[index];
+            let days = BillingPeriod::starting_at(start, days_in_month(start));
+            let bill = plan.bill_breakdown_for_usage(EnergyBillEntry::from_signed(usage), &days);
+            // credits are one offs of the past, a year ahead has none of them
+            (start, bill.total - bill.credits)
+        })
+        .collect();
+    let year: Decimal = costs.iter().map(|(_, cost)| cost.signed_amount()).sum();
+    let monthly = (year / Decimal::from(12) * (Decimal::ONE + buffer))
+        .max(Decimal::ZERO)
+        .ceil();
+    let mut balance = Decimal::ZERO;
+    let months = costs
+        .into_iter()
+        .map(|(month, cost)| {
+            balance += monthly - cost.signed_amount();
+            MonthBalance {
+                month,
+                cost,
+                balance: Money::of(balance, plan.currency()).round(&Rounding::default()),
+            }
+        })
+        .collect();
+    LevelPay {
+        monthly_payment: Money::of(monthly, plan.currency()).round(&rounding),
+        buffer,
+        months,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, sse_airtricity_20};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn the_buffer_keeps_the_account_in_credit_through_the_winter() {
+        let plan = sse_airtricity_20();
+        // a week of two kW at six in the evening in june, the debits start in july
+        let data: Vec<SmartMeterData> = (10..17)
+            .map(|day| reading_at(&format!("{day}-06-2024 18:00"), 2.0))
+            .collect();
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let plain = level_pay(&plan, &data, &period, Decimal::ZERO);
+        assert_eq!(plain.months.len(), 12);
+        assert_eq!(
+            plain.months[0].month,
+            NaiveDate::from_ymd_opt(2024, 7, 1).unwrap()
+        );
+        assert_eq!(plain.monthly_payment.amount().fract(), Decimal::ZERO);
+        // the summer builds up what the winter uses, a debit rounded up ends the year in credit
+        assert!(plain.months[5].balance.amount() > plain.final_balance().amount());
+        assert!(plain.final_balance().amount() >= Decimal::ZERO);
+
+        let buffered = level_pay(&plan, &data, &period, dec!(0.1));
+        assert!(buffered.monthly_payment.amount() > plain.monthly_payment.amount());
+        assert!(buffered.lowest_balance().amount() > plain.lowest_balance().amount());
+    }
+}
@@ -0,0 +1,225 @@
+mod aggregation;
+mod anonymize;
+mod audit;
+mod baseload;
+mod battery;
+mod bill;
+mod bill_credits;
+mod builtin_plans;
+mod carbon;
+mod comparison;
+mod conditional_plan;
+mod configurable_plan;
+mod cost_chart;
+mod coverage;
+mod custom_plan;
+mod customer_type;
+mod day_ahead;
+mod day_night;
+mod day_set;
+mod demand_charge;
+mod discount;
+mod dual_fuel;
+mod duos_group;
+mod dynamic_plan;
+mod eab;
+mod eligibility;
+mod error;
+mod esb_portal;
+mod ev;
+mod export;
+mod export_credit;
+mod export_earnings;
+mod free_time;
+mod gap_fill;
+mod gas;
+mod heatmap;
+mod holidays;
+mod home_assistant;
+mod incremental;
+mod interactive;
+mod invoices;
+mod level_pay;
+mod load_profile;
+mod load_shift;
+mod locale;
+mod manual_reads;
+mod meter_data;
+mod meter_source;
+mod meter_swap;
+mod metrics;
+mod money;
+mod monthly_winners;
+mod notify;
+mod numeric;
+mod parquet;
+mod peak_demand;
+mod period;
+mod plan;
+mod plan_metadata;
+mod plan_registry;
+mod plan_selection;
+mod plugin_plan;
+mod progress;
+mod projection;
+mod pso_levy;
+mod reading_store;
+mod readings_export;
+mod reconcile;
+mod report;
+mod results_diff;
+mod scenario;
+mod schedule;
+mod sensitivity;
+mod server;
+mod snapshot;
+mod solar;
+mod streaming;
+mod sub_metering;
+mod switching;
+mod tariff_validation;
+mod tiered_plan;
+mod time_of_use;
+mod usage_cache;
+mod usage_growth;
+mod usage_stats;
+mod validation;
+mod vat;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+
+pub use aggregation::{
+    breakdown_by_band, breakdown_by_bucket, effective_rate, most_expensive_days, split_period,
+    BandBreakdown, Bucket, BucketBreakdown, DayCost, EffectiveRate, Granularity,
+};
+pub use anonymize::{anonymize_readings, write_hdf_csv};
+pub use audit::write_audit_csv;
+pub use baseload::{baseload, Baseload, HIGH_BASELOAD_KW};
+pub use battery::{Battery, BatterySaving};
+pub use bill::{BillBreakdown, EnergyBillEntry};
+pub use bill_credits::{load_credit_schedule, BillCredit, CreditSchedule};
+pub use builtin_plans::{
+    bord_gais_energy_25_weekend_free, builtin_gas_plans, builtin_plans,
+    electric_ireland_home_electric_14, sse_airtricity_20, sse_airtricity_ev_boost,
+};
+pub use carbon::{
+    carbon_footprint, fetch_carbon_intensity, parse_dashboard_intensity, write_intensity_file,
+    CarbonFootprint, CarbonIntensity, IntensityPoint, DEFAULT_SHIFTABLE_SHARE,
+    IRISH_AVERAGE_INTENSITY,
+};
+pub use comparison::{
+    compare_meter_points, compare_plans, rank_gas_plans, rank_plans, saving_against,
+    ComparisonOptions, ComparisonReport, PlanComparison, Saving,
+};
+pub use conditional_plan::{
+    fetch_wind_share, parse_dashboard_wind_share, write_conditions_file, ConditionPoint,
+    ConditionalPlan, GridConditions,
+};
+pub use configurable_plan::{
+    load_plan_file, load_plans, ConfigurablePlan, MonthDay, PlanFile, PrepayCharges, RateBand,
+    Season, StandingChargeDefinition,
+};
+pub use cost_chart::{cumulative_costs, render_cost_chart_svg, CostCurve};
+pub use coverage::{coverage, Coverage, DEFAULT_MIN_COVERAGE};
+pub use custom_plan::CustomPlan;
+pub use customer_type::CustomerType;
+pub use day_ahead::{fetch_day_ahead_prices, parse_day_ahead_document, write_price_file};
+pub use day_night::{register_reads, DayNightPlan, Register, RegisterReads};
+pub use day_set::{DaySet, HolidayRule};
+pub use demand_charge::{
+    max_demand_kw, CapacityBand, DemandCharge, MaximumImportCapacity, WithImportCapacity,
+};
+pub use discount::Discount;
+pub use dual_fuel::{compare_dual_fuel, DualFuelBundle, DualFuelComparison, STANDALONE};
+pub use duos_group::{DuosGroup, InDuosGroup};
+pub use dynamic_plan::{DynamicPlan, PricePoint, PriceSeries};
+pub use eab::{estimated_annual_bills, standard_profile_readings, ConsumptionProfile};
+pub use eligibility::{Eligibility, Household};
+pub use error::{Error, ParseMode, Quarantine, RowChecker, RowProblem};
+pub use esb_portal::fetch_hdf_file;
+pub use ev::{ChargingSession, EvCharging, EvCost, EvDetection};
+pub use export::write_results_csv;
+pub use export_credit::{ExportRules, Netting};
+pub use export_earnings::{export_earnings, ExportEarnings, MonthExport};
+pub use free_time::{FreeTime, FreeTimeChoice, FreeWindow};
+pub use gap_fill::{fill_gaps, FilledGap, GapFillStrategy, GapFilling};
+pub use gas::{
+    estimated_gas_readings, FlatRateGasPlan, GasConversion, GasMeterData, GasMeterUnit,
+    GasPlanStrategy,
+};
+pub use heatmap::{render_heatmap_svg, usage_heatmap, UsageHeatmap};
+pub use holidays::{load_holiday_overrides, HolidayCalendar, HolidayOverrides};
+pub use home_assistant::HomeAssistantStatistics;
+pub use incremental::{compare_incremental, ComparisonState};
+pub use interactive::Explorer;
+pub use invoices::{
+    invoices_total, simulate_invoices, Invoice, InvoiceCycle, DEFAULT_INVOICE_MONTHS,
+};
+pub use level_pay::{level_pay, LevelPay, MonthBalance};
+pub use load_profile::{
+    annual_usage_readings, billed_usage_readings, load_billed_usage, BilledUsage, PROFILE_MPRN,
+};
+pub use load_shift::{shift_load, LoadShift, ShiftedCost, TimeWindow};
+pub use locale::Locale;
+pub use manual_reads::{
+    estimate_from_manual_reads, load_manual_reads, EstimatedPeriod, ManualReading,
+};
+pub use meter_data::{
+    detect_intervals, filter_by_date, group_by_mprn, load_smart_meter_data, merge_readings,
+    parse_smart_meter_csv, parse_smart_meter_csv_with_progress, read_csv, stream_csv,
+    MixedIntervals, ReadUnit, SmartMeterData, SmartMeterDataType, LOCAL_TIMEZONE,
+};
+pub use meter_source::{
+    detect_source, parse_readings, source_for_header, EsbHdf, JsonReadings, MeterDataSource,
+    ParsedReadings, UkConsumption,
+};
+pub use meter_swap::{meter_segments, resolve_meter_swaps, MeterSegment, MeterSwap};
+pub use metrics::Metrics;
+pub use money::{Currency, Money, Rounding, RoundingLevel, RoundingStrategy};
+pub use monthly_winners::{monthly_winners, MonthWinner, MonthlyWinners, PlanWins};
+pub use notify::{CheapestChange, CheapestTracker, EmailConfig, Notifier, RankedPlan};
+pub use numeric::{quantity_cost, swap_vat, Numeric, PricingNumber};
+pub use peak_demand::{peak_cost, peak_demand, PeakCost, PeakDemand, PeakInterval};
+pub use period::{annual_to_daily, days_in_year, BillingPeriod};
+pub use plan::{time_in_window, PricePlanStrategy, StandingCharge};
+pub use plan_metadata::PlanMetadata;
+pub use plan_registry::{
+    load_registry, merge_plans, parse_registry, sha256_hex, update_registry, Duplicate, PlanSource,
+    RegistryUpdate,
+};
+pub use plan_selection::{PlanSelection, SelectedPlans};
+pub use plugin_plan::{register_plan_kind, CommandPlan, PlanFactory, PluginPlan};
+pub use progress::Progress;
+pub use projection::{annual_estimate, project_plan, YearProjection, DEFAULT_DISCOUNT_MONTHS};
+pub use pso_levy::{load_pso_overrides, PsoLevy, PsoOverrides, PsoRate};
+pub use reading_store::{ImportSummary, ReadingStore};
+pub use readings_export::{write_readings_csv, write_readings_parquet};
+pub use reconcile::{
+    load_supplier_bill, reconcile_bill, BilledUnits, ReconciledLine, Reconciliation, SupplierBill,
+};
+pub use report::{render_report, ReportFormat};
+pub use results_diff::{
+    diff_results, load_saved_results, MeterPointDiff, PlanChange, ResultsDiff, SavedBreakdown,
+    SavedEntry, SavedPeriod, SavedPlan, SavedReport,
+};
+pub use scenario::{Scenario, ScenarioProjection};
+pub use schedule::CronSchedule;
+pub use sensitivity::{rate_sensitivity, PlanSensitivity, RateSensitivity};
+pub use server::{serve_metrics, ComparisonServer};
+pub use snapshot::{load_snapshot, FileSnapshot, InputSnapshot};
+pub use solar::{GenerationPoint, GenerationSeries, Solar, SolarArray, SolarSummary};
+pub use streaming::stream_comparison;
+pub use sub_metering::{
+    attribute_circuits, Circuit, CircuitCost, CircuitCosts, CircuitUsage, SubMetering,
+};
+pub use switching::{Contract, SwitchAdvice};
+pub use tariff_validation::{validate_plan, TariffIssue};
+pub use tiered_plan::{Tier, TieredPlan};
+pub use time_of_use::{TimeOfUseTariff, TimeOfUseTariffBuilder};
+pub use usage_cache::{CachedPlan, SharedUsageCache, UsageCache};
+pub use usage_growth::{GrowthSummary, HeatPump, NightlyCharging, UsageGrowth};
+pub use usage_stats::{usage_stats, DayUsage, UsageStats};
+pub use validation::{
+    check_mprns, is_valid_mprn, validate_readings, Gap, GapReport, MprnIssue, OutOfOrder, ShortDay,
+};
+pub use vat::{vat_rate_on, with_vat_on, QUOTED_VAT_RATE};
@@ -0,0 +1,163 @@
+use anyhow::{bail, Result};
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    eab::profile_day, projection::days_in_month, read_csv, ConsumptionProfile, EstimatedPeriod,
+    ReadUnit, SmartMeterData, SmartMeterDataType,
+};
+
+// The meter point of readings made up from totals alone
+pub const PROFILE_MPRN: &str = "standard profile";
+
+// The share of a year of an irish home's usage in each month, january first. Homes use about
+// half as much again in the winter as in the summer
+const MONTHLY_SHARES: [f32; 12] = [
+    0.100, 0.089, 0.090, 0.079, 0.074, 0.068, 0.069, 0.071, 0.073, 0.084, 0.093, 0.110,
+];
+
+// The kWh of one bill, from and to the days it covers, a csv of from,to,kwh rows like
+// 2024-01-01,2024-02-29,780
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct BilledUsage {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub kwh: f32,
+}
+
+pub fn load_billed_usage(filename: &Path) -> Result<Vec<BilledUsage>> {
+    let mut bills: Vec<BilledUsage> = read_csv(filename)?;
+    bills.sort_by_key(|bill| bill.from);
+    if bills.is_empty() {
+        bail!("{} has no bills in it", filename.display());
+    }
+    for bill in &bills {
+        if bill.to < bill.from || bill.kwh < 0.0 {
+            bail!(
+                "the bill from {} to {} of {} kWh in {} is not right",
+                bill.from,
+                bill.to,
+                bill.kwh,
+                filename.display()
+            );
+        }
+    }
+    for pair in bills.windows(2) {
+        if pair[1].from <= pair[0].to {
+            bail!(
+                "the bills from {} and {} of {} overlap",
+                pair[0].from,
+                pair[1].from,
+                filename.display()
+            );
+        }
+    }
+    Ok(bills)
+}
+
+fn profile_readings(days: impl Iterator<Item = (NaiveDate, f32)>) -> Vec<SmartMeterData> {
+    let meter: Arc<str> = PROFILE_MPRN.into();
+    days.flat_map(|(date, kwh)| profile_day(&ConsumptionProfile::CRU_STANDARD, date, kwh))
+        .map(|(end, kwh)| SmartMeterData {
+            mprn: Arc::clone(&meter),
+            meter_serial_number: Arc::clone(&meter),
+            read_value: kwh,
+            read_type: SmartMeterDataType::ActiveImport,
+            unit: ReadUnit::KilowattHours,
+            read_data_and_end_time: end,
+            interval_minutes: 30,
+        })
+        .collect()
+}
+
+// A year of half hours from the start day for a home of the annual kWh, each month taking its
+// share of the year and each day like the CRU standard profile, so time of use plans can be
+// priced roughly without an HDF file. The shares are scaled to the 365 days so they add up to
+// the annual kWh whichever day the year starts on
+pub fn annual_usage_readings(
+    annual_kwh: f32,
+    start: NaiveDate,
+) -> (Vec<SmartMeterData>, EstimatedPeriod) {
+    let shares: Vec<(NaiveDate, f32)> = start
+        .iter_days()
+        .take(365)
+        .map(|date| {
+            let share = MONTHLY_SHARES[date.month0() as usize] / days_in_month(date) as f32;
+            (date, share)
+        })
+        .collect();
+    let total: f32 = shares.iter().map(|(_, share)| share).sum();
+    let readings = profile_readings(
+        shares
+            .into_iter()
+            .map(|(date, share)| (date, annual_kwh * share / total)),
+    );
+    let period = EstimatedPeriod {
+        from: start,
+        to: start + chrono::Duration::days(364),
+        kwh: readings.iter().map(|d| d.read_value).sum(),
+    };
+    (readings, period)
+}
+
+// Half hours for the days of the bills, the kWh of each bill spread evenly over its days and
+// over each day like the CRU standard profile. Days between the bills have no readings
+pub fn billed_usage_readings(bills: &[BilledUsage]) -> (Vec<SmartMeterData>, Vec<EstimatedPeriod>) {
+    let readings = profile_readings(bills.iter().flat_map(|bill| {
+        let days = (bill.to - bill.from).num_days() + 1;
+        bill.from
+            .iter_days()
+            .take(days as usize)
+            .map(move |date| (date, bill.kwh / days as f32))
+    }));
+    let periods = bills
+        .iter()
+        .map(|bill| EstimatedPeriod {
+            from: bill.from,
+            to: bill.to,
+            kwh: bill.kwh,
+        })
+        .collect();
+    (readings, periods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_totals_are_spread_over_the_year_and_the_bills() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let (readings, period) = annual_usage_readings(4200.0, start);
+        assert_eq!(readings.len(), 365 * 48);
+        assert!((period.kwh - 4200.0).abs() < 0.5, "{period:?}");
+        let month = |month: u32| -> f32 {
+            readings
+                .iter()
+                .filter(|d| d.local_end_time().month() == month && d.local_end_time().day() > 1)
+                .map(|d| d.read_value)
+                .sum()
+        };
+        assert!(month(12) > month(6) * 1.5);
+
+        let bills = [
+            BilledUsage {
+                from: start,
+                to: NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(),
+                kwh: 600.0,
+            },
+            BilledUsage {
+                from: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+                to: NaiveDate::from_ymd_opt(2024, 4, 30).unwrap(),
+                kwh: 488.0,
+            },
+        ];
+        let (readings, periods) = billed_usage_readings(&bills);
+        assert_eq!(periods.len(), 2);
+        // the clocks go forward in march
+        assert_eq!(readings.len(), 121 * 48 - 2);
+        let kwh: f32 = readings.iter().map(|d| d.read_value).sum();
+        assert!((kwh - 1088.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::{bail, Context, Result};
+use chrono::{NaiveDate, NaiveTime};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use crate::{time_in_window, BillBreakdown, ReadUnit, Saving, SmartMeterData, SmartMeterDataType};
+
+// A daily time window like 17:00-19:00, the readings ending inside it belong to it like in
+// the bands of the plans
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn contains(&self, datapoint: &SmartMeterData) -> bool {
+        time_in_window(datapoint.local_end_time().time(), self.start, self.end)
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<TimeWindow> {
+        let Some((start, end)) = text.split_once('-') else {
+            bail!("{text} is not a window like 17:00-19:00");
+        };
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .with_context(|| format!("{time} in {text} is not a time like 17:00"))
+        };
+        Ok(TimeWindow {
+            start: time(start)?,
+            end: time(end)?,
+        })
+    }
+}
+
+impl Display for TimeWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+// Moving kwh_per_day of the imports of every day from one window to another, like running
+// the dishwasher at night instead of after dinner
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LoadShift {
+    pub kwh_per_day: f32,
+    pub from: TimeWindow,
+    pub to: TimeWindow,
+}
+
+// What a plan would cost with the readings changed, the load shifted or solar panels added,
+// and what that saves on the same plan
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ShiftedCost {
+    pub breakdown: BillBreakdown,
+    pub saving: Saving,
+}
+
+type Windows = (Vec<usize>, Vec<usize>);
+
+// Adds kwh to the energy of a reading whatever its unit
+pub(crate) fn add_energy(datapoint: &mut SmartMeterData, kwh: f32) {
+    datapoint.read_value += match datapoint.unit {
+        ReadUnit::Kilowatts => kwh * 60.0 / datapoint.interval_minutes as f32,
+        ReadUnit::KilowattHours => kwh,
+    };
+}
+
+// The readings with the shift applied to each day of each meter point. The energy is taken
+// from the readings of the from window in proportion to their usage, never more than they
+// used, and spread evenly over the readings of the to window of the same day. A day without
+// readings in the to window is left as it is
+pub fn shift_load(datapoints: &[SmartMeterData], shift: &LoadShift) -> Vec<SmartMeterData> {
+    let mut shifted = datapoints.to_vec();
+    // the readings of the from and to windows of each day of each meter point
+    let mut days: BTreeMap<(&str, NaiveDate), Windows> = BTreeMap::new();
+    for (index, datapoint) in datapoints.iter().enumerate() {
+        if datapoint.read_type != SmartMeterDataType::ActiveImport {
+            continue;
+        }
+        let day = days
+            .entry((datapoint.mprn.as_ref(), datapoint.date()))
+            .or_default();
+        if shift.from.contains(datapoint) {
+            day.0.push(index);
+        } else if shift.to.contains(datapoint) {
+            day.1.push(index);
+        }
+    }
+    for (from, to) in days.values() {
+        let available: f32 = from.iter().map(|&i| datapoints[i].energy_kwh()).sum();
+        let moved = shift.kwh_per_day.min(available);
+        if to.is_empty() || moved <= 0.0 {
+            continue;
+        }
+        let kept = (available - moved) / available;
+        for &i in from {
+            shifted[i].read_value *= kept;
+        }
+        for &i in to {
+            add_energy(&mut shifted[i], moved / to.len() as f32);
+        }
+    }
+    shifted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn energy_moves_between_the_windows_of_each_day() {
+        let shift = LoadShift {
+            kwh_per_day: 1.5,
+            from: "17:00-19:00".parse().unwrap(),
+            to: "02:00-05:00".parse().unwrap(),
+        };
+        let data = vec![
+            // 1 kWh each in the evening, 0.5 kWh at night
+            reading_at("08-01-2024 17:30", 2.0),
+            reading_at("08-01-2024 19:00", 2.0),
+            reading_at("08-01-2024 03:00", 1.0),
+            reading_at("08-01-2024 12:00", 1.0),
+            // a day with nothing in the night window keeps its evening
+            reading_at("09-01-2024 18:00", 2.0),
+        ];
+        let shifted = shift_load(&data, &shift);
+        let kwh: Vec<f32> = shifted.iter().map(SmartMeterData::energy_kwh).collect();
+        assert_eq!(kwh, vec![0.25, 0.25, 2.0, 0.5, 1.0]);
+        assert_eq!(shift.from.to_string(), "17:00-19:00");
+        assert!("17:00".parse::<TimeWindow>().is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use std::{fmt, str::FromStr, sync::OnceLock};
+
+// The language and number format of the reports, so they can be handed to someone who reads
+// portuguese. en-IE is what the tool always printed, ISO dates and a decimal point
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    EnIe,
+    PtBr,
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+// The english strings of the reports and what they are in brazilian portuguese, {} is where
+// the values go. Strings missing here stay in english
+const PT_BR: &[(&str, &str)] = &[
+    ("Energy plan comparison", "Comparação de planos de energia"),
+    ("Electricity plans", "Planos de eletricidade"),
+    ("Gas plans", "Planos de gás"),
+    (
+        "Estimated annual bills on the CRU standard usage",
+        "Contas anuais estimadas com o consumo padrão da CRU",
+    ),
+    (
+        "First bill with sign up bonuses",
+        "Primeira conta com os bônus de adesão",
+    ),
+    (
+        "Projected cost of a year from {} days of readings",
+        "Custo projetado de um ano a partir de {} dias de leituras",
+    ),
+    (
+        "Projected cost of a year from {} days of readings, cheapest first:",
+        "Custo projetado de um ano a partir de {} dias de leituras, do mais barato:",
+    ),
+    ("Maximum demand of {} kW", "Demanda máxima de {} kW"),
+    (
+        "Cost of the imports in the peak",
+        "Custo das importações no horário de ponta",
+    ),
+    ("Export earnings", "Ganhos com a exportação"),
+    (
+        "Average cost of a kWh imported",
+        "Custo médio de um kWh importado",
+    ),
+    ("Scenario {}", "Cenário {}"),
+    ("Free time choices of {}", "Opções de horário grátis de {}"),
+    ("Most expensive days on {}", "Dias mais caros em {}"),
+    ("Cheapest plan per period", "Plano mais barato por período"),
+    ("Plan details", "Detalhes dos planos"),
+    ("Plan details:", "Detalhes dos planos:"),
+    ("{} bands", "Faixas de {}"),
+    ("current plan", "plano atual"),
+    ("A month", "Por mês"),
+    ("After a year", "Após um ano"),
+    ("All in c/kWh", "Tudo incluído c/kWh"),
+    ("Band", "Faixa"),
+    ("Bands", "Faixas"),
+    ("Chosen", "Escolhida"),
+    ("Circuits", "Circuitos"),
+    ("Cost of a year", "Custo de um ano"),
+    ("Cost", "Custo"),
+    ("Day", "Dia"),
+    ("Details", "Detalhes"),
+    ("Source", "Origem"),
+    ("the built in plans", "os planos embutidos"),
+    ("the plan registry", "o registro de planos"),
+    ("the tariff file", "o arquivo de tarifas"),
+    ("EV per kWh", "VE por kWh"),
+    ("EV", "VE"),
+    ("Each year", "Por ano"),
+    ("Earned", "Ganho"),
+    ("Energy c/kWh", "Energia c/kWh"),
+    ("Free window", "Janela grátis"),
+    ("Higher", "Maior"),
+    ("Interval ending", "Intervalo até"),
+    ("Lead goes at", "Vantagem acaba em"),
+    ("Lower", "Menor"),
+    ("Lowest balance", "Menor saldo"),
+    ("Month", "Mês"),
+    ("Peak cost", "Custo na ponta"),
+    ("Period", "Período"),
+    ("Plan", "Plano"),
+    ("Rank", "Posição"),
+    ("Rest of the house", "Resto da casa"),
+    ("Saving on the same plan", "Economia no mesmo plano"),
+    ("Saving", "Economia"),
+    ("Share of the imports", "Parcela das importações"),
+    ("Standing charge", "Taxa fixa"),
+    ("Usage", "Consumo"),
+    ("Imports", "Importações"),
+    ("Export credit", "Crédito de exportação"),
+    ("kWh exported", "kWh exportados"),
+    (
+        "Meter point {}, billing period {} to {} ({} days).",
+        "Ponto de medição {}, período de faturamento de {} a {} ({} dias).",
+    ),
+    (
+        " The cheapest plan is {} at {}.",
+        " O plano mais barato é {} por {}.",
+    ),
+    (
+        " The readings from {} to {} are estimated, {} kWh spread like the standard profile.",
+        " As leituras de {} a {} são estimadas, {} kWh distribuídos como o perfil padrão.",
+    ),
+    ("Meter point: {}", "Ponto de medição: {}"),
+    (
+        "Billing period: {} to {} ({} days)",
+        "Período de faturamento: {} a {} ({} dias)",
+    ),
+    (
+        "Estimated readings: {} to {}, {} kWh spread like the standard profile",
+        "Leituras estimadas: {} a {}, {} kWh distribuídos como o perfil padrão",
+    ),
+    (
+        " Only {}% of the intervals have actual readings, {}% are filled in, {}% estimated and {}% missing.",
+        " Apenas {}% dos intervalos têm leituras reais, {}% foram preenchidos, {}% estimados e {}% faltam.",
+    ),
+    (
+        "Coverage: {}% actual readings, {}% filled in, {}% estimated, {}% missing",
+        "Cobertura: {}% leituras reais, {}% preenchidas, {}% estimadas, {}% faltando",
+    ),
+    ("Actual cost of the readings:", "Custo real das leituras:"),
+    ("usage {}, ", "consumo {}, "),
+    ("imports {}, ", "importações {}, "),
+    ("exports {}, ", "exportações {}, "),
+    ("standing charge {}, ", "taxa fixa {}, "),
+    ("PSO levy {}, ", "taxa PSO {}, "),
+    ("credits {}, ", "créditos {}, "),
+    ("bill credit {}, ", "crédito na conta {}, "),
+    ("demand charge {}, ", "encargo de demanda {}, "),
+    ("total {}", "total {}"),
+    (
+        ", first bill with sign up bonus {}",
+        ", primeira conta com o bônus de adesão {}",
+    ),
+    (" (current plan)", " (plano atual)"),
+    (", saves {} ({}%)", ", economiza {} ({}%)"),
+];
+
+impl Locale {
+    // The locale every report is printed in, en-IE unless another was installed first
+    pub fn global() -> Locale {
+        *LOCALE.get_or_init(Locale::default)
+    }
+
+    // Only works before anything was printed, so it has to happen while starting up
+    pub fn install(self) -> Result<()> {
+        LOCALE
+            .set(self)
+            .map_err(|_| anyhow::anyhow!("the locale is already in use"))
+    }
+
+    // The string in the language of the locale, the english one when it has no translation
+    pub fn text(self, english: &str) -> &str {
+        match self {
+            Locale::EnIe => english,
+            Locale::PtBr => PT_BR
+                .iter()
+                .find(|(from, _)| *from == english)
+                .map_or(english, |(_, to)| to),
+        }
+    }
+
+    // The translated string with each {} replaced by the next value, the values formatted
+    // already with number and date
+    pub fn fill(self, english: &str, values: &[&dyn fmt::Display]) -> String {
+        let mut parts = self.text(english).split("{}");
+        let mut out = parts.next().unwrap_or_default().to_string();
+        for (index, part) in parts.enumerate() {
+            if let Some(value) = values.get(index) {
+                out.push_str(&value.to_string());
+            }
+            out.push_str(part);
+        }
+        out
+    }
+
+    // An amount or a number already formatted with a decimal point, like €843.23, with the
+    // decimal separator of the locale
+    pub fn number(self, formatted: String) -> String {
+        match self {
+            Locale::EnIe => formatted,
+            Locale::PtBr => formatted.replace('.', ","),
+        }
+    }
+
+    pub fn date(self, date: NaiveDate) -> String {
+        match self {
+            Locale::EnIe => date.to_string(),
+            Locale::PtBr => date.format("%d/%m/%Y").to_string(),
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Locale> {
+        match text.to_ascii_lowercase().replace('_', "-").as_str() {
+            "en-ie" | "en" => Ok(Locale::EnIe),
+            "pt-br" | "pt" => Ok(Locale::PtBr),
+            _ => bail!("{text} is not a locale, they are en-IE and pt-BR"),
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Locale::EnIe => f.write_str("en-IE"),
+            Locale::PtBr => f.write_str("pt-BR"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_are_translated_with_a_decimal_comma() {
+        let locale: Locale = "pt_BR".parse().unwrap();
+        assert_eq!(locale, Locale::PtBr);
+        assert_eq!(locale.text("Standing charge"), "Taxa fixa");
+        assert_eq!(locale.text("Not translated"), "Not translated");
+        let total = locale.number("€843.23".to_string());
+        assert_eq!(
+            locale.fill(" The cheapest plan is {} at {}.", &[&"Flat", &total]),
+            " O plano mais barato é Flat por €843,23."
+        );
+        let date = NaiveDate::from_ymd_opt(2024, 1, 8).unwrap();
+        assert_eq!(locale.date(date), "08/01/2024");
+        assert_eq!(Locale::EnIe.date(date), "2024-01-08");
+        assert_eq!(
+            Locale::EnIe.fill("Scenario {}", &[&"rising"]),
+            "Scenario rising"
+        );
+        assert!("fr-FR".parse::<Locale>().is_err());
+    }
+}
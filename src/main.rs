@@ -1,280 +1,1636 @@
-use anyhow::{Ok, Result};
-use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
-use serde::Deserialize;
-use std::{collections::HashSet, fs::File, io::BufReader, ops::Add, fmt::Debug};
+mod compare_command;
+mod text_output;
 
-// Defines the signature for the functions to define the price for a plan
-trait PricePlanStrategy : Debug {
-    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry;
-    fn standing_charge_per_day(&self) -> EnergyBillEntry;
-    fn standing_charge_per_number_of_days(&self, days: u32) -> EnergyBillEntry {
-        match self.standing_charge_per_day() {
-            EnergyBillEntry::Credit(_) => panic!("we shouldnever get credit per dau"),
-            EnergyBillEntry::Debit(day_value) => EnergyBillEntry::Debit(day_value * days as f32),
-        }
-    }
+use anyhow::{bail, Context, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use compare_command::{compare, install_options, load_plans, tariff_plans};
+use energy_comparator::{
+    anonymize_readings, builtin_plans, check_mprns, diff_results, fetch_carbon_intensity,
+    fetch_day_ahead_prices, fetch_hdf_file, fetch_wind_share, fill_gaps, filter_by_date,
+    group_by_mprn, is_valid_mprn, load_plan_file, load_saved_results, load_smart_meter_data,
+    load_snapshot, load_supplier_bill, merge_readings, read_csv, reconcile_bill,
+    resolve_meter_swaps, serve_metrics, update_registry, usage_stats, validate_readings,
+    write_conditions_file, write_hdf_csv, write_intensity_file, write_price_file,
+    write_readings_csv, write_readings_parquet, CheapestTracker, ComparisonReport,
+    ComparisonServer, CronSchedule, CustomPlan, CustomerType, DuosGroup, FileSnapshot,
+    GapFillStrategy, GapFilling, Granularity, InputSnapshot, Locale, MprnIssue, Notifier,
+    PricePlanStrategy, ReadingStore, SavedReport, Scenario, SmartMeterData, TimeWindow, UsageStats,
+    DEFAULT_INVOICE_MONTHS, DEFAULT_MIN_COVERAGE, DEFAULT_SHIFTABLE_SHARE,
+};
+use rust_decimal::Decimal;
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+    time::SystemTime,
+};
+use text_output::{
+    print_gap_filling, print_gap_report, print_meter_segments, print_results_diff,
+    print_usage_stats,
+};
 
-    fn compute_total_bill_for_period(&self, datapoints: &Vec<SmartMeterData>) -> EnergyBillEntry {
-        datapoints
-            .iter()
-            .fold(EnergyBillEntry::Debit(0.0), |acc, d| {
-                let price_for_singe_period = self.price_for_singe_period(d);
-                let energy_bill_entry = acc + price_for_singe_period;
-                energy_bill_entry
-            })
+fn percent(text: &str) -> Result<f32, String> {
+    match text.parse::<f32>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err(format!("{text} is not a percent between 0 and 100")),
     }
 }
 
-#[derive(Debug)]
-struct ElectricIrelandHomeElectric14;
-impl PricePlanStrategy for ElectricIrelandHomeElectric14 {
-    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
-        match datapoint.read_type {
-            SmartMeterDataType::ActiveImport => {
-                EnergyBillEntry::Debit(0.3895 * (1.0 - 0.14) * datapoint.read_value)
-            }
-            SmartMeterDataType::ActiveExport => {
-                EnergyBillEntry::Credit(0.21 * datapoint.read_value)
-            }
+// The usage of every plan and month from the last runs, see UsageCache
+const USAGE_CACHE: &str = ".usage-cache.bincode";
+// Where plans update keeps the downloaded plan registry
+const PLAN_REGISTRY: &str = ".plan-registry";
+
+// How often --watch looks for new files
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+// how often a scheduled run checks the clock
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Compares how much the readings of a smart meter would cost on different plans",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    global: GlobalArgs,
+
+    // without a command the readings are compared, like with compare
+    #[command(flatten)]
+    compare: CompareArgs,
+}
+
+// The options every command shares, given after the command
+#[derive(Debug, Clone, Args)]
+struct GlobalArgs {
+    /// The reading store to compare or validate the readings of instead of csv files, and the
+    /// one import adds them to, readings by default
+    #[arg(long, value_name = "DIR", global = true)]
+    store: Option<PathBuf>,
+
+    /// Only use readings from this day on (YYYY-MM-DD)
+    #[arg(long, global = true)]
+    from: Option<NaiveDate>,
+
+    /// Only use readings up to and including this day (YYYY-MM-DD)
+    #[arg(long, global = true)]
+    to: Option<NaiveDate>,
+
+    /// How to print the comparison, text unless given, html and markdown print a whole report
+    /// with band and monthly tables. csv and parquet are what export writes
+    #[arg(long, value_enum, global = true)]
+    format: Option<OutputFormat>,
+
+    /// The meter point the readings should be of, every file with the readings of another one
+    /// is warned about, like the download of a neighbour
+    #[arg(long, value_name = "MPRN", value_parser = parse_mprn, global = true)]
+    expect_mprn: Option<String>,
+
+    /// Language and number format of the text output and the reports, en-IE or pt-BR
+    #[arg(long, global = true)]
+    locale: Option<Locale>,
+}
+
+fn parse_circuit(text: &str) -> Result<(String, PathBuf)> {
+    match text.split_once('=') {
+        Some((name, file)) if !name.trim().is_empty() && !file.is_empty() => {
+            Ok((name.trim().to_string(), PathBuf::from(file)))
         }
+        _ => bail!("{text} is not a circuit like heating=heating.csv"),
     }
+}
 
-    fn standing_charge_per_day(&self) -> EnergyBillEntry {
-        EnergyBillEntry::Debit(272.61 / 365f32)
+fn parse_mprn(text: &str) -> Result<String> {
+    if !is_valid_mprn(text) {
+        bail!("{text} is not an MPRN, they have 11 digits");
     }
+    Ok(text.to_string())
 }
 
-#[derive(Debug)]
-struct SSEAirtricity20;
-impl PricePlanStrategy for SSEAirtricity20 {
-    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
-        const PEAK_ENERGY_START_TIME: NaiveTime = match NaiveTime::from_hms_opt(17, 0, 0) {
-            Some(t) => t,
-            None => panic!("Must be a valid time"),
-        };
-        const PEAK_ENERGY_END_TIME: NaiveTime = match NaiveTime::from_hms_opt(19, 0, 0) {
-            Some(t) => t,
-            None => panic!("Must be a valid time"),
-        };
-
-        const NIGHT_ENERGY_START_TIME: NaiveTime = match NaiveTime::from_hms_opt(23, 0, 0) {
-            Some(t) => t,
-            None => panic!("Must be a valid time"),
-        };
-        const NIGHT_ENERGY_END_TIME: NaiveTime = match NaiveTime::from_hms_opt(8, 0, 0) {
-            Some(t) => t,
-            None => panic!("Must be a valid time"),
-        };
+impl GlobalArgs {
+    fn format(&self) -> OutputFormat {
+        self.format.unwrap_or_default()
+    }
 
-        match datapoint.read_type {
-            SmartMeterDataType::ActiveImport => {
-                if datapoint.read_data_and_end_time.time() > PEAK_ENERGY_START_TIME
-                    && datapoint.read_data_and_end_time.time() <= PEAK_ENERGY_END_TIME
-                {
-                    EnergyBillEntry::Debit(0.4882 * (1.0 - 0.20) * datapoint.read_value)
-                } else if datapoint.read_data_and_end_time.time() > NIGHT_ENERGY_START_TIME
-                    && datapoint.read_data_and_end_time.time() <= NIGHT_ENERGY_END_TIME
-                {
-                    EnergyBillEntry::Debit(0.2506 * (1.0 - 0.20) * datapoint.read_value)
-                } else {
-                    EnergyBillEntry::Debit(0.3865 * (1.0 - 0.20) * datapoint.read_value)
+    // The readings of the store or of the inputs, from --from to --to
+    fn load_readings(&self, inputs: &[String], use_cache: bool) -> Result<Vec<SmartMeterData>> {
+        if self.store.is_some() && !inputs.is_empty() {
+            bail!("the readings come from --store or from the inputs, not both");
+        }
+        match &self.store {
+            Some(store) => {
+                let data = ReadingStore::open(store)?.load(self.from, self.to)?;
+                // the store is of every meter point imported, only the expected one is checked
+                for issue in check_mprns(&data, self.expect_mprn.as_deref()) {
+                    if let MprnIssue::Unexpected { .. } = issue {
+                        eprintln!("warning: {}: {issue}", store.display());
+                    }
                 }
+                Ok(data)
             }
-            SmartMeterDataType::ActiveExport => {
-                EnergyBillEntry::Credit(0.24 * datapoint.read_value)
-            }
+            None => Ok(filter_by_date(
+                load_inputs(inputs, use_cache, self.expect_mprn.as_deref())?,
+                self.from,
+                self.to,
+            )),
         }
     }
+}
 
-    fn standing_charge_per_day(&self) -> EnergyBillEntry {
-        EnergyBillEntry::Debit(0.6602)
-    }
+// The readings of the commands that don't compare plans, of the files or of the store of
+// --store. Import adds the files to the store instead
+#[derive(Debug, Clone, Args)]
+struct InputArgs {
+    /// HDF csv files downloaded from ESB Networks or directories of them, glob patterns like data/*.csv are expanded
+    #[arg(required_unless_present = "store")]
+    inputs: Vec<String>,
+
+    /// Always parse the csv instead of using the cached readings
+    #[arg(long)]
+    no_cache: bool,
 }
 
-#[derive(Debug)]
-struct BordGaisEnergy25WeekendFree;
-impl PricePlanStrategy for BordGaisEnergy25WeekendFree {
-    /**
-        Urban Day units (8am to 11pm)    43.04 35.30 cent per kWh
-        Urban Peak units (5pm to 7pm)    52.58 43.12 cent per kWh
-        Urban Night units (11pm to 8am)  31.63 25.94 cent per kWh
-        Annual Standing Charge           €237.56
-    */
-    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
-        const FREE_ENERGY_START_TIME: NaiveTime = match NaiveTime::from_hms_opt(9, 0, 0) {
-            Some(t) => t,
-            None => panic!("Must be a valid time"),
-        };
-        const FREE_ENERGY_END_TIME: NaiveTime = match NaiveTime::from_hms_opt(18, 0, 0) {
-            Some(t) => t,
-            None => panic!("Must be a valid time"),
-        };
+#[derive(Debug, Clone, Args)]
+struct CompareArgs {
+    /// HDF csv files downloaded from ESB Networks or directories of them, glob patterns like data/*.csv are expanded
+    #[arg(required_unless_present_any = ["store", "annual_kwh", "billed_kwh"], conflicts_with = "store")]
+    inputs: Vec<String>,
 
-        const PEAK_ENERGY_START_TIME: NaiveTime = match NaiveTime::from_hms_opt(17, 0, 0) {
-            Some(t) => t,
-            None => panic!("Must be a valid time"),
-        };
-        const PEAK_ENERGY_END_TIME: NaiveTime = match NaiveTime::from_hms_opt(19, 0, 0) {
-            Some(t) => t,
-            None => panic!("Must be a valid time"),
-        };
+    /// Only compare pay as you go plans, which come from the tariff file
+    #[arg(long)]
+    prepay: bool,
 
-        const NIGHT_ENERGY_START_TIME: NaiveTime = match NaiveTime::from_hms_opt(23, 0, 0) {
-            Some(t) => t,
-            None => panic!("Must be a valid time"),
-        };
-        const NIGHT_ENERGY_END_TIME: NaiveTime = match NaiveTime::from_hms_opt(8, 0, 0) {
-            Some(t) => t,
-            None => panic!("Must be a valid time"),
+    /// Only compare these plans, can be repeated
+    #[arg(long = "plan", value_name = "NAME")]
+    plans_to_compare: Vec<String>,
+
+    /// Bill this many days of standing charge instead of the days covered by the readings
+    #[arg(long)]
+    days: Option<u32>,
+
+    /// The plan you are on now, every other plan shows how much switching would save
+    #[arg(long, value_name = "NAME")]
+    current_plan: Option<String>,
+
+    /// Also show what each plan costs per month or per week
+    #[arg(long, value_name = "PERIOD")]
+    breakdown: Option<BreakdownArg>,
+
+    /// Also project the cost over this many years, with the discounts ending after the first year
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=10))]
+    years: Option<u32>,
+
+    /// Also project the years again with prices changing every year, like
+    /// "rising:unit=5%,standing=0%,export=-2%". Plans with fixed_rate_months in a tariff file
+    /// keep their rates for those months. Can be given more than once
+    #[arg(long, value_name = "SCENARIO", requires = "years")]
+    scenario: Vec<Scenario>,
+
+    /// Also suggest an equal monthly direct debit for a year of each plan from the month after
+    /// the readings, with the balance of the account month by month
+    #[arg(long)]
+    level_pay: bool,
+
+    /// The margin put on top of the level pay debit, in percent of it
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        requires = "level_pay",
+        default_value = "10"
+    )]
+    level_pay_buffer: Decimal,
+
+    /// Also estimate a year of each plan from the readings, scaled up by how much energy the
+    /// months of the readings usually have, for when there are only a few months of readings
+    #[arg(long)]
+    annualize: bool,
+
+    /// Also show what a kWh imported cost on average on each plan, for the energy alone and
+    /// with the standing charge and levy spread over the kWh
+    #[arg(long)]
+    effective_rates: bool,
+
+    /// Also show what the exports earn on each plan month by month, at its export rate and once
+    /// its rules have capped the credit or netted it against the imports
+    #[arg(long)]
+    exports: bool,
+
+    /// Also show the energy and cost that fell into each band (day, night, peak...) of each plan
+    #[arg(long)]
+    bands: bool,
+
+    /// Also write the results and the monthly breakdown to this csv file
+    #[arg(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Write how every reading was priced on every plan to this csv: its band, kWh, rate and charge
+    #[arg(long, value_name = "FILE", conflicts_with = "stream")]
+    explain: Option<PathBuf>,
+
+    #[command(flatten)]
+    gaps: GapFillArgs,
+
+    /// Percent of the intervals of the billing period that need actual readings for the plans
+    /// to be ranked, filled in and estimated readings don't count
+    #[arg(long, value_name = "PERCENT", default_value_t = DEFAULT_MIN_COVERAGE * 100.0, value_parser = percent)]
+    min_coverage: f32,
+
+    /// Rank the plans with a warning when fewer intervals than --min-coverage have actual
+    /// readings, instead of stopping
+    #[arg(long)]
+    allow_partial: bool,
+
+    /// Readings of the meter register from before the smart meter, a csv of date,kwh rows like
+    /// 2023-11-01,12345.6. What it went up by is spread over the half hours like the CRU
+    /// standard profile and compared with the smart readings, marked as estimated
+    #[arg(long, value_name = "FILE", conflicts_with = "stream")]
+    manual_reads: Option<PathBuf>,
+
+    /// Compare without an HDF file on a year of this many kWh, spread over the months like an
+    /// irish home and over each day like the CRU standard profile, from --from or a year ago
+    #[arg(long, value_name = "KWH", conflicts_with_all = ["inputs", "store", "stream", "watch", "schedule", "manual_reads", "billed_kwh"])]
+    annual_kwh: Option<f32>,
+
+    /// Compare without an HDF file on the kWh of bills, a csv of from,to,kwh rows like
+    /// 2024-01-01,2024-02-29,780, each spread over its days like the CRU standard profile
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["inputs", "store", "stream", "watch", "schedule", "manual_reads"])]
+    billed_kwh: Option<PathBuf>,
+
+    /// Also price every plan on the CRU standard usage of 4,200 kWh a year, like the estimated
+    /// annual bills the suppliers publish
+    #[arg(long)]
+    eab: bool,
+
+    /// Draw the average energy of each hour of each day of the week to this svg, exports under the imports
+    #[arg(long, value_name = "FILE", conflicts_with = "stream")]
+    heatmap: Option<PathBuf>,
+
+    /// Draw the running total of every plan over the period to this svg, a meter point of its own
+    /// when there are several
+    #[arg(long, value_name = "FILE", conflicts_with = "stream")]
+    cost_chart: Option<PathBuf>,
+
+    /// Price every plan again with this many kWh a day moved from --shift-from to --shift-to,
+    /// to see what changing when the energy is used would save on each plan
+    #[arg(long, value_name = "KWH", requires_all = ["shift_from", "shift_to"])]
+    shift_kwh: Option<f32>,
+
+    /// The window the shifted energy is taken from, like 17:00-19:00
+    #[arg(long, value_name = "WINDOW", requires = "shift_kwh")]
+    shift_from: Option<TimeWindow>,
+
+    /// The window the shifted energy is used in instead, like 02:00-05:00
+    #[arg(long, value_name = "WINDOW", requires = "shift_kwh")]
+    shift_to: Option<TimeWindow>,
+
+    /// Price every plan again with a home battery of this many kWh, charged from the grid in
+    /// --battery-charge and covering the imports of the rest of the day
+    #[arg(long, value_name = "KWH")]
+    battery_kwh: Option<f32>,
+
+    /// The most the battery charges or discharges in an hour
+    #[arg(
+        long,
+        value_name = "KW",
+        requires = "battery_kwh",
+        default_value_t = 5.0
+    )]
+    battery_kw: f32,
+
+    /// Percent of the energy charged that comes back out of the battery
+    #[arg(long, value_name = "PERCENT", requires = "battery_kwh", default_value_t = 90.0, value_parser = percent)]
+    battery_efficiency: f32,
+
+    /// The window the battery charges in, the night rate of most plans by default
+    #[arg(
+        long,
+        value_name = "WINDOW",
+        requires = "battery_kwh",
+        default_value = "23:00-08:00"
+    )]
+    battery_charge: TimeWindow,
+
+    /// Only discharge in this window, like the 17:00-19:00 peak, instead of whenever there are imports
+    #[arg(long, value_name = "WINDOW", requires = "battery_kwh")]
+    battery_discharge: Option<TimeWindow>,
+
+    /// What the battery costs installed, to show how many years it takes to pay for itself on each plan
+    #[arg(long, value_name = "EURO", requires = "battery_kwh")]
+    battery_cost: Option<Decimal>,
+
+    /// Price every plan again with solar panels of this many kWp on the house, their
+    /// generation is modelled from the sun over Ireland
+    #[arg(long, value_name = "KWP", conflicts_with = "solar_generation")]
+    solar_kwp: Option<f32>,
+
+    /// Degrees the panels are tilted from flat
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        requires = "solar_kwp",
+        default_value_t = 35.0
+    )]
+    solar_tilt: f32,
+
+    /// Compass degrees the panels face, 180 is south, 90 east and 270 west
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        requires = "solar_kwp",
+        default_value_t = 180.0
+    )]
+    solar_azimuth: f32,
+
+    /// Price every plan again with the generation of this csv instead of a modelled array, the
+    /// kWh made per interval like an inverter export
+    #[arg(long, value_name = "FILE")]
+    solar_generation: Option<PathBuf>,
+
+    /// Price every plan again with the usage of the future on top of the readings, like a heat
+    /// pump or nightly charging of a car, see growth.example.toml
+    #[arg(long, value_name = "FILE")]
+    usage_growth: Option<PathBuf>,
+
+    /// Find the charging of an electric car in the readings and show what it costs on each
+    /// plan apart from the rest of the house
+    #[arg(long)]
+    ev: bool,
+
+    /// The power the imports stay at or above while the car charges
+    #[arg(long, value_name = "KW", requires = "ev", default_value_t = 3.0)]
+    ev_kw: f32,
+
+    /// How long the imports have to stay that high to be a charging session
+    #[arg(long, value_name = "MINUTES", requires = "ev", default_value_t = 60)]
+    ev_minutes: u32,
+
+    /// A circuit of the house read by a CT clamp monitor, NAME=FILE with a csv of end,kwh rows
+    /// in UTC like 08-01-2024 00:30,0.12, to show what it costs on each plan apart from the
+    /// rest of the house. Give it once for each circuit, like heating and ev
+    #[arg(long, value_name = "NAME=FILE", value_parser = parse_circuit)]
+    circuit: Vec<(String, PathBuf)>,
+
+    /// Also bill every plan invoice by invoice like the supplier would, each invoice rounded
+    /// and charged the levy on its own
+    #[arg(long)]
+    invoices: bool,
+
+    /// A day an invoice of the account starts on, like the start of the last bill, the
+    /// first day of the readings by default
+    #[arg(long, value_name = "DATE", requires = "invoices")]
+    invoice_anchor: Option<NaiveDate>,
+
+    /// How many months each invoice covers
+    #[arg(long, value_name = "MONTHS", requires = "invoices", default_value_t = DEFAULT_INVOICE_MONTHS, value_parser = clap::value_parser!(u32).range(1..=12))]
+    invoice_months: u32,
+
+    /// Also work out the standby power of the house from the quietest overnight readings and
+    /// what a year of it costs on each plan
+    #[arg(long)]
+    baseload: bool,
+
+    /// Also list the N intervals with the highest demand and the share of the cost of each
+    /// plan that falls in its peak, the 17:00 to 19:00 peak for plans without one
+    #[arg(long, value_name = "N")]
+    peaks: Option<usize>,
+
+    /// Also list the N days each plan charged the most for, with their kWh and bands
+    #[arg(long, value_name = "N")]
+    expensive_days: Option<usize>,
+
+    /// Also price the free time plans with each of the free days or hours they let you pick
+    #[arg(long)]
+    free_time_choices: bool,
+
+    /// The day the contract of the current plan ends, to work out whether paying the exit fee to
+    /// switch now is cheaper than waiting and when each plan pays the fee back
+    #[arg(long, value_name = "DATE", requires = "current_plan")]
+    contract_end: Option<NaiveDate>,
+
+    /// What leaving the current plan before the end of its contract costs, in euro
+    #[arg(
+        long,
+        value_name = "EURO",
+        requires = "contract_end",
+        default_value = "0"
+    )]
+    exit_fee: Decimal,
+
+    /// The day the switch would happen, today unless given
+    #[arg(long, value_name = "DATE", requires = "contract_end")]
+    switch_on: Option<NaiveDate>,
+
+    /// Only list the most expensive days of this plan
+    #[arg(long, value_name = "NAME", requires = "expensive_days")]
+    expensive_days_plan: Option<String>,
+
+    /// Also price every plan month by month and list the cheapest plan of each month, with how
+    /// often the cheapest plan of the whole period was beaten, to tell a seasonal advantage
+    #[arg(long)]
+    monthly_winners: bool,
+
+    /// Also move the unit rates and standing charge of each plan up and down by this percent,
+    /// one plan at a time, and flag the plans whose lead over the next one goes with it
+    #[arg(long, value_name = "PERCENT")]
+    sensitivity: Option<Decimal>,
+
+    /// Also estimate the kg of CO2 of the imports and how much shifting usage to the cleanest
+    /// hours of each day would save, with the average of the Irish grid unless --carbon-intensity
+    #[arg(long)]
+    carbon: bool,
+
+    /// Csv of the carbon intensity of the grid per half hour or quarter hour, in g of CO2 per
+    /// kWh, like the one fetch-carbon writes
+    #[arg(long, value_name = "FILE", requires = "carbon")]
+    carbon_intensity: Option<PathBuf>,
+
+    /// Percent of the usage of each day that could be moved to another time, like the washing
+    /// machine or charging a car
+    #[arg(long, value_name = "PERCENT", requires = "carbon", default_value_t = DEFAULT_SHIFTABLE_SHARE * 100.0, value_parser = percent)]
+    shiftable: f32,
+
+    /// Gas readings csv to compare gas plans too
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["gas_kwh_per_day", "gas_kwh"])]
+    gas: Option<PathBuf>,
+
+    /// Compare gas plans for this much gas a day instead of readings, like from a gas bill
+    #[arg(long, value_name = "KWH", conflicts_with = "gas_kwh")]
+    gas_kwh_per_day: Option<f32>,
+
+    /// Compare gas plans for this much gas over the whole billing period
+    #[arg(long, value_name = "KWH")]
+    gas_kwh: Option<f32>,
+
+    /// The calorific value of gas readings in m3, in MJ/m3 as printed on the gas bill [default: 39.5]
+    #[arg(long, value_name = "MJ")]
+    calorific_value: Option<f32>,
+
+    /// The correction factor for the temperature and pressure of gas readings in m3 [default: 1.02264]
+    #[arg(long, value_name = "FACTOR")]
+    correction_factor: Option<f32>,
+
+    /// Always parse the csv and price every month instead of using the cached readings and usage
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Stop at the first row of a csv that can't be read, with its line, and always parse the csv
+    #[arg(long, conflicts_with = "lenient")]
+    strict: bool,
+
+    /// Skip the rows of a csv that can't be read and say how many were skipped, the default
+    #[arg(long)]
+    lenient: bool,
+
+    /// Decimal places of the amounts in the text output
+    #[arg(long, default_value_t = 2)]
+    decimals: usize,
+
+    /// Also list the supplier, tariff date, contract and sign up terms of each plan in the
+    /// text output, the other formats always have them
+    #[arg(long)]
+    plan_details: bool,
+
+    /// Copy the rows of the csv files that couldn't be read to this csv, with what was wrong with them
+    #[arg(long, value_name = "FILE")]
+    rejects: Option<PathBuf>,
+
+    /// Add or remove bank holidays from the built in irish calendar, see holidays.example.toml
+    #[arg(long, value_name = "FILE")]
+    holidays: Option<PathBuf>,
+
+    /// Network group of the meter point, rural meter points pay the rural standing charges
+    #[arg(long, value_enum)]
+    duos_group: Option<DuosGroupArg>,
+
+    /// Compare the plans of this price list, business plans come from the tariff file
+    #[arg(long, value_enum, default_value_t = CustomerTypeArg::Domestic)]
+    customer_type: CustomerTypeArg,
+
+    /// Maximum import capacity of a business connection in kVA, for the MIC charges of its plans
+    #[arg(long, value_name = "KVA")]
+    mic_kva: Option<f32>,
+
+    /// Leave out the plans that need a smart meter
+    #[arg(long)]
+    no_smart_meter: bool,
+
+    /// Leave out the plans that need proof of owning an electric car
+    #[arg(long)]
+    no_ev: bool,
+
+    /// Leave out the plans that are only sold with gas
+    #[arg(long, conflicts_with_all = ["gas", "gas_kwh_per_day", "gas_kwh"])]
+    no_gas: bool,
+
+    /// The supplier you are with now, its plans for new customers are left out. The supplier of
+    /// --current-plan unless given
+    #[arg(long, value_name = "NAME")]
+    current_supplier: Option<String>,
+
+    /// Add or change the PSO levy values charged per day, see pso.example.toml
+    #[arg(long, value_name = "FILE")]
+    pso: Option<PathBuf>,
+
+    /// Fixed credits taken off every bill, like the government electricity credits, see credits.example.toml
+    #[arg(long, value_name = "FILE")]
+    credits: Option<PathBuf>,
+
+    /// Price the readings while reading the csv files instead of loading them, for very large
+    /// downloads that don't overlap. Only the totals are compared
+    #[arg(long, conflicts_with_all = ["store", "watch", "breakdown", "bands", "effective_rates", "exports", "years", "scenario", "level_pay", "annualize", "days", "eab", "carbon", "shift_kwh", "battery_kwh", "solar_kwp", "solar_generation", "usage_growth", "ev", "circuit", "invoices", "baseload", "peaks", "expensive_days", "free_time_choices", "monthly_winners", "sensitivity", "fill_gaps", "contract_end", "gas", "gas_kwh_per_day", "gas_kwh"])]
+    stream: bool,
+
+    /// Explore the comparison from the terminal, hiding plans, changing the dates and switching
+    /// to the projected cost of a year with commands
+    #[arg(long, conflicts_with_all = ["stream", "watch"])]
+    interactive: bool,
+
+    /// Keep running and compare again whenever a csv is added to or changed in the inputs, or
+    /// the tariff file changes
+    #[arg(long, conflicts_with = "store")]
+    watch: bool,
+
+    /// Send word with --watch when the cheapest plan changes, to the webhook or email of the
+    /// file, see notify.example.toml
+    #[arg(long, value_name = "FILE", requires = "watch")]
+    notify: Option<PathBuf>,
+
+    /// Keep running and compare again on a cron schedule in local time, like "0 6 * * *" for
+    /// six every morning, writing the report to --report-to. The first comparison is right away
+    #[arg(long, value_name = "CRON", requires = "report_to", conflicts_with_all = ["watch", "interactive"])]
+    schedule: Option<CronSchedule>,
+
+    /// Answer GET /metrics on this address with --watch or --schedule, like 127.0.0.1:9090, for
+    /// prometheus to scrape: the readings read, the rows skipped, when readings were last read
+    /// and the cost of the cheapest plan of each meter point
+    #[arg(long, value_name = "ADDRESS")]
+    metrics: Option<String>,
+
+    /// Where --schedule writes the report, html unless --format markdown or json. It's replaced
+    /// in one go so nothing reading it sees it half written
+    #[arg(long, value_name = "FILE", requires = "schedule")]
+    report_to: Option<PathBuf>,
+
+    /// Download the latest readings of this meter point from the ESB Networks portal before each
+    /// scheduled comparison, as HDF_<mprn>.csv in the first input directory. Logs in with
+    /// ESB_USERNAME and ESB_PASSWORD like fetch. Can be given more than once
+    #[arg(
+        long,
+        value_name = "MPRN",
+        value_parser = parse_mprn,
+        requires = "schedule",
+        conflicts_with = "store"
+    )]
+    refresh_mprn: Vec<String>,
+
+    /// Download the registry of plans again from this url before each scheduled comparison,
+    /// like plans update
+    #[arg(
+        long,
+        value_name = "URL",
+        requires = "schedule",
+        conflicts_with = "no_registry"
+    )]
+    refresh_registry: Option<String>,
+
+    /// Tariff file with extra plans to compare, see plans.example.toml
+    #[arg(long, value_name = "FILE")]
+    tariffs: Option<PathBuf>,
+
+    /// A flat plan to compare without a tariff file, like
+    /// "myplan:unit=0.32,standing=0.70/day,export=0.20,discount=15%", standing can be /year
+    /// and bonus= adds a sign up bonus. Can be given more than once
+    #[arg(long, value_name = "PLAN")]
+    custom_plan: Vec<CustomPlan>,
+
+    /// Leave out the plans of the registry downloaded by plans update
+    #[arg(long)]
+    no_registry: bool,
+}
+
+// A comparison with a change to the home priced on every plan, it needs at least one
+#[derive(Debug, Args)]
+#[command(group(
+    ArgGroup::new("simulation")
+        .required(true)
+        .multiple(true)
+        .args(["shift_kwh", "battery_kwh", "solar_kwp", "solar_generation", "usage_growth", "scenario"])
+))]
+struct SimulateArgs {
+    #[command(flatten)]
+    compare: CompareArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+struct GapFillArgs {
+    /// Make up the readings of short gaps instead of comparing without them: the reading of the
+    /// same time a week before, a straight line across the gap or nothing. --explain marks them
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    fill_gaps: Option<GapFillArg>,
+
+    /// The longest gap --fill-gaps fills, in hours, longer ones stay missing
+    #[arg(long, default_value_t = 6, requires = "fill_gaps")]
+    max_gap_hours: u32,
+}
+
+impl CompareArgs {
+    // The arguments with every plan asked for by an alias asked for by its name
+    fn with_plan_names(&self, plans: &[Box<dyn PricePlanStrategy>]) -> CompareArgs {
+        let name_of = |name: &String| {
+            plans
+                .iter()
+                .find(|plan| plan.is_called(name))
+                .map_or_else(|| name.clone(), |plan| plan.name())
         };
+        CompareArgs {
+            current_plan: self.current_plan.as_ref().map(name_of),
+            plans_to_compare: self.plans_to_compare.iter().map(name_of).collect(),
+            expensive_days_plan: self.expensive_days_plan.as_ref().map(name_of),
+            ..self.clone()
+        }
+    }
+}
 
-        const WEEKDAYS: [Weekday; 5] = [
-            Weekday::Mon,
-            Weekday::Tue,
-            Weekday::Wed,
-            Weekday::Thu,
-            Weekday::Fri,
-        ];
-        match datapoint.read_type {
-            SmartMeterDataType::ActiveImport => {
-                // treat the sunday special case
-                // free from 9am to 5pm
-                // no peak time on weekends
-                if datapoint.read_data_and_end_time.weekday() == Weekday::Sun
-                    && datapoint.read_data_and_end_time.time() > FREE_ENERGY_START_TIME
-                    && datapoint.read_data_and_end_time.time() <= FREE_ENERGY_END_TIME
-                {
-                    EnergyBillEntry::Debit(0.0)
-                } else if WEEKDAYS.contains(&datapoint.read_data_and_end_time.weekday())
-                    && datapoint.read_data_and_end_time.time() > PEAK_ENERGY_START_TIME
-                    && datapoint.read_data_and_end_time.time() <= PEAK_ENERGY_END_TIME
-                {
-                    EnergyBillEntry::Debit(0.5258 * (1.0 - 0.25) * datapoint.read_value)
-                } else if datapoint.read_data_and_end_time.time() > NIGHT_ENERGY_START_TIME
-                    && datapoint.read_data_and_end_time.time() <= NIGHT_ENERGY_END_TIME
-                {
-                    EnergyBillEntry::Debit(0.3163 * (1.0 - 0.25) * datapoint.read_value)
-                } else {
-                    EnergyBillEntry::Debit(0.4304 * (1.0 - 0.25) * datapoint.read_value)
-                }
-            }
-            SmartMeterDataType::ActiveExport => {
-                EnergyBillEntry::Credit(0.185 * datapoint.read_value)
-            }
+impl GapFillArgs {
+    fn fill(&self, data: Vec<SmartMeterData>) -> (Vec<SmartMeterData>, GapFilling) {
+        match self.fill_gaps {
+            Some(strategy) => fill_gaps(
+                data,
+                strategy.into(),
+                chrono::Duration::hours(i64::from(self.max_gap_hours)),
+            ),
+            None => (data, GapFilling::default()),
         }
     }
+}
+
+#[derive(Debug, Subcommand)]
+enum PlansCommand {
+    /// Downloads a tariff registry, a tariff file in toml or json, and keeps it to compare its
+    /// plans with the built in ones. The download has to match the sha256 checksum published
+    /// next to it
+    Update {
+        /// Where to download the registry from, over https
+        #[arg(long)]
+        url: String,
+
+        /// Where to download the checksum from, <url>.sha256 by default, like sha256sum prints it
+        #[arg(long, value_name = "URL")]
+        checksum_url: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Compares what the readings would have cost on every plan, the same as without a command
+    Compare(CompareArgs),
+    /// Prints the comparison as a standalone report with the band and monthly tables, in html
+    /// unless --format markdown
+    Report(CompareArgs),
+    /// Prices every plan again with a change to the home: usage shifted to another time, a
+    /// battery, solar panels, usage growing or prices moving over the years
+    Simulate(SimulateArgs),
+    /// Checks the readings for missing intervals, short days and out of order rows
+    Validate {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        gaps: GapFillArgs,
+    },
+    /// Sums up the imports and exports of the readings without any plan: the average day, the
+    /// lowest and highest days, weekdays against weekends and the share of the standard day,
+    /// night and peak windows
+    Stats {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Writes an HDF file again with made up meter point and meter numbers, and the days moved
+    /// with --shift-days, to share it in a bug report without giving away whose it is
+    Anonymize {
+        /// The HDF csv file to anonymize
+        input: PathBuf,
+
+        /// The HDF csv file to write
+        #[arg(long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Move every reading this many days, earlier when negative. Whole weeks keep the
+        /// weekdays the weekend plans depend on
+        #[arg(
+            long,
+            value_name = "DAYS",
+            default_value_t = 0,
+            allow_hyphen_values = true
+        )]
+        shift_days: i64,
+    },
+    /// Writes the readings in kWh with the end of each interval in UTC, for DuckDB, pandas or
+    /// Polars. --format csv (the default) or parquet
+    Export {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// The file to write
+        #[arg(long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Adds the readings of HDF files to the reading store of --store, replacing the ones
+    /// already there
+    Import {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Downloads the latest HDF file of the meter point from the ESB Networks portal, logging in
+    /// with the account in ESB_USERNAME and ESB_PASSWORD
+    Fetch {
+        /// The meter point to download the readings of
+        #[arg(long, value_parser = parse_mprn)]
+        mprn: String,
+
+        /// The HDF file to write, HDF_<mprn>.csv by default
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Downloads the irish day ahead prices from --from to --to from ENTSO-E into a price file
+    /// for dynamic plans, needs an ENTSO-E api token in ENTSOE_TOKEN
+    FetchPrices {
+        /// The price file to write
+        #[arg(long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Where the prices of each month are kept, so they're only downloaded once
+        #[arg(long, value_name = "DIR", default_value = ".day-ahead-cache")]
+        cache: PathBuf,
+    },
+    /// Downloads the carbon intensity of the irish grid from --from to --to from the EirGrid
+    /// smart grid dashboard into a csv for --carbon-intensity
+    FetchCarbon {
+        /// The intensity file to write
+        #[arg(long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Downloads the percent of the demand of the irish grid met by wind from --from to --to
+    /// from the EirGrid smart grid dashboard into a conditions file for conditional plans
+    FetchWind {
+        /// The conditions file to write
+        #[arg(long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Works out a bill from the supplier again from the readings and the plan it was charged
+    /// on, and shows the difference on each line
+    Reconcile {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// The bill to check, see bill.example.toml
+        #[arg(long, value_name = "FILE")]
+        bill: PathBuf,
+
+        /// Tariff file with the plan of the bill when it isn't a built in plan
+        #[arg(long, value_name = "FILE")]
+        tariffs: Option<PathBuf>,
+    },
+    /// Shows the plans whose total or place changed between two comparisons saved with
+    /// --format json, like before and after updating the plan registry
+    Diff {
+        /// The earlier comparison
+        before: PathBuf,
+
+        /// The later comparison
+        after: PathBuf,
+    },
+    /// Makes a comparison saved with --format json again from the inputs in its snapshot,
+    /// warning about the ones that changed since and whether the totals came out the same
+    Rerun {
+        /// The saved comparison
+        result: PathBuf,
+    },
+    /// Manages the plans compared on top of the built in ones
+    Plans {
+        #[command(subcommand)]
+        command: PlansCommand,
+    },
+    /// Answers comparisons over http: GET /plans lists the plans and POST /compare takes the
+    /// readings csv as the body and answers with the comparison as json
+    Serve {
+        /// The address to listen on, 0.0.0.0:8080 to be reachable from the rest of the network
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: String,
+
+        /// Tariff file with extra plans to compare, see plans.example.toml
+        #[arg(long, value_name = "FILE")]
+        tariffs: Option<PathBuf>,
+
+        /// Leave out the plans of the registry downloaded by plans update
+        #[arg(long)]
+        no_registry: bool,
+
+        /// Send word when an upload makes another plan the cheapest for its meter point, to
+        /// the webhook or email of the file, see notify.example.toml
+        #[arg(long, value_name = "FILE")]
+        notify: Option<PathBuf>,
+    },
+}
 
-    fn standing_charge_per_day(&self) -> EnergyBillEntry {
-        EnergyBillEntry::Debit(237.56 / 365f32)
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BreakdownArg {
+    Month,
+    Week,
+}
+
+impl From<BreakdownArg> for Granularity {
+    fn from(arg: BreakdownArg) -> Self {
+        match arg {
+            BreakdownArg::Month => Granularity::Month,
+            BreakdownArg::Week => Granularity::Week,
+        }
     }
 }
 
-fn smart_meter_datetime_desserialize<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    struct CustomVisitor;
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum GapFillArg {
+    LastWeek,
+    Interpolate,
+    Zero,
+}
 
-    impl<'de> serde::de::Visitor<'de> for CustomVisitor {
-        type Value = NaiveDateTime;
-        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-            formatter.write_str("a datetime in the format %d-%m-%Y %H:%M")
+impl From<GapFillArg> for GapFillStrategy {
+    fn from(arg: GapFillArg) -> Self {
+        match arg {
+            GapFillArg::LastWeek => GapFillStrategy::LastWeek,
+            GapFillArg::Interpolate => GapFillStrategy::Interpolate,
+            GapFillArg::Zero => GapFillStrategy::Zero,
         }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum DuosGroupArg {
+    Urban,
+    Rural,
+}
 
-        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
-        where
-            E: serde::de::Error,
-        {
-            NaiveDateTime::parse_from_str(value, "%d-%m-%Y %H:%M").map_err(E::custom)
+impl From<DuosGroupArg> for DuosGroup {
+    fn from(arg: DuosGroupArg) -> Self {
+        match arg {
+            DuosGroupArg::Urban => DuosGroup::Urban,
+            DuosGroupArg::Rural => DuosGroup::Rural,
         }
     }
+}
 
-    deserializer.deserialize_str(CustomVisitor)
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CustomerTypeArg {
+    Domestic,
+    Business,
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-enum SmartMeterDataType {
-    #[serde(rename = "Active Import Interval (kW)")]
-    ActiveImport,
-    #[serde(rename = "Active Export Interval (kW)")]
-    ActiveExport,
+impl From<CustomerTypeArg> for CustomerType {
+    fn from(arg: CustomerTypeArg) -> Self {
+        match arg {
+            CustomerTypeArg::Domestic => CustomerType::Domestic,
+            CustomerTypeArg::Business => CustomerType::Business,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum EnergyBillEntry {
-    Credit(f32),
-    Debit(f32),
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    // standalone reports that are easier to share than the console output
+    Html,
+    Markdown,
+    // the readings as a table, for export
+    Csv,
+    Parquet,
+}
+
+// The files of a directory that can have readings in them
+const INPUT_EXTENSIONS: [&str; 4] = ["csv", "tsv", "json", "jsonl"];
+
+// Expands the glob patterns and directories to the csv and json files in them, paths without
+// any pattern are kept as they are so a typo is reported
+fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for input in inputs {
+        let patterns = if PathBuf::from(input).is_dir() {
+            INPUT_EXTENSIONS
+                .iter()
+                .map(|extension| format!("{}/*.{extension}", input.trim_end_matches('/')))
+                .collect()
+        } else {
+            vec![input.clone()]
+        };
+        if !input.contains(['*', '?', '[']) && patterns.len() == 1 {
+            paths.push(PathBuf::from(input));
+            continue;
+        }
+        let mut matches = vec![];
+        for pattern in &patterns {
+            matches.extend(glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?);
+        }
+        if matches.is_empty() {
+            bail!("no files match {input}");
+        }
+        paths.extend(matches);
+    }
+    Ok(paths)
 }
 
-impl Add for EnergyBillEntry {
-    type Output = Self;
+// Files are merged oldest first, so the readings of the latest download win where they overlap
+fn load_inputs(
+    inputs: &[String],
+    use_cache: bool,
+    expected_mprn: Option<&str>,
+) -> Result<Vec<SmartMeterData>> {
+    let mut paths = expand_inputs(inputs)?;
+    paths.sort_by_key(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok());
+    let mut downloads = vec![];
+    for path in &paths {
+        let data = load_smart_meter_data(path, use_cache)?;
+        for issue in check_mprns(&data, expected_mprn) {
+            eprintln!("warning: {}: {issue}", path.display());
+        }
+        downloads.push(data);
+    }
+    let read: usize = downloads.iter().map(Vec::len).sum();
+    let data = merge_readings(downloads);
+    if data.len() < read {
+        eprintln!(
+            "dropped {} readings already present in another file",
+            read - data.len()
+        );
+    }
+    // a replaced meter and its replacement both report around the swap
+    let (data, swaps) = resolve_meter_swaps(data);
+    for swap in &swaps {
+        eprintln!(
+            "meter {} of {} replaced by {} from {}, dropped {} readings counted on the other meter",
+            swap.old_serial,
+            swap.mprn,
+            swap.new_serial,
+            swap.local_changeover().format("%d-%m-%Y %H:%M"),
+            swap.dropped
+        );
+        if swap.missing_intervals > 0 {
+            eprintln!(
+                "warning: {} intervals of {} are on neither meter before the swap",
+                swap.missing_intervals, swap.mprn
+            );
+        }
+    }
+    Ok(data)
+}
 
-    fn add(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (EnergyBillEntry::Debit(self_value), EnergyBillEntry::Debit(rhs_value)) => {
-                EnergyBillEntry::Debit(self_value + rhs_value)
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let global = &cli.global;
+    if let Some(locale) = global.locale {
+        locale.install()?;
+    }
+    match &cli.command {
+        None => run(global, &cli.compare),
+        Some(Command::Compare(args)) => run(global, args),
+        Some(Command::Report(args)) => {
+            let format = global.format.unwrap_or(OutputFormat::Html);
+            if !matches!(format, OutputFormat::Html | OutputFormat::Markdown) {
+                bail!("report prints html or markdown, compare prints text and json");
             }
-            (EnergyBillEntry::Credit(self_value), EnergyBillEntry::Credit(rhs_value)) => {
-                EnergyBillEntry::Credit(self_value + rhs_value)
+            let global = GlobalArgs {
+                format: Some(format),
+                ..global.clone()
+            };
+            run(&global, args)
+        }
+        Some(Command::Simulate(args)) => run(global, &args.compare),
+        Some(Command::Validate { input, gaps }) => {
+            let data = global.load_readings(&input.inputs, !input.no_cache)?;
+            print_meter_segments(&data);
+            print_gap_report(&validate_readings(&data));
+            if gaps.fill_gaps.is_some() {
+                print_gap_filling(&gaps.fill(data).1);
             }
-            (EnergyBillEntry::Credit(self_value), EnergyBillEntry::Debit(rhs_value)) => {
-                if self_value > rhs_value {
-                    EnergyBillEntry::Credit(self_value - rhs_value)
-                } else {
-                    EnergyBillEntry::Debit(rhs_value - self_value)
-                }
+            Ok(())
+        }
+        Some(Command::Stats { input }) => {
+            let data = global.load_readings(&input.inputs, !input.no_cache)?;
+            let stats: Vec<(String, UsageStats)> = group_by_mprn(data)
+                .into_iter()
+                .filter_map(|(mprn, data)| Some((mprn.to_string(), usage_stats(&data)?)))
+                .collect();
+            if stats.is_empty() {
+                bail!("no readings to sum up");
             }
-            (EnergyBillEntry::Debit(self_value), EnergyBillEntry::Credit(rhs_value)) => {
-                if rhs_value > self_value {
-                    EnergyBillEntry::Credit(rhs_value - self_value)
-                } else {
-                    EnergyBillEntry::Debit(self_value - rhs_value)
+            match global.format() {
+                OutputFormat::Text => {
+                    for (index, (mprn, stats)) in stats.iter().enumerate() {
+                        if index > 0 {
+                            println!();
+                        }
+                        print_usage_stats(mprn, stats);
+                    }
                 }
+                OutputFormat::Json => {
+                    let stats: serde_json::Map<String, serde_json::Value> = stats
+                        .into_iter()
+                        .map(|(mprn, stats)| Ok((mprn, serde_json::to_value(stats)?)))
+                        .collect::<Result<_>>()?;
+                    println!("{}", serde_json::to_string_pretty(&stats)?);
+                }
+                _ => bail!("stats prints text or json"),
+            }
+            Ok(())
+        }
+        Some(Command::Anonymize {
+            input,
+            output,
+            shift_days,
+        }) => {
+            let data = anonymize_readings(read_csv(input)?, *shift_days);
+            write_hdf_csv(&data, File::create(output)?)?;
+            println!(
+                "{} anonymized readings written to {}",
+                data.len(),
+                output.display()
+            );
+            Ok(())
+        }
+        Some(Command::Export { input, output }) => {
+            let format = global.format.unwrap_or(OutputFormat::Csv);
+            if !matches!(format, OutputFormat::Csv | OutputFormat::Parquet) {
+                bail!("export writes csv or parquet");
+            }
+            let data = global.load_readings(&input.inputs, !input.no_cache)?;
+            let file = File::create(output)?;
+            match format {
+                OutputFormat::Parquet => write_readings_parquet(&data, file)?,
+                _ => write_readings_csv(&data, file)?,
+            }
+            println!("{} readings written to {}", data.len(), output.display());
+            Ok(())
+        }
+        Some(Command::Import { input }) => {
+            if input.inputs.is_empty() {
+                bail!("import needs the HDF files to add to the store");
+            }
+            let store = global
+                .store
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("readings"));
+            let data = filter_by_date(
+                load_inputs(
+                    &input.inputs,
+                    !input.no_cache,
+                    global.expect_mprn.as_deref(),
+                )?,
+                global.from,
+                global.to,
+            );
+            let summary = ReadingStore::open(&store)?.import(data)?;
+            println!(
+                "{} readings added and {} replaced in {}",
+                summary.added,
+                summary.replaced,
+                store.display()
+            );
+            Ok(())
+        }
+        Some(Command::Fetch { mprn, output }) => {
+            let (username, password) = esb_login()?;
+            let hdf = fetch_hdf_file(&username, &password, mprn)?;
+            let output = output
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(format!("HDF_{mprn}.csv")));
+            std::fs::write(&output, hdf)?;
+            println!("readings of {mprn} written to {}", output.display());
+            Ok(())
+        }
+        Some(Command::FetchPrices { output, cache }) => {
+            let (from, to) = date_range(global, "fetch-prices")?;
+            let Some(token) = std::env::var("ENTSOE_TOKEN").ok().filter(|t| !t.is_empty()) else {
+                bail!("set ENTSOE_TOKEN to an ENTSO-E transparency platform api token");
+            };
+            let today = chrono::Utc::now().date_naive();
+            let prices = fetch_day_ahead_prices(&token, from, to, cache, today)?;
+            write_price_file(&prices, File::create(output)?)?;
+            println!("{} prices written to {}", prices.len(), output.display());
+            Ok(())
+        }
+        Some(Command::FetchCarbon { output }) => {
+            let (from, to) = date_range(global, "fetch-carbon")?;
+            let points = fetch_carbon_intensity(from, to)?;
+            write_intensity_file(&points, File::create(output)?)?;
+            println!(
+                "{} carbon intensities written to {}",
+                points.len(),
+                output.display()
+            );
+            Ok(())
+        }
+        Some(Command::FetchWind { output }) => {
+            let (from, to) = date_range(global, "fetch-wind")?;
+            let points = fetch_wind_share(from, to)?;
+            write_conditions_file(&points, File::create(output)?)?;
+            println!(
+                "{} wind shares written to {}",
+                points.len(),
+                output.display()
+            );
+            Ok(())
+        }
+        Some(Command::Plans {
+            command: PlansCommand::Update { url, checksum_url },
+        }) => {
+            let update = update_registry(url, checksum_url.as_deref(), PLAN_REGISTRY.as_ref())?;
+            println!(
+                "{} plans written to {PLAN_REGISTRY}, sha256 {}",
+                update.plans, update.sha256
+            );
+            Ok(())
+        }
+        Some(Command::Rerun { result }) => rerun(result),
+        Some(Command::Diff { before, after }) => {
+            let diff = diff_results(&load_saved_results(before)?, &load_saved_results(after)?);
+            match global.format() {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+                _ => print_results_diff(&diff),
+            }
+            Ok(())
+        }
+        Some(Command::Reconcile {
+            input,
+            bill,
+            tariffs,
+        }) => reconcile(
+            &global.load_readings(&input.inputs, !input.no_cache)?,
+            bill,
+            tariffs.as_deref(),
+        ),
+        Some(Command::Serve {
+            address,
+            tariffs,
+            no_registry,
+            notify,
+        }) => {
+            let plans = load_plans(tariffs.as_deref(), *no_registry, &[])?.plans;
+            let mut server = ComparisonServer::new(plans);
+            if let Some(notify) = notify {
+                server = server.with_notifier(Notifier::load(notify)?);
             }
+            eprintln!("comparing plans on http://{address}");
+            server.serve(address)
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct SmartMeterData {
-    //format:
-    // MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
-    // 10308375697,34996871,0,Active Export Interval (kW),08-01-2024 03:30
-    #[serde(rename = "MPRN")]
-    mprn: String,
-    #[serde(rename = "Meter Serial Number")]
-    meter_serial_number: String,
-    #[serde(rename = "Read Value")]
-    read_value: f32,
-    #[serde(rename = "Read Type")]
-    read_type: SmartMeterDataType,
-    #[serde(
-        rename = "Read Date and End Time",
-        deserialize_with = "smart_meter_datetime_desserialize"
-    )]
-    read_data_and_end_time: NaiveDateTime,
+// The downloads need both ends of the range
+fn date_range(global: &GlobalArgs, command: &str) -> Result<(NaiveDate, NaiveDate)> {
+    match (global.from, global.to) {
+        (Some(from), Some(to)) => Ok((from, to)),
+        _ => bail!("{command} needs the days to download with --from and --to"),
+    }
 }
 
-fn main() -> Result<()> {
-    const FILENAME: &str = "data/HDF_10308375697_09-01-2024.csv";
-    let f = File::open(FILENAME)?;
-    let reader = BufReader::new(f);
-    // Build the CSV reader and iterate over each record.
-    let mut rdr = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .from_reader(reader);
-    let data: Vec<SmartMeterData> = rdr.deserialize().flat_map(|x| x).collect();
-
-    let plans: Vec<Box<dyn PricePlanStrategy>> = vec![
-        Box::new(ElectricIrelandHomeElectric14),
-        Box::new(SSEAirtricity20),
-        Box::new(BordGaisEnergy25WeekendFree),
+// The arguments of the run, those of the result for a rerun
+static RUN_ARGS: OnceLock<Vec<String>> = OnceLock::new();
+
+fn run_args() -> Vec<String> {
+    RUN_ARGS
+        .get()
+        .cloned()
+        .unwrap_or_else(|| std::env::args().skip(1).collect())
+}
+
+// The readings and every other file the comparison reads, with what was in them
+fn input_snapshot(global: &GlobalArgs, cli: &CompareArgs) -> Result<InputSnapshot> {
+    let mut files = match &global.store {
+        Some(store) => FileSnapshot::of_directory(store)?,
+        None => expand_inputs(&cli.inputs)?
+            .iter()
+            .map(|path| FileSnapshot::of(path))
+            .collect::<Result<_>>()?,
+    };
+    let optional = [
+        &cli.tariffs,
+        &cli.holidays,
+        &cli.pso,
+        &cli.credits,
+        &cli.manual_reads,
+        &cli.billed_kwh,
+        &cli.solar_generation,
+        &cli.usage_growth,
+        &cli.carbon_intensity,
+        &cli.gas,
     ];
-    for plan in plans {
-        let total = plan.compute_total_bill_for_period(&data)
-            + plan.standing_charge_per_number_of_days(300);
-        println!("{plan:?}: {total:?}");
+    for path in optional
+        .into_iter()
+        .flatten()
+        .chain(cli.circuit.iter().map(|(_, path)| path))
+    {
+        files.push(FileSnapshot::of(path)?);
+    }
+    let registry = Path::new(PLAN_REGISTRY);
+    Ok(InputSnapshot {
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        args: run_args(),
+        directory: std::env::current_dir()?,
+        from: global.from,
+        to: global.to,
+        registry: (!cli.no_registry && registry.exists())
+            .then(|| FileSnapshot::of(registry))
+            .transpose()?,
+        files,
+    })
+}
+
+// Compares again with the arguments of a saved comparison, in the directory it was made in
+// when it is still there, and checks the totals against it
+fn rerun(result: &Path) -> Result<()> {
+    let snapshot = load_snapshot(result)?;
+    let saved = load_saved_results(result)?;
+    if snapshot.directory.is_dir() {
+        std::env::set_current_dir(&snapshot.directory)?;
+    } else {
+        eprintln!(
+            "warning: {} is gone, the inputs are taken from here",
+            snapshot.directory.display()
+        );
+    }
+    for change in snapshot.changes() {
+        eprintln!("warning: {change} since the result was made");
+    }
+    let mut cli = Cli::try_parse_from(
+        std::iter::once("energy_comparator".to_string()).chain(snapshot.args.iter().cloned()),
+    )?;
+    let args = match &mut cli.command {
+        None => &mut cli.compare,
+        Some(Command::Compare(args)) => args,
+        Some(Command::Simulate(args)) => &mut args.compare,
+        Some(_) => bail!("{} is not the result of a comparison", result.display()),
+    };
+    if args.watch || args.schedule.is_some() {
+        bail!("comparisons made with --watch or --schedule can't be rerun");
+    }
+    // printed again rather than written over the files of the first run
+    args.report_to = None;
+    args.output = None;
+    let global = GlobalArgs {
+        format: Some(OutputFormat::Json),
+        ..cli.global.clone()
+    };
+    if let Some(locale) = global.locale {
+        locale.install()?;
+    }
+    RUN_ARGS.set(snapshot.args.clone()).ok();
+    install_options(args)?;
+    let reports = compare(&global, args)?;
+    let rerun = reports
+        .iter()
+        .map(|report| Ok(serde_json::from_value(serde_json::to_value(report)?)?))
+        .collect::<Result<Vec<SavedReport>>>()?;
+    let diff = diff_results(&saved, &rerun);
+    let same = diff.only_before.is_empty()
+        && diff.only_after.is_empty()
+        && diff.meter_points.iter().all(|m| m.is_unchanged());
+    if same {
+        eprintln!("every total came out the same as in {}", result.display());
+    } else {
+        eprintln!(
+            "warning: the totals came out different from {}, save this output and compare them with diff",
+            result.display()
+        );
+    }
+    Ok(())
+}
+
+// Compares the readings after setting up what every plan is priced with
+fn run(global: &GlobalArgs, cli: &CompareArgs) -> Result<()> {
+    install_options(cli)?;
+    if let Some(address) = cli.metrics.clone() {
+        if cli.schedule.is_none() && !cli.watch {
+            bail!("--metrics is for the instances that keep running, with --watch or --schedule");
+        }
+        eprintln!("metrics on http://{address}/metrics");
+        std::thread::spawn(move || {
+            if let Err(e) = serve_metrics(&address) {
+                eprintln!("warning: could not serve the metrics: {e}");
+            }
+        });
+    }
+    if let Some(schedule) = &cli.schedule {
+        return scheduled(global, cli, schedule);
+    }
+    if cli.watch {
+        return watch(global, cli);
+    }
+    compare(global, cli).map(|_| ())
+}
+
+fn esb_login() -> Result<(String, String)> {
+    let credential = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    let (Some(username), Some(password)) = (credential("ESB_USERNAME"), credential("ESB_PASSWORD"))
+    else {
+        bail!("set ESB_USERNAME and ESB_PASSWORD to the login of the ESB Networks portal");
+    };
+    Ok((username, password))
+}
+
+// Compares on the schedule, downloading the readings and the registry first. Whatever fails
+// to download is compared from what is already on disk, the next run tries again
+fn scheduled(global: &GlobalArgs, cli: &CompareArgs, schedule: &CronSchedule) -> Result<()> {
+    report_format(global)?;
+    let downloads = match cli.refresh_mprn.is_empty() {
+        true => None,
+        false => match cli.inputs.iter().find(|input| Path::new(input).is_dir()) {
+            Some(directory) => Some(PathBuf::from(directory)),
+            None => bail!("--refresh-mprn downloads into a directory, give one as an input"),
+        },
+    };
+    let next = |after: NaiveDateTime| {
+        schedule
+            .next_after(after)
+            .with_context(|| format!("the schedule {schedule} never comes round"))
+    };
+    next(chrono::Local::now().naive_local())?;
+    loop {
+        if let Some(directory) = &downloads {
+            for mprn in &cli.refresh_mprn {
+                let hdf = esb_login()
+                    .and_then(|(username, password)| fetch_hdf_file(&username, &password, mprn));
+                let written = hdf.and_then(|hdf| {
+                    replace_file(&directory.join(format!("HDF_{mprn}.csv")), hdf.as_bytes())
+                });
+                if let Err(e) = written {
+                    eprintln!("warning: could not download the readings of {mprn}: {e}");
+                }
+            }
+        }
+        if let Some(url) = &cli.refresh_registry {
+            if let Err(e) = update_registry(url, None, PLAN_REGISTRY.as_ref()) {
+                eprintln!("warning: could not update the registry: {e}");
+            }
+        }
+        match compare(global, cli) {
+            Ok(_) => eprintln!("report written to {}", display_report_to(cli)),
+            Err(e) => eprintln!("Error: {e}"),
+        }
+        let next = next(chrono::Local::now().naive_local())?;
+        eprintln!("comparing again at {next}");
+        // short sleeps so a clock change or a suspended machine doesn't push it back
+        while chrono::Local::now().naive_local() < next {
+            std::thread::sleep(SCHEDULE_INTERVAL);
+        }
+    }
+}
+
+fn display_report_to(cli: &CompareArgs) -> String {
+    cli.report_to
+        .as_deref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default()
+}
+
+// Writes next to the file and moves it over the old one
+fn replace_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let partial = PathBuf::from(format!("{}.partial", path.display()));
+    std::fs::write(&partial, contents)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+// What --report-to writes, html unless asked for markdown or json
+fn report_format(global: &GlobalArgs) -> Result<OutputFormat> {
+    match global.format.unwrap_or(OutputFormat::Html) {
+        format @ (OutputFormat::Html | OutputFormat::Markdown | OutputFormat::Json) => Ok(format),
+        _ => bail!("--report-to writes html, markdown or json"),
+    }
+}
+
+// What the inputs are made of, a new download or a changed file changes it
+fn input_state(inputs: &[String]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut state: Vec<_> = expand_inputs(inputs)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect();
+    state.sort();
+    state
+}
+
+// Compares again every time a file is added to or changed in the inputs. Files that didn't
+// change come from their cache, so only the new download is parsed
+fn watch(global: &GlobalArgs, cli: &CompareArgs) -> Result<()> {
+    let notifier = cli.notify.as_deref().map(Notifier::load).transpose()?;
+    let mut cheapest = CheapestTracker::default();
+    // new rates in the tariff file or the registry can change the cheapest plan too
+    let mut watched = cli.inputs.clone();
+    if let Some(tariffs) = &cli.tariffs {
+        watched.push(tariffs.display().to_string());
+    }
+    if !cli.no_registry && Path::new(PLAN_REGISTRY).exists() {
+        watched.push(PLAN_REGISTRY.to_string());
+    }
+    let mut state = input_state(&watched);
+    loop {
+        // a file still being downloaded can fail, the next change tries again
+        match compare(global, cli) {
+            Ok(reports) => notify_cheapest(notifier.as_ref(), &mut cheapest, &reports),
+            Err(e) => eprintln!("Error: {e}"),
+        }
+        eprintln!("watching {} for new readings", watched.join(" "));
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let current = input_state(&watched);
+            if current != state {
+                state = current;
+                break;
+            }
+        }
+        println!();
+    }
+}
+
+// Tells the notifier about every meter point whose cheapest plan changed, failing to send
+// only costs the notification
+fn notify_cheapest(
+    notifier: Option<&Notifier>,
+    cheapest: &mut CheapestTracker,
+    reports: &[ComparisonReport],
+) {
+    let Some(notifier) = notifier else {
+        return;
+    };
+    for change in cheapest.update(reports) {
+        eprintln!("{}", change.subject());
+        if let Err(e) = notifier.send(&change) {
+            eprintln!("warning: could not send the notification: {e}");
+        }
     }
+}
 
+fn reconcile(data: &[SmartMeterData], bill: &Path, tariffs: Option<&Path>) -> Result<()> {
+    let bill = load_supplier_bill(bill)?;
+    let mut plans = builtin_plans();
+    if let Some(tariffs) = tariffs {
+        plans.extend(tariff_plans(&mut load_plan_file(tariffs)?));
+    }
+    let Some(plan) = plans.iter().find(|plan| plan.is_called(&bill.plan)) else {
+        bail!(
+            "the bill is on {}, which isn't a built in plan or in the tariff file",
+            bill.plan
+        );
+    };
+    let reconciliation = reconcile_bill(plan.as_ref(), &bill, data)?;
+    if let Some(warning) = &reconciliation.warning {
+        eprintln!("warning: {warning}");
+    }
+    let period = &reconciliation.period;
+    println!("Meter point: {}", reconciliation.mprn);
+    println!(
+        "Bill of {} to {} ({} days) on {}, readings on {} of the days",
+        period.start,
+        period.end,
+        period.number_of_days(),
+        reconciliation.plan,
+        reconciliation.days_with_readings
+    );
+    let kwh = |kwh: Option<f32>| {
+        kwh.map(|kwh| format!(" ({kwh:.2} kWh)"))
+            .unwrap_or_default()
+    };
+    for line in &reconciliation.lines {
+        print!(
+            "  {}: computed {}{}",
+            line.line,
+            line.computed,
+            kwh(line.computed_kwh)
+        );
+        match (line.billed, line.difference()) {
+            (Some(billed), Some(difference)) => println!(
+                ", billed {billed}{}, difference {difference}",
+                kwh(line.billed_kwh)
+            ),
+            _ => println!(", not on the bill"),
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_an_mprn_is_fetched() {
+        assert!(Cli::try_parse_from(["compare", "fetch", "--mprn", "10308375697"]).is_ok());
+        // it names the file the readings are written to
+        assert!(Cli::try_parse_from(["compare", "fetch", "--mprn", "../10308375697"]).is_err());
+    }
+
+    #[test]
+    fn only_an_mprn_is_refreshed() {
+        let refresh = |mprn| {
+            Cli::try_parse_from([
+                "compare",
+                "data",
+                "--schedule",
+                "0 6 * * *",
+                "--report-to",
+                "report.html",
+                "--refresh-mprn",
+                mprn,
+            ])
+        };
+        assert!(refresh("10308375697").is_ok());
+        // it names the file written in the input directory
+        assert!(refresh("../../x").is_err());
+    }
+
+    #[test]
+    fn every_command_takes_the_global_options() {
+        let cli =
+            Cli::try_parse_from(["compare", "report", "HDF.csv", "--from", "2024-01-01"]).unwrap();
+        let Some(Command::Report(report)) = cli.command else {
+            panic!("{:?} isn't a report", cli.command);
+        };
+        assert_eq!(report.inputs, ["HDF.csv"]);
+        assert_eq!(cli.global.from, NaiveDate::from_ymd_opt(2024, 1, 1));
+
+        let cli = Cli::try_parse_from(["compare", "HDF.csv", "--format", "markdown"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.compare.inputs, ["HDF.csv"]);
+        assert!(matches!(cli.global.format, Some(OutputFormat::Markdown)));
+
+        // a simulation needs a change to price
+        assert!(Cli::try_parse_from(["compare", "simulate", "HDF.csv"]).is_err());
+        let cli =
+            Cli::try_parse_from(["compare", "simulate", "HDF.csv", "--battery-kwh", "5"]).unwrap();
+        let Some(Command::Simulate(simulate)) = cli.command else {
+            panic!("{:?} isn't a simulation", cli.command);
+        };
+        assert_eq!(simulate.compare.battery_kwh, Some(5.0));
+    }
+
+    #[test]
+    fn the_commands_without_plans_take_the_same_inputs() {
+        let input = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).unwrap();
+            match cli.command {
+                Some(
+                    Command::Validate { input, .. }
+                    | Command::Stats { input }
+                    | Command::Export { input, .. }
+                    | Command::Import { input }
+                    | Command::Reconcile { input, .. },
+                ) => input,
+                command => panic!("{command:?} doesn't take inputs"),
+            }
+        };
+        let stats = input(&["compare", "stats", "HDF.csv", "--no-cache"]);
+        assert_eq!(stats.inputs, ["HDF.csv"]);
+        assert!(stats.no_cache);
+        let reconcile = input(&[
+            "compare",
+            "reconcile",
+            "--bill",
+            "bill.toml",
+            "--store",
+            "readings",
+        ]);
+        assert!(reconcile.inputs.is_empty());
+        let import = input(&["compare", "import", "HDF.csv", "--store", "readings"]);
+        assert_eq!(import.inputs, ["HDF.csv"]);
+        assert!(Cli::try_parse_from(["compare", "export", "--output", "out.csv"]).is_err());
+    }
+
+    #[test]
+    fn a_new_download_in_a_watched_directory_changes_the_inputs() {
+        let dir = std::env::temp_dir().join(format!("watched-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("HDF.csv"), "MPRN\n").unwrap();
+        // files that aren't readings are left out
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        let watched = vec![dir.display().to_string()];
+        let before = input_state(&watched);
+        let unchanged = input_state(&watched);
+        std::fs::write(dir.join("HDF-2.csv"), "MPRN\n").unwrap();
+        let after = input_state(&watched);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(before.len(), 1);
+        assert_eq!(before, unchanged);
+        assert_ne!(after, before);
+        assert_eq!(after.len(), 2);
+    }
+}
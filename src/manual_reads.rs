@@ -0,0 +1,139 @@
+use anyhow::{bail, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    eab::profile_day, read_csv, ConsumptionProfile, ReadUnit, SmartMeterData, SmartMeterDataType,
+};
+
+// The register of the meter read by hand before it was smart, off the display or a bill. A
+// csv of date,kwh rows like 2023-11-01,12345.6, the reading at the start of the day
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ManualReading {
+    pub date: NaiveDate,
+    pub kwh: f32,
+}
+
+// The days between two manual readings that were made into half hours, the first and last
+// of them and the kWh they add up to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct EstimatedPeriod {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub kwh: f32,
+}
+
+pub fn load_manual_reads(filename: &Path) -> Result<Vec<ManualReading>> {
+    let mut reads: Vec<ManualReading> = read_csv(filename)?;
+    reads.sort_by_key(|read| read.date);
+    if reads.len() < 2 {
+        bail!(
+            "{} needs two manual readings at least to tell what was used between them",
+            filename.display()
+        );
+    }
+    for pair in reads.windows(2) {
+        if pair[0].date == pair[1].date {
+            bail!(
+                "{} has two readings on {}",
+                filename.display(),
+                pair[0].date
+            );
+        }
+        if pair[1].kwh < pair[0].kwh {
+            bail!(
+                "the register of {} goes back from {} on {} to {} on {}",
+                filename.display(),
+                pair[0].kwh,
+                pair[0].date,
+                pair[1].kwh,
+                pair[1].date
+            );
+        }
+    }
+    Ok(reads)
+}
+
+// Half hour imports of the meter point for the days between the manual readings, what the
+// register went up by spread evenly over the days and over each day like the CRU standard
+// profile. The readings stop before until, the first smart reading, so the kWh of a period
+// running into the smart readings only counts the days before them
+pub fn estimate_from_manual_reads(
+    reads: &[ManualReading],
+    mprn: Arc<str>,
+    meter_serial_number: Arc<str>,
+    until: Option<NaiveDateTime>,
+) -> (Vec<SmartMeterData>, Vec<EstimatedPeriod>) {
+    let mut readings = vec![];
+    let mut periods = vec![];
+    for pair in reads.windows(2) {
+        let days = (pair[1].date - pair[0].date).num_days();
+        let daily_kwh = (pair[1].kwh - pair[0].kwh) / days as f32;
+        let estimated: Vec<SmartMeterData> = pair[0]
+            .date
+            .iter_days()
+            .take(days as usize)
+            .flat_map(|date| profile_day(&ConsumptionProfile::CRU_STANDARD, date, daily_kwh))
+            .filter(|(end, _)| until.is_none_or(|until| *end < until))
+            .map(|(end, kwh)| SmartMeterData {
+                mprn: Arc::clone(&mprn),
+                meter_serial_number: Arc::clone(&meter_serial_number),
+                read_value: kwh,
+                read_type: SmartMeterDataType::ActiveImport,
+                unit: ReadUnit::KilowattHours,
+                read_data_and_end_time: end,
+                interval_minutes: 30,
+            })
+            .collect();
+        let (Some(first), Some(last)) = (estimated.first(), estimated.last()) else {
+            continue;
+        };
+        periods.push(EstimatedPeriod {
+            from: first.date(),
+            to: last.date(),
+            kwh: estimated.iter().map(|d| d.read_value).sum(),
+        });
+        readings.extend(estimated);
+    }
+    (readings, periods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn the_register_is_spread_up_to_the_smart_readings() {
+        let date = |day: u32| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let reads = [
+            ManualReading {
+                date: date(1),
+                kwh: 1000.0,
+            },
+            ManualReading {
+                date: date(11),
+                kwh: 1100.0,
+            },
+        ];
+        let smart = reading_at("08-01-2024 00:30", 0.5);
+        let (readings, periods) = estimate_from_manual_reads(
+            &reads,
+            Arc::clone(&smart.mprn),
+            Arc::clone(&smart.meter_serial_number),
+            Some(smart.read_data_and_end_time),
+        );
+        // seven days of 10 kWh before the first smart reading
+        assert_eq!(readings.len(), 7 * 48);
+        assert_eq!(periods.len(), 1);
+        assert_eq!(periods[0].from, date(1));
+        assert!((periods[0].kwh - 70.0).abs() < 0.01, "{periods:?}");
+        // the half hour to 18:00 has a share of the peak, more than one of the day
+        assert_eq!(
+            readings[35].local_end_time().format("%H:%M").to_string(),
+            "18:00"
+        );
+        assert!(readings[35].read_value > readings[23].read_value);
+    }
+}
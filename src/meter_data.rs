@@ -0,0 +1,1050 @@
+use anyhow::Result;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    fs::File,
+    io::{BufReader, BufWriter, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bincode::Options;
+
+use crate::{
+    detect_source, usage_cache::Fnv, Error, Metrics, ParseMode, Progress, RowChecker, RowProblem,
+};
+
+// How the datetime errors start, so the skipped rows can be counted as bad dates
+const BAD_DATE: &str = "bad date";
+
+pub(crate) fn smart_meter_datetime_desserialize<'de, D>(
+    deserializer: D,
+) -> Result<NaiveDateTime, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct CustomVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for CustomVisitor {
+        type Value = NaiveDateTime;
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a datetime in the format %d-%m-%Y %H:%M")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            NaiveDateTime::parse_from_str(value, "%d-%m-%Y %H:%M")
+                .map_err(|e| E::custom(format!("{BAD_DATE} {value:?}, {e}")))
+        }
+    }
+
+    deserializer.deserialize_str(CustomVisitor)
+}
+
+// Writes the datetime back in the same format it is read, so cached data round trips
+pub(crate) fn smart_meter_datetime_serialize<S>(
+    datetime: &NaiveDateTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&datetime.format("%d-%m-%Y %H:%M").to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SmartMeterDataType {
+    ActiveImport,
+    ActiveExport,
+}
+
+// What the read value is, older exports give the average power over the interval and newer
+// ones can give the energy of the interval itself
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ReadUnit {
+    #[default]
+    Kilowatts,
+    KilowattHours,
+}
+
+// The Read Type column, the direction and the unit of the read value in one
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+enum ReadType {
+    #[serde(rename = "Active Import Interval (kW)")]
+    ImportKw,
+    #[serde(rename = "Active Export Interval (kW)")]
+    ExportKw,
+    #[serde(rename = "Active Import Interval (kWh)")]
+    ImportKwh,
+    #[serde(rename = "Active Export Interval (kWh)")]
+    ExportKwh,
+}
+
+impl ReadType {
+    fn split(self) -> (SmartMeterDataType, ReadUnit) {
+        match self {
+            ReadType::ImportKw => (SmartMeterDataType::ActiveImport, ReadUnit::Kilowatts),
+            ReadType::ExportKw => (SmartMeterDataType::ActiveExport, ReadUnit::Kilowatts),
+            ReadType::ImportKwh => (SmartMeterDataType::ActiveImport, ReadUnit::KilowattHours),
+            ReadType::ExportKwh => (SmartMeterDataType::ActiveExport, ReadUnit::KilowattHours),
+        }
+    }
+
+    fn join(read_type: SmartMeterDataType, unit: ReadUnit) -> ReadType {
+        match (read_type, unit) {
+            (SmartMeterDataType::ActiveImport, ReadUnit::Kilowatts) => ReadType::ImportKw,
+            (SmartMeterDataType::ActiveExport, ReadUnit::Kilowatts) => ReadType::ExportKw,
+            (SmartMeterDataType::ActiveImport, ReadUnit::KilowattHours) => ReadType::ImportKwh,
+            (SmartMeterDataType::ActiveExport, ReadUnit::KilowattHours) => ReadType::ExportKwh,
+        }
+    }
+}
+
+// A row of the HDF export as it is in the file, SmartMeterData is read and written through it
+#[derive(Deserialize, Serialize)]
+struct HdfRow {
+    #[serde(rename = "MPRN")]
+    mprn: Arc<str>,
+    #[serde(rename = "Meter Serial Number")]
+    meter_serial_number: Arc<str>,
+    #[serde(rename = "Read Value")]
+    read_value: f32,
+    #[serde(rename = "Read Type")]
+    read_type: ReadType,
+    #[serde(
+        rename = "Read Date and End Time",
+        deserialize_with = "smart_meter_datetime_desserialize",
+        serialize_with = "smart_meter_datetime_serialize"
+    )]
+    read_data_and_end_time: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(from = "HdfRow", into = "HdfRow")]
+pub struct SmartMeterData {
+    //format:
+    // MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+    // 10308375697,34996871,0,Active Export Interval (kW),08-01-2024 03:30
+    // newer exports also have Active Import Interval (kWh) rows, see unit
+    // shared between the readings of the same meter, a file only has a handful of them
+    pub mprn: Arc<str>,
+    pub meter_serial_number: Arc<str>,
+    pub read_value: f32,
+    pub read_type: SmartMeterDataType,
+    pub unit: ReadUnit,
+    // in UTC, the HDF export doesn't follow daylight saving time, see local_end_time
+    pub read_data_and_end_time: NaiveDateTime,
+    // not in the file, worked out from the spacing of the readings by detect_intervals
+    pub interval_minutes: u32,
+}
+
+impl From<HdfRow> for SmartMeterData {
+    fn from(row: HdfRow) -> SmartMeterData {
+        let (read_type, unit) = row.read_type.split();
+        SmartMeterData {
+            mprn: row.mprn,
+            meter_serial_number: row.meter_serial_number,
+            read_value: row.read_value,
+            read_type,
+            unit,
+            read_data_and_end_time: row.read_data_and_end_time,
+            interval_minutes: default_interval_minutes(),
+        }
+    }
+}
+
+impl From<SmartMeterData> for HdfRow {
+    fn from(datapoint: SmartMeterData) -> HdfRow {
+        HdfRow {
+            mprn: datapoint.mprn,
+            meter_serial_number: datapoint.meter_serial_number,
+            read_value: datapoint.read_value,
+            read_type: ReadType::join(datapoint.read_type, datapoint.unit),
+            read_data_and_end_time: datapoint.read_data_and_end_time,
+        }
+    }
+}
+
+// ESB Networks exports half hourly readings
+fn default_interval_minutes() -> u32 {
+    30
+}
+
+// Tariff bands and billing days follow the clock on the wall in Ireland
+pub const LOCAL_TIMEZONE: Tz = chrono_tz::Europe::Dublin;
+
+impl SmartMeterData {
+    // The end of the interval in Irish local time, in summer 16:30 UTC is 17:30 and already peak.
+    // When the clocks go back two readings share the same local time and both are kept,
+    // when they go forward the skipped hour simply has no readings
+    pub fn local_end_time(&self) -> NaiveDateTime {
+        LOCAL_TIMEZONE
+            .from_utc_datetime(&self.read_data_and_end_time)
+            .naive_local()
+    }
+
+    // The local day the interval belongs to, the reading ending at 00:00 is the last one of the previous day
+    pub fn date(&self) -> NaiveDate {
+        (self.local_end_time() - Duration::seconds(1)).date()
+    }
+
+    // A read value in kW is the average power over the interval, so a 30 minute interval
+    // at 1 kW is 0.5 kWh
+    pub fn energy_kwh(&self) -> f32 {
+        match self.unit {
+            ReadUnit::Kilowatts => self.read_value * self.interval_minutes as f32 / 60.0,
+            ReadUnit::KilowattHours => self.read_value,
+        }
+    }
+}
+
+// A stretch of at least this many equal gaps between readings is the series really being at
+// that interval, a shorter one is readings gone missing
+const SETTLED_GAPS: usize = 4;
+
+// A series that changes interval part way, like a meter set from half hours to quarter hours,
+// its readings are summed up to the longest interval so every one covers the same time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MixedIntervals {
+    pub mprn: Arc<str>,
+    pub read_type: SmartMeterDataType,
+    // the intervals seen, shortest first, the last is what they were summed up to
+    pub intervals: Vec<u32>,
+}
+
+impl Display for MixedIntervals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let direction = match self.read_type {
+            SmartMeterDataType::ActiveImport => "import",
+            SmartMeterDataType::ActiveExport => "export",
+        };
+        let minutes: Vec<String> = self.intervals.iter().map(u32::to_string).collect();
+        write!(
+            f,
+            "the {direction} readings of {} mix {} minute intervals, summed up to {} minutes",
+            self.mprn,
+            minutes.join(" and "),
+            self.intervals.last().copied().unwrap_or_default()
+        )
+    }
+}
+
+// The interval of each reading of a series in time order: the gap before it inside a settled
+// stretch, and the interval of the stretch before it otherwise. Without any settled stretch
+// the whole series is at its most common gap. None for a single reading
+fn reading_intervals(times: &[NaiveDateTime]) -> Option<Vec<u32>> {
+    let gaps: Vec<i64> = times
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).num_minutes())
+        .collect();
+    let mut settled: Vec<Option<u32>> = vec![None; gaps.len()];
+    let mut start = 0;
+    while start < gaps.len() {
+        let end = start
+            + gaps[start..]
+                .iter()
+                .take_while(|&&g| g == gaps[start])
+                .count();
+        if end - start >= SETTLED_GAPS {
+            if let Ok(minutes @ 1..) = u32::try_from(gaps[start]) {
+                settled[start..end].fill(Some(minutes));
+            }
+        }
+        start = end;
+    }
+    let first = match settled.iter().flatten().next() {
+        Some(&minutes) => minutes,
+        None => {
+            let mut counts: HashMap<i64, usize> = HashMap::new();
+            for &gap in gaps.iter().filter(|&&gap| gap > 0) {
+                *counts.entry(gap).or_default() += 1;
+            }
+            return Some(vec![most_common_gap(counts)?; times.len()]);
+        }
+    };
+    let mut current = first;
+    let mut intervals = vec![first];
+    for minutes in settled {
+        current = minutes.unwrap_or(current);
+        intervals.push(current);
+    }
+    Some(intervals)
+}
+
+// The readings of a series summed up into intervals of minutes counted from midnight UTC,
+// a reading over two of them is shared by how long it was in each
+fn resample(series: &[SmartMeterData], minutes: u32) -> Vec<SmartMeterData> {
+    let length = Duration::minutes(minutes as i64);
+    let mut intervals: BTreeMap<NaiveDateTime, (f32, &SmartMeterData)> = BTreeMap::new();
+    for datapoint in series {
+        let end = datapoint.read_data_and_end_time;
+        let start = end - Duration::minutes(datapoint.interval_minutes as i64);
+        let covered = (end - start).num_seconds() as f32;
+        let midnight = start.date().and_time(NaiveTime::MIN);
+        let offset = (start - midnight).num_minutes() / minutes as i64 * minutes as i64;
+        let mut interval_start = midnight + Duration::minutes(offset);
+        while interval_start < end {
+            let interval_end = interval_start + length;
+            let overlap = (interval_end.min(end) - interval_start.max(start)).num_seconds() as f32;
+            let interval = intervals.entry(interval_end).or_insert((0.0, datapoint));
+            interval.0 += datapoint.energy_kwh() * overlap / covered.max(1.0);
+            interval.1 = datapoint;
+            interval_start = interval_end;
+        }
+    }
+    intervals
+        .into_iter()
+        .map(|(end, (kwh, datapoint))| SmartMeterData {
+            read_value: kwh,
+            unit: ReadUnit::KilowattHours,
+            read_data_and_end_time: end,
+            interval_minutes: minutes,
+            ..datapoint.clone()
+        })
+        .collect()
+}
+
+// Sets the interval length of every reading from the spacing of the readings of the same
+// meter and direction, so a missing reading doesn't make its neighbour look twice as long. A
+// series with a single reading keeps the half hour default, a series that changes interval
+// is summed up to its longest one and returned
+pub fn detect_intervals(data: &mut Vec<SmartMeterData>) -> Vec<MixedIntervals> {
+    let mut series: HashMap<(Arc<str>, SmartMeterDataType), Vec<usize>> = HashMap::new();
+    for (index, datapoint) in data.iter().enumerate() {
+        series
+            .entry((datapoint.mprn.clone(), datapoint.read_type))
+            .or_default()
+            .push(index);
+    }
+
+    let mut mixed = vec![];
+    for ((mprn, read_type), mut indexes) in series {
+        indexes.sort_by_key(|&index| data[index].read_data_and_end_time);
+        let times: Vec<NaiveDateTime> = indexes
+            .iter()
+            .map(|&index| data[index].read_data_and_end_time)
+            .collect();
+        let Some(intervals) = reading_intervals(&times) else {
+            continue;
+        };
+        for (&index, &minutes) in indexes.iter().zip(&intervals) {
+            data[index].interval_minutes = minutes;
+        }
+        let seen: BTreeSet<u32> = intervals.into_iter().collect();
+        if seen.len() > 1 {
+            mixed.push(MixedIntervals {
+                mprn,
+                read_type,
+                intervals: seen.into_iter().collect(),
+            });
+        }
+    }
+
+    if !mixed.is_empty() {
+        let (changed, mut kept): (Vec<SmartMeterData>, Vec<SmartMeterData>) =
+            std::mem::take(data).into_iter().partition(|datapoint| {
+                mixed
+                    .iter()
+                    .any(|m| m.mprn == datapoint.mprn && m.read_type == datapoint.read_type)
+            });
+        for series in &mixed {
+            // a replaced meter and its replacement are summed up apart, see resolve_meter_swaps
+            let mut meters: BTreeMap<Arc<str>, Vec<SmartMeterData>> = BTreeMap::new();
+            for datapoint in changed
+                .iter()
+                .filter(|d| d.mprn == series.mprn && d.read_type == series.read_type)
+            {
+                meters
+                    .entry(datapoint.meter_serial_number.clone())
+                    .or_default()
+                    .push(datapoint.clone());
+            }
+            let minutes = series.intervals.last().copied().unwrap_or(30);
+            for readings in meters.values() {
+                kept.extend(resample(readings, minutes));
+            }
+        }
+        *data = kept;
+        mixed.sort_by(|a, b| (&a.mprn, a.read_type).cmp(&(&b.mprn, b.read_type)));
+    }
+    mixed
+}
+
+// The interval of a series from how often each gap in minutes was seen, ties go to the shorter
+// interval so the result doesn't depend on the hash order
+pub(crate) fn most_common_gap(gaps: HashMap<i64, usize>) -> Option<u32> {
+    let (minutes, _) = gaps
+        .into_iter()
+        .max_by_key(|&(minutes, count)| (count, std::cmp::Reverse(minutes)))?;
+    u32::try_from(minutes).ok()
+}
+
+// Keeps the readings between from and to, both inclusive
+pub fn filter_by_date(
+    data: Vec<SmartMeterData>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Vec<SmartMeterData> {
+    data.into_iter()
+        .filter(|d| from.is_none_or(|from| d.date() >= from))
+        .filter(|d| to.is_none_or(|to| d.date() <= to))
+        .collect()
+}
+
+// HDF downloads are cumulative, so two downloads overlap. A reading is identified by its meter,
+// direction and end time and later downloads replace the readings of earlier ones
+pub fn merge_readings(downloads: Vec<Vec<SmartMeterData>>) -> Vec<SmartMeterData> {
+    let mut merged: Vec<SmartMeterData> = vec![];
+    let mut positions: HashMap<(Arc<str>, Arc<str>, SmartMeterDataType, NaiveDateTime), usize> =
+        HashMap::new();
+    for datapoint in downloads.into_iter().flatten() {
+        let key = (
+            datapoint.mprn.clone(),
+            datapoint.meter_serial_number.clone(),
+            datapoint.read_type,
+            datapoint.read_data_and_end_time,
+        );
+        match positions.get(&key) {
+            Some(&position) => merged[position] = datapoint,
+            None => {
+                positions.insert(key, merged.len());
+                merged.push(datapoint);
+            }
+        }
+    }
+    merged
+}
+
+// One entry per meter point, a household with a second property gets a comparison for each
+pub fn group_by_mprn(data: Vec<SmartMeterData>) -> BTreeMap<Arc<str>, Vec<SmartMeterData>> {
+    let mut groups: BTreeMap<Arc<str>, Vec<SmartMeterData>> = BTreeMap::new();
+    for datapoint in data {
+        groups
+            .entry(datapoint.mprn.clone())
+            .or_default()
+            .push(datapoint);
+    }
+    groups
+}
+
+// The row as it was in the file, for the quarantine file
+pub(crate) fn raw_row<'a>(fields: impl Iterator<Item = &'a [u8]>) -> String {
+    fields
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// The problem of a row serde couldn't read, from what went wrong and the column it was in
+fn row_problem(error: &csv::Error, column: Option<&str>) -> RowProblem {
+    use csv::DeserializeErrorKind as Kind;
+    match error.kind() {
+        csv::ErrorKind::UnequalLengths { .. } => RowProblem::MissingField,
+        csv::ErrorKind::Deserialize { err, .. } => match err.kind() {
+            Kind::UnexpectedEndOfRow => RowProblem::MissingField,
+            Kind::ParseFloat(_) | Kind::ParseInt(_) => RowProblem::NotANumber,
+            Kind::Message(message) if message.starts_with(BAD_DATE) => RowProblem::BadDate,
+            Kind::Message(message) if message.starts_with("unknown variant") => {
+                RowProblem::UnknownType
+            }
+            _ if column.is_some_and(|c| c.contains("Date") || c.contains("Time")) => {
+                RowProblem::BadDate
+            }
+            _ => RowProblem::Unreadable,
+        },
+        _ => RowProblem::Unreadable,
+    }
+}
+
+// Rows that can't be read are skipped with a warning or stop the reading, see ParseMode
+pub fn read_csv<T: serde::de::DeserializeOwned>(filename: impl AsRef<Path>) -> Result<Vec<T>> {
+    let mut rows = RowChecker::new(filename.as_ref().display().to_string(), ParseMode::global());
+    let mut data = vec![];
+    for row in stream_csv(filename)? {
+        data.extend(rows.check(row)?);
+    }
+    rows.finish();
+    Ok(data)
+}
+
+// The rows of the csv one at a time as they are read, a row that can't be read is an error
+// with its line so the caller can skip it or stop, see RowChecker
+pub fn stream_csv<T: serde::de::DeserializeOwned>(
+    filename: impl AsRef<Path>,
+) -> Result<impl Iterator<Item = Result<T, Error>>> {
+    let file = filename.as_ref().display().to_string();
+    let f = File::open(filename)?;
+    let reader = BufReader::new(f);
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(reader);
+    let headers = rdr.byte_headers()?.clone();
+    Ok(rdr.into_byte_records().map(move |record| {
+        let bad_row = |e: csv::Error, row: String| {
+            let column = match e.kind() {
+                csv::ErrorKind::Deserialize { err, .. } => err
+                    .field()
+                    .and_then(|i| headers.get(i as usize))
+                    .map(String::from_utf8_lossy),
+                _ => None,
+            };
+            let reason = match e.kind() {
+                csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+                _ => e.to_string(),
+            };
+            Error::BadRow {
+                file: file.clone(),
+                line: e.position().map_or(0, |position| position.line()),
+                problem: row_problem(&e, column.as_deref()),
+                reason,
+                row,
+            }
+        };
+        let record = record.map_err(|e| bad_row(e, String::new()))?;
+        let row = || raw_row(record.iter());
+        if record.len() < headers.len() {
+            return Err(Error::BadRow {
+                file: file.clone(),
+                line: record.position().map_or(0, |position| position.line()),
+                problem: RowProblem::MissingField,
+                reason: format!("the row has {} of {} fields", record.len(), headers.len()),
+                row: row(),
+            });
+        }
+        record
+            .deserialize(Some(&headers))
+            .map_err(|e| bad_row(e, row()))
+    }))
+}
+
+// The cache lives next to the csv, e.g. data/HDF.csv.bincode
+fn cache_path(filename: &Path) -> PathBuf {
+    let mut cache = filename.as_os_str().to_owned();
+    cache.push(".bincode");
+    PathBuf::from(cache)
+}
+
+// Bumped whenever the readings are stored differently, older caches are parsed again
+const READINGS_CACHE_VERSION: u32 = 2;
+
+// What identifies the csv the readings of a cache came from, written before the readings
+#[derive(Serialize, Deserialize)]
+struct CacheHeader {
+    version: u32,
+    source_len: u64,
+    source_hash: u64,
+}
+
+// The length and a hash of the contents of a file, to tell whether it changed
+fn fingerprint(filename: &Path) -> std::io::Result<(u64, u64)> {
+    let mut reader = BufReader::new(File::open(filename)?);
+    let mut hasher = Fnv::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut len = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.bytes(&buffer[..read]);
+        len += read as u64;
+    }
+    Ok((len, hasher.finish()))
+}
+
+// The readings of the cache when it was written after the last change to the csv, or when the
+// csv was only touched since, like by a copy or a checkout, and still has the same contents
+fn cached_readings(filename: &Path, cache: &Path) -> Result<Option<Vec<SmartMeterData>>> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified());
+    let (Ok(source), Ok(cached)) = (modified(filename), modified(cache)) else {
+        return Ok(None);
+    };
+    // limited to the size of the file, a cache of an older version can't ask for more
+    let options = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(std::fs::metadata(cache)?.len());
+    let mut reader = BufReader::new(File::open(cache)?);
+    let header: CacheHeader = options.deserialize_from(&mut reader)?;
+    if header.version != READINGS_CACHE_VERSION {
+        return Ok(None);
+    }
+    let fresh = cached >= source && std::fs::metadata(filename)?.len() == header.source_len;
+    if !fresh && fingerprint(filename)? != (header.source_len, header.source_hash) {
+        return Ok(None);
+    }
+    Ok(Some(options.deserialize_from(&mut reader)?))
+}
+
+fn write_readings_cache(filename: &Path, cache: &Path, readings: &[SmartMeterData]) -> Result<()> {
+    let (source_len, source_hash) = fingerprint(filename)?;
+    let header = CacheHeader {
+        version: READINGS_CACHE_VERSION,
+        source_len,
+        source_hash,
+    };
+    let mut writer = BufWriter::new(File::create(cache)?);
+    bincode::serialize_into(&mut writer, &header)?;
+    bincode::serialize_into(&mut writer, readings)?;
+    Ok(())
+}
+
+// Hands out one shared copy of each value, the last one is checked first since a file is
+// usually a single meter
+#[derive(Default)]
+pub(crate) struct Interner {
+    last: Option<Arc<str>>,
+    seen: HashMap<String, Arc<str>>,
+}
+
+impl Interner {
+    pub(crate) fn intern(&mut self, text: &str) -> Arc<str> {
+        if let Some(last) = self.last.as_ref().filter(|last| last.as_ref() == text) {
+            return Arc::clone(last);
+        }
+        let value = match self.seen.get(text) {
+            Some(value) => Arc::clone(value),
+            None => {
+                let value: Arc<str> = text.into();
+                self.seen.insert(text.to_string(), Arc::clone(&value));
+                value
+            }
+        };
+        self.last = Some(Arc::clone(&value));
+        value
+    }
+}
+
+fn digits(bytes: &[u8]) -> Option<u32> {
+    bytes.iter().try_fold(0, |acc, &b| {
+        b.is_ascii_digit().then(|| acc * 10 + (b - b'0') as u32)
+    })
+}
+
+// The HDF datetimes always look like 08-01-2024 03:30, anything else goes through chrono
+fn parse_hdf_datetime(bytes: &[u8]) -> Option<NaiveDateTime> {
+    if let [d1, d2, b'-', m1, m2, b'-', y1, y2, y3, y4, b' ', h1, h2, b':', n1, n2] = *bytes {
+        let date = NaiveDate::from_ymd_opt(
+            digits(&[y1, y2, y3, y4])? as i32,
+            digits(&[m1, m2])?,
+            digits(&[d1, d2])?,
+        )?;
+        return date.and_hms_opt(digits(&[h1, h2])?, digits(&[n1, n2])?, 0);
+    }
+    NaiveDateTime::parse_from_str(std::str::from_utf8(bytes).ok()?, "%d-%m-%Y %H:%M").ok()
+}
+
+fn parse_read_type(bytes: &[u8]) -> Option<ReadType> {
+    match bytes {
+        b"Active Import Interval (kW)" => Some(ReadType::ImportKw),
+        b"Active Export Interval (kW)" => Some(ReadType::ExportKw),
+        b"Active Import Interval (kWh)" => Some(ReadType::ImportKwh),
+        b"Active Export Interval (kWh)" => Some(ReadType::ExportKwh),
+        _ => None,
+    }
+}
+
+// Reads an HDF export without going through serde, reusing one record for every row and
+// sharing the MPRN and serial between the readings, which is most of the allocations on a
+// big file. Rows that can't be read are handled like read_csv does, see ParseMode
+pub fn parse_smart_meter_csv(reader: impl Read) -> Result<Vec<SmartMeterData>> {
+    let mut rows = RowChecker::new("the csv", ParseMode::global());
+    let data = parse_smart_meter_csv_with_progress(reader, &mut rows, &mut Progress::hidden())?;
+    rows.finish();
+    Ok(data)
+}
+
+// Checking the clock on every row would cost more than the parsing
+const ROWS_PER_PROGRESS_UPDATE: usize = 4096;
+
+pub fn parse_smart_meter_csv_with_progress(
+    reader: impl Read,
+    rows: &mut RowChecker,
+    progress: &mut Progress,
+) -> Result<Vec<SmartMeterData>> {
+    // short rows are reported below instead of failing the whole file
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(BufReader::new(reader));
+    let headers = rdr.byte_headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name.as_bytes())
+            .ok_or_else(|| Error::MissingColumn {
+                file: rows.file().to_string(),
+                column: name.to_string(),
+            })
+    };
+    let mprn = column("MPRN")?;
+    let serial = column("Meter Serial Number")?;
+    let value = column("Read Value")?;
+    let read_type = column("Read Type")?;
+    let end_time = column("Read Date and End Time")?;
+
+    let (mut mprns, mut serials) = (Interner::default(), Interner::default());
+    let mut record = csv::ByteRecord::new();
+    let mut data = vec![];
+    let mut count = 0;
+    while rdr.read_byte_record(&mut record)? {
+        count += 1;
+        if count % ROWS_PER_PROGRESS_UPDATE == 0 {
+            progress.update(data.len(), rows.skipped());
+        }
+        let field = |index: usize, name: &str| {
+            let bytes = record
+                .get(index)
+                .ok_or_else(|| (RowProblem::MissingField, format!("the row has no {name}")))?;
+            std::str::from_utf8(bytes)
+                .map_err(|_| (RowProblem::Unreadable, format!("the {name} is not text")))
+        };
+        let parsed = (|| -> Result<SmartMeterData, (RowProblem, String)> {
+            let (value, read_type, end_time) = (
+                field(value, "Read Value")?,
+                field(read_type, "Read Type")?,
+                field(end_time, "Read Date and End Time")?,
+            );
+            let (read_type, unit) = parse_read_type(read_type.as_bytes())
+                .ok_or_else(|| {
+                    (
+                        RowProblem::UnknownType,
+                        format!("unknown Read Type {read_type:?}"),
+                    )
+                })?
+                .split();
+            Ok(SmartMeterData {
+                mprn: mprns.intern(field(mprn, "MPRN")?),
+                meter_serial_number: serials.intern(field(serial, "Meter Serial Number")?),
+                read_value: value.parse().map_err(|_| {
+                    (
+                        RowProblem::NotANumber,
+                        format!("the Read Value {value:?} is not a number"),
+                    )
+                })?,
+                read_type,
+                unit,
+                read_data_and_end_time: parse_hdf_datetime(end_time.as_bytes()).ok_or_else(
+                    || {
+                        (
+                            RowProblem::BadDate,
+                            format!("the Read Date and End Time {end_time:?} is not like 08-01-2024 03:30"),
+                        )
+                    },
+                )?,
+                interval_minutes: default_interval_minutes(),
+            })
+        })();
+        let parsed = parsed.map_err(|(problem, reason)| {
+            let line = record.position().map_or(0, |position| position.line());
+            rows.bad_row(line, problem, reason, raw_row(record.iter()))
+        });
+        data.extend(rows.check(parsed)?);
+    }
+    progress.finish(data.len(), rows.skipped());
+    Ok(data)
+}
+
+pub fn load_smart_meter_data(filename: &Path, use_cache: bool) -> Result<Vec<SmartMeterData>> {
+    let mut data = read_smart_meter_data(filename, use_cache)?;
+    for mixed in detect_intervals(&mut data) {
+        eprintln!("warning: {}: {mixed}", filename.display());
+    }
+    Ok(data)
+}
+
+fn read_smart_meter_data(filename: &Path, use_cache: bool) -> Result<Vec<SmartMeterData>> {
+    let cache = cache_path(filename);
+    // strict runs always parse, the cache would hide the rows it skipped
+    if use_cache && ParseMode::global() == ParseMode::Lenient {
+        match cached_readings(filename, &cache) {
+            Ok(Some(data)) => {
+                Metrics::global().ingested(data.len(), 0);
+                return Ok(data);
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("ignoring unreadable cache {}: {e}", cache.display()),
+        }
+    }
+
+    let mut rows = RowChecker::new(filename.display().to_string(), ParseMode::global());
+    let data = detect_source(filename)?.parse(
+        &mut File::open(filename)?,
+        &mut rows,
+        &mut Progress::new(filename.display().to_string()),
+    )?;
+    Metrics::global().ingested(data.len(), rows.skipped());
+    rows.finish();
+    if use_cache {
+        // failing to write the cache only costs time on the next run
+        if let Err(e) = write_readings_cache(filename, &cache, &data) {
+            eprintln!("could not write cache {}: {e}", cache.display());
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+pub(crate) fn reading_at(datetime: &str, read_value: f32) -> SmartMeterData {
+    SmartMeterData {
+        mprn: "10000000000".into(),
+        meter_serial_number: "00000000".into(),
+        read_value,
+        read_type: SmartMeterDataType::ActiveImport,
+        unit: ReadUnit::Kilowatts,
+        read_data_and_end_time: NaiveDateTime::parse_from_str(datetime, "%d-%m-%Y %H:%M").unwrap(),
+        interval_minutes: default_interval_minutes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_cache_is_kept_while_the_csv_has_the_same_contents() {
+        let dir = std::env::temp_dir().join(format!("readings-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv = dir.join("HDF.csv");
+        let header = "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time\n";
+        let row = "10308375697,34996871,0.5,Active Import Interval (kW),08-01-2024 03:30\n";
+        std::fs::write(&csv, format!("{header}{row}")).unwrap();
+        let cache = cache_path(&csv);
+        assert_eq!(read_smart_meter_data(&csv, true).unwrap().len(), 1);
+
+        // a cache that says something else shows whether it was read
+        let mut marked = reading_at("08-01-2024 03:30", 9.0);
+        marked.mprn = "10308375697".into();
+        write_readings_cache(&csv, &cache, &[marked]).unwrap();
+        // touched after the cache but the same contents
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(&csv)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(
+            read_smart_meter_data(&csv, true).unwrap()[0].read_value,
+            9.0
+        );
+
+        // changed contents are parsed again
+        std::fs::write(&csv, format!("{header}{row}{row}")).unwrap();
+        assert_eq!(read_smart_meter_data(&csv, true).unwrap().len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_later_download_replaces_the_readings_it_overlaps() {
+        let first = vec![
+            reading_at("08-01-2024 00:30", 1.0),
+            reading_at("08-01-2024 01:00", 1.0),
+        ];
+        // the 01:00 reading was corrected, and 01:30 is new
+        let second = vec![
+            reading_at("08-01-2024 01:00", 3.0),
+            reading_at("08-01-2024 01:30", 2.0),
+        ];
+        let merged = merge_readings(vec![first, second]);
+        let values: Vec<(String, f32)> = merged
+            .iter()
+            .map(|d| {
+                (
+                    d.read_data_and_end_time.format("%H:%M").to_string(),
+                    d.read_value,
+                )
+            })
+            .collect();
+        // a replaced reading keeps its place, new ones come after in the order they were read
+        assert_eq!(
+            values,
+            vec![
+                ("00:30".to_string(), 1.0),
+                ("01:00".to_string(), 3.0),
+                ("01:30".to_string(), 2.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn readings_that_dont_overlap_are_all_kept() {
+        let mut export = reading_at("08-01-2024 00:30", 0.5);
+        export.read_type = SmartMeterDataType::ActiveExport;
+        let mut new_meter = reading_at("08-01-2024 00:30", 0.7);
+        new_meter.meter_serial_number = "11111111".into();
+        let merged = merge_readings(vec![
+            vec![reading_at("08-01-2024 00:30", 1.0), export],
+            vec![new_meter, reading_at("09-01-2024 00:30", 1.0)],
+            vec![],
+        ]);
+        // the same end time in another direction or on another meter is another reading
+        assert_eq!(merged.len(), 4);
+        assert_eq!(merged[2].meter_serial_number.as_ref(), "11111111");
+        assert!(merge_readings(vec![]).is_empty());
+    }
+
+    #[test]
+    fn the_date_range_keeps_both_ends_and_midnight_ends_the_day_before() {
+        let data = vec![
+            reading_at("07-01-2024 23:30", 1.0),
+            reading_at("08-01-2024 00:00", 1.0),
+            reading_at("08-01-2024 00:30", 1.0),
+            reading_at("09-01-2024 00:00", 1.0),
+            reading_at("09-01-2024 00:30", 1.0),
+        ];
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d);
+        assert_eq!(data[1].date(), day(7).unwrap());
+        let times = |data: Vec<SmartMeterData>| -> Vec<String> {
+            data.iter()
+                .map(|d| d.read_data_and_end_time.format("%d %H:%M").to_string())
+                .collect()
+        };
+        assert_eq!(
+            times(filter_by_date(data.clone(), day(8), day(8))),
+            vec!["08 00:30", "09 00:00"]
+        );
+        assert_eq!(times(filter_by_date(data.clone(), None, day(7))).len(), 2);
+        assert_eq!(filter_by_date(data, None, None).len(), 5);
+    }
+
+    #[test]
+    fn quarter_hourly_readings_with_a_gap_are_detected() {
+        let mut data = vec![
+            reading_at("08-01-2024 00:15", 2.0),
+            reading_at("08-01-2024 00:30", 2.0),
+            reading_at("08-01-2024 00:45", 2.0),
+            // 01:00 is missing
+            reading_at("08-01-2024 01:15", 2.0),
+        ];
+        detect_intervals(&mut data);
+
+        assert!(data.iter().all(|d| d.interval_minutes == 15));
+        assert_eq!(data[3].energy_kwh(), 0.5);
+    }
+
+    #[test]
+    fn a_series_changing_to_quarter_hours_is_summed_up_to_half_hours() {
+        let at = |times: &[&str]| -> Vec<SmartMeterData> {
+            times
+                .iter()
+                .map(|time| reading_at(&format!("08-01-2024 {time}"), 1.0))
+                .collect()
+        };
+        // quarter hours with 01:15 missing aren't mixed
+        let mut data = at(&[
+            "00:15", "00:30", "00:45", "01:00", "01:30", "01:45", "02:00",
+        ]);
+        assert!(detect_intervals(&mut data).is_empty());
+        assert!(data.iter().all(|d| d.interval_minutes == 15));
+
+        // half hours up to 02:30 and quarter hours after, 1 kW all along
+        let mut data = at(&[
+            "00:30", "01:00", "01:30", "02:00", "02:30", "02:45", "03:00", "03:15", "03:30",
+        ]);
+        let mixed = detect_intervals(&mut data);
+        assert_eq!(mixed.len(), 1);
+        assert_eq!(mixed[0].intervals, vec![15, 30]);
+        assert_eq!(
+            mixed[0].to_string(),
+            "the import readings of 10000000000 mix 15 and 30 minute intervals, summed up to 30 minutes"
+        );
+        data.sort_by_key(|d| d.read_data_and_end_time);
+        assert_eq!(data.len(), 7);
+        assert!(data
+            .iter()
+            .all(|d| d.interval_minutes == 30 && d.energy_kwh() == 0.5));
+        assert_eq!(
+            data[6].local_end_time().time(),
+            NaiveTime::from_hms_opt(3, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn bad_rows_are_reported_with_their_line() {
+        let csv = "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+10308375697,34996871,0.5,Active Import Interval (kW),08-01-2024 03:30
+10308375697,34996871,abc,Active Import Interval (kW),08-01-2024 04:00
+10308375697,34996871
+";
+        let mut rows = RowChecker::new("HDF.csv", ParseMode::Lenient);
+        let data =
+            parse_smart_meter_csv_with_progress(csv.as_bytes(), &mut rows, &mut Progress::hidden())
+                .unwrap();
+        assert_eq!((data.len(), rows.skipped()), (1, 2));
+
+        let mut rows = RowChecker::new("HDF.csv", ParseMode::Strict);
+        let error =
+            parse_smart_meter_csv_with_progress(csv.as_bytes(), &mut rows, &mut Progress::hidden())
+                .unwrap_err();
+        assert_eq!(
+            error.downcast::<Error>().unwrap(),
+            Error::BadRow {
+                file: "HDF.csv".to_string(),
+                line: 3,
+                problem: RowProblem::NotANumber,
+                reason: "the Read Value \"abc\" is not a number".to_string(),
+                row: "10308375697,34996871,abc,Active Import Interval (kW),08-01-2024 04:00"
+                    .to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn fast_path_reads_the_same_as_serde() {
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/HDF_week.csv");
+        let serde: Vec<SmartMeterData> = read_csv(fixture).unwrap();
+        let fast = parse_smart_meter_csv(File::open(fixture).unwrap()).unwrap();
+        assert_eq!(format!("{fast:?}"), format!("{serde:?}"));
+        // every reading shares the one MPRN
+        assert!(Arc::ptr_eq(&fast[0].mprn, &fast[fast.len() - 1].mprn));
+    }
+
+    #[test]
+    fn kwh_readings_are_already_energy() {
+        let csv = "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+10308375697,34996871,0.5,Active Import Interval (kWh),08-01-2024 03:30
+10308375697,34996871,0.5,Active Import Interval (kW),08-01-2024 03:30
+";
+        let mut rows = RowChecker::new("HDF.csv", ParseMode::Strict);
+        let fast =
+            parse_smart_meter_csv_with_progress(csv.as_bytes(), &mut rows, &mut Progress::hidden())
+                .unwrap();
+        let serde: Vec<SmartMeterData> = csv::Reader::from_reader(csv.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(format!("{fast:?}"), format!("{serde:?}"));
+        assert_eq!(fast[0].unit, ReadUnit::KilowattHours);
+        assert_eq!((fast[0].energy_kwh(), fast[1].energy_kwh()), (0.5, 0.25));
+    }
+
+    #[test]
+    fn readings_follow_irish_daylight_saving_time() {
+        // winter time is UTC, summer time is an hour ahead
+        assert_eq!(
+            reading_at("08-01-2024 17:30", 1.0).local_end_time(),
+            NaiveDateTime::parse_from_str("08-01-2024 17:30", "%d-%m-%Y %H:%M").unwrap()
+        );
+        assert_eq!(
+            reading_at("01-07-2024 16:30", 1.0).local_end_time(),
+            NaiveDateTime::parse_from_str("01-07-2024 17:30", "%d-%m-%Y %H:%M").unwrap()
+        );
+        // the clocks went back at 02:00 local on 27-10-2024, 00:30 and 01:30 UTC are both 01:30
+        assert_eq!(
+            reading_at("27-10-2024 00:30", 1.0).local_end_time(),
+            reading_at("27-10-2024 01:30", 1.0).local_end_time()
+        );
+        // and the reading ending at 23:30 UTC on the 26th is still the 27th locally in summer time
+        assert_eq!(
+            reading_at("26-10-2024 23:30", 1.0).date(),
+            NaiveDate::from_ymd_opt(2024, 10, 27).unwrap()
+        );
+    }
+}
@@ -0,0 +1,435 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime};
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    detect_intervals,
+    meter_data::{raw_row, Interner},
+    parse_smart_meter_csv_with_progress, Currency, Error, HomeAssistantStatistics, ParseMode,
+    Progress, ReadUnit, RowChecker, RowProblem, SmartMeterData, SmartMeterDataType,
+};
+
+// A format of meter readings the plans can be compared on, every source turns its rows into
+// the same readings so the pricing doesn't know where they came from
+pub trait MeterDataSource {
+    fn name(&self) -> &'static str;
+
+    // What the plans compared on these readings are priced in
+    fn currency(&self) -> Currency {
+        Currency::Eur
+    }
+
+    fn parse(
+        &self,
+        reader: &mut dyn Read,
+        rows: &mut RowChecker,
+        progress: &mut Progress,
+    ) -> Result<Vec<SmartMeterData>>;
+}
+
+// The HDF export of ESB Networks in Ireland
+pub struct EsbHdf;
+
+impl MeterDataSource for EsbHdf {
+    fn name(&self) -> &'static str {
+        "ESB Networks HDF"
+    }
+
+    fn parse(
+        &self,
+        reader: &mut dyn Read,
+        rows: &mut RowChecker,
+        progress: &mut Progress,
+    ) -> Result<Vec<SmartMeterData>> {
+        parse_smart_meter_csv_with_progress(reader, rows, progress)
+    }
+}
+
+// The half hourly consumption csv UK suppliers and n3rgy export, either
+// Consumption (kWh), Start, End like Octopus or timestamp (UTC),energyConsumption (kWh) like
+// n3rgy. Neither has the meter in it, so the readings are put on the meter named after the file
+pub struct UkConsumption {
+    meter: Arc<str>,
+}
+
+// The kWh column and the column with the end of the interval of each format
+const UK_COLUMNS: [(&str, &str); 2] = [
+    ("Consumption (kWh)", "End"),
+    ("energyConsumption (kWh)", "timestamp (UTC)"),
+];
+
+// UK exports and loggers give times with their offset, or in UTC when they don't
+pub(crate) fn parse_timestamp(text: &str) -> Option<NaiveDateTime> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Some(datetime.naive_utc());
+    }
+    ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+}
+
+impl UkConsumption {
+    pub fn new(meter: impl Into<Arc<str>>) -> UkConsumption {
+        UkConsumption {
+            meter: meter.into(),
+        }
+    }
+
+    fn recognises(headers: &[String]) -> bool {
+        UK_COLUMNS
+            .iter()
+            .any(|(kwh, _)| headers.iter().any(|header| header == kwh))
+    }
+}
+
+impl MeterDataSource for UkConsumption {
+    fn name(&self) -> &'static str {
+        "UK half hourly consumption"
+    }
+
+    fn currency(&self) -> Currency {
+        Currency::Gbp
+    }
+
+    fn parse(
+        &self,
+        reader: &mut dyn Read,
+        rows: &mut RowChecker,
+        progress: &mut Progress,
+    ) -> Result<Vec<SmartMeterData>> {
+        // Octopus puts a space after every comma
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(BufReader::new(reader));
+        let headers = rdr.headers()?.clone();
+        let position = |name: &str| headers.iter().position(|header| header == name);
+        let (kwh, end) = UK_COLUMNS
+            .iter()
+            .find_map(|(kwh, end)| Some((position(kwh)?, position(end)?)))
+            .ok_or_else(|| Error::MissingColumn {
+                file: rows.file().to_string(),
+                column: "End".to_string(),
+            })?;
+
+        let mut data = vec![];
+        for record in rdr.records() {
+            let record = record?;
+            let parsed = (|| -> Result<SmartMeterData, (RowProblem, String)> {
+                let field = |index: usize| {
+                    record
+                        .get(index)
+                        .filter(|field| !field.is_empty())
+                        .ok_or((RowProblem::MissingField, "the row is short".to_string()))
+                };
+                let (value, end_time) = (field(kwh)?, field(end)?);
+                Ok(SmartMeterData {
+                    mprn: Arc::clone(&self.meter),
+                    meter_serial_number: Arc::clone(&self.meter),
+                    read_value: value.parse().map_err(|_| {
+                        (
+                            RowProblem::NotANumber,
+                            format!("the consumption {value:?} is not a number"),
+                        )
+                    })?,
+                    read_type: SmartMeterDataType::ActiveImport,
+                    unit: ReadUnit::KilowattHours,
+                    read_data_and_end_time: parse_timestamp(end_time).ok_or_else(|| {
+                        (
+                            RowProblem::BadDate,
+                            format!("the end {end_time:?} is not like 2024-01-08T03:30:00Z"),
+                        )
+                    })?,
+                    interval_minutes: 30,
+                })
+            })();
+            let parsed = parsed.map_err(|(problem, reason)| {
+                let line = record.position().map_or(0, |position| position.line());
+                rows.bad_row(
+                    line,
+                    problem,
+                    reason,
+                    raw_row(record.as_byte_record().iter()),
+                )
+            });
+            data.extend(rows.check(parsed)?);
+            progress.update(data.len(), rows.skipped());
+        }
+        progress.finish(data.len(), rows.skipped());
+        Ok(data)
+    }
+}
+
+// Readings from Home Assistant or a logger, one json object per line or an array of them:
+// {"end": "2024-01-08T03:30:00Z", "kwh": 0.25, "meter": "10308375697", "direction": "export"}
+// with the energy of the interval in kwh or its average power in kw. Without a meter the
+// readings are put on the meter named after the file, without a direction they are imports
+pub struct JsonReadings {
+    meter: Arc<str>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+    Import,
+    Export,
+}
+
+#[derive(Deserialize)]
+struct JsonReading {
+    end: String,
+    kwh: Option<f32>,
+    kw: Option<f32>,
+    meter: Option<String>,
+    direction: Option<Direction>,
+}
+
+impl JsonReadings {
+    pub fn new(meter: impl Into<Arc<str>>) -> JsonReadings {
+        JsonReadings {
+            meter: meter.into(),
+        }
+    }
+
+    fn reading(
+        &self,
+        text: &str,
+        meters: &mut Interner,
+    ) -> Result<SmartMeterData, (RowProblem, String)> {
+        let reading: JsonReading = serde_json::from_str(text).map_err(|e| {
+            let reason = e.to_string();
+            let problem = if reason.starts_with("missing field") {
+                RowProblem::MissingField
+            } else if reason.starts_with("unknown variant") {
+                RowProblem::UnknownType
+            } else if reason.starts_with("invalid type") {
+                RowProblem::NotANumber
+            } else {
+                RowProblem::Unreadable
+            };
+            (problem, reason)
+        })?;
+        let (read_value, unit) = match (reading.kwh, reading.kw) {
+            (Some(kwh), _) => (kwh, ReadUnit::KilowattHours),
+            (None, Some(kw)) => (kw, ReadUnit::Kilowatts),
+            (None, None) => {
+                return Err((
+                    RowProblem::MissingField,
+                    "the reading has no kwh or kw".to_string(),
+                ))
+            }
+        };
+        let meter = match &reading.meter {
+            Some(meter) => meters.intern(meter),
+            None => Arc::clone(&self.meter),
+        };
+        Ok(SmartMeterData {
+            mprn: Arc::clone(&meter),
+            meter_serial_number: meter,
+            read_value,
+            read_type: match reading.direction {
+                Some(Direction::Export) => SmartMeterDataType::ActiveExport,
+                Some(Direction::Import) | None => SmartMeterDataType::ActiveImport,
+            },
+            unit,
+            read_data_and_end_time: parse_timestamp(&reading.end).ok_or_else(|| {
+                (
+                    RowProblem::BadDate,
+                    format!("the end {:?} is not like 2024-01-08T03:30:00Z", reading.end),
+                )
+            })?,
+            interval_minutes: 30,
+        })
+    }
+}
+
+impl MeterDataSource for JsonReadings {
+    fn name(&self) -> &'static str {
+        "json readings"
+    }
+
+    fn parse(
+        &self,
+        reader: &mut dyn Read,
+        rows: &mut RowChecker,
+        progress: &mut Progress,
+    ) -> Result<Vec<SmartMeterData>> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        // an array is read as if every reading was on its own line, counting them from 1
+        let readings: Vec<(u64, String)> = if text.trim_start().starts_with('[') {
+            let values: Vec<serde_json::Value> = serde_json::from_str(&text)?;
+            (1..)
+                .zip(values.iter().map(|value| value.to_string()))
+                .collect()
+        } else {
+            (1..)
+                .zip(text.lines().map(str::to_string))
+                .filter(|(_, line)| !line.trim().is_empty())
+                .collect()
+        };
+
+        let mut meters = Interner::default();
+        let mut data = vec![];
+        for (line, reading) in readings {
+            let parsed = self
+                .reading(&reading, &mut meters)
+                .map_err(|(problem, reason)| rows.bad_row(line, problem, reason, reading));
+            data.extend(rows.check(parsed)?);
+            progress.update(data.len(), rows.skipped());
+        }
+        progress.finish(data.len(), rows.skipped());
+        Ok(data)
+    }
+}
+
+// Readings handed over in memory instead of a file, like an upload or a file picked in a browser
+#[derive(Debug, Clone)]
+pub struct ParsedReadings {
+    pub readings: Vec<SmartMeterData>,
+    pub currency: Currency,
+    pub skipped_rows: usize,
+}
+
+// Reads the bytes of any of the formats detect_source knows, skipping the rows that can't be
+// read, meter names the readings of the formats without a meter column
+pub fn parse_readings(bytes: &[u8], meter: &str) -> Result<ParsedReadings> {
+    let header = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+    let source = source_for_header(&String::from_utf8_lossy(header), meter);
+    let mut rows = RowChecker::new(meter, ParseMode::Lenient);
+    let mut readings = source.parse(&mut &*bytes, &mut rows, &mut Progress::hidden())?;
+    // the comparison of a mixed download is of the summed up readings either way
+    let _ = detect_intervals(&mut readings);
+    Ok(ParsedReadings {
+        readings,
+        currency: source.currency(),
+        skipped_rows: rows.skipped(),
+    })
+}
+
+// The source of a file from its first line, json starts with { or [ and the csv and tsv
+// formats are told apart by their header. Files in no known format are read as HDF exports, which
+// says which columns are missing
+pub fn detect_source(path: &Path) -> Result<Box<dyn MeterDataSource>> {
+    let mut header = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut header)?;
+    let meter = path
+        .file_stem()
+        .map_or("meter".into(), |stem| stem.to_string_lossy());
+    Ok(source_for_header(&header, &meter))
+}
+
+// The same for readings that aren't in a file, like an upload, meter names the readings of
+// the formats without a meter column
+pub fn source_for_header(header: &str, meter: &str) -> Box<dyn MeterDataSource> {
+    let headers: Vec<String> = header
+        .trim_start_matches('\u{feff}')
+        .split([',', '\t'])
+        .map(|header| header.trim().trim_matches('"').to_string())
+        .collect();
+    if header.trim_start().starts_with(['{', '[']) {
+        return Box::new(JsonReadings::new(meter));
+    }
+    if HomeAssistantStatistics::recognises(&headers) {
+        return Box::new(HomeAssistantStatistics);
+    }
+    if UkConsumption::recognises(&headers) {
+        return Box::new(UkConsumption::new(meter));
+    }
+    Box::new(EsbHdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseMode;
+
+    fn parse(csv: &str) -> Vec<SmartMeterData> {
+        let mut rows = RowChecker::new("uk.csv", ParseMode::Strict);
+        UkConsumption::new("uk")
+            .parse(&mut csv.as_bytes(), &mut rows, &mut Progress::hidden())
+            .unwrap()
+    }
+
+    #[test]
+    fn octopus_and_n3rgy_exports_read_the_same() {
+        let octopus = parse(
+            " Consumption (kWh), Start, End
+0.250, 2024-07-01T00:00:00+01:00, 2024-07-01T00:30:00+01:00
+0.125, 2024-07-01T00:30:00+01:00, 2024-07-01T01:00:00+01:00
+",
+        );
+        let n3rgy = parse(
+            "timestamp (UTC),energyConsumption (kWh)
+2024-06-30 23:30,0.250
+2024-07-01 00:00,0.125
+",
+        );
+        assert_eq!(format!("{octopus:?}"), format!("{n3rgy:?}"));
+        assert_eq!(octopus[0].energy_kwh(), 0.25);
+        // British summer time is on the same clock as Irish
+        assert_eq!(
+            octopus[1].local_end_time().to_string(),
+            "2024-07-01 01:00:00"
+        );
+    }
+
+    #[test]
+    fn json_lines_and_arrays_read_the_same() {
+        let read = |json: &str| {
+            let mut rows = RowChecker::new("readings.json", ParseMode::Lenient);
+            let data = JsonReadings::new("logger")
+                .parse(&mut json.as_bytes(), &mut rows, &mut Progress::hidden())
+                .unwrap();
+            (data, rows.skipped())
+        };
+        let (lines, skipped) = read(
+            r#"{"end": "2024-01-08T03:30:00Z", "kwh": 0.25}
+{"end": "2024-01-08T03:30:00Z", "kw": 1.5, "meter": "10308375697", "direction": "export"}
+{"end": "08-01-2024 04:00", "kwh": 0.25}
+"#,
+        );
+        let (array, _) = read(
+            r#"[{"end": "2024-01-08 03:30", "kwh": 0.25},
+{"end": "2024-01-08T03:30:00+00:00", "kw": 1.5, "meter": "10308375697", "direction": "export"}]"#,
+        );
+        assert_eq!(format!("{lines:?}"), format!("{array:?}"));
+        // the date of the third reading isn't one a logger writes
+        assert_eq!(skipped, 1);
+        assert_eq!(&*lines[0].mprn, "logger");
+        assert_eq!(lines[1].read_type, SmartMeterDataType::ActiveExport);
+        assert_eq!(lines[1].energy_kwh(), 0.75);
+    }
+
+    #[test]
+    fn uploaded_bytes_are_read_like_a_file() {
+        let hdf = parse_readings(
+            b"MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+10308375697,34996871,0.5,Active Import Interval (kW),08-01-2024 03:30
+10308375697,34996871,half,Active Import Interval (kW),08-01-2024 04:00
+10308375697,34996871,0.25,Active Import Interval (kW),08-01-2024 04:30
+",
+            "upload",
+        )
+        .unwrap();
+        assert_eq!(hdf.readings.len(), 2);
+        assert_eq!(hdf.skipped_rows, 1);
+        assert_eq!(hdf.currency, Currency::Eur);
+        assert_eq!(&*hdf.readings[0].mprn, "10308375697");
+
+        let uk = parse_readings(
+            b"timestamp (UTC),energyConsumption (kWh)\n2024-06-30 23:30,0.250\n",
+            "upload",
+        )
+        .unwrap();
+        assert_eq!(uk.currency, Currency::Gbp);
+        assert_eq!(&*uk.readings[0].mprn, "upload");
+    }
+}
@@ -0,0 +1,230 @@
+use chrono::{NaiveDateTime, TimeZone};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use crate::{SmartMeterData, SmartMeterDataType, LOCAL_TIMEZONE};
+
+// The readings of one meter of a meter point, from the first to the last in UTC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeterSegment {
+    pub mprn: String,
+    pub serial: String,
+    pub first: NaiveDateTime,
+    pub last: NaiveDateTime,
+    pub readings: usize,
+}
+
+// A meter replaced by another on the same meter point. Both usually report for a while around
+// the swap, the old one zeros after it comes off the wall or the new one zeros before it's
+// commissioned, so only one of them is kept for each interval
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeterSwap {
+    pub mprn: String,
+    pub old_serial: String,
+    pub new_serial: String,
+    // the end of the first interval counted on the new meter, in UTC like the readings
+    pub changeover: NaiveDateTime,
+    // readings of the interval already counted on the other meter
+    pub dropped: usize,
+    // import intervals neither meter has between the last one of the old and the changeover
+    pub missing_intervals: i64,
+}
+
+impl MeterSwap {
+    pub fn local_changeover(&self) -> NaiveDateTime {
+        LOCAL_TIMEZONE
+            .from_utc_datetime(&self.changeover)
+            .naive_local()
+    }
+}
+
+// Every meter of every meter point, each meter point's in the order they were fitted
+pub fn meter_segments(data: &[SmartMeterData]) -> Vec<MeterSegment> {
+    let mut segments: BTreeMap<(&str, &str), MeterSegment> = BTreeMap::new();
+    for datapoint in data {
+        let time = datapoint.read_data_and_end_time;
+        segments
+            .entry((&datapoint.mprn, &datapoint.meter_serial_number))
+            .and_modify(|segment| {
+                segment.first = segment.first.min(time);
+                segment.last = segment.last.max(time);
+                segment.readings += 1;
+            })
+            .or_insert_with(|| MeterSegment {
+                mprn: datapoint.mprn.to_string(),
+                serial: datapoint.meter_serial_number.to_string(),
+                first: time,
+                last: time,
+                readings: 1,
+            });
+    }
+    let mut segments: Vec<MeterSegment> = segments.into_values().collect();
+    segments.sort_by(|a, b| (&a.mprn, a.first, &a.serial).cmp(&(&b.mprn, b.first, &b.serial)));
+    segments
+}
+
+// Keeps a single reading of every interval of a meter point with more than one meter. Each
+// meter counts from its changeover, the end of its first reading that isn't zero, or of its
+// first reading at all when they all are, until the changeover of the next one. An interval
+// only one of the meters has is kept whoever it belongs to
+pub fn resolve_meter_swaps(data: Vec<SmartMeterData>) -> (Vec<SmartMeterData>, Vec<MeterSwap>) {
+    let segments = meter_segments(&data);
+    let mut changeovers: HashMap<(Arc<str>, Arc<str>), NaiveDateTime> = HashMap::new();
+    for datapoint in &data {
+        let key = (
+            datapoint.mprn.clone(),
+            datapoint.meter_serial_number.clone(),
+        );
+        let time = datapoint.read_data_and_end_time;
+        if datapoint.read_value != 0.0 {
+            changeovers
+                .entry(key)
+                .and_modify(|changeover| *changeover = (*changeover).min(time))
+                .or_insert(time);
+        }
+    }
+    let mut swaps = vec![];
+    for pair in segments.windows(2) {
+        let (old, new) = (&pair[0], &pair[1]);
+        if old.mprn != new.mprn {
+            continue;
+        }
+        let changeover = changeovers
+            .get(&(new.mprn.as_str().into(), new.serial.as_str().into()))
+            .copied()
+            .unwrap_or(new.first);
+        swaps.push(MeterSwap {
+            mprn: new.mprn.clone(),
+            old_serial: old.serial.clone(),
+            new_serial: new.serial.clone(),
+            changeover,
+            dropped: 0,
+            missing_intervals: 0,
+        });
+    }
+    if swaps.is_empty() {
+        return (data, swaps);
+    }
+
+    // the meter counting at a time is the last one whose changeover is at or before it
+    let counting = |mprn: &str, time: NaiveDateTime| {
+        swaps
+            .iter()
+            .filter(|swap| swap.mprn == mprn && swap.changeover <= time)
+            .max_by_key(|swap| swap.changeover)
+            .map(|swap| swap.new_serial.as_str())
+    };
+    let mut kept: HashMap<(Arc<str>, SmartMeterDataType, NaiveDateTime), usize> = HashMap::new();
+    let mut keep = vec![true; data.len()];
+    let mut dropped: HashMap<String, usize> = HashMap::new();
+    for (index, datapoint) in data.iter().enumerate() {
+        let key = (
+            datapoint.mprn.clone(),
+            datapoint.read_type,
+            datapoint.read_data_and_end_time,
+        );
+        let Some(&other) = kept.get(&key) else {
+            kept.insert(key, index);
+            continue;
+        };
+        let owner = counting(&datapoint.mprn, datapoint.read_data_and_end_time);
+        let is_owner = |d: &SmartMeterData| match owner {
+            Some(serial) => *d.meter_serial_number == *serial,
+            // before the first changeover the first meter fitted counts
+            None => segments
+                .iter()
+                .find(|s| *s.mprn == *d.mprn)
+                .is_some_and(|s| *s.serial == *d.meter_serial_number),
+        };
+        let loser = if is_owner(datapoint) && !is_owner(&data[other]) {
+            kept.insert(key, index);
+            other
+        } else {
+            index
+        };
+        keep[loser] = false;
+        // the swap whose changeover the interval is closest to
+        let swap = swaps
+            .iter()
+            .filter(|swap| swap.mprn == *datapoint.mprn)
+            .min_by_key(|swap| {
+                (swap.changeover - datapoint.read_data_and_end_time)
+                    .num_minutes()
+                    .abs()
+            })
+            .map(|swap| swap.new_serial.clone())
+            .unwrap_or_default();
+        *dropped.entry(swap).or_default() += 1;
+    }
+
+    let resolved: Vec<SmartMeterData> = data
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(datapoint, keep)| keep.then_some(datapoint))
+        .collect();
+    for swap in &mut swaps {
+        swap.dropped = dropped.get(&swap.new_serial).copied().unwrap_or(0);
+        let before = resolved
+            .iter()
+            .filter(|d| {
+                *d.mprn == *swap.mprn
+                    && d.read_type == SmartMeterDataType::ActiveImport
+                    && d.read_data_and_end_time < swap.changeover
+            })
+            .max_by_key(|d| d.read_data_and_end_time);
+        if let Some(before) = before {
+            let minutes = (swap.changeover - before.read_data_and_end_time).num_minutes();
+            swap.missing_intervals = (minutes / before.interval_minutes as i64 - 1).max(0);
+        }
+    }
+    (resolved, swaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    fn on_meter(serial: &str, datetime: &str, kw: f32) -> SmartMeterData {
+        let mut reading = reading_at(datetime, kw);
+        reading.meter_serial_number = serial.into();
+        reading
+    }
+
+    #[test]
+    fn each_interval_is_counted_once_across_the_swap() {
+        let data = vec![
+            on_meter("OLD", "08-01-2024 10:00", 1.0),
+            on_meter("OLD", "08-01-2024 10:30", 1.0),
+            on_meter("OLD", "08-01-2024 11:00", 0.0),
+            on_meter("OLD", "08-01-2024 11:30", 0.0),
+            // the new meter is fitted at 10:30 and counts from 11:00
+            on_meter("NEW", "08-01-2024 10:30", 0.0),
+            on_meter("NEW", "08-01-2024 11:00", 2.0),
+            on_meter("NEW", "08-01-2024 11:30", 2.0),
+            on_meter("NEW", "08-01-2024 12:00", 2.0),
+        ];
+        let (resolved, swaps) = resolve_meter_swaps(data);
+        assert_eq!(swaps.len(), 1);
+        assert_eq!(swaps[0].old_serial, "OLD");
+        assert_eq!(swaps[0].changeover.format("%H:%M").to_string(), "11:00");
+        assert_eq!(swaps[0].dropped, 3);
+        assert_eq!(swaps[0].missing_intervals, 0);
+        let kept: Vec<(String, f32)> = resolved
+            .iter()
+            .map(|d| (d.meter_serial_number.to_string(), d.read_value))
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                ("OLD".to_string(), 1.0),
+                ("OLD".to_string(), 1.0),
+                ("NEW".to_string(), 2.0),
+                ("NEW".to_string(), 2.0),
+                ("NEW".to_string(), 2.0),
+            ]
+        );
+    }
+}
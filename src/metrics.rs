@@ -0,0 +1,164 @@
+use rust_decimal::Decimal;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+use crate::{ComparisonReport, Currency};
+
+// The cheapest plan of a meter point in the last comparison of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cheapest {
+    plan: String,
+    total: Decimal,
+    currency: Currency,
+}
+
+// What a long running instance has done, for GET /metrics in the prometheus text format. The
+// readings read from files and uploads and the rows skipped are counted for the whole process
+#[derive(Debug, Default)]
+pub struct Metrics {
+    readings: AtomicU64,
+    parse_errors: AtomicU64,
+    // unix time, 0 until the first readings
+    last_ingestion: AtomicI64,
+    cheapest: Mutex<BTreeMap<String, Cheapest>>,
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+// quotes, backslashes and new lines are escaped in the value of a label
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Metrics {
+    pub fn global() -> &'static Metrics {
+        METRICS.get_or_init(Metrics::default)
+    }
+
+    // A file or upload was read, with the rows of it that couldn't be
+    pub fn ingested(&self, readings: usize, skipped_rows: usize) {
+        self.readings.fetch_add(readings as u64, Ordering::Relaxed);
+        self.parse_errors
+            .fetch_add(skipped_rows as u64, Ordering::Relaxed);
+        self.last_ingestion
+            .store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    pub fn compared(&self, reports: &[ComparisonReport]) {
+        let mut cheapest = self.cheapest.lock().unwrap();
+        for report in reports {
+            let Some(plan) = report.plans.first() else {
+                continue;
+            };
+            cheapest.insert(
+                report.mprn.clone(),
+                Cheapest {
+                    plan: plan.name.clone(),
+                    total: plan.breakdown.total.signed_amount(),
+                    currency: plan.breakdown.total.currency(),
+                },
+            );
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        // a sample is the labels and the value that follow the name
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[String]| {
+            let _ = writeln!(text, "# HELP energy_comparator_{name} {help}");
+            let _ = writeln!(text, "# TYPE energy_comparator_{name} {kind}");
+            for sample in samples {
+                let _ = writeln!(text, "energy_comparator_{name}{sample}");
+            }
+        };
+        metric(
+            "readings_ingested_total",
+            "counter",
+            "Readings read from files and uploads.",
+            &[format!(" {}", self.readings.load(Ordering::Relaxed))],
+        );
+        metric(
+            "parse_errors_total",
+            "counter",
+            "Rows of files and uploads skipped because they couldn't be read.",
+            &[format!(" {}", self.parse_errors.load(Ordering::Relaxed))],
+        );
+        metric(
+            "last_ingestion_timestamp_seconds",
+            "gauge",
+            "Unix time readings were last read, 0 before the first.",
+            &[format!(" {}", self.last_ingestion.load(Ordering::Relaxed))],
+        );
+        let cheapest: Vec<String> = self
+            .cheapest
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(mprn, cheapest)| {
+                format!(
+                    "{{mprn=\"{}\",plan=\"{}\",currency=\"{}\"}} {}",
+                    label(mprn),
+                    label(&cheapest.plan),
+                    cheapest.currency,
+                    cheapest.total
+                )
+            })
+            .collect();
+        metric(
+            "cheapest_plan_cost",
+            "gauge",
+            "Total of the cheapest plan over the billing period of the last comparison of the meter point.",
+            &cheapest,
+        );
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builtin_plans, compare_meter_points, meter_data::reading_at, ComparisonOptions};
+
+    #[test]
+    fn the_metrics_are_in_the_prometheus_format() {
+        let metrics = Metrics::default();
+        assert!(metrics
+            .render()
+            .contains("\nenergy_comparator_last_ingestion_timestamp_seconds 0\n"));
+        metrics.ingested(48, 2);
+        metrics.ingested(2, 0);
+        let reports = compare_meter_points(
+            &builtin_plans(),
+            vec![reading_at("08-01-2024 03:30", 2.0)],
+            &ComparisonOptions::default(),
+        )
+        .unwrap();
+        metrics.compared(&reports);
+        let text = metrics.render();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "# HELP energy_comparator_readings_ingested_total Readings read from files and uploads."
+        );
+        assert_eq!(lines[2], "energy_comparator_readings_ingested_total 50");
+        assert_eq!(lines[5], "energy_comparator_parse_errors_total 2");
+        let cheapest = lines.last().unwrap();
+        assert!(
+            cheapest.starts_with(&format!(
+                "energy_comparator_cheapest_plan_cost{{mprn=\"10000000000\",plan=\"{}\",currency=\"EUR\"}} ",
+                reports[0].plans[0].name
+            )),
+            "{cheapest}"
+        );
+        assert_eq!(label("a \"b\""), "a \\\"b\\\"");
+    }
+}
@@ -0,0 +1,270 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Debug, Display},
+    ops::{Add, Sub},
+};
+
+use crate::{quantity_cost, Numeric, PricingNumber};
+
+// What a plan is priced in, euro unless its tariff says otherwise
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    #[default]
+    Eur,
+    Gbp,
+}
+
+impl Currency {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Currency::Eur => "€",
+            Currency::Gbp => "£",
+        }
+    }
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Currency::Eur => "EUR",
+            Currency::Gbp => "GBP",
+        })
+    }
+}
+
+// An amount of money, kept as a decimal so summing thousands of readings doesn't drift
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money {
+    amount: Decimal,
+    currency: Currency,
+}
+
+impl Money {
+    pub const ZERO: Money = Money {
+        amount: Decimal::ZERO,
+        currency: Currency::Eur,
+    };
+
+    // An amount of euro
+    pub fn new(amount: Decimal) -> Money {
+        Money::of(amount, Currency::Eur)
+    }
+
+    pub fn of(amount: Decimal, currency: Currency) -> Money {
+        Money { amount, currency }
+    }
+
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    // The same amount in another currency, plans price in euro and their bills are put in
+    // the currency of the plan at the end, see PricePlanStrategy::currency
+    pub fn in_currency(self, currency: Currency) -> Money {
+        Money::of(self.amount, currency)
+    }
+
+    // The cost of a quantity, usually kWh, at a rate per unit
+    pub fn for_quantity(rate: Decimal, quantity: f32) -> Money {
+        Money::new(quantity_cost::<PricingNumber>(rate, quantity).to_decimal())
+    }
+
+    // Amounts of two currencies can't be added up, a zero takes the currency of the other
+    fn common_currency(self, rhs: Money) -> Currency {
+        if self.amount.is_zero() {
+            return rhs.currency;
+        }
+        if !rhs.amount.is_zero() && rhs.currency != self.currency {
+            panic!(
+                "can't add up amounts in {} and {}",
+                self.currency, rhs.currency
+            );
+        }
+        self.currency
+    }
+
+    pub fn round(self, rounding: &Rounding) -> Money {
+        let strategy = match rounding.strategy {
+            RoundingStrategy::HalfUp => rust_decimal::RoundingStrategy::MidpointAwayFromZero,
+            RoundingStrategy::Bankers => rust_decimal::RoundingStrategy::MidpointNearestEven,
+        };
+        Money::of(
+            self.amount
+                .round_dp_with_strategy(rounding.decimal_places, strategy),
+            self.currency,
+        )
+    }
+}
+
+// Prints like the plain number it wraps, so entries read as Debit(12.34)
+impl Debug for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.amount, f)
+    }
+}
+
+// Prints with the symbol of its currency and two decimal places unless asked for more, like
+// €843.23, £12.10 or {:.4} for €843.2341
+impl Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decimals = f.precision().unwrap_or(2);
+        let amount = self.amount.round_dp(decimals as u32);
+        let sign = if amount.is_sign_negative() && !amount.is_zero() {
+            "-"
+        } else {
+            ""
+        };
+        let symbol = self.currency.symbol();
+        write!(f, "{sign}{symbol}{:.decimals$}", amount.abs())
+    }
+}
+
+// A number rather than a string, so dashboards and scripts can use it as is
+impl Serialize for Money {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        rust_decimal::serde::float::serialize(&self.amount, serializer)
+    }
+}
+
+impl From<Decimal> for Money {
+    fn from(amount: Decimal) -> Self {
+        Money::new(amount)
+    }
+}
+
+impl Add for Money {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Money::of(self.amount + rhs.amount, self.common_currency(rhs))
+    }
+}
+
+impl Sub for Money {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Money::of(self.amount - rhs.amount, self.common_currency(rhs))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingStrategy {
+    // 0.005 becomes 0.01
+    #[default]
+    HalfUp,
+    // 0.005 becomes 0.00 and 0.015 becomes 0.02
+    Bankers,
+}
+
+// What the usage is rounded on before it is added up, the usage of every interval, of every
+// day or only the usage line of the bill
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingLevel {
+    Interval,
+    Day,
+    #[default]
+    Bill,
+}
+
+// How a plan rounds the lines of its bill
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Rounding {
+    pub decimal_places: u32,
+    #[serde(default)]
+    pub strategy: RoundingStrategy,
+    #[serde(default)]
+    pub per: RoundingLevel,
+}
+
+impl Default for Rounding {
+    fn default() -> Self {
+        Rounding {
+            decimal_places: 2,
+            strategy: RoundingStrategy::HalfUp,
+            per: RoundingLevel::Bill,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn halves_are_rounded_the_way_the_plan_says() {
+        let half_up = Rounding::default();
+        let bankers = Rounding {
+            strategy: RoundingStrategy::Bankers,
+            ..Rounding::default()
+        };
+        assert_eq!(
+            Money::new(dec!(0.005)).round(&half_up),
+            Money::new(dec!(0.01))
+        );
+        assert_eq!(
+            Money::new(dec!(0.005)).round(&bankers),
+            Money::new(dec!(0.00))
+        );
+        assert_eq!(
+            Money::new(dec!(0.015)).round(&bankers),
+            Money::new(dec!(0.02))
+        );
+        assert_eq!(
+            Money::new(dec!(-0.005)).round(&half_up),
+            Money::new(dec!(-0.01))
+        );
+        let three = Rounding {
+            decimal_places: 3,
+            ..Rounding::default()
+        };
+        assert_eq!(
+            Money::new(dec!(1.23456)).round(&three),
+            Money::new(dec!(1.235))
+        );
+        // the currency stays what it was
+        let pounds = Money::of(dec!(2.345), Currency::Gbp).round(&half_up);
+        assert_eq!(pounds.currency(), Currency::Gbp);
+    }
+
+    #[test]
+    fn amounts_of_one_currency_add_up() {
+        let pounds = Money::of(dec!(1.50), Currency::Gbp);
+        assert_eq!(pounds + pounds, Money::of(dec!(3.00), Currency::Gbp));
+        assert_eq!(pounds - pounds, Money::of(dec!(0), Currency::Gbp));
+        // a zero of any currency takes the currency of the other
+        assert_eq!(Money::ZERO + pounds, pounds);
+        assert_eq!(pounds + Money::ZERO, pounds);
+        assert_eq!((Money::ZERO - pounds).currency(), Currency::Gbp);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't add up amounts in EUR and GBP")]
+    fn amounts_of_two_currencies_do_not_add_up() {
+        let _ = Money::new(dec!(1)) + Money::of(dec!(1), Currency::Gbp);
+    }
+
+    #[test]
+    fn an_amount_is_put_in_another_currency_as_it_is() {
+        let euro = Money::new(dec!(12.10));
+        let pounds = euro.in_currency(Currency::Gbp);
+        assert_eq!(pounds.amount(), dec!(12.10));
+        assert_eq!(pounds.currency(), Currency::Gbp);
+        assert_eq!(pounds.to_string(), "£12.10");
+        assert_eq!(format!("{:.4}", Money::new(dec!(-843.23412))), "-€843.2341");
+    }
+}
@@ -0,0 +1,147 @@
+use serde::Serialize;
+
+use crate::{
+    breakdown_by_bucket, comparison::parallel_map, BillingPeriod, Bucket, BucketBreakdown,
+    EnergyBillEntry, Granularity, PlanComparison, PricePlanStrategy, SmartMeterData,
+};
+
+// The plan that was cheapest over one month of the period, and what the plan that was
+// cheapest over the whole period cost in it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MonthWinner {
+    pub month: Bucket,
+    // the part of the month in the period, the first and last months are usually partial
+    pub period: BillingPeriod,
+    pub plan: String,
+    pub total: EnergyBillEntry,
+    pub overall_total: EnergyBillEntry,
+}
+
+impl MonthWinner {
+    // what the month's plan saved over the plan of the whole period, zero when they're the same
+    pub fn saving(&self) -> EnergyBillEntry {
+        self.overall_total - self.total
+    }
+}
+
+// The months a plan was the cheapest in, a plan that only wins the summer months owes its
+// place to the season
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlanWins {
+    pub plan: String,
+    pub months: Vec<Bucket>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MonthlyWinners {
+    // the cheapest plan of the whole period
+    pub overall: String,
+    pub months: Vec<MonthWinner>,
+    // the months another plan was cheaper than the overall one
+    pub beaten: usize,
+    // most months first
+    pub wins: Vec<PlanWins>,
+}
+
+// Every plan priced month by month, with the cheapest plan of each month. The results are the
+// plans cheapest first, their buckets are used when they are already months. A tie goes to the
+// plan cheapest over the period
+pub fn monthly_winners(
+    plans: &[Box<dyn PricePlanStrategy>],
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+    results: &[PlanComparison],
+) -> Option<MonthlyWinners> {
+    let overall = results.first()?;
+    let by_month = parallel_map(results, |result| match result.buckets.first() {
+        Some(BucketBreakdown {
+            bucket: Bucket::Month { .. },
+            ..
+        }) => result.buckets.clone(),
+        _ => plans
+            .iter()
+            .find(|plan| plan.name() == result.name)
+            .map(|plan| breakdown_by_bucket(plan.as_ref(), datapoints, period, Granularity::Month))
+            .unwrap_or_default(),
+    });
+    let months: Vec<(&str, Vec<BucketBreakdown>)> = results
+        .iter()
+        .map(|result| result.name.as_str())
+        .zip(by_month)
+        .collect();
+    let (_, overall_months) = months.first()?;
+    let months: Vec<MonthWinner> = overall_months
+        .iter()
+        .enumerate()
+        .filter_map(|(index, month)| {
+            let (plan, total) = months
+                .iter()
+                .filter_map(|(name, buckets)| Some((*name, buckets.get(index)?.breakdown.total)))
+                .min_by_key(|(_, total)| *total)?;
+            Some(MonthWinner {
+                month: month.bucket,
+                period: month.period,
+                plan: plan.to_string(),
+                total,
+                overall_total: month.breakdown.total,
+            })
+        })
+        .collect();
+    let mut wins: Vec<PlanWins> = vec![];
+    for month in &months {
+        match wins.iter_mut().find(|wins| wins.plan == month.plan) {
+            Some(wins) => wins.months.push(month.month),
+            None => wins.push(PlanWins {
+                plan: month.plan.clone(),
+                months: vec![month.month],
+            }),
+        }
+    }
+    wins.sort_by_key(|wins| std::cmp::Reverse(wins.months.len()));
+    Some(MonthlyWinners {
+        overall: overall.name.clone(),
+        beaten: months
+            .iter()
+            .filter(|month| month.plan != overall.name)
+            .count(),
+        months,
+        wins,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builtin_plans, meter_data::reading_at, rank_plans};
+
+    #[test]
+    fn a_weekend_month_goes_to_the_weekend_plan() {
+        let plans = builtin_plans();
+        // january charges a car at night, february only uses power on sunday afternoons
+        let mut data: Vec<SmartMeterData> = (8..31)
+            .flat_map(|day| {
+                ["02:30", "03:00", "03:30", "04:00"]
+                    .map(|time| reading_at(&format!("{day:02}-01-2024 {time}"), 7.0))
+            })
+            .collect();
+        data.extend(
+            [4, 11, 18, 25]
+                .iter()
+                .flat_map(|day| (10..18).map(move |hour| (day, hour)))
+                .map(|(day, hour)| reading_at(&format!("{day:02}-02-2024 {hour}:00"), 4.0)),
+        );
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let results = rank_plans(&plans, &data, &period);
+        let winners = monthly_winners(&plans, &data, &period, &results).unwrap();
+        assert_eq!(winners.overall, results[0].name);
+        assert_eq!(winners.months.len(), 2);
+        let february = &winners.months[1];
+        assert_eq!(february.month.to_string(), "2024-02");
+        assert_eq!(february.plan, "BordGaisEnergy25WeekendFree");
+        assert_ne!(winners.months[0].plan, february.plan);
+        assert_eq!(winners.beaten, 1);
+        assert_eq!(winners.months[0].saving(), EnergyBillEntry::ZERO);
+        assert!(february.saving() > EnergyBillEntry::ZERO);
+        assert_eq!(winners.wins.len(), 2);
+    }
+}
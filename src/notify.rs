@@ -0,0 +1,371 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{ComparisonReport, EnergyBillEntry};
+
+// An email sent through an SMTP server by curl, like url = "smtps://smtp.example.com:465".
+// The password is read from the environment variable named by password_env so it isn't kept
+// in the file
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct EmailConfig {
+    pub url: String,
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password_env: Option<String>,
+}
+
+// Where word goes when the cheapest plan changes, read from a file like notify.example.toml.
+// The webhook gets the change as json
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Notifier {
+    #[serde(default)]
+    pub webhook: Option<String>,
+    #[serde(default)]
+    pub email: Option<EmailConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RankedPlan {
+    pub plan: String,
+    pub total: EnergyBillEntry,
+}
+
+// The cheapest plan of a meter point isn't the one it was, with what the new one saves over the
+// old one on the same readings and the plans cheapest first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheapestChange {
+    pub mprn: String,
+    pub previous: String,
+    pub cheapest: String,
+    pub saving: EnergyBillEntry,
+    pub ranking: Vec<RankedPlan>,
+}
+
+// The cheapest plan of every meter point seen so far
+#[derive(Debug, Default)]
+pub struct CheapestTracker {
+    cheapest: BTreeMap<String, String>,
+}
+
+impl CheapestTracker {
+    // The meter points whose cheapest plan changed since the last reports, the first reports of
+    // a meter point only set where it starts from
+    pub fn update(&mut self, reports: &[ComparisonReport]) -> Vec<CheapestChange> {
+        let mut changes = vec![];
+        for report in reports {
+            let Some(cheapest) = report.plans.first() else {
+                continue;
+            };
+            let previous = self
+                .cheapest
+                .insert(report.mprn.clone(), cheapest.name.clone());
+            let Some(previous) = previous.filter(|previous| *previous != cheapest.name) else {
+                continue;
+            };
+            // a plan that is gone saves nothing to speak of
+            let saving = report
+                .plans
+                .iter()
+                .find(|plan| plan.name == previous)
+                .map_or(EnergyBillEntry::ZERO, |plan| {
+                    plan.breakdown.total - cheapest.breakdown.total
+                });
+            changes.push(CheapestChange {
+                mprn: report.mprn.clone(),
+                previous,
+                cheapest: cheapest.name.clone(),
+                saving,
+                ranking: report
+                    .plans
+                    .iter()
+                    .map(|plan| RankedPlan {
+                        plan: plan.name.clone(),
+                        total: plan.breakdown.total,
+                    })
+                    .collect(),
+            });
+        }
+        changes
+    }
+}
+
+impl CheapestChange {
+    pub fn subject(&self) -> String {
+        format!(
+            "{} is now the cheapest plan for {}",
+            self.cheapest, self.mprn
+        )
+    }
+
+    // The email, the plans one per line cheapest first
+    pub fn message(&self) -> String {
+        let mut message = format!(
+            "{} is now cheaper than {} on the readings of {}, saving {} over the period.\n\n",
+            self.cheapest, self.previous, self.mprn, self.saving
+        );
+        for (index, plan) in self.ranking.iter().enumerate() {
+            message.push_str(&format!(
+                "{:>2}. {}: {}\n",
+                index + 1,
+                plan.plan,
+                plan.total
+            ));
+        }
+        message
+    }
+}
+
+fn run_curl(args: &[String], input: &[u8], what: &str) -> Result<()> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("could not run curl to send the {what}"))?;
+    child
+        .stdin
+        .take()
+        .context("curl has no stdin")?
+        .write_all(input)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "sending the {what} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+// A header is one line, a plan name or address with a line break in it can't add headers of
+// its own like a Bcc
+fn header_value(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).collect()
+}
+
+// The mail as curl uploads it, the lines of the message end in CRLF. The names of the plans
+// lose their control characters in the body too, so each stays on its line
+fn mail_text(email: &EmailConfig, change: &CheapestChange) -> String {
+    let change = CheapestChange {
+        mprn: header_value(&change.mprn),
+        previous: header_value(&change.previous),
+        cheapest: header_value(&change.cheapest),
+        saving: change.saving,
+        ranking: change
+            .ranking
+            .iter()
+            .map(|plan| RankedPlan {
+                plan: header_value(&plan.plan),
+                total: plan.total,
+            })
+            .collect(),
+    };
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}",
+        header_value(&email.from),
+        header_value(&email.to.join(", ")),
+        header_value(&change.subject()),
+        change.message().replace('\n', "\r\n")
+    )
+}
+
+// Emails sent so far by this process, so the ones sent at the same time get files of their own
+static MAIL_COUNT: AtomicU64 = AtomicU64::new(0);
+
+// A new file for a mail, never one another send is still using
+fn new_mail_file() -> Result<(PathBuf, File)> {
+    loop {
+        let count = MAIL_COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!(
+            "energy_comparator-mail-{}-{count}.eml",
+            std::process::id()
+        ));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            // left over from a process that had the same id
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).context("could not write the email"),
+        }
+    }
+}
+
+impl Notifier {
+    pub fn load(filename: &Path) -> Result<Notifier> {
+        let contents = std::fs::read_to_string(filename)?;
+        let notifier: Notifier = toml::from_str(&contents).map_err(|e| {
+            anyhow::anyhow!("invalid notification file {}: {e}", filename.display())
+        })?;
+        if notifier.webhook.is_none() && notifier.email.is_none() {
+            bail!(
+                "the notification file {} has neither a webhook nor an email",
+                filename.display()
+            );
+        }
+        Ok(notifier)
+    }
+
+    // Sends the change everywhere the file says, one failing doesn't stop the other
+    pub fn send(&self, change: &CheapestChange) -> Result<()> {
+        let mut failures = vec![];
+        if let Some(webhook) = &self.webhook {
+            let body = serde_json::to_vec(change)?;
+            let args = [
+                "--header".to_string(),
+                "Content-Type: application/json".to_string(),
+                "--data-binary".to_string(),
+                "@-".to_string(),
+                webhook.clone(),
+            ];
+            if let Err(e) = run_curl(&args, &body, "webhook") {
+                failures.push(e.to_string());
+            }
+        }
+        if let Some(email) = &self.email {
+            if let Err(e) = self.send_email(email, change) {
+                failures.push(e.to_string());
+            }
+        }
+        if !failures.is_empty() {
+            bail!("{}", failures.join(", "));
+        }
+        Ok(())
+    }
+
+    // The mail goes in a file of its own so the login can go in through stdin, the password
+    // never shows up in the list of processes
+    fn send_email(&self, email: &EmailConfig, change: &CheapestChange) -> Result<()> {
+        let mail = mail_text(email, change);
+        let (mail_file, mut file) = new_mail_file()?;
+        let written = file.write_all(mail.as_bytes());
+        drop(file);
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&mail_file);
+            return Err(e.into());
+        }
+        let mut args = vec![
+            "--ssl-reqd".to_string(),
+            "--url".to_string(),
+            email.url.clone(),
+            "--mail-from".to_string(),
+            email.from.clone(),
+            "--upload-file".to_string(),
+            mail_file.display().to_string(),
+            "--config".to_string(),
+            "-".to_string(),
+        ];
+        for to in &email.to {
+            args.extend(["--mail-rcpt".to_string(), to.clone()]);
+        }
+        let mut config = String::new();
+        if let Some(username) = &email.username {
+            let password = match &email.password_env {
+                Some(name) => std::env::var(name)
+                    .with_context(|| format!("the SMTP password is meant to be in {name}"))?,
+                None => String::new(),
+            };
+            let quoted = format!("{username}:{password}")
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            config = format!("user = \"{quoted}\"\n");
+        }
+        let sent = run_curl(&args, config.as_bytes(), "email");
+        let _ = std::fs::remove_file(&mail_file);
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin_plans, compare_meter_points, meter_data::reading_at, ComparisonOptions,
+        SmartMeterData,
+    };
+
+    #[test]
+    fn a_plan_name_can_not_add_mail_headers() {
+        let email = EmailConfig {
+            url: "smtps://smtp.example.com:465".to_string(),
+            from: "me@example.com".to_string(),
+            to: vec!["you@example.com".to_string()],
+            username: None,
+            password_env: None,
+        };
+        let change = CheapestChange {
+            mprn: "10000000000".to_string(),
+            previous: "Old".to_string(),
+            cheapest: "Cheap\r\nBcc: everyone@example.com".to_string(),
+            saving: EnergyBillEntry::ZERO,
+            ranking: vec![],
+        };
+        let mail = mail_text(&email, &change);
+        let (headers, body) = mail.split_once("\r\n\r\n").unwrap();
+        assert_eq!(headers.lines().count(), 4);
+        assert!(!headers.contains("\r\nBcc:"));
+        assert!(!body.contains("\r\nBcc:"));
+    }
+
+    #[test]
+    fn every_mail_gets_a_file_of_its_own() {
+        let (first, _) = new_mail_file().unwrap();
+        let (second, _) = new_mail_file().unwrap();
+        assert_ne!(first, second);
+        std::fs::remove_file(first).unwrap();
+        std::fs::remove_file(second).unwrap();
+    }
+
+    #[test]
+    fn only_a_new_cheapest_plan_is_a_change() {
+        let plans = builtin_plans();
+        let compare = |data: Vec<SmartMeterData>| {
+            compare_meter_points(&plans, data, &ComparisonOptions::default()).unwrap()
+        };
+        // a car charged in the boost window and then a sunday, when the weekend plan is free
+        let night: Vec<SmartMeterData> = ["02:30", "03:00", "03:30", "04:00"]
+            .iter()
+            .map(|time| reading_at(&format!("08-01-2024 {time}"), 7.0))
+            .collect();
+        let mut tracker = CheapestTracker::default();
+        assert!(tracker.update(&compare(night.clone())).is_empty());
+        assert!(tracker.update(&compare(night)).is_empty());
+
+        let sunday: Vec<SmartMeterData> = (10..18)
+            .map(|hour| reading_at(&format!("14-01-2024 {hour}:00"), 4.0))
+            .collect();
+        let changes = tracker.update(&compare(sunday));
+        assert_eq!(changes.len(), 1, "{changes:?}");
+        let change = &changes[0];
+        assert_eq!(change.cheapest, "BordGaisEnergy25WeekendFree");
+        assert_ne!(change.previous, change.cheapest);
+        assert!(change.saving.signed_amount() > rust_decimal::Decimal::ZERO);
+        assert_eq!(change.ranking[0].plan, change.cheapest);
+        assert_eq!(change.ranking.len(), plans.len());
+        assert!(change
+            .message()
+            .contains(" 1. BordGaisEnergy25WeekendFree: €"));
+
+        let notifier: Notifier = toml::from_str(
+            r#"
+            [email]
+            url = "smtps://smtp.example.com:465"
+            from = "meter@example.com"
+            to = ["me@example.com"]
+            "#,
+        )
+        .unwrap();
+        assert!(notifier.webhook.is_none());
+    }
+}
@@ -0,0 +1,106 @@
+use rust_decimal::{
+    prelude::{FromPrimitive, ToPrimitive},
+    Decimal,
+};
+use std::{
+    fmt::Debug,
+    iter::Sum,
+    ops::{Add, Div, Mul, Sub},
+};
+
+// The numbers the cost of a reading is worked out in. The rates of the plans are applied
+// through quantity_cost and swap_vat and the kWh they count up are added in it, so they run on
+// either. Each priced reading is a decimal again, only the arithmetic of a reading changes
+pub trait Numeric:
+    Copy
+    + Default
+    + PartialOrd
+    + Debug
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Sum
+    + Send
+    + Sync
+{
+    const ONE: Self;
+
+    fn from_decimal(value: Decimal) -> Self;
+
+    fn from_f32(value: f32) -> Self;
+
+    fn to_decimal(self) -> Decimal;
+}
+
+impl Numeric for Decimal {
+    const ONE: Self = Decimal::ONE;
+
+    fn from_decimal(value: Decimal) -> Self {
+        value
+    }
+
+    fn from_f32(value: f32) -> Self {
+        <Decimal as FromPrimitive>::from_f32(value).unwrap_or_default()
+    }
+
+    fn to_decimal(self) -> Decimal {
+        self
+    }
+}
+
+impl Numeric for f64 {
+    const ONE: Self = 1.0;
+
+    fn from_decimal(value: Decimal) -> Self {
+        value.to_f64().unwrap_or_default()
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value as f64
+    }
+
+    fn to_decimal(self) -> Decimal {
+        Decimal::from_f64(self).unwrap_or_default()
+    }
+}
+
+// What the readings are priced in, floats in the browser build and exact decimals everywhere
+// else. Either way the amounts are decimals once priced
+#[cfg(not(target_arch = "wasm32"))]
+pub type PricingNumber = Decimal;
+#[cfg(target_arch = "wasm32")]
+pub type PricingNumber = f64;
+
+// The cost of a quantity, usually kWh, at a rate per unit
+pub fn quantity_cost<N: Numeric>(rate: Decimal, quantity: f32) -> N {
+    N::from_decimal(rate) * N::from_f32(quantity)
+}
+
+// The amount with the VAT it includes swapped for the rate
+pub fn swap_vat<N: Numeric>(amount: N, included: Decimal, rate: Decimal) -> N {
+    amount * (N::ONE + N::from_decimal(rate)) / (N::ONE + N::from_decimal(included))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn floats_and_decimals_price_to_the_same_cent() {
+        let price = |quantity: f32| -> (Decimal, Decimal) {
+            let exact: Decimal =
+                swap_vat(quantity_cost(dec!(0.3865), quantity), dec!(0), dec!(0.09));
+            let float: f64 = swap_vat(quantity_cost(dec!(0.3865), quantity), dec!(0), dec!(0.09));
+            (exact, float.to_decimal())
+        };
+        // a year of half hours at a quarter kWh each
+        let (exact, float) = (0..17520).fold((Decimal::ZERO, Decimal::ZERO), |total, _| {
+            let (exact, float) = price(0.25);
+            (total.0 + exact, total.1 + float)
+        });
+        assert_eq!(exact.round_dp(2), dec!(1845.23));
+        assert_eq!(float.round_dp(2), exact.round_dp(2));
+    }
+}
@@ -0,0 +1,478 @@
+use std::io::{self, Write};
+
+// Just enough of parquet to write a table: required columns of plain values, uncompressed, in a
+// single row group, which every reader understands. The metadata is thrift in its compact
+// protocol, see https://parquet.apache.org/docs/file-format/
+const MAGIC: &[u8] = b"PAR1";
+
+// the types of the compact protocol
+const BOOL_TRUE: u8 = 1;
+const BOOL_FALSE: u8 = 2;
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+// the physical types and the encodings of parquet
+const TYPE_INT32: i32 = 1;
+const TYPE_INT64: i32 = 2;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+
+// One column of the table, every column has a value for every row
+pub(crate) enum Column {
+    Strings(&'static str, Vec<String>),
+    // microseconds since 1970 in UTC
+    Timestamps(&'static str, Vec<i64>),
+    Doubles(&'static str, Vec<f64>),
+    Ints(&'static str, Vec<i32>),
+}
+
+impl Column {
+    fn name(&self) -> &'static str {
+        match self {
+            Column::Strings(name, _)
+            | Column::Timestamps(name, _)
+            | Column::Doubles(name, _)
+            | Column::Ints(name, _) => name,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Column::Strings(_, values) => values.len(),
+            Column::Timestamps(_, values) => values.len(),
+            Column::Doubles(_, values) => values.len(),
+            Column::Ints(_, values) => values.len(),
+        }
+    }
+
+    fn physical_type(&self) -> i32 {
+        match self {
+            Column::Strings(..) => TYPE_BYTE_ARRAY,
+            Column::Timestamps(..) => TYPE_INT64,
+            Column::Doubles(..) => TYPE_DOUBLE,
+            Column::Ints(..) => TYPE_INT32,
+        }
+    }
+
+    // the values one after the other in the plain encoding, strings with their length first
+    fn plain(&self) -> Vec<u8> {
+        let mut out = vec![];
+        match self {
+            Column::Strings(_, values) => {
+                for value in values {
+                    out.extend((value.len() as u32).to_le_bytes());
+                    out.extend(value.as_bytes());
+                }
+            }
+            Column::Timestamps(_, values) => {
+                values.iter().for_each(|v| out.extend(v.to_le_bytes()))
+            }
+            Column::Doubles(_, values) => values.iter().for_each(|v| out.extend(v.to_le_bytes())),
+            Column::Ints(_, values) => values.iter().for_each(|v| out.extend(v.to_le_bytes())),
+        }
+        out
+    }
+
+    // the schema element, strings are utf8 and timestamps in microseconds
+    fn write_schema(&self, thrift: &mut Thrift) {
+        thrift.begin_element();
+        thrift.i32(1, self.physical_type());
+        thrift.i32(3, 0);
+        thrift.binary(4, self.name().as_bytes());
+        match self {
+            Column::Strings(..) => {
+                thrift.i32(6, 0);
+                thrift.begin(10);
+                thrift.begin(1);
+                thrift.end();
+                thrift.end();
+            }
+            Column::Timestamps(..) => {
+                thrift.i32(6, 10);
+                thrift.begin(10);
+                thrift.begin(8);
+                thrift.bool(1, true);
+                thrift.begin(2);
+                thrift.begin(2);
+                thrift.end();
+                thrift.end();
+                thrift.end();
+                thrift.end();
+            }
+            Column::Doubles(..) | Column::Ints(..) => {}
+        }
+        thrift.end();
+    }
+}
+
+// Writes the compact protocol, field ids are written as the difference to the one before in
+// the same struct
+struct Thrift {
+    out: Vec<u8>,
+    last: Vec<i16>,
+}
+
+impl Thrift {
+    fn new() -> Thrift {
+        Thrift {
+            out: vec![],
+            last: vec![0],
+        }
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.out.push(value as u8);
+    }
+
+    fn zigzag(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, kind: u8) {
+        let last = self
+            .last
+            .last_mut()
+            .expect("a struct to write the field in");
+        let delta = id - std::mem::replace(last, id);
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | kind);
+        } else {
+            self.out.push(kind);
+            self.zigzag(id as i64);
+        }
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, I32);
+        self.zigzag(value as i64);
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, I64);
+        self.zigzag(value);
+    }
+
+    fn bool(&mut self, id: i16, value: bool) {
+        self.field(id, if value { BOOL_TRUE } else { BOOL_FALSE });
+    }
+
+    fn binary(&mut self, id: i16, bytes: &[u8]) {
+        self.field(id, BINARY);
+        self.varint(bytes.len() as u64);
+        self.out.extend(bytes);
+    }
+
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | kind);
+        } else {
+            self.out.push(0xf0 | kind);
+            self.varint(len as u64);
+        }
+    }
+
+    fn begin(&mut self, id: i16) {
+        self.field(id, STRUCT);
+        self.begin_element();
+    }
+
+    // a struct in a list, which has no field header
+    fn begin_element(&mut self) {
+        self.last.push(0);
+    }
+
+    fn end(&mut self) {
+        self.out.push(0);
+        self.last.pop();
+    }
+}
+
+// The page header of a column of values, the values follow it
+fn page_header(values: usize, size: usize) -> Vec<u8> {
+    let mut thrift = Thrift::new();
+    thrift.i32(1, 0);
+    thrift.i32(2, size as i32);
+    thrift.i32(3, size as i32);
+    thrift.begin(5);
+    thrift.i32(1, values as i32);
+    thrift.i32(2, PLAIN);
+    thrift.i32(3, RLE);
+    thrift.i32(4, RLE);
+    thrift.end();
+    thrift.end();
+    thrift.out
+}
+
+pub(crate) fn write_parquet(columns: &[Column], mut writer: impl Write) -> io::Result<()> {
+    let rows = columns.first().map_or(0, Column::len);
+    assert!(
+        columns.iter().all(|column| column.len() == rows),
+        "every column needs a value for every row"
+    );
+    writer.write_all(MAGIC)?;
+    let mut offset = MAGIC.len();
+    // where each column starts and how long it is
+    let mut chunks = vec![];
+    for column in columns {
+        let values = column.plain();
+        let header = page_header(column.len(), values.len());
+        writer.write_all(&header)?;
+        writer.write_all(&values)?;
+        chunks.push((offset, header.len() + values.len()));
+        offset += header.len() + values.len();
+    }
+
+    let mut thrift = Thrift::new();
+    thrift.i32(1, 1);
+    thrift.list(2, STRUCT, columns.len() + 1);
+    thrift.begin_element();
+    thrift.binary(4, b"schema");
+    thrift.i32(5, columns.len() as i32);
+    thrift.end();
+    for column in columns {
+        column.write_schema(&mut thrift);
+    }
+    thrift.i64(3, rows as i64);
+    thrift.list(4, STRUCT, 1);
+    thrift.begin_element();
+    thrift.list(1, STRUCT, columns.len());
+    for (column, &(start, size)) in columns.iter().zip(&chunks) {
+        thrift.begin_element();
+        thrift.i64(2, start as i64);
+        thrift.begin(3);
+        thrift.i32(1, column.physical_type());
+        thrift.list(2, I32, 2);
+        thrift.zigzag(PLAIN as i64);
+        thrift.zigzag(RLE as i64);
+        thrift.list(3, BINARY, 1);
+        thrift.varint(column.name().len() as u64);
+        thrift.out.extend(column.name().as_bytes());
+        thrift.i32(4, 0);
+        thrift.i64(5, rows as i64);
+        thrift.i64(6, size as i64);
+        thrift.i64(7, size as i64);
+        thrift.i64(9, start as i64);
+        thrift.end();
+        thrift.end();
+    }
+    thrift.i64(2, chunks.iter().map(|&(_, size)| size as i64).sum());
+    thrift.i64(3, rows as i64);
+    thrift.end();
+    thrift.binary(6, b"energy_comparator");
+    thrift.end();
+
+    writer.write_all(&thrift.out)?;
+    writer.write_all(&(thrift.out.len() as u32).to_le_bytes())?;
+    writer.write_all(MAGIC)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    // What a reader makes of the compact protocol, the fields of a struct by their id
+    #[derive(Debug, Clone, PartialEq)]
+    enum Value {
+        Bool(bool),
+        Int(i64),
+        Binary(Vec<u8>),
+        List(Vec<Value>),
+        Struct(BTreeMap<i16, Value>),
+    }
+
+    impl Value {
+        fn field(&self, id: i16) -> &Value {
+            match self {
+                Value::Struct(fields) => fields.get(&id).unwrap_or_else(|| panic!("no field {id}")),
+                other => panic!("{other:?} is not a struct"),
+            }
+        }
+
+        fn int(&self, id: i16) -> i64 {
+            match self.field(id) {
+                Value::Int(value) => *value,
+                other => panic!("field {id} is {other:?}"),
+            }
+        }
+
+        fn text(&self, id: i16) -> String {
+            match self.field(id) {
+                Value::Binary(bytes) => String::from_utf8(bytes.clone()).unwrap(),
+                other => panic!("field {id} is {other:?}"),
+            }
+        }
+
+        fn items(&self, id: i16) -> &[Value] {
+            match self.field(id) {
+                Value::List(items) => items,
+                other => panic!("field {id} is {other:?}"),
+            }
+        }
+    }
+
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        at: usize,
+    }
+
+    impl Reader<'_> {
+        fn byte(&mut self) -> u8 {
+            self.at += 1;
+            self.bytes[self.at - 1]
+        }
+
+        fn varint(&mut self) -> u64 {
+            let mut value = 0;
+            for shift in (0..).step_by(7) {
+                let byte = self.byte();
+                value |= u64::from(byte & 0x7f) << shift;
+                if byte < 0x80 {
+                    break;
+                }
+            }
+            value
+        }
+
+        fn zigzag(&mut self) -> i64 {
+            let value = self.varint();
+            (value >> 1) as i64 ^ -((value & 1) as i64)
+        }
+
+        fn value(&mut self, kind: u8) -> Value {
+            match kind {
+                BOOL_TRUE => Value::Bool(true),
+                BOOL_FALSE => Value::Bool(false),
+                I32 | I64 => Value::Int(self.zigzag()),
+                BINARY => {
+                    let len = self.varint() as usize;
+                    self.at += len;
+                    Value::Binary(self.bytes[self.at - len..self.at].to_vec())
+                }
+                LIST => {
+                    let header = self.byte();
+                    let len = match header >> 4 {
+                        15 => self.varint() as usize,
+                        len => len as usize,
+                    };
+                    Value::List((0..len).map(|_| self.value(header & 0x0f)).collect())
+                }
+                STRUCT => self.structure(),
+                kind => panic!("unexpected type {kind}"),
+            }
+        }
+
+        fn structure(&mut self) -> Value {
+            let mut fields = BTreeMap::new();
+            let mut last = 0;
+            loop {
+                let header = self.byte();
+                if header == 0 {
+                    return Value::Struct(fields);
+                }
+                last = match header >> 4 {
+                    0 => self.zigzag() as i16,
+                    delta => last + delta as i16,
+                };
+                fields.insert(last, self.value(header & 0x0f));
+            }
+        }
+    }
+
+    #[test]
+    fn the_metadata_and_the_pages_read_back() {
+        let columns = [
+            Column::Strings("mprn", vec!["10000000000".to_string(), "2".to_string()]),
+            Column::Timestamps("end_time", vec![1_704_684_600_000_000, -1]),
+            Column::Doubles("kwh", vec![0.5, 1.25]),
+            Column::Ints("interval_minutes", vec![30, 15]),
+        ];
+        let mut file = vec![];
+        write_parquet(&columns, &mut file).unwrap();
+        assert!(file.starts_with(MAGIC) && file.ends_with(MAGIC));
+        let footer = u32::from_le_bytes(file[file.len() - 8..][..4].try_into().unwrap()) as usize;
+        let metadata_start = file.len() - 8 - footer;
+        let mut reader = Reader {
+            bytes: &file[..file.len() - 8],
+            at: metadata_start,
+        };
+        let metadata = reader.structure();
+        // nothing is left over between the metadata and its length
+        assert_eq!(reader.at, file.len() - 8);
+
+        assert_eq!(metadata.int(1), 1);
+        assert_eq!(metadata.int(3), 2);
+        assert_eq!(metadata.text(6), "energy_comparator");
+        let schema = metadata.items(2);
+        assert_eq!(schema.len(), 5);
+        assert_eq!(schema[0].text(4), "schema");
+        assert_eq!(schema[0].int(5), 4);
+        let described: Vec<(String, i64, i64)> = schema[1..]
+            .iter()
+            .map(|element| (element.text(4), element.int(1), element.int(3)))
+            .collect();
+        assert_eq!(
+            described,
+            vec![
+                ("mprn".to_string(), i64::from(TYPE_BYTE_ARRAY), 0),
+                ("end_time".to_string(), i64::from(TYPE_INT64), 0),
+                ("kwh".to_string(), i64::from(TYPE_DOUBLE), 0),
+                ("interval_minutes".to_string(), i64::from(TYPE_INT32), 0),
+            ]
+        );
+        // utf8 strings and timestamps in microseconds
+        assert_eq!(schema[1].int(6), 0);
+        assert_eq!(schema[2].int(6), 10);
+        // and plain numbers
+        for element in &schema[3..] {
+            assert!(matches!(element, Value::Struct(fields) if !fields.contains_key(&6)));
+        }
+
+        let row_groups = metadata.items(4);
+        assert_eq!(row_groups.len(), 1);
+        assert_eq!(row_groups[0].int(3), 2);
+        let chunks = row_groups[0].items(1);
+        assert_eq!(chunks.len(), columns.len());
+        let mut next = MAGIC.len();
+        for (chunk, column) in chunks.iter().zip(&columns) {
+            let meta = chunk.field(3);
+            assert_eq!(meta.int(1), i64::from(column.physical_type()));
+            assert_eq!(
+                meta.items(3),
+                &[Value::Binary(column.name().as_bytes().to_vec())]
+            );
+            assert_eq!(meta.int(5), 2);
+            let start = meta.int(9) as usize;
+            assert_eq!(start, next);
+            assert_eq!(chunk.int(2) as usize, start);
+
+            let mut page = Reader {
+                bytes: &file,
+                at: start,
+            };
+            let header = page.structure();
+            assert_eq!(header.int(1), 0);
+            assert_eq!(header.field(5).int(1), 2);
+            assert_eq!(header.field(5).int(2), i64::from(PLAIN));
+            let size = header.int(3) as usize;
+            let values = &file[page.at..page.at + size];
+            assert_eq!(values, column.plain().as_slice());
+            assert_eq!(meta.int(6) as usize, page.at + size - start);
+            next = page.at + size;
+        }
+        // the pages are followed by the metadata
+        assert_eq!(next, metadata_start);
+        assert_eq!(row_groups[0].int(2) as usize, next - MAGIC.len());
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    builtin_plans::{PEAK_ENERGY_END_TIME, PEAK_ENERGY_START_TIME},
+    time_in_window, EnergyBillEntry, PricePlanStrategy, SmartMeterData, SmartMeterDataType,
+};
+
+// One of the intervals the house drew the most in, at the local end of the interval
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeakInterval {
+    pub mprn: String,
+    pub end: NaiveDateTime,
+    pub kw: f32,
+    pub kwh: f32,
+}
+
+// The highest average power of any interval of the imports, the first being the maximum demand
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeakDemand {
+    pub top: Vec<PeakInterval>,
+}
+
+impl PeakDemand {
+    pub fn max_kw(&self) -> f32 {
+        self.top.first().map_or(0.0, |peak| peak.kw)
+    }
+}
+
+// What a plan charged for the imports of its peak, the share is of all the imports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PeakCost {
+    pub cost: EnergyBillEntry,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub share: Decimal,
+}
+
+// The count intervals of the imports with the highest average power, highest first
+pub fn peak_demand(datapoints: &[SmartMeterData], count: usize) -> PeakDemand {
+    let mut intervals: Vec<PeakInterval> = datapoints
+        .iter()
+        .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+        .map(|d| PeakInterval {
+            mprn: d.mprn.to_string(),
+            end: d.local_end_time(),
+            kw: d.energy_kwh() * 60.0 / d.interval_minutes as f32,
+            kwh: d.energy_kwh(),
+        })
+        .collect();
+    intervals.sort_by(|a, b| b.kw.total_cmp(&a.kw).then(a.end.cmp(&b.end)));
+    intervals.truncate(count);
+    PeakDemand { top: intervals }
+}
+
+// A band of the plan that is its peak, off peak bands aren't
+fn is_peak_band(band: &str) -> bool {
+    let band = band.to_lowercase();
+    band.contains("peak") && !band.contains("off")
+}
+
+// The cost of the readings a plan priced under its peak band, or of the readings of the
+// standard 17:00 to 19:00 peak for a plan without one, so flat plans can be compared too
+pub fn peak_cost(plan: &dyn PricePlanStrategy, datapoints: &[SmartMeterData]) -> PeakCost {
+    let bands: Vec<String> = datapoints
+        .iter()
+        .map(|d| plan.band_for_singe_period(d))
+        .collect();
+    let has_peak = bands.iter().any(|band| is_peak_band(band));
+    let mut peak = EnergyBillEntry::ZERO;
+    let mut usage = EnergyBillEntry::ZERO;
+    for ((datapoint, price), band) in datapoints
+        .iter()
+        .zip(plan.price_readings(datapoints))
+        .zip(&bands)
+    {
+        if datapoint.read_type != SmartMeterDataType::ActiveImport {
+            continue;
+        }
+        usage += price;
+        let in_peak = if has_peak {
+            is_peak_band(band)
+        } else {
+            time_in_window(
+                datapoint.local_end_time().time(),
+                PEAK_ENERGY_START_TIME,
+                PEAK_ENERGY_END_TIME,
+            )
+        };
+        if in_peak {
+            peak += price;
+        }
+    }
+    let share = if usage.signed_amount().is_zero() {
+        Decimal::ZERO
+    } else {
+        (peak.signed_amount() / usage.signed_amount() * Decimal::ONE_HUNDRED).round_dp(2)
+    };
+    PeakCost {
+        cost: peak.in_currency(plan.currency()).round(&plan.rounding()),
+        share,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{electric_ireland_home_electric_14, meter_data::reading_at, sse_airtricity_20};
+
+    #[test]
+    fn the_peak_is_the_highest_power_and_its_cost() {
+        let data = vec![
+            reading_at("08-01-2024 12:00", 1.0),
+            reading_at("08-01-2024 18:00", 4.0),
+            reading_at("08-01-2024 18:30", 4.0),
+            reading_at("08-01-2024 03:00", 2.0),
+        ];
+        let demand = peak_demand(&data, 2);
+        assert_eq!(demand.max_kw(), 4.0);
+        assert_eq!(demand.top[1].end.format("%H:%M").to_string(), "18:30");
+
+        // a flat plan is charged the same everywhere, so the standard peak has its share
+        // of the energy, 4 of the 5.5 kWh
+        let flat = peak_cost(&electric_ireland_home_electric_14(), &data);
+        assert!((flat.share - Decimal::new(7273, 2)).abs() < Decimal::ONE);
+        // the peak rate is above the average on a time of use plan
+        let time_of_use = peak_cost(&sse_airtricity_20(), &data);
+        assert!(time_of_use.share > flat.share);
+    }
+}
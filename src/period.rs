@@ -0,0 +1,108 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::SmartMeterData;
+
+// The calendar days a bill covers, both ends inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BillingPeriod {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl BillingPeriod {
+    // The span between the first and the last reading, days missing in the middle still pay
+    // the standing charge so they are counted too
+    pub fn from_data(datapoints: &[SmartMeterData]) -> Option<BillingPeriod> {
+        let start = datapoints.iter().map(|d| d.date()).min()?;
+        let end = datapoints.iter().map(|d| d.date()).max()?;
+        Some(BillingPeriod { start, end })
+    }
+
+    pub fn starting_at(start: NaiveDate, days: u32) -> BillingPeriod {
+        BillingPeriod {
+            start,
+            end: start + Duration::days(days as i64 - 1),
+        }
+    }
+
+    pub fn number_of_days(&self) -> u32 {
+        ((self.end - self.start).num_days() + 1).max(0) as u32
+    }
+
+    pub fn days(&self) -> impl Iterator<Item = NaiveDate> {
+        let end = self.end;
+        self.start.iter_days().take_while(move |d| *d <= end)
+    }
+}
+
+pub fn days_in_year(year: i32) -> u32 {
+    if NaiveDate::from_ymd_opt(year, 2, 29).is_some() {
+        366
+    } else {
+        365
+    }
+}
+
+// Annual charges are spread over the days of the year the day falls in, so leap years cost the same
+pub fn annual_to_daily(annual: Decimal, date: NaiveDate) -> Decimal {
+    annual / Decimal::from(days_in_year(date.year()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn leap_years_have_a_day_more_and_cost_the_same() {
+        assert_eq!(days_in_year(2024), 366);
+        assert_eq!(days_in_year(2023), 365);
+        let leap = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        let other = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        assert_eq!(annual_to_daily(dec!(366), leap), dec!(1));
+        assert_eq!(annual_to_daily(dec!(365), other), dec!(1));
+        let year = BillingPeriod::starting_at(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), 366);
+        let total: Decimal = year
+            .days()
+            .map(|date| annual_to_daily(dec!(200), date))
+            .sum();
+        assert_eq!(total.round_dp(10), dec!(200));
+    }
+
+    #[test]
+    fn a_day_of_readings_is_a_period_of_one_day() {
+        let data = vec![
+            reading_at("15-07-2024 08:00", 2.0),
+            reading_at("15-07-2024 18:00", 1.0),
+        ];
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let day = NaiveDate::from_ymd_opt(2024, 7, 15).unwrap();
+        assert_eq!(
+            period,
+            BillingPeriod {
+                start: day,
+                end: day
+            }
+        );
+        assert_eq!(period.number_of_days(), 1);
+        assert_eq!(period.days().collect::<Vec<_>>(), [day]);
+        assert_eq!(BillingPeriod::starting_at(day, 1), period);
+        assert!(BillingPeriod::from_data(&[]).is_none());
+    }
+
+    #[test]
+    fn both_ends_of_a_period_are_counted() {
+        let data = vec![
+            reading_at("01-02-2024 12:00", 1.0),
+            reading_at("01-03-2024 12:00", 1.0),
+        ];
+        // february of a leap year and the first of march, with nothing read in between
+        assert_eq!(
+            BillingPeriod::from_data(&data).unwrap().number_of_days(),
+            30
+        );
+    }
+}
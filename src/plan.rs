@@ -0,0 +1,363 @@
+use chrono::{NaiveDate, NaiveTime};
+use rust_decimal::Decimal;
+use std::{collections::BTreeMap, fmt::Debug};
+
+use crate::{
+    with_vat_on, BillBreakdown, BillingPeriod, CreditSchedule, Currency, Discount, DuosGroup,
+    EnergyBillEntry, ExportRules, FreeTime, FreeWindow, HolidayCalendar, MaximumImportCapacity,
+    Money, PlanMetadata, PsoLevy, Rounding, RoundingLevel, SmartMeterData, SmartMeterDataType,
+    DEFAULT_DISCOUNT_MONTHS, QUOTED_VAT_RATE,
+};
+
+// The fixed daily cost of a plan, shared by electricity and gas plans
+pub trait StandingCharge: Debug {
+    fn standing_charge_per_day(&self, date: NaiveDate, group: DuosGroup) -> EnergyBillEntry;
+
+    // The VAT rate already in the figures of the plan, zero when they are before VAT.
+    // Every charge is repriced with the rate in force on its day, see with_vat_on
+    fn vat_included(&self) -> Decimal {
+        QUOTED_VAT_RATE
+    }
+
+    // What the plan is priced in, the rates are in it and so is its bill. Only plans in euro
+    // pay the Irish VAT, the others keep the VAT they were quoted with
+    fn currency(&self) -> Currency {
+        Currency::Eur
+    }
+
+    // A credit per day, like a daily rebate, comes off the standing charge of the other days.
+    // Plans are charged the urban standing charge unless they are put in a group, see
+    // InDuosGroup
+    fn standing_charge_for_period(&self, period: &BillingPeriod) -> EnergyBillEntry {
+        period
+            .days()
+            .map(|date| {
+                let charge = self.standing_charge_per_day(date, DuosGroup::Urban);
+                with_vat_on(charge, self.vat_included(), date, self.currency())
+            })
+            .sum()
+    }
+}
+
+// Defines the signature for the functions to define the price for a plan, plans are priced
+// on several threads at once so they have to be shareable
+pub trait PricePlanStrategy: StandingCharge + Send + Sync {
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry;
+
+    // The band of the tariff the datapoint is priced under, like day, night or peak
+    fn band_for_singe_period(&self, datapoint: &SmartMeterData) -> String {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => "standard".to_string(),
+            SmartMeterDataType::ActiveExport => "export".to_string(),
+        }
+    }
+
+    // Plans that price public holidays like a weekend ask this, see HolidayCalendar::install
+    fn is_holiday(&self, date: NaiveDate) -> bool {
+        HolidayCalendar::global().is_holiday(date)
+    }
+
+    // Applied to each line of the bill, the total is the sum of the rounded lines like on a real bill
+    fn rounding(&self) -> Rounding {
+        Rounding::default()
+    }
+
+    // The name used to select the plan on the command line and to show it on the output
+    fn name(&self) -> String {
+        format!("{self:?}")
+    }
+
+    // Whether the plan goes by the name, its own or one of the aliases in its metadata
+    fn is_called(&self, name: &str) -> bool {
+        self.name().eq_ignore_ascii_case(name)
+            || self
+                .metadata()
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+
+    // Plans whose price depends on how much was already imported in the period, like tiered
+    // plans, override this, imported_kwh is the energy imported by the readings before this one
+    fn price_with_accumulated_usage(
+        &self,
+        datapoint: &SmartMeterData,
+        _imported_kwh: f32,
+    ) -> EnergyBillEntry {
+        self.price_for_singe_period(datapoint)
+    }
+
+    // Prices the readings in time order keeping a running total of the imported energy,
+    // the prices line up with the datapoints as they were given. Plans that net the exports
+    // against the imports price what is left of each reading, plans that round the usage
+    // of each interval or day have it rounded here, see round_readings
+    fn price_readings(&self, datapoints: &[SmartMeterData]) -> Vec<EnergyBillEntry> {
+        let datapoints = self.export_rules().netting.apply(datapoints);
+        let mut order: Vec<usize> = (0..datapoints.len()).collect();
+        order.sort_by_key(|&i| datapoints[i].read_data_and_end_time);
+        let mut prices = vec![EnergyBillEntry::ZERO; datapoints.len()];
+        let mut imported_kwh = 0.0;
+        for i in order {
+            let datapoint = &datapoints[i];
+            prices[i] = with_vat_on(
+                self.price_with_accumulated_usage(datapoint, imported_kwh),
+                self.vat_included(),
+                datapoint.date(),
+                self.currency(),
+            );
+            if datapoint.read_type == SmartMeterDataType::ActiveImport {
+                imported_kwh += datapoint.energy_kwh();
+            }
+        }
+        round_readings(&self.rounding(), &datapoints, &mut prices);
+        prices
+    }
+
+    // The same plan without its introductory discount, used to project past the discount period
+    fn at_standard_rates(&self) -> Option<Box<dyn PricePlanStrategy>> {
+        None
+    }
+
+    // How long the discount lasts from the start of the contract
+    fn discount_months(&self) -> u32 {
+        DEFAULT_DISCOUNT_MONTHS
+    }
+
+    // What comes off the plan while the discount lasts. The unit rate and standing charge
+    // parts are in the prices of the plan already, the bill credit is added by the breakdown
+    fn discount(&self) -> Discount {
+        Discount::default()
+    }
+
+    // How long the rates are fixed from the start of the contract, a scenario only moves them
+    // after that, see Scenario. Variable plans have none
+    fn fixed_rate_months(&self) -> u32 {
+        0
+    }
+
+    // A one off credit for switching, like cashback, taken off the first bill only
+    fn sign_up_bonus(&self) -> Money {
+        Money::ZERO
+    }
+
+    // Who sells the plan and on what terms, shown next to its price
+    fn metadata(&self) -> PlanMetadata {
+        PlanMetadata::default()
+    }
+
+    // The free window the customer picked on a free time plan
+    fn free_time(&self) -> Option<FreeTime> {
+        None
+    }
+
+    // The plan with each of the free windows the supplier lets the customer pick from
+    fn free_time_choices(&self) -> Vec<(FreeWindow, Box<dyn PricePlanStrategy>)> {
+        vec![]
+    }
+
+    // Pay as you go plans, only compared with each other when asked for
+    fn is_prepay(&self) -> bool {
+        false
+    }
+
+    // What went wrong pricing readings on plans priced outside the crate, whose prices are
+    // zero then, none once every reading was priced
+    fn pricing_error(&self) -> Option<String> {
+        None
+    }
+
+    // What the user should know about the prices of the readings though nothing failed, like
+    // readings no band takes priced at zero. Asked once for all the readings of a meter point
+    fn pricing_warning(&self, _datapoints: &[SmartMeterData]) -> Option<String> {
+        None
+    }
+
+    // Caps and limits on the export credit, applied to the whole period
+    fn export_rules(&self) -> ExportRules {
+        ExportRules::default()
+    }
+
+    // Whether the usage of a period is the usage of each of its months added up, so the usage
+    // cache can reuse the months that didn't change. Export caps count the whole period
+    fn usage_adds_up_by_month(&self) -> bool {
+        self.export_rules().is_unlimited()
+    }
+
+    // Identifies what the plan is priced from besides the tariff file, like the prices of a
+    // price file, so the usage cache prices it again when that changes
+    fn cache_version(&self) -> u64 {
+        0
+    }
+
+    // The same for every plan, see PsoLevy::install to change the values. The levy is
+    // Irish, plans priced in another currency don't pay it
+    fn pso_levy_for_period(&self, period: &BillingPeriod) -> EnergyBillEntry {
+        if self.currency() != Currency::Eur {
+            return EnergyBillEntry::ZERO;
+        }
+        PsoLevy::global().for_period(period)
+    }
+
+    // What the plan charges on the peak demand of the readings rather than on the energy,
+    // nothing unless it has a DemandCharge. Without a MIC the connection is taken to be sized
+    // for the peak
+    fn demand_charge_with_mic(
+        &self,
+        _datapoints: &[SmartMeterData],
+        _period: &BillingPeriod,
+        _mic: Option<MaximumImportCapacity>,
+    ) -> EnergyBillEntry {
+        EnergyBillEntry::ZERO
+    }
+
+    // Plans are priced without a MIC unless they are given one, see WithImportCapacity
+    fn demand_charge_for_period(
+        &self,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+    ) -> EnergyBillEntry {
+        self.demand_charge_with_mic(datapoints, period, None)
+    }
+
+    fn compute_total_bill_for_period(&self, datapoints: &[SmartMeterData]) -> EnergyBillEntry {
+        self.export_rules()
+            .apply(datapoints, &self.price_readings(datapoints))
+    }
+
+    // Computes the bill for the period keeping the usage and the fixed costs apart
+    fn compute_bill_breakdown(
+        &self,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+    ) -> BillBreakdown {
+        // the imports are priced again on their own to tell the export credit apart, only
+        // when something was exported
+        let import_cost = has_exports(datapoints)
+            .then(|| self.compute_total_bill_for_period(&imports_of(datapoints)));
+        readings_breakdown(
+            self,
+            self.compute_total_bill_for_period(datapoints),
+            import_cost,
+            datapoints,
+            period,
+        )
+    }
+
+    // The bill for the period once the usage is known, the fixed costs only need the days.
+    // Everything is priced as plain amounts and put in the currency of the plan here
+    fn bill_breakdown_for_usage(
+        &self,
+        usage: EnergyBillEntry,
+        period: &BillingPeriod,
+    ) -> BillBreakdown {
+        let rounding = self.rounding();
+        let usage = usage.round(&rounding);
+        let standing_charge = self.standing_charge_for_period(period).round(&rounding);
+        let pso_levy = self.pso_levy_for_period(period).round(&rounding);
+        let credits = CreditSchedule::global()
+            .for_period(period, self.currency())
+            .round(&rounding);
+        let bill_credit = self
+            .discount()
+            .bill_credit_for_period(period)
+            .round(&rounding);
+        BillBreakdown {
+            usage,
+            import_cost: usage,
+            export_credit: EnergyBillEntry::ZERO,
+            standing_charge,
+            pso_levy,
+            credits,
+            bill_credit,
+            demand_charge: EnergyBillEntry::ZERO,
+            total: usage + standing_charge + pso_levy + credits + bill_credit,
+        }
+        .in_currency(self.currency())
+    }
+}
+
+pub(crate) fn has_exports(datapoints: &[SmartMeterData]) -> bool {
+    datapoints
+        .iter()
+        .any(|d| d.read_type == SmartMeterDataType::ActiveExport && d.read_value > 0.0)
+}
+
+pub(crate) fn imports_of(datapoints: &[SmartMeterData]) -> Vec<SmartMeterData> {
+    datapoints
+        .iter()
+        .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+        .cloned()
+        .collect()
+}
+
+// The bill of the readings once the usage of the plan and the cost of the imports alone are
+// known, see compute_bill_breakdown
+pub(crate) fn readings_breakdown(
+    plan: &(impl PricePlanStrategy + ?Sized),
+    usage: EnergyBillEntry,
+    import_cost: Option<EnergyBillEntry>,
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+) -> BillBreakdown {
+    let in_plan =
+        |entry: EnergyBillEntry| entry.round(&plan.rounding()).in_currency(plan.currency());
+    let breakdown = plan
+        .bill_breakdown_for_usage(usage, period)
+        .with_demand_charge(in_plan(plan.demand_charge_for_period(datapoints, period)));
+    match import_cost {
+        Some(import_cost) => breakdown.with_import_cost(in_plan(import_cost)),
+        None => breakdown,
+    }
+}
+
+// Rounds the prices of the readings like the plan does before adding them up. Rounded per day,
+// the imports and the exports of each day are rounded apart and what the rounding changed goes
+// on the last reading of the day, so the readings still add up to the rounded days
+pub(crate) fn round_readings(
+    rounding: &Rounding,
+    datapoints: &[SmartMeterData],
+    prices: &mut [EnergyBillEntry],
+) {
+    match rounding.per {
+        RoundingLevel::Bill => {}
+        RoundingLevel::Interval => {
+            for price in prices.iter_mut() {
+                *price = price.round(rounding);
+            }
+        }
+        RoundingLevel::Day => {
+            let mut days: BTreeMap<(NaiveDate, SmartMeterDataType), (Decimal, usize)> =
+                BTreeMap::new();
+            for (index, datapoint) in datapoints.iter().enumerate() {
+                let day = days
+                    .entry((datapoint.date(), datapoint.read_type))
+                    .or_insert((Decimal::ZERO, index));
+                day.0 += prices[index].signed_amount();
+                if datapoint.read_data_and_end_time >= datapoints[day.1].read_data_and_end_time {
+                    day.1 = index;
+                }
+            }
+            for (total, last) in days.into_values() {
+                let rounded = EnergyBillEntry::from_signed(total).round(rounding);
+                let price = &mut prices[last];
+                *price = EnergyBillEntry::from_signed(
+                    price.signed_amount() + rounded.signed_amount() - total,
+                )
+                .in_currency(price.currency());
+            }
+        }
+    }
+}
+
+// Readings are timestamped at the end of their interval, so a reading at 17:00 covers 16:30 to 17:00
+// this makes the start of a window exclusive and its end inclusive
+// a window whose start is after its end wraps around midnight and an empty window covers the whole day
+pub fn time_in_window(time: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start < end {
+        time > start && time <= end
+    } else if start > end {
+        time > start || time <= end
+    } else {
+        true
+    }
+}
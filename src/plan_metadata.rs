@@ -0,0 +1,134 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::{CustomerType, Eligibility};
+
+// Where a plan comes from and what it takes to sign up for it, so the plan compared can be
+// checked against the one on the website of the supplier. Nothing here changes the price
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PlanMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supplier: Option<String>,
+    // the name the supplier sells the plan as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_name: Option<String>,
+    // the date of the price list the rates were taken from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tariff_date: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_months: Option<u32>,
+    #[serde(default)]
+    pub direct_debit: bool,
+    #[serde(default)]
+    pub e_billing: bool,
+    // the price list the plan is on, only plans of the customer type compared are kept
+    #[serde(default, skip_serializing_if = "CustomerType::is_domestic")]
+    pub customer_type: CustomerType,
+    // who can sign up, plans the home can't have are left out, see Household
+    #[serde(default, skip_serializing_if = "Eligibility::is_empty")]
+    pub eligibility: Eligibility,
+    // other names the plan is asked for by, like EI HE 14% for --current-plan
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+impl PlanMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == PlanMetadata::default()
+    }
+
+    // The supplier, the plan and the price list, which are the same plan whatever it is called
+    // in the tariff files. None unless both the supplier and the plan are known
+    pub fn identity(&self) -> Option<(String, String, Option<NaiveDate>)> {
+        match (&self.supplier, &self.plan_name) {
+            (Some(supplier), Some(plan)) => Some((
+                supplier.to_lowercase(),
+                plan.to_lowercase(),
+                self.tariff_date,
+            )),
+            _ => None,
+        }
+    }
+
+    // One line of what is known, like SSE Airtricity Standard Smart, prices of 2024-01-01,
+    // 12 month contract, direct debit and e-billing, https://www.sseairtricity.com/...
+    pub fn summary(&self) -> String {
+        let mut parts = vec![];
+        match (&self.supplier, &self.plan_name) {
+            (Some(supplier), Some(plan)) => parts.push(format!("{supplier} {plan}")),
+            (Some(name), None) | (None, Some(name)) => parts.push(name.clone()),
+            (None, None) => {}
+        }
+        if let Some(date) = self.tariff_date {
+            parts.push(format!("prices of {date}"));
+        }
+        if let Some(months) = self.contract_months {
+            parts.push(format!("{months} month contract"));
+        }
+        match (self.direct_debit, self.e_billing) {
+            (true, true) => parts.push("direct debit and e-billing".to_string()),
+            (true, false) => parts.push("direct debit".to_string()),
+            (false, true) => parts.push("e-billing".to_string()),
+            (false, false) => {}
+        }
+        parts.extend(
+            self.eligibility
+                .requirements()
+                .iter()
+                .map(|r| r.to_string()),
+        );
+        if !self.customer_type.is_domestic() {
+            parts.push(format!("{} customers", self.customer_type));
+        }
+        if !self.aliases.is_empty() {
+            parts.push(format!("also called {}", self.aliases.join(", ")));
+        }
+        parts.extend(self.source_url.clone());
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_summary_leaves_out_what_is_not_known() {
+        let metadata: PlanMetadata = toml::from_str(
+            r#"
+supplier = "SSE Airtricity"
+tariff_date = "2024-01-01"
+contract_months = 12
+direct_debit = true
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            metadata.summary(),
+            "SSE Airtricity, prices of 2024-01-01, 12 month contract, direct debit"
+        );
+        assert!(PlanMetadata::default().is_empty());
+        let business: PlanMetadata = toml::from_str("customer_type = \"business\"").unwrap();
+        assert_eq!(business.summary(), "business customers");
+
+        let named: PlanMetadata = toml::from_str(
+            "supplier = \"Electric Ireland\"\nplan_name = \"Home Electric+\"\naliases = [\"EI HE 14%\"]",
+        )
+        .unwrap();
+        assert_eq!(
+            named.summary(),
+            "Electric Ireland Home Electric+, also called EI HE 14%"
+        );
+        assert_eq!(
+            named.identity(),
+            Some((
+                "electric ireland".to_string(),
+                "home electric+".to_string(),
+                None
+            ))
+        );
+        assert_eq!(metadata.identity(), None);
+    }
+}
@@ -0,0 +1,342 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::{fmt::Display, path::Path, process::Command};
+
+use crate::{PlanFile, PricePlanStrategy};
+
+// The round constants of SHA-256, the fractional parts of the cube roots of the first 64 primes
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// What a plans update did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryUpdate {
+    pub plans: usize,
+    pub sha256: String,
+}
+
+// The SHA-256 of the bytes in lowercase hex, like sha256sum prints it. Only used to check a
+// download against the checksum published next to it
+pub fn sha256_hex(data: &[u8]) -> String {
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(SHA256_K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            (h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+    state.iter().map(|word| format!("{word:08x}")).collect()
+}
+
+// A registry is a tariff file like plans.example.toml, in toml or in json. Dynamic and
+// conditional plans need a price or conditions file next to them and plugin plans run code of the machine, so a registry can't have
+// any
+pub fn parse_registry(contents: &str) -> Result<PlanFile> {
+    let plan_file: PlanFile = if contents.trim_start().starts_with('{') {
+        serde_json::from_str(contents).context("invalid plan registry")?
+    } else {
+        toml::from_str(contents).map_err(|e| anyhow::anyhow!("invalid plan registry: {e}"))?
+    };
+    if let Some(plan) = plan_file.dynamic_plan.first() {
+        bail!(
+            "the plan registry has the dynamic plan {}, dynamic plans need a local price file",
+            plan.name
+        );
+    }
+    if let Some(plan) = plan_file.conditional_plan.first() {
+        bail!(
+            "the plan registry has the conditional plan {}, conditional plans need a local conditions file",
+            plan.name
+        );
+    }
+    if let Some(plan) = plan_file.plugin_plan.first() {
+        bail!(
+            "the plan registry has the plugin plan {}, plugin plans can only be in a local tariff file",
+            plan.name
+        );
+    }
+    // the names go into notifications, where a line break could start a mail header
+    let names = plan_file
+        .plan
+        .iter()
+        .map(|plan| &plan.name)
+        .chain(plan_file.tiered_plan.iter().map(|plan| &plan.name))
+        .chain(plan_file.day_night_plan.iter().map(|plan| &plan.name))
+        .chain(plan_file.gas_plan.iter().map(|plan| &plan.name))
+        .chain(plan_file.dual_fuel.iter().map(|bundle| &bundle.name));
+    for name in names {
+        if name.chars().any(char::is_control) {
+            bail!(
+                "the plan registry has the plan {name:?}, plan names can't have control characters"
+            );
+        }
+    }
+    Ok(plan_file)
+}
+
+// The registry kept by plans update, None before the first update
+pub fn load_registry(cache: &Path) -> Result<Option<PlanFile>> {
+    if !cache.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(cache)?;
+    Ok(Some(parse_registry(&contents).with_context(|| {
+        format!("run plans update again to replace {}", cache.display())
+    })?))
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    // curl is on every machine that can reach the registry, like for the day ahead prices
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", url])
+        .output()
+        .context("could not run curl to download the plan registry")?;
+    if !output.status.success() {
+        bail!(
+            "downloading {url} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+// Downloads the registry and the checksum published with it, <url>.sha256 unless given, in
+// the format of sha256sum. The cache is only replaced by a registry that matches its checksum
+// and can be read
+pub fn update_registry(
+    url: &str,
+    checksum_url: Option<&str>,
+    cache: &Path,
+) -> Result<RegistryUpdate> {
+    let registry = download(url)?;
+    let checksum_url = checksum_url.map_or_else(|| format!("{url}.sha256"), str::to_string);
+    let checksum = String::from_utf8(download(&checksum_url)?)?;
+    let expected = checksum
+        .split_whitespace()
+        .next()
+        .context("the checksum of the plan registry is empty")?
+        .to_lowercase();
+    let sha256 = sha256_hex(&registry);
+    if sha256 != expected {
+        bail!("the plan registry has the checksum {sha256} instead of the published {expected}");
+    }
+    let contents = String::from_utf8(registry)?;
+    let plan_file = parse_registry(&contents)?;
+    std::fs::write(cache, &contents)?;
+    Ok(RegistryUpdate {
+        plans: plan_file.plan.len() + plan_file.tiered_plan.len() + plan_file.day_night_plan.len(),
+        sha256,
+    })
+}
+
+// Where a compared plan was defined, the plans of each one override those of the ones before
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanSource {
+    BuiltIn,
+    Registry,
+    TariffFile,
+    CustomPlan,
+}
+
+impl Display for PlanSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PlanSource::BuiltIn => "the built in plans",
+            PlanSource::Registry => "the plan registry",
+            PlanSource::TariffFile => "the tariff file",
+            PlanSource::CustomPlan => "--custom-plan",
+        })
+    }
+}
+
+// A plan left out for another that is the same plan. replaced is set when the plan was there
+// before and the other overrides it, and not when both came in the same overrides
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    pub left_out: String,
+    pub kept: String,
+    pub replaced: bool,
+}
+
+// Two plans are the same with a name or alias in common, or the same supplier, plan and price
+// list in their metadata
+fn same_plan(plan: &dyn PricePlanStrategy, other: &dyn PricePlanStrategy) -> bool {
+    let (metadata, other_metadata) = (plan.metadata(), other.metadata());
+    plan.is_called(&other.name())
+        || other.is_called(&plan.name())
+        || metadata.aliases.iter().any(|alias| other.is_called(alias))
+        || metadata
+            .identity()
+            .is_some_and(|identity| other_metadata.identity() == Some(identity))
+}
+
+// Adds the plans to the others, one that is the same as a plan already there replaces it, so
+// a local tariff file overrides the registry and the registry the built in plans. Of the same
+// plan twice in the overrides the first one is kept. Returns the plans left out
+pub fn merge_plans(
+    plans: &mut Vec<Box<dyn PricePlanStrategy>>,
+    overrides: Vec<Box<dyn PricePlanStrategy>>,
+) -> Vec<Duplicate> {
+    let mut duplicates = vec![];
+    let mut kept: Vec<Box<dyn PricePlanStrategy>> = vec![];
+    for plan in overrides {
+        match kept
+            .iter()
+            .find(|other| same_plan(other.as_ref(), plan.as_ref()))
+        {
+            Some(other) => duplicates.push(Duplicate {
+                left_out: plan.name(),
+                kept: other.name(),
+                replaced: false,
+            }),
+            None => kept.push(plan),
+        }
+    }
+    plans.retain(|plan| {
+        match kept
+            .iter()
+            .find(|other| same_plan(other.as_ref(), plan.as_ref()))
+        {
+            Some(other) => {
+                duplicates.push(Duplicate {
+                    left_out: plan.name(),
+                    kept: other.name(),
+                    replaced: true,
+                });
+                false
+            }
+            None => true,
+        }
+    });
+    plans.extend(kept);
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin_plans;
+
+    #[test]
+    fn sha256_matches_sha256sum() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // two blocks once padded
+        assert_eq!(
+            sha256_hex(&[b'a'; 64]),
+            "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb"
+        );
+    }
+
+    #[test]
+    fn registry_plans_replace_plans_of_the_same_name() {
+        let registry = parse_registry(
+            r#"{"plan": [{"name": "SSEAirtricity20", "standing_charge": {"per_day": 0.7},
+                "bands": [{"name": "all day", "start": "00:00", "end": "00:00", "rate": 0.3}]}]}"#,
+        )
+        .unwrap();
+        let mut plans = builtin_plans();
+        let count = plans.len();
+        let mut registry = registry;
+        let replaced = merge_plans(&mut plans, registry.take_electricity_plans());
+        assert_eq!(replaced.len(), 1);
+        assert_eq!(plans.len(), count);
+        assert_eq!(plans.last().unwrap().name(), "SSEAirtricity20");
+    }
+
+    #[test]
+    fn a_plan_name_with_a_line_break_is_refused() {
+        let error = parse_registry(
+            r#"{"plan": [{"name": "Cheap\r\nBcc: everyone@example.com", "standing_charge": {"per_day": 0.7},
+                "bands": [{"name": "all day", "start": "00:00", "end": "00:00", "rate": 0.3}]}]}"#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("control characters"), "{error}");
+    }
+
+    #[test]
+    fn the_same_plan_under_another_name_is_left_out() {
+        let plan = |name: &str, metadata: &str| {
+            format!(
+                r#"{{"name": "{name}", "standing_charge": {{"per_day": 0.7}}, "metadata": {metadata},
+                "bands": [{{"name": "all day", "start": "00:00", "end": "00:00", "rate": 0.3}}]}}"#
+            )
+        };
+        let home_electric = r#"{"supplier": "Electric Ireland", "plan_name": "Home Electric+"}"#;
+        let mut registry = parse_registry(&format!(
+            r#"{{"plan": [{}, {}, {}]}}"#,
+            plan("EI Home Electric", home_electric),
+            plan("Home Electric+ again", home_electric),
+            plan(
+                "EI HE 14% v2",
+                r#"{"aliases": ["ElectricIrelandHomeElectric14"]}"#
+            ),
+        ))
+        .unwrap();
+        let mut plans = builtin_plans();
+        let duplicates = merge_plans(&mut plans, registry.take_electricity_plans());
+        assert_eq!(
+            duplicates,
+            vec![
+                Duplicate {
+                    left_out: "Home Electric+ again".to_string(),
+                    kept: "EI Home Electric".to_string(),
+                    replaced: false,
+                },
+                Duplicate {
+                    left_out: "ElectricIrelandHomeElectric14".to_string(),
+                    kept: "EI HE 14% v2".to_string(),
+                    replaced: true,
+                },
+            ]
+        );
+        assert!(plans
+            .iter()
+            .any(|plan| plan.is_called("electricirelandhomeelectric14")));
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::{bail, Result};
+use std::ops::Deref;
+
+use crate::{Currency, CustomerType, Household, PricePlanStrategy};
+
+// Which of the plans a comparison is made on, the same for the command line and the server.
+// The current plan is kept whatever it is, to compare the others against
+#[derive(Debug, Clone, Default)]
+pub struct PlanSelection {
+    // plans of another country can't be compared on the readings
+    pub currency: Currency,
+    // homes and businesses are priced from different price lists
+    pub customer_type: CustomerType,
+    // the plans the home can't sign up for are left out, only for what was said about it.
+    // The supplier of the current plan is taken when no supplier is given
+    pub household: Household,
+    pub prepay: bool,
+    // only these and the current plan, every plan when empty
+    pub plans: Vec<String>,
+    pub current_plan: Option<String>,
+}
+
+// The plans a selection is made of and what it left out, for the warnings
+#[derive(Debug)]
+pub struct SelectedPlans<P> {
+    pub plans: Vec<P>,
+    pub other_currency: usize,
+    pub ineligible: Vec<String>,
+}
+
+impl PlanSelection {
+    pub fn select<P>(&self, mut plans: Vec<P>) -> Result<SelectedPlans<P>>
+    where
+        P: Deref<Target = dyn PricePlanStrategy>,
+    {
+        let currency = self.currency;
+        let before = plans.len();
+        plans.retain(|plan| plan.currency() == currency);
+        if plans.is_empty() {
+            bail!(
+                "none of the plans are priced in {currency}, add them to a tariff file with --tariffs and currency = \"{currency}\""
+            );
+        }
+        let other_currency = before - plans.len();
+
+        let is_current = |plan: &P| {
+            self.current_plan
+                .as_ref()
+                .is_some_and(|current| plan.is_called(current))
+        };
+        let customer_type = self.customer_type;
+        plans.retain(|plan| plan.metadata().customer_type == customer_type || is_current(plan));
+        if plans
+            .iter()
+            .all(|plan| plan.metadata().customer_type != customer_type)
+        {
+            bail!("no {customer_type} plans to compare, add them to a tariff file with --tariffs and customer_type = \"{customer_type}\" in their metadata");
+        }
+
+        let household = Household {
+            supplier: self.household.supplier.clone().or_else(|| {
+                plans
+                    .iter()
+                    .find(|plan| is_current(plan))
+                    .and_then(|plan| plan.metadata().supplier)
+            }),
+            ..self.household.clone()
+        };
+        let mut ineligible = vec![];
+        plans.retain(|plan| {
+            let metadata = plan.metadata();
+            match metadata
+                .eligibility
+                .unmet(metadata.supplier.as_deref(), &household)
+            {
+                Some(reason) if !is_current(plan) => {
+                    ineligible.push(format!("{} ({reason})", plan.name()));
+                    false
+                }
+                _ => true,
+            }
+        });
+
+        if self.prepay {
+            plans.retain(|plan| plan.is_prepay() || is_current(plan));
+            if plans.iter().all(|plan| !plan.is_prepay()) {
+                bail!("no prepay plans to compare, add them to a tariff file with --tariffs");
+            }
+        }
+        if !self.plans.is_empty() {
+            plans.retain(|plan| {
+                self.plans.iter().any(|name| plan.is_called(name)) || is_current(plan)
+            });
+            if plans.is_empty() {
+                bail!("none of the plans match {:?}", self.plans);
+            }
+        }
+        Ok(SelectedPlans {
+            plans,
+            other_currency,
+            ineligible,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builtin_plans, PlanFile};
+
+    fn plans() -> Vec<Box<dyn PricePlanStrategy>> {
+        let mut plans = builtin_plans();
+        plans.extend(
+            toml::from_str::<PlanFile>(
+                r#"
+                [[plan]]
+                name = "Shop"
+                standing_charge = { per_day = 0.9 }
+                metadata = { customer_type = "business" }
+                [[plan.bands]]
+                name = "All day"
+                start = "00:00"
+                end = "00:00"
+                rate = 0.1
+
+                [[plan]]
+                name = "Card"
+                prepay = { service_charge = { per_day = 0.3 } }
+                standing_charge = { per_day = 0.5 }
+                [[plan.bands]]
+                name = "All day"
+                start = "00:00"
+                end = "00:00"
+                rate = 0.1
+                "#,
+            )
+            .unwrap()
+            .take_electricity_plans(),
+        );
+        plans
+    }
+
+    fn names(selection: &PlanSelection) -> Vec<String> {
+        let selected = selection.select(plans()).unwrap();
+        selected.plans.iter().map(|plan| plan.name()).collect()
+    }
+
+    #[test]
+    fn business_plans_are_only_compared_for_businesses() {
+        let domestic = names(&PlanSelection::default());
+        assert!(!domestic.contains(&"Shop".to_string()));
+        assert!(domestic.contains(&"Card".to_string()));
+        let business = PlanSelection {
+            customer_type: CustomerType::Business,
+            ..PlanSelection::default()
+        };
+        assert_eq!(names(&business), ["Shop"]);
+        // the current plan is kept to compare against
+        let current = PlanSelection {
+            current_plan: Some("sseairtricity20".to_string()),
+            ..business
+        };
+        assert_eq!(names(&current), ["SSEAirtricity20", "Shop"]);
+    }
+
+    #[test]
+    fn prepay_and_named_plans_narrow_the_selection() {
+        let prepay = PlanSelection {
+            prepay: true,
+            ..PlanSelection::default()
+        };
+        assert_eq!(names(&prepay), ["Card"]);
+        let named = PlanSelection {
+            plans: vec!["card".to_string()],
+            current_plan: Some("SSEAirtricity20".to_string()),
+            ..PlanSelection::default()
+        };
+        assert_eq!(names(&named), ["SSEAirtricity20", "Card"]);
+        let none = PlanSelection {
+            plans: vec!["nothing like it".to_string()],
+            ..PlanSelection::default()
+        };
+        assert!(none.select(plans()).is_err());
+    }
+}
@@ -0,0 +1,450 @@
+use anyhow::{bail, Context, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex, OnceLock},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    plan::round_readings, usage_cache::Fnv, with_vat_on, DuosGroup, EnergyBillEntry,
+    PricePlanStrategy, SmartMeterData, SmartMeterDataType, StandingCharge,
+    StandingChargeDefinition, QUOTED_VAT_RATE,
+};
+
+// Makes the plan of a [[plugin_plan]] of the kind it was registered for
+pub type PlanFactory = fn(&PluginPlan) -> Result<Box<dyn PricePlanStrategy>>;
+
+static PLAN_KINDS: OnceLock<Mutex<BTreeMap<String, PlanFactory>>> = OnceLock::new();
+
+fn plan_kinds() -> &'static Mutex<BTreeMap<String, PlanFactory>> {
+    PLAN_KINDS.get_or_init(|| {
+        let command: PlanFactory = CommandPlan::from_plugin;
+        Mutex::new(BTreeMap::from([("command".to_string(), command)]))
+    })
+}
+
+// Lets a program built on the crate price tariffs no table of a tariff file can describe, like
+// free electricity when the wind blows, with its own PricePlanStrategy. Tariff files then give
+// them as [[plugin_plan]] with that kind. Call it before the tariff files are read, a kind
+// can't be registered twice
+pub fn register_plan_kind(kind: &str, factory: PlanFactory) -> Result<()> {
+    let mut kinds = plan_kinds().lock().unwrap();
+    if kinds.contains_key(kind) {
+        bail!("the plugin plan kind {kind} is already registered");
+    }
+    kinds.insert(kind.to_string(), factory);
+    Ok(())
+}
+
+// A plan of a tariff file priced by code outside the crate, the kind picks the factory and the
+// rest of the table is left to it
+#[derive(Debug, Deserialize)]
+pub struct PluginPlan {
+    pub name: String,
+    pub kind: String,
+    #[serde(flatten)]
+    pub params: toml::Table,
+    // the tariff file the plan is in, so paths in the params can be taken next to it
+    #[serde(skip)]
+    pub tariff_file: PathBuf,
+    #[serde(skip)]
+    plan: Option<Box<dyn PricePlanStrategy>>,
+}
+
+impl PluginPlan {
+    // The param of the key as the type the factory wants, none when the table doesn't have it
+    pub fn param<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.params
+            .get(key)
+            .map(|value| value.clone().try_into())
+            .transpose()
+            .with_context(|| format!("{key} of the plugin plan {} is not right", self.name))
+    }
+
+    // A relative path is taken from the directory of the tariff file, like the price file of a
+    // dynamic plan
+    pub fn path(&self, path: &Path) -> PathBuf {
+        match self.tariff_file.parent() {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    // Makes the plan with the factory of its kind
+    pub fn load(&mut self, tariff_file: &Path) -> Result<()> {
+        self.tariff_file = tariff_file.to_path_buf();
+        let factory = {
+            let kinds = plan_kinds().lock().unwrap();
+            match kinds.get(&self.kind) {
+                Some(factory) => *factory,
+                None => bail!(
+                    "the plugin plan {} is of the kind {}, the kinds are {}",
+                    self.name,
+                    self.kind,
+                    kinds.keys().cloned().collect::<Vec<_>>().join(", ")
+                ),
+            }
+        };
+        let plan = factory(self).with_context(|| format!("plugin plan {}", self.name))?;
+        if plan.name() != self.name {
+            bail!(
+                "the plugin plan {} was made as {}, the names have to match",
+                self.name,
+                plan.name()
+            );
+        }
+        self.plan = Some(plan);
+        Ok(())
+    }
+
+    // The plan loaded, none before load
+    pub(crate) fn into_plan(self) -> Option<Box<dyn PricePlanStrategy>> {
+        self.plan
+    }
+}
+
+// The kind every build has: a program prices the readings. It gets each reading as a line of
+// json on its stdin, {"mprn", "end_time", "local_end_time", "read_type" (import or export),
+// "kwh"}, and writes a line for each back with the euro of it, below zero for a credit.
+// Like the tariff files the prices include VAT unless vat_included says otherwise
+#[derive(Debug, Clone)]
+pub struct CommandPlan {
+    pub name: String,
+    // the program and its arguments, run in the directory of the tariff file
+    pub command: Vec<String>,
+    pub directory: PathBuf,
+    pub standing_charge: StandingChargeDefinition,
+    pub vat_included: Decimal,
+    state: Arc<Mutex<CommandState>>,
+}
+
+// What the program said about the readings it was given, so a single reading can be looked
+// up without running it again, and the first time it failed
+#[derive(Debug, Default)]
+struct CommandState {
+    prices: HashMap<(Arc<str>, SmartMeterDataType, NaiveDateTime, u32), EnergyBillEntry>,
+    error: Option<String>,
+}
+
+fn reading_key(datapoint: &SmartMeterData) -> (Arc<str>, SmartMeterDataType, NaiveDateTime, u32) {
+    (
+        datapoint.mprn.clone(),
+        datapoint.read_type,
+        datapoint.read_data_and_end_time,
+        datapoint.read_value.to_bits(),
+    )
+}
+
+impl CommandPlan {
+    fn from_plugin(plugin: &PluginPlan) -> Result<Box<dyn PricePlanStrategy>> {
+        let command: Vec<String> = plugin
+            .param("command")?
+            .context("a command plan needs a command, e.g. command = [\"python3\", \"wind.py\"]")?;
+        if command.is_empty() {
+            bail!("the command is empty");
+        }
+        Ok(Box::new(CommandPlan {
+            name: plugin.name.clone(),
+            command,
+            directory: plugin.path(Path::new(".")),
+            standing_charge: plugin
+                .param("standing_charge")?
+                .unwrap_or(StandingChargeDefinition::PerDay(Decimal::ZERO)),
+            vat_included: plugin.param("vat_included")?.unwrap_or(QUOTED_VAT_RATE),
+            state: Arc::default(),
+        }))
+    }
+
+    fn run(&self, datapoints: &[SmartMeterData]) -> Result<Vec<EnergyBillEntry>> {
+        let mut input = String::new();
+        for datapoint in datapoints {
+            let line = serde_json::json!({
+                "mprn": &*datapoint.mprn,
+                "end_time": datapoint.read_data_and_end_time,
+                "local_end_time": datapoint.local_end_time(),
+                "read_type": match datapoint.read_type {
+                    SmartMeterDataType::ActiveImport => "import",
+                    SmartMeterDataType::ActiveExport => "export",
+                },
+                "kwh": datapoint.energy_kwh(),
+            });
+            input.push_str(&format!("{line}\n"));
+        }
+        let mut child = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .current_dir(&self.directory)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("could not run {}", self.command[0]))?;
+        // written from another thread so a program answering as it reads can't block on us
+        let mut stdin = child.stdin.take().context("no stdin")?;
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output()?;
+        writer.join().ok();
+        if !output.status.success() {
+            bail!("{} failed with {}", self.command[0], output.status);
+        }
+        let prices: Vec<EnergyBillEntry> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let price: Decimal = line
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("{line} is not a price"))?;
+                Ok(EnergyBillEntry::from_signed(price))
+            })
+            .collect::<Result<_>>()?;
+        if prices.len() != datapoints.len() {
+            bail!(
+                "{} priced {} readings of {}",
+                self.command[0],
+                prices.len(),
+                datapoints.len()
+            );
+        }
+        Ok(prices)
+    }
+
+    // A program that fails prices the readings at zero, the comparison then fails with the
+    // error, see pricing_error
+    fn prices(&self, datapoints: &[SmartMeterData]) -> Vec<EnergyBillEntry> {
+        match self.run(datapoints) {
+            Ok(prices) => {
+                let mut state = self.state.lock().unwrap();
+                for (datapoint, price) in datapoints.iter().zip(&prices) {
+                    state.prices.insert(reading_key(datapoint), *price);
+                }
+                prices
+            }
+            Err(e) => {
+                let mut state = self.state.lock().unwrap();
+                state.error.get_or_insert_with(|| format!("{e:#}"));
+                vec![EnergyBillEntry::ZERO; datapoints.len()]
+            }
+        }
+    }
+}
+
+impl StandingCharge for CommandPlan {
+    fn standing_charge_per_day(&self, date: NaiveDate, _group: DuosGroup) -> EnergyBillEntry {
+        self.standing_charge.for_day(date)
+    }
+
+    fn vat_included(&self) -> Decimal {
+        self.vat_included
+    }
+}
+
+impl PricePlanStrategy for CommandPlan {
+    // the price the program gave the reading with the others, it only runs for a reading it
+    // wasn't given yet
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+        let known = self
+            .state
+            .lock()
+            .unwrap()
+            .prices
+            .get(&reading_key(datapoint))
+            .copied();
+        known.unwrap_or_else(|| self.prices(std::slice::from_ref(datapoint))[0])
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    // the program runs once for all the readings rather than once each, netted and rounded
+    // like the default
+    fn price_readings(&self, datapoints: &[SmartMeterData]) -> Vec<EnergyBillEntry> {
+        let datapoints = self.export_rules().netting.apply(datapoints);
+        let mut prices: Vec<EnergyBillEntry> = self
+            .prices(&datapoints)
+            .into_iter()
+            .zip(datapoints.iter())
+            .map(|(price, datapoint)| {
+                with_vat_on(price, self.vat_included, datapoint.date(), self.currency())
+            })
+            .collect();
+        round_readings(&self.rounding(), &datapoints, &mut prices);
+        prices
+    }
+
+    fn pricing_error(&self) -> Option<String> {
+        self.state.lock().unwrap().error.clone()
+    }
+
+    // the program and the files it is given, by when they were changed. What it answers can
+    // still change without them, like from a website, which takes --no-cache
+    fn cache_version(&self) -> u64 {
+        let mut hasher = Fnv::new();
+        hasher.str(&self.directory.to_string_lossy());
+        for arg in &self.command {
+            hasher.str(arg);
+            let modified = std::fs::metadata(self.directory.join(arg))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+            if let Some(modified) = modified {
+                hasher.u64(modified.as_nanos() as u64);
+            }
+        }
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, PlanFile};
+    use rust_decimal_macros::dec;
+
+    #[derive(Debug)]
+    struct FreeAtNight(String);
+
+    impl StandingCharge for FreeAtNight {
+        fn standing_charge_per_day(&self, _date: NaiveDate, _group: DuosGroup) -> EnergyBillEntry {
+            EnergyBillEntry::ZERO
+        }
+    }
+
+    impl PricePlanStrategy for FreeAtNight {
+        fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+            let hour = chrono::Timelike::hour(&datapoint.local_end_time());
+            let rate = if hour < 8 { dec!(0) } else { dec!(0.40) };
+            EnergyBillEntry::from_signed(rate * Decimal::try_from(datapoint.energy_kwh()).unwrap())
+        }
+
+        fn name(&self) -> String {
+            self.0.clone()
+        }
+    }
+
+    fn load_plans(toml: &str) -> Result<Vec<Box<dyn PricePlanStrategy>>> {
+        let mut file: PlanFile = toml::from_str(toml).unwrap();
+        for plan in &mut file.plugin_plan {
+            plan.load(Path::new("plans.toml"))?;
+        }
+        Ok(file.take_electricity_plans())
+    }
+
+    #[test]
+    fn registered_and_command_plans_come_from_the_tariff_file() {
+        register_plan_kind("free at night", |plugin| {
+            Ok(Box::new(FreeAtNight(plugin.name.clone())))
+        })
+        .unwrap();
+        assert!(register_plan_kind("free at night", |_| bail!("twice")).is_err());
+        let plans = load_plans(
+            "[[plugin_plan]]\nname = \"Nights\"\nkind = \"free at night\"\n\n\
+             [[plugin_plan]]\nname = \"Flat\"\nkind = \"command\"\n\
+             command = [\"sh\", \"-c\", \"while read line; do echo 0.30; done\"]\n\
+             standing_charge = { per_day = 0.5 }\n",
+        )
+        .unwrap();
+        let readings = [
+            reading_at("08-01-2024 03:30", 2.0),
+            reading_at("08-01-2024 18:00", 1.0),
+        ];
+        let total = |plan: &dyn PricePlanStrategy| -> Decimal {
+            plan.price_readings(&readings)
+                .iter()
+                .map(|price| price.signed_amount())
+                .sum()
+        };
+        assert_eq!(plans[0].name(), "Nights");
+        // the readings are in kW, half a kWh at 18:00
+        assert_eq!(total(&*plans[0]), dec!(0.20));
+        assert_eq!(plans[1].name(), "Flat");
+        assert_eq!(total(&*plans[1]), dec!(0.60));
+
+        let error = load_plans("[[plugin_plan]]\nname = \"Wind\"\nkind = \"wind\"\n").unwrap_err();
+        assert!(error.to_string().contains("command"), "{error}");
+    }
+
+    fn command_plan(script: &str) -> CommandPlan {
+        CommandPlan {
+            name: "Flat".to_string(),
+            command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            directory: std::env::temp_dir(),
+            standing_charge: StandingChargeDefinition::PerDay(Decimal::ZERO),
+            vat_included: QUOTED_VAT_RATE,
+            state: Arc::default(),
+        }
+    }
+
+    #[test]
+    fn a_failing_program_fails_the_comparison() {
+        let plans: Vec<Box<dyn PricePlanStrategy>> = vec![Box::new(command_plan("exit 3"))];
+        let error = crate::compare_meter_points(
+            &plans,
+            vec![reading_at("08-01-2024 18:00", 1.0)],
+            &crate::ComparisonOptions::default(),
+        )
+        .unwrap_err();
+        assert!(
+            error.to_string().contains("Flat could not be priced"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn a_reading_priced_with_the_others_is_not_priced_again() {
+        let runs = std::env::temp_dir().join(format!("plugin-runs-{}", std::process::id()));
+        let plan = command_plan(&format!(
+            "echo run >> {}; while read line; do echo 0.30; done",
+            runs.display()
+        ));
+        let readings = [
+            reading_at("08-01-2024 03:30", 2.0),
+            reading_at("08-01-2024 18:00", 1.0),
+        ];
+        plan.price_readings(&readings);
+        assert_eq!(
+            plan.price_for_singe_period(&readings[1]),
+            EnergyBillEntry::from_signed(dec!(0.30))
+        );
+        assert_eq!(std::fs::read_to_string(&runs).unwrap().lines().count(), 1);
+        assert!(plan.pricing_error().is_none());
+        std::fs::remove_file(&runs).unwrap();
+    }
+
+    #[test]
+    fn the_usage_cache_prices_the_plan_again_when_its_program_changes() {
+        let script = std::env::temp_dir().join(format!("plugin-price-{}.sh", std::process::id()));
+        let readings = [reading_at("08-01-2024 03:30", 2.0)];
+        let cache = crate::UsageCache::default().shared();
+        // what the plan charges with and without the cache, the script changed a minute apart
+        let usage = |price: &str, minutes: u64| {
+            std::fs::write(&script, format!("while read line; do echo {price}; done\n")).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&script)
+                .unwrap()
+                .set_modified(UNIX_EPOCH + std::time::Duration::from_secs(60 * minutes))
+                .unwrap();
+            let plan = CommandPlan {
+                command: vec!["sh".to_string(), script.display().to_string()],
+                ..command_plan("")
+            };
+            let uncached = plan.compute_total_bill_for_period(&readings);
+            let cached = crate::CachedPlan::wrap(Box::new(plan), "tariffs", &cache)
+                .compute_total_bill_for_period(&readings);
+            (cached, uncached)
+        };
+        let (before, uncached) = usage("0.30", 1);
+        assert_eq!(before, uncached);
+        let (after, uncached) = usage("0.60", 2);
+        std::fs::remove_file(&script).unwrap();
+        assert_eq!(after, uncached);
+        assert_ne!(after, before);
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    io::{IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+// Redrawing more often than this only slows the parsing down
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+// A line on stderr rewritten while a big file is read, so a long import doesn't look hung.
+// It's only drawn on a terminal, logs and pipes don't get the redraws. A hidden one never
+// reads the clock, which a browser doesn't have
+pub struct Progress {
+    label: String,
+    // when it started being drawn, none when it's hidden
+    started: Option<Instant>,
+    last_drawn: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(label: impl Into<String>) -> Progress {
+        Progress {
+            label: label.into(),
+            started: std::io::stderr().is_terminal().then(Instant::now),
+            last_drawn: None,
+        }
+    }
+
+    // For callers that don't want any output, like tests and benchmarks
+    pub fn hidden() -> Progress {
+        Progress {
+            label: String::new(),
+            started: None,
+            last_drawn: None,
+        }
+    }
+
+    fn draw(&self, parsed: usize, skipped: usize) {
+        let elapsed = self
+            .started
+            .map_or(0.0, |started| started.elapsed().as_secs_f32());
+        eprint!(
+            "\r{}: {parsed} rows parsed, {skipped} skipped, {elapsed:.1}s",
+            self.label
+        );
+        let _ = std::io::stderr().flush();
+    }
+
+    pub fn update(&mut self, parsed: usize, skipped: usize) {
+        let Some(since) = self.last_drawn.or(self.started) else {
+            return;
+        };
+        if since.elapsed() < REDRAW_INTERVAL {
+            return;
+        }
+        self.last_drawn = Some(Instant::now());
+        self.draw(parsed, skipped);
+    }
+
+    // Leaves the final counts on their own line, files read in no time never show a line
+    pub fn finish(&self, parsed: usize, skipped: usize) {
+        if self.last_drawn.is_some() {
+            self.draw(parsed, skipped);
+            eprintln!();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_line_is_redrawn_at_most_every_interval() {
+        let mut hidden = Progress::hidden();
+        hidden.update(4096, 1);
+        assert!(hidden.last_drawn.is_none());
+
+        let mut shown = Progress {
+            label: "HDF.csv".to_string(),
+            started: Instant::now().checked_sub(REDRAW_INTERVAL * 2),
+            last_drawn: None,
+        };
+        shown.update(4096, 1);
+        let drawn = shown.last_drawn.expect("drawn once the interval is over");
+        // too soon for another one
+        shown.update(8192, 1);
+        assert_eq!(shown.last_drawn, Some(drawn));
+    }
+}
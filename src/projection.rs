@@ -0,0 +1,247 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+
+use crate::{
+    BillBreakdown, BillingPeriod, EnergyBillEntry, Money, PricePlanStrategy, SmartMeterData,
+    SmartMeterDataType,
+};
+
+// Irish suppliers give the advertised discount for the first year of the contract
+pub const DEFAULT_DISCOUNT_MONTHS: u32 = 12;
+
+pub(crate) fn default_discount_months() -> u32 {
+    DEFAULT_DISCOUNT_MONTHS
+}
+
+// The share of a year's electricity an Irish home imports in each month, more in the winter
+pub(crate) const IMPORT_PROFILE: [Decimal; 12] = [
+    dec!(0.104),
+    dec!(0.091),
+    dec!(0.090),
+    dec!(0.078),
+    dec!(0.073),
+    dec!(0.066),
+    dec!(0.067),
+    dec!(0.069),
+    dec!(0.073),
+    dec!(0.085),
+    dec!(0.096),
+    dec!(0.108),
+];
+
+// and the share of a year's solar export in each month, most of it in the summer
+pub(crate) const EXPORT_PROFILE: [Decimal; 12] = [
+    dec!(0.030),
+    dec!(0.050),
+    dec!(0.080),
+    dec!(0.110),
+    dec!(0.130),
+    dec!(0.130),
+    dec!(0.130),
+    dec!(0.115),
+    dec!(0.095),
+    dec!(0.065),
+    dec!(0.035),
+    dec!(0.030),
+];
+
+pub(crate) fn days_in_month(date: NaiveDate) -> u32 {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        month => (date.year(), month + 1),
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|next| next.pred_opt())
+        .map_or(30, |last| last.day())
+}
+
+// The part of a year's energy that falls in the period by the monthly profile, a whole
+// year is one
+fn share_of_year(profile: &[Decimal; 12], period: &BillingPeriod) -> Decimal {
+    period
+        .days()
+        .map(|date| profile[date.month0() as usize] / Decimal::from(days_in_month(date)))
+        .sum::<Decimal>()
+        .round_dp(9)
+}
+
+// What a year of the imports or of the exports costs on the plan, scaled up from the readings
+// of the period by the share of a year's energy of its months, a credit is negative
+pub(crate) fn annual_usage(
+    plan: &dyn PricePlanStrategy,
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+    read_type: SmartMeterDataType,
+) -> Decimal {
+    let profile = match read_type {
+        SmartMeterDataType::ActiveImport => &IMPORT_PROFILE,
+        SmartMeterDataType::ActiveExport => &EXPORT_PROFILE,
+    };
+    let readings: Vec<SmartMeterData> = datapoints
+        .iter()
+        .filter(|datapoint| datapoint.read_type == read_type)
+        .cloned()
+        .collect();
+    let share = share_of_year(profile, period);
+    if readings.is_empty() || share.is_zero() {
+        return Decimal::ZERO;
+    }
+    plan.compute_total_bill_for_period(&readings)
+        .signed_amount()
+        / share
+}
+
+// A year of the plan from the start of the period, estimated from the readings of the period.
+// The imports and exports are each scaled up by how much of a year's energy the months of
+// the period usually have, so a winter of readings doesn't make the summer as dear. The
+// standing charge and the levy are the ones of the year, one off credits are left out
+pub fn annual_estimate(
+    plan: &dyn PricePlanStrategy,
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+) -> BillBreakdown {
+    let imports = annual_usage(plan, datapoints, period, SmartMeterDataType::ActiveImport);
+    let usage = imports + annual_usage(plan, datapoints, period, SmartMeterDataType::ActiveExport);
+    let year = BillingPeriod::starting_at(period.start, 365);
+    // the peaks of the period are taken to repeat all year
+    let demand_charge = plan
+        .demand_charge_for_period(datapoints, period)
+        .signed_amount()
+        * Decimal::from(365)
+        / Decimal::from(period.number_of_days().max(1));
+    let breakdown = plan
+        .bill_breakdown_for_usage(EnergyBillEntry::from_signed(usage), &year)
+        .with_demand_charge(
+            EnergyBillEntry::from_signed(demand_charge)
+                .round(&plan.rounding())
+                .in_currency(plan.currency()),
+        )
+        .with_import_cost(
+            EnergyBillEntry::from_signed(imports)
+                .round(&plan.rounding())
+                .in_currency(plan.currency()),
+        );
+    BillBreakdown {
+        credits: EnergyBillEntry::ZERO.in_currency(plan.currency()),
+        total: breakdown.total - breakdown.credits,
+        ..breakdown
+    }
+}
+
+// What a plan is expected to cost in one year of the contract
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct YearProjection {
+    pub year: u32,
+    pub total: EnergyBillEntry,
+}
+
+// Estimates a whole year of the plan, once with the discount and once at the standard rates,
+// and mixes them by how many months of each year are still discounted, see annual_estimate.
+// The sign up bonus comes off the first year
+pub fn project_plan(
+    plan: &dyn PricePlanStrategy,
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+    years: u32,
+) -> Vec<YearProjection> {
+    let annual = |plan: &dyn PricePlanStrategy| {
+        annual_estimate(plan, datapoints, period)
+            .total
+            .signed_amount()
+    };
+    let discounted = annual(plan);
+    let standard = plan
+        .at_standard_rates()
+        .map_or(discounted, |standard| annual(standard.as_ref()));
+    let discount_months = plan.discount_months();
+    let bonus = plan.sign_up_bonus().amount();
+
+    (0..years)
+        .map(|year| {
+            let months = discount_months.saturating_sub(year * 12).min(12);
+            let share = Decimal::from(months) / Decimal::from(12);
+            let mut total = discounted * share + standard * (Decimal::ONE - share);
+            if year == 0 {
+                total -= bonus;
+            }
+            YearProjection {
+                year: year + 1,
+                total: EnergyBillEntry::from_signed(
+                    Money::new(total).round(&plan.rounding()).amount(),
+                )
+                .in_currency(plan.currency()),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, sse_airtricity_20};
+
+    fn flat_plan(extra: &str) -> crate::ConfigurablePlan {
+        toml::from_str(&format!(
+            r#"
+name = "flat"
+standing_charge = {{ per_day = 0.5 }}
+{extra}
+
+[[bands]]
+name = "all"
+start = "00:00"
+end = "00:00"
+rate = 0.30
+"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn the_discount_rolls_off_after_its_months() {
+        let plan = flat_plan("discount = 0.2\ndiscount_months = 18");
+        let data = vec![reading_at("08-01-2024 18:00", 2.0)];
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let years: Vec<Decimal> = project_plan(&plan, &data, &period, 3)
+            .iter()
+            .map(|year| year.total.signed_amount())
+            .collect();
+        let discounted = annual_estimate(&plan, &data, &period).total.signed_amount();
+        let standard = annual_estimate(plan.at_standard_rates().unwrap().as_ref(), &data, &period)
+            .total
+            .signed_amount();
+        assert!(discounted < standard);
+        // the second year has six months of the discount left
+        assert_eq!(
+            years,
+            vec![
+                discounted,
+                ((discounted + standard) / dec!(2)).round_dp(2),
+                standard
+            ]
+        );
+    }
+
+    #[test]
+    fn a_winter_month_is_less_than_a_twelfth_of_the_year() {
+        let plan = sse_airtricity_20();
+        let january = BillingPeriod {
+            start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+        };
+        assert_eq!(share_of_year(&IMPORT_PROFILE, &january), dec!(0.104));
+        let data = vec![reading_at("08-01-2024 18:00", 2.0)];
+        let usage = plan.compute_total_bill_for_period(&data).signed_amount();
+        let estimate = annual_estimate(&plan, &data, &january);
+        assert_eq!(
+            estimate.usage,
+            EnergyBillEntry::from_signed(usage / dec!(0.104)).round(&plan.rounding())
+        );
+        assert_eq!(
+            estimate.total,
+            estimate.usage + estimate.standing_charge + estimate.pso_levy
+        );
+    }
+}
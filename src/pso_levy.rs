@@ -0,0 +1,120 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path, sync::OnceLock};
+
+use crate::{annual_to_daily, vat_rate_on, BillingPeriod, EnergyBillEntry};
+
+// The domestic Public Service Obligation levy set by the CRU for each year starting in October,
+// euro per year before VAT. Each value applies from its date until the next one
+const IRISH_PSO_LEVY: &[((i32, u32, u32), Decimal)] = &[
+    ((2022, 10, 1), dec!(0)),
+    ((2023, 10, 1), dec!(0)),
+    ((2024, 10, 1), dec!(38.68)),
+    ((2025, 10, 1), dec!(18.24)),
+];
+
+// One value of the levy, negative when it was a credit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct PsoRate {
+    pub from: NaiveDate,
+    pub annual: Decimal,
+}
+
+// Changes to the built in levy values, see pso.example.toml
+#[derive(Debug, Default, Deserialize)]
+pub struct PsoOverrides {
+    #[serde(default)]
+    pub rate: Vec<PsoRate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsoLevy {
+    rates: BTreeMap<NaiveDate, Decimal>,
+}
+
+static LEVY: OnceLock<PsoLevy> = OnceLock::new();
+
+impl PsoLevy {
+    pub fn irish() -> PsoLevy {
+        PsoLevy {
+            rates: IRISH_PSO_LEVY
+                .iter()
+                .filter_map(|&((year, month, day), annual)| {
+                    NaiveDate::from_ymd_opt(year, month, day).map(|from| (from, annual))
+                })
+                .collect(),
+        }
+    }
+
+    // A value from the same date replaces the built in one
+    pub fn with_overrides(mut self, overrides: &PsoOverrides) -> PsoLevy {
+        for rate in &overrides.rate {
+            self.rates.insert(rate.from, rate.annual);
+        }
+        self
+    }
+
+    // The levy charged for a day with its VAT, nothing before the first known value
+    pub fn for_day(&self, date: NaiveDate) -> EnergyBillEntry {
+        match self.rates.range(..=date).next_back() {
+            Some((_, &annual)) => EnergyBillEntry::from_signed(
+                annual_to_daily(annual, date) * (Decimal::ONE + vat_rate_on(date)),
+            ),
+            None => EnergyBillEntry::ZERO,
+        }
+    }
+
+    pub fn for_period(&self, period: &BillingPeriod) -> EnergyBillEntry {
+        period.days().map(|date| self.for_day(date)).sum()
+    }
+
+    // The levy every plan asks, the built in one unless another was installed first
+    pub fn global() -> &'static PsoLevy {
+        LEVY.get_or_init(PsoLevy::irish)
+    }
+
+    // Only works before any bill was computed, so it has to happen while starting up
+    pub fn install(self) -> Result<()> {
+        LEVY.set(self)
+            .map_err(|_| anyhow::anyhow!("the PSO levy is already in use"))
+    }
+}
+
+pub fn load_pso_overrides(filename: &Path) -> Result<PsoOverrides> {
+    let contents = std::fs::read_to_string(filename)?;
+    toml::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("invalid PSO levy file {}: {e}", filename.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levy_follows_the_value_in_force_and_can_be_a_credit() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let levy = PsoLevy::irish().with_overrides(&PsoOverrides {
+            rate: vec![PsoRate {
+                from: date(2023, 10, 1),
+                annual: dec!(-36.6),
+            }],
+        });
+
+        // 2024 is a leap year, so a day is a 366th of the year, plus 9% VAT
+        assert_eq!(
+            levy.for_day(date(2024, 9, 30)),
+            EnergyBillEntry::Credit(crate::Money::new(dec!(0.109)))
+        );
+        let period = BillingPeriod {
+            start: date(2024, 9, 30),
+            end: date(2024, 10, 1),
+        };
+        assert_eq!(
+            levy.for_period(&period),
+            EnergyBillEntry::from_signed(dec!(-0.109) + dec!(38.68) / dec!(366) * dec!(1.09))
+        );
+    }
+}
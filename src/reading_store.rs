@@ -0,0 +1,217 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, NaiveDate};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{detect_intervals, filter_by_date, is_valid_mprn, merge_readings, SmartMeterData};
+
+// Readings kept between runs, so the whole history doesn't have to be parsed from the csv
+// every time. There is one bincode file per meter point and month, e.g.
+// readings/10308375697/2024-01.bincode, so a date range only reads the months it covers. It
+// stands in for a SQLite database, which would link a C library into every build including
+// the browser one: a month is small enough to read and write whole, and picking the files of
+// the range is what pushing the date filters down to a query would do
+#[derive(Debug, Clone)]
+pub struct ReadingStore {
+    dir: PathBuf,
+}
+
+// What an import changed in the store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub replaced: usize,
+}
+
+impl ReadingStore {
+    pub fn open(dir: &Path) -> Result<ReadingStore> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create the reading store {}", dir.display()))?;
+        Ok(ReadingStore {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    // The MPRN is the name of a directory, so anything but 11 digits could point outside the
+    // store
+    fn month_file(&self, mprn: &str, month: NaiveDate) -> Result<PathBuf> {
+        if !is_valid_mprn(mprn) {
+            bail!("{mprn:?} is not an MPRN of 11 digits, its readings can't be stored");
+        }
+        Ok(self
+            .dir
+            .join(mprn)
+            .join(format!("{}.bincode", month.format("%Y-%m"))))
+    }
+
+    fn read_month(path: &Path) -> Result<Vec<SmartMeterData>> {
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        let reader = BufReader::new(File::open(path)?);
+        bincode::deserialize_from(reader)
+            .with_context(|| format!("unreadable reading store file {}", path.display()))
+    }
+
+    // Adds the readings to the store, a reading already there for the same meter point, meter
+    // serial, direction and end time is replaced like merge_readings does with overlapping
+    // downloads. Each month is written next to its file and then renamed over it, so an import
+    // that stops half way leaves the month as it was
+    pub fn import(&self, readings: Vec<SmartMeterData>) -> Result<ImportSummary> {
+        let mut months: BTreeMap<(Arc<str>, NaiveDate), Vec<SmartMeterData>> = BTreeMap::new();
+        for datapoint in readings {
+            let month = datapoint
+                .date()
+                .with_day(1)
+                .context("invalid reading date")?;
+            months
+                .entry((datapoint.mprn.clone(), month))
+                .or_default()
+                .push(datapoint);
+        }
+
+        let mut summary = ImportSummary::default();
+        for ((mprn, month), readings) in months {
+            let path = self.month_file(&mprn, month)?;
+            let stored = ReadingStore::read_month(&path)?;
+            let before = stored.len();
+            let imported = readings.len();
+            let merged = merge_readings(vec![stored, readings]);
+            summary.added += merged.len() - before;
+            summary.replaced += imported - (merged.len() - before);
+
+            fs::create_dir_all(path.parent().context("store file without a directory")?)?;
+            let partial = path.with_extension("bincode.partial");
+            let mut writer = BufWriter::new(File::create(&partial)?);
+            bincode::serialize_into(&mut writer, &merged)?;
+            writer.flush()?;
+            fs::rename(&partial, &path)?;
+        }
+        Ok(summary)
+    }
+
+    // The readings between from and to, every meter point and only the months in the range
+    // are read
+    pub fn load(
+        &self,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Vec<SmartMeterData>> {
+        let from_month = from.and_then(|from| from.with_day(1));
+        let mut data = vec![];
+        for meter in fs::read_dir(&self.dir)? {
+            let meter = meter?.path();
+            if !meter.is_dir() {
+                continue;
+            }
+            let mut files: Vec<PathBuf> = fs::read_dir(&meter)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<_, _>>()?;
+            files.sort();
+            for file in files {
+                let month = file
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| {
+                        NaiveDate::parse_from_str(&format!("{stem}-01"), "%Y-%m-%d").ok()
+                    });
+                let Some(month) = month else {
+                    continue;
+                };
+                if from_month.is_some_and(|from| month < from) || to.is_some_and(|to| month > to) {
+                    continue;
+                }
+                data.extend(ReadingStore::read_month(&file)?);
+            }
+        }
+        let mut data = filter_by_date(data, from, to);
+        for mixed in detect_intervals(&mut data) {
+            eprintln!("warning: {}: {mixed}", self.dir.display());
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn imports_replace_readings_and_loads_only_read_the_range() {
+        let dir = std::env::temp_dir().join(format!("reading-store-{}", std::process::id()));
+        let store = ReadingStore::open(&dir).unwrap();
+        let summary = store
+            .import(vec![
+                reading_at("31-01-2024 12:00", 1.0),
+                reading_at("01-02-2024 12:00", 1.0),
+            ])
+            .unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                added: 2,
+                replaced: 0
+            }
+        );
+
+        // a later download with a corrected reading
+        let summary = store
+            .import(vec![reading_at("01-02-2024 12:00", 2.0)])
+            .unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                added: 0,
+                replaced: 1
+            }
+        );
+
+        let february = NaiveDate::from_ymd_opt(2024, 2, 1);
+        let loaded = store.load(february, None).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].read_value, 2.0);
+    }
+
+    #[test]
+    fn both_meters_of_a_swap_are_kept_and_an_unfinished_import_is_ignored() {
+        let dir = std::env::temp_dir().join(format!("reading-store-swap-{}", std::process::id()));
+        let store = ReadingStore::open(&dir).unwrap();
+        let mut new_meter = reading_at("01-02-2024 12:00", 2.0);
+        new_meter.meter_serial_number = "11111111".into();
+        let summary = store
+            .import(vec![reading_at("01-02-2024 12:00", 1.0), new_meter])
+            .unwrap();
+        assert_eq!(summary.added, 2);
+        // what an import stopped while writing the month leaves behind
+        let month = store
+            .month_file("10000000000", NaiveDate::from_ymd_opt(2024, 2, 1).unwrap())
+            .unwrap();
+        fs::write(month.with_extension("bincode.partial"), b"half a month").unwrap();
+        let loaded = store.load(None, None).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.len(), 2);
+    }
+
+    #[test]
+    fn readings_of_a_malformed_mprn_are_not_stored() {
+        let dir = std::env::temp_dir().join(format!("reading-store-mprn-{}", std::process::id()));
+        let store = ReadingStore::open(&dir).unwrap();
+        let mut reading = reading_at("01-02-2024 12:00", 1.0);
+        reading.mprn = "../../etc".into();
+        let error = store.import(vec![reading]).unwrap_err();
+        let stored = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            error.to_string(),
+            "\"../../etc\" is not an MPRN of 11 digits, its readings can't be stored"
+        );
+        assert_eq!(stored, 0);
+    }
+}
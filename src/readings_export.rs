@@ -0,0 +1,124 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::{
+    parquet::{write_parquet, Column},
+    SmartMeterData, SmartMeterDataType,
+};
+
+// A reading the way other tools want it, the HDF quirks already taken care of: the energy in
+// kWh whatever the file had, the end of the interval in UTC and the direction as a word
+#[derive(Debug, Serialize)]
+struct NormalizedReading<'a> {
+    mprn: &'a str,
+    end_time: String,
+    kwh: f32,
+    direction: &'static str,
+    interval_minutes: u32,
+}
+
+fn direction(datapoint: &SmartMeterData) -> &'static str {
+    match datapoint.read_type {
+        SmartMeterDataType::ActiveImport => "import",
+        SmartMeterDataType::ActiveExport => "export",
+    }
+}
+
+// The kWh of the reading as the double closest to what the file had, 0.1 and not the
+// 0.10000000149 a float becomes on its own
+fn kwh(datapoint: &SmartMeterData) -> f64 {
+    datapoint
+        .energy_kwh()
+        .to_string()
+        .parse()
+        .unwrap_or_default()
+}
+
+// Like 2024-01-08T03:30:00Z, which DuckDB, pandas and Polars all read as a UTC timestamp
+pub fn write_readings_csv(datapoints: &[SmartMeterData], writer: impl Write) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for datapoint in datapoints {
+        writer.serialize(NormalizedReading {
+            mprn: &datapoint.mprn,
+            end_time: datapoint
+                .read_data_and_end_time
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string(),
+            kwh: datapoint.energy_kwh(),
+            direction: direction(datapoint),
+            interval_minutes: datapoint.interval_minutes,
+        })?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// The same columns as write_readings_csv, the end of the interval as a UTC timestamp
+pub fn write_readings_parquet(datapoints: &[SmartMeterData], writer: impl Write) -> Result<()> {
+    let columns = [
+        Column::Strings(
+            "mprn",
+            datapoints.iter().map(|d| d.mprn.to_string()).collect(),
+        ),
+        Column::Timestamps(
+            "end_time",
+            datapoints
+                .iter()
+                .map(|d| d.read_data_and_end_time.and_utc().timestamp_micros())
+                .collect(),
+        ),
+        Column::Doubles("kwh", datapoints.iter().map(kwh).collect()),
+        Column::Strings(
+            "direction",
+            datapoints
+                .iter()
+                .map(|d| direction(d).to_string())
+                .collect(),
+        ),
+        Column::Ints(
+            "interval_minutes",
+            datapoints
+                .iter()
+                .map(|d| d.interval_minutes as i32)
+                .collect(),
+        ),
+    ];
+    write_parquet(&columns, writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn readings_are_written_in_kwh_and_utc() {
+        let data = vec![
+            reading_at("08-07-2024 17:30", 2.0),
+            SmartMeterData {
+                read_type: SmartMeterDataType::ActiveExport,
+                ..reading_at("08-07-2024 18:00", 1.0)
+            },
+        ];
+        let mut csv = vec![];
+        write_readings_csv(&data, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "mprn,end_time,kwh,direction,interval_minutes\n\
+             10000000000,2024-07-08T17:30:00Z,1.0,import,30\n\
+             10000000000,2024-07-08T18:00:00Z,0.5,export,30\n"
+        );
+
+        let mut parquet = vec![];
+        write_readings_parquet(&data, &mut parquet).unwrap();
+        assert!(parquet.starts_with(b"PAR1") && parquet.ends_with(b"PAR1"));
+        let footer = u32::from_le_bytes(parquet[parquet.len() - 8..][..4].try_into().unwrap());
+        assert!((footer as usize) < parquet.len() - 12);
+        // the kWh are plain doubles after the strings and the timestamps
+        assert!(parquet
+            .windows(8)
+            .any(|window| window == 0.5f64.to_le_bytes()));
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{collections::BTreeSet, path::Path};
+
+use crate::{
+    breakdown_by_band, filter_by_date, BillingPeriod, Money, PricePlanStrategy, SmartMeterData,
+};
+
+// A bill from the supplier to check the plan and the readings against, see bill.example.toml.
+// Amounts are as printed on the bill with VAT, credits are negative
+#[derive(Debug, Clone, Deserialize)]
+pub struct SupplierBill {
+    // the plan the bill was charged on
+    pub plan: String,
+    // only needed when the readings have more than one meter point
+    pub mprn: Option<String>,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    #[serde(default)]
+    pub units: Vec<BilledUnits>,
+    pub standing_charge: Option<Decimal>,
+    pub pso_levy: Option<Decimal>,
+    // credits like the government electricity credits aren't part of the plan, they are taken
+    // as billed
+    pub credits: Option<Decimal>,
+    pub total: Decimal,
+}
+
+// A unit charge line of the bill, like day, night or export
+#[derive(Debug, Clone, Deserialize)]
+pub struct BilledUnits {
+    pub band: String,
+    pub kwh: Option<f32>,
+    pub charge: Decimal,
+}
+
+// One line of the bill next to the same line worked out from the readings, a line only on
+// one side has nothing on the other
+#[derive(Debug, Clone)]
+pub struct ReconciledLine {
+    pub line: String,
+    pub billed_kwh: Option<f32>,
+    pub computed_kwh: Option<f32>,
+    pub billed: Option<Money>,
+    pub computed: Money,
+}
+
+impl ReconciledLine {
+    // What the bill charged on top of the readings, negative when it charged less
+    pub fn difference(&self) -> Option<Money> {
+        self.billed.map(|billed| billed - self.computed)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Reconciliation {
+    pub plan: String,
+    pub mprn: String,
+    pub period: BillingPeriod,
+    // readings missing on some days make the units come out short
+    pub days_with_readings: u32,
+    pub lines: Vec<ReconciledLine>,
+    // what the plan warned about pricing the readings of the bill
+    pub warning: Option<String>,
+}
+
+pub fn load_supplier_bill(filename: &Path) -> Result<SupplierBill> {
+    let contents = std::fs::read_to_string(filename)?;
+    toml::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("invalid bill file {}: {e}", filename.display()))
+}
+
+// Works out the period of the bill again with the plan, band by band like the unit charges
+// of the bill and then the fixed charges and the total
+pub fn reconcile_bill(
+    plan: &dyn PricePlanStrategy,
+    bill: &SupplierBill,
+    datapoints: &[SmartMeterData],
+) -> Result<Reconciliation> {
+    let period = BillingPeriod {
+        start: bill.from,
+        end: bill.to,
+    };
+    let mut readings = filter_by_date(datapoints.to_vec(), Some(bill.from), Some(bill.to));
+    if let Some(mprn) = &bill.mprn {
+        readings.retain(|datapoint| &*datapoint.mprn == mprn);
+    }
+    let meters: BTreeSet<&str> = readings.iter().map(|d| &*d.mprn).collect();
+    let mprn = match meters.len() {
+        0 => bail!(
+            "no readings from {} to {} to check the bill with",
+            bill.from,
+            bill.to
+        ),
+        1 => meters.into_iter().next().unwrap_or_default().to_string(),
+        _ => bail!(
+            "the readings have {} meter points, say which one the bill is for with mprn",
+            meters.len()
+        ),
+    };
+    let days_with_readings = readings
+        .iter()
+        .map(|datapoint| datapoint.date())
+        .collect::<BTreeSet<_>>()
+        .len() as u32;
+
+    let currency = plan.currency();
+    let money = |amount: Decimal| Money::of(amount, currency);
+    let mut lines = vec![];
+    let mut bands = breakdown_by_band(plan, &readings);
+    for units in &bill.units {
+        let computed = bands
+            .iter()
+            .position(|band| band.band.eq_ignore_ascii_case(&units.band))
+            .map(|index| bands.remove(index));
+        lines.push(ReconciledLine {
+            line: units.band.clone(),
+            billed_kwh: units.kwh,
+            computed_kwh: Some(computed.as_ref().map_or(0.0, |band| band.kwh)),
+            billed: Some(money(units.charge)),
+            computed: money(computed.map_or(Decimal::ZERO, |band| band.cost.signed_amount())),
+        });
+    }
+    // bands of the plan the bill has no line for
+    for band in bands {
+        lines.push(ReconciledLine {
+            line: band.band,
+            billed_kwh: None,
+            computed_kwh: Some(band.kwh),
+            billed: None,
+            computed: money(band.cost.signed_amount()),
+        });
+    }
+
+    let breakdown = plan.compute_bill_breakdown(&readings, &period);
+    let credits = money(bill.credits.unwrap_or_default());
+    let fixed = [
+        (
+            "standing charge",
+            bill.standing_charge,
+            breakdown.standing_charge,
+        ),
+        ("PSO levy", bill.pso_levy, breakdown.pso_levy),
+    ];
+    for (line, billed, computed) in fixed {
+        lines.push(ReconciledLine {
+            line: line.to_string(),
+            billed_kwh: None,
+            computed_kwh: None,
+            billed: billed.map(money),
+            computed: money(computed.signed_amount()),
+        });
+    }
+    if bill.credits.is_some() {
+        lines.push(ReconciledLine {
+            line: "credits".to_string(),
+            billed_kwh: None,
+            computed_kwh: None,
+            billed: Some(credits),
+            computed: credits,
+        });
+    }
+    lines.push(ReconciledLine {
+        line: "total".to_string(),
+        billed_kwh: None,
+        computed_kwh: None,
+        billed: Some(money(bill.total)),
+        computed: money(breakdown.total.signed_amount()) + credits,
+    });
+
+    Ok(Reconciliation {
+        plan: plan.name(),
+        mprn,
+        period,
+        days_with_readings,
+        lines,
+        warning: plan.pricing_warning(&readings),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, sse_airtricity_20};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn lines_of_the_bill_are_checked_against_the_readings() {
+        let data = vec![
+            reading_at("08-01-2024 18:00", 2.0),
+            reading_at("08-01-2024 03:30", 2.0),
+        ];
+        let plan = sse_airtricity_20();
+        let peak = breakdown_by_band(&plan, &data[..1])[0].cost.signed_amount();
+        let bill = SupplierBill {
+            plan: "SSEAirtricity20".to_string(),
+            mprn: None,
+            from: NaiveDate::from_ymd_opt(2024, 1, 8).unwrap(),
+            to: NaiveDate::from_ymd_opt(2024, 1, 9).unwrap(),
+            units: vec![BilledUnits {
+                band: "Peak".to_string(),
+                kwh: Some(1.0),
+                charge: peak + dec!(0.10),
+            }],
+            standing_charge: None,
+            pso_levy: None,
+            credits: None,
+            total: dec!(10),
+        };
+        let reconciliation = reconcile_bill(&plan, &bill, &data).unwrap();
+        assert_eq!(reconciliation.days_with_readings, 1);
+        let lines: Vec<(&str, Option<Money>)> = reconciliation
+            .lines
+            .iter()
+            .map(|line| (line.line.as_str(), line.difference()))
+            .collect();
+        assert_eq!(lines[0], ("Peak", Some(Money::new(dec!(0.10)))));
+        // the night reading has no line on the bill
+        assert_eq!(lines[1], ("night", None));
+        assert_eq!(lines[2], ("standing charge", None));
+        assert_eq!(lines.last().unwrap().0, "total");
+    }
+}
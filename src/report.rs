@@ -0,0 +1,902 @@
+use rust_decimal::Decimal;
+use std::fmt::Write;
+
+use crate::{
+    BatterySaving, BillBreakdown, ComparisonReport, DayCost, EffectiveRate, EnergyBillEntry,
+    EvCost, ExportEarnings, LevelPay, Locale, PeakCost, PlanComparison, Scenario,
+    ScenarioProjection, ShiftedCost, SwitchAdvice,
+};
+
+// The two standalone report layouts, both carry the same tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Html,
+    Markdown,
+}
+
+// One document for all the meters, each meter gets its own summary and tables
+pub fn render_report(reports: &[ComparisonReport], format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Html => render_html(reports),
+        ReportFormat::Markdown => render_markdown(reports),
+    }
+}
+
+// A titled table of already formatted cells
+struct Table {
+    title: String,
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+// Amounts and numbers with the decimal separator of the locale
+fn euro(entry: &EnergyBillEntry) -> String {
+    Locale::global().number(entry.to_string())
+}
+
+fn number(value: f32, decimals: usize) -> String {
+    Locale::global().number(format!("{value:.decimals$}"))
+}
+
+fn ranking_table(title: &str, results: &[PlanComparison]) -> Table {
+    // the usage is told apart once any plan credits the exports
+    let exports = results
+        .iter()
+        .any(|result| !result.breakdown.export_credit.is_zero());
+    let rows = results
+        .iter()
+        .enumerate()
+        .map(|(position, result)| {
+            let saving = match (&result.saving, result.current) {
+                (_, true) => Locale::global().text("current plan").to_string(),
+                (Some(saving), _) => {
+                    Locale::global().number(format!("{} ({}%)", saving.amount, saving.percentage))
+                }
+                (None, false) => String::new(),
+            };
+            let mut row = vec![(position + 1).to_string(), result.name.clone()];
+            if exports {
+                row.push(euro(&result.breakdown.import_cost));
+                row.push(euro(&result.breakdown.export_credit));
+            } else {
+                row.push(euro(&result.breakdown.usage));
+            }
+            row.extend([
+                euro(&result.breakdown.standing_charge),
+                euro(&result.breakdown.total),
+                saving,
+            ]);
+            row
+        })
+        .collect();
+    let usage: &[&str] = match exports {
+        true => &["Imports", "Export credit"],
+        false => &["Usage"],
+    };
+    Table {
+        title: title.to_string(),
+        header: [
+            &["#", "Plan"],
+            usage,
+            &["Standing charge", "Total", "Saving"],
+        ]
+        .concat(),
+        rows,
+    }
+}
+
+// The ranking tables are the steady state, this one is what the first bill looks like
+fn first_bill_table(results: &[PlanComparison]) -> Option<Table> {
+    if results.iter().all(|r| r.with_bonus.is_none()) {
+        return None;
+    }
+    let mut first_bill: Vec<(&str, EnergyBillEntry)> = results
+        .iter()
+        .map(|r| (r.name.as_str(), r.with_bonus.unwrap_or(r.breakdown.total)))
+        .collect();
+    first_bill.sort_by_key(|(_, total)| *total);
+    Some(Table {
+        title: "First bill with sign up bonuses".to_string(),
+        header: vec!["#", "Plan", "Total"],
+        rows: first_bill
+            .iter()
+            .enumerate()
+            .map(|(position, (name, total))| {
+                vec![(position + 1).to_string(), name.to_string(), euro(total)]
+            })
+            .collect(),
+    })
+}
+
+// Kept apart from the rankings so a projection is never mistaken for what the readings cost
+fn annual_estimate_table(report: &ComparisonReport) -> Option<Table> {
+    let mut estimates: Vec<(&str, &BillBreakdown)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.annual_estimate.as_ref()?)))
+        .collect();
+    if estimates.is_empty() {
+        return None;
+    }
+    estimates.sort_by_key(|(_, estimate)| estimate.total);
+    Some(Table {
+        title: Locale::global().fill(
+            "Projected cost of a year from {} days of readings",
+            &[&report.period.number_of_days()],
+        ),
+        header: vec!["#", "Plan", "Usage", "Standing charge", "Total"],
+        rows: estimates
+            .iter()
+            .enumerate()
+            .map(|(position, (name, estimate))| {
+                vec![
+                    (position + 1).to_string(),
+                    name.to_string(),
+                    euro(&estimate.usage),
+                    euro(&estimate.standing_charge),
+                    euro(&estimate.total),
+                ]
+            })
+            .collect(),
+    })
+}
+
+// The plans priced again on changed readings, cheapest first
+fn changed_readings_table(
+    title: String,
+    report: &ComparisonReport,
+    cost: impl Fn(&PlanComparison) -> Option<&ShiftedCost>,
+) -> Table {
+    let mut changed: Vec<(&str, &ShiftedCost)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), cost(r)?)))
+        .collect();
+    changed.sort_by_key(|(_, changed)| changed.breakdown.total);
+    Table {
+        title,
+        header: vec!["#", "Plan", "Total", "Saving on the same plan"],
+        rows: changed
+            .iter()
+            .enumerate()
+            .map(|(position, (name, changed))| {
+                vec![
+                    (position + 1).to_string(),
+                    name.to_string(),
+                    euro(&changed.breakdown.total),
+                    format!("{} ({}%)", changed.saving.amount, changed.saving.percentage),
+                ]
+            })
+            .collect(),
+    }
+}
+
+fn load_shift_table(report: &ComparisonReport) -> Option<Table> {
+    let shift = report.load_shift.as_ref()?;
+    let title = format!(
+        "With {} kWh a day moved from {} to {}",
+        shift.kwh_per_day, shift.from, shift.to
+    );
+    Some(changed_readings_table(title, report, |r| {
+        r.load_shifted.as_ref()
+    }))
+}
+
+fn solar_table(report: &ComparisonReport) -> Option<Table> {
+    let solar = report.solar.as_ref()?;
+    let title = format!(
+        "With solar, {:.1} kWh made, {:.0}% used in the house and {:.1} kWh exported",
+        solar.generation_kwh,
+        solar.self_consumption(),
+        solar.exported_kwh
+    );
+    Some(changed_readings_table(title, report, |r| {
+        r.with_solar.as_ref()
+    }))
+}
+
+fn usage_growth_table(report: &ComparisonReport) -> Option<Table> {
+    let growth = report.usage_growth.as_ref()?;
+    let title = format!(
+        "With the usage grown from {:.1} kWh to {:.1} kWh",
+        growth.imported_kwh, growth.grown_kwh
+    );
+    Some(changed_readings_table(title, report, |r| {
+        r.with_growth.as_ref()
+    }))
+}
+
+fn battery_table(report: &ComparisonReport) -> Option<Table> {
+    let battery = report.battery.as_ref()?;
+    let mut with_battery: Vec<(&str, &BatterySaving)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.with_battery.as_ref()?)))
+        .collect();
+    with_battery.sort_by_key(|(_, with)| with.breakdown.total);
+    let mut header = vec!["#", "Plan", "Total", "Saving on the same plan"];
+    if battery.cost.is_some() {
+        header.push("Pays for itself in");
+    }
+    Some(Table {
+        title: format!(
+            "With a {} kWh battery charging {}",
+            battery.capacity_kwh, battery.charge
+        ),
+        header,
+        rows: with_battery
+            .iter()
+            .enumerate()
+            .map(|(position, (name, with))| {
+                let mut row = vec![
+                    (position + 1).to_string(),
+                    name.to_string(),
+                    euro(&with.breakdown.total),
+                    format!("{} ({}%)", with.saving.amount, with.saving.percentage),
+                ];
+                if battery.cost.is_some() {
+                    row.push(match with.payback_years {
+                        Some(years) => format!("{years} years"),
+                        None => "never".to_string(),
+                    });
+                }
+                row
+            })
+            .collect(),
+    })
+}
+
+fn ev_table(report: &ComparisonReport) -> Option<Table> {
+    let charging = report.ev_charging.as_ref()?;
+    let mut ev_costs: Vec<(&str, &EvCost)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.ev_cost.as_ref()?)))
+        .collect();
+    ev_costs.sort_by_key(|(_, cost)| cost.ev);
+    Some(Table {
+        title: format!(
+            "EV charging, {} sessions and {:.1} kWh",
+            charging.sessions.len(),
+            charging.kwh
+        ),
+        header: vec!["#", "Plan", "EV", "EV per kWh", "Rest of the house"],
+        rows: ev_costs
+            .iter()
+            .enumerate()
+            .map(|(position, (name, cost))| {
+                vec![
+                    (position + 1).to_string(),
+                    name.to_string(),
+                    euro(&cost.ev),
+                    format!("{:.4}", cost.ev_per_kwh()),
+                    euro(&cost.house),
+                ]
+            })
+            .collect(),
+    })
+}
+
+fn circuits_table(report: &ComparisonReport) -> Option<Table> {
+    let metering = report.sub_metering.as_ref()?;
+    let kwh: Vec<String> = metering
+        .circuits
+        .iter()
+        .map(|circuit| format!("{} {:.1} kWh", circuit.name, circuit.kwh))
+        .collect();
+    Some(Table {
+        title: format!(
+            "Circuits, {} and the rest of the house {:.1} kWh",
+            kwh.join(", "),
+            metering.rest_kwh
+        ),
+        header: vec!["#", "Plan", "Circuits", "Rest of the house"],
+        rows: report
+            .plans
+            .iter()
+            .enumerate()
+            .filter_map(|(position, plan)| {
+                let costs = plan.circuit_costs.as_ref()?;
+                let circuits: Vec<String> = costs
+                    .circuits
+                    .iter()
+                    .map(|circuit| format!("{} {}", circuit.name, euro(&circuit.cost)))
+                    .collect();
+                Some(vec![
+                    (position + 1).to_string(),
+                    plan.name.clone(),
+                    circuits.join(", "),
+                    euro(&costs.rest_of_house),
+                ])
+            })
+            .collect(),
+    })
+}
+
+fn baseload_table(report: &ComparisonReport) -> Option<Table> {
+    let baseload = report.baseload.as_ref()?;
+    let mut costs: Vec<(&str, EnergyBillEntry)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.baseload_cost?)))
+        .collect();
+    costs.sort_by_key(|(_, cost)| *cost);
+    let high = if baseload.high {
+        ", higher than most homes"
+    } else {
+        ""
+    };
+    Some(Table {
+        title: format!(
+            "Baseload of {:.0} W, {:.0} kWh a year{high}",
+            baseload.kw * 1000.0,
+            baseload.annual_kwh
+        ),
+        header: vec!["Plan", "Cost of a year"],
+        rows: costs
+            .iter()
+            .map(|(name, cost)| vec![name.to_string(), euro(cost)])
+            .collect(),
+    })
+}
+
+fn peak_demand_table(report: &ComparisonReport) -> Option<Table> {
+    let demand = report.peak_demand.as_ref()?;
+    Some(Table {
+        title: Locale::global().fill("Maximum demand of {} kW", &[&number(demand.max_kw(), 2)]),
+        header: vec!["Interval ending", "kW", "kWh"],
+        rows: demand
+            .top
+            .iter()
+            .map(|peak| {
+                vec![
+                    peak.end.format("%d-%m-%Y %H:%M").to_string(),
+                    format!("{:.2}", peak.kw),
+                    format!("{:.2}", peak.kwh),
+                ]
+            })
+            .collect(),
+    })
+}
+
+fn peak_cost_table(report: &ComparisonReport) -> Option<Table> {
+    report.peak_demand.as_ref()?;
+    let mut costs: Vec<(&str, &PeakCost)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.peak_cost.as_ref()?)))
+        .collect();
+    costs.sort_by_key(|(_, cost)| cost.share);
+    Some(Table {
+        title: "Cost of the imports in the peak".to_string(),
+        header: vec!["Plan", "Peak cost", "Share of the imports"],
+        rows: costs
+            .iter()
+            .map(|(name, cost)| {
+                vec![
+                    name.to_string(),
+                    euro(&cost.cost),
+                    format!("{}%", cost.share),
+                ]
+            })
+            .collect(),
+    })
+}
+
+fn switch_table(report: &ComparisonReport) -> Option<Table> {
+    let contract = report.contract.as_ref()?;
+    let mut advice: Vec<(&str, &SwitchAdvice)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.switch_advice.as_ref()?)))
+        .collect();
+    advice.sort_by_key(|(_, advice)| std::cmp::Reverse(advice.switch_now_gain));
+    Some(Table {
+        title: format!(
+            "Switching on {} before the contract ends on {}",
+            contract.switch_on, contract.end
+        ),
+        header: vec![
+            "Plan",
+            "Saving a day",
+            "Fee paid back by",
+            "Switching now gains",
+            "Switch",
+        ],
+        rows: advice
+            .iter()
+            .map(|(name, advice)| {
+                vec![
+                    name.to_string(),
+                    advice.daily_saving.to_string(),
+                    advice
+                        .break_even
+                        .map_or_else(|| "never".to_string(), |date| date.to_string()),
+                    advice.switch_now_gain.to_string(),
+                    match (advice.break_even, advice.switch_now) {
+                        (None, _) => "stay",
+                        (Some(_), true) => "now",
+                        (Some(_), false) => "at the contract end",
+                    }
+                    .to_string(),
+                ]
+            })
+            .collect(),
+    })
+}
+
+fn export_table(report: &ComparisonReport) -> Option<Table> {
+    let mut exports: Vec<(&str, &ExportEarnings)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.exports.as_ref()?)))
+        .collect();
+    if exports.is_empty() {
+        return None;
+    }
+    exports.sort_by_key(|(_, exports)| std::cmp::Reverse(exports.credited.amount()));
+    let mut rows = vec![];
+    for (name, exports) in exports {
+        for month in &exports.months {
+            rows.push(vec![
+                name.to_string(),
+                month.month.to_string(),
+                format!("{:.2}", month.kwh),
+                month.earned.to_string(),
+            ]);
+        }
+        rows.push(vec![
+            name.to_string(),
+            format!("credited, {}", exports.rules),
+            format!("{:.2}", exports.kwh),
+            exports.credited.to_string(),
+        ]);
+    }
+    Some(Table {
+        title: "Export earnings".to_string(),
+        header: vec!["Plan", "Month", "kWh exported", "Earned"],
+        rows,
+    })
+}
+
+fn effective_rate_table(report: &ComparisonReport) -> Option<Table> {
+    let mut rates: Vec<(&str, &EffectiveRate)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.effective_rate.as_ref()?)))
+        .collect();
+    if rates.is_empty() {
+        return None;
+    }
+    rates.sort_by_key(|(_, rate)| rate.all_in_cents);
+    Some(Table {
+        title: "Average cost of a kWh imported".to_string(),
+        header: vec!["Plan", "All in c/kWh", "Energy c/kWh"],
+        rows: rates
+            .iter()
+            .map(|(name, rate)| {
+                vec![
+                    name.to_string(),
+                    format!("{:.2}", rate.all_in_cents),
+                    format!("{:.2}", rate.energy_cents),
+                ]
+            })
+            .collect(),
+    })
+}
+
+fn level_pay_table(report: &ComparisonReport) -> Option<Table> {
+    let mut level_pay: Vec<(&str, &LevelPay)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.level_pay.as_ref()?)))
+        .collect();
+    let buffer = level_pay.first()?.1.buffer;
+    level_pay.sort_by_key(|(_, level_pay)| level_pay.monthly_payment.amount());
+    Some(Table {
+        title: format!(
+            "Level pay with a {}% buffer",
+            (buffer * rust_decimal::Decimal::ONE_HUNDRED).normalize()
+        ),
+        header: vec!["Plan", "A month", "Lowest balance", "After a year"],
+        rows: level_pay
+            .iter()
+            .map(|(name, level_pay)| {
+                vec![
+                    name.to_string(),
+                    level_pay.monthly_payment.to_string(),
+                    level_pay.lowest_balance().to_string(),
+                    level_pay.final_balance().to_string(),
+                ]
+            })
+            .collect(),
+    })
+}
+
+fn scenario_table(report: &ComparisonReport, index: usize, scenario: &Scenario) -> Table {
+    let mut projected: Vec<(&str, &ScenarioProjection)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.scenarios.get(index)?)))
+        .collect();
+    projected.sort_by_key(|(_, projection)| projection.total);
+    Table {
+        title: Locale::global().fill("Scenario {}", &[scenario]),
+        header: vec!["Plan", "Each year", "Total"],
+        rows: projected
+            .iter()
+            .map(|(name, projection)| {
+                let years: Vec<String> = projection.years.iter().map(|y| euro(&y.total)).collect();
+                vec![name.to_string(), years.join(", "), euro(&projection.total)]
+            })
+            .collect(),
+    }
+}
+
+fn free_time_table(result: &PlanComparison) -> Table {
+    Table {
+        title: Locale::global().fill("Free time choices of {}", &[&result.name]),
+        header: vec!["Free window", "Total", "Chosen"],
+        rows: result
+            .free_time_choices
+            .iter()
+            .map(|choice| {
+                vec![
+                    choice.window.clone(),
+                    euro(&choice.breakdown.total),
+                    if choice.chosen { "yes" } else { "" }.to_string(),
+                ]
+            })
+            .collect(),
+    }
+}
+
+fn expensive_days_table(name: &str, days: &[DayCost]) -> Table {
+    Table {
+        title: Locale::global().fill("Most expensive days on {}", &[&name]),
+        header: vec!["Day", "kWh", "Cost", "Bands"],
+        rows: days
+            .iter()
+            .map(|day| {
+                let bands: Vec<String> = day
+                    .bands
+                    .iter()
+                    .map(|b| format!("{} {} for {:.1} kWh", b.band, euro(&b.cost), b.kwh))
+                    .collect();
+                vec![
+                    day.date.format("%d-%m-%Y").to_string(),
+                    format!("{:.1}", day.kwh),
+                    euro(&day.cost),
+                    bands.join(", "),
+                ]
+            })
+            .collect(),
+    }
+}
+
+// Every plan is split the same way, so the buckets line up across plans
+fn cheapest_per_bucket(results: &[PlanComparison]) -> Option<Table> {
+    let first = results.first().filter(|r| !r.buckets.is_empty())?;
+    let rows = first
+        .buckets
+        .iter()
+        .enumerate()
+        .filter_map(|(index, bucket)| {
+            let (name, total) = results
+                .iter()
+                .filter_map(|r| r.buckets.get(index).map(|b| (&r.name, b.breakdown.total)))
+                .min_by_key(|(_, total)| *total)?;
+            Some(vec![bucket.bucket.to_string(), name.clone(), euro(&total)])
+        })
+        .collect();
+    Some(Table {
+        title: "Cheapest plan per period".to_string(),
+        header: vec!["Period", "Plan", "Total"],
+        rows,
+    })
+}
+
+// The saving is over the cheapest plan of the whole period
+fn monthly_winners_table(report: &ComparisonReport) -> Option<Table> {
+    let winners = report.monthly_winners.as_ref()?;
+    Some(Table {
+        title: format!(
+            "Cheapest plan per month, {} beaten in {} of {} months",
+            winners.overall,
+            winners.beaten,
+            winners.months.len()
+        ),
+        header: vec!["Month", "Plan", "Total", "Saving"],
+        rows: winners
+            .months
+            .iter()
+            .map(|month| {
+                vec![
+                    month.month.to_string(),
+                    month.plan.clone(),
+                    euro(&month.total),
+                    euro(&month.saving()),
+                ]
+            })
+            .collect(),
+    })
+}
+
+fn sensitivity_table(report: &ComparisonReport) -> Option<Table> {
+    let sensitivity = report.rate_sensitivity.as_ref()?;
+    let percent = (sensitivity.change * Decimal::ONE_HUNDRED).normalize();
+    Some(Table {
+        title: format!(
+            "Ranking with the rates of each plan {percent}% higher or lower, the cheapest plan {}",
+            if sensitivity.winner_holds {
+                "holds"
+            } else {
+                "can lose its place"
+            }
+        ),
+        header: vec!["Plan", "Rank", "Higher", "Lower", "Lead goes at"],
+        rows: sensitivity
+            .plans
+            .iter()
+            .map(|plan| {
+                let lead = plan.lead_lost_at.map_or(String::new(), |lead| {
+                    let lead = format!("{}%", (lead * Decimal::ONE_HUNDRED).round_dp(1));
+                    if plan.fragile {
+                        format!("{lead} (fragile)")
+                    } else {
+                        lead
+                    }
+                });
+                vec![
+                    plan.plan.clone(),
+                    plan.rank.to_string(),
+                    plan.rank_if_higher.to_string(),
+                    plan.rank_if_lower.to_string(),
+                    lead,
+                ]
+            })
+            .collect(),
+    })
+}
+
+// What is known of each plan, so it can be checked against the price list of the supplier
+fn plan_details_table(results: &[PlanComparison]) -> Option<Table> {
+    if results
+        .iter()
+        .all(|r| r.metadata.is_none() && r.source.is_none())
+    {
+        return None;
+    }
+    Some(Table {
+        title: "Plan details".to_string(),
+        header: vec!["Plan", "Details", "Source"],
+        rows: results
+            .iter()
+            .map(|r| {
+                let details = r.metadata.as_ref().map_or(String::new(), |m| m.summary());
+                let source = r.source.map_or(String::new(), |source| {
+                    Locale::global().text(&source.to_string()).to_string()
+                });
+                vec![r.name.clone(), details, source]
+            })
+            .collect(),
+    })
+}
+
+fn tables(report: &ComparisonReport) -> Vec<Table> {
+    let mut tables = vec![ranking_table("Electricity plans", &report.plans)];
+    tables.extend(plan_details_table(&report.plans));
+    tables.extend(export_table(report));
+    tables.extend(effective_rate_table(report));
+    tables.extend(first_bill_table(&report.plans));
+    tables.extend(annual_estimate_table(report));
+    tables.extend(load_shift_table(report));
+    tables.extend(battery_table(report));
+    tables.extend(solar_table(report));
+    tables.extend(usage_growth_table(report));
+    tables.extend(ev_table(report));
+    tables.extend(circuits_table(report));
+    tables.extend(baseload_table(report));
+    tables.extend(peak_demand_table(report));
+    tables.extend(peak_cost_table(report));
+    tables.extend(switch_table(report));
+    tables.extend(level_pay_table(report));
+    tables.extend(sensitivity_table(report));
+    for (index, scenario) in report.scenarios.iter().enumerate() {
+        tables.push(scenario_table(report, index, scenario));
+    }
+    for result in report
+        .plans
+        .iter()
+        .filter(|r| !r.free_time_choices.is_empty())
+    {
+        tables.push(free_time_table(result));
+    }
+    for result in report.plans.iter().filter(|r| !r.expensive_days.is_empty()) {
+        tables.push(expensive_days_table(&result.name, &result.expensive_days));
+    }
+    if !report.gas_plans.is_empty() {
+        tables.push(ranking_table("Gas plans", &report.gas_plans));
+    }
+    if !report.estimated_annual_bills.is_empty() {
+        tables.push(ranking_table(
+            "Estimated annual bills on the CRU standard usage",
+            &report.estimated_annual_bills,
+        ));
+    }
+    match monthly_winners_table(report) {
+        Some(table) => tables.push(table),
+        None => tables.extend(cheapest_per_bucket(&report.plans)),
+    }
+    for result in report.plans.iter().filter(|r| !r.bands.is_empty()) {
+        tables.push(Table {
+            title: Locale::global().fill("{} bands", &[&result.name]),
+            header: vec!["Band", "kWh", "Cost"],
+            rows: result
+                .bands
+                .iter()
+                .map(|band| vec![band.band.clone(), number(band.kwh, 2), euro(&band.cost)])
+                .collect(),
+        });
+    }
+    tables
+}
+
+fn summary(report: &ComparisonReport) -> String {
+    let locale = Locale::global();
+    let period = &report.period;
+    let mut summary = locale.fill(
+        "Meter point {}, billing period {} to {} ({} days).",
+        &[
+            &report.mprn,
+            &locale.date(period.start),
+            &locale.date(period.end),
+            &period.number_of_days(),
+        ],
+    );
+    if let Some(cheapest) = report.plans.first() {
+        summary.push_str(&locale.fill(
+            " The cheapest plan is {} at {}.",
+            &[&cheapest.name, &euro(&cheapest.breakdown.total)],
+        ));
+    }
+    for estimated in &report.estimated {
+        summary.push_str(&locale.fill(
+            " The readings from {} to {} are estimated, {} kWh spread like the standard profile.",
+            &[
+                &locale.date(estimated.from),
+                &locale.date(estimated.to),
+                &number(estimated.kwh, 1),
+            ],
+        ));
+    }
+    if let Some(coverage) = report.coverage.filter(|c| !c.is_complete()) {
+        summary.push_str(&locale.fill(
+            " Only {}% of the intervals have actual readings, {}% are filled in, {}% estimated and {}% missing.",
+            &[
+                &number(coverage.actual_share() * 100.0, 1),
+                &number(coverage.share(coverage.filled_intervals) * 100.0, 1),
+                &number(coverage.share(coverage.estimated_intervals) * 100.0, 1),
+                &number(coverage.share(coverage.missing_intervals) * 100.0, 1),
+            ],
+        ));
+    }
+    if let Some(carbon) = &report.carbon {
+        let _ = write!(
+            summary,
+            " The imports emitted about {:.1} kg of CO2, moving {:.0}% of the usage of each day to its cleanest interval would save {:.1} kg.",
+            carbon.kg_co2,
+            carbon.shiftable_share * 100.0,
+            carbon.shifting_saves_kg_co2
+        );
+    }
+    summary
+}
+
+fn render_markdown(reports: &[ComparisonReport]) -> String {
+    // pipes would end the cell early
+    let cell = |text: &str| text.replace('|', "\\|");
+    let locale = Locale::global();
+    let mut out = format!("# {}\n\n", locale.text("Energy plan comparison"));
+    for report in reports {
+        let _ = writeln!(out, "{}\n", summary(report));
+        for table in tables(report) {
+            let header: Vec<&str> = table.header.iter().map(|h| locale.text(h)).collect();
+            let _ = writeln!(out, "## {}\n", locale.text(&table.title));
+            let _ = writeln!(out, "| {} |", header.join(" | "));
+            let _ = writeln!(out, "|{}", "---|".repeat(table.header.len()));
+            for row in &table.rows {
+                let cells: Vec<String> = row.iter().map(|c| cell(c)).collect();
+                let _ = writeln!(out, "| {} |", cells.join(" | "));
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_html(reports: &[ComparisonReport]) -> String {
+    let locale = Locale::global();
+    let title = escape_html(locale.text("Energy plan comparison"));
+    let mut out = format!(
+        concat!(
+            "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n",
+            "<title>{}</title>\n<style>\n",
+            "body {{ font-family: sans-serif; margin: 2em; }}\n",
+            "table {{ border-collapse: collapse; margin-bottom: 2em; }}\n",
+            "th, td {{ border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }}\n",
+            "th {{ background: #f0f0f0; }}\n",
+            "</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        ),
+        locale, title, title
+    );
+    for report in reports {
+        let _ = writeln!(out, "<p>{}</p>", escape_html(&summary(report)));
+        for table in tables(report) {
+            let _ = writeln!(
+                out,
+                "<h2>{}</h2>\n<table>\n<tr>",
+                escape_html(locale.text(&table.title))
+            );
+            for header in &table.header {
+                let _ = writeln!(out, "<th>{}</th>", escape_html(locale.text(header)));
+            }
+            out.push_str("</tr>\n");
+            for row in &table.rows {
+                out.push_str("<tr>");
+                for cell in row {
+                    let _ = write!(out, "<td>{}</td>", escape_html(cell));
+                }
+                out.push_str("</tr>\n");
+            }
+            out.push_str("</table>\n");
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builtin_plans, compare_meter_points, meter_data::reading_at, ComparisonOptions};
+
+    fn reports() -> Vec<ComparisonReport> {
+        let data = vec![reading_at("08-01-2024 18:00", 1.5)];
+        let options = ComparisonOptions {
+            current_plan: Some("SSEAirtricity20".to_string()),
+            ..ComparisonOptions::default()
+        };
+        let mut reports = compare_meter_points(&builtin_plans()[..2], data, &options).unwrap();
+        reports[0].plans[1].name = "Night | <Saver>".to_string();
+        reports
+    }
+
+    #[test]
+    fn markdown_tables_escape_the_pipes_of_the_cells() {
+        let markdown = render_report(&reports(), ReportFormat::Markdown);
+        assert!(markdown.starts_with("# Energy plan comparison\n\nMeter point 10000000000, "));
+        assert!(markdown.contains("The cheapest plan is SSEAirtricity20 at €0.95."));
+        assert!(markdown.contains(
+            "| # | Plan | Usage | Standing charge | Total | Saving |\n|---|---|---|---|---|---|\n"
+        ));
+        assert!(markdown.contains("| 1 | SSEAirtricity20 | €0.29 | €0.66 | €0.95 | current plan |"));
+        assert!(markdown.contains("| 2 | Night \\| <Saver> | €0.25 |"));
+    }
+
+    #[test]
+    fn html_reports_escape_the_text_of_the_cells() {
+        let html = render_report(&reports(), ReportFormat::Html);
+        assert!(html.starts_with("<!DOCTYPE html>\n<html lang=\"en-IE\">"));
+        assert!(html.ends_with("</body>\n</html>\n"));
+        assert!(html.contains("<td>2</td><td>Night | &lt;Saver&gt;</td><td>€0.25</td>"));
+        assert!(!html.contains("<Saver>"));
+    }
+}
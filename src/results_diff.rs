@@ -0,0 +1,233 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// The parts of a saved json comparison the diff looks at, the rest of it is ignored so the
+// files of older and newer versions can be compared
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SavedReport {
+    pub mprn: String,
+    pub period: SavedPeriod,
+    pub plans: Vec<SavedPlan>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedPeriod {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SavedPlan {
+    pub name: String,
+    pub breakdown: SavedBreakdown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SavedBreakdown {
+    pub total: SavedEntry,
+}
+
+// an EnergyBillEntry as it is written, without its currency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", content = "amount", rename_all = "snake_case")]
+pub enum SavedEntry {
+    Credit(#[serde(with = "rust_decimal::serde::float")] Decimal),
+    Debit(#[serde(with = "rust_decimal::serde::float")] Decimal),
+}
+
+impl SavedEntry {
+    pub fn signed_amount(&self) -> Decimal {
+        match self {
+            SavedEntry::Credit(amount) => -amount,
+            SavedEntry::Debit(amount) => *amount,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedFile {
+    One(SavedReport),
+    Several(Vec<SavedReport>),
+}
+
+// What --format json printed for one meter point or for several
+pub fn load_saved_results(filename: &Path) -> Result<Vec<SavedReport>> {
+    let contents = std::fs::read_to_string(filename)?;
+    let saved: SavedFile = serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not a json comparison", filename.display()))?;
+    Ok(match saved {
+        SavedFile::One(report) => vec![report],
+        SavedFile::Several(reports) => reports,
+    })
+}
+
+// A plan whose total or place changed, none before for a new plan and none after for one that
+// is gone. Ranks start at 1 for the cheapest and totals are signed, credits below zero
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlanChange {
+    pub plan: String,
+    pub rank_before: Option<usize>,
+    pub rank_after: Option<usize>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub total_before: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub total_after: Option<Decimal>,
+}
+
+impl PlanChange {
+    // what the plan costs more now, below zero when it got cheaper
+    pub fn change(&self) -> Option<Decimal> {
+        Some(self.total_after? - self.total_before?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MeterPointDiff {
+    pub mprn: String,
+    pub period_before: SavedPeriod,
+    pub period_after: SavedPeriod,
+    pub cheapest_before: Option<String>,
+    pub cheapest_after: Option<String>,
+    // in the order of the later run, the plans that are gone last
+    pub changes: Vec<PlanChange>,
+    pub unchanged: usize,
+}
+
+impl MeterPointDiff {
+    pub fn is_unchanged(&self) -> bool {
+        self.changes.is_empty() && self.period_before == self.period_after
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResultsDiff {
+    pub meter_points: Vec<MeterPointDiff>,
+    // meter points in only one of the runs
+    pub only_before: Vec<String>,
+    pub only_after: Vec<String>,
+}
+
+fn diff_meter_point(before: &SavedReport, after: &SavedReport) -> MeterPointDiff {
+    let find = |report: &SavedReport, name: &str| {
+        report
+            .plans
+            .iter()
+            .position(|plan| plan.name == name)
+            .map(|index| {
+                (
+                    index + 1,
+                    report.plans[index].breakdown.total.signed_amount(),
+                )
+            })
+    };
+    let names = after.plans.iter().chain(
+        before
+            .plans
+            .iter()
+            .filter(|plan| find(after, &plan.name).is_none()),
+    );
+    let mut changes = vec![];
+    let mut unchanged = 0;
+    for plan in names {
+        let (rank_before, total_before) = find(before, &plan.name).unzip();
+        let (rank_after, total_after) = find(after, &plan.name).unzip();
+        if rank_before == rank_after && total_before == total_after {
+            unchanged += 1;
+            continue;
+        }
+        changes.push(PlanChange {
+            plan: plan.name.clone(),
+            rank_before,
+            rank_after,
+            total_before,
+            total_after,
+        });
+    }
+    let cheapest = |report: &SavedReport| report.plans.first().map(|plan| plan.name.clone());
+    MeterPointDiff {
+        mprn: after.mprn.clone(),
+        period_before: before.period,
+        period_after: after.period,
+        cheapest_before: cheapest(before),
+        cheapest_after: cheapest(after),
+        changes,
+        unchanged,
+    }
+}
+
+// The plans of every meter point in both runs whose total or rank changed from the first run
+// to the second, like before and after an update of the plan registry
+pub fn diff_results(before: &[SavedReport], after: &[SavedReport]) -> ResultsDiff {
+    let find = |reports: &'_ [SavedReport], mprn: &str| {
+        reports.iter().position(|report| report.mprn == mprn)
+    };
+    ResultsDiff {
+        meter_points: after
+            .iter()
+            .filter_map(|report| {
+                let index = find(before, &report.mprn)?;
+                Some(diff_meter_point(&before[index], report))
+            })
+            .collect(),
+        only_before: before
+            .iter()
+            .filter(|report| find(after, &report.mprn).is_none())
+            .map(|report| report.mprn.clone())
+            .collect(),
+        only_after: after
+            .iter()
+            .filter(|report| find(before, &report.mprn).is_none())
+            .map(|report| report.mprn.clone())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builtin_plans, compare_meter_points, meter_data::reading_at, ComparisonOptions};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn changed_and_gone_plans_are_listed() {
+        let data = vec![
+            reading_at("08-01-2024 03:30", 2.0),
+            reading_at("08-01-2024 18:00", 1.0),
+        ];
+        let mut plans = builtin_plans();
+        let reports =
+            compare_meter_points(&plans, data.clone(), &ComparisonOptions::default()).unwrap();
+        let json = serde_json::to_string(&reports).unwrap();
+        let before: Vec<SavedReport> = serde_json::from_str(&json).unwrap();
+        assert_eq!(before[0].plans.len(), plans.len());
+        assert!(diff_results(&before, &before).meter_points[0].is_unchanged());
+
+        // the last plan leaves and the totals are the same otherwise
+        let last = before[0].plans.last().unwrap().name.clone();
+        plans.retain(|plan| plan.name() != last);
+        let reports = compare_meter_points(&plans, data, &ComparisonOptions::default()).unwrap();
+        let after: Vec<SavedReport> =
+            serde_json::from_str(&serde_json::to_string(&reports).unwrap()).unwrap();
+        let diff = diff_results(&before, &after);
+        let meter = &diff.meter_points[0];
+        assert_eq!(meter.changes.len(), 1, "{meter:?}");
+        assert_eq!(meter.changes[0].plan, last);
+        assert_eq!(meter.changes[0].rank_after, None);
+        assert_eq!(meter.changes[0].change(), None);
+        assert_eq!(meter.unchanged, plans.len());
+
+        let mut dearer = after.clone();
+        dearer[0].plans[0].breakdown.total = SavedEntry::Debit(dec!(100));
+        let diff = diff_results(&after, &dearer);
+        let change = &diff.meter_points[0].changes[0];
+        assert_eq!(
+            change.change(),
+            Some(dec!(100) - after[0].plans[0].breakdown.total.signed_amount())
+        );
+        assert!(diff.only_before.is_empty() && diff.only_after.is_empty());
+    }
+}
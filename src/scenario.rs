@@ -0,0 +1,253 @@
+use anyhow::{bail, Context, Result};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    projection::annual_usage, BillingPeriod, EnergyBillEntry, Money, PricePlanStrategy,
+    SmartMeterData, SmartMeterDataType, YearProjection,
+};
+
+// How prices move over the years of a projection, each a change a year as a fraction, 0.05
+// for rising 5% a year and zero for frozen. Given on the command line like
+// rising:unit=5%,standing=0%,export=-2%, whatever is left out stays frozen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Scenario {
+    pub name: String,
+    pub unit_rates: Decimal,
+    pub standing_charges: Decimal,
+    pub export_rates: Decimal,
+}
+
+// What a plan costs each year under a scenario, and over all of them
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioProjection {
+    pub scenario: String,
+    pub years: Vec<YearProjection>,
+    pub total: EnergyBillEntry,
+}
+
+fn change(key: &str, value: &str) -> Result<Decimal> {
+    let value = value.trim();
+    let change = match value.strip_suffix('%') {
+        Some(percent) => percent
+            .trim()
+            .parse::<Decimal>()
+            .map(|p| p / Decimal::ONE_HUNDRED),
+        None => value.parse(),
+    }
+    .with_context(|| format!("{key}={value} is not a change a year like 5% or -0.02"))?;
+    if change <= -Decimal::ONE {
+        bail!("{key}={value} would bring the prices below zero");
+    }
+    Ok(change)
+}
+
+impl FromStr for Scenario {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Scenario> {
+        let (name, fields) = text.split_once(':').unwrap_or((text, ""));
+        if name.trim().is_empty() {
+            bail!("the scenario {text} has no name");
+        }
+        let mut scenario = Scenario {
+            name: name.trim().to_string(),
+            unit_rates: Decimal::ZERO,
+            standing_charges: Decimal::ZERO,
+            export_rates: Decimal::ZERO,
+        };
+        for field in fields.split(',').filter(|field| !field.trim().is_empty()) {
+            let Some((key, value)) = field.split_once('=') else {
+                bail!("{field} in {text} is not a key=value pair");
+            };
+            match key.trim() {
+                "unit" => scenario.unit_rates = change(key, value)?,
+                "standing" => scenario.standing_charges = change(key, value)?,
+                "export" => scenario.export_rates = change(key, value)?,
+                other => {
+                    bail!("unknown {other} in {text}, the scenario takes unit, standing and export")
+                }
+            }
+        }
+        Ok(scenario)
+    }
+}
+
+// Like rising (unit rates +5%, standing charges +0%, export rates -2% a year)
+impl Display for Scenario {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = |change: Decimal| {
+            let percent = (change * Decimal::ONE_HUNDRED).normalize();
+            if percent < Decimal::ZERO {
+                format!("{percent}%")
+            } else {
+                format!("+{percent}%")
+            }
+        };
+        write!(
+            f,
+            "{} (unit rates {}, standing charges {}, export rates {} a year)",
+            self.name,
+            percent(self.unit_rates),
+            percent(self.standing_charges),
+            percent(self.export_rates)
+        )
+    }
+}
+
+// A year of the plan kept in the parts that move apart
+struct YearParts {
+    imports: Decimal,
+    exports: Decimal,
+    standing_charge: Decimal,
+    // the levy and the bill credit, which no scenario changes
+    fixed: Decimal,
+}
+
+impl YearParts {
+    fn of(
+        plan: &dyn PricePlanStrategy,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+    ) -> Self {
+        let year = BillingPeriod::starting_at(period.start, 365);
+        let charges = plan.bill_breakdown_for_usage(EnergyBillEntry::ZERO, &year);
+        YearParts {
+            imports: annual_usage(plan, datapoints, period, SmartMeterDataType::ActiveImport),
+            exports: annual_usage(plan, datapoints, period, SmartMeterDataType::ActiveExport),
+            standing_charge: charges.standing_charge.signed_amount(),
+            fixed: charges.pso_levy.signed_amount() + charges.bill_credit.signed_amount(),
+        }
+    }
+}
+
+impl Scenario {
+    // The prices of the year against the first one. The rates of a plan are fixed for its
+    // fixed_rate_months, after that they have caught up with every year of change so far
+    fn factor(change: Decimal, year: u32, fixed: Decimal) -> Decimal {
+        let mut moved = Decimal::ONE;
+        for _ in 0..year {
+            moved *= Decimal::ONE + change;
+        }
+        fixed + moved * (Decimal::ONE - fixed)
+    }
+
+    // Like project_plan, with the discount mixed in by how many months of each year are still
+    // discounted and the sign up bonus off the first year, and the prices moved every year
+    pub fn project(
+        &self,
+        plan: &dyn PricePlanStrategy,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+        years: u32,
+    ) -> ScenarioProjection {
+        let discounted = YearParts::of(plan, datapoints, period);
+        let standard = plan
+            .at_standard_rates()
+            .map(|standard| YearParts::of(standard.as_ref(), datapoints, period));
+        let standard = standard.as_ref().unwrap_or(&discounted);
+        let discount_months = plan.discount_months();
+        let fixed_rate_months = plan.fixed_rate_months();
+        let bonus = plan.sign_up_bonus().amount();
+        let months_of = |months: u32, year: u32| {
+            Decimal::from(months.saturating_sub(year * 12).min(12)) / Decimal::from(12)
+        };
+
+        let years: Vec<YearProjection> = (0..years)
+            .map(|year| {
+                let fixed = months_of(fixed_rate_months, year);
+                let price = |parts: &YearParts| {
+                    parts.imports * Self::factor(self.unit_rates, year, fixed)
+                        + parts.exports * Self::factor(self.export_rates, year, fixed)
+                        + parts.standing_charge * Self::factor(self.standing_charges, year, fixed)
+                        + parts.fixed
+                };
+                let share = months_of(discount_months, year);
+                let mut total =
+                    price(&discounted) * share + price(standard) * (Decimal::ONE - share);
+                if year == 0 {
+                    total -= bonus;
+                }
+                YearProjection {
+                    year: year + 1,
+                    total: EnergyBillEntry::from_signed(
+                        Money::new(total).round(&plan.rounding()).amount(),
+                    )
+                    .in_currency(plan.currency()),
+                }
+            })
+            .collect();
+        ScenarioProjection {
+            scenario: self.name.clone(),
+            total: years
+                .iter()
+                .map(|year| year.total)
+                .sum::<EnergyBillEntry>()
+                .in_currency(plan.currency()),
+            years,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, project_plan, ConfigurablePlan};
+    use rust_decimal_macros::dec;
+
+    fn plan(fixed_rate_months: u32) -> ConfigurablePlan {
+        toml::from_str(&format!(
+            r#"
+name = "fixed"
+standing_charge = {{ per_day = 0.5 }}
+fixed_rate_months = {fixed_rate_months}
+
+[[bands]]
+name = "all day"
+start = "00:00"
+end = "00:00"
+rate = 0.40
+"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn rates_only_rise_once_the_fixed_term_ends() {
+        let scenario: Scenario = "rising:unit=10%,standing=0".parse().unwrap();
+        assert_eq!(scenario.unit_rates, dec!(0.1));
+        assert_eq!(
+            scenario.to_string(),
+            "rising (unit rates +10%, standing charges +0%, export rates +0% a year)"
+        );
+        let data = vec![reading_at("08-01-2024 12:00", 2.0)];
+        let period = BillingPeriod::from_data(&data).unwrap();
+
+        // frozen prices are the plain projection
+        let frozen: Scenario = "frozen".parse().unwrap();
+        let variable = plan(0);
+        assert_eq!(
+            frozen.project(&variable, &data, &period, 3).years,
+            project_plan(&variable, &data, &period, 3)
+        );
+
+        let totals = |plan: &ConfigurablePlan| -> Vec<Decimal> {
+            scenario
+                .project(plan, &data, &period, 3)
+                .years
+                .iter()
+                .map(|year| year.total.signed_amount())
+                .collect()
+        };
+        let variable = totals(&variable);
+        let fixed = totals(&plan(24));
+        assert!(variable[0] < variable[1] && variable[1] < variable[2]);
+        assert_eq!(fixed[0], fixed[1]);
+        // the third year has caught up with two years of rises
+        assert_eq!(fixed[2], variable[2]);
+
+        assert!("rising:unit=-100%".parse::<Scenario>().is_err());
+        assert!("rising:gas=5%".parse::<Scenario>().is_err());
+    }
+}
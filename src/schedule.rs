@@ -0,0 +1,179 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use std::{fmt::Display, str::FromStr};
+
+// When to compare again, the five fields of cron: minute, hour, day of the month, month and
+// day of the week, each a *, a number, a range like 1-5 or a list of them, with /step. Like
+// cron a day matches either of its fields when both are given, "0 6 1 * 1" is the first of
+// the month and every monday
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    text: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+// the values of a field as bits, bit n set when n matches
+fn parse_field(field: &str, name: &str, min: u32, max: u32) -> Result<u64> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .with_context(|| format!("{step} in {part} is not a step like */15"))?,
+            ),
+            None => (part, 1),
+        };
+        let number = |text: &str| {
+            text.parse::<u32>()
+                .ok()
+                .filter(|value| (min..=max).contains(value))
+                .with_context(|| format!("{text} is not a {name} from {min} to {max}"))
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // 5/10 runs from 5 to the end like in cron
+                None if step > 1 => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if start > end {
+            bail!("{range} is a {name} range that runs backwards");
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn matches(bits: u64, value: u32) -> bool {
+    bits & 1 << value != 0
+}
+
+impl CronSchedule {
+    // The first minute of the schedule after the time, none when it never comes round in the
+    // next few years, like on the 30th of february
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = time + Duration::days(5 * 366);
+        while time < limit {
+            let date = time.date();
+            if !matches(self.months, date.month()) {
+                let next = date
+                    .with_day(1)?
+                    .checked_add_months(chrono::Months::new(1))?;
+                time = next.and_time(NaiveTime::MIN);
+            } else if !self.day_matches(date) {
+                time = date.succ_opt()?.and_time(NaiveTime::MIN);
+            } else if !matches(self.hours, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !matches(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = matches(self.days, date.day());
+        let weekday = matches(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<CronSchedule> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!("{text} is not a schedule like \"0 6 * * *\", minute hour day month weekday");
+        };
+        let mut weekdays = parse_field(weekday, "day of the week", 0, 7)?;
+        // sunday is both 0 and 7
+        if matches(weekdays, 7) {
+            weekdays |= 1;
+        }
+        Ok(CronSchedule {
+            text: fields.join(" "),
+            minutes: parse_field(minute, "minute", 0, 59)?,
+            hours: parse_field(hour, "hour", 0, 23)?,
+            days: parse_field(day, "day of the month", 1, 31)?,
+            months: parse_field(month, "month", 1, 12)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+}
+
+impl Display for CronSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%d-%m-%Y %H:%M").unwrap()
+    }
+
+    #[test]
+    fn the_next_time_is_the_first_minute_that_matches() {
+        let daily: CronSchedule = "0 6 * * *".parse().unwrap();
+        assert_eq!(
+            daily.next_after(at("08-01-2024 05:59")),
+            Some(at("08-01-2024 06:00"))
+        );
+        assert_eq!(
+            daily.next_after(at("08-01-2024 06:00")),
+            Some(at("09-01-2024 06:00"))
+        );
+        // every quarter hour on weekdays, a saturday waits for monday
+        let weekdays: CronSchedule = "*/15 8-9 * * 1-5".parse().unwrap();
+        assert_eq!(
+            weekdays.next_after(at("13-01-2024 12:00")),
+            Some(at("15-01-2024 08:00"))
+        );
+        assert_eq!(
+            weekdays.next_after(at("15-01-2024 09:40")),
+            Some(at("15-01-2024 09:45"))
+        );
+        // the first of the month or a sunday, whichever comes first
+        let either: CronSchedule = "30 7 1 * 7".parse().unwrap();
+        assert_eq!(
+            either.next_after(at("29-01-2024 12:00")),
+            Some(at("01-02-2024 07:30"))
+        );
+        assert_eq!(
+            either.next_after(at("02-02-2024 12:00")),
+            Some(at("04-02-2024 07:30"))
+        );
+        let never: CronSchedule = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(never.next_after(at("01-01-2024 00:00")), None);
+        assert_eq!(weekdays.to_string(), "*/15 8-9 * * 1-5");
+        assert!("0 24 * * *".parse::<CronSchedule>().is_err());
+        assert!("0 6 * *".parse::<CronSchedule>().is_err());
+    }
+}
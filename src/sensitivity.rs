@@ -0,0 +1,146 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    comparison::parallel_map, BillingPeriod, EnergyBillEntry, PlanComparison, PricePlanStrategy,
+    SmartMeterData, SmartMeterDataType,
+};
+
+// Where a plan lands when its own rates move and the others stay as they are
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlanSensitivity {
+    pub plan: String,
+    pub rank: usize,
+    // with the unit rates and the standing charge of the plan up by the change, and down by it
+    pub rank_if_higher: usize,
+    pub rank_if_lower: usize,
+    // the part of the total that moves with those rates, the imports and the standing charge
+    pub rate_cost: EnergyBillEntry,
+    // the smallest move of the rates, as a fraction, that ties the plan with the next one, its
+    // own rising or those of the next plan falling. None for the last plan
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub lead_lost_at: Option<Decimal>,
+    // the lead over the next plan goes with a move of the change or less
+    pub fragile: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RateSensitivity {
+    // the move tried, 0.05 for 5% either way
+    #[serde(with = "rust_decimal::serde::float")]
+    pub change: Decimal,
+    // cheapest first like the ranking
+    pub plans: Vec<PlanSensitivity>,
+    // the cheapest plan stays the cheapest whichever single plan moves by the change
+    pub winner_holds: bool,
+}
+
+// 1 for the cheapest, ties share the better rank
+fn rank_of(total: Decimal, others: impl Iterator<Item = Decimal>) -> usize {
+    1 + others.filter(|other| *other < total).count()
+}
+
+// Every plan with its unit rates and standing charge moved by the change either way, one plan
+// at a time, against the results cheapest first. A supplier reprices one plan, not the market,
+// so each plan moves alone. The export rates and the levy stay as they are
+pub fn rate_sensitivity(
+    plans: &[Box<dyn PricePlanStrategy>],
+    datapoints: &[SmartMeterData],
+    period: &BillingPeriod,
+    results: &[PlanComparison],
+    change: Decimal,
+) -> RateSensitivity {
+    let exports = datapoints
+        .iter()
+        .any(|d| d.read_type == SmartMeterDataType::ActiveExport);
+    let imports: Vec<SmartMeterData> = match exports {
+        true => datapoints
+            .iter()
+            .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+            .cloned()
+            .collect(),
+        false => vec![],
+    };
+    // without exports the usage is all imports already
+    let rate_costs = parallel_map(results, |result| {
+        let usage = match plans.iter().find(|plan| plan.name() == result.name) {
+            Some(plan) if exports => plan.compute_bill_breakdown(&imports, period).usage,
+            _ => result.breakdown.usage,
+        };
+        usage.signed_amount() + result.breakdown.standing_charge.signed_amount()
+    });
+    let totals: Vec<Decimal> = results
+        .iter()
+        .map(|result| result.breakdown.total.signed_amount())
+        .collect();
+    let others = |index: usize| {
+        totals
+            .iter()
+            .enumerate()
+            .filter(move |(other, _)| *other != index)
+            .map(|(_, total)| *total)
+    };
+    let sensitivities: Vec<PlanSensitivity> = results
+        .iter()
+        .enumerate()
+        .map(|(index, result)| {
+            let moved = |by: Decimal| totals[index] + rate_costs[index] * by;
+            let lead_lost_at = totals.get(index + 1).and_then(|next| {
+                let moving = rate_costs[index].max(rate_costs[index + 1]);
+                (moving > Decimal::ZERO).then(|| ((next - totals[index]) / moving).round_dp(4))
+            });
+            PlanSensitivity {
+                plan: result.name.clone(),
+                rank: rank_of(totals[index], others(index)),
+                rank_if_higher: rank_of(moved(change), others(index)),
+                rank_if_lower: rank_of(moved(-change), others(index)),
+                rate_cost: EnergyBillEntry::from_signed(rate_costs[index])
+                    .in_currency(result.breakdown.total.currency()),
+                lead_lost_at,
+                fragile: lead_lost_at.is_some_and(|lead| lead <= change),
+            }
+        })
+        .collect();
+    RateSensitivity {
+        change,
+        winner_holds: sensitivities.iter().all(|plan| match plan.rank {
+            1 => plan.rank_if_higher == 1,
+            _ => plan.rank_if_lower > 1,
+        }),
+        plans: sensitivities,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builtin_plans, meter_data::reading_at, rank_plans};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn a_narrow_lead_is_fragile() {
+        let plans = builtin_plans();
+        let data: Vec<SmartMeterData> = (8..15)
+            .flat_map(|day| (0..24).map(move |hour| (day, hour)))
+            .map(|(day, hour)| reading_at(&format!("{day:02}-01-2024 {hour:02}:00"), 1.0))
+            .collect();
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let results = rank_plans(&plans, &data, &period);
+        let wide = rate_sensitivity(&plans, &data, &period, &results, dec!(0.001));
+        let first = &wide.plans[0];
+        assert_eq!(first.rank, 1);
+        assert_eq!(wide.plans.last().unwrap().lead_lost_at, None);
+        let lead = first.lead_lost_at.unwrap();
+        assert!(lead > dec!(0.001));
+        assert!(!first.fragile && first.rank_if_higher == 1);
+
+        // a move past the lead costs the cheapest plan its place
+        let narrow = rate_sensitivity(&plans, &data, &period, &results, lead + dec!(0.01));
+        assert!(narrow.plans[0].fragile);
+        assert!(!narrow.winner_holds);
+        assert!(narrow
+            .plans
+            .iter()
+            .all(|plan| plan.rank_if_lower <= plan.rank));
+    }
+}
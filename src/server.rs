@@ -0,0 +1,663 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{
+    compare_meter_points, filter_by_date, parse_readings, CheapestTracker, ComparisonOptions,
+    ComparisonReport, Currency, CustomerType, DuosGroup, Household, InDuosGroup,
+    MaximumImportCapacity, Metrics, Notifier, PlanSelection, PricePlanStrategy, WithImportCapacity,
+};
+
+// Bigger than years of half hour readings, so an upload can't take all the memory
+const MAX_UPLOAD: usize = 256 * 1024 * 1024;
+// A client that stops sending is dropped after this long instead of keeping its thread
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+// Connections answered at the same time, the others get a 503 straight away
+const MAX_CONNECTIONS: usize = 16;
+// The request line and the headers together, a client sending more gets a 431 instead of the
+// memory
+const MAX_HEADERS: u64 = 64 * 1024;
+
+#[derive(Debug)]
+struct HeadersTooLarge;
+
+impl std::fmt::Display for HeadersTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "the request headers are bigger than {MAX_HEADERS} bytes")
+    }
+}
+
+impl std::error::Error for HeadersTooLarge {}
+
+// What a request that couldn't be read is answered with
+fn read_error(error: anyhow::Error) -> Response {
+    let status = if error.is::<HeadersTooLarge>() {
+        431
+    } else {
+        400
+    };
+    Response::error(status, error.to_string())
+}
+
+// A plan as GET /plans lists it, the name is what current_plan takes
+#[derive(Debug, Serialize)]
+struct PlanSummary {
+    name: String,
+    currency: Currency,
+    prepay: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn query(&self, key: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // a key given more than once, like plan=A&plan=B
+    fn queries<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.query
+            .iter()
+            .filter(move |(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.query(key).is_some()
+    }
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+    skipped_rows: Option<usize>,
+}
+
+impl Response {
+    fn json(value: &impl Serialize) -> Response {
+        match serde_json::to_string_pretty(value) {
+            Ok(body) => Response {
+                status: 200,
+                content_type: "application/json",
+                body,
+                skipped_rows: None,
+            },
+            Err(e) => Response::error(500, e.to_string()),
+        }
+    }
+
+    fn error(status: u16, error: impl Into<String>) -> Response {
+        let body = serde_json::to_string(&ErrorBody {
+            error: error.into(),
+        })
+        .unwrap_or_default();
+        Response {
+            status,
+            content_type: "application/json",
+            body,
+            skipped_rows: None,
+        }
+    }
+
+    // GET /metrics, the text format prometheus scrapes
+    fn metrics() -> Response {
+        Response {
+            status: 200,
+            content_type: "text/plain; version=0.0.4",
+            body: Metrics::global().render(),
+            skipped_rows: None,
+        }
+    }
+}
+
+// %41 and + in the query string of a url
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// A line of the headers, out of what is left of MAX_HEADERS
+fn read_header_line(reader: &mut impl BufRead, line: &mut String, left: &mut u64) -> Result<usize> {
+    if *left == 0 {
+        return Err(HeadersTooLarge.into());
+    }
+    let read = reader.by_ref().take(*left).read_line(line)?;
+    *left -= read as u64;
+    if *left == 0 && !line.ends_with('\n') {
+        return Err(HeadersTooLarge.into());
+    }
+    Ok(read)
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request> {
+    let mut line = String::new();
+    let mut left = MAX_HEADERS;
+    read_header_line(reader, &mut line, &mut left)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        bail!("not an http request");
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(key), decode(value))
+            })
+            .collect(),
+        body: vec![],
+    };
+    let mut length = 0;
+    loop {
+        line.clear();
+        if read_header_line(reader, &mut line, &mut left)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().context("bad content-length")?;
+            }
+        }
+    }
+    if length > MAX_UPLOAD {
+        bail!("the upload is bigger than {MAX_UPLOAD} bytes");
+    }
+    // the body is read as it comes, a content-length alone doesn't get its memory
+    reader.take(length as u64).read_to_end(&mut request.body)?;
+    if request.body.len() < length {
+        bail!("the upload ended before its content-length");
+    }
+    Ok(request)
+}
+
+fn respond(mut stream: TcpStream, response: &Response) -> Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        204 => "No Content",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Bad Request",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    )?;
+    // the frontend can be served from anywhere on the home network
+    write!(
+        stream,
+        "Access-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+             Access-Control-Allow-Headers: Content-Type\r\nConnection: close\r\n"
+    )?;
+    if let Some(skipped) = response.skipped_rows {
+        write!(stream, "X-Skipped-Rows: {skipped}\r\n")?;
+    }
+    write!(stream, "\r\n{}", response.body)?;
+    Ok(())
+}
+
+fn reader_of(stream: &TcpStream) -> Result<BufReader<TcpStream>> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    Ok(BufReader::new(stream.try_clone()?))
+}
+
+// The connections being answered, a slot is given back when its guard is dropped
+#[derive(Default)]
+struct Connections(AtomicUsize);
+
+struct ConnectionGuard(Arc<Connections>);
+
+impl Connections {
+    fn open(connections: &Arc<Connections>) -> Option<ConnectionGuard> {
+        connections
+            .0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| {
+                (open < MAX_CONNECTIONS).then_some(open + 1)
+            })
+            .ok()
+            .map(|_| ConnectionGuard(Arc::clone(connections)))
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0 .0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Answers GET /metrics and nothing else until the program is stopped, for the instances that
+// compare files instead of uploads like --watch
+pub fn serve_metrics(address: &str) -> Result<()> {
+    let listener = TcpListener::bind(address)?;
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let mut reader = reader_of(&stream)?;
+        let response = match read_request(&mut reader) {
+            Ok(request) if request.path == "/metrics" => Response::metrics(),
+            Ok(request) => Response::error(404, format!("no such endpoint {}", request.path)),
+            Err(e) => read_error(e),
+        };
+        if let Err(e) = respond(stream, &response) {
+            eprintln!("request failed: {e}");
+        }
+    }
+    Ok(())
+}
+
+// Compares the plans on readings uploaded over http: GET /plans lists the plans and POST
+// /compare takes the csv or json of the readings as the body and answers with the
+// comparison of each meter point, like --format json does. The query takes the options of
+// compare: the plans are picked for each upload like PlanSelection picks them and priced for
+// its own DUoS group and MIC
+pub struct ComparisonServer {
+    plans: Vec<Arc<dyn PricePlanStrategy>>,
+    // told when an upload makes another plan the cheapest for its meter point
+    notifier: Option<(Notifier, Mutex<CheapestTracker>)>,
+}
+
+impl ComparisonServer {
+    pub fn new(plans: Vec<Box<dyn PricePlanStrategy>>) -> ComparisonServer {
+        ComparisonServer {
+            plans: plans.into_iter().map(Arc::from).collect(),
+            notifier: None,
+        }
+    }
+
+    pub fn with_notifier(self, notifier: Notifier) -> ComparisonServer {
+        ComparisonServer {
+            notifier: Some((notifier, Mutex::new(CheapestTracker::default()))),
+            ..self
+        }
+    }
+
+    // sent on a thread of its own, the answer doesn't wait for curl
+    fn notify(&self, reports: &[ComparisonReport]) {
+        let Some((notifier, cheapest)) = &self.notifier else {
+            return;
+        };
+        let changes = cheapest.lock().unwrap().update(reports);
+        for change in changes {
+            let notifier = notifier.clone();
+            eprintln!("{}", change.subject());
+            std::thread::spawn(move || {
+                if let Err(e) = notifier.send(&change) {
+                    eprintln!("warning: could not send the notification: {e}");
+                }
+            });
+        }
+    }
+
+    // Answers every connection on a thread of its own until the program is stopped
+    pub fn serve(self, address: &str) -> Result<()> {
+        let listener = TcpListener::bind(address)?;
+        let server = Arc::new(self);
+        let connections = Arc::new(Connections::default());
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let Some(guard) = Connections::open(&connections) else {
+                let _ = respond(stream, &Response::error(503, "too many connections"));
+                continue;
+            };
+            let server = Arc::clone(&server);
+            std::thread::spawn(move || {
+                let _guard = guard;
+                if let Err(e) = server.answer(stream) {
+                    eprintln!("request failed: {e}");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn answer(&self, stream: TcpStream) -> Result<()> {
+        let mut reader = reader_of(&stream)?;
+        let response = match read_request(&mut reader) {
+            Ok(request) => self.handle(&request),
+            Err(e) => read_error(e),
+        };
+        respond(stream, &response)
+    }
+
+    fn handle(&self, request: &Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("OPTIONS", _) => Response {
+                status: 204,
+                content_type: "application/json",
+                body: String::new(),
+                skipped_rows: None,
+            },
+            ("GET", "/metrics") => Response::metrics(),
+            ("GET", "/plans") => Response::json(
+                &self
+                    .plans
+                    .iter()
+                    .map(|plan| PlanSummary {
+                        name: plan.name(),
+                        currency: plan.currency(),
+                        prepay: plan.is_prepay(),
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            ("POST", "/compare") => self
+                .compare(request)
+                .unwrap_or_else(|e| Response::error(400, e.to_string())),
+            (_, "/plans" | "/compare" | "/metrics") => {
+                Response::error(405, "use GET /plans, POST /compare or GET /metrics")
+            }
+            _ => Response::error(404, format!("no such endpoint {}", request.path)),
+        }
+    }
+
+    fn compare(&self, request: &Request) -> Result<Response> {
+        let date = |key: &str| -> Result<Option<NaiveDate>> {
+            request
+                .query(key)
+                .map(|text| {
+                    text.parse()
+                        .with_context(|| format!("{key}={text} is not like 2024-01-31"))
+                })
+                .transpose()
+        };
+        let (from, to) = (date("from")?, date("to")?);
+        let group = match request.query("duos_group") {
+            None | Some("urban") => DuosGroup::Urban,
+            Some("rural") => DuosGroup::Rural,
+            Some(other) => bail!("duos_group={other} is not urban or rural"),
+        };
+        let customer_type = match request.query("customer_type") {
+            None | Some("domestic") => CustomerType::Domestic,
+            Some("business") => CustomerType::Business,
+            Some(other) => bail!("customer_type={other} is not domestic or business"),
+        };
+        let mic = request
+            .query("mic_kva")
+            .map(|text| {
+                text.parse()
+                    .map(MaximumImportCapacity)
+                    .with_context(|| format!("mic_kva={text} is not a number of kVA"))
+            })
+            .transpose()?;
+        // a bad row of an upload is skipped, the number skipped goes back in a header
+        let upload = parse_readings(&request.body, "upload")?;
+        Metrics::global().ingested(upload.readings.len(), upload.skipped_rows);
+        let data = filter_by_date(upload.readings, from, to);
+        if data.is_empty() {
+            bail!("no readings to compare in the upload");
+        }
+
+        let current_plan = request.query("current_plan").map(str::to_string);
+        let selection = PlanSelection {
+            currency: upload.currency,
+            customer_type,
+            household: Household {
+                smart_meter: request.flag("no_smart_meter").then_some(false),
+                ev: request.flag("no_ev").then_some(false),
+                gas: request.flag("no_gas").then_some(false),
+                supplier: request.query("current_supplier").map(str::to_string),
+            },
+            prepay: request.flag("prepay"),
+            plans: request.queries("plan").map(str::to_string).collect(),
+            current_plan: current_plan.clone(),
+        };
+        let selected = selection.select(self.plans.clone())?;
+        // the current plan goes by its name from here on, not an alias
+        let current_plan = current_plan.map(|current| {
+            selected
+                .plans
+                .iter()
+                .find(|plan| plan.is_called(&current))
+                .map_or(current, |plan| plan.name())
+        });
+        let plans: Vec<Box<dyn PricePlanStrategy>> = selected
+            .plans
+            .into_iter()
+            .map(|plan| {
+                let plan: Box<dyn PricePlanStrategy> = Box::new(InDuosGroup::new(plan, group));
+                match mic {
+                    Some(mic) => WithImportCapacity::wrap(plan, mic),
+                    None => plan,
+                }
+            })
+            .collect();
+        let options = ComparisonOptions {
+            current_plan,
+            bands: request.query("bands").is_some(),
+            ..ComparisonOptions::default()
+        };
+        let reports = compare_meter_points(&plans, data, &options)?;
+        Metrics::global().compared(&reports);
+        self.notify(&reports);
+        let mut response = Response::json(&reports);
+        response.skipped_rows = Some(upload.skipped_rows);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin_plans;
+
+    #[test]
+    fn an_upload_is_compared_on_the_plans() {
+        let server = ComparisonServer::new(builtin_plans());
+        let request = |method: &str, target: &str, body: &str| {
+            let http = format!(
+                "{method} {target} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            server.handle(&read_request(&mut http.as_bytes()).unwrap())
+        };
+        let plans = request("GET", "/plans", "");
+        assert_eq!(plans.status, 200);
+        assert!(plans.body.contains("\"SSEAirtricity20\""));
+
+        let hdf = "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+10308375697,34996871,0.177,Active Import Interval (kW),01-01-2024 00:30
+10308375697,34996871,oops,Active Import Interval (kW),01-01-2024 01:00
+10308375697,34996871,0.200,Active Import Interval (kW),02-01-2024 00:30
+";
+        let compared = request(
+            "POST",
+            "/compare?current_plan=SSEAirtricity20&to=2024-01-01",
+            hdf,
+        );
+        assert_eq!(compared.status, 200, "{}", compared.body);
+        assert_eq!(compared.skipped_rows, Some(1));
+        let reports: serde_json::Value = serde_json::from_str(&compared.body).unwrap();
+        assert_eq!(reports[0]["period"]["end"], "2024-01-01");
+        assert_eq!(
+            reports[0]["plans"].as_array().unwrap().len(),
+            builtin_plans().len()
+        );
+
+        let metrics = request("GET", "/metrics", "");
+        assert_eq!(metrics.content_type, "text/plain; version=0.0.4");
+        assert!(metrics
+            .body
+            .contains("energy_comparator_cheapest_plan_cost{mprn=\"10308375697\""));
+
+        assert_eq!(request("GET", "/compare", "").status, 405);
+        assert_eq!(request("POST", "/compare?from=soon", hdf).status, 400);
+    }
+
+    #[test]
+    fn each_upload_is_charged_the_standing_charge_of_its_group() {
+        let server = ComparisonServer::new(builtin_plans());
+        let hdf = "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+10308375697,34996871,0.177,Active Import Interval (kW),01-01-2024 00:30
+";
+        let standing_charge = |query: &str| {
+            let http = format!(
+                "POST /compare?{query} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{hdf}",
+                hdf.len()
+            );
+            let response = server.handle(&read_request(&mut http.as_bytes()).unwrap());
+            let reports: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+            reports[0]["plans"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|plan| plan["name"] == "ElectricIrelandHomeElectric14")
+                .unwrap()["breakdown"]["standing_charge"]
+                .clone()
+        };
+        let urban = standing_charge("");
+        assert_ne!(standing_charge("duos_group=rural"), urban);
+        assert_eq!(standing_charge("duos_group=urban"), urban);
+    }
+
+    #[test]
+    fn each_upload_is_compared_on_the_plans_it_can_have() {
+        let mut plans = builtin_plans();
+        plans.extend(
+            toml::from_str::<crate::PlanFile>(
+                r#"
+                [[plan]]
+                name = "Shop"
+                standing_charge = { per_day = 0.9 }
+                metadata = { customer_type = "business" }
+                [[plan.bands]]
+                name = "All day"
+                start = "00:00"
+                end = "00:00"
+                rate = 0.1
+
+                [[plan]]
+                name = "Card"
+                prepay = { service_charge = { per_day = 0.3 } }
+                standing_charge = { per_day = 0.5 }
+                [[plan.bands]]
+                name = "All day"
+                start = "00:00"
+                end = "00:00"
+                rate = 0.1
+                "#,
+            )
+            .unwrap()
+            .take_electricity_plans(),
+        );
+        let server = ComparisonServer::new(plans);
+        let hdf = "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+10308375697,34996871,0.177,Active Import Interval (kW),01-01-2024 00:30
+";
+        let ranked = |query: &str| {
+            let http = format!(
+                "POST /compare?{query} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{hdf}",
+                hdf.len()
+            );
+            let response = server.handle(&read_request(&mut http.as_bytes()).unwrap());
+            assert_eq!(response.status, 200, "{}", response.body);
+            let reports: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+            reports[0]["plans"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|plan| plan["name"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        let family = ranked("");
+        assert!(!family.contains(&"Shop".to_string()));
+        assert_eq!(ranked("customer_type=business"), ["Shop"]);
+        assert_eq!(ranked("prepay"), ["Card"]);
+        let mut named = ranked("plan=card&plan=sseairtricity20");
+        named.sort();
+        assert_eq!(named, ["Card", "SSEAirtricity20"]);
+    }
+
+    #[test]
+    fn an_upload_shorter_than_its_content_length_is_refused() {
+        let http = format!("POST /compare HTTP/1.1\r\nContent-Length: {MAX_UPLOAD}\r\n\r\nMPRN");
+        let error = read_request(&mut http.as_bytes()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the upload ended before its content-length"
+        );
+    }
+
+    #[test]
+    fn headers_without_an_end_are_refused() {
+        let endless = format!(
+            "POST /compare HTTP/1.1\r\nX-Padding: {}",
+            "a".repeat(1 << 20)
+        );
+        let error = read_request(&mut endless.as_bytes()).unwrap_err();
+        assert_eq!(read_error(error).status, 431);
+        let many = format!(
+            "GET /plans HTTP/1.1\r\n{}\r\n",
+            "X-Padding: a\r\n".repeat(10_000)
+        );
+        let error = read_request(&mut many.as_bytes()).unwrap_err();
+        assert_eq!(read_error(error).status, 431);
+        let fine = "GET /plans HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert_eq!(read_request(&mut fine.as_bytes()).unwrap().path, "/plans");
+    }
+
+    #[test]
+    fn connections_over_the_limit_are_turned_away() {
+        let connections = Arc::new(Connections::default());
+        let mut open: Vec<ConnectionGuard> = (0..MAX_CONNECTIONS)
+            .map(|_| Connections::open(&connections).unwrap())
+            .collect();
+        assert!(Connections::open(&connections).is_none());
+        open.pop();
+        assert!(Connections::open(&connections).is_some());
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::sha256_hex;
+
+// A file the comparison read and the SHA-256 of what was in it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSnapshot {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+impl FileSnapshot {
+    pub fn of(path: &Path) -> Result<FileSnapshot> {
+        let contents =
+            std::fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+        Ok(FileSnapshot {
+            path: path.to_path_buf(),
+            sha256: sha256_hex(&contents),
+        })
+    }
+
+    // The files of a directory, like the reading store, in the order of their paths
+    pub fn of_directory(directory: &Path) -> Result<Vec<FileSnapshot>> {
+        let mut paths = vec![];
+        let mut directories = vec![directory.to_path_buf()];
+        while let Some(directory) = directories.pop() {
+            for entry in std::fs::read_dir(&directory)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    directories.push(path);
+                } else {
+                    paths.push(path);
+                }
+            }
+        }
+        paths.sort();
+        paths.iter().map(|path| FileSnapshot::of(path)).collect()
+    }
+
+    // What is different about the file now, none when it is the same
+    pub fn change(&self) -> Option<String> {
+        match std::fs::read(&self.path) {
+            Err(_) => Some(format!("{} is gone", self.path.display())),
+            Ok(contents) if sha256_hex(&contents) != self.sha256 => {
+                Some(format!("{} changed", self.path.display()))
+            }
+            Ok(_) => None,
+        }
+    }
+}
+
+// Everything a comparison was made from, so it can be made again months later with rerun and
+// a difference tracked down to the input that changed. Paths are as given, relative to
+// directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputSnapshot {
+    pub tool_version: String,
+    // the arguments of the run, without the program
+    pub args: Vec<String>,
+    pub directory: PathBuf,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    // the plan registry downloaded with plans update, none when it wasn't used
+    pub registry: Option<FileSnapshot>,
+    // the readings and every other file the plans and the options came from
+    pub files: Vec<FileSnapshot>,
+}
+
+impl InputSnapshot {
+    // What is different now from when the snapshot was taken, taken in directory
+    pub fn changes(&self) -> Vec<String> {
+        let mut changes = vec![];
+        let version = env!("CARGO_PKG_VERSION");
+        if self.tool_version != version {
+            changes.push(format!(
+                "the result was made with version {} and this is {version}",
+                self.tool_version
+            ));
+        }
+        changes.extend(
+            self.registry
+                .iter()
+                .chain(&self.files)
+                .filter_map(FileSnapshot::change),
+        );
+        changes
+    }
+}
+
+#[derive(Deserialize)]
+struct WithSnapshot {
+    snapshot: Option<InputSnapshot>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SnapshotFile {
+    One(WithSnapshot),
+    Several(Vec<WithSnapshot>),
+}
+
+// The snapshot of a json comparison, every meter point of a run shares it
+pub fn load_snapshot(filename: &Path) -> Result<InputSnapshot> {
+    let contents = std::fs::read_to_string(filename)?;
+    let saved: SnapshotFile = serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not a json comparison", filename.display()))?;
+    let snapshot = match saved {
+        SnapshotFile::One(report) => report.snapshot,
+        SnapshotFile::Several(reports) => reports.into_iter().find_map(|r| r.snapshot),
+    };
+    match snapshot {
+        Some(snapshot) => Ok(snapshot),
+        None => bail!(
+            "{} has no snapshot of its inputs, only comparisons of this version printed with --format json do",
+            filename.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_changed_input_is_reported() {
+        let directory = std::env::temp_dir().join(format!("snapshot-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("2024")).unwrap();
+        let readings = directory.join("2024").join("HDF.csv");
+        std::fs::write(&readings, "a").unwrap();
+        std::fs::write(directory.join("plans.toml"), "b").unwrap();
+        let files = FileSnapshot::of_directory(&directory).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, readings);
+        let snapshot = InputSnapshot {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            args: vec!["HDF.csv".to_string()],
+            directory: directory.clone(),
+            from: None,
+            to: None,
+            registry: None,
+            files,
+        };
+        assert!(snapshot.changes().is_empty());
+        std::fs::write(&readings, "c").unwrap();
+        assert_eq!(
+            snapshot.changes(),
+            vec![format!("{} changed", readings.display())]
+        );
+
+        let saved = directory.join("result.json");
+        let json = serde_json::json!([{ "mprn": "1" }, { "mprn": "2", "snapshot": snapshot }]);
+        std::fs::write(&saved, json.to_string()).unwrap();
+        assert_eq!(load_snapshot(&saved).unwrap(), snapshot);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::{bail, Result};
+use chrono::{Datelike, Duration, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+use crate::{
+    meter_data::{smart_meter_datetime_desserialize, smart_meter_datetime_serialize},
+    read_csv, ReadUnit, SmartMeterData, SmartMeterDataType,
+};
+
+// Roughly the middle of Ireland, close enough for the sun of any Irish home
+const LATITUDE: f64 = 53.4;
+const LONGITUDE: f64 = -7.9;
+
+// The losses of the inverter, wiring and heat, and the share of the clear sky sun the Irish
+// clouds let through. Together they make a south facing array at 35° give about 900 kWh a
+// year per kWp, the usual figure for Ireland
+const PERFORMANCE_RATIO: f64 = 0.85;
+const SKY_FACTOR: f64 = 0.53;
+
+// A point of a generation file lasts until the next one, and at most this long
+const MAX_GENERATION_LENGTH_MINUTES: i64 = 60;
+
+// Panels on the roof, modelled from the position of the sun in a clear sky
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SolarArray {
+    pub kwp: f32,
+    // degrees from flat
+    pub tilt: f32,
+    // compass degrees the panels face, 180 is south
+    pub azimuth: f32,
+}
+
+impl SolarArray {
+    // The energy made in the interval starting at start, from the sun at its middle
+    pub fn generation_kwh(&self, start: NaiveDateTime, minutes: i64) -> f32 {
+        let middle = start + Duration::minutes(minutes / 2);
+        let day = middle.ordinal() as f64;
+        let declination = (23.45 * (360.0 / 365.0 * (284.0 + day)).to_radians().sin()).to_radians();
+        let hours = middle.hour() as f64 + middle.minute() as f64 / 60.0;
+        let hour_angle = (15.0 * (hours + LONGITUDE / 15.0 - 12.0)).to_radians();
+        let latitude = LATITUDE.to_radians();
+        let sin_elevation = latitude.sin() * declination.sin()
+            + latitude.cos() * declination.cos() * hour_angle.cos();
+        if sin_elevation <= 0.0 {
+            return 0.0;
+        }
+        let elevation = sin_elevation.asin();
+        // degrees from south, west positive, like the hour angle
+        let sun_azimuth = hour_angle
+            .sin()
+            .atan2(hour_angle.cos() * latitude.sin() - declination.tan() * latitude.cos());
+        let (tilt, panel_azimuth) = (
+            (self.tilt as f64).to_radians(),
+            (self.azimuth as f64 - 180.0).to_radians(),
+        );
+        let incidence = elevation.sin() * tilt.cos()
+            + elevation.cos() * tilt.sin() * (sun_azimuth - panel_azimuth).cos();
+        // the direct sun through the air mass of its height and a little diffuse light
+        let direct = 1353.0 * 0.7_f64.powf((1.0 / sin_elevation).powf(0.678));
+        let diffuse = 0.1 * direct * (1.0 + tilt.cos()) / 2.0;
+        let watts_per_m2 = direct * incidence.max(0.0) + diffuse;
+        let kw = self.kwp as f64 * watts_per_m2 / 1000.0 * PERFORMANCE_RATIO * SKY_FACTOR;
+        (kw * minutes as f64 / 60.0) as f32
+    }
+}
+
+// One row of a generation file, the start of the interval in UTC like the HDF readings and
+// the kWh made in it, e.g. 08-06-2024 12:00,1.21
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GenerationPoint {
+    #[serde(
+        deserialize_with = "smart_meter_datetime_desserialize",
+        serialize_with = "smart_meter_datetime_serialize"
+    )]
+    pub start: NaiveDateTime,
+    pub kwh: f32,
+}
+
+// The generation of an existing array or an inverter export, by the start of its interval
+#[derive(Debug, Clone, Default)]
+pub struct GenerationSeries {
+    points: Arc<BTreeMap<NaiveDateTime, f32>>,
+}
+
+impl GenerationSeries {
+    pub fn new(points: Vec<GenerationPoint>) -> GenerationSeries {
+        GenerationSeries {
+            points: Arc::new(points.into_iter().map(|p| (p.start, p.kwh)).collect()),
+        }
+    }
+
+    pub fn load(filename: &Path) -> Result<GenerationSeries> {
+        let series = GenerationSeries::new(read_csv(filename)?);
+        if series.points.is_empty() {
+            bail!("no generation in {}", filename.display());
+        }
+        Ok(series)
+    }
+
+    // The share of every point that overlaps the interval, a point lasting until the next one,
+    // so the file can be hourly for half hour readings or the other way around
+    pub fn generation_kwh(&self, start: NaiveDateTime, minutes: i64) -> f32 {
+        let end = start + Duration::minutes(minutes);
+        let first = self
+            .points
+            .range(..=start)
+            .next_back()
+            .map_or(start, |(&from, _)| from);
+        let points: Vec<(NaiveDateTime, f32)> = self
+            .points
+            .range(first..)
+            .map(|(&from, &kwh)| (from, kwh))
+            .take_while(|&(from, _)| from < end)
+            .collect();
+        let mut kwh = 0.0;
+        for (index, &(from, point_kwh)) in points.iter().enumerate() {
+            let next = match points.get(index + 1) {
+                Some(&(next, _)) => next,
+                None => self.points.range(end..).next().map_or(
+                    from + Duration::minutes(MAX_GENERATION_LENGTH_MINUTES),
+                    |(&next, _)| next,
+                ),
+            };
+            let length = (next - from)
+                .num_minutes()
+                .clamp(1, MAX_GENERATION_LENGTH_MINUTES);
+            let until = from + Duration::minutes(length);
+            let overlap = (until.min(end) - from.max(start)).num_minutes();
+            if overlap > 0 {
+                kwh += point_kwh * overlap as f32 / length as f32;
+            }
+        }
+        kwh
+    }
+}
+
+// Where the solar generation comes from
+#[derive(Debug, Clone)]
+pub enum Solar {
+    Modelled(SolarArray),
+    Measured(GenerationSeries),
+}
+
+// What the panels made over the readings and where it went
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SolarSummary {
+    pub generation_kwh: f32,
+    // used in the house instead of imported
+    pub self_consumed_kwh: f32,
+    pub exported_kwh: f32,
+}
+
+impl SolarSummary {
+    pub fn self_consumption(&self) -> f32 {
+        if self.generation_kwh > 0.0 {
+            self.self_consumed_kwh / self.generation_kwh * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+fn set_energy(datapoint: &mut SmartMeterData, kwh: f32) {
+    datapoint.read_value = match datapoint.unit {
+        ReadUnit::Kilowatts => kwh * 60.0 / datapoint.interval_minutes as f32,
+        ReadUnit::KilowattHours => kwh,
+    };
+}
+
+impl Solar {
+    fn generation_kwh(&self, start: NaiveDateTime, minutes: i64) -> f32 {
+        match self {
+            Solar::Modelled(array) => array.generation_kwh(start, minutes),
+            Solar::Measured(series) => series.generation_kwh(start, minutes),
+        }
+    }
+
+    // The readings with the panels on the house: the generation of each import interval
+    // is used in the house first and what's left is exported, added to the export reading
+    // of the interval or to a new one
+    pub fn apply(&self, datapoints: &[SmartMeterData]) -> (Vec<SmartMeterData>, SolarSummary) {
+        let mut readings = datapoints.to_vec();
+        let exports: BTreeMap<(&str, NaiveDateTime), usize> = datapoints
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.read_type == SmartMeterDataType::ActiveExport)
+            .map(|(index, d)| ((d.mprn.as_ref(), d.read_data_and_end_time), index))
+            .collect();
+        let mut summary = SolarSummary {
+            generation_kwh: 0.0,
+            self_consumed_kwh: 0.0,
+            exported_kwh: 0.0,
+        };
+        let mut new_exports = vec![];
+        for (index, datapoint) in datapoints.iter().enumerate() {
+            if datapoint.read_type != SmartMeterDataType::ActiveImport {
+                continue;
+            }
+            let minutes = datapoint.interval_minutes as i64;
+            let start = datapoint.read_data_and_end_time - Duration::minutes(minutes);
+            let generated = self.generation_kwh(start, minutes);
+            if generated <= 0.0 {
+                continue;
+            }
+            let import = datapoint.energy_kwh();
+            let used = generated.min(import);
+            let surplus = generated - used;
+            set_energy(&mut readings[index], import - used);
+            summary.generation_kwh += generated;
+            summary.self_consumed_kwh += used;
+            summary.exported_kwh += surplus;
+            if surplus <= 0.0 {
+                continue;
+            }
+            match exports.get(&(datapoint.mprn.as_ref(), datapoint.read_data_and_end_time)) {
+                Some(&export) => {
+                    let exported = readings[export].energy_kwh();
+                    set_energy(&mut readings[export], exported + surplus);
+                }
+                None => {
+                    let mut export = datapoint.clone();
+                    export.read_type = SmartMeterDataType::ActiveExport;
+                    set_energy(&mut export, surplus);
+                    new_exports.push(export);
+                }
+            }
+        }
+        readings.extend(new_exports);
+        readings.sort_by_key(|d| d.read_data_and_end_time);
+        (readings, summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn a_south_array_makes_an_irish_year() {
+        let array = SolarArray {
+            kwp: 1.0,
+            tilt: 35.0,
+            azimuth: 180.0,
+        };
+        let start = NaiveDateTime::parse_from_str("01-01-2024 00:00", "%d-%m-%Y %H:%M").unwrap();
+        let year: f32 = (0..366 * 48)
+            .map(|half_hour| array.generation_kwh(start + Duration::minutes(30 * half_hour), 30))
+            .sum();
+        assert!((850.0..950.0).contains(&year), "{year}");
+        // nothing at night, more at midday in June than in December
+        let at = |text| NaiveDateTime::parse_from_str(text, "%d-%m-%Y %H:%M").unwrap();
+        assert_eq!(array.generation_kwh(at("21-06-2024 01:00"), 30), 0.0);
+        assert!(
+            array.generation_kwh(at("21-06-2024 12:30"), 30)
+                > array.generation_kwh(at("21-12-2024 12:30"), 30)
+        );
+    }
+
+    #[test]
+    fn generation_offsets_imports_and_exports_the_rest() {
+        let series = GenerationSeries::new(vec![GenerationPoint {
+            start: NaiveDateTime::parse_from_str("08-06-2024 11:00", "%d-%m-%Y %H:%M").unwrap(),
+            kwh: 2.0,
+        }]);
+        // half of the hourly point in each half hour, the house uses 0.5 kWh of each
+        let data = vec![
+            reading_at("08-06-2024 11:30", 1.0),
+            reading_at("08-06-2024 12:00", 1.0),
+        ];
+        let (readings, summary) = Solar::Measured(series).apply(&data);
+        assert_eq!(summary.generation_kwh, 2.0);
+        assert_eq!(summary.self_consumed_kwh, 1.0);
+        assert_eq!(summary.self_consumption(), 50.0);
+        let exports: Vec<f32> = readings
+            .iter()
+            .filter(|d| d.read_type == SmartMeterDataType::ActiveExport)
+            .map(SmartMeterData::energy_kwh)
+            .collect();
+        assert_eq!(exports, vec![0.5, 0.5]);
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::{bail, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use crate::{
+    comparison::{ensure_one_currency, mark_current_plan, sort_by_total},
+    detect_source,
+    meter_data::most_common_gap,
+    stream_csv, BillingPeriod, ComparisonReport, EnergyBillEntry, EsbHdf, MeterDataSource, Netting,
+    ParseMode, PlanComparison, PricePlanStrategy, RowChecker, SmartMeterData, SmartMeterDataType,
+};
+
+// The usage of every plan on one meter point, added up as the readings go past
+struct MeterTotals {
+    start: NaiveDate,
+    end: NaiveDate,
+    usage: Vec<Decimal>,
+}
+
+// The interval of every meter and direction in the files, the most common gap between the
+// readings that follow each other in the file. Unlike detect_intervals a series that changes
+// interval isn't summed up, it's all taken to be at the most common one
+fn stream_intervals(paths: &[PathBuf]) -> Result<HashMap<(Arc<str>, SmartMeterDataType), u32>> {
+    let mut previous: HashMap<(Arc<str>, SmartMeterDataType), NaiveDateTime> = HashMap::new();
+    let mut gaps: HashMap<(Arc<str>, SmartMeterDataType), HashMap<i64, usize>> = HashMap::new();
+    for path in paths {
+        // bad rows are reported on the second pass
+        for datapoint in stream_csv::<SmartMeterData>(path)?.flatten() {
+            let key = (datapoint.mprn, datapoint.read_type);
+            let time = datapoint.read_data_and_end_time;
+            if let Some(before) = previous.insert(key.clone(), time) {
+                // the export is newest first, so the gap is taken either way round
+                let minutes = (time - before).num_minutes().abs();
+                if minutes > 0 {
+                    *gaps.entry(key).or_default().entry(minutes).or_default() += 1;
+                }
+            }
+        }
+    }
+    Ok(gaps
+        .into_iter()
+        .filter_map(|(key, gaps)| Some((key, most_common_gap(gaps)?)))
+        .collect())
+}
+
+// Compares the plans without keeping the readings in memory, each file is read once to find
+// the intervals and once more pricing every reading on all the plans at the same time. Only
+// plans whose usage is the sum of their readings can be priced one reading at a time, the
+// others are left out with a warning, and so are the plans netting the exports, which need
+// the other readings of the interval or day. Readings are not merged, so downloads that overlap
+// are counted twice
+pub fn stream_comparison(
+    paths: &[PathBuf],
+    plans: &[Box<dyn PricePlanStrategy>],
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    current_plan: Option<&str>,
+) -> Result<Vec<ComparisonReport>> {
+    let (plans, skipped): (Vec<_>, Vec<_>) = plans.iter().partition(|plan| {
+        plan.usage_adds_up_by_month() && plan.export_rules().netting == Netting::None
+    });
+    ensure_one_currency(&plans.iter().map(|plan| plan.as_ref()).collect::<Vec<_>>())?;
+    for plan in skipped {
+        eprintln!(
+            "warning: {} can't be priced one reading at a time and was left out",
+            plan.name()
+        );
+    }
+
+    for path in paths {
+        let source = detect_source(path)?;
+        if source.name() != EsbHdf.name() {
+            bail!(
+                "{} is a {} csv, only HDF files can be streamed",
+                path.display(),
+                source.name()
+            );
+        }
+    }
+    let intervals = stream_intervals(paths)?;
+    let mut meters: BTreeMap<Arc<str>, MeterTotals> = BTreeMap::new();
+    for path in paths {
+        let mut rows = RowChecker::new(path.display().to_string(), ParseMode::global());
+        for row in stream_csv::<SmartMeterData>(path)? {
+            let Some(mut datapoint) = rows.check(row)? else {
+                continue;
+            };
+            let date = datapoint.date();
+            if from.is_some_and(|from| date < from) || to.is_some_and(|to| date > to) {
+                continue;
+            }
+            if let Some(&minutes) = intervals.get(&(datapoint.mprn.clone(), datapoint.read_type)) {
+                datapoint.interval_minutes = minutes;
+            }
+            let totals = meters
+                .entry(datapoint.mprn.clone())
+                .or_insert_with(|| MeterTotals {
+                    start: date,
+                    end: date,
+                    usage: vec![Decimal::ZERO; plans.len()],
+                });
+            totals.start = totals.start.min(date);
+            totals.end = totals.end.max(date);
+            for (plan, usage) in plans.iter().zip(totals.usage.iter_mut()) {
+                // a reading at a time, plans rounding per day are rounded per reading here
+                let prices = plan.price_readings(std::slice::from_ref(&datapoint));
+                *usage += prices[0].signed_amount();
+            }
+        }
+        rows.finish();
+    }
+
+    let mut reports = vec![];
+    for (mprn, totals) in meters {
+        let period = BillingPeriod {
+            start: totals.start,
+            end: totals.end,
+        };
+        let results = plans
+            .iter()
+            .zip(totals.usage)
+            .map(|(plan, usage)| {
+                let breakdown =
+                    plan.bill_breakdown_for_usage(EnergyBillEntry::from_signed(usage), &period);
+                let mut result = PlanComparison::new(plan.name(), breakdown);
+                result.metadata = Some(plan.metadata()).filter(|m| !m.is_empty());
+                let bonus = plan.sign_up_bonus();
+                if bonus.amount() > Decimal::ZERO {
+                    result.with_bonus = Some(breakdown.total + EnergyBillEntry::Credit(bonus));
+                }
+                result
+            })
+            .collect();
+        let mut plans = sort_by_total(results);
+        if let Some(current) = current_plan {
+            mark_current_plan(&mut plans, current)?;
+        }
+        reports.push(ComparisonReport {
+            mprn: mprn.to_string(),
+            period,
+            plans,
+            gas_plans: vec![],
+            dual_fuel: vec![],
+            estimated_annual_bills: vec![],
+            carbon: None,
+            load_shift: None,
+            battery: None,
+            solar: None,
+            ev_charging: None,
+            sub_metering: None,
+            baseload: None,
+            peak_demand: None,
+            contract: None,
+            usage_growth: None,
+            scenarios: vec![],
+            monthly_winners: None,
+            rate_sensitivity: None,
+            estimated: vec![],
+            coverage: None,
+            snapshot: None,
+        });
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compare_meter_points, sse_airtricity_20, ComparisonOptions, PlanFile};
+
+    #[test]
+    fn streamed_totals_are_the_loaded_ones_without_the_netted_plans() {
+        let csv = "MPRN,Meter Serial Number,Read Value,Read Type,Read Date and End Time
+10308375697,34996871,2.0,Active Import Interval (kW),08-01-2024 03:30
+10308375697,34996871,2.0,Active Import Interval (kW),08-01-2024 04:00
+10308375697,34996871,1.0,Active Import Interval (kW),08-01-2024 04:30
+10308375697,34996871,2.0,Active Export Interval (kW),08-01-2024 13:00
+10308375697,34996871,1.0,Active Export Interval (kW),08-01-2024 13:30
+10308375697,34996871,1.0,Active Export Interval (kW),08-01-2024 14:00
+10308375697,34996871,1.0,Active Import Interval (kW),09-01-2024 18:00
+10308375697,34996871,4.0,Active Export Interval (kW),09-01-2024 12:00
+";
+        let path = std::env::temp_dir().join(format!("streamed-{}.csv", std::process::id()));
+        std::fs::write(&path, csv).unwrap();
+        let mut file: PlanFile = toml::from_str(
+            r#"
+            [[plan]]
+            name = "netted"
+            standing_charge = { per_day = 0.5 }
+            export_rate = 0.2
+            export = { netting = "day" }
+
+            [[plan.bands]]
+            name = "all day"
+            start = "00:00"
+            end = "00:00"
+            rate = 0.30
+            "#,
+        )
+        .unwrap();
+        let mut plans = file.take_electricity_plans();
+        plans.push(Box::new(sse_airtricity_20()));
+        let streamed = stream_comparison(std::slice::from_ref(&path), &plans, None, None, None);
+        std::fs::remove_file(&path).unwrap();
+        let streamed = streamed.unwrap();
+        let loaded = compare_meter_points(
+            &plans,
+            crate::parse_smart_meter_csv(csv.as_bytes()).unwrap(),
+            &ComparisonOptions::default(),
+        )
+        .unwrap();
+        let names: Vec<&str> = streamed[0].plans.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["SSEAirtricity20"]);
+        let sse = loaded[0]
+            .plans
+            .iter()
+            .find(|plan| plan.name == "SSEAirtricity20")
+            .unwrap();
+        assert_eq!(streamed[0].period, loaded[0].period);
+        assert_eq!(streamed[0].plans[0].breakdown.total, sse.breakdown.total);
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{bail, Result};
+use chrono::{Duration, NaiveDateTime};
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    meter_data::smart_meter_datetime_desserialize, read_csv, EnergyBillEntry, PricePlanStrategy,
+    SmartMeterData, SmartMeterDataType,
+};
+
+// One row of a circuit csv, the end of the interval in UTC like the HDF readings and the kWh
+// the circuit used in it, e.g. 08-01-2024 00:30,0.12
+#[derive(Debug, Clone, Deserialize)]
+struct CircuitReading {
+    #[serde(deserialize_with = "smart_meter_datetime_desserialize")]
+    end: NaiveDateTime,
+    kwh: f32,
+}
+
+// A circuit of the house behind the main meter, like the heating or the car charger, read by a
+// CT clamp monitor. Its readings can be of the intervals of the meter or shorter ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circuit {
+    pub name: String,
+    readings: BTreeMap<NaiveDateTime, f32>,
+}
+
+impl Circuit {
+    pub fn new(name: &str, readings: impl IntoIterator<Item = (NaiveDateTime, f32)>) -> Circuit {
+        Circuit {
+            name: name.to_string(),
+            readings: readings.into_iter().collect(),
+        }
+    }
+
+    pub fn load(name: &str, filename: &Path) -> Result<Circuit> {
+        let readings: Vec<CircuitReading> = read_csv(filename)?;
+        if readings.is_empty() {
+            bail!(
+                "no readings of the circuit {name} in {}",
+                filename.display()
+            );
+        }
+        Ok(Circuit::new(
+            name,
+            readings
+                .into_iter()
+                .map(|reading| (reading.end, reading.kwh)),
+        ))
+    }
+
+    // the kWh of the readings ending inside the interval of the meter reading
+    fn kwh_in(&self, datapoint: &SmartMeterData) -> (f32, usize) {
+        let end = datapoint.read_data_and_end_time;
+        let start = end - Duration::minutes(datapoint.interval_minutes as i64);
+        self.readings
+            .range(start + Duration::seconds(1)..=end)
+            .fold((0.0, 0), |(kwh, count), (_, value)| {
+                (kwh + value, count + 1)
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CircuitUsage {
+    pub name: String,
+    // what the monitor read, and what was left of it once no interval used more than the meter
+    pub metered_kwh: f32,
+    pub kwh: f32,
+}
+
+// The imports of the main meter split between the circuits and the rest of the house. The
+// circuits of an interval that add up to more than the meter read are scaled down to it, the
+// clamps read a little high or the clocks of the monitor drift
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SubMetering {
+    pub circuits: Vec<CircuitUsage>,
+    pub main_kwh: f32,
+    pub rest_kwh: f32,
+    pub intervals_over_main: usize,
+    // readings of the circuits in no interval of the main meter
+    pub unmatched_readings: usize,
+    // the energy of each circuit in each reading, lined up with the readings
+    #[serde(skip)]
+    pub circuit_kwh: Vec<Vec<f32>>,
+}
+
+pub fn attribute_circuits(circuits: &[Circuit], datapoints: &[SmartMeterData]) -> SubMetering {
+    let mut circuit_kwh = vec![vec![0.0; datapoints.len()]; circuits.len()];
+    let mut matched = vec![0; circuits.len()];
+    let mut intervals_over_main = 0;
+    let mut main_kwh = 0.0;
+    for (index, datapoint) in datapoints.iter().enumerate() {
+        if datapoint.read_type != SmartMeterDataType::ActiveImport {
+            continue;
+        }
+        let kwh = datapoint.energy_kwh();
+        main_kwh += kwh;
+        let mut total = 0.0;
+        for (number, circuit) in circuits.iter().enumerate() {
+            let (circuit_total, count) = circuit.kwh_in(datapoint);
+            circuit_kwh[number][index] = circuit_total.max(0.0);
+            matched[number] += count;
+            total += circuit_kwh[number][index];
+        }
+        if total > kwh + 0.001 {
+            intervals_over_main += 1;
+            let scale = kwh / total;
+            for circuit in &mut circuit_kwh {
+                circuit[index] *= scale;
+            }
+        }
+    }
+    let usage: Vec<CircuitUsage> = circuits
+        .iter()
+        .zip(&circuit_kwh)
+        .map(|(circuit, kwh)| CircuitUsage {
+            name: circuit.name.clone(),
+            metered_kwh: circuit.readings.values().sum(),
+            kwh: kwh.iter().sum(),
+        })
+        .collect();
+    SubMetering {
+        rest_kwh: main_kwh - usage.iter().map(|circuit| circuit.kwh).sum::<f32>(),
+        main_kwh,
+        intervals_over_main,
+        unmatched_readings: circuits
+            .iter()
+            .zip(matched)
+            .map(|(circuit, matched)| circuit.readings.len() - matched)
+            .sum(),
+        circuits: usage,
+        circuit_kwh,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CircuitCost {
+    pub name: String,
+    pub cost: EnergyBillEntry,
+}
+
+// What each circuit costs on a plan, the rest of the house pays the fixed charges like with
+// the car of EvCost
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CircuitCosts {
+    pub circuits: Vec<CircuitCost>,
+    pub rest_of_house: EnergyBillEntry,
+}
+
+impl SubMetering {
+    // Whether the circuits account for the main meter, no interval over it and every reading
+    // of them in one
+    pub fn reconciles(&self) -> bool {
+        self.intervals_over_main == 0 && self.unmatched_readings == 0
+    }
+
+    // The share of the price of each reading each circuit used, so tiers and time of use
+    // bands are charged to the circuits as they fell
+    pub fn cost(
+        &self,
+        plan: &dyn PricePlanStrategy,
+        datapoints: &[SmartMeterData],
+        total: EnergyBillEntry,
+    ) -> CircuitCosts {
+        let prices = plan.price_readings(datapoints);
+        let circuits: Vec<CircuitCost> = self
+            .circuits
+            .iter()
+            .zip(&self.circuit_kwh)
+            .map(|(circuit, circuit_kwh)| {
+                let mut cost = Decimal::ZERO;
+                for ((datapoint, price), &kwh) in datapoints.iter().zip(&prices).zip(circuit_kwh) {
+                    let main = datapoint.energy_kwh();
+                    if kwh <= 0.0 || main <= 0.0 {
+                        continue;
+                    }
+                    let share = Decimal::from_f32(kwh / main).unwrap_or_default();
+                    cost += price.signed_amount() * share;
+                }
+                CircuitCost {
+                    name: circuit.name.clone(),
+                    cost: EnergyBillEntry::from_signed(cost)
+                        .in_currency(plan.currency())
+                        .round(&plan.rounding()),
+                }
+            })
+            .collect();
+        CircuitCosts {
+            rest_of_house: circuits
+                .iter()
+                .fold(total, |rest, circuit| rest - circuit.cost),
+            circuits,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, sse_airtricity_20, BillingPeriod};
+
+    #[test]
+    fn the_circuits_pay_their_share_of_each_interval() {
+        // 1 kWh in each half hour, the clamp reads quarter hours
+        let data = vec![
+            reading_at("08-01-2024 03:30", 2.0),
+            reading_at("08-01-2024 18:00", 2.0),
+        ];
+        let at = |datetime| reading_at(datetime, 0.0).read_data_and_end_time;
+        let heating = Circuit::new(
+            "heating",
+            [
+                (at("08-01-2024 03:15"), 0.25),
+                (at("08-01-2024 03:30"), 0.25),
+                (at("08-01-2024 18:00"), 0.5),
+                (at("09-01-2024 00:30"), 0.5),
+            ],
+        );
+        // more than the meter at 18:00, scaled down with the heating
+        let ev = Circuit::new("ev", [(at("08-01-2024 18:00"), 1.5)]);
+        let metering = attribute_circuits(&[heating, ev], &data);
+        assert_eq!(metering.main_kwh, 2.0);
+        assert_eq!(metering.circuits[0].metered_kwh, 1.5);
+        assert_eq!(metering.circuits[0].kwh, 0.75);
+        assert_eq!(metering.circuits[1].kwh, 0.75);
+        assert!((metering.rest_kwh - 0.5).abs() < 1e-6);
+        assert_eq!(metering.intervals_over_main, 1);
+        assert_eq!(metering.unmatched_readings, 1);
+        assert!(!metering.reconciles());
+
+        let plan = sse_airtricity_20();
+        let period = BillingPeriod::from_data(&data).unwrap();
+        let breakdown = plan.compute_bill_breakdown(&data, &period);
+        let costs = metering.cost(&plan, &data, breakdown.total);
+        let circuits: EnergyBillEntry = costs.circuits.iter().map(|c| c.cost).sum();
+        assert_eq!(circuits + costs.rest_of_house, breakdown.total);
+        // the car is on the dearer evening half hour alone
+        assert!(costs.circuits[1].cost > costs.circuits[0].cost);
+    }
+}
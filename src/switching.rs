@@ -0,0 +1,118 @@
+use chrono::{Duration, NaiveDate};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::Serialize;
+
+use crate::{BillBreakdown, Money};
+
+// The contract of the current plan, leaving it before its end costs the exit fee. The switch
+// would happen on switch_on, today unless another date is given
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Contract {
+    pub end: NaiveDate,
+    pub exit_fee: Decimal,
+    pub switch_on: NaiveDate,
+}
+
+// Whether to switch to a plan now or once the contract of the current plan ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SwitchAdvice {
+    // what the plan saves a day over the current one, from a year of each estimated from the
+    // readings, see annual_estimate
+    pub daily_saving: Money,
+    // the day the savings have paid back the exit fee when switching on switch_on, None when
+    // the plan doesn't save anything
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub break_even: Option<NaiveDate>,
+    // what switching now saves by the end of the contract once the fee is paid, against
+    // switching at the end, negative when waiting is cheaper
+    pub switch_now_gain: Money,
+    pub switch_now: bool,
+}
+
+impl Contract {
+    // The fee is only charged before the end of the contract
+    pub fn fee_on(&self, date: NaiveDate) -> Decimal {
+        if date < self.end {
+            self.exit_fee
+        } else {
+            Decimal::ZERO
+        }
+    }
+
+    pub fn advice(&self, current: &BillBreakdown, plan: &BillBreakdown) -> SwitchAdvice {
+        let currency = current.total.currency();
+        let daily_saving = ((current.total.signed_amount() - plan.total.signed_amount())
+            / Decimal::from(365))
+        .round_dp(4);
+        let fee = self.fee_on(self.switch_on);
+        let remaining = (self.end - self.switch_on).num_days().max(0);
+        let break_even = (daily_saving > Decimal::ZERO).then(|| {
+            let days = (fee / daily_saving).ceil().to_i64().unwrap_or(i64::MAX / 2);
+            self.switch_on + Duration::days(days.min(365 * 100))
+        });
+        let switch_now_gain = (daily_saving * Decimal::from(remaining) - fee).round_dp(2);
+        SwitchAdvice {
+            daily_saving: Money::of(daily_saving, currency),
+            break_even,
+            switch_now_gain: Money::of(switch_now_gain, currency),
+            switch_now: daily_saving > Decimal::ZERO && switch_now_gain >= Decimal::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EnergyBillEntry;
+    use rust_decimal_macros::dec;
+
+    fn year_costing(total: Decimal) -> BillBreakdown {
+        let entry = EnergyBillEntry::Debit(Money::new(total));
+        BillBreakdown {
+            usage: entry,
+            import_cost: entry,
+            export_credit: EnergyBillEntry::ZERO,
+            standing_charge: EnergyBillEntry::ZERO,
+            pso_levy: EnergyBillEntry::ZERO,
+            credits: EnergyBillEntry::ZERO,
+            bill_credit: EnergyBillEntry::ZERO,
+            demand_charge: EnergyBillEntry::ZERO,
+            total: entry,
+        }
+    }
+
+    #[test]
+    fn the_fee_is_worth_paying_once_the_savings_beat_it() {
+        let date = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let contract = Contract {
+            end: date(31).checked_add_signed(Duration::days(90)).unwrap(),
+            exit_fee: dec!(50),
+            switch_on: date(1),
+        };
+        let current = year_costing(dec!(1825));
+        // a euro a day cheaper pays back the fee in 50 days, before the contract ends
+        let advice = contract.advice(&current, &year_costing(dec!(1460)));
+        assert_eq!(advice.daily_saving.amount(), dec!(1));
+        assert_eq!(advice.break_even, Some(date(1) + Duration::days(50)));
+        assert_eq!(advice.switch_now_gain.amount(), dec!(70));
+        assert!(advice.switch_now);
+
+        // 10 cent a day takes longer than what is left of the contract
+        let advice = contract.advice(&current, &year_costing(dec!(1788.50)));
+        assert_eq!(advice.break_even, Some(date(1) + Duration::days(500)));
+        assert!(!advice.switch_now);
+
+        // after the end there is no fee to pay back
+        let ended = Contract {
+            switch_on: contract.end,
+            ..contract
+        };
+        let advice = ended.advice(&current, &year_costing(dec!(1788.50)));
+        assert_eq!(advice.break_even, Some(contract.end));
+        assert!(advice.switch_now);
+        assert!(ended
+            .advice(&current, &year_costing(dec!(2000)))
+            .break_even
+            .is_none());
+    }
+}
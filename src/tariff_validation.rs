@@ -0,0 +1,499 @@
+use chrono::{Duration, NaiveDate, NaiveTime, Timelike, Weekday};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::fmt::Display;
+
+use crate::{
+    time_in_window, ConfigurablePlan, HolidayRule, RateBand, Season, StandingChargeDefinition,
+};
+
+// Anything above these is almost certainly a typo, like cent instead of euro
+const MAX_UNIT_RATE: Decimal = dec!(2);
+const MAX_STANDING_CHARGE_PER_DAY: Decimal = dec!(5);
+// VAT is a fraction too, 9 instead of 0.09 is the usual mistake
+const MAX_VAT_RATE: Decimal = dec!(0.25);
+
+const WEEK: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+// Something in a tariff file that would price readings in a way nobody meant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TariffIssue {
+    // no band covers the readings ending after start up to and including end
+    Gap {
+        day: Weekday,
+        start: NaiveTime,
+        end: NaiveTime,
+    },
+    // more than one band covers them, only the first one is ever used
+    Overlap {
+        day: Weekday,
+        start: NaiveTime,
+        end: NaiveTime,
+        bands: Vec<String>,
+    },
+    // readings end on the hour and half hour, so a boundary in between splits an interval
+    OffBoundary {
+        band: String,
+        time: NaiveTime,
+    },
+    OutOfRange {
+        field: String,
+        value: Decimal,
+    },
+    // prepay plans are never discounted, so the discount is ignored
+    PrepayDiscount {
+        discount: String,
+    },
+    // an issue with the rates of a later version of the plan
+    InVersion {
+        from: NaiveDate,
+        issue: Box<TariffIssue>,
+    },
+    // a gap or an overlap only on bank holidays, with bands that leave them out or only
+    // apply on them
+    OnHolidays {
+        issue: Box<TariffIssue>,
+    },
+}
+
+impl Display for TariffIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = |t: &NaiveTime| t.format("%H:%M");
+        match self {
+            TariffIssue::Gap { day, start, end } => write!(
+                f,
+                "no band covers {day} from {} to {}",
+                time(start),
+                time(end)
+            ),
+            TariffIssue::Overlap {
+                day,
+                start,
+                end,
+                bands,
+            } => write!(
+                f,
+                "bands {} all cover {day} from {} to {}, only the first is used",
+                bands.join(", "),
+                time(start),
+                time(end)
+            ),
+            TariffIssue::OffBoundary { band, time: t } => write!(
+                f,
+                "band {band} starts or ends at {}, which is not on the half hour",
+                time(t)
+            ),
+            TariffIssue::OutOfRange { field, value } => {
+                write!(f, "{field} of {value} is out of the expected range")
+            }
+            TariffIssue::PrepayDiscount { discount } => write!(
+                f,
+                "prepay plans get no discount, the discount of {discount} is ignored"
+            ),
+            TariffIssue::InVersion { from, issue } => write!(f, "from {from}: {issue}"),
+            TariffIssue::OnHolidays { issue } => write!(f, "on bank holidays: {issue}"),
+        }
+    }
+}
+
+// Checks every minute of every weekday against the bands, as well as the rates themselves
+pub fn validate_plan(plan: &ConfigurablePlan) -> Vec<TariffIssue> {
+    let mut issues = band_issues(&plan.bands, plan.holidays_like);
+    // the boost is meant to sit on top of the bands, so it isn't part of the coverage
+    if let Some(boost) = &plan.boost {
+        check_band(&mut issues, boost);
+    }
+    check_range(
+        &mut issues,
+        "discount".to_string(),
+        plan.discount.unit_rate,
+        Decimal::ONE,
+    );
+    check_range(
+        &mut issues,
+        "standing charge discount".to_string(),
+        plan.discount.standing_charge,
+        Decimal::ONE,
+    );
+    if let Some(credit) = plan.discount.bill_credit {
+        check_standing_charge(&mut issues, credit);
+    }
+    check_range(
+        &mut issues,
+        "export rate".to_string(),
+        plan.export_rate,
+        MAX_UNIT_RATE,
+    );
+    // exports outside the export bands get the export rate, so they needn't cover the day
+    for band in &plan.export_bands {
+        check_band(&mut issues, band);
+    }
+    check_range(
+        &mut issues,
+        "vat included".to_string(),
+        plan.vat_included,
+        MAX_VAT_RATE,
+    );
+    check_standing_charge(&mut issues, plan.standing_charge);
+    if plan.prepay.is_some() && !plan.discount.is_none() {
+        issues.push(TariffIssue::PrepayDiscount {
+            discount: plan.discount.summary(),
+        });
+    }
+
+    for version in &plan.versions {
+        let mut version_issues = band_issues(&version.bands, plan.holidays_like);
+        if let Some(standing_charge) = version.standing_charge {
+            check_standing_charge(&mut version_issues, standing_charge);
+        }
+        if let Some(export_rate) = version.export_rate {
+            check_range(
+                &mut version_issues,
+                "export rate".to_string(),
+                export_rate,
+                MAX_UNIT_RATE,
+            );
+        }
+        for band in version.export_bands.iter().flatten() {
+            check_band(&mut version_issues, band);
+        }
+        issues.extend(
+            version_issues
+                .into_iter()
+                .map(|issue| TariffIssue::InVersion {
+                    from: version.from,
+                    issue: Box::new(issue),
+                }),
+        );
+    }
+    issues
+}
+
+// The boundaries, rates and coverage of one set of bands. Bank holidays are checked too when a
+// band treats them apart, as holidays_like or as every day of the week without it
+fn band_issues(bands: &[RateBand], holidays_like: Option<Weekday>) -> Vec<TariffIssue> {
+    let mut issues = vec![];
+    for band in bands {
+        check_band(&mut issues, band);
+    }
+    // each season is checked with the bands of the whole year, which it takes over from
+    // where it has its own, and the whole year alone if some days are in no season
+    let mut seasons: Vec<Option<Season>> = vec![];
+    for season in bands.iter().filter_map(|band| band.season) {
+        if !seasons.contains(&Some(season)) {
+            seasons.push(Some(season));
+        }
+    }
+    let leap_year = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    if leap_year
+        .iter_days()
+        .take(366)
+        .any(|date| !seasons.iter().flatten().any(|season| season.contains(date)))
+    {
+        seasons.insert(0, None);
+    }
+    let holidays_apart = bands
+        .iter()
+        .any(|band| band.days.holidays != HolidayRule::Include);
+    let holidays: Vec<Weekday> = match (holidays_apart, holidays_like) {
+        (false, _) => vec![],
+        (true, Some(day)) => vec![day],
+        (true, None) => WEEK.to_vec(),
+    };
+    for season in seasons {
+        for day in WEEK {
+            for issue in coverage_issues(bands, day, season, false) {
+                if !issues.contains(&issue) {
+                    issues.push(issue);
+                }
+            }
+        }
+        for &day in &holidays {
+            for issue in coverage_issues(bands, day, season, true) {
+                let issue = TariffIssue::OnHolidays {
+                    issue: Box::new(issue),
+                };
+                if !issues.contains(&issue) {
+                    issues.push(issue);
+                }
+            }
+        }
+    }
+    issues
+}
+
+fn check_band(issues: &mut Vec<TariffIssue>, band: &RateBand) {
+    for time in [band.start, band.end] {
+        if time.minute() % 30 != 0 || time.second() != 0 {
+            issues.push(TariffIssue::OffBoundary {
+                band: band.name.clone(),
+                time,
+            });
+        }
+    }
+    check_range(
+        issues,
+        format!("rate of band {}", band.name),
+        band.rate,
+        MAX_UNIT_RATE,
+    );
+}
+
+fn check_standing_charge(issues: &mut Vec<TariffIssue>, standing_charge: StandingChargeDefinition) {
+    let per_day = match standing_charge {
+        StandingChargeDefinition::PerDay(value) => value,
+        StandingChargeDefinition::Annual(value) => value / dec!(365),
+    };
+    check_range(
+        issues,
+        "standing charge per day".to_string(),
+        per_day,
+        MAX_STANDING_CHARGE_PER_DAY,
+    );
+}
+
+fn check_range(issues: &mut Vec<TariffIssue>, field: String, value: Decimal, max: Decimal) {
+    if value < Decimal::ZERO || value > max {
+        issues.push(TariffIssue::OutOfRange { field, value });
+    }
+}
+
+// Walks the end of every minute long interval of the day and reports each run of minutes
+// that is covered by no band or by several
+fn coverage_issues(
+    bands: &[RateBand],
+    day: Weekday,
+    season: Option<Season>,
+    holiday: bool,
+) -> Vec<TariffIssue> {
+    let covering = |minute: i64| -> Vec<usize> {
+        let time = NaiveTime::MIN + Duration::minutes(minute);
+        let covering: Vec<usize> = bands
+            .iter()
+            .enumerate()
+            .filter(|(_, band)| band.days.applies(day, holiday))
+            .filter(|(_, band)| band.season.is_none() || band.season == season)
+            .filter(|(_, band)| time_in_window(time, band.start, band.end))
+            .map(|(index, _)| index)
+            .collect();
+        if covering.iter().any(|&index| bands[index].season.is_some()) {
+            covering
+                .into_iter()
+                .filter(|&index| bands[index].season.is_some())
+                .collect()
+        } else {
+            covering
+        }
+    };
+
+    let coverage: Vec<Vec<usize>> = (1..=24 * 60).map(covering).collect();
+    let mut issues = vec![];
+    let mut first = 0;
+    while first < coverage.len() {
+        let mut last = first;
+        while last + 1 < coverage.len() && coverage[last + 1] == coverage[first] {
+            last += 1;
+        }
+        // the run holds the intervals ending at minute first + 1 up to last + 1
+        let start = NaiveTime::MIN + Duration::minutes(first as i64);
+        let end = NaiveTime::MIN + Duration::minutes(last as i64 + 1);
+        match coverage[first].len() {
+            0 => issues.push(TariffIssue::Gap { day, start, end }),
+            1 => {}
+            _ => issues.push(TariffIssue::Overlap {
+                day,
+                start,
+                end,
+                bands: coverage[first]
+                    .iter()
+                    .map(|&i| bands[i].name.clone())
+                    .collect(),
+            }),
+        }
+        first = last + 1;
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PlanFile;
+
+    fn plan(toml: &str) -> ConfigurablePlan {
+        let file: PlanFile = toml::from_str(toml).unwrap();
+        file.plan.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn gaps_overlaps_and_rates_are_reported() {
+        let plan = plan(
+            r#"
+            [[plan]]
+            name = "broken"
+            discount = 1.5
+            standing_charge = { per_day = 0.5 }
+
+            [[plan.bands]]
+            name = "day"
+            start = "08:00"
+            end = "17:00"
+            rate = 0.40
+
+            [[plan.bands]]
+            name = "peak"
+            days = ["Mon"]
+            start = "16:00"
+            end = "19:00"
+            rate = 0.50
+
+            [[plan.bands]]
+            name = "night"
+            start = "19:00"
+            end = "08:00"
+            rate = 0.20
+            "#,
+        );
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let issues = validate_plan(&plan);
+
+        assert!(issues.contains(&TariffIssue::Overlap {
+            day: Weekday::Mon,
+            start: at(16, 0),
+            end: at(17, 0),
+            bands: vec!["day".to_string(), "peak".to_string()],
+        }));
+        // the other days have nothing from 17:00 to 19:00
+        assert!(issues.contains(&TariffIssue::Gap {
+            day: Weekday::Tue,
+            start: at(17, 0),
+            end: at(19, 0),
+        }));
+        assert!(!issues
+            .iter()
+            .any(|issue| matches!(issue, TariffIssue::Gap { day, .. } if *day == Weekday::Mon)));
+        assert!(issues.contains(&TariffIssue::OutOfRange {
+            field: "discount".to_string(),
+            value: dec!(1.5),
+        }));
+    }
+
+    #[test]
+    fn each_season_is_checked_over_the_rest_of_the_year() {
+        let plan = plan(
+            r#"
+            [[plan]]
+            name = "seasonal"
+            standing_charge = { per_day = 0.5 }
+
+            [[plan.bands]]
+            name = "all day"
+            start = "00:00"
+            end = "00:00"
+            rate = 0.30
+
+            [[plan.bands]]
+            name = "winter peak"
+            season = { from = "11-01", to = "02-29" }
+            start = "17:00"
+            end = "19:00"
+            rate = 0.50
+
+            [[plan.bands]]
+            name = "summer nights"
+            season = { from = "06-01", to = "08-31" }
+            start = "23:00"
+            end = "08:00"
+            rate = 0.10
+
+            [[plan.bands]]
+            name = "summer days"
+            season = { from = "06-01", to = "08-31" }
+            start = "08:00"
+            end = "23:30"
+            rate = 0.25
+            "#,
+        );
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        // the seasons take over from the year round band where they have bands, only the two
+        // summer bands overlap
+        let issues = validate_plan(&plan);
+        assert_eq!(issues.len(), 7, "{issues:?}");
+        assert_eq!(
+            issues[0],
+            TariffIssue::Overlap {
+                day: Weekday::Mon,
+                start: at(23, 0),
+                end: at(23, 30),
+                bands: vec!["summer nights".to_string(), "summer days".to_string()],
+            }
+        );
+        assert!(toml::from_str::<Season>(
+            r#"from = "02-30"
+to = "03-01""#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn bands_that_leave_out_holidays_have_them_checked_apart() {
+        let toml = |holidays_like: &str| {
+            format!(
+                r#"
+                [[plan]]
+                name = "no holidays"
+                standing_charge = {{ per_day = 0.5 }}
+                {holidays_like}
+
+                [[plan.bands]]
+                name = "day"
+                days = "weekdays except holidays"
+                start = "08:00"
+                end = "23:00"
+                rate = 0.40
+
+                [[plan.bands]]
+                name = "weekend"
+                days = "weekends"
+                start = "08:00"
+                end = "23:00"
+                rate = 0.30
+
+                [[plan.bands]]
+                name = "night"
+                start = "23:00"
+                end = "08:00"
+                rate = 0.20
+                "#
+            )
+        };
+        let issues = validate_plan(&plan(&toml("")));
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        // a holiday on a weekday has no day band, the ordinary days are covered
+        assert_eq!(issues.len(), 5, "{issues:?}");
+        assert_eq!(
+            issues[0],
+            TariffIssue::OnHolidays {
+                issue: Box::new(TariffIssue::Gap {
+                    day: Weekday::Mon,
+                    start: at(8, 0),
+                    end: at(23, 0),
+                }),
+            }
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            "on bank holidays: no band covers Mon from 08:00 to 23:00"
+        );
+        // priced like a sunday they have the weekend band
+        assert!(validate_plan(&plan(&toml(r#"holidays_like = "Sun""#))).is_empty());
+    }
+}
@@ -0,0 +1,897 @@
+use energy_comparator::{
+    invoices_total, meter_segments, BatterySaving, BillBreakdown, ComparisonReport, DayCost,
+    DayUsage, EffectiveRate, EnergyBillEntry, EvCost, ExportEarnings, GapFilling, GapReport,
+    LevelPay, Locale, MeterSegment, Money, PeakCost, PlanComparison, ResultsDiff,
+    ScenarioProjection, ShiftedCost, SmartMeterData, SubMetering, SwitchAdvice, UsageStats,
+};
+use rust_decimal::Decimal;
+
+fn print_breakdown(breakdown: &BillBreakdown, decimals: usize) {
+    let BillBreakdown {
+        usage,
+        import_cost,
+        export_credit,
+        standing_charge,
+        pso_levy,
+        credits,
+        bill_credit,
+        demand_charge,
+        total,
+    } = breakdown;
+    let locale = Locale::global();
+    let line = |text: &str, amount: &EnergyBillEntry| {
+        print!(
+            "{}",
+            locale.fill(text, &[&locale.number(format!("{amount:.decimals$}"))])
+        );
+    };
+    if export_credit.is_zero() {
+        line("usage {}, ", usage);
+    } else {
+        line("imports {}, ", import_cost);
+        line("exports {}, ", export_credit);
+    }
+    line("standing charge {}, ", standing_charge);
+    if !pso_levy.signed_amount().is_zero() {
+        line("PSO levy {}, ", pso_levy);
+    }
+    if !credits.signed_amount().is_zero() {
+        line("credits {}, ", credits);
+    }
+    if !bill_credit.signed_amount().is_zero() {
+        line("bill credit {}, ", bill_credit);
+    }
+    if !demand_charge.is_zero() {
+        line("demand charge {}, ", demand_charge);
+    }
+    line("total {}", total);
+}
+
+fn print_ranking(results: &[PlanComparison], decimals: usize) {
+    let locale = Locale::global();
+    for (position, result) in results.iter().enumerate() {
+        print!("{:>2}. {}: ", position + 1, result.name);
+        print_breakdown(&result.breakdown, decimals);
+        if let Some(with_bonus) = &result.with_bonus {
+            let with_bonus = locale.number(format!("{with_bonus:.decimals$}"));
+            print!(
+                "{}",
+                locale.fill(", first bill with sign up bonus {}", &[&with_bonus])
+            );
+        }
+        if result.current {
+            print!("{}", locale.text(" (current plan)"));
+        }
+        if let Some(saving) = &result.saving {
+            let amount = locale.number(format!("{:.decimals$}", saving.amount));
+            let percentage = locale.number(saving.percentage.to_string());
+            print!(
+                "{}",
+                locale.fill(", saves {} ({}%)", &[&amount, &percentage])
+            );
+        }
+        println!();
+    }
+}
+
+// The kWh of each circuit against the main meter and what they cost on each plan, in the order
+// of the ranking
+fn print_sub_metering(report: &ComparisonReport, metering: &SubMetering, decimals: usize) {
+    println!("Circuits of the {:.1} kWh imported:", metering.main_kwh);
+    for circuit in &metering.circuits {
+        if (circuit.metered_kwh - circuit.kwh).abs() < 0.05 {
+            println!("  {}: {:.1} kWh", circuit.name, circuit.kwh);
+        } else {
+            println!(
+                "  {}: {:.1} kWh of the {:.1} kWh read",
+                circuit.name, circuit.kwh, circuit.metered_kwh
+            );
+        }
+    }
+    println!("  rest of the house: {:.1} kWh", metering.rest_kwh);
+    if metering.intervals_over_main > 0 {
+        println!(
+            "  the circuits read more than the meter in {} intervals and were scaled down to it",
+            metering.intervals_over_main
+        );
+    }
+    if metering.unmatched_readings > 0 {
+        println!(
+            "  {} readings of the circuits have no reading of the meter and were left out",
+            metering.unmatched_readings
+        );
+    }
+    for (position, plan) in report.plans.iter().enumerate() {
+        let Some(costs) = &plan.circuit_costs else {
+            continue;
+        };
+        let circuits: Vec<String> = costs
+            .circuits
+            .iter()
+            .map(|circuit| format!("{} {:.decimals$}", circuit.name, circuit.cost))
+            .collect();
+        println!(
+            "{:>2}. {}: {}, rest of the house {:.decimals$}",
+            position + 1,
+            plan.name,
+            circuits.join(", "),
+            costs.rest_of_house
+        );
+    }
+}
+
+// The invoices of a plan and how far they are from the period priced at once
+fn print_invoices(result: &PlanComparison, decimals: usize) {
+    println!("{} invoices:", result.name);
+    for invoice in &result.invoices {
+        print!(
+            "  {} to {}{}: {:.1} kWh, ",
+            invoice.period.start,
+            invoice.period.end,
+            if invoice.partial { " (part)" } else { "" },
+            invoice.import_kwh
+        );
+        print_breakdown(&invoice.breakdown, decimals);
+        println!();
+    }
+    let total = invoices_total(&result.invoices);
+    let difference = total - result.breakdown.total;
+    if difference.signed_amount().is_zero() {
+        println!("  together {total:.decimals$}, the same as the whole period");
+    } else {
+        println!(
+            "  together {total:.decimals$}, {:.decimals$} {} than the whole period",
+            EnergyBillEntry::from_signed(difference.signed_amount().abs()),
+            if difference.signed_amount() > Decimal::ZERO {
+                "more"
+            } else {
+                "less"
+            }
+        );
+    }
+}
+
+// The plans priced again on changed readings, cheapest first
+fn print_changed_ranking(
+    results: &[PlanComparison],
+    cost: impl Fn(&PlanComparison) -> Option<&ShiftedCost>,
+    decimals: usize,
+) {
+    let mut changed: Vec<(&str, &ShiftedCost)> = results
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), cost(r)?)))
+        .collect();
+    changed.sort_by_key(|(_, changed)| changed.breakdown.total);
+    for (position, (name, changed)) in changed.iter().enumerate() {
+        println!(
+            "{:>2}. {name}: {:.decimals$}, saves {:.decimals$} ({}%) on the same plan",
+            position + 1,
+            changed.breakdown.total,
+            changed.saving.amount,
+            changed.saving.percentage
+        );
+    }
+}
+
+// A day with what it cost and each of its bands, like 09-01-2024: €3.12 for 14.2 kWh (day
+// €1.40 for 7.1 kWh, peak €1.72 for 7.1 kWh)
+fn print_expensive_day(day: &DayCost, decimals: usize) {
+    let bands: Vec<String> = day
+        .bands
+        .iter()
+        .map(|b| format!("{} {:.decimals$} for {:.1} kWh", b.band, b.cost, b.kwh))
+        .collect();
+    println!(
+        "  {}: {:.decimals$} for {:.1} kWh ({})",
+        day.date.format("%d-%m-%Y"),
+        day.cost,
+        day.kwh,
+        bands.join(", ")
+    );
+}
+
+fn projected_total(result: &PlanComparison) -> EnergyBillEntry {
+    result.projection.iter().map(|year| year.total).sum()
+}
+
+fn first_bill_total(result: &PlanComparison) -> EnergyBillEntry {
+    result.with_bonus.unwrap_or(result.breakdown.total)
+}
+
+pub(crate) fn print_text(report: &ComparisonReport, decimals: usize, plan_details: bool) {
+    let locale = Locale::global();
+    let period = &report.period;
+    println!("{}", locale.fill("Meter point: {}", &[&report.mprn]));
+    println!(
+        "{}",
+        locale.fill(
+            "Billing period: {} to {} ({} days)",
+            &[
+                &locale.date(period.start),
+                &locale.date(period.end),
+                &period.number_of_days()
+            ]
+        )
+    );
+    for estimated in &report.estimated {
+        println!(
+            "{}",
+            locale.fill(
+                "Estimated readings: {} to {}, {} kWh spread like the standard profile",
+                &[
+                    &locale.date(estimated.from),
+                    &locale.date(estimated.to),
+                    &locale.number(format!("{:.1}", estimated.kwh))
+                ]
+            )
+        );
+    }
+    if let Some(coverage) = report.coverage.filter(|c| !c.is_complete()) {
+        let percent =
+            |intervals| locale.number(format!("{:.1}", coverage.share(intervals) * 100.0));
+        println!(
+            "{}",
+            locale.fill(
+                "Coverage: {}% actual readings, {}% filled in, {}% estimated, {}% missing",
+                &[
+                    &percent(coverage.actual_intervals),
+                    &percent(coverage.filled_intervals),
+                    &percent(coverage.estimated_intervals),
+                    &percent(coverage.missing_intervals)
+                ]
+            )
+        );
+    }
+    let annualized = report.plans.iter().any(|r| r.annual_estimate.is_some());
+    if annualized {
+        println!("{}", locale.text("Actual cost of the readings:"));
+    }
+    print_ranking(&report.plans, decimals);
+    if plan_details {
+        println!("{}", locale.text("Plan details:"));
+        for result in report.plans.iter() {
+            let source = result
+                .source
+                .map_or(String::new(), |source| format!(" (from {source})"));
+            match &result.metadata {
+                Some(metadata) => println!("  {}: {}{source}", result.name, metadata.summary()),
+                None => println!("  {}: nothing known{source}", result.name),
+            }
+        }
+    }
+    if annualized {
+        let mut estimates: Vec<(&str, &BillBreakdown)> = report
+            .plans
+            .iter()
+            .filter_map(|r| Some((r.name.as_str(), r.annual_estimate.as_ref()?)))
+            .collect();
+        estimates.sort_by_key(|(_, estimate)| estimate.total);
+        println!(
+            "{}",
+            locale.fill(
+                "Projected cost of a year from {} days of readings, cheapest first:",
+                &[&period.number_of_days()]
+            )
+        );
+        for (position, (name, estimate)) in estimates.iter().enumerate() {
+            print!("{:>2}. {name}: ", position + 1);
+            print_breakdown(estimate, decimals);
+            println!();
+        }
+    }
+    print_changes(report, decimals);
+    print_choices(report, decimals);
+    print_exports_and_rates(report, decimals);
+    print_over_time(report, decimals);
+}
+
+// What the plans cost with a change to the home, and the parts of the usage priced apart
+fn print_changes(report: &ComparisonReport, decimals: usize) {
+    if let Some(shift) = &report.load_shift {
+        println!(
+            "With {} kWh a day moved from {} to {}, cheapest first:",
+            shift.kwh_per_day, shift.from, shift.to
+        );
+        print_changed_ranking(&report.plans, |r| r.load_shifted.as_ref(), decimals);
+    }
+    let mut with_battery: Vec<(&str, &BatterySaving)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.with_battery.as_ref()?)))
+        .collect();
+    if let Some(battery) = &report.battery {
+        with_battery.sort_by_key(|(_, with)| with.breakdown.total);
+        println!(
+            "With a {} kWh battery charging {}, cheapest first:",
+            battery.capacity_kwh, battery.charge
+        );
+        for (position, (name, with)) in with_battery.iter().enumerate() {
+            print!(
+                "{:>2}. {name}: {:.decimals$}, saves {:.decimals$} ({}%) on the same plan",
+                position + 1,
+                with.breakdown.total,
+                with.saving.amount,
+                with.saving.percentage
+            );
+            match (battery.cost, with.payback_years) {
+                (Some(_), Some(years)) => print!(", pays for itself in {years} years"),
+                (Some(_), None) => print!(", never pays for itself"),
+                _ => {}
+            }
+            println!();
+        }
+    }
+    if let Some(solar) = &report.solar {
+        println!(
+            "With solar, {:.1} kWh made, {:.1} kWh used in the house ({:.0}%) and {:.1} kWh exported, cheapest first:",
+            solar.generation_kwh,
+            solar.self_consumed_kwh,
+            solar.self_consumption(),
+            solar.exported_kwh
+        );
+        print_changed_ranking(&report.plans, |r| r.with_solar.as_ref(), decimals);
+    }
+    if let Some(growth) = &report.usage_growth {
+        println!(
+            "With the usage grown from {:.1} kWh to {:.1} kWh, cheapest first:",
+            growth.imported_kwh, growth.grown_kwh
+        );
+        print_changed_ranking(&report.plans, |r| r.with_growth.as_ref(), decimals);
+    }
+    if let Some(charging) = &report.ev_charging {
+        println!(
+            "EV charging: {} sessions, {:.1} kWh, cheapest for the car first:",
+            charging.sessions.len(),
+            charging.kwh
+        );
+        let mut ev_costs: Vec<(&str, &EvCost)> = report
+            .plans
+            .iter()
+            .filter_map(|r| Some((r.name.as_str(), r.ev_cost.as_ref()?)))
+            .collect();
+        ev_costs.sort_by_key(|(_, cost)| cost.ev);
+        for (position, (name, cost)) in ev_costs.iter().enumerate() {
+            println!(
+                "{:>2}. {name}: EV {:.decimals$} ({:.4}/kWh), rest of the house {:.decimals$}",
+                position + 1,
+                cost.ev,
+                cost.ev_per_kwh(),
+                cost.house
+            );
+        }
+    }
+    if let Some(metering) = &report.sub_metering {
+        print_sub_metering(report, metering, decimals);
+    }
+    if let Some(baseload) = &report.baseload {
+        println!(
+            "Baseload: {:.0} W all day, {:.0} kWh a year{}, a year of it costs:",
+            baseload.kw * 1000.0,
+            baseload.annual_kwh,
+            if baseload.high {
+                ", higher than most homes, look for appliances left on standby"
+            } else {
+                ""
+            }
+        );
+        let mut costs: Vec<(&str, EnergyBillEntry)> = report
+            .plans
+            .iter()
+            .filter_map(|r| Some((r.name.as_str(), r.baseload_cost?)))
+            .collect();
+        costs.sort_by_key(|(_, cost)| *cost);
+        for (name, cost) in costs {
+            println!("  {name}: {cost:.decimals$}");
+        }
+    }
+    if let Some(demand) = &report.peak_demand {
+        println!(
+            "Maximum demand: {:.2} kW, the highest intervals:",
+            demand.max_kw()
+        );
+        for peak in &demand.top {
+            println!(
+                "  {}: {:.2} kW ({:.2} kWh)",
+                peak.end.format("%d-%m-%Y %H:%M"),
+                peak.kw,
+                peak.kwh
+            );
+        }
+        let mut costs: Vec<(&str, &PeakCost)> = report
+            .plans
+            .iter()
+            .filter_map(|r| Some((r.name.as_str(), r.peak_cost.as_ref()?)))
+            .collect();
+        costs.sort_by_key(|(_, cost)| cost.share);
+        println!("Share of the imports cost in the peak, lowest first:");
+        for (name, cost) in costs {
+            println!("  {name}: {:.decimals$} ({}%)", cost.cost, cost.share);
+        }
+    }
+}
+
+// The choices within a plan and between plans that only come up on some of them
+fn print_choices(report: &ComparisonReport, decimals: usize) {
+    let period = &report.period;
+    for result in report
+        .plans
+        .iter()
+        .filter(|r| !r.free_time_choices.is_empty())
+    {
+        println!("Free time choices of {}, cheapest first:", result.name);
+        for choice in &result.free_time_choices {
+            println!(
+                "  {}: {:.decimals$}{}",
+                choice.window,
+                choice.breakdown.total,
+                if choice.chosen { " (chosen)" } else { "" }
+            );
+        }
+    }
+    for result in report.plans.iter().filter(|r| !r.expensive_days.is_empty()) {
+        println!("Most expensive days on {}:", result.name);
+        for day in &result.expensive_days {
+            print_expensive_day(day, decimals);
+        }
+    }
+    if let Some(contract) = &report.contract {
+        println!(
+            "Switching on {} with the contract ending on {} and an exit fee of {:.decimals$}:",
+            contract.switch_on,
+            contract.end,
+            Money::new(contract.fee_on(contract.switch_on))
+        );
+        let mut advice: Vec<(&str, &SwitchAdvice)> = report
+            .plans
+            .iter()
+            .filter_map(|r| Some((r.name.as_str(), r.switch_advice.as_ref()?)))
+            .collect();
+        advice.sort_by_key(|(_, advice)| std::cmp::Reverse(advice.switch_now_gain));
+        for (name, advice) in advice {
+            match advice.break_even {
+                None => println!("  {name}: costs more than the current plan, stay"),
+                Some(date) if advice.switch_now => println!(
+                    "  {name}: saves {:.decimals$} a day, the fee is paid back by {date}, switch now for {:.decimals$} more by the contract end",
+                    advice.daily_saving, advice.switch_now_gain
+                ),
+                Some(date) => println!(
+                    "  {name}: saves {:.decimals$} a day, the fee is only paid back by {date}, switch at the contract end, now costs {:.decimals$} more",
+                    advice.daily_saving,
+                    Money::of(-advice.switch_now_gain.amount(), advice.switch_now_gain.currency())
+                ),
+            }
+        }
+    }
+    // the ranking above is the steady state, a bonus only helps the first bill
+    if report.plans.iter().any(|r| r.with_bonus.is_some()) {
+        let mut first_bill: Vec<&PlanComparison> = report.plans.iter().collect();
+        first_bill.sort_by_key(|r| first_bill_total(r));
+        println!("First bill with sign up bonuses, cheapest first:");
+        for (position, result) in first_bill.iter().enumerate() {
+            println!(
+                "{:>2}. {}: {:.decimals$}",
+                position + 1,
+                result.name,
+                first_bill_total(result)
+            );
+        }
+    }
+    if !report.gas_plans.is_empty() {
+        println!("Gas plans:");
+        print_ranking(&report.gas_plans, decimals);
+    }
+    if !report.dual_fuel.is_empty() {
+        println!("Dual fuel, cheapest first:");
+        for (position, bundle) in report.dual_fuel.iter().enumerate() {
+            println!(
+                "{:>2}. {} ({} and {}): electricity {:.decimals$}, gas {:.decimals$}, total {:.decimals$}",
+                position + 1,
+                bundle.name,
+                bundle.electricity_plan,
+                bundle.gas_plan,
+                bundle.electricity,
+                bundle.gas,
+                bundle.total
+            );
+        }
+    }
+
+    if !report.estimated_annual_bills.is_empty() {
+        println!(
+            "Estimated annual bills on the CRU standard usage of 4,200 kWh from {}:",
+            period.start
+        );
+        print_ranking(&report.estimated_annual_bills, decimals);
+    }
+
+    if let Some(carbon) = &report.carbon {
+        println!(
+            "Carbon: {:.1} kg CO2 for {:.1} kWh imported, {:.0} g/kWh",
+            carbon.kg_co2, carbon.import_kwh, carbon.average_intensity
+        );
+        println!(
+            "  moving {:.0}% of the usage of each day to its cleanest interval saves {:.1} kg CO2",
+            carbon.shiftable_share * 100.0,
+            carbon.shifting_saves_kg_co2
+        );
+    }
+}
+
+fn print_exports_and_rates(report: &ComparisonReport, decimals: usize) {
+    let mut exports: Vec<(&str, &ExportEarnings)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.exports.as_ref()?)))
+        .collect();
+    if let Some((_, first)) = exports.first() {
+        let months: Vec<String> = first
+            .months
+            .iter()
+            .map(|month| format!("{} {:.2} kWh", month.month, month.kwh))
+            .collect();
+        println!("Exported {:.2} kWh: {}", first.kwh, months.join(", "));
+        exports.sort_by_key(|(_, exports)| std::cmp::Reverse(exports.credited.amount()));
+        println!("Export earnings, most credited first:");
+        for (name, exports) in exports {
+            println!(
+                "  {name}: {:.decimals$} credited of {:.decimals$} earned ({})",
+                exports.credited, exports.earned, exports.rules
+            );
+            if exports.months.len() > 1 {
+                for month in &exports.months {
+                    println!("     {}: {:.decimals$}", month.month, month.earned);
+                }
+            }
+        }
+    }
+    let mut rates: Vec<(&str, &EffectiveRate)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.effective_rate.as_ref()?)))
+        .collect();
+    if !rates.is_empty() {
+        rates.sort_by_key(|(_, rate)| rate.all_in_cents);
+        println!("Average cost of a kWh imported, all in, cheapest first:");
+        for (name, rate) in rates {
+            println!(
+                "  {name}: {:.2}c/kWh all in, {:.2}c/kWh for the energy",
+                rate.all_in_cents, rate.energy_cents
+            );
+        }
+    }
+    for result in report.plans.iter().filter(|r| !r.bands.is_empty()) {
+        println!("{} bands:", result.name);
+        for band in &result.bands {
+            println!(
+                "  {}: {:.2} kWh, {:.decimals$}",
+                band.band, band.kwh, band.cost
+            );
+        }
+    }
+}
+
+// The bills of the plans over the months and years
+fn print_over_time(report: &ComparisonReport, decimals: usize) {
+    let mut projected: Vec<&PlanComparison> = report
+        .plans
+        .iter()
+        .filter(|r| !r.projection.is_empty())
+        .collect();
+    if let Some(first) = projected.first() {
+        println!(
+            "Projection over {} years, cheapest first:",
+            first.projection.len()
+        );
+        projected.sort_by_key(|r| projected_total(r));
+        for result in projected {
+            print!("  {}: ", result.name);
+            for year in &result.projection {
+                print!("year {} {:.decimals$}, ", year.year, year.total);
+            }
+            println!("total {:.decimals$}", projected_total(result));
+        }
+    }
+    let mut level_pay: Vec<(&str, &LevelPay)> = report
+        .plans
+        .iter()
+        .filter_map(|r| Some((r.name.as_str(), r.level_pay.as_ref()?)))
+        .collect();
+    level_pay.sort_by_key(|(_, level_pay)| level_pay.monthly_payment.amount());
+    if let (Some((_, first)), Some(month)) = (
+        level_pay.first(),
+        level_pay.first().and_then(|(_, l)| l.months.first()),
+    ) {
+        println!(
+            "Level pay with a {}% buffer from {} on, cheapest first:",
+            (first.buffer * Decimal::ONE_HUNDRED).normalize(),
+            month.month.format("%m-%Y")
+        );
+        for (name, level_pay) in &level_pay {
+            println!(
+                "  {name}: {:.decimals$} a month, the balance is lowest at {:.decimals$} and {:.decimals$} after a year",
+                level_pay.monthly_payment,
+                level_pay.lowest_balance(),
+                level_pay.final_balance()
+            );
+            let months: Vec<String> = level_pay
+                .months
+                .iter()
+                .map(|month| {
+                    format!(
+                        "{} {:.decimals$}",
+                        month.month.format("%m-%Y"),
+                        month.balance
+                    )
+                })
+                .collect();
+            println!("    {}", months.join(", "));
+        }
+    }
+    for (index, scenario) in report.scenarios.iter().enumerate() {
+        let mut projected: Vec<(&str, &ScenarioProjection)> = report
+            .plans
+            .iter()
+            .filter_map(|r| Some((r.name.as_str(), r.scenarios.get(index)?)))
+            .collect();
+        projected.sort_by_key(|(_, projection)| projection.total);
+        println!("Scenario {scenario}, cheapest first:");
+        for (name, projection) in projected {
+            print!("  {name}: ");
+            for year in &projection.years {
+                print!("year {} {:.decimals$}, ", year.year, year.total);
+            }
+            println!("total {:.decimals$}", projection.total);
+        }
+    }
+
+    for result in report.plans.iter().filter(|r| !r.invoices.is_empty()) {
+        print_invoices(result, decimals);
+    }
+    for result in report.plans.iter().filter(|r| !r.buckets.is_empty()) {
+        println!("{}:", result.name);
+        for bucket in &result.buckets {
+            print!(
+                "  {} ({} to {}): ",
+                bucket.bucket, bucket.period.start, bucket.period.end
+            );
+            print_breakdown(&bucket.breakdown, decimals);
+            println!();
+        }
+    }
+    if let Some(winners) = &report.monthly_winners {
+        println!(
+            "Cheapest plan per month, {} was beaten in {} of {} months:",
+            winners.overall,
+            winners.beaten,
+            winners.months.len()
+        );
+        for month in &winners.months {
+            print!(
+                "  {} ({} to {}): {} {:.decimals$}",
+                month.month, month.period.start, month.period.end, month.plan, month.total
+            );
+            if month.plan != winners.overall {
+                print!(
+                    ", {:.decimals$} less than {}",
+                    month.saving(),
+                    winners.overall
+                );
+            }
+            println!();
+        }
+        let wins: Vec<String> = winners
+            .wins
+            .iter()
+            .map(|wins| format!("{} {}", wins.plan, wins.months.len()))
+            .collect();
+        println!("Months each plan was the cheapest: {}", wins.join(", "));
+    }
+    if let Some(sensitivity) = &report.rate_sensitivity {
+        let percent = (sensitivity.change * Decimal::ONE_HUNDRED).normalize();
+        println!(
+            "Rates of each plan moved {percent}% either way, the cheapest plan {}:",
+            if sensitivity.winner_holds {
+                "holds"
+            } else {
+                "can lose its place"
+            }
+        );
+        for plan in &sensitivity.plans {
+            print!(
+                "  {}: rank {}, {} if {percent}% higher and {} if lower",
+                plan.plan, plan.rank, plan.rank_if_higher, plan.rank_if_lower
+            );
+            if let Some(lead) = plan.lead_lost_at {
+                print!(
+                    ", the lead over the next plan goes at {}%",
+                    (lead * Decimal::ONE_HUNDRED).round_dp(1)
+                );
+            }
+            println!("{}", if plan.fragile { " (fragile)" } else { "" });
+        }
+    }
+}
+
+// Only for meter points that had their meter replaced, one line per meter in the order fitted
+pub(crate) fn print_meter_segments(data: &[SmartMeterData]) {
+    let segments = meter_segments(data);
+    let swapped: Vec<&MeterSegment> = segments
+        .iter()
+        .filter(|segment| segments.iter().filter(|s| s.mprn == segment.mprn).count() > 1)
+        .collect();
+    if swapped.is_empty() {
+        return;
+    }
+    println!("Replaced meters:");
+    for segment in swapped {
+        println!(
+            "  {} meter {}: {} readings from {} to {}",
+            segment.mprn,
+            segment.serial,
+            segment.readings,
+            segment.first.format("%d-%m-%Y %H:%M"),
+            segment.last.format("%d-%m-%Y %H:%M")
+        );
+    }
+}
+
+pub(crate) fn print_usage_stats(mprn: &str, stats: &UsageStats) {
+    let day = |day: &DayUsage| format!("{} with {:.2} kWh", day.date, day.import_kwh);
+    let average = |kwh: Option<f32>| kwh.map_or("-".to_string(), |kwh| format!("{kwh:.2}"));
+    println!("Meter point: {mprn}");
+    println!(
+        "Readings of {} days from {} to {}",
+        stats.days, stats.first_day, stats.last_day
+    );
+    println!(
+        "Imported {:.2} kWh, {:.2} kWh a day: {} on weekdays and {} at weekends",
+        stats.import_kwh,
+        stats.daily_import_kwh,
+        average(stats.weekday_import_kwh),
+        average(stats.weekend_import_kwh)
+    );
+    if stats.export_kwh > 0.0 {
+        println!(
+            "Exported {:.2} kWh, {:.2} kWh a day",
+            stats.export_kwh, stats.daily_export_kwh
+        );
+    }
+    println!(
+        "Lowest day {}, highest day {}",
+        day(&stats.lowest_day),
+        day(&stats.highest_day)
+    );
+    println!(
+        "Imports by window: day {:.1}%, night {:.1}% (23:00-08:00), peak {:.1}% (17:00-19:00)",
+        stats.day_share * 100.0,
+        stats.night_share * 100.0,
+        stats.peak_share * 100.0
+    );
+}
+
+pub(crate) fn print_gap_report(report: &GapReport) {
+    if report.is_clean() {
+        println!("No missing or out of order readings found");
+        return;
+    }
+    if !report.out_of_order.is_empty() {
+        println!("Out of order readings:");
+        for reading in &report.out_of_order {
+            println!(
+                "  {} {:?}: {} listed after {}",
+                reading.mprn, reading.read_type, reading.reading, reading.previous
+            );
+        }
+    }
+    if !report.gaps.is_empty() {
+        println!("Gaps:");
+        for gap in &report.gaps {
+            println!(
+                "  {} {:?}: {} intervals missing between {} and {}",
+                gap.mprn, gap.read_type, gap.missing_intervals, gap.after, gap.before
+            );
+        }
+    }
+    if !report.short_days.is_empty() {
+        println!("Days with missing import readings:");
+        for day in &report.short_days {
+            println!(
+                "  {} {}: {} of {} readings",
+                day.mprn, day.date, day.readings, day.expected
+            );
+        }
+    }
+}
+
+fn ordinal(rank: usize) -> String {
+    let suffix = match (rank % 10, rank % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{rank}{suffix}")
+}
+
+pub(crate) fn print_results_diff(diff: &ResultsDiff) {
+    for mprn in &diff.only_before {
+        println!("Meter point {mprn} is only in the earlier comparison");
+    }
+    for mprn in &diff.only_after {
+        println!("Meter point {mprn} is only in the later comparison");
+    }
+    for meter in &diff.meter_points {
+        if meter.is_unchanged() {
+            println!("Meter point {}: no changes", meter.mprn);
+            continue;
+        }
+        println!("Meter point {}:", meter.mprn);
+        let (before, after) = (meter.period_before, meter.period_after);
+        if before != after {
+            println!(
+                "  billing period {} to {}, was {} to {}",
+                after.start, after.end, before.start, before.end
+            );
+        }
+        if meter.cheapest_before != meter.cheapest_after {
+            println!(
+                "  cheapest plan {}, was {}",
+                meter.cheapest_after.as_deref().unwrap_or("none"),
+                meter.cheapest_before.as_deref().unwrap_or("none")
+            );
+        }
+        for change in &meter.changes {
+            let line = match (change.rank_before, change.rank_after) {
+                (None, Some(rank)) => format!(
+                    "new, {} at {:.2}",
+                    ordinal(rank),
+                    change.total_after.unwrap_or_default()
+                ),
+                (Some(rank), None) => format!(
+                    "gone, was {} at {:.2}",
+                    ordinal(rank),
+                    change.total_before.unwrap_or_default()
+                ),
+                (Some(was), Some(rank)) => {
+                    let moved = match was == rank {
+                        true => ordinal(rank),
+                        false => format!("{}, was {}", ordinal(rank), ordinal(was)),
+                    };
+                    match change.change().unwrap_or_default() {
+                        difference if difference.is_zero() => format!(
+                            "{moved}, still {:.2}",
+                            change.total_after.unwrap_or_default()
+                        ),
+                        difference => format!(
+                            "{moved}, {:.2} to {:.2} ({difference:+.2})",
+                            change.total_before.unwrap_or_default(),
+                            change.total_after.unwrap_or_default()
+                        ),
+                    }
+                }
+                (None, None) => continue,
+            };
+            println!("  {}: {line}", change.plan);
+        }
+        if meter.unchanged > 0 {
+            println!("  {} plans unchanged", meter.unchanged);
+        }
+    }
+}
+
+pub(crate) fn print_gap_filling(filling: &GapFilling) {
+    for filled in &filling.filled {
+        let gap = &filled.gap;
+        eprintln!(
+            "filled {} {:?} readings of {} between {} and {} with {}",
+            gap.missing_intervals, gap.read_type, gap.mprn, gap.after, gap.before, filled.strategy
+        );
+    }
+    for gap in &filling.left {
+        eprintln!(
+            "left {} {:?} readings of {} missing between {} and {}, too long a gap or nothing to fill it with",
+            gap.missing_intervals, gap.read_type, gap.mprn, gap.after, gap.before
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::fmt::Debug;
+
+use crate::{
+    projection::default_discount_months, vat::default_vat_included, Currency, Discount, DuosGroup,
+    EnergyBillEntry, ExportRules, Money, Numeric, PlanMetadata, PricePlanStrategy, PricingNumber,
+    Rounding, SmartMeterData, SmartMeterDataType, StandingCharge, StandingChargeDefinition,
+};
+
+// One block of a tiered plan, up_to is the kWh imported in the period where the block ends
+// and is left out for the last block
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tier {
+    #[serde(default)]
+    pub up_to: Option<Decimal>,
+    pub rate: Decimal,
+}
+
+// A plan that prices the first kWh of the billing period at one rate and the rest at others,
+// whatever the time of day. A reading that crosses into the next block is split between them
+#[derive(Clone, Deserialize)]
+pub struct TieredPlan {
+    pub name: String,
+    pub tiers: Vec<Tier>,
+    #[serde(default)]
+    pub discount: Discount,
+    #[serde(default = "default_discount_months")]
+    pub discount_months: u32,
+    #[serde(default)]
+    pub fixed_rate_months: u32,
+    #[serde(default)]
+    pub sign_up_bonus: Decimal,
+    #[serde(default)]
+    pub currency: Currency,
+    #[serde(default = "default_vat_included")]
+    pub vat_included: Decimal,
+    #[serde(default)]
+    pub export_rate: Decimal,
+    #[serde(default)]
+    pub export: ExportRules,
+    pub standing_charge: StandingChargeDefinition,
+    // charged instead of standing_charge for rural meter points, see DuosGroup
+    #[serde(default)]
+    pub rural_standing_charge: Option<StandingChargeDefinition>,
+    #[serde(default)]
+    pub rounding: Rounding,
+    #[serde(default)]
+    pub metadata: PlanMetadata,
+}
+
+// The name is enough to identify the plan on the output
+impl Debug for TieredPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl TieredPlan {
+    // The cost of importing kwh after imported_kwh were already imported, energy past the last
+    // block is priced at the rate of the last block
+    fn import_cost<N: Numeric>(&self, imported_kwh: N, kwh: N) -> Decimal {
+        let zero = N::default();
+        let mut cost = zero;
+        let mut position = imported_kwh;
+        let mut remaining = kwh;
+        for (index, tier) in self.tiers.iter().enumerate() {
+            if remaining <= zero {
+                break;
+            }
+            let is_last = index + 1 == self.tiers.len();
+            let in_tier = match tier.up_to {
+                Some(up_to) if !is_last => {
+                    let left = N::from_decimal(up_to) - position;
+                    if left <= zero {
+                        zero
+                    } else if left < remaining {
+                        left
+                    } else {
+                        remaining
+                    }
+                }
+                _ => remaining,
+            };
+            cost = cost + in_tier * N::from_decimal(tier.rate);
+            position = position + in_tier;
+            remaining = remaining - in_tier;
+        }
+        self.discount.on_unit_rate(cost.to_decimal())
+    }
+}
+
+impl PricePlanStrategy for TieredPlan {
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+        self.price_with_accumulated_usage(datapoint, 0.0)
+    }
+
+    fn price_with_accumulated_usage(
+        &self,
+        datapoint: &SmartMeterData,
+        imported_kwh: f32,
+    ) -> EnergyBillEntry {
+        let kwh = PricingNumber::from_f32(datapoint.energy_kwh());
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => {
+                let imported = PricingNumber::from_f32(imported_kwh);
+                EnergyBillEntry::Debit(Money::new(self.import_cost(imported, kwh)))
+            }
+            SmartMeterDataType::ActiveExport => EnergyBillEntry::Credit(Money::for_quantity(
+                self.export_rate,
+                datapoint.energy_kwh(),
+            )),
+        }
+    }
+
+    fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    fn export_rules(&self) -> ExportRules {
+        self.export
+    }
+
+    // the tiers count the usage of the whole period
+    fn usage_adds_up_by_month(&self) -> bool {
+        false
+    }
+
+    fn at_standard_rates(&self) -> Option<Box<dyn PricePlanStrategy>> {
+        Some(Box::new(TieredPlan {
+            discount: Discount::default(),
+            ..self.clone()
+        }))
+    }
+
+    fn discount(&self) -> Discount {
+        self.discount
+    }
+
+    fn discount_months(&self) -> u32 {
+        self.discount_months
+    }
+
+    fn fixed_rate_months(&self) -> u32 {
+        self.fixed_rate_months
+    }
+
+    fn sign_up_bonus(&self) -> Money {
+        Money::of(self.sign_up_bonus, self.currency)
+    }
+
+    fn metadata(&self) -> PlanMetadata {
+        self.metadata.clone()
+    }
+}
+
+impl StandingCharge for TieredPlan {
+    fn standing_charge_per_day(&self, date: NaiveDate, group: DuosGroup) -> EnergyBillEntry {
+        self.discount.on_standing_charge(
+            group
+                .pick(self.standing_charge, self.rural_standing_charge)
+                .for_day(date),
+        )
+    }
+
+    fn vat_included(&self) -> Decimal {
+        self.vat_included
+    }
+
+    fn currency(&self) -> Currency {
+        self.currency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn readings_crossing_a_block_are_split() {
+        let plan = TieredPlan {
+            name: "tiered".to_string(),
+            tiers: vec![
+                Tier {
+                    up_to: Some(dec!(1.5)),
+                    rate: dec!(0.20),
+                },
+                Tier {
+                    up_to: None,
+                    rate: dec!(0.40),
+                },
+            ],
+            discount: Discount::default(),
+            discount_months: crate::DEFAULT_DISCOUNT_MONTHS,
+            fixed_rate_months: 0,
+            sign_up_bonus: Decimal::ZERO,
+            currency: Currency::Eur,
+            vat_included: crate::QUOTED_VAT_RATE,
+            export_rate: Decimal::ZERO,
+            export: ExportRules::default(),
+            standing_charge: StandingChargeDefinition::PerDay(Decimal::ZERO),
+            rural_standing_charge: None,
+            rounding: Rounding::default(),
+            metadata: PlanMetadata::default(),
+        };
+        // 1 kWh each, given out of order to check they are priced in time order
+        let data = vec![
+            reading_at("08-01-2024 01:00", 2.0),
+            reading_at("08-01-2024 00:30", 2.0),
+        ];
+
+        // the first kWh at 0.20, then half a kWh at 0.20 and half at 0.40
+        assert_eq!(
+            plan.price_readings(&data),
+            vec![
+                EnergyBillEntry::Debit(Money::new(dec!(0.30))),
+                EnergyBillEntry::Debit(Money::new(dec!(0.20))),
+            ]
+        );
+    }
+}
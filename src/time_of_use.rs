@@ -0,0 +1,402 @@
+use anyhow::{bail, Result};
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike, Weekday};
+use rust_decimal::Decimal;
+use std::fmt::Debug;
+
+use crate::{
+    DaySet, Discount, DuosGroup, EnergyBillEntry, ExportRules, FreeTime, FreeWindow, Money,
+    PlanMetadata, PricePlanStrategy, RateBand, Rounding, SmartMeterData, SmartMeterDataType,
+    StandingCharge, StandingChargeDefinition, DEFAULT_DISCOUNT_MONTHS, QUOTED_VAT_RATE,
+};
+
+// A plan made of time windows with their own rate, anything outside the windows is priced
+// at the default rate. Unlike a ConfigurablePlan the bands can't overlap, so the order they
+// are declared in doesn't matter
+#[derive(Clone)]
+pub struct TimeOfUseTariff {
+    name: String,
+    bands: Vec<RateBand>,
+    boost: Option<RateBand>,
+    free_time: Option<FreeTime>,
+    default_band: String,
+    default_rate: Decimal,
+    discount: Discount,
+    discount_months: u32,
+    sign_up_bonus: Decimal,
+    vat_included: Decimal,
+    export_rate: Decimal,
+    export: ExportRules,
+    standing_charge: StandingChargeDefinition,
+    rural_standing_charge: Option<StandingChargeDefinition>,
+    rounding: Rounding,
+    holidays_like: Option<Weekday>,
+    metadata: PlanMetadata,
+}
+
+pub struct TimeOfUseTariffBuilder {
+    tariff: TimeOfUseTariff,
+}
+
+// The name is enough to identify the plan on the output
+impl Debug for TimeOfUseTariff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl TimeOfUseTariff {
+    pub fn builder(name: impl Into<String>) -> TimeOfUseTariffBuilder {
+        TimeOfUseTariffBuilder {
+            tariff: TimeOfUseTariff {
+                name: name.into(),
+                bands: vec![],
+                boost: None,
+                free_time: None,
+                default_band: "standard".to_string(),
+                default_rate: Decimal::ZERO,
+                discount: Discount::default(),
+                discount_months: DEFAULT_DISCOUNT_MONTHS,
+                sign_up_bonus: Decimal::ZERO,
+                vat_included: QUOTED_VAT_RATE,
+                export_rate: Decimal::ZERO,
+                export: ExportRules::default(),
+                standing_charge: StandingChargeDefinition::PerDay(Decimal::ZERO),
+                rural_standing_charge: None,
+                rounding: Rounding::default(),
+                holidays_like: None,
+                metadata: PlanMetadata::default(),
+            },
+        }
+    }
+
+    fn band_for(&self, datapoint: &SmartMeterData) -> Option<&RateBand> {
+        let local = datapoint.local_end_time();
+        let weekday = match self.holidays_like {
+            Some(weekday) if self.is_holiday(local.date()) => weekday,
+            _ => local.weekday(),
+        };
+        self.free_time
+            .iter()
+            .map(FreeTime::band)
+            .chain(&self.boost)
+            .chain(&self.bands)
+            .find(|band| band.applies_to(&local, weekday, datapoint.date()))
+    }
+}
+
+impl TimeOfUseTariffBuilder {
+    // see time_in_window for the boundaries
+    pub fn band(
+        mut self,
+        name: &str,
+        days: DaySet,
+        start: NaiveTime,
+        end: NaiveTime,
+        rate: Decimal,
+    ) -> TimeOfUseTariffBuilder {
+        self.tariff.bands.push(RateBand {
+            name: name.to_string(),
+            days,
+            start,
+            end,
+            rate,
+            season: None,
+        });
+        self
+    }
+
+    // A cheap window every day on top of the other bands, like the one EV tariffs have at night,
+    // it wins over any band it overlaps
+    pub fn boost(
+        mut self,
+        name: &str,
+        start: NaiveTime,
+        end: NaiveTime,
+        rate: Decimal,
+    ) -> TimeOfUseTariffBuilder {
+        self.tariff.boost = Some(RateBand {
+            name: name.to_string(),
+            days: DaySet::all(),
+            start,
+            end,
+            rate,
+            season: None,
+        });
+        self
+    }
+
+    // Free hours the customer picks, they win over the boost and any band they overlap
+    pub fn free_time(mut self, free_time: FreeTime) -> TimeOfUseTariffBuilder {
+        self.tariff.free_time = Some(free_time);
+        self
+    }
+
+    // The rate of every reading that no band covers
+    pub fn default_rate(mut self, name: &str, rate: Decimal) -> TimeOfUseTariffBuilder {
+        self.tariff.default_band = name.to_string();
+        self.tariff.default_rate = rate;
+        self
+    }
+
+    // A fraction taken off the import rates, 0.20 is 20% off
+    // A plain fraction is off the unit rates, see Discount for the other kinds
+    pub fn discount(mut self, discount: impl Into<Discount>) -> TimeOfUseTariffBuilder {
+        self.tariff.discount = discount.into();
+        self
+    }
+
+    // How long the discount lasts, DEFAULT_DISCOUNT_MONTHS unless set
+    pub fn discount_months(mut self, months: u32) -> TimeOfUseTariffBuilder {
+        self.tariff.discount_months = months;
+        self
+    }
+
+    // Cashback or bill credit for switching, taken off the first bill
+    pub fn sign_up_bonus(mut self, bonus: Decimal) -> TimeOfUseTariffBuilder {
+        self.tariff.sign_up_bonus = bonus;
+        self
+    }
+
+    // The VAT in the rates and standing charge, QUOTED_VAT_RATE unless set, zero for rates before VAT
+    pub fn vat_included(mut self, vat: Decimal) -> TimeOfUseTariffBuilder {
+        self.tariff.vat_included = vat;
+        self
+    }
+
+    pub fn export_rate(mut self, rate: Decimal) -> TimeOfUseTariffBuilder {
+        self.tariff.export_rate = rate;
+        self
+    }
+
+    pub fn export_rules(mut self, rules: ExportRules) -> TimeOfUseTariffBuilder {
+        self.tariff.export = rules;
+        self
+    }
+
+    pub fn standing_charge(
+        mut self,
+        standing_charge: StandingChargeDefinition,
+    ) -> TimeOfUseTariffBuilder {
+        self.tariff.standing_charge = standing_charge;
+        self
+    }
+
+    // Charged instead of the standing charge for rural meter points
+    pub fn rural_standing_charge(
+        mut self,
+        standing_charge: StandingChargeDefinition,
+    ) -> TimeOfUseTariffBuilder {
+        self.tariff.rural_standing_charge = Some(standing_charge);
+        self
+    }
+
+    pub fn rounding(mut self, rounding: Rounding) -> TimeOfUseTariffBuilder {
+        self.tariff.rounding = rounding;
+        self
+    }
+
+    // Bank holidays use the bands of this day
+    pub fn holidays_like(mut self, weekday: Weekday) -> TimeOfUseTariffBuilder {
+        self.tariff.holidays_like = Some(weekday);
+        self
+    }
+
+    pub fn metadata(mut self, metadata: PlanMetadata) -> TimeOfUseTariffBuilder {
+        self.tariff.metadata = metadata;
+        self
+    }
+
+    // Fails when two bands cover the same time of the same day, the boost is allowed to
+    pub fn build(self) -> Result<TimeOfUseTariff> {
+        let bands = &self.tariff.bands;
+        for (index, band) in bands.iter().enumerate() {
+            for other in &bands[index + 1..] {
+                if bands_overlap(band, other) {
+                    bail!(
+                        "the {} and {} bands of {} overlap",
+                        band.name,
+                        other.name,
+                        self.tariff.name
+                    );
+                }
+            }
+        }
+        Ok(self.tariff)
+    }
+}
+
+// Minutes after midnight, with midnight at the end of the day as 1440 so (start, end] ranges
+// line up with time_in_window
+fn window_ranges(start: NaiveTime, end: NaiveTime) -> Vec<(u32, u32)> {
+    let minutes = |time: NaiveTime| time.hour() * 60 + time.minute();
+    let (start, end) = (minutes(start), minutes(end));
+    if start < end {
+        vec![(start, end)]
+    } else if start > end {
+        vec![(start, 24 * 60), (0, end)]
+    } else {
+        vec![(0, 24 * 60)]
+    }
+}
+
+fn bands_overlap(a: &RateBand, b: &RateBand) -> bool {
+    a.days.overlaps(&b.days)
+        && window_ranges(a.start, a.end)
+            .iter()
+            .any(|&(a_start, a_end)| {
+                window_ranges(b.start, b.end)
+                    .iter()
+                    .any(|&(b_start, b_end)| a_start < b_end && b_start < a_end)
+            })
+}
+
+impl PricePlanStrategy for TimeOfUseTariff {
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => {
+                let rate = self
+                    .band_for(datapoint)
+                    .map_or(self.default_rate, |b| b.rate);
+                EnergyBillEntry::Debit(Money::for_quantity(
+                    self.discount.on_unit_rate(rate),
+                    datapoint.energy_kwh(),
+                ))
+            }
+            SmartMeterDataType::ActiveExport => EnergyBillEntry::Credit(Money::for_quantity(
+                self.export_rate,
+                datapoint.energy_kwh(),
+            )),
+        }
+    }
+
+    fn band_for_singe_period(&self, datapoint: &SmartMeterData) -> String {
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => match self.band_for(datapoint) {
+                Some(band) => band.name.clone(),
+                None => self.default_band.clone(),
+            },
+            SmartMeterDataType::ActiveExport => "export".to_string(),
+        }
+    }
+
+    fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    fn export_rules(&self) -> ExportRules {
+        self.export
+    }
+
+    fn at_standard_rates(&self) -> Option<Box<dyn PricePlanStrategy>> {
+        Some(Box::new(TimeOfUseTariff {
+            discount: Discount::default(),
+            ..self.clone()
+        }))
+    }
+
+    fn discount(&self) -> Discount {
+        self.discount
+    }
+
+    fn discount_months(&self) -> u32 {
+        self.discount_months
+    }
+
+    fn sign_up_bonus(&self) -> Money {
+        Money::new(self.sign_up_bonus)
+    }
+
+    fn metadata(&self) -> PlanMetadata {
+        self.metadata.clone()
+    }
+
+    fn free_time(&self) -> Option<FreeTime> {
+        self.free_time.clone()
+    }
+
+    fn free_time_choices(&self) -> Vec<(FreeWindow, Box<dyn PricePlanStrategy>)> {
+        let Some(free_time) = &self.free_time else {
+            return vec![];
+        };
+        free_time
+            .windows()
+            .into_iter()
+            .map(|window| {
+                let plan: Box<dyn PricePlanStrategy> = Box::new(TimeOfUseTariff {
+                    free_time: Some(free_time.choosing(&window)),
+                    ..self.clone()
+                });
+                (window, plan)
+            })
+            .collect()
+    }
+}
+
+impl StandingCharge for TimeOfUseTariff {
+    fn standing_charge_per_day(&self, date: NaiveDate, group: DuosGroup) -> EnergyBillEntry {
+        self.discount.on_standing_charge(
+            group
+                .pick(self.standing_charge, self.rural_standing_charge)
+                .for_day(date),
+        )
+    }
+
+    fn vat_included(&self) -> Decimal {
+        self.vat_included
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn overlapping_bands_are_rejected() {
+        let tariff = TimeOfUseTariff::builder("overlapping")
+            .band("peak", DaySet::all(), time(17, 0), time(19, 0), dec!(0.50))
+            .band(
+                "evening",
+                DaySet::of(&[Weekday::Mon]),
+                time(18, 30),
+                time(23, 0),
+                dec!(0.40),
+            )
+            .build();
+        assert!(tariff.is_err());
+    }
+
+    #[test]
+    fn touching_and_other_day_bands_are_allowed() {
+        // 19:00 belongs to peak only, and the sunday band never meets the weekday one
+        let tariff = TimeOfUseTariff::builder("touching")
+            .band(
+                "peak",
+                DaySet::of(&[Weekday::Mon]),
+                time(17, 0),
+                time(19, 0),
+                dec!(0.50),
+            )
+            .band(
+                "evening",
+                DaySet::all(),
+                time(19, 0),
+                time(23, 0),
+                dec!(0.40),
+            )
+            .band(
+                "free",
+                DaySet::of(&[Weekday::Sun]),
+                time(9, 0),
+                time(18, 0),
+                dec!(0),
+            )
+            .band("night", DaySet::all(), time(23, 0), time(8, 0), dec!(0.20))
+            .build();
+        assert!(tariff.is_ok());
+    }
+}
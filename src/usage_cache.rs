@@ -0,0 +1,371 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    BillingPeriod, Bucket, Currency, Discount, DuosGroup, EnergyBillEntry, ExportRules, FreeTime,
+    FreeWindow, Granularity, MaximumImportCapacity, Money, PlanMetadata, PricePlanStrategy,
+    ReadUnit, Rounding, SmartMeterData, SmartMeterDataType, StandingCharge,
+};
+
+// Bumped whenever the keys are made differently, older caches are started again
+const USAGE_CACHE_VERSION: u32 = 3;
+
+// The 64 bit FNV-1a hash, written out here because the keys are saved and DefaultHasher can
+// change between Rust releases. Every value goes in as little endian bytes
+pub(crate) struct Fnv(u64);
+
+impl Fnv {
+    pub(crate) fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) -> &mut Fnv {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+        self
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) -> &mut Fnv {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    // with its length first, so two strings can't run into each other
+    pub(crate) fn str(&mut self, value: &str) -> &mut Fnv {
+        self.u64(value.len() as u64).bytes(value.as_bytes())
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// The usage of each plan and month from earlier runs, so running again over years of readings
+// only prices the months whose readings changed
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UsageCache {
+    #[serde(serialize_with = "save_usage", deserialize_with = "load_usage")]
+    usage: HashMap<u64, Decimal>,
+}
+
+// bincode can't read decimals back in their serde form, so they are kept as their 16 bytes
+fn save_usage<S: serde::Serializer>(
+    usage: &HashMap<u64, Decimal>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(usage.iter().map(|(key, usage)| (key, usage.serialize())))
+}
+
+fn load_usage<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<u64, Decimal>, D::Error> {
+    let usage: HashMap<u64, [u8; 16]> = Deserialize::deserialize(deserializer)?;
+    Ok(usage
+        .into_iter()
+        .map(|(key, bytes)| (key, Decimal::deserialize(bytes)))
+        .collect())
+}
+
+pub type SharedUsageCache = Arc<Mutex<UsageCache>>;
+
+impl UsageCache {
+    // A missing or unreadable cache is an empty one, it only costs time, and so is one of
+    // another version
+    pub fn load(path: &Path) -> UsageCache {
+        let Ok(file) = File::open(path) else {
+            return UsageCache::default();
+        };
+        let mut reader = BufReader::new(file);
+        match bincode::deserialize_from::<_, u32>(&mut reader) {
+            Ok(USAGE_CACHE_VERSION) => bincode::deserialize_from(reader).unwrap_or_default(),
+            _ => UsageCache::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(&mut writer, &USAGE_CACHE_VERSION)?;
+        bincode::serialize_into(&mut writer, self)?;
+        Ok(())
+    }
+
+    pub fn shared(self) -> SharedUsageCache {
+        Arc::new(Mutex::new(self))
+    }
+}
+
+// Identifies the readings of a month, any corrected or added reading changes it
+fn readings_hash(datapoints: &[SmartMeterData]) -> u64 {
+    let mut hasher = Fnv::new();
+    for datapoint in datapoints {
+        let read_type = match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => 0,
+            SmartMeterDataType::ActiveExport => 1,
+        };
+        let unit = match datapoint.unit {
+            ReadUnit::Kilowatts => 0,
+            ReadUnit::KilowattHours => 1,
+        };
+        let end_time = datapoint.read_data_and_end_time.and_utc().timestamp();
+        hasher
+            .str(&datapoint.mprn)
+            .str(&datapoint.meter_serial_number)
+            .u64(read_type)
+            .u64(unit)
+            .u64(end_time as u64)
+            .u64(u64::from(datapoint.read_value.to_bits()))
+            .u64(u64::from(datapoint.interval_minutes));
+    }
+    hasher.finish()
+}
+
+// A plan whose usage is looked up in the cache month by month, everything else is left to
+// the plan. The version identifies the rates and settings the usage was priced with, like
+// the contents of the tariff file, with the cache_version of the plan for the rest
+pub struct CachedPlan {
+    plan: Box<dyn PricePlanStrategy>,
+    version: u64,
+    cache: SharedUsageCache,
+}
+
+impl CachedPlan {
+    // Plans whose usage doesn't add up month by month are returned as they are
+    pub fn wrap(
+        plan: Box<dyn PricePlanStrategy>,
+        version: &str,
+        cache: &SharedUsageCache,
+    ) -> Box<dyn PricePlanStrategy> {
+        if !plan.usage_adds_up_by_month() {
+            return plan;
+        }
+        let version = Fnv::new()
+            .str(version)
+            .str(&plan.name())
+            .u64(plan.cache_version())
+            .finish();
+        Box::new(CachedPlan {
+            plan,
+            version,
+            cache: Arc::clone(cache),
+        })
+    }
+
+    fn month_usage(&self, datapoints: &[SmartMeterData]) -> Decimal {
+        let key = Fnv::new()
+            .u64(self.version)
+            .u64(readings_hash(datapoints))
+            .finish();
+        if let Some(&usage) = self.cache.lock().unwrap().usage.get(&key) {
+            return usage;
+        }
+        let usage = self
+            .plan
+            .compute_total_bill_for_period(datapoints)
+            .signed_amount();
+        // a plan that failed priced the readings at zero
+        if self.plan.pricing_error().is_none() {
+            self.cache.lock().unwrap().usage.insert(key, usage);
+        }
+        usage
+    }
+}
+
+impl Debug for CachedPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.plan.fmt(f)
+    }
+}
+
+impl StandingCharge for CachedPlan {
+    fn standing_charge_per_day(&self, date: NaiveDate, group: DuosGroup) -> EnergyBillEntry {
+        self.plan.standing_charge_per_day(date, group)
+    }
+
+    fn vat_included(&self) -> Decimal {
+        self.plan.vat_included()
+    }
+
+    fn currency(&self) -> Currency {
+        self.plan.currency()
+    }
+
+    fn standing_charge_for_period(&self, period: &BillingPeriod) -> EnergyBillEntry {
+        self.plan.standing_charge_for_period(period)
+    }
+}
+
+impl PricePlanStrategy for CachedPlan {
+    fn price_for_singe_period(&self, datapoint: &SmartMeterData) -> EnergyBillEntry {
+        self.plan.price_for_singe_period(datapoint)
+    }
+
+    fn band_for_singe_period(&self, datapoint: &SmartMeterData) -> String {
+        self.plan.band_for_singe_period(datapoint)
+    }
+
+    fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.plan.is_holiday(date)
+    }
+
+    fn rounding(&self) -> Rounding {
+        self.plan.rounding()
+    }
+
+    fn name(&self) -> String {
+        self.plan.name()
+    }
+
+    fn price_with_accumulated_usage(
+        &self,
+        datapoint: &SmartMeterData,
+        imported_kwh: f32,
+    ) -> EnergyBillEntry {
+        self.plan
+            .price_with_accumulated_usage(datapoint, imported_kwh)
+    }
+
+    fn price_readings(&self, datapoints: &[SmartMeterData]) -> Vec<EnergyBillEntry> {
+        self.plan.price_readings(datapoints)
+    }
+
+    fn at_standard_rates(&self) -> Option<Box<dyn PricePlanStrategy>> {
+        self.plan.at_standard_rates()
+    }
+
+    fn discount_months(&self) -> u32 {
+        self.plan.discount_months()
+    }
+
+    fn fixed_rate_months(&self) -> u32 {
+        self.plan.fixed_rate_months()
+    }
+
+    fn discount(&self) -> Discount {
+        self.plan.discount()
+    }
+
+    fn sign_up_bonus(&self) -> Money {
+        self.plan.sign_up_bonus()
+    }
+
+    fn metadata(&self) -> PlanMetadata {
+        self.plan.metadata()
+    }
+
+    fn free_time(&self) -> Option<FreeTime> {
+        self.plan.free_time()
+    }
+
+    fn free_time_choices(&self) -> Vec<(FreeWindow, Box<dyn PricePlanStrategy>)> {
+        self.plan.free_time_choices()
+    }
+
+    fn is_prepay(&self) -> bool {
+        self.plan.is_prepay()
+    }
+
+    fn pricing_error(&self) -> Option<String> {
+        self.plan.pricing_error()
+    }
+
+    fn pricing_warning(&self, datapoints: &[SmartMeterData]) -> Option<String> {
+        self.plan.pricing_warning(datapoints)
+    }
+
+    fn export_rules(&self) -> ExportRules {
+        self.plan.export_rules()
+    }
+
+    fn usage_adds_up_by_month(&self) -> bool {
+        true
+    }
+
+    fn cache_version(&self) -> u64 {
+        self.plan.cache_version()
+    }
+
+    fn pso_levy_for_period(&self, period: &BillingPeriod) -> EnergyBillEntry {
+        self.plan.pso_levy_for_period(period)
+    }
+
+    fn demand_charge_with_mic(
+        &self,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+        mic: Option<MaximumImportCapacity>,
+    ) -> EnergyBillEntry {
+        self.plan.demand_charge_with_mic(datapoints, period, mic)
+    }
+
+    fn demand_charge_for_period(
+        &self,
+        datapoints: &[SmartMeterData],
+        period: &BillingPeriod,
+    ) -> EnergyBillEntry {
+        self.plan.demand_charge_for_period(datapoints, period)
+    }
+
+    fn compute_total_bill_for_period(&self, datapoints: &[SmartMeterData]) -> EnergyBillEntry {
+        let mut months: BTreeMap<Bucket, Vec<SmartMeterData>> = BTreeMap::new();
+        for datapoint in datapoints {
+            months
+                .entry(Bucket::of(datapoint.date(), Granularity::Month))
+                .or_default()
+                .push(datapoint.clone());
+        }
+        EnergyBillEntry::from_signed(
+            months
+                .values()
+                .map(|readings| self.month_usage(readings))
+                .sum(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meter_data::reading_at, sse_airtricity_20};
+
+    #[test]
+    fn usage_is_the_same_from_the_cache() {
+        let data = vec![
+            reading_at("31-01-2024 12:00", 1.0),
+            reading_at("01-02-2024 18:00", 2.0),
+            reading_at("01-02-2024 23:30", 0.5),
+        ];
+        let plan: Box<dyn PricePlanStrategy> = Box::new(sse_airtricity_20());
+        let expected = plan.compute_total_bill_for_period(&data);
+
+        let cache = UsageCache::default().shared();
+        let cached = CachedPlan::wrap(Box::new(sse_airtricity_20()), "1", &cache);
+        assert_eq!(cached.compute_total_bill_for_period(&data), expected);
+        assert_eq!(cache.lock().unwrap().usage.len(), 2);
+        // the second time every month comes from the cache
+        assert_eq!(cached.compute_total_bill_for_period(&data), expected);
+        assert_eq!(cache.lock().unwrap().usage.len(), 2);
+
+        // the keys don't depend on the Rust that built the tool
+        assert_eq!(Fnv::new().finish(), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(Fnv::new().bytes(b"a").finish(), 0xaf63_dc4c_8601_ec8c);
+        let path = std::env::temp_dir().join(format!("usage-cache-{}", std::process::id()));
+        cache.lock().unwrap().save(&path).unwrap();
+        assert_eq!(UsageCache::load(&path).usage, cache.lock().unwrap().usage);
+        // a cache of another version is started again
+        let mut file = File::create(&path).unwrap();
+        bincode::serialize_into(&mut file, &1u32).unwrap();
+        bincode::serialize_into(&mut file, &*cache.lock().unwrap()).unwrap();
+        assert!(UsageCache::load(&path).usage.is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
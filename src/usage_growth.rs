@@ -0,0 +1,153 @@
+use anyhow::Result;
+use chrono::{Datelike, Duration, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    load_shift::add_energy, projection::days_in_month, time_in_window, SmartMeterData,
+    SmartMeterDataType,
+};
+
+// The share of a year's heating an Irish heat pump uses in each month, next to nothing in
+// the summer but the hot water
+const HEAT_PUMP_MONTHS: [f32; 12] = [
+    0.16, 0.15, 0.13, 0.08, 0.04, 0.02, 0.02, 0.02, 0.03, 0.08, 0.12, 0.15,
+];
+
+// and in each hour of the day, most in the morning and the evening when the house is heated
+const HEAT_PUMP_HOURS: [f32; 24] = [
+    2.0, 2.0, 2.0, 2.0, 2.5, 3.5, 6.0, 6.5, 5.5, 5.0, 5.0, 4.0, 4.0, 4.0, 4.0, 4.5, 4.5, 6.0, 6.5,
+    6.0, 5.0, 4.0, 3.0, 2.5,
+];
+
+fn one() -> f32 {
+    1.0
+}
+
+// A heat pump using kwh_per_year, spread over the readings by HEAT_PUMP_MONTHS and
+// HEAT_PUMP_HOURS
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct HeatPump {
+    pub kwh_per_year: f32,
+}
+
+// A car charged every night, kwh_per_night spread evenly over the readings of the window of
+// each day
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct NightlyCharging {
+    pub kwh_per_night: f32,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+// The usage of the future on top of the readings, read from a file like growth.example.toml.
+// The imports are scaled first and whatever is added comes on top
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct UsageGrowth {
+    #[serde(default = "one")]
+    pub scale: f32,
+    #[serde(default)]
+    pub heat_pump: Option<HeatPump>,
+    #[serde(default)]
+    pub ev: Option<NightlyCharging>,
+}
+
+// The imports of the readings before and with the growth
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct GrowthSummary {
+    pub imported_kwh: f32,
+    pub grown_kwh: f32,
+}
+
+impl UsageGrowth {
+    pub fn load(filename: &Path) -> Result<UsageGrowth> {
+        let contents = std::fs::read_to_string(filename)?;
+        toml::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("invalid usage growth file {}: {e}", filename.display()))
+    }
+
+    fn heat_pump_kwh(heat_pump: &HeatPump, datapoint: &SmartMeterData) -> f32 {
+        let start =
+            datapoint.local_end_time() - Duration::minutes(datapoint.interval_minutes as i64);
+        let date = start.date();
+        let per_day = heat_pump.kwh_per_year * HEAT_PUMP_MONTHS[date.month0() as usize]
+            / days_in_month(date) as f32;
+        let hour_share =
+            HEAT_PUMP_HOURS[start.hour() as usize] / HEAT_PUMP_HOURS.iter().sum::<f32>();
+        per_day * hour_share * datapoint.interval_minutes as f32 / 60.0
+    }
+
+    pub fn apply(&self, datapoints: &[SmartMeterData]) -> (Vec<SmartMeterData>, GrowthSummary) {
+        let mut grown = datapoints.to_vec();
+        let imports = |data: &[SmartMeterData]| -> f32 {
+            data.iter()
+                .filter(|d| d.read_type == SmartMeterDataType::ActiveImport)
+                .map(SmartMeterData::energy_kwh)
+                .sum()
+        };
+        // the readings of the charging window of each day of each meter point
+        let mut nights: BTreeMap<(String, chrono::NaiveDate), Vec<usize>> = BTreeMap::new();
+        for (index, datapoint) in grown.iter_mut().enumerate() {
+            if datapoint.read_type != SmartMeterDataType::ActiveImport {
+                continue;
+            }
+            datapoint.read_value *= self.scale;
+            if let Some(heat_pump) = &self.heat_pump {
+                let kwh = Self::heat_pump_kwh(heat_pump, datapoint);
+                add_energy(datapoint, kwh);
+            }
+            if let Some(ev) = &self.ev {
+                if time_in_window(datapoint.local_end_time().time(), ev.start, ev.end) {
+                    nights
+                        .entry((datapoint.mprn.to_string(), datapoint.date()))
+                        .or_default()
+                        .push(index);
+                }
+            }
+        }
+        if let Some(ev) = &self.ev {
+            for night in nights.values() {
+                for &index in night {
+                    add_energy(&mut grown[index], ev.kwh_per_night / night.len() as f32);
+                }
+            }
+        }
+        let summary = GrowthSummary {
+            imported_kwh: imports(datapoints),
+            grown_kwh: imports(&grown),
+        };
+        (grown, summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn the_growth_comes_on_top_of_the_scaled_imports() {
+        let growth: UsageGrowth = toml::from_str(
+            r#"
+scale = 1.5
+heat_pump = { kwh_per_year = 3100 }
+ev = { kwh_per_night = 10, start = "02:00", end = "04:00" }
+"#,
+        )
+        .unwrap();
+        // a cold january night, 8 january 2024 from 02:00 to 04:00
+        let data: Vec<SmartMeterData> = ["02:30", "03:00", "03:30", "04:00", "12:00"]
+            .iter()
+            .map(|time| reading_at(&format!("08-01-2024 {time}"), 1.0))
+            .collect();
+        let (grown, summary) = growth.apply(&data);
+        assert_eq!(summary.imported_kwh, 2.5);
+        // 16% of the year in january is 16 kWh a day, 2 am has 2 of the 100 parts of a day
+        let heat_pump = 0.16 * 3100.0 / 31.0 * 2.0 / 100.0 / 2.0;
+        let night = grown[0].energy_kwh();
+        assert!((night - (0.75 + heat_pump + 2.5)).abs() < 0.001, "{night}");
+        // the noon reading gets no charging
+        assert!(grown[4].energy_kwh() < 1.5);
+        assert!(summary.grown_kwh > summary.imported_kwh * 1.5 + 10.0);
+    }
+}
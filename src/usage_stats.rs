@@ -0,0 +1,131 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{eab::standard_window, SmartMeterData, SmartMeterDataType};
+
+// The energy of one day of the readings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DayUsage {
+    pub date: NaiveDate,
+    pub import_kwh: f32,
+    pub export_kwh: f32,
+}
+
+// What the readings of a meter point used, without any plan. The lowest and highest days are
+// by their imports, and the shares are of the imports in the standard smart tariff windows:
+// night is 23:00 to 08:00, peak 17:00 to 19:00 and day the rest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageStats {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+    pub days: usize,
+    pub import_kwh: f32,
+    pub export_kwh: f32,
+    pub daily_import_kwh: f32,
+    pub daily_export_kwh: f32,
+    pub lowest_day: DayUsage,
+    pub highest_day: DayUsage,
+    // the average day of each, None without any days of them
+    pub weekday_import_kwh: Option<f32>,
+    pub weekend_import_kwh: Option<f32>,
+    pub day_share: f32,
+    pub night_share: f32,
+    pub peak_share: f32,
+}
+
+// The stats of the readings of one meter point, None without any readings
+pub fn usage_stats(datapoints: &[SmartMeterData]) -> Option<UsageStats> {
+    let mut days: BTreeMap<NaiveDate, DayUsage> = BTreeMap::new();
+    let mut windows = [0.0f32; 3];
+    for datapoint in datapoints {
+        let date = datapoint.date();
+        let day = days.entry(date).or_insert(DayUsage {
+            date,
+            import_kwh: 0.0,
+            export_kwh: 0.0,
+        });
+        let kwh = datapoint.energy_kwh();
+        match datapoint.read_type {
+            SmartMeterDataType::ActiveImport => {
+                day.import_kwh += kwh;
+                windows[standard_window(datapoint.local_end_time().time())] += kwh;
+            }
+            SmartMeterDataType::ActiveExport => day.export_kwh += kwh,
+        }
+    }
+    let first_day = *days.keys().next()?;
+    let last_day = *days.keys().next_back()?;
+    let days: Vec<DayUsage> = days.into_values().collect();
+    let import_kwh: f32 = days.iter().map(|day| day.import_kwh).sum();
+    let export_kwh: f32 = days.iter().map(|day| day.export_kwh).sum();
+    let average = |weekend: bool| {
+        let kwh: Vec<f32> = days
+            .iter()
+            .filter(|day| matches!(day.date.weekday(), Weekday::Sat | Weekday::Sun) == weekend)
+            .map(|day| day.import_kwh)
+            .collect();
+        (!kwh.is_empty()).then(|| kwh.iter().sum::<f32>() / kwh.len() as f32)
+    };
+    let share = |kwh: f32| {
+        if import_kwh > 0.0 {
+            kwh / import_kwh
+        } else {
+            0.0
+        }
+    };
+    let by_imports = |a: &&DayUsage, b: &&DayUsage| a.import_kwh.total_cmp(&b.import_kwh);
+    Some(UsageStats {
+        first_day,
+        last_day,
+        days: days.len(),
+        import_kwh,
+        export_kwh,
+        daily_import_kwh: import_kwh / days.len() as f32,
+        daily_export_kwh: export_kwh / days.len() as f32,
+        lowest_day: *days.iter().min_by(by_imports)?,
+        highest_day: *days.iter().max_by(by_imports)?,
+        weekday_import_kwh: average(false),
+        weekend_import_kwh: average(true),
+        day_share: share(windows[0]),
+        night_share: share(windows[1]),
+        peak_share: share(windows[2]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn the_days_are_split_by_weekend_and_window() {
+        // saturday 6 january 2024 at night and at peak, monday 8 january in the day
+        let mut data = vec![
+            reading_at("06-01-2024 02:00", 2.0),
+            reading_at("06-01-2024 18:00", 4.0),
+            reading_at("08-01-2024 12:00", 2.0),
+        ];
+        let mut export = reading_at("08-01-2024 13:00", 3.0);
+        export.read_type = SmartMeterDataType::ActiveExport;
+        data.push(export);
+
+        let stats = usage_stats(&data).unwrap();
+        assert_eq!(stats.days, 2);
+        assert_eq!(stats.import_kwh, 4.0);
+        assert_eq!(stats.export_kwh, 1.5);
+        assert_eq!(stats.daily_import_kwh, 2.0);
+        assert_eq!(
+            stats.lowest_day.date,
+            NaiveDate::from_ymd_opt(2024, 1, 8).unwrap()
+        );
+        assert_eq!(stats.highest_day.import_kwh, 3.0);
+        assert_eq!(stats.weekday_import_kwh, Some(1.0));
+        assert_eq!(stats.weekend_import_kwh, Some(3.0));
+        assert_eq!(
+            (stats.day_share, stats.night_share, stats.peak_share),
+            (0.25, 0.25, 0.5)
+        );
+        assert!(usage_stats(&[]).is_none());
+    }
+}
@@ -0,0 +1,320 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+};
+
+use crate::{SmartMeterData, SmartMeterDataType, LOCAL_TIMEZONE};
+
+// Readings missing between two readings of the same meter and direction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gap {
+    pub mprn: String,
+    pub read_type: SmartMeterDataType,
+    pub after: NaiveDateTime,
+    pub before: NaiveDateTime,
+    pub missing_intervals: i64,
+}
+
+// A day that has fewer import readings than a full local day of its interval length
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortDay {
+    pub mprn: String,
+    pub date: NaiveDate,
+    pub readings: usize,
+    pub expected: usize,
+}
+
+// A reading that ends before the reading listed just before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfOrder {
+    pub mprn: String,
+    pub read_type: SmartMeterDataType,
+    pub previous: NaiveDateTime,
+    pub reading: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GapReport {
+    pub gaps: Vec<Gap>,
+    pub short_days: Vec<ShortDay>,
+    pub out_of_order: Vec<OutOfOrder>,
+}
+
+impl GapReport {
+    pub fn is_clean(&self) -> bool {
+        self.gaps.is_empty() && self.short_days.is_empty() && self.out_of_order.is_empty()
+    }
+}
+
+// Something off with the meter points of a download, most often the file of another home
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MprnIssue {
+    // not the 11 digits of an irish MPRN
+    Malformed { mprn: String },
+    // a download is of one meter point, with the readings of each
+    Several { mprns: Vec<(String, usize)> },
+    // not the meter point the readings were expected to be of
+    Unexpected { mprn: String, expected: String },
+    // a meter only ever reads for one meter point
+    SharedSerial { serial: String, mprns: Vec<String> },
+}
+
+impl Display for MprnIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MprnIssue::Malformed { mprn } => write!(f, "{mprn:?} is not an MPRN of 11 digits"),
+            MprnIssue::Several { mprns } => {
+                let mprns: Vec<String> = mprns
+                    .iter()
+                    .map(|(mprn, readings)| format!("{mprn} ({readings} readings)"))
+                    .collect();
+                write!(
+                    f,
+                    "the readings are of several meter points: {}",
+                    mprns.join(", ")
+                )
+            }
+            MprnIssue::Unexpected { mprn, expected } => {
+                write!(f, "the readings are of {mprn}, not of {expected}")
+            }
+            MprnIssue::SharedSerial { serial, mprns } => {
+                write!(f, "meter {serial} reads for {}", mprns.join(" and "))
+            }
+        }
+    }
+}
+
+pub fn is_valid_mprn(mprn: &str) -> bool {
+    mprn.len() == 11 && mprn.bytes().all(|b| b.is_ascii_digit())
+}
+
+// The meter points of the readings of one download, against the one expected when there is one
+pub fn check_mprns(data: &[SmartMeterData], expected: Option<&str>) -> Vec<MprnIssue> {
+    let mut readings: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut serials: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for datapoint in data {
+        *readings.entry(&datapoint.mprn).or_default() += 1;
+        serials
+            .entry(&datapoint.meter_serial_number)
+            .or_default()
+            .insert(&datapoint.mprn);
+    }
+    let mut issues: Vec<MprnIssue> = readings
+        .keys()
+        .filter(|mprn| !is_valid_mprn(mprn))
+        .map(|mprn| MprnIssue::Malformed {
+            mprn: mprn.to_string(),
+        })
+        .collect();
+    if readings.len() > 1 {
+        issues.push(MprnIssue::Several {
+            mprns: readings
+                .iter()
+                .map(|(mprn, count)| (mprn.to_string(), *count))
+                .collect(),
+        });
+    }
+    if let Some(expected) = expected {
+        issues.extend(
+            readings
+                .keys()
+                .filter(|mprn| **mprn != expected)
+                .map(|mprn| MprnIssue::Unexpected {
+                    mprn: mprn.to_string(),
+                    expected: expected.to_string(),
+                }),
+        );
+    }
+    issues.extend(
+        serials
+            .into_iter()
+            .filter(|(_, mprns)| mprns.len() > 1)
+            .map(|(serial, mprns)| MprnIssue::SharedSerial {
+                serial: serial.to_string(),
+                mprns: mprns.into_iter().map(str::to_string).collect(),
+            }),
+    );
+    issues
+}
+
+// Looks for the holes that make a comparison look cheaper than it is, the readings are
+// checked in the order they were read so out of order rows are caught before sorting
+pub fn validate_readings(data: &[SmartMeterData]) -> GapReport {
+    let mut report = GapReport::default();
+    let mut series: BTreeMap<(&str, SmartMeterDataType), Vec<&SmartMeterData>> = BTreeMap::new();
+    for datapoint in data {
+        series
+            .entry((&datapoint.mprn, datapoint.read_type))
+            .or_default()
+            .push(datapoint);
+    }
+
+    for ((mprn, read_type), mut readings) in series {
+        for pair in readings.windows(2) {
+            if pair[1].read_data_and_end_time < pair[0].read_data_and_end_time {
+                report.out_of_order.push(OutOfOrder {
+                    mprn: mprn.to_string(),
+                    read_type,
+                    previous: pair[0].read_data_and_end_time,
+                    reading: pair[1].read_data_and_end_time,
+                });
+            }
+        }
+
+        readings.sort_by_key(|d| d.read_data_and_end_time);
+        for pair in readings.windows(2) {
+            let interval = i64::from(pair[1].interval_minutes);
+            let minutes =
+                (pair[1].read_data_and_end_time - pair[0].read_data_and_end_time).num_minutes();
+            if interval > 0 && minutes > interval {
+                report.gaps.push(Gap {
+                    mprn: mprn.to_string(),
+                    read_type,
+                    after: pair[0].read_data_and_end_time,
+                    before: pair[1].read_data_and_end_time,
+                    missing_intervals: minutes / interval - 1,
+                });
+            }
+        }
+
+        if read_type == SmartMeterDataType::ActiveImport {
+            report.short_days.extend(short_days(mprn, &readings));
+        }
+    }
+    report
+}
+
+// 23 hours when the clocks go forward and 25 when they go back
+pub(crate) fn local_day_minutes(date: NaiveDate) -> i64 {
+    let midnight = |date: NaiveDate| {
+        LOCAL_TIMEZONE
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()
+    };
+    match (midnight(date), midnight(date + Duration::days(1))) {
+        (Some(start), Some(end)) => (end - start).num_minutes(),
+        _ => 24 * 60,
+    }
+}
+
+// Every day from the first to the last reading, so days without any reading are listed too
+fn short_days(mprn: &str, readings: &[&SmartMeterData]) -> Vec<ShortDay> {
+    let (Some(first), Some(last)) = (readings.first(), readings.last()) else {
+        return vec![];
+    };
+    let mut per_day: HashMap<NaiveDate, usize> = HashMap::new();
+    for datapoint in readings {
+        *per_day.entry(datapoint.date()).or_default() += 1;
+    }
+    let interval = i64::from(first.interval_minutes);
+    if interval == 0 {
+        return vec![];
+    }
+
+    let mut days = vec![];
+    let mut date = first.date();
+    while date <= last.date() {
+        let count = per_day.get(&date).copied().unwrap_or(0);
+        let expected = (local_day_minutes(date) / interval) as usize;
+        if count < expected {
+            days.push(ShortDay {
+                mprn: mprn.to_string(),
+                date,
+                readings: count,
+                expected,
+            });
+        }
+        date += Duration::days(1);
+    }
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meter_data::reading_at;
+
+    #[test]
+    fn gaps_short_days_and_readings_out_of_order_are_reported() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 8)
+            .unwrap()
+            .and_time(NaiveTime::MIN);
+        let mut data: Vec<SmartMeterData> = (1..=48)
+            .map(|i| {
+                let end = start + Duration::minutes(30 * i);
+                reading_at(&end.format("%d-%m-%Y %H:%M").to_string(), 1.0)
+            })
+            .collect();
+        // 10:00 and 10:30 are missing and 12:00 comes before 11:30
+        data.drain(19..21);
+        data.swap(20, 21);
+        let report = validate_readings(&data);
+        assert!(!report.is_clean());
+
+        let time = |text| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+        assert_eq!(report.gaps.len(), 1);
+        assert_eq!(report.gaps[0].after, time("2024-01-08 09:30"));
+        assert_eq!(report.gaps[0].before, time("2024-01-08 11:00"));
+        assert_eq!(report.gaps[0].missing_intervals, 2);
+        assert_eq!(
+            report.short_days,
+            vec![ShortDay {
+                mprn: "10000000000".to_string(),
+                date: NaiveDate::from_ymd_opt(2024, 1, 8).unwrap(),
+                readings: 46,
+                expected: 48,
+            }]
+        );
+        assert_eq!(report.out_of_order.len(), 1);
+        assert_eq!(report.out_of_order[0].previous, time("2024-01-08 12:00"));
+        assert_eq!(report.out_of_order[0].reading, time("2024-01-08 11:30"));
+
+        data.swap(20, 21);
+        data.retain(|d| d.read_data_and_end_time < time("2024-01-08 09:00"));
+        let report = validate_readings(&data);
+        assert!(report.gaps.is_empty() && report.out_of_order.is_empty());
+    }
+
+    #[test]
+    fn the_days_the_clocks_change_are_an_hour_shorter_or_longer() {
+        let day = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        assert_eq!(local_day_minutes(day(3, 31)), 23 * 60);
+        assert_eq!(local_day_minutes(day(10, 27)), 25 * 60);
+        assert_eq!(local_day_minutes(day(6, 1)), 24 * 60);
+    }
+
+    #[test]
+    fn the_readings_of_another_meter_point_are_caught() {
+        let ours = reading_at("08-01-2024 03:30", 1.0);
+        assert!(check_mprns(std::slice::from_ref(&ours), Some("10000000000")).is_empty());
+        assert_eq!(
+            check_mprns(std::slice::from_ref(&ours), Some("10308375697")),
+            vec![MprnIssue::Unexpected {
+                mprn: "10000000000".to_string(),
+                expected: "10308375697".to_string(),
+            }]
+        );
+
+        let theirs = SmartMeterData {
+            mprn: "1030837569".into(),
+            ..ours.clone()
+        };
+        let issues = check_mprns(&[ours, theirs], None);
+        assert_eq!(
+            issues[0],
+            MprnIssue::Malformed {
+                mprn: "1030837569".to_string()
+            }
+        );
+        assert_eq!(
+            issues[1].to_string(),
+            "the readings are of several meter points: 10000000000 (1 readings), 1030837569 (1 readings)"
+        );
+        assert_eq!(
+            issues[2].to_string(),
+            "meter 00000000 reads for 10000000000 and 1030837569"
+        );
+        assert!(!is_valid_mprn("1030837569x"));
+    }
+}
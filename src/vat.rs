@@ -0,0 +1,100 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::{swap_vat, Currency, EnergyBillEntry, Money, Numeric, PricingNumber};
+
+// VAT on domestic electricity and gas in Ireland, each rate applies from its date until the
+// next one. The reduced rate started in May 2022 and was extended up to the end of 2030
+const IRISH_ENERGY_VAT: &[((i32, u32, u32), Decimal)] =
+    &[((2022, 5, 1), dec!(0.09)), ((2031, 1, 1), dec!(0.135))];
+
+// The rate in force before the first change in the table
+const STANDARD_ENERGY_VAT: Decimal = dec!(0.135);
+
+// The VAT the built in rates and tariff files include unless they say otherwise, the rate in
+// force when suppliers published them
+pub const QUOTED_VAT_RATE: Decimal = dec!(0.09);
+
+pub(crate) fn default_vat_included() -> Decimal {
+    QUOTED_VAT_RATE
+}
+
+pub fn vat_rate_on(date: NaiveDate) -> Decimal {
+    IRISH_ENERGY_VAT
+        .iter()
+        .take_while(|&&((year, month, day), _)| {
+            NaiveDate::from_ymd_opt(year, month, day).is_some_and(|from| from <= date)
+        })
+        .last()
+        .map_or(STANDARD_ENERGY_VAT, |&(_, rate)| rate)
+}
+
+// Swaps the included VAT of a charge for the rate in force on date, a rate before VAT has
+// included as zero. Nothing is charged on credits, export payments carry no VAT. The table is
+// Irish, charges in another currency keep the VAT they were quoted with
+pub fn with_vat_on(
+    entry: EnergyBillEntry,
+    included: Decimal,
+    date: NaiveDate,
+    currency: Currency,
+) -> EnergyBillEntry {
+    if currency != Currency::Eur {
+        return entry;
+    }
+    let rate = vat_rate_on(date);
+    match entry {
+        EnergyBillEntry::Debit(value) if rate != included => {
+            let amount = PricingNumber::from_decimal(value.amount());
+            EnergyBillEntry::Debit(Money::new(swap_vat(amount, included, rate).to_decimal()))
+        }
+        entry => entry,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn charges_use_the_rate_in_force_on_the_day() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let charge = EnergyBillEntry::Debit(Money::new(dec!(1.09)));
+
+        let eur = Currency::Eur;
+
+        assert_eq!(
+            with_vat_on(charge, QUOTED_VAT_RATE, date(2024, 1, 8), eur),
+            charge
+        );
+        assert_eq!(
+            with_vat_on(charge, QUOTED_VAT_RATE, date(2022, 4, 30), eur),
+            EnergyBillEntry::Debit(Money::new(dec!(1.135)))
+        );
+        assert_eq!(
+            with_vat_on(
+                EnergyBillEntry::Debit(Money::new(dec!(1))),
+                Decimal::ZERO,
+                date(2022, 5, 1),
+                eur
+            ),
+            charge
+        );
+    }
+
+    #[test]
+    fn charges_in_pounds_keep_their_quoted_vat() {
+        let date = NaiveDate::from_ymd_opt(2022, 4, 30).unwrap();
+        let charge = EnergyBillEntry::Debit(Money::new(dec!(1.05)));
+        assert_eq!(with_vat_on(charge, dec!(0.05), date, Currency::Gbp), charge);
+        assert_eq!(
+            with_vat_on(
+                EnergyBillEntry::Debit(Money::new(dec!(1))),
+                Decimal::ZERO,
+                date,
+                Currency::Gbp
+            ),
+            EnergyBillEntry::Debit(Money::new(dec!(1)))
+        );
+    }
+}
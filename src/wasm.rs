@@ -0,0 +1,61 @@
+use anyhow::Result;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    builtin_plans, compare_meter_points, parse_readings, ComparisonOptions, PlanFile,
+    PricePlanStrategy,
+};
+
+// The built in plans and the ones of a tariff file given as text, the file picked in the page.
+// Dynamic and conditional plans need their csv from the disk and plugin plans can't run in the page, so
+// they are left out
+fn plans(tariffs: &str) -> Result<Vec<Box<dyn PricePlanStrategy>>> {
+    let mut plans = builtin_plans();
+    if !tariffs.trim().is_empty() {
+        let mut plan_file: PlanFile =
+            toml::from_str(tariffs).map_err(|e| anyhow::anyhow!("invalid tariff file: {e}"))?;
+        plan_file.dynamic_plan.clear();
+        plan_file.conditional_plan.clear();
+        plan_file.plugin_plan.clear();
+        plans.extend(plan_file.take_electricity_plans());
+    }
+    Ok(plans)
+}
+
+fn to_js(e: anyhow::Error) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
+
+// The names of the plans as a json array, for the page to offer as the current plan
+#[wasm_bindgen(js_name = planNames)]
+pub fn plan_names(tariffs: &str) -> Result<String, JsValue> {
+    let names: Vec<String> = plans(tariffs)
+        .map_err(to_js)?
+        .iter()
+        .map(|plan| plan.name())
+        .collect();
+    serde_json::to_string(&names).map_err(|e| to_js(e.into()))
+}
+
+// Compares the plans on the bytes of a readings file read in the browser, the readings never
+// leave the page. Answers with the same json as --format json for every meter point
+#[wasm_bindgen]
+pub fn compare(
+    readings: &[u8],
+    tariffs: &str,
+    current_plan: Option<String>,
+) -> Result<String, JsValue> {
+    let parsed = parse_readings(readings, "meter").map_err(to_js)?;
+    let plans: Vec<Box<dyn PricePlanStrategy>> = plans(tariffs)
+        .map_err(to_js)?
+        .into_iter()
+        .filter(|plan| plan.currency() == parsed.currency)
+        .collect();
+    let options = ComparisonOptions {
+        current_plan,
+        bands: true,
+        ..ComparisonOptions::default()
+    };
+    let reports = compare_meter_points(&plans, parsed.readings, &options).map_err(to_js)?;
+    serde_json::to_string(&reports).map_err(|e| to_js(e.into()))
+}
@@ -0,0 +1,68 @@
+use energy_comparator::{
+    bord_gais_energy_25_weekend_free, detect_intervals, electric_ireland_home_electric_14,
+    read_csv, sse_airtricity_20, stream_comparison, BillingPeriod, EnergyBillEntry, Money,
+    PricePlanStrategy, SmartMeterData,
+};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+// The expected totals were worked out by hand from the fixture, a week of half-hourly readings
+// from Monday 01-01-2024 to Sunday 07-01-2024, and include 7 days of standing charge
+// Monday 01-01-2024 is a bank holiday, so Bord Gais prices it like a sunday
+// 2024 is a leap year so the annual standing charges are spread over 366 days
+// the readings are half hourly kW averages, so each one is half its value in kWh
+// usage and standing charge are rounded to the cent before being added up
+fn expected_totals() -> Vec<(Box<dyn PricePlanStrategy>, Decimal)> {
+    vec![
+        (Box::new(electric_ireland_home_electric_14()), dec!(34.08)),
+        (Box::new(sse_airtricity_20()), dec!(30.08)),
+        (Box::new(bord_gais_energy_25_weekend_free()), dec!(27.65)),
+    ]
+}
+
+#[test]
+fn totals_match_reference_bill() {
+    let mut data: Vec<SmartMeterData> = read_csv(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/HDF_week.csv"
+    ))
+    .unwrap();
+    assert_eq!(data.len(), 672);
+    detect_intervals(&mut data);
+    assert!(data.iter().all(|d| d.interval_minutes == 30));
+    let period = BillingPeriod::from_data(&data).unwrap();
+    assert_eq!(period.number_of_days(), 7);
+
+    for (plan, expected) in expected_totals() {
+        assert_eq!(
+            plan.compute_bill_breakdown(&data, &period).total,
+            EnergyBillEntry::Debit(Money::new(expected)),
+            "{plan:?}"
+        );
+    }
+}
+
+#[test]
+fn streamed_totals_match_reference_bill() {
+    let path = std::path::PathBuf::from(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/HDF_week.csv"
+    ));
+    let (plans, expected): (Vec<_>, Vec<_>) = expected_totals().into_iter().unzip();
+    let reports = stream_comparison(&[path], &plans, None, None, None).unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].period.number_of_days(), 7);
+
+    for (plan, expected) in plans.iter().zip(expected) {
+        let result = reports[0]
+            .plans
+            .iter()
+            .find(|r| r.name == plan.name())
+            .unwrap();
+        assert_eq!(
+            result.breakdown.total,
+            EnergyBillEntry::Debit(Money::new(expected)),
+            "{plan:?}"
+        );
+    }
+}